@@ -0,0 +1,99 @@
+//! Benchmarks of validating, (de)serializing and filtering tasks, the work
+//! done on every request before the database is reached.
+//!
+//! Run with `cargo bench`, and compare against a baseline saved before a
+//! change with `cargo bench -- --save-baseline before` and
+//! `cargo bench -- --baseline before`.
+
+use std::hint::black_box;
+
+use chrono::{DateTime, Utc};
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use dts_developer_challenge::{
+    FilterExpr, TodoTask, TodoTaskUnchecked,
+    loadgen::{GeneratedTask, TaskGenerator},
+};
+
+/// Number of tasks in the listings benchmarked.
+const PAGE_SIZE: usize = 100;
+
+/// Filter expressions of increasing complexity.
+const FILTERS: [(&str, &str); 3] = [
+    ("condition", "status:in_progress"),
+    (
+        "compound",
+        "status:not_started AND due<2026-11-01 AND NOT tag:urgent",
+    ),
+    (
+        "nested",
+        "(title:hearing OR description:\"case 1\") AND (tag:family OR tag:civil OR tag:probate) \
+        AND NOT (status:complete OR status:cancelled) AND completed>=2026-01-01",
+    ),
+];
+
+/// Realistic tasks, the same on every run.
+fn tasks(count: usize) -> Vec<TodoTask> {
+    let now: DateTime<Utc> = "2026-10-16T12:00:00Z".parse().unwrap();
+    TaskGenerator::new(0, 20, now)
+        .take(count)
+        .map(|GeneratedTask { task, .. }| task)
+        .collect()
+}
+
+fn validation(c: &mut Criterion) {
+    let task = &tasks(1)[0];
+    let unchecked = TodoTaskUnchecked::new(
+        task.title().to_owned(),
+        task.description().map(str::to_owned),
+        task.status.clone(),
+        *task.due(),
+        task.tags().to_vec(),
+    );
+    c.bench_function("validate", |b| {
+        b.iter_batched(
+            || unchecked.clone(),
+            |unchecked| TodoTask::try_from(black_box(unchecked)),
+            BatchSize::SmallInput,
+        );
+    });
+}
+
+fn json(c: &mut Criterion) {
+    let tasks = tasks(PAGE_SIZE);
+    let task = serde_json::to_vec(&tasks[0]).unwrap();
+    let page = serde_json::to_vec(&tasks).unwrap();
+
+    let mut group = c.benchmark_group("json");
+    group.bench_function("serialize", |b| {
+        b.iter(|| serde_json::to_vec(black_box(&tasks[0])));
+    });
+    group.bench_function("deserialize", |b| {
+        b.iter(|| serde_json::from_slice::<TodoTask>(black_box(&task)));
+    });
+    group.throughput(Throughput::Elements(PAGE_SIZE as u64));
+    group.bench_function("serialize_page", |b| {
+        b.iter(|| serde_json::to_vec(black_box(&tasks)));
+    });
+    group.bench_function("deserialize_page", |b| {
+        b.iter(|| serde_json::from_slice::<Vec<TodoTask>>(black_box(&page)));
+    });
+    group.finish();
+}
+
+fn filters(c: &mut Criterion) {
+    let mut group = c.benchmark_group("filter");
+    for (name, filter) in FILTERS {
+        // benchmark parsing valid expressions, not failing early
+        filter.parse::<FilterExpr>().unwrap();
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(filter).parse::<FilterExpr>());
+        });
+    }
+    group.bench_function("tags", |b| {
+        b.iter(|| FilterExpr::parse_tags(black_box("(home OR work) AND NOT shopping")));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, validation, json, filters);
+criterion_main!(benches);
@@ -0,0 +1,86 @@
+/*
+ * C interface for validating and (de)serializing tasks, the same way as the
+ * task server does. Due dates are checked against the server's default due
+ * window, around the time they are checked.
+ *
+ * Link against libdts_tasks, built from backend/bindings/c. Every object
+ * returned must be freed with the matching dts_*_free function, and strings
+ * are UTF-8.
+ */
+
+#ifndef DTS_TASKS_H
+#define DTS_TASKS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Valid task. */
+typedef struct DtsTask DtsTask;
+
+/* Problem with a task, and the field it is about. */
+typedef struct DtsError DtsError;
+
+/* Bytes owned by the library. */
+typedef struct DtsBuffer {
+    uint8_t *data;
+    size_t len;
+} DtsBuffer;
+
+/*
+ * Validate a task. `description` may be NULL, `status` is the name of a
+ * status, `due` is in seconds since the Unix epoch, and `tags` points to
+ * `tag_count` strings.
+ *
+ * Returns NULL and sets `*error`, if `error` isn't NULL, if the task is
+ * invalid.
+ */
+DtsTask *dts_task_new(const char *title, const char *description, const char *status,
+                      int64_t due, const char *const *tags, size_t tag_count,
+                      DtsError **error);
+
+/*
+ * Deserialize and validate a task from the `len` bytes of JSON at `json`.
+ *
+ * Returns NULL and sets `*error`, if `error` isn't NULL, if the task is
+ * invalid.
+ */
+DtsTask *dts_task_from_json(const uint8_t *json, size_t len, DtsError **error);
+
+/*
+ * Check that the `len` bytes of JSON at `json` are a valid task.
+ *
+ * Returns 0 if so, or -1 and sets `*error`, if `error` isn't NULL, if not.
+ */
+int32_t dts_task_validate(const uint8_t *json, size_t len, DtsError **error);
+
+/* Serialize `task` as JSON, as the task server accepts it. */
+DtsBuffer dts_task_to_json(const DtsTask *task);
+
+/* Free a task. */
+void dts_task_free(DtsTask *task);
+
+/* Free a buffer. */
+void dts_buffer_free(DtsBuffer buffer);
+
+/*
+ * Get the path to the field at fault, such as "title" or "tags[0]", or NULL
+ * if the problem is with the task as a whole. Valid until the error is
+ * freed.
+ */
+const char *dts_error_field(const DtsError *error);
+
+/* Get a description of the problem. Valid until the error is freed. */
+const char *dts_error_message(const DtsError *error);
+
+/* Free an error. */
+void dts_error_free(DtsError *error);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* DTS_TASKS_H */
@@ -0,0 +1,392 @@
+//! C interface for validating and (de)serializing tasks, the same way as the
+//! task server does, declared in `include/dts_tasks.h`.
+//!
+//! Due dates are checked against the server's default due window, around
+//! the time they are checked.
+//!
+//! Objects are handed to callers as pointers to boxes, which they must give
+//! back to the matching `dts_*_free` function. Functions which can fail
+//! return null or -1, and describe the problem in an error object if given
+//! somewhere to put it.
+
+#![deny(clippy::pedantic)]
+#![deny(missing_docs)]
+
+use std::{
+    ffi::{CStr, CString, c_char},
+    ptr, slice,
+};
+
+use chrono::{DateTime, Utc};
+use dts_developer_challenge::{DueWindow, InvalidTask, TodoStatus, TodoTask, TodoTaskUnchecked};
+
+/// Valid task.
+pub struct DtsTask(TodoTask);
+
+/// Problem with a task, and the field it is about.
+pub struct DtsError {
+    field: Option<CString>,
+    message: CString,
+}
+
+/// Bytes owned by the library.
+#[repr(C)]
+pub struct DtsBuffer {
+    /// First byte.
+    pub data: *mut u8,
+    /// Number of bytes.
+    pub len: usize,
+}
+
+impl From<InvalidTask> for DtsError {
+    fn from(error: InvalidTask) -> Self {
+        // messages and paths come from the library or serde, so have no nul
+        // bytes, but drop any rather than fail to report the error
+        let c_string = |s: String| CString::new(s.replace('\0', "")).unwrap_or_default();
+        Self {
+            field: error.field.map(c_string),
+            message: c_string(error.message),
+        }
+    }
+}
+
+/// Put `result`'s task in a box for the caller, or its error in `error`.
+///
+/// # Safety
+///
+/// `error` must be null or valid for writes.
+unsafe fn hand_over(
+    result: Result<TodoTask, InvalidTask>,
+    error: *mut *mut DtsError,
+) -> *mut DtsTask {
+    match result {
+        Ok(task) => Box::into_raw(Box::new(DtsTask(task))),
+        Err(e) => {
+            if !error.is_null() {
+                // SAFETY: the caller promises `error` is valid for writes
+                unsafe { *error = Box::into_raw(Box::new(DtsError::from(e))) };
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Read `len` bytes of JSON at `json` as a task.
+///
+/// # Safety
+///
+/// `json` must be valid for reads of `len` bytes, or null if `len` is 0.
+unsafe fn parse(json: *const u8, len: usize) -> Result<TodoTask, InvalidTask> {
+    let bytes = if len == 0 {
+        &[]
+    } else {
+        // SAFETY: the caller promises `json` is valid for `len` bytes
+        unsafe { slice::from_raw_parts(json, len) }
+    };
+    let json = std::str::from_utf8(bytes).map_err(|_| InvalidTask {
+        field: None,
+        message: "task must be UTF-8".to_owned(),
+    })?;
+    TodoTask::from_json(json, DueWindow::default(), Utc::now())
+}
+
+/// Read the string at `s`.
+///
+/// # Safety
+///
+/// `s` must be a valid pointer to a nul-terminated string.
+unsafe fn string(s: *const c_char, field: &str) -> Result<String, InvalidTask> {
+    // SAFETY: the caller promises `s` is a nul-terminated string
+    let s = unsafe { CStr::from_ptr(s) };
+    s.to_str().map(str::to_owned).map_err(|_| InvalidTask {
+        field: Some(field.to_owned()),
+        message: format!("{field} must be UTF-8"),
+    })
+}
+
+/// Validate a task, returning null and setting `*error` if it is invalid.
+///
+/// # Safety
+///
+/// `title` and `status` must be nul-terminated strings, and `description`
+/// one or null. `tags` must point to `tag_count` of them, or be null if
+/// `tag_count` is 0. `error` must be null or valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dts_task_new(
+    title: *const c_char,
+    description: *const c_char,
+    status: *const c_char,
+    due: i64,
+    tags: *const *const c_char,
+    tag_count: usize,
+    error: *mut *mut DtsError,
+) -> *mut DtsTask {
+    // SAFETY: the caller promises the strings are valid
+    let result = (|| unsafe {
+        let description = if description.is_null() {
+            None
+        } else {
+            Some(string(description, "description")?)
+        };
+        let tags = if tag_count == 0 {
+            &[]
+        } else {
+            slice::from_raw_parts(tags, tag_count)
+        };
+        let tags = tags
+            .iter()
+            .map(|tag| string(*tag, "tags"))
+            .collect::<Result<_, _>>()?;
+        let due = DateTime::from_timestamp(due, 0).ok_or_else(|| InvalidTask {
+            field: Some("due".to_owned()),
+            message: "due date is out of range".to_owned(),
+        })?;
+        let task = TodoTaskUnchecked::new(
+            string(title, "title")?,
+            description,
+            TodoStatus::named(&string(status, "status")?),
+            due,
+            tags,
+        );
+        task.check(DueWindow::default(), Utc::now())
+            .map_err(InvalidTask::from)
+    })();
+    // SAFETY: the caller promises `error` is valid
+    unsafe { hand_over(result, error) }
+}
+
+/// Deserialize and validate a task from the `len` bytes of JSON at `json`,
+/// returning null and setting `*error` if it is invalid.
+///
+/// # Safety
+///
+/// `json` must be valid for reads of `len` bytes, or null if `len` is 0.
+/// `error` must be null or valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dts_task_from_json(
+    json: *const u8,
+    len: usize,
+    error: *mut *mut DtsError,
+) -> *mut DtsTask {
+    // SAFETY: the caller promises the pointers are valid
+    unsafe { hand_over(parse(json, len), error) }
+}
+
+/// Check that the `len` bytes of JSON at `json` are a valid task, returning
+/// 0 if so, or -1 and setting `*error` if not.
+///
+/// # Safety
+///
+/// As for [`dts_task_from_json`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dts_task_validate(
+    json: *const u8,
+    len: usize,
+    error: *mut *mut DtsError,
+) -> i32 {
+    // SAFETY: the caller promises the pointers are valid
+    let task = unsafe { dts_task_from_json(json, len, error) };
+    if task.is_null() {
+        return -1;
+    }
+    // SAFETY: the task was just handed over
+    unsafe { dts_task_free(task) };
+    0
+}
+
+/// Serialize `task` as JSON, as the task server accepts it.
+///
+/// # Safety
+///
+/// `task` must have been returned by this library, and not yet freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dts_task_to_json(task: *const DtsTask) -> DtsBuffer {
+    // SAFETY: the caller promises `task` is valid
+    let task = unsafe { &(*task).0 };
+    // tasks always serialize, having only strings as map keys
+    let json = serde_json::to_vec(task).unwrap_or_default();
+    let len = json.len();
+    DtsBuffer {
+        data: Box::into_raw(json.into_boxed_slice()).cast(),
+        len,
+    }
+}
+
+/// Free a task.
+///
+/// # Safety
+///
+/// `task` must be null, or have been returned by this library and not yet
+/// freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dts_task_free(task: *mut DtsTask) {
+    if !task.is_null() {
+        // SAFETY: the caller promises `task` is ours to free
+        drop(unsafe { Box::from_raw(task) });
+    }
+}
+
+/// Free a buffer.
+///
+/// # Safety
+///
+/// `buffer` must have been returned by this library and not yet freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dts_buffer_free(buffer: DtsBuffer) {
+    if !buffer.data.is_null() {
+        // SAFETY: the caller promises the buffer is ours to free, as a boxed
+        // slice of its length
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)) });
+    }
+}
+
+/// Get the path to the field at fault, or null if the problem is with the
+/// task as a whole.
+///
+/// # Safety
+///
+/// `error` must have been returned by this library, and not yet freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dts_error_field(error: *const DtsError) -> *const c_char {
+    // SAFETY: the caller promises `error` is valid
+    unsafe { &*error }
+        .field
+        .as_ref()
+        .map_or(ptr::null(), |field| field.as_ptr())
+}
+
+/// Get a description of the problem.
+///
+/// # Safety
+///
+/// `error` must have been returned by this library, and not yet freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dts_error_message(error: *const DtsError) -> *const c_char {
+    // SAFETY: the caller promises `error` is valid
+    unsafe { &*error }.message.as_ptr()
+}
+
+/// Free an error.
+///
+/// # Safety
+///
+/// `error` must be null, or have been returned by this library and not yet
+/// freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dts_error_free(error: *mut DtsError) {
+    if !error.is_null() {
+        // SAFETY: the caller promises `error` is ours to free
+        drop(unsafe { Box::from_raw(error) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use super::*;
+
+    /// Take the field and message of `error`, freeing it.
+    fn take_error(error: *mut DtsError) -> (Option<String>, String) {
+        assert!(!error.is_null());
+        unsafe {
+            let field = dts_error_field(error);
+            let field =
+                (!field.is_null()).then(|| CStr::from_ptr(field).to_string_lossy().into_owned());
+            let message = CStr::from_ptr(dts_error_message(error))
+                .to_string_lossy()
+                .into_owned();
+            dts_error_free(error);
+            (field, message)
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let due = (Utc::now() + TimeDelta::days(7)).timestamp();
+        let tags = [c"hearing".as_ptr()];
+        let mut error = ptr::null_mut();
+        unsafe {
+            let task = dts_task_new(
+                c"Review bundle".as_ptr(),
+                ptr::null(),
+                c"in_progress".as_ptr(),
+                due,
+                tags.as_ptr(),
+                tags.len(),
+                &raw mut error,
+            );
+            assert!(!task.is_null());
+            assert!(error.is_null());
+
+            let json = dts_task_to_json(task);
+            dts_task_free(task);
+            assert_eq!(dts_task_validate(json.data, json.len, &raw mut error), 0);
+            let copy = dts_task_from_json(json.data, json.len, ptr::null_mut());
+            dts_buffer_free(json);
+            assert_eq!((*copy).0.status, TodoStatus::InProgress);
+            assert_eq!((*copy).0.tags(), ["hearing"]);
+            dts_task_free(copy);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let mut error = ptr::null_mut();
+        unsafe {
+            let task = dts_task_new(
+                c"".as_ptr(),
+                ptr::null(),
+                c"NotStarted".as_ptr(),
+                Utc::now().timestamp(),
+                ptr::null(),
+                0,
+                &raw mut error,
+            );
+            assert!(task.is_null());
+        }
+        assert_eq!(
+            take_error(error),
+            (Some("title".to_owned()), "title cannot be empty".to_owned())
+        );
+
+        let json = br#"{"title": "t", "status": "NotStarted", "due": 7}"#;
+        unsafe {
+            assert_eq!(
+                dts_task_validate(json.as_ptr(), json.len(), &raw mut error),
+                -1
+            );
+        }
+        let (field, _) = take_error(error);
+        assert_eq!(field.as_deref(), Some("due"));
+
+        // as the server does, with its default due window
+        let distant = (Utc::now() + TimeDelta::days(365 * 60)).timestamp();
+        unsafe {
+            let task = dts_task_new(
+                c"File appeal".as_ptr(),
+                ptr::null(),
+                c"NotStarted".as_ptr(),
+                distant,
+                ptr::null(),
+                0,
+                &raw mut error,
+            );
+            assert!(task.is_null());
+        }
+        assert_eq!(take_error(error).0.as_deref(), Some("due"));
+        let json = br#"{"title": "t", "status": "NotStarted", "due": "2125-06-30T12:00:00Z"}"#;
+        unsafe {
+            assert_eq!(
+                dts_task_validate(json.as_ptr(), json.len(), &raw mut error),
+                -1
+            );
+        }
+        assert_eq!(take_error(error).0.as_deref(), Some("due"));
+
+        unsafe {
+            assert_eq!(dts_task_validate(ptr::null(), 0, &raw mut error), -1);
+        }
+        assert_eq!(take_error(error).0, None);
+    }
+}
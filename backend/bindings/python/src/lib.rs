@@ -0,0 +1,202 @@
+//! Python bindings for validating and (de)serializing tasks, the same way
+//! as the task server does.
+//!
+//! Built as the `dts_tasks` extension module with `maturin build`. Tasks are
+//! validated as they are constructed, and invalid ones raise
+//! `InvalidTaskError`, whose `field` attribute names the field at fault,
+//! such as `title` or `tags[0]`, or is `None` if the task as a whole is at
+//! fault. Due dates are checked against the server's default due window,
+//! around the time they are checked.
+
+#![deny(clippy::pedantic)]
+#![deny(missing_docs)]
+
+use chrono::{DateTime, Utc};
+use dts_developer_challenge::{DueWindow, InvalidTask, TodoStatus, TodoTask, TodoTaskUnchecked};
+use pyo3::{create_exception, exceptions::PyValueError, prelude::*};
+
+create_exception!(
+    dts_tasks,
+    InvalidTaskError,
+    PyValueError,
+    "Task which isn't valid."
+);
+
+/// Convert a problem with a task into a Python exception.
+fn invalid(py: Python<'_>, error: InvalidTask) -> PyErr {
+    let exception = InvalidTaskError::new_err(error.message);
+    match exception.value(py).setattr("field", error.field) {
+        Ok(()) => exception,
+        Err(e) => e,
+    }
+}
+
+/// Valid task.
+#[pyclass(name = "Task", module = "dts_tasks", frozen)]
+struct Task(TodoTask);
+
+#[pymethods]
+impl Task {
+    /// Validate a task with a status named `status`, which is custom unless
+    /// it is the name of a built-in status.
+    #[new]
+    #[pyo3(signature = (title, due, status = "NotStarted", description = None, tags = Vec::new()))]
+    fn new(
+        py: Python<'_>,
+        title: String,
+        due: DateTime<Utc>,
+        status: &str,
+        description: Option<String>,
+        tags: Vec<String>,
+    ) -> PyResult<Self> {
+        let task = TodoTaskUnchecked::new(title, description, TodoStatus::named(status), due, tags);
+        task.check(DueWindow::default(), Utc::now())
+            .map(Self)
+            .map_err(|e| invalid(py, e.into()))
+    }
+
+    /// Deserialize and validate a task from JSON.
+    #[staticmethod]
+    fn from_json(py: Python<'_>, json: &str) -> PyResult<Self> {
+        TodoTask::from_json(json, DueWindow::default(), Utc::now())
+            .map(Self)
+            .map_err(|e| invalid(py, e))
+    }
+
+    /// Serialize the task as JSON, as the task server accepts it.
+    fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(&self.0).map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Title of the task.
+    #[getter]
+    fn title(&self) -> &str {
+        self.0.title()
+    }
+
+    /// Description of the task, if any.
+    #[getter]
+    fn description(&self) -> Option<&str> {
+        self.0.description()
+    }
+
+    /// Name of the status of the task.
+    #[getter]
+    fn status(&self) -> String {
+        match serde_json::to_value(&self.0.status) {
+            Ok(serde_json::Value::String(name)) => name,
+            _ => format!("{:?}", self.0.status),
+        }
+    }
+
+    /// Why work on the task stopped, if a reason was given.
+    #[getter]
+    fn status_reason(&self) -> Option<&str> {
+        self.0.status_reason()
+    }
+
+    /// Date & time at which the task is due.
+    #[getter]
+    fn due(&self) -> DateTime<Utc> {
+        *self.0.due()
+    }
+
+    /// Tags of the task.
+    #[getter]
+    fn tags(&self) -> Vec<String> {
+        self.0.tags().to_vec()
+    }
+
+    /// Percentage of the task which is done, if set.
+    #[getter]
+    fn progress(&self) -> Option<u8> {
+        self.0.progress()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Task(title={:?}, status={:?})",
+            self.0.title(),
+            self.status()
+        )
+    }
+}
+
+/// Check that `json` is a valid task, raising `InvalidTaskError` if not.
+#[pyfunction]
+fn validate(py: Python<'_>, json: &str) -> PyResult<()> {
+    TodoTask::from_json(json, DueWindow::default(), Utc::now())
+        .map(|_| ())
+        .map_err(|e| invalid(py, e))
+}
+
+/// Validation and (de)serialization of tasks.
+#[pymodule]
+fn dts_tasks(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Task>()?;
+    module.add_function(wrap_pyfunction!(validate, module)?)?;
+    module.add(
+        "InvalidTaskError",
+        module.py().get_type::<InvalidTaskError>(),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pyo3::{py_run, wrap_pymodule};
+
+    use super::*;
+
+    #[test]
+    fn module() {
+        Python::attach(|py| {
+            let dts_tasks = wrap_pymodule!(dts_tasks)(py);
+            py_run!(
+                py,
+                dts_tasks,
+                r#"
+import json
+from datetime import datetime, timedelta, timezone
+
+due = datetime.now(timezone.utc) + timedelta(days=7)
+task = dts_tasks.Task("Review bundle", due, status="in_progress", tags=["hearing"])
+assert task.status == "InProgress"
+assert task.due == due
+
+copy = dts_tasks.Task.from_json(task.to_json())
+assert (copy.title, copy.tags) == ("Review bundle", ["hearing"])
+
+body = json.loads(task.to_json())
+body["tags"] = ["two words"]
+try:
+    dts_tasks.validate(json.dumps(body))
+    assert False, "invalid tags accepted"
+except dts_tasks.InvalidTaskError as e:
+    assert e.field == "tags"
+
+try:
+    dts_tasks.Task("", due)
+    assert False, "empty title accepted"
+except ValueError as e:
+    assert e.field == "title"
+
+# as the server does, with its default due window
+distant = datetime(2125, 6, 30, 12, tzinfo=timezone.utc)
+body["tags"] = ["hearing"]
+body["due"] = distant.isoformat()
+try:
+    dts_tasks.Task("File appeal", distant)
+    assert False, "distant due date accepted"
+except dts_tasks.InvalidTaskError as e:
+    assert e.field == "due"
+try:
+    dts_tasks.validate(json.dumps(body))
+    assert False, "distant due date accepted"
+except dts_tasks.InvalidTaskError as e:
+    assert e.field == "due"
+"#
+            );
+        });
+    }
+}
@@ -0,0 +1,5 @@
+ALTER TABLE tasks
+ADD COLUMN tags text [] NOT NULL DEFAULT '{}';
+
+-- GIN index to serve array containment (`@>`) queries on tags
+CREATE INDEX tasks_tags_idx ON tasks USING gin (tags);
@@ -0,0 +1,4 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- trigram index to serve fuzzy title searches
+CREATE INDEX tasks_title_trgm_idx ON tasks USING gin (title gin_trgm_ops);
@@ -0,0 +1,13 @@
+CREATE TYPE task_link_kind AS ENUM ('relates_to', 'duplicates', 'supersedes');
+
+CREATE TABLE task_links (
+    source uuid NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    target uuid NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    kind task_link_kind NOT NULL,
+    PRIMARY KEY (source, target, kind),
+    -- a task cannot be related to itself
+    CHECK (source <> target)
+);
+
+-- links are looked up from either end
+CREATE INDEX task_links_target_idx ON task_links (target);
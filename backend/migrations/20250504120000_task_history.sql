@@ -0,0 +1,57 @@
+-- every version of every task, recorded by trigger so no write path can skip it
+CREATE TABLE task_history (
+    task_id uuid NOT NULL,
+    version integer NOT NULL,
+    recorded_at timestamp with time zone NOT NULL DEFAULT now(),
+    -- what caused this version to be recorded, e.g. "insert" or "update"
+    action text NOT NULL,
+    -- snapshot of the task's fields as of this version
+    title varchar(64) NOT NULL,
+    description text,
+    status task_status NOT NULL,
+    due timestamp with time zone NOT NULL,
+    tags text [] NOT NULL,
+    PRIMARY KEY (task_id, version)
+);
+
+CREATE FUNCTION record_task_history() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO task_history
+        (task_id, version, action, title, description, status, due, tags)
+    SELECT
+        NEW.id,
+        coalesce(max(version), 0) + 1,
+        lower(TG_OP),
+        NEW.title,
+        NEW.description,
+        NEW.status,
+        NEW.due,
+        NEW.tags
+    FROM task_history
+    WHERE task_id = NEW.id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER tasks_history_insert
+AFTER INSERT ON tasks
+FOR EACH ROW EXECUTE FUNCTION record_task_history();
+
+CREATE TRIGGER tasks_history_update
+AFTER UPDATE ON tasks
+FOR EACH ROW WHEN (old.* IS DISTINCT FROM new.*)
+EXECUTE FUNCTION record_task_history();
+
+-- tasks created before history was recorded start from their current state
+INSERT INTO task_history
+(task_id, version, action, title, description, status, due, tags)
+SELECT
+    id,
+    1,
+    'insert',
+    title,
+    description,
+    status,
+    due,
+    tags
+FROM tasks;
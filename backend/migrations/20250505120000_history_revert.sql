@@ -0,0 +1,28 @@
+-- version whose state was restored, for versions recorded by a revert
+ALTER TABLE task_history
+ADD COLUMN reverted_to integer;
+
+-- writers may describe their change with the transaction-local settings
+-- `app.history_action` and `app.history_reverted_to`
+CREATE OR REPLACE FUNCTION record_task_history() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO task_history
+        (task_id, version, action, reverted_to, title, description, status, due, tags)
+    SELECT
+        NEW.id,
+        coalesce(max(version), 0) + 1,
+        coalesce(
+            nullif(current_setting('app.history_action', true), ''),
+            lower(TG_OP)
+        ),
+        nullif(current_setting('app.history_reverted_to', true), '')::integer,
+        NEW.title,
+        NEW.description,
+        NEW.status,
+        NEW.due,
+        NEW.tags
+    FROM task_history
+    WHERE task_id = NEW.id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,33 @@
+-- append-only stream of changes to each task, for event-sourced storage
+CREATE TABLE task_events (
+    task_id uuid NOT NULL,
+    sequence integer NOT NULL,
+    recorded_at timestamp with time zone NOT NULL DEFAULT now(),
+    payload jsonb NOT NULL,
+    -- sha256 of the previous event's hash followed by this event's payload,
+    -- chaining each task's events so that tampering with any is evident
+    hash bytea NOT NULL,
+    PRIMARY KEY (task_id, sequence)
+);
+
+CREATE FUNCTION reject_task_event_change() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'task events are append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER task_events_append_only
+BEFORE UPDATE OR DELETE ON task_events
+FOR EACH ROW EXECUTE FUNCTION reject_task_event_change();
+
+CREATE TRIGGER task_events_no_truncate
+BEFORE TRUNCATE ON task_events
+FOR EACH STATEMENT EXECUTE FUNCTION reject_task_event_change();
+
+-- state of a task as of an event, so reads needn't replay the whole stream
+CREATE TABLE task_snapshots (
+    task_id uuid NOT NULL,
+    sequence integer NOT NULL,
+    state jsonb NOT NULL,
+    PRIMARY KEY (task_id, sequence)
+);
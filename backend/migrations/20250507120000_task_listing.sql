@@ -0,0 +1,84 @@
+-- denormalised read model of every task for listings and reporting, so that
+-- heavy reads stay off the `tasks` table which writers contend for
+CREATE TABLE task_listing (
+    id uuid PRIMARY KEY,
+    title varchar(64) NOT NULL,
+    description text,
+    status task_status NOT NULL,
+    due timestamp with time zone NOT NULL,
+    tags text [] NOT NULL,
+    -- latest version of the task in `task_history`
+    version integer NOT NULL,
+    created_at timestamp with time zone NOT NULL,
+    updated_at timestamp with time zone NOT NULL,
+    -- when the task was last marked complete, if it still is
+    completed_at timestamp with time zone
+);
+
+CREATE INDEX task_listing_due_idx ON task_listing (due);
+CREATE INDEX task_listing_status_idx ON task_listing (status);
+CREATE INDEX task_listing_tags_idx ON task_listing USING gin (tags);
+
+-- refreshed from the versions recorded in `task_history`, so every write path
+-- (table or event storage) keeps it up to date
+CREATE FUNCTION refresh_task_listing() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO task_listing AS l
+        (id, title, description, status, due, tags, version,
+            created_at, updated_at, completed_at)
+    VALUES (
+        NEW.task_id,
+        NEW.title,
+        NEW.description,
+        NEW.status,
+        NEW.due,
+        NEW.tags,
+        NEW.version,
+        NEW.recorded_at,
+        NEW.recorded_at,
+        CASE WHEN NEW.status = 'complete' THEN NEW.recorded_at END
+    )
+    ON CONFLICT (id) DO UPDATE SET
+        title = excluded.title,
+        description = excluded.description,
+        status = excluded.status,
+        due = excluded.due,
+        tags = excluded.tags,
+        version = excluded.version,
+        updated_at = excluded.updated_at,
+        completed_at = CASE
+            WHEN excluded.status <> 'complete' THEN NULL
+            WHEN l.status = 'complete' THEN l.completed_at
+            ELSE excluded.updated_at
+        END
+    WHERE l.version < excluded.version;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER task_history_listing
+AFTER INSERT ON task_history
+FOR EACH ROW EXECUTE FUNCTION refresh_task_listing();
+
+-- build the listing from the history recorded so far
+INSERT INTO task_listing
+(id, title, description, status, due, tags, version,
+    created_at, updated_at, completed_at)
+SELECT
+    latest.task_id,
+    latest.title,
+    latest.description,
+    latest.status,
+    latest.due,
+    latest.tags,
+    latest.version,
+    first.recorded_at,
+    latest.recorded_at,
+    CASE WHEN latest.status = 'complete' THEN latest.recorded_at END
+FROM (
+    SELECT DISTINCT ON (task_id) *
+    FROM task_history
+    ORDER BY task_id, version DESC
+) AS latest
+INNER JOIN task_history AS first
+    ON latest.task_id = first.task_id AND first.version = 1;
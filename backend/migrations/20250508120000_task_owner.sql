@@ -0,0 +1,7 @@
+-- identity of whoever created the task, null for anonymous clients
+ALTER TABLE tasks
+ADD COLUMN owner text;
+
+-- serve counts of each owner's open tasks, for quotas
+CREATE INDEX tasks_open_owner_idx ON tasks (owner)
+WHERE status NOT IN ('complete', 'cancelled');
@@ -0,0 +1,55 @@
+-- role which the server assumes, in row-level security mode, for the rest of
+-- each transaction; unlike the tables' owner (or a superuser) it is subject to
+-- the policies below
+DO $$
+BEGIN
+    CREATE ROLE tasks_rls NOLOGIN;
+EXCEPTION WHEN duplicate_object THEN NULL;
+END;
+$$;
+
+DO $$
+BEGIN
+    EXECUTE format('GRANT tasks_rls TO %I', current_user);
+END;
+$$;
+
+GRANT SELECT, INSERT, UPDATE, DELETE ON ALL TABLES IN SCHEMA public TO tasks_rls;
+REVOKE ALL ON _sqlx_migrations FROM tasks_rls;
+ALTER DEFAULT PRIVILEGES IN SCHEMA public
+GRANT SELECT, INSERT, UPDATE, DELETE ON TABLES TO tasks_rls;
+
+-- owner acted for by the current transaction, set with
+-- `SET LOCAL app.current_user`; empty or unset for the anonymous owner
+CREATE FUNCTION app_current_user() RETURNS text AS $$
+    SELECT nullif(current_setting('app.current_user', true), '')
+$$ LANGUAGE sql STABLE;
+
+ALTER TABLE tasks ENABLE ROW LEVEL SECURITY;
+CREATE POLICY tasks_owner ON tasks TO tasks_rls
+USING (owner IS NOT DISTINCT FROM app_current_user());
+
+-- rows describing tasks are visible along with the task itself, since the
+-- subqueries on `tasks` are subject to its policy
+ALTER TABLE task_links ENABLE ROW LEVEL SECURITY;
+CREATE POLICY task_links_owner ON task_links TO tasks_rls
+USING (
+    EXISTS (SELECT 1 FROM tasks WHERE id = source)
+    AND EXISTS (SELECT 1 FROM tasks WHERE id = target)
+);
+
+ALTER TABLE task_history ENABLE ROW LEVEL SECURITY;
+CREATE POLICY task_history_owner ON task_history TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE id = task_id));
+
+ALTER TABLE task_listing ENABLE ROW LEVEL SECURITY;
+CREATE POLICY task_listing_owner ON task_listing TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE tasks.id = task_listing.id));
+
+ALTER TABLE task_events ENABLE ROW LEVEL SECURITY;
+CREATE POLICY task_events_owner ON task_events TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE id = task_id));
+
+ALTER TABLE task_snapshots ENABLE ROW LEVEL SECURITY;
+CREATE POLICY task_snapshots_owner ON task_snapshots TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE id = task_id));
@@ -0,0 +1,10 @@
+-- events may be deleted, but not changed, by erasure of their owner's data
+-- within a transaction which sets `app.erasure`
+CREATE OR REPLACE FUNCTION reject_task_event_change() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' AND current_setting('app.erasure', true) = 'on' THEN
+        RETURN OLD;
+    END IF;
+    RAISE EXCEPTION 'task events are append-only';
+END;
+$$ LANGUAGE plpgsql;
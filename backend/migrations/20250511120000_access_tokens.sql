@@ -0,0 +1,18 @@
+CREATE TYPE token_scope AS ENUM ('read', 'write');
+
+-- personal access tokens, which authenticate requests as their owner
+CREATE TABLE access_tokens (
+    id uuid PRIMARY KEY,
+    owner text NOT NULL,
+    -- description of what the token is for, chosen by its owner
+    name text NOT NULL,
+    -- sha256 of the token, which is only shown to its owner on creation
+    token_hash bytea NOT NULL UNIQUE,
+    scopes token_scope [] NOT NULL,
+    created_at timestamp with time zone NOT NULL DEFAULT now(),
+    expires_at timestamp with time zone,
+    last_used_at timestamp with time zone,
+    revoked_at timestamp with time zone
+);
+
+CREATE INDEX access_tokens_owner_idx ON access_tokens (owner);
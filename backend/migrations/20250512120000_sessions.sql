@@ -0,0 +1,10 @@
+-- browser sessions, started by logging in with the OpenID Connect provider
+CREATE TABLE sessions (
+    -- sha256 of the session cookie's value
+    id_hash bytea PRIMARY KEY,
+    owner text NOT NULL,
+    created_at timestamp with time zone NOT NULL DEFAULT now(),
+    expires_at timestamp with time zone NOT NULL
+);
+
+CREATE INDEX sessions_expires_at_idx ON sessions (expires_at);
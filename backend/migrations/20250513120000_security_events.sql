@@ -0,0 +1,22 @@
+CREATE TYPE security_event_kind AS ENUM (
+    'login_succeeded',
+    'login_failed',
+    'authentication_failed',
+    'token_created',
+    'token_revoked',
+    'permission_denied'
+);
+
+-- audit log of authentication and authorisation decisions
+CREATE TABLE security_events (
+    id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    kind security_event_kind NOT NULL,
+    -- owner concerned, if known
+    owner text,
+    -- human-readable description of what happened
+    detail text NOT NULL,
+    occurred_at timestamp with time zone NOT NULL DEFAULT now()
+);
+
+CREATE INDEX security_events_occurred_at_idx ON security_events (occurred_at);
+CREATE INDEX security_events_owner_idx ON security_events (owner, occurred_at);
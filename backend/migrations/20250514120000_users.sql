@@ -0,0 +1,15 @@
+CREATE TYPE user_role AS ENUM ('admin', 'auditor');
+
+-- registered principals, identified by the same name as the owner of their
+-- tasks
+CREATE TABLE users (
+    id text PRIMARY KEY,
+    display_name text NOT NULL,
+    email text,
+    roles user_role [] NOT NULL DEFAULT '{}',
+    created_at timestamp with time zone NOT NULL DEFAULT now(),
+    -- deactivated users can no longer authenticate
+    deactivated_at timestamp with time zone
+);
+
+ALTER TYPE security_event_kind ADD VALUE 'user_changed';
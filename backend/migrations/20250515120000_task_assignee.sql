@@ -0,0 +1,5 @@
+-- who is responsible for working on the task; unassigned if NULL
+ALTER TABLE tasks ADD COLUMN assignee text;
+
+CREATE INDEX tasks_open_assignee_idx ON tasks (assignee, due)
+WHERE status NOT IN ('complete', 'cancelled');
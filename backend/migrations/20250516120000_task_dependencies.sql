@@ -0,0 +1,2 @@
+-- the source task can't be finished before the target task
+ALTER TYPE task_link_kind ADD VALUE 'depends_on';
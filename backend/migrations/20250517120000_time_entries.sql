@@ -0,0 +1,20 @@
+-- periods of time spent working on tasks, tracked with timers
+CREATE TABLE time_entries (
+    id uuid PRIMARY KEY,
+    task_id uuid NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    -- who spent the time
+    owner text NOT NULL,
+    started_at timestamp with time zone NOT NULL DEFAULT now(),
+    -- NULL while the timer is running
+    stopped_at timestamp with time zone,
+    CHECK (stopped_at >= started_at)
+);
+
+-- each owner may only have one timer running on a task at a time
+CREATE UNIQUE INDEX time_entries_running_idx ON time_entries (task_id, owner)
+WHERE stopped_at IS NULL;
+CREATE INDEX time_entries_owner_idx ON time_entries (owner, started_at);
+
+ALTER TABLE time_entries ENABLE ROW LEVEL SECURITY;
+CREATE POLICY time_entries_owner ON time_entries TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE id = task_id));
@@ -0,0 +1,11 @@
+-- tasks which each owner keeps at the top of their listings
+CREATE TABLE task_pins (
+    task_id uuid NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    owner text NOT NULL,
+    pinned_at timestamp with time zone NOT NULL DEFAULT now(),
+    PRIMARY KEY (owner, task_id)
+);
+
+ALTER TABLE task_pins ENABLE ROW LEVEL SECURITY;
+CREATE POLICY task_pins_owner ON task_pins TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE id = task_id));
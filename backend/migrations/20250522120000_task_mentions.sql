@@ -0,0 +1,20 @@
+-- users mentioned as `@username` in the descriptions of tasks
+CREATE TABLE task_mentions (
+    task_id uuid NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    user_id text NOT NULL,
+    mentioned_at timestamp with time zone NOT NULL DEFAULT now(),
+    PRIMARY KEY (task_id, user_id)
+);
+CREATE INDEX task_mentions_user_idx ON task_mentions (user_id, mentioned_at);
+
+ALTER TABLE task_mentions ENABLE ROW LEVEL SECURITY;
+CREATE POLICY task_mentions_owner ON task_mentions TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE id = task_id));
+
+-- mentions in existing descriptions, following the same rules as the
+-- application
+INSERT INTO task_mentions (task_id, user_id)
+SELECT DISTINCT id, m[2]
+FROM tasks,
+    regexp_matches(description, '(^|[^[:alnum:]_.-])@([[:alnum:]_.-]*[[:alnum:]_])', 'g')
+        AS m;
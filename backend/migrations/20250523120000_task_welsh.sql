@@ -0,0 +1,86 @@
+-- Welsh translations of the title and description
+ALTER TABLE tasks
+ADD COLUMN title_cy text CHECK (title_cy <> ''),
+ADD COLUMN description_cy text CHECK (description_cy <> '');
+ALTER TABLE task_history
+ADD COLUMN title_cy text,
+ADD COLUMN description_cy text;
+ALTER TABLE task_listing
+ADD COLUMN title_cy text,
+ADD COLUMN description_cy text;
+
+CREATE OR REPLACE FUNCTION record_task_history() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO task_history
+        (task_id, version, action, reverted_to,
+            title, description, status, due, tags, estimate, progress, colour,
+            title_cy, description_cy)
+    SELECT
+        NEW.id,
+        coalesce(max(version), 0) + 1,
+        coalesce(
+            nullif(current_setting('app.history_action', true), ''),
+            lower(TG_OP)
+        ),
+        nullif(current_setting('app.history_reverted_to', true), '')::integer,
+        NEW.title,
+        NEW.description,
+        NEW.status,
+        NEW.due,
+        NEW.tags,
+        NEW.estimate,
+        NEW.progress,
+        NEW.colour,
+        NEW.title_cy,
+        NEW.description_cy
+    FROM task_history
+    WHERE task_id = NEW.id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION refresh_task_listing() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO task_listing AS l
+        (id, title, description, status, due, tags, estimate, progress, colour,
+            title_cy, description_cy, version,
+            created_at, updated_at, completed_at)
+    VALUES (
+        NEW.task_id,
+        NEW.title,
+        NEW.description,
+        NEW.status,
+        NEW.due,
+        NEW.tags,
+        NEW.estimate,
+        NEW.progress,
+        NEW.colour,
+        NEW.title_cy,
+        NEW.description_cy,
+        NEW.version,
+        NEW.recorded_at,
+        NEW.recorded_at,
+        CASE WHEN NEW.status = 'complete' THEN NEW.recorded_at END
+    )
+    ON CONFLICT (id) DO UPDATE SET
+        title = excluded.title,
+        description = excluded.description,
+        status = excluded.status,
+        due = excluded.due,
+        tags = excluded.tags,
+        estimate = excluded.estimate,
+        progress = excluded.progress,
+        colour = excluded.colour,
+        title_cy = excluded.title_cy,
+        description_cy = excluded.description_cy,
+        version = excluded.version,
+        updated_at = excluded.updated_at,
+        completed_at = CASE
+            WHEN excluded.status <> 'complete' THEN NULL
+            WHEN l.status = 'complete' THEN l.completed_at
+            ELSE excluded.updated_at
+        END
+    WHERE l.version < excluded.version;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,16 @@
+-- items in other applications which tasks are synced from
+CREATE TABLE external_refs (
+    task_id uuid NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    -- application the item is kept in, e.g. `google`
+    source text NOT NULL,
+    external_id text NOT NULL,
+    -- version of the task in `task_history` as of the last sync
+    synced_version integer NOT NULL,
+    synced_at timestamp with time zone NOT NULL DEFAULT now(),
+    PRIMARY KEY (source, external_id)
+);
+CREATE INDEX external_refs_task_idx ON external_refs (task_id);
+
+ALTER TABLE external_refs ENABLE ROW LEVEL SECURITY;
+CREATE POLICY external_refs_owner ON external_refs TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE id = task_id));
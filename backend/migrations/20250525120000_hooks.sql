@@ -0,0 +1,10 @@
+-- REST hooks subscribed by integrations such as Zapier, which are sent new
+-- tasks
+CREATE TABLE hooks (
+    id uuid PRIMARY KEY,
+    -- owner whose new tasks are sent
+    owner text,
+    target_url text NOT NULL,
+    created_at timestamp with time zone NOT NULL DEFAULT now()
+);
+CREATE INDEX hooks_owner_idx ON hooks (owner);
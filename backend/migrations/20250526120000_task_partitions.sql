@@ -0,0 +1,160 @@
+-- split `tasks` by status, so that the open tasks which hot paths read and
+-- write stay in a partition of their own while the closed (complete or
+-- cancelled) tasks, which only accumulate, are kept apart; queries filtering
+-- on status are pruned to the partitions they need
+
+-- foreign keys can't reference the ID of a partitioned table alone, since its
+-- unique constraints must include the partition key, so rows describing
+-- tasks reference this registry of the IDs in `tasks` instead, kept up to
+-- date by the triggers below
+CREATE TABLE task_ids (id uuid PRIMARY KEY);
+INSERT INTO task_ids SELECT id FROM tasks;
+
+ALTER TABLE task_links
+DROP CONSTRAINT task_links_source_fkey,
+DROP CONSTRAINT task_links_target_fkey,
+ADD FOREIGN KEY (source) REFERENCES task_ids (id) ON DELETE CASCADE,
+ADD FOREIGN KEY (target) REFERENCES task_ids (id) ON DELETE CASCADE;
+ALTER TABLE time_entries
+DROP CONSTRAINT time_entries_task_id_fkey,
+ADD FOREIGN KEY (task_id) REFERENCES task_ids (id) ON DELETE CASCADE;
+ALTER TABLE task_pins
+DROP CONSTRAINT task_pins_task_id_fkey,
+ADD FOREIGN KEY (task_id) REFERENCES task_ids (id) ON DELETE CASCADE;
+ALTER TABLE task_mentions
+DROP CONSTRAINT task_mentions_task_id_fkey,
+ADD FOREIGN KEY (task_id) REFERENCES task_ids (id) ON DELETE CASCADE;
+ALTER TABLE external_refs
+DROP CONSTRAINT external_refs_task_id_fkey,
+ADD FOREIGN KEY (task_id) REFERENCES task_ids (id) ON DELETE CASCADE;
+
+-- the policies referring to `tasks` are recreated for the partitioned table
+DROP POLICY task_links_owner ON task_links;
+DROP POLICY task_history_owner ON task_history;
+DROP POLICY task_listing_owner ON task_listing;
+DROP POLICY task_events_owner ON task_events;
+DROP POLICY task_snapshots_owner ON task_snapshots;
+DROP POLICY time_entries_owner ON time_entries;
+DROP POLICY task_pins_owner ON task_pins;
+DROP POLICY task_mentions_owner ON task_mentions;
+DROP POLICY external_refs_owner ON external_refs;
+
+ALTER TABLE tasks RENAME TO unpartitioned_tasks;
+CREATE TABLE tasks (
+    LIKE unpartitioned_tasks INCLUDING DEFAULTS INCLUDING CONSTRAINTS
+) PARTITION BY LIST (status);
+CREATE TABLE tasks_closed PARTITION OF tasks
+FOR VALUES IN ('complete', 'cancelled');
+-- every other status, including any added later
+CREATE TABLE tasks_open PARTITION OF tasks DEFAULT;
+
+-- copied before the triggers exist, so no history is recorded
+INSERT INTO tasks SELECT * FROM unpartitioned_tasks;
+DROP TABLE unpartitioned_tasks;
+
+ALTER TABLE tasks ADD PRIMARY KEY (id, status);
+CREATE INDEX tasks_tags_idx ON tasks USING gin (tags);
+CREATE INDEX tasks_title_trgm_idx ON tasks USING gin (title gin_trgm_ops);
+CREATE INDEX tasks_open_owner_idx ON tasks (owner)
+WHERE status NOT IN ('complete', 'cancelled');
+CREATE INDEX tasks_open_assignee_idx ON tasks (assignee, due)
+WHERE status NOT IN ('complete', 'cancelled');
+
+-- an update changing whether a task is open moves its row to the other
+-- partition, which fires the insert triggers rather than the update ones, so
+-- an insert of a task with history already recorded is an update
+CREATE OR REPLACE FUNCTION record_task_history() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO task_history
+        (task_id, version, action, reverted_to,
+            title, description, status, due, tags, estimate, progress, colour,
+            title_cy, description_cy)
+    SELECT
+        NEW.id,
+        coalesce(max(version), 0) + 1,
+        coalesce(
+            nullif(current_setting('app.history_action', true), ''),
+            CASE WHEN max(version) IS NOT NULL THEN 'update' ELSE lower(TG_OP) END
+        ),
+        nullif(current_setting('app.history_reverted_to', true), '')::integer,
+        NEW.title,
+        NEW.description,
+        NEW.status,
+        NEW.due,
+        NEW.tags,
+        NEW.estimate,
+        NEW.progress,
+        NEW.colour,
+        NEW.title_cy,
+        NEW.description_cy
+    FROM task_history
+    WHERE task_id = NEW.id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER tasks_history_insert
+AFTER INSERT ON tasks
+FOR EACH ROW EXECUTE FUNCTION record_task_history();
+
+CREATE TRIGGER tasks_history_update
+AFTER UPDATE ON tasks
+FOR EACH ROW WHEN (old.* IS DISTINCT FROM new.*)
+EXECUTE FUNCTION record_task_history();
+
+CREATE FUNCTION register_task_id() RETURNS trigger AS $$
+BEGIN
+    -- already registered if the row was moved between partitions
+    INSERT INTO task_ids VALUES (NEW.id) ON CONFLICT DO NOTHING;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE FUNCTION unregister_task_id() RETURNS trigger AS $$
+BEGIN
+    -- deleting the ID deletes the rows describing the task, unless the row
+    -- was only moved between partitions
+    DELETE FROM task_ids
+    WHERE id = OLD.id AND NOT EXISTS (SELECT 1 FROM tasks WHERE id = OLD.id);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER tasks_register_id
+AFTER INSERT ON tasks
+FOR EACH ROW EXECUTE FUNCTION register_task_id();
+
+CREATE TRIGGER tasks_unregister_id
+AFTER DELETE ON tasks
+FOR EACH ROW EXECUTE FUNCTION unregister_task_id();
+
+-- the partitions are only read and written through `tasks`, whose policy
+-- doesn't apply to them directly
+GRANT SELECT, INSERT, UPDATE, DELETE ON tasks, task_ids TO tasks_rls;
+REVOKE ALL ON tasks_open, tasks_closed FROM tasks_rls;
+
+ALTER TABLE tasks ENABLE ROW LEVEL SECURITY;
+CREATE POLICY tasks_owner ON tasks TO tasks_rls
+USING (owner IS NOT DISTINCT FROM app_current_user());
+
+CREATE POLICY task_links_owner ON task_links TO tasks_rls
+USING (
+    EXISTS (SELECT 1 FROM tasks WHERE id = source)
+    AND EXISTS (SELECT 1 FROM tasks WHERE id = target)
+);
+CREATE POLICY task_history_owner ON task_history TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE id = task_id));
+CREATE POLICY task_listing_owner ON task_listing TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE tasks.id = task_listing.id));
+CREATE POLICY task_events_owner ON task_events TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE id = task_id));
+CREATE POLICY task_snapshots_owner ON task_snapshots TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE id = task_id));
+CREATE POLICY time_entries_owner ON time_entries TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE id = task_id));
+CREATE POLICY task_pins_owner ON task_pins TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE id = task_id));
+CREATE POLICY task_mentions_owner ON task_mentions TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE id = task_id));
+CREATE POLICY external_refs_owner ON external_refs TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE id = task_id));
@@ -0,0 +1,62 @@
+-- statistics computed when refreshed by the scheduler rather than on each
+-- request, so dashboards polling them don't add load to `tasks`; they are
+-- grouped by owner too so they can be limited to one owner's tasks, which
+-- the queries reading them do since materialised views aren't subject to
+-- row-level security
+CREATE MATERIALIZED VIEW workload_stats AS
+SELECT owner, assignee,
+    count(*) AS open,
+    count(*) FILTER (WHERE due < now()) AS overdue,
+    min(due) AS next_due
+FROM tasks
+WHERE status NOT IN ('complete', 'cancelled')
+GROUP BY owner, assignee;
+
+-- a unique index lets the views be refreshed without blocking reads
+CREATE UNIQUE INDEX workload_stats_idx ON workload_stats (owner, assignee);
+
+CREATE MATERIALIZED VIEW estimate_stats AS
+WITH estimated AS (
+    SELECT t.owner, l.tags, t.assignee,
+        extract(epoch FROM l.estimate) AS estimated,
+        extract(epoch FROM coalesce(l.completed_at, now()) - l.created_at)
+            AS elapsed,
+        (
+            SELECT coalesce(
+                sum(extract(epoch FROM coalesce(stopped_at, now()) - started_at)),
+                0
+            )
+            FROM time_entries
+            WHERE task_id = l.id
+        ) AS tracked
+    FROM task_listing AS l
+    JOIN tasks AS t ON t.id = l.id
+    WHERE l.estimate IS NOT NULL AND l.status <> 'cancelled'
+), grouped AS (
+    -- untagged tasks are grouped together under NULL
+    SELECT 'tag' AS grouping, owner,
+        unnest(CASE WHEN tags = '{}' THEN ARRAY[NULL::text] ELSE tags END)
+            AS "group",
+        estimated, elapsed, tracked
+    FROM estimated
+    UNION ALL
+    SELECT 'assignee', owner, assignee, estimated, elapsed, tracked
+    FROM estimated
+)
+SELECT grouping, owner, "group",
+    count(*) AS tasks,
+    sum(estimated) AS estimated_seconds,
+    sum(tracked) AS tracked_seconds,
+    sum(elapsed) AS elapsed_seconds
+FROM grouped
+GROUP BY grouping, owner, "group";
+
+CREATE UNIQUE INDEX estimate_stats_idx ON estimate_stats (grouping, owner, "group");
+
+-- when the views were last refreshed
+CREATE TABLE stats_refreshes (
+    refreshed_at timestamp with time zone NOT NULL
+);
+INSERT INTO stats_refreshes VALUES (now());
+
+GRANT SELECT ON workload_stats, estimate_stats, stats_refreshes TO tasks_rls;
@@ -0,0 +1,102 @@
+-- when the task was last marked complete, if it still is, kept by the
+-- application as the status of the task changes
+ALTER TABLE tasks
+ADD COLUMN completed_at timestamp with time zone;
+ALTER TABLE task_history
+ADD COLUMN completed_at timestamp with time zone;
+
+-- taken from the read model, which has tracked completions so far; the
+-- change isn't a new version of the tasks, so isn't recorded in history
+ALTER TABLE tasks DISABLE TRIGGER tasks_history_update;
+UPDATE tasks
+SET completed_at = coalesce(l.completed_at, l.updated_at, now())
+FROM task_listing AS l
+WHERE l.id = tasks.id AND tasks.status = 'complete';
+ALTER TABLE tasks ENABLE TRIGGER tasks_history_update;
+
+ALTER TABLE tasks
+ADD CHECK ((completed_at IS NOT NULL) = (status = 'complete'));
+
+CREATE OR REPLACE FUNCTION record_task_history() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO task_history
+        (task_id, version, action, reverted_to,
+            title, description, status, due, tags, estimate, progress, colour,
+            title_cy, description_cy, completed_at)
+    SELECT
+        NEW.id,
+        coalesce(max(version), 0) + 1,
+        coalesce(
+            nullif(current_setting('app.history_action', true), ''),
+            CASE WHEN max(version) IS NOT NULL THEN 'update' ELSE lower(TG_OP) END
+        ),
+        nullif(current_setting('app.history_reverted_to', true), '')::integer,
+        NEW.title,
+        NEW.description,
+        NEW.status,
+        NEW.due,
+        NEW.tags,
+        NEW.estimate,
+        NEW.progress,
+        NEW.colour,
+        NEW.title_cy,
+        NEW.description_cy,
+        NEW.completed_at
+    FROM task_history
+    WHERE task_id = NEW.id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- the listing now copies the completion time of each version rather than
+-- working it out, falling back to that for versions recorded before it was
+-- kept
+CREATE OR REPLACE FUNCTION refresh_task_listing() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO task_listing AS l
+        (id, title, description, status, due, tags, estimate, progress, colour,
+            title_cy, description_cy, version,
+            created_at, updated_at, completed_at)
+    VALUES (
+        NEW.task_id,
+        NEW.title,
+        NEW.description,
+        NEW.status,
+        NEW.due,
+        NEW.tags,
+        NEW.estimate,
+        NEW.progress,
+        NEW.colour,
+        NEW.title_cy,
+        NEW.description_cy,
+        NEW.version,
+        NEW.recorded_at,
+        NEW.recorded_at,
+        CASE WHEN NEW.status = 'complete' THEN coalesce(NEW.completed_at, NEW.recorded_at) END
+    )
+    ON CONFLICT (id) DO UPDATE SET
+        title = excluded.title,
+        description = excluded.description,
+        status = excluded.status,
+        due = excluded.due,
+        tags = excluded.tags,
+        estimate = excluded.estimate,
+        progress = excluded.progress,
+        colour = excluded.colour,
+        title_cy = excluded.title_cy,
+        description_cy = excluded.description_cy,
+        version = excluded.version,
+        updated_at = excluded.updated_at,
+        completed_at = CASE
+            WHEN excluded.status <> 'complete' THEN NULL
+            WHEN NEW.completed_at IS NOT NULL THEN NEW.completed_at
+            WHEN l.status = 'complete' THEN l.completed_at
+            ELSE excluded.updated_at
+        END
+    WHERE l.version < excluded.version;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE INDEX task_listing_completed_at_idx ON task_listing (completed_at)
+WHERE completed_at IS NOT NULL;
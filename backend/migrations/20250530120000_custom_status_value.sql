@@ -0,0 +1,4 @@
+-- statuses defined by deployments' workflows, named in `custom_status`;
+-- added apart from the columns using it, as a new enum value can't be used
+-- in the transaction adding it
+ALTER TYPE task_status ADD VALUE 'custom';
@@ -0,0 +1,102 @@
+-- custom statuses configured by the deployment's workflow, which tasks
+-- with them reference; statuses dropped from the workflow are kept for the
+-- tasks which still have them
+CREATE TABLE statuses (
+    name text PRIMARY KEY CHECK (name <> ''),
+    -- order of the status in the workflow
+    position integer NOT NULL
+);
+
+ALTER TABLE tasks
+ADD COLUMN custom_status text REFERENCES statuses (name),
+ADD CHECK ((custom_status IS NOT NULL) = (status = 'custom'));
+ALTER TABLE task_history
+ADD COLUMN custom_status text;
+ALTER TABLE task_listing
+ADD COLUMN custom_status text;
+CREATE INDEX task_listing_custom_status_idx ON task_listing (custom_status)
+WHERE custom_status IS NOT NULL;
+
+CREATE OR REPLACE FUNCTION record_task_history() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO task_history
+        (task_id, version, action, reverted_to,
+            title, description, status, due, tags, estimate, progress, colour,
+            title_cy, description_cy, completed_at, status_reason, custom_status)
+    SELECT
+        NEW.id,
+        coalesce(max(version), 0) + 1,
+        coalesce(
+            nullif(current_setting('app.history_action', true), ''),
+            CASE WHEN max(version) IS NOT NULL THEN 'update' ELSE lower(TG_OP) END
+        ),
+        nullif(current_setting('app.history_reverted_to', true), '')::integer,
+        NEW.title,
+        NEW.description,
+        NEW.status,
+        NEW.due,
+        NEW.tags,
+        NEW.estimate,
+        NEW.progress,
+        NEW.colour,
+        NEW.title_cy,
+        NEW.description_cy,
+        NEW.completed_at,
+        NEW.status_reason,
+        NEW.custom_status
+    FROM task_history
+    WHERE task_id = NEW.id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION refresh_task_listing() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO task_listing AS l
+        (id, title, description, status, due, tags, estimate, progress, colour,
+            title_cy, description_cy, status_reason, custom_status, version,
+            created_at, updated_at, completed_at)
+    VALUES (
+        NEW.task_id,
+        NEW.title,
+        NEW.description,
+        NEW.status,
+        NEW.due,
+        NEW.tags,
+        NEW.estimate,
+        NEW.progress,
+        NEW.colour,
+        NEW.title_cy,
+        NEW.description_cy,
+        NEW.status_reason,
+        NEW.custom_status,
+        NEW.version,
+        NEW.recorded_at,
+        NEW.recorded_at,
+        CASE WHEN NEW.status = 'complete' THEN coalesce(NEW.completed_at, NEW.recorded_at) END
+    )
+    ON CONFLICT (id) DO UPDATE SET
+        title = excluded.title,
+        description = excluded.description,
+        status = excluded.status,
+        due = excluded.due,
+        tags = excluded.tags,
+        estimate = excluded.estimate,
+        progress = excluded.progress,
+        colour = excluded.colour,
+        title_cy = excluded.title_cy,
+        description_cy = excluded.description_cy,
+        status_reason = excluded.status_reason,
+        custom_status = excluded.custom_status,
+        version = excluded.version,
+        updated_at = excluded.updated_at,
+        completed_at = CASE
+            WHEN excluded.status <> 'complete' THEN NULL
+            WHEN NEW.completed_at IS NOT NULL THEN NEW.completed_at
+            WHEN l.status = 'complete' THEN l.completed_at
+            ELSE excluded.updated_at
+        END
+    WHERE l.version < excluded.version;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,67 @@
+-- when work on the task first started, as the first version of it with a
+-- status other than not started, for service level targets on responding
+ALTER TABLE task_listing
+ADD COLUMN responded_at timestamp with time zone;
+
+UPDATE task_listing AS l
+SET responded_at = h.responded_at
+FROM (
+    SELECT task_id, min(recorded_at) AS responded_at
+    FROM task_history
+    WHERE status <> 'not_started'
+    GROUP BY task_id
+) AS h
+WHERE h.task_id = l.id;
+
+CREATE OR REPLACE FUNCTION refresh_task_listing() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO task_listing AS l
+        (id, title, description, status, due, tags, estimate, progress, colour,
+            title_cy, description_cy, status_reason, custom_status, version,
+            created_at, updated_at, completed_at, responded_at)
+    VALUES (
+        NEW.task_id,
+        NEW.title,
+        NEW.description,
+        NEW.status,
+        NEW.due,
+        NEW.tags,
+        NEW.estimate,
+        NEW.progress,
+        NEW.colour,
+        NEW.title_cy,
+        NEW.description_cy,
+        NEW.status_reason,
+        NEW.custom_status,
+        NEW.version,
+        NEW.recorded_at,
+        NEW.recorded_at,
+        CASE WHEN NEW.status = 'complete' THEN coalesce(NEW.completed_at, NEW.recorded_at) END,
+        CASE WHEN NEW.status <> 'not_started' THEN NEW.recorded_at END
+    )
+    ON CONFLICT (id) DO UPDATE SET
+        title = excluded.title,
+        description = excluded.description,
+        status = excluded.status,
+        due = excluded.due,
+        tags = excluded.tags,
+        estimate = excluded.estimate,
+        progress = excluded.progress,
+        colour = excluded.colour,
+        title_cy = excluded.title_cy,
+        description_cy = excluded.description_cy,
+        status_reason = excluded.status_reason,
+        custom_status = excluded.custom_status,
+        version = excluded.version,
+        updated_at = excluded.updated_at,
+        completed_at = CASE
+            WHEN excluded.status <> 'complete' THEN NULL
+            WHEN NEW.completed_at IS NOT NULL THEN NEW.completed_at
+            WHEN l.status = 'complete' THEN l.completed_at
+            ELSE excluded.updated_at
+        END,
+        responded_at = coalesce(l.responded_at, excluded.responded_at)
+    WHERE l.version < excluded.version;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,38 @@
+-- the latest time up to `at` which is within working hours: on one of the
+-- ISO weekdays `days`, between `day_start` and `day_end` in UTC; tasks due
+-- before it are overdue when only working hours count, and with no working
+-- days given it is `at` itself, so every hour counts
+CREATE FUNCTION last_working_time(
+    at timestamp with time zone, days integer[], day_start time, day_end time
+) RETURNS timestamp with time zone AS $$
+    SELECT coalesce(
+        max(least(utc, day::date + day_end)) AT TIME ZONE 'UTC',
+        last_working_time.at
+    )
+    FROM (SELECT last_working_time.at AT TIME ZONE 'UTC' AS utc) AS local,
+        generate_series(utc::date - 7, utc::date, interval '1 day') AS day
+    WHERE extract(isodow FROM day) = ANY (days) AND day::date + day_start < utc
+$$ LANGUAGE sql IMMUTABLE;
+
+-- the working hours are set for the refresh, as materialised views can't
+-- take parameters
+DROP MATERIALIZED VIEW workload_stats;
+CREATE MATERIALIZED VIEW workload_stats AS
+SELECT owner, assignee,
+    count(*) AS open,
+    count(*) FILTER (
+        WHERE due < last_working_time(
+            now(),
+            nullif(current_setting('app.working_days', true), '')::integer[],
+            nullif(current_setting('app.working_day_start', true), '')::time,
+            nullif(current_setting('app.working_day_end', true), '')::time
+        )
+    ) AS overdue,
+    min(due) AS next_due
+FROM tasks
+WHERE status NOT IN ('complete', 'cancelled')
+GROUP BY owner, assignee;
+
+CREATE UNIQUE INDEX workload_stats_idx ON workload_stats (owner, assignee);
+
+GRANT SELECT ON workload_stats TO tasks_rls;
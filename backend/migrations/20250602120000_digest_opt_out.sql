@@ -0,0 +1,3 @@
+-- whether users are emailed digests of their tasks, which they may opt out
+-- of
+ALTER TABLE users ADD COLUMN digest boolean NOT NULL DEFAULT true;
@@ -0,0 +1,65 @@
+CREATE TYPE notification_channel AS ENUM ('email', 'slack', 'web_push');
+CREATE TYPE notification_event AS ENUM (
+    'assigned', 'due_soon', 'mentioned', 'overdue'
+);
+
+-- the channels users chose to be notified of events over, or not; those
+-- without a row follow the application's defaults
+CREATE TABLE notification_preferences (
+    user_id text NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    channel notification_channel NOT NULL,
+    event notification_event NOT NULL,
+    enabled boolean NOT NULL,
+    PRIMARY KEY (user_id, channel, event)
+);
+
+-- notifications of users about tasks, waiting to be dispatched to their
+-- channels or recently dispatched
+CREATE TABLE notifications (
+    id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    user_id text NOT NULL,
+    event notification_event NOT NULL,
+    task_id uuid NOT NULL REFERENCES task_ids (id) ON DELETE CASCADE,
+    -- due date that deadline notifications are about, so each is raised once
+    -- per due date of the task
+    due timestamp with time zone,
+    created_at timestamp with time zone NOT NULL DEFAULT now(),
+    dispatched_at timestamp with time zone,
+    UNIQUE (task_id, user_id, event, due)
+);
+CREATE INDEX notifications_pending_idx ON notifications (id)
+WHERE dispatched_at IS NULL;
+CREATE INDEX notifications_dispatched_idx ON notifications (dispatched_at);
+
+-- tasks assigned to someone other than their owner notify the new assignee
+CREATE FUNCTION notify_assignment() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO notifications (user_id, event, task_id)
+    VALUES (NEW.assignee, 'assigned', NEW.id);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER tasks_notify_assignment
+AFTER UPDATE OF assignee ON tasks
+FOR EACH ROW
+WHEN (
+    NEW.assignee IS NOT NULL
+    AND NEW.assignee IS DISTINCT FROM OLD.assignee
+    AND NEW.assignee IS DISTINCT FROM NEW.owner
+)
+EXECUTE FUNCTION notify_assignment();
+
+-- users mentioned in a task's description for the first time are notified
+CREATE FUNCTION notify_mention() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO notifications (user_id, event, task_id)
+    VALUES (NEW.user_id, 'mentioned', NEW.task_id);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER task_mentions_notify
+AFTER INSERT ON task_mentions
+FOR EACH ROW
+EXECUTE FUNCTION notify_mention();
@@ -0,0 +1,12 @@
+-- browsers subscribed to Web Push notifications of users
+CREATE TABLE push_subscriptions (
+    id uuid PRIMARY KEY,
+    user_id text NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- URL of the push service which notifications are posted to
+    endpoint text NOT NULL,
+    -- browser's public key and shared secret, base64url-encoded
+    p256dh text NOT NULL,
+    auth text NOT NULL,
+    created_at timestamp with time zone NOT NULL DEFAULT now(),
+    UNIQUE (user_id, endpoint)
+);
@@ -0,0 +1,4 @@
+-- phone numbers of users, in E.164 format, to send SMS notifications to
+ALTER TABLE users ADD COLUMN phone text;
+
+ALTER TYPE notification_channel ADD VALUE 'sms';
@@ -0,0 +1,30 @@
+-- files attached to tasks, which are scanned for viruses when uploaded
+CREATE TYPE attachment_scan_result AS ENUM ('clean', 'infected');
+
+CREATE TABLE attachments (
+    id uuid PRIMARY KEY,
+    task_id uuid NOT NULL REFERENCES task_ids (id) ON DELETE CASCADE,
+    filename text NOT NULL,
+    content_type text NOT NULL,
+    size bigint NOT NULL,
+    -- SHA-256 digest of the content, in hexadecimal
+    sha256 text NOT NULL,
+    uploaded_by text,
+    uploaded_at timestamp with time zone NOT NULL DEFAULT now(),
+    scan_result attachment_scan_result NOT NULL,
+    -- scanner's name for the threat found, if any
+    threat text,
+    scanned_by text NOT NULL,
+    scanned_at timestamp with time zone NOT NULL,
+    -- infected files kept for investigation, which can't be downloaded
+    quarantined boolean NOT NULL,
+    content bytea NOT NULL,
+    CHECK ((scan_result = 'infected') = (threat IS NOT NULL))
+);
+CREATE INDEX attachments_task_idx ON attachments (task_id, uploaded_at);
+
+ALTER TABLE attachments ENABLE ROW LEVEL SECURITY;
+CREATE POLICY attachments_owner ON attachments TO tasks_rls
+USING (EXISTS (SELECT 1 FROM tasks WHERE id = task_id));
+
+ALTER TYPE security_event_kind ADD VALUE 'infected_upload';
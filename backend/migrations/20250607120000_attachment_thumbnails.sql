@@ -0,0 +1,13 @@
+-- thumbnails of image attachments, generated in the background
+CREATE TABLE attachment_thumbnails (
+    attachment_id uuid PRIMARY KEY REFERENCES attachments (id) ON DELETE CASCADE,
+    -- PNG of the thumbnail, or null if the image couldn't be decoded
+    content bytea,
+    error text,
+    generated_at timestamp with time zone NOT NULL DEFAULT now(),
+    CHECK ((content IS NULL) <> (error IS NULL))
+);
+
+ALTER TABLE attachment_thumbnails ENABLE ROW LEVEL SECURITY;
+CREATE POLICY attachment_thumbnails_owner ON attachment_thumbnails TO tasks_rls
+USING (EXISTS (SELECT 1 FROM attachments WHERE id = attachment_id));
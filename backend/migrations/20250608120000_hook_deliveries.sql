@@ -0,0 +1,20 @@
+-- attempts to deliver new tasks to REST hooks, so integrators can debug
+-- failed deliveries and have them redelivered
+CREATE TABLE hook_deliveries (
+    id uuid PRIMARY KEY,
+    hook_id uuid NOT NULL REFERENCES hooks (id) ON DELETE CASCADE,
+    task_id uuid NOT NULL,
+    -- delivery this one redelivered, if any
+    redelivery_of uuid,
+    -- body posted to the hook
+    payload jsonb NOT NULL,
+    attempted_at timestamp with time zone NOT NULL DEFAULT now(),
+    latency_ms integer NOT NULL,
+    -- status and start of the body of the response, if there was one
+    response_status smallint,
+    response_snippet text,
+    -- why no response was received
+    error text,
+    CHECK ((response_status IS NULL) = (error IS NOT NULL))
+);
+CREATE INDEX hook_deliveries_hook_id_idx ON hook_deliveries (hook_id, attempted_at);
@@ -0,0 +1,8 @@
+-- templates of the payloads posted to hooks, for services expecting
+-- particular JSON
+ALTER TABLE hooks ADD COLUMN payload_template text;
+
+-- body posted to the hook, which is no longer always the payload, or null if
+-- its payload template failed to render
+ALTER TABLE hook_deliveries ADD COLUMN body jsonb;
+UPDATE hook_deliveries SET body = payload;
@@ -0,0 +1,24 @@
+-- bulk operations run in the background
+CREATE TYPE job_kind AS ENUM ('import', 'reschedule');
+CREATE TYPE job_status AS ENUM ('running', 'succeeded', 'failed', 'cancelled');
+CREATE TABLE jobs (
+    id uuid PRIMARY KEY,
+    -- owner who started the job, who alone can see it
+    owner text,
+    kind job_kind NOT NULL,
+    status job_status NOT NULL DEFAULT 'running',
+    -- number of items to process, if known
+    total integer,
+    processed integer NOT NULL DEFAULT 0,
+    -- items which couldn't be processed, as objects with `index` and `error`
+    errors jsonb NOT NULL DEFAULT '[]',
+    -- response the operation's synchronous endpoint would have given
+    result jsonb,
+    error text,
+    cancel_requested boolean NOT NULL DEFAULT false,
+    created_at timestamp with time zone NOT NULL DEFAULT now(),
+    finished_at timestamp with time zone,
+    CHECK ((status = 'running') = (finished_at IS NULL))
+);
+CREATE INDEX jobs_owner_idx ON jobs (owner);
+CREATE INDEX jobs_finished_at_idx ON jobs (finished_at);
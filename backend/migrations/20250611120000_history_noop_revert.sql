@@ -0,0 +1,11 @@
+-- reverts are recorded even if they restore the current state, so every
+-- revert has a version saying so
+DROP TRIGGER tasks_history_update ON tasks;
+
+CREATE TRIGGER tasks_history_update
+AFTER UPDATE ON tasks
+FOR EACH ROW WHEN (
+    old.* IS DISTINCT FROM new.*
+    OR current_setting('app.history_action', true) = 'revert'
+)
+EXECUTE FUNCTION record_task_history();
@@ -0,0 +1,29 @@
+-- the row-level security role only reaches the tables of tasks, which have
+-- policies, and what their triggers write; the other tables, such as
+-- sessions and access tokens, have no policies to keep owners apart
+ALTER DEFAULT PRIVILEGES IN SCHEMA public
+REVOKE SELECT, INSERT, UPDATE, DELETE ON TABLES FROM tasks_rls;
+REVOKE ALL ON ALL TABLES IN SCHEMA public FROM tasks_rls;
+
+GRANT SELECT, INSERT, UPDATE, DELETE
+ON
+tasks,
+task_ids,
+task_links,
+task_history,
+task_listing,
+task_events,
+task_snapshots,
+time_entries,
+task_pins,
+task_mentions,
+external_refs,
+attachments,
+attachment_thumbnails
+TO tasks_rls;
+
+-- statistics are limited to the owner by their queries
+GRANT SELECT ON workload_stats, estimate_stats, stats_refreshes TO tasks_rls;
+
+-- by the assignment and mention triggers
+GRANT INSERT ON notifications TO tasks_rls;
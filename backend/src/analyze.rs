@@ -0,0 +1,22 @@
+//! Report on the plans the database chooses for the store's queries.
+
+use dts_developer_challenge::store::PgTaskStore;
+use sqlx::PgPool;
+
+/// Explain the store's queries against the database behind `pool`, printing
+/// a report, and return whether any plan was warned of.
+pub(crate) async fn run(pool: PgPool, min_rows: f64, verbose: bool) -> Result<bool, sqlx::Error> {
+    let plans = PgTaskStore::new(pool).explain(min_rows).await?;
+    let mut warned = false;
+    for plan in &plans {
+        println!("{}: {:.1}ms", plan.name, plan.execution_ms);
+        for warning in &plan.warnings {
+            println!("  warning: {warning}");
+        }
+        if verbose {
+            println!("{:#}", plan.plan);
+        }
+        warned |= !plan.warnings.is_empty();
+    }
+    Ok(warned)
+}
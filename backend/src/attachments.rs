@@ -0,0 +1,285 @@
+//! Files attached to [`TodoTask`](crate::TodoTask)s.
+//!
+//! Every file is scanned for viruses when it is uploaded, and the result is
+//! kept with it. Infected files are either refused or kept in quarantine,
+//! where they can't be downloaded.
+//!
+//! Files can be downloaded by browsers through short-lived URLs signed by a
+//! [`DownloadSigner`], so the URLs carry no credentials of their own.
+
+use std::fmt::{self, Write};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, TimeDelta, Utc};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+#[cfg(feature = "postgres")]
+use sqlx::{FromRow, prelude::Type};
+use uuid::Uuid;
+
+use crate::scan::ScanVerdict;
+
+/// Longest file name attachments may have, in bytes.
+pub const MAX_FILENAME_LENGTH: usize = 255;
+
+/// Result of scanning an attachment for viruses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "postgres", derive(Type))]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "postgres", sqlx(type_name = "attachment_scan_result"))]
+#[cfg_attr(feature = "postgres", sqlx(rename_all = "snake_case"))]
+pub enum ScanResult {
+    /// No threat was found.
+    Clean,
+    /// A threat was found.
+    Infected,
+}
+
+/// File attached to a task, without its content.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct Attachment {
+    /// ID of the attachment.
+    pub id: Uuid,
+    /// ID of the task the file is attached to.
+    pub task_id: Uuid,
+    /// Name of the file.
+    pub filename: String,
+    /// Media type of the file.
+    pub content_type: String,
+    /// Size of the file in bytes.
+    pub size: i64,
+    /// SHA-256 digest of the file, in hexadecimal.
+    pub sha256: String,
+    /// Owner who uploaded the file, if known.
+    pub uploaded_by: Option<String>,
+    /// Date & time at which the file was uploaded.
+    pub uploaded_at: DateTime<Utc>,
+    /// Result of scanning the file for viruses.
+    pub scan_result: ScanResult,
+    /// Scanner's name for the threat found, if any.
+    pub threat: Option<String>,
+    /// Name of the scanner which scanned the file.
+    pub scanned_by: String,
+    /// Date & time at which the file was scanned.
+    pub scanned_at: DateTime<Utc>,
+    /// Whether the file is quarantined, so can't be downloaded.
+    pub quarantined: bool,
+}
+
+/// File to attach to a task, with the result of scanning it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NewAttachment {
+    /// Name of the file.
+    pub filename: String,
+    /// Media type of the file.
+    pub content_type: String,
+    /// Content of the file.
+    pub content: Vec<u8>,
+    /// Owner uploading the file, if known.
+    pub uploaded_by: Option<String>,
+    /// Result of scanning the file.
+    pub verdict: ScanVerdict,
+    /// Name of the scanner which scanned the file.
+    pub scanned_by: String,
+    /// Date & time at which the file was scanned.
+    pub scanned_at: DateTime<Utc>,
+}
+
+impl NewAttachment {
+    /// SHA-256 digest of the content, in hexadecimal.
+    #[must_use]
+    pub fn sha256(&self) -> String {
+        Sha256::digest(&self.content)
+            .iter()
+            .fold(String::new(), |mut hex, byte| {
+                let _ = write!(hex, "{byte:02x}");
+                hex
+            })
+    }
+
+    /// Whether the file was found to be infected, so must be quarantined.
+    #[must_use]
+    pub fn infected(&self) -> bool {
+        matches!(self.verdict, ScanVerdict::Infected(_))
+    }
+}
+
+/// Signer of URLs which let an attachment be downloaded without
+/// credentials until they expire.
+#[derive(Clone)]
+pub struct DownloadSigner {
+    key: Vec<u8>,
+    /// How long signed URLs last.
+    lifetime: TimeDelta,
+}
+
+impl fmt::Debug for DownloadSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DownloadSigner")
+            .field("lifetime", &self.lifetime)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Query parameters of a signed download URL.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadSignature {
+    /// Unix time at which the URL expires.
+    pub expires: i64,
+    /// Signature of the attachment's IDs and the expiry.
+    pub signature: String,
+}
+
+impl DownloadSigner {
+    /// Create a signer with `key`, signing URLs which last `lifetime`.
+    #[must_use]
+    pub fn new(key: Vec<u8>, lifetime: TimeDelta) -> Self {
+        Self { key, lifetime }
+    }
+
+    /// Create a signer with a random key, whose URLs are only accepted by
+    /// this process.
+    #[must_use]
+    pub fn random(lifetime: TimeDelta) -> Self {
+        let mut key = vec![0; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        Self::new(key, lifetime)
+    }
+
+    /// MAC of the attachment `attachment_id` of `task_id` expiring at
+    /// `expires`.
+    fn mac(&self, task_id: Uuid, attachment_id: Uuid, expires: i64) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC keys can be any length");
+        mac.update(format!("{task_id}/{attachment_id}/{expires}").as_bytes());
+        mac
+    }
+
+    /// Sign a download of the attachment `attachment_id` of `task_id`,
+    /// lasting from `now`.
+    #[must_use]
+    pub fn sign(
+        &self,
+        task_id: Uuid,
+        attachment_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> DownloadSignature {
+        let expires = (now + self.lifetime).timestamp();
+        let signature = self.mac(task_id, attachment_id, expires).finalize();
+        DownloadSignature {
+            expires,
+            signature: URL_SAFE_NO_PAD.encode(signature.into_bytes()),
+        }
+    }
+
+    /// Whether `signature` allows the attachment `attachment_id` of
+    /// `task_id` to be downloaded at `now`.
+    #[must_use]
+    pub fn verify(
+        &self,
+        task_id: Uuid,
+        attachment_id: Uuid,
+        signature: &DownloadSignature,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let Ok(decoded) = URL_SAFE_NO_PAD.decode(&signature.signature) else {
+            return false;
+        };
+        // compare in constant time, even if the URL has expired
+        let valid = self
+            .mac(task_id, attachment_id, signature.expires)
+            .verify_slice(&decoded)
+            .is_ok();
+        valid && now.timestamp() <= signature.expires
+    }
+}
+
+/// Check that `filename` can be given for an attachment: that it isn't
+/// empty, too long, a path, or contains control characters.
+///
+/// # Errors
+///
+/// Returns a description of the problem if it can't.
+pub fn check_filename(filename: &str) -> Result<(), &'static str> {
+    if filename.trim().is_empty() || filename.len() > MAX_FILENAME_LENGTH {
+        return Err("filename must be between 1 and 255 bytes long");
+    }
+    if filename.contains(['/', '\\']) || filename == "." || filename == ".." {
+        return Err("filename must not be a path");
+    }
+    if filename.chars().any(char::is_control) {
+        return Err("filename must not contain control characters");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("report.pdf", true)]
+    #[case("Quarterly report (final).docx", true)]
+    #[case("", false)]
+    #[case("../etc/passwd", false)]
+    #[case("C:\\boot.ini", false)]
+    #[case("..", false)]
+    #[case("evil\r\nname.txt", false)]
+    fn filenames(#[case] filename: &str, #[case] valid: bool) {
+        assert_eq!(check_filename(filename).is_ok(), valid);
+    }
+
+    #[test]
+    fn download_signatures() {
+        let signer = DownloadSigner::new(b"secret".to_vec(), TimeDelta::minutes(5));
+        let (task_id, attachment_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = "2026-10-16T12:00:00Z".parse().unwrap();
+        let signature = signer.sign(task_id, attachment_id, now);
+        assert!(signer.verify(task_id, attachment_id, &signature, now));
+        assert!(signer.verify(
+            task_id,
+            attachment_id,
+            &signature,
+            now + TimeDelta::minutes(5)
+        ));
+        assert!(!signer.verify(
+            task_id,
+            attachment_id,
+            &signature,
+            now + TimeDelta::minutes(6)
+        ));
+        assert!(!signer.verify(task_id, Uuid::new_v4(), &signature, now));
+        assert!(!signer.verify(Uuid::new_v4(), attachment_id, &signature, now));
+
+        let extended = DownloadSignature {
+            expires: signature.expires + 3600,
+            ..signature.clone()
+        };
+        assert!(!signer.verify(task_id, attachment_id, &extended, now));
+        let other = DownloadSigner::random(TimeDelta::minutes(5));
+        assert!(!other.verify(task_id, attachment_id, &signature, now));
+        assert!(!format!("{signer:?}").contains("secret"));
+    }
+
+    #[test]
+    fn digest() {
+        let attachment = NewAttachment {
+            filename: "hello.txt".to_owned(),
+            content_type: "text/plain".to_owned(),
+            content: b"hello".to_vec(),
+            uploaded_by: None,
+            verdict: ScanVerdict::Clean,
+            scanned_by: "clamav".to_owned(),
+            scanned_at: "2026-10-16T12:00:00Z".parse().unwrap(),
+        };
+        assert_eq!(
+            attachment.sha256(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert!(!attachment.infected());
+    }
+}
@@ -0,0 +1,676 @@
+//! Terminal client for browsing and editing the tasks on a running task
+//! server.
+//!
+//! Only built with the `tui` feature. The last tasks fetched are cached, and
+//! shown read-only when the server can't be reached.
+
+#![deny(clippy::pedantic)]
+#![deny(missing_docs)]
+
+use std::{
+    fmt::Write,
+    io,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use clap::Parser;
+use dts_developer_challenge::{
+    TodoStatus,
+    client::{Client, blocking},
+    email,
+};
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Clear, Paragraph, Row, Table, TableState},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+/// How long a request to the server may take before it's given up on.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Format in which due dates are shown and edited; see
+/// [`email::parse_due`] for the formats accepted.
+const DUE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Labels of the fields of the task form, in order.
+const TASK_FIELDS: [&str; 5] = ["Title", "Description", "Status", "Due", "Tags"];
+
+/// Position of the status in [`TASK_FIELDS`], which is chosen rather than
+/// typed.
+const STATUS_FIELD: usize = 2;
+
+/// Labels of the fields of the filter form, in order.
+const FILTER_FIELDS: [&str; 2] = ["Filter", "Tags"];
+
+/// Command-line arguments of the client.
+#[derive(Parser, Debug)]
+struct Opt {
+    /// Base URL of the server.
+    #[clap(long, default_value = "http://localhost:8080")]
+    url: String,
+    /// File containing a personal access token to authenticate with.
+    #[clap(long)]
+    token_file: Option<PathBuf>,
+    /// Owner to act as with the `X-Owner` header, if the server accepts it.
+    #[clap(long, conflicts_with = "token_file")]
+    owner: Option<String>,
+    /// Filter expression to list tasks with, e.g. `status = in_progress`.
+    #[clap(long)]
+    filter: Option<String>,
+    /// Tag expression to list tasks with, e.g. `work,-blocked`.
+    #[clap(long)]
+    tags: Option<String>,
+    /// File caching the last tasks fetched.
+    ///
+    /// Defaults to `todo-tui/tasks.json` in the user's cache directory.
+    #[clap(long)]
+    cache_file: Option<PathBuf>,
+}
+
+/// Fields of a task which can be edited, as sent to and from the server.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct TaskFields {
+    title: String,
+    description: Option<String>,
+    status: TodoStatus,
+    due: DateTime<Utc>,
+    tags: Vec<String>,
+    /// Other fields, such as the estimate, which are sent back unchanged.
+    #[serde(flatten)]
+    other: Map<String, Value>,
+}
+
+/// Task listed by the server.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Task {
+    id: Uuid,
+    #[serde(flatten)]
+    fields: TaskFields,
+}
+
+/// Tasks fetched from a server, as cached.
+#[derive(Serialize, Deserialize, Debug)]
+struct Cache {
+    /// Base URL of the server.
+    url: String,
+    /// Date & time at which the tasks were fetched.
+    fetched_at: DateTime<Utc>,
+    tasks: Vec<Task>,
+}
+
+/// Client of the task server's HTTP API.
+struct Api {
+    client: blocking::Client,
+}
+
+impl Api {
+    /// List the tasks matching `filter` and `tags` expressions.
+    fn list(&self, filter: &str, tags: &str) -> Result<Vec<Task>, String> {
+        let [filter, tags] =
+            [filter.trim(), tags.trim()].map(|value| (!value.is_empty()).then_some(value));
+        self.client.list(filter, tags).map_err(|e| e.to_string())
+    }
+
+    /// Create a task.
+    fn create(&self, fields: &TaskFields) -> Result<(), String> {
+        self.client
+            .create(fields)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Replace the task with `id`.
+    fn update(&self, id: Uuid, fields: &TaskFields) -> Result<(), String> {
+        match self.client.update(id, fields) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err("task no longer exists".to_owned()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// What a form is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Purpose {
+    /// Changing the filters tasks are listed with.
+    Filter,
+    /// Creating a task.
+    Create,
+    /// Editing the task at an index of the list.
+    Edit(usize),
+}
+
+/// Form of text fields, edited one at a time.
+#[derive(Debug)]
+struct Form {
+    purpose: Purpose,
+    values: Vec<String>,
+    /// Index of the field being edited.
+    focus: usize,
+}
+
+impl Form {
+    /// Get the labels of the form's fields.
+    fn labels(&self) -> &'static [&'static str] {
+        match self.purpose {
+            Purpose::Filter => &FILTER_FIELDS,
+            Purpose::Create | Purpose::Edit(_) => &TASK_FIELDS,
+        }
+    }
+
+    /// Start a form for a task, filled in with `fields`.
+    fn for_task(purpose: Purpose, fields: &TaskFields) -> Self {
+        Self {
+            purpose,
+            values: vec![
+                fields.title.clone(),
+                fields.description.clone().unwrap_or_default(),
+                status_name(&fields.status).to_owned(),
+                fields.due.format(DUE_FORMAT).to_string(),
+                fields.tags.join(" "),
+            ],
+            focus: 0,
+        }
+    }
+
+    /// Get the fields of the task in the form, along with `other` fields
+    /// which it can't edit.
+    fn task_fields(&self, other: Map<String, Value>) -> Result<TaskFields, String> {
+        let description = self.values[1].trim();
+        // anything else is a custom status, which the server checks
+        let status = self.values[STATUS_FIELD].trim();
+        let status = TodoStatus::ALL
+            .into_iter()
+            .find(|known| status_name(known) == status)
+            .or_else(|| (!status.is_empty()).then(|| TodoStatus::Custom(status.to_owned())))
+            .ok_or("unknown status")?;
+        let due = email::parse_due(&self.values[3], Utc::now())
+            .ok_or("due date must be like 2025-06-30 14:00")?;
+        Ok(TaskFields {
+            title: self.values[0].trim().to_owned(),
+            description: (!description.is_empty()).then(|| description.to_owned()),
+            status,
+            due,
+            tags: self.values[4]
+                .split_whitespace()
+                .map(str::to_owned)
+                .collect(),
+            other,
+        })
+    }
+
+    /// Choose the next or previous status, if the status is being edited.
+    fn cycle_status(&mut self, forwards: bool) {
+        if self.purpose == Purpose::Filter || self.focus != STATUS_FIELD {
+            return;
+        }
+        let current = TodoStatus::ALL
+            .iter()
+            .position(|status| status_name(status) == self.values[STATUS_FIELD])
+            .unwrap_or_default();
+        let count = TodoStatus::ALL.len();
+        let next = if forwards {
+            (current + 1) % count
+        } else {
+            (current + count - 1) % count
+        };
+        status_name(&TodoStatus::ALL[next]).clone_into(&mut self.values[STATUS_FIELD]);
+    }
+}
+
+/// State of the interface.
+struct App {
+    api: Api,
+    cache_file: Option<PathBuf>,
+    /// Filter and tag expressions tasks are listed with.
+    filter: [String; 2],
+    tasks: Vec<Task>,
+    table: TableState,
+    /// Form being filled in, if any.
+    form: Option<Form>,
+    /// When the tasks shown were cached, if the server couldn't be reached.
+    offline_since: Option<DateTime<Utc>>,
+    /// Outcome of the last action.
+    message: String,
+}
+
+impl App {
+    /// Fetch the tasks again, falling back to the cache if the server can't
+    /// be reached.
+    fn refresh(&mut self) {
+        match self.api.list(&self.filter[0], &self.filter[1]) {
+            Ok(tasks) => {
+                self.tasks = tasks;
+                self.offline_since = None;
+                self.message = format!("{} tasks", self.tasks.len());
+                self.save_cache();
+            }
+            Err(e) => {
+                if let Some(cache) = self.load_cache() {
+                    self.tasks = cache.tasks;
+                    self.offline_since = Some(cache.fetched_at);
+                    self.message = format!("offline: {e}");
+                } else {
+                    self.tasks.clear();
+                    self.message = e;
+                }
+            }
+        }
+        let selected = self.table.selected().unwrap_or_default();
+        self.table
+            .select((!self.tasks.is_empty()).then(|| selected.min(self.tasks.len() - 1)));
+    }
+
+    /// Write the tasks shown to the cache file, if there is one.
+    fn save_cache(&mut self) {
+        let Some(path) = &self.cache_file else {
+            return;
+        };
+        let cache = Cache {
+            url: self.api.client.inner().url().to_owned(),
+            fetched_at: Utc::now(),
+            tasks: self.tasks.clone(),
+        };
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::write(path, serde_json::to_vec(&cache)?));
+        if let Err(e) = written {
+            self.message = format!("failed to write cache: {e}");
+        }
+    }
+
+    /// Read the cached tasks, if they were fetched from the same server.
+    fn load_cache(&self) -> Option<Cache> {
+        let cache: Cache =
+            serde_json::from_slice(&std::fs::read(self.cache_file.as_ref()?).ok()?).ok()?;
+        (cache.url == self.api.client.inner().url()).then_some(cache)
+    }
+
+    /// Handle a key press, returning whether to carry on.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if self.form.is_some() {
+            self.handle_form_key(key);
+            return true;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') => self.table.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.table.select_previous(),
+            KeyCode::Char('r') => self.refresh(),
+            KeyCode::Char('/') => {
+                self.form = Some(Form {
+                    purpose: Purpose::Filter,
+                    values: self.filter.to_vec(),
+                    focus: 0,
+                });
+            }
+            KeyCode::Char('n') if self.online() => {
+                let fields = TaskFields {
+                    title: String::new(),
+                    description: None,
+                    status: TodoStatus::NotStarted,
+                    due: Utc::now() + TimeDelta::days(1),
+                    tags: Vec::new(),
+                    other: Map::new(),
+                };
+                self.form = Some(Form::for_task(Purpose::Create, &fields));
+            }
+            KeyCode::Char('e') | KeyCode::Enter if self.online() => {
+                if let Some(index) = self.selected() {
+                    let form = Form::for_task(Purpose::Edit(index), &self.tasks[index].fields);
+                    self.form = Some(form);
+                }
+            }
+            KeyCode::Char('s') if self.online() => self.advance_status(),
+            KeyCode::Char(digit @ '1'..='5') if self.online() => {
+                let index = digit as usize - '1' as usize;
+                self.set_status(TodoStatus::ALL[index].clone());
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Handle a key press while a form is being filled in.
+    fn handle_form_key(&mut self, key: KeyEvent) {
+        let Some(form) = &mut self.form else {
+            return;
+        };
+        let count = form.values.len();
+        match key.code {
+            KeyCode::Esc => self.form = None,
+            KeyCode::Tab | KeyCode::Down => form.focus = (form.focus + 1) % count,
+            KeyCode::BackTab | KeyCode::Up => form.focus = (form.focus + count - 1) % count,
+            KeyCode::Right => form.cycle_status(true),
+            KeyCode::Left => form.cycle_status(false),
+            KeyCode::Backspace if !is_status(form) => {
+                form.values[form.focus].pop();
+            }
+            KeyCode::Char(c) if !is_status(form) => form.values[form.focus].push(c),
+            KeyCode::Enter => self.submit(),
+            _ => {}
+        }
+    }
+
+    /// Act on the completed form.
+    fn submit(&mut self) {
+        let Some(form) = self.form.take() else {
+            return;
+        };
+        let result = match form.purpose {
+            Purpose::Filter => {
+                self.filter = [form.values[0].clone(), form.values[1].clone()];
+                self.refresh();
+                return;
+            }
+            Purpose::Create => form
+                .task_fields(Map::new())
+                .and_then(|fields| self.api.create(&fields)),
+            Purpose::Edit(index) => {
+                let task = &self.tasks[index];
+                form.task_fields(task.fields.other.clone())
+                    .and_then(|fields| self.api.update(task.id, &fields))
+            }
+        };
+        match result {
+            Ok(()) => self.refresh(),
+            Err(e) => {
+                // keep the form open to correct it
+                self.message = e;
+                self.form = Some(form);
+            }
+        }
+    }
+
+    /// Move the selected task on to the next status.
+    fn advance_status(&mut self) {
+        let Some(index) = self.selected() else {
+            return;
+        };
+        let status = &self.tasks[index].fields.status;
+        let position = TodoStatus::ALL
+            .iter()
+            .position(|s| s == status)
+            .unwrap_or_default();
+        self.set_status(TodoStatus::ALL[(position + 1) % TodoStatus::ALL.len()].clone());
+    }
+
+    /// Change the status of the selected task.
+    fn set_status(&mut self, status: TodoStatus) {
+        let Some(index) = self.selected() else {
+            return;
+        };
+        let task = &self.tasks[index];
+        let fields = TaskFields {
+            status,
+            ..task.fields.clone()
+        };
+        match self.api.update(task.id, &fields) {
+            Ok(()) => {
+                self.message = format!("{}: {}", task.fields.title, status_name(&fields.status));
+                self.tasks[index].fields = fields;
+                self.save_cache();
+            }
+            Err(e) => self.message = e,
+        }
+    }
+
+    /// Get the index of the selected task.
+    fn selected(&self) -> Option<usize> {
+        self.table
+            .selected()
+            .filter(|index| *index < self.tasks.len())
+    }
+
+    /// Check whether the server can be reached, saying so if not.
+    fn online(&mut self) -> bool {
+        if self.offline_since.is_some() {
+            "offline: press r to reconnect before making changes".clone_into(&mut self.message);
+        }
+        self.offline_since.is_none()
+    }
+
+    /// Draw the interface.
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(2),
+        ])
+        .areas(frame.area());
+
+        let mut title = format!(
+            "{}  filter: {}",
+            self.api.client.inner().url(),
+            self.filter.join(" | ")
+        );
+        if let Some(fetched_at) = self.offline_since {
+            let _ = write!(
+                title,
+                "  [offline, cached {}]",
+                fetched_at.format(DUE_FORMAT)
+            );
+        }
+        frame.render_widget(Line::from(title).style(bold()), header);
+
+        let rows = self.tasks.iter().map(|task| {
+            Row::new([
+                task.fields.title.clone(),
+                status_name(&task.fields.status).to_owned(),
+                task.fields.due.format(DUE_FORMAT).to_string(),
+                task.fields.tags.join(" "),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(3),
+                Constraint::Length(12),
+                Constraint::Length(16),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(Row::new(["Title", "Status", "Due", "Tags"]).style(bold()))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .block(Block::bordered());
+        frame.render_stateful_widget(table, body, &mut self.table);
+
+        let help = if self.form.is_some() {
+            "tab: next field  ←/→: status  enter: save  esc: cancel"
+        } else {
+            "j/k: move  n: new  e: edit  s: next status  1-5: set status  /: filter  r: refresh  q: quit"
+        };
+        frame.render_widget(
+            Paragraph::new(vec![Line::from(self.message.as_str()), Line::from(help)]),
+            footer,
+        );
+
+        if let Some(form) = &self.form {
+            draw_form(frame, form);
+        }
+    }
+}
+
+/// Draw a form in a box over the middle of the screen.
+fn draw_form(frame: &mut Frame, form: &Form) {
+    let labels = form.labels();
+    let height = u16::try_from(labels.len()).unwrap_or(u16::MAX) + 2;
+    let [area] = Layout::horizontal([Constraint::Percentage(70)])
+        .flex(Flex::Center)
+        .areas(frame.area());
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(area);
+
+    let title = match form.purpose {
+        Purpose::Filter => "Filter tasks",
+        Purpose::Create => "New task",
+        Purpose::Edit(_) => "Edit task",
+    };
+    let lines: Vec<Line> = labels
+        .iter()
+        .zip(&form.values)
+        .enumerate()
+        .map(|(i, (label, value))| {
+            let line = Line::from(format!("{label:>11}: {value}"));
+            if i == form.focus {
+                line.style(bold())
+            } else {
+                line
+            }
+        })
+        .collect();
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(title)),
+        area,
+    );
+
+    let column = 13 + form.values[form.focus].chars().count();
+    frame.set_cursor_position(cursor(area, column, form.focus));
+}
+
+/// Get the position of `column` on `line` inside a bordered `area`.
+fn cursor(area: Rect, column: usize, line: usize) -> (u16, u16) {
+    let offset = |n: usize| u16::try_from(n + 1).unwrap_or(u16::MAX);
+    (
+        area.x.saturating_add(offset(column)),
+        area.y.saturating_add(offset(line)),
+    )
+}
+
+/// Check whether the status of a task form is being edited, which is done
+/// by choosing rather than typing.
+fn is_status(form: &Form) -> bool {
+    form.purpose != Purpose::Filter && form.focus == STATUS_FIELD
+}
+
+/// Get the style of headings and the focused field.
+fn bold() -> Style {
+    Style::new().add_modifier(Modifier::BOLD)
+}
+
+/// Get the name of a status as shown in the interface.
+fn status_name(status: &TodoStatus) -> &str {
+    match status {
+        TodoStatus::NotStarted => "not started",
+        TodoStatus::InProgress => "in progress",
+        TodoStatus::Complete => "complete",
+        TodoStatus::Cancelled => "cancelled",
+        TodoStatus::Blocked => "blocked",
+        TodoStatus::Custom(name) => name,
+    }
+}
+
+/// Get the default cache file, in the user's cache directory.
+fn default_cache_file() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .map(|dir| dir.join("todo-tui").join("tasks.json"))
+}
+
+/// Run the interface until the user quits.
+fn run(mut terminal: DefaultTerminal, app: &mut App) -> io::Result<()> {
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !app.handle_key(key) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let opt = Opt::parse();
+    let token = match opt.token_file.as_deref().map(std::fs::read_to_string) {
+        Some(Ok(token)) => Some(token.trim().to_owned()),
+        Some(Err(e)) => {
+            eprintln!("todo-tui: failed to read token file: {e}");
+            return ExitCode::FAILURE;
+        }
+        None => None,
+    };
+    let mut client = match Client::new(&opt.url).with_timeout(REQUEST_TIMEOUT) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("todo-tui: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(token) = token {
+        client = client.with_token(token);
+    }
+    if let Some(owner) = opt.owner {
+        client = client.with_owner(owner);
+    }
+    let client = match blocking::Client::new(client) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("todo-tui: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut app = App {
+        api: Api { client },
+        cache_file: opt.cache_file.or_else(default_cache_file),
+        filter: [opt.filter.unwrap_or_default(), opt.tags.unwrap_or_default()],
+        tasks: Vec::new(),
+        table: TableState::default(),
+        form: None,
+        offline_since: None,
+        message: String::new(),
+    };
+    app.refresh();
+
+    let terminal = ratatui::init();
+    let result = run(terminal, &mut app);
+    ratatui::restore();
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("todo-tui: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn task_form() {
+        let base: TaskFields = serde_json::from_str(
+            r#"{"title": "Rota", "description": null, "status": "Blocked",
+            "due": "2025-06-30T12:00:00Z", "tags": ["admin"], "estimate": 3600}"#,
+        )
+        .unwrap();
+        let mut form = Form::for_task(Purpose::Edit(0), &base);
+        assert_eq!(form.values[3], "2025-06-30 12:00");
+        form.focus = STATUS_FIELD;
+        form.cycle_status(true);
+        form.values[4] = "admin urgent".into();
+
+        let fields = form.task_fields(base.other.clone()).unwrap();
+        assert_eq!(fields.status, TodoStatus::Complete);
+        assert_eq!(fields.tags, ["admin", "urgent"]);
+        assert_eq!(fields.due, base.due);
+        assert_eq!(fields.other["estimate"], 3600);
+
+        form.values[3] = "soon".into();
+        assert!(form.task_fields(Map::new()).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+//! Command-line client for administering a running task server.
+
+#![deny(clippy::pedantic)]
+#![deny(missing_docs)]
+
+use std::{path::PathBuf, process::ExitCode};
+
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use dts_developer_challenge::{client::Client, import::ImportFormat};
+
+/// Command-line arguments of the client.
+#[derive(Parser, Debug)]
+struct Opt {
+    /// Base URL of the server.
+    #[clap(long, default_value = "http://localhost:8080")]
+    url: String,
+    /// File containing a personal access token to authenticate with.
+    #[clap(long)]
+    token_file: Option<PathBuf>,
+    /// Owner to act as with the `X-Owner` header, if the server accepts it.
+    #[clap(long, conflicts_with = "token_file")]
+    owner: Option<String>,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+/// Operation to perform.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Create tasks from another application's export.
+    Import {
+        /// Application the tasks were exported from.
+        #[clap(long, value_enum)]
+        from: Format,
+        /// Due date of items which don't have one, in RFC 3339 format.
+        ///
+        /// Such items are rejected by default.
+        #[clap(long)]
+        default_due: Option<DateTime<Utc>>,
+        /// JSON export file.
+        file: PathBuf,
+    },
+}
+
+/// Application which tasks were exported from.
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Format {
+    /// Todoist, as JSON from its REST or Sync API.
+    Todoist,
+    /// Trello, as a board exported to JSON.
+    Trello,
+}
+
+impl From<Format> for ImportFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Todoist => Self::Todoist,
+            Format::Trello => Self::Trello,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let opt = Opt::parse();
+    match run(opt).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("todoctl: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Perform the command given in `opt`.
+async fn run(opt: Opt) -> Result<(), String> {
+    let Command::Import {
+        from,
+        default_due,
+        file,
+    } = opt.command;
+
+    let export = std::fs::read(&file)
+        .map_err(|e| format!("failed to read {}: {e}", file.to_string_lossy()))?;
+    let mut client = Client::new(&opt.url);
+    if let Some(path) = opt.token_file {
+        let token = std::fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read {}: {e}", path.to_string_lossy()))?;
+        client = client.with_token(token.trim());
+    }
+    if let Some(owner) = opt.owner {
+        client = client.with_owner(owner);
+    }
+
+    let report = client
+        .import(from.into(), default_due, export)
+        .await
+        .map_err(|e| e.to_string())?;
+    println!(
+        "{}",
+        serde_json::to_string(&report).map_err(|e| e.to_string())?
+    );
+    Ok(())
+}
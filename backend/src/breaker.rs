@@ -0,0 +1,185 @@
+//! Circuit breaker tripped by database outages, so requests made during one
+//! can fail fast rather than each waiting for the database to time out.
+
+use std::{
+    num::NonZeroU32,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use sqlx::PgPool;
+use tracing::{info, warn};
+
+/// State of a [`CircuitBreaker`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// The database is believed to be up.
+    Closed {
+        /// Number of outage errors in a row.
+        failures: u32,
+    },
+    /// The database is believed to be down.
+    Open {
+        /// When the database will next be probed.
+        retry_at: Instant,
+    },
+}
+
+impl State {
+    /// Close the breaker.
+    fn close(&mut self) {
+        if matches!(self, Self::Open { .. }) {
+            info!("database available again, closing circuit breaker");
+        }
+        *self = Self::Closed { failures: 0 };
+    }
+}
+
+/// Breaker which opens after a number of database outage errors in a row,
+/// and closes again once the database answers a probe.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    /// Number of outage errors in a row which open the breaker.
+    threshold: NonZeroU32,
+    /// Time between probes while the breaker is open.
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    /// Create a closed breaker, which opens after `threshold` outage errors
+    /// in a row and probes the database every `cooldown` while open.
+    #[must_use]
+    pub fn new(threshold: NonZeroU32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Record the result of a database operation.
+    ///
+    /// Only errors reaching the database count towards opening the breaker,
+    /// and any other result closes it.
+    pub fn record<T>(&self, result: &Result<T, sqlx::Error>) {
+        self.record_outage(matches!(result, Err(e) if is_outage(e)));
+    }
+
+    /// Record whether a database operation failed because the database
+    /// couldn't be reached.
+    pub fn record_outage(&self, outage: bool) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match (outage, *state) {
+            (true, State::Closed { failures }) => {
+                let failures = failures + 1;
+                if failures >= self.threshold.get() {
+                    warn!(failures, "database unavailable, opening circuit breaker");
+                    *state = State::Open {
+                        retry_at: Instant::now() + self.cooldown,
+                    };
+                } else {
+                    *state = State::Closed { failures };
+                }
+            }
+            (true, State::Open { .. }) => {}
+            _ => state.close(),
+        }
+    }
+
+    /// How long until the database is next probed, if the breaker is open.
+    ///
+    /// This is never less than a second, so it can be sent as a
+    /// `Retry-After` header.
+    #[must_use]
+    pub fn retry_after(&self) -> Option<Duration> {
+        match *self.state.lock().unwrap_or_else(PoisonError::into_inner) {
+            State::Closed { .. } => None,
+            State::Open { retry_at } => Some(
+                retry_at
+                    .saturating_duration_since(Instant::now())
+                    .max(Duration::from_secs(1)),
+            ),
+        }
+    }
+
+    /// Whether the breaker is open, so database operations should not be
+    /// attempted.
+    #[must_use]
+    pub fn is_open(&self) -> bool {
+        self.retry_after().is_some()
+    }
+
+    /// Probe the database behind `pool` every cooldown while the breaker is
+    /// open, closing it once the database answers, in the background.
+    pub fn spawn_probe(self: &Arc<Self>, pool: PgPool) {
+        let breaker = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(breaker.cooldown);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                if !breaker.is_open() {
+                    continue;
+                }
+                let probe = sqlx::query("SELECT 1").execute(&pool);
+                let result = match tokio::time::timeout(breaker.cooldown, probe).await {
+                    Ok(result) => result.map(|_| ()),
+                    Err(_) => Err(sqlx::Error::PoolTimedOut),
+                };
+                let mut state = breaker.state.lock().unwrap_or_else(PoisonError::into_inner);
+                if result.is_ok() {
+                    state.close();
+                } else {
+                    *state = State::Open {
+                        retry_at: Instant::now() + breaker.cooldown,
+                    };
+                }
+            }
+        });
+    }
+}
+
+/// Whether `error` means the database couldn't be reached, rather than it
+/// refusing the operation.
+pub(crate) fn is_outage(error: &sqlx::Error) -> bool {
+    matches!(
+        error,
+        sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::Protocol(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_outages_in_a_row() {
+        let breaker = CircuitBreaker::new(NonZeroU32::new(2).unwrap(), Duration::from_secs(30));
+        let outage = Err::<(), _>(sqlx::Error::PoolTimedOut);
+
+        breaker.record(&outage);
+        assert!(!breaker.is_open());
+        // success resets the count
+        breaker.record(&Ok(()));
+        breaker.record(&outage);
+        assert!(!breaker.is_open());
+        // errors from a database which is up don't count
+        breaker.record(&Err::<(), _>(sqlx::Error::RowNotFound));
+        breaker.record(&outage);
+        assert!(!breaker.is_open());
+
+        breaker.record(&outage);
+        let retry_after = breaker.retry_after().unwrap();
+        assert!(retry_after > Duration::from_secs(29));
+        assert!(retry_after <= Duration::from_secs(30));
+
+        breaker.record(&Ok(()));
+        assert!(!breaker.is_open());
+    }
+}
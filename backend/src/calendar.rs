@@ -0,0 +1,256 @@
+//! Working hours, which service level targets are measured in, and which
+//! tasks may be counted as overdue in.
+//!
+//! Calendars are written like `Mon-Fri 09:00-17:00`: a comma-separated list
+//! of weekdays or ranges of them, then the start and end of the working day.
+//! Times are in UTC.
+
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Datelike, NaiveTime, TimeDelta, Utc, Weekday};
+
+/// Days of the week and hours of the day in which work happens.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorkCalendar {
+    /// Expression the calendar was parsed from.
+    expression: String,
+    /// Whether each weekday is worked, from Monday.
+    weekdays: [bool; 7],
+    /// Start of the working day.
+    start: NaiveTime,
+    /// End of the working day, after `start`.
+    end: NaiveTime,
+}
+
+impl Default for WorkCalendar {
+    /// Nine to five, Monday to Friday.
+    fn default() -> Self {
+        "Mon-Fri 09:00-17:00"
+            .parse()
+            .expect("default calendar is valid")
+    }
+}
+
+impl WorkCalendar {
+    /// Working hours between `day`'s midnight and the next, if it's worked.
+    fn hours_of(&self, day: DateTime<Utc>) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let date = day.date_naive();
+        self.weekdays[date.weekday().num_days_from_monday() as usize].then(|| {
+            (
+                date.and_time(self.start).and_utc(),
+                date.and_time(self.end).and_utc(),
+            )
+        })
+    }
+
+    /// Working hours of each day from that of `from` on.
+    fn days_from(
+        &self,
+        from: DateTime<Utc>,
+    ) -> impl Iterator<Item = (DateTime<Utc>, DateTime<Utc>)> {
+        let midnight = from.date_naive().and_time(NaiveTime::MIN).and_utc();
+        (0..)
+            .map_while(move |days| midnight.checked_add_signed(TimeDelta::days(days)))
+            .filter_map(|day| self.hours_of(day))
+    }
+
+    /// The latest time up to `at` which is within working hours.
+    ///
+    /// Tasks due before it have been overdue for some working time.
+    #[must_use]
+    pub fn last_working_time(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let midnight = at.date_naive().and_time(NaiveTime::MIN).and_utc();
+        (0..7)
+            .map(|days| midnight - TimeDelta::days(days))
+            .filter_map(|day| self.hours_of(day))
+            .find(|(start, _)| *start < at)
+            .map_or(at, |(_, end)| end.min(at))
+    }
+
+    /// Working weekdays, numbered from Monday as 1 as in ISO 8601.
+    #[must_use]
+    pub fn iso_weekdays(&self) -> Vec<i32> {
+        (1..)
+            .zip(self.weekdays)
+            .filter_map(|(day, worked)| worked.then_some(day))
+            .collect()
+    }
+
+    /// Start and end of the working day.
+    #[must_use]
+    pub fn hours(&self) -> (NaiveTime, NaiveTime) {
+        (self.start, self.end)
+    }
+
+    /// Amount of working time from `from` until `to`, which is zero if `to`
+    /// isn't after `from`.
+    #[must_use]
+    pub fn working_time(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> TimeDelta {
+        if to <= from {
+            return TimeDelta::zero();
+        }
+        self.days_from(from)
+            .take_while(|(start, _)| *start < to)
+            .map(|(start, end)| (end.min(to) - start.max(from)).max(TimeDelta::zero()))
+            .sum()
+    }
+
+    /// The time at which `duration` of working time has passed since `from`.
+    ///
+    /// Targets ending as a working day does are met then, rather than at the
+    /// start of the next working day.
+    #[must_use]
+    pub fn add_working_time(&self, from: DateTime<Utc>, duration: TimeDelta) -> DateTime<Utc> {
+        let mut remaining = duration;
+        if remaining <= TimeDelta::zero() {
+            return from;
+        }
+        for (start, end) in self.days_from(from) {
+            let start = start.max(from);
+            if start >= end {
+                continue;
+            }
+            if remaining <= end - start {
+                return start + remaining;
+            }
+            remaining -= end - start;
+        }
+        DateTime::<Utc>::MAX_UTC
+    }
+}
+
+impl fmt::Display for WorkCalendar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+/// Failure to parse a [`WorkCalendar`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CalendarError(String);
+
+impl fmt::Display for CalendarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid working hours: {}", self.0)
+    }
+}
+
+impl std::error::Error for CalendarError {}
+
+impl FromStr for WorkCalendar {
+    type Err = CalendarError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<_> = s.split_whitespace().collect();
+        let [days, hours] = fields[..] else {
+            return Err(CalendarError(format!(
+                "expected days and hours, like \"Mon-Fri 09:00-17:00\", found {s:?}"
+            )));
+        };
+
+        let weekday = |name: &str| {
+            name.parse::<Weekday>()
+                .map(|day| day.num_days_from_monday() as usize)
+                .map_err(|_| CalendarError(format!("{name:?} is not a weekday")))
+        };
+        let mut weekdays = [false; 7];
+        for part in days.split(',') {
+            let (first, last) = match part.split_once('-') {
+                Some((first, last)) => (weekday(first)?, weekday(last)?),
+                None => (weekday(part)?, weekday(part)?),
+            };
+            if first > last {
+                return Err(CalendarError(format!(
+                    "days {part:?} don't run from Monday towards Sunday"
+                )));
+            }
+            weekdays[first..=last].fill(true);
+        }
+
+        let time = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M")
+                .map_err(|_| CalendarError(format!("{time:?} is not a time like 09:00")))
+        };
+        let (start, end) = hours
+            .split_once('-')
+            .ok_or_else(|| CalendarError(format!("hours {hours:?} are not a range")))?;
+        let (start, end) = (time(start)?, time(end)?);
+        if start >= end {
+            return Err(CalendarError(format!(
+                "working day {hours:?} ends before it starts"
+            )));
+        }
+
+        Ok(Self {
+            expression: s.trim().to_owned(),
+            weekdays,
+            start,
+            end,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[rstest]
+    // Friday afternoon to Monday morning
+    #[case("2025-06-06T16:00:00Z", "2025-06-09T10:00:00Z", 2)]
+    #[case("2025-06-07T12:00:00Z", "2025-06-08T12:00:00Z", 0)]
+    #[case("2025-06-09T08:00:00Z", "2025-06-10T08:00:00Z", 8)]
+    #[case("2025-06-10T08:00:00Z", "2025-06-09T08:00:00Z", 0)]
+    fn working_time(#[case] from: &str, #[case] to: &str, #[case] hours: i64) {
+        assert_eq!(
+            WorkCalendar::default().working_time(at(from), at(to)),
+            TimeDelta::hours(hours)
+        );
+    }
+
+    #[rstest]
+    #[case("2025-06-06T16:00:00Z", 2, "2025-06-09T10:00:00Z")]
+    #[case("2025-06-06T16:00:00Z", 1, "2025-06-06T17:00:00Z")]
+    #[case("2025-06-07T12:00:00Z", 8, "2025-06-09T17:00:00Z")]
+    #[case("2025-06-09T20:00:00Z", 0, "2025-06-09T20:00:00Z")]
+    fn add_working_time(#[case] from: &str, #[case] hours: i64, #[case] expected: &str) {
+        assert_eq!(
+            WorkCalendar::default().add_working_time(at(from), TimeDelta::hours(hours)),
+            at(expected)
+        );
+    }
+
+    #[rstest]
+    #[case("2025-06-07T12:00:00Z", "2025-06-06T17:00:00Z")]
+    #[case("2025-06-09T08:00:00Z", "2025-06-06T17:00:00Z")]
+    #[case("2025-06-09T09:30:00Z", "2025-06-09T09:30:00Z")]
+    fn last_working_time(#[case] at: &str, #[case] expected: &str) {
+        assert_eq!(
+            WorkCalendar::default().last_working_time(self::at(at)),
+            self::at(expected)
+        );
+    }
+
+    #[rstest]
+    fn weekends() {
+        let calendar: WorkCalendar = "Sat,Sun 10:00-14:00".parse().unwrap();
+        assert_eq!(
+            calendar.working_time(at("2025-06-06T00:00:00Z"), at("2025-06-13T00:00:00Z")),
+            TimeDelta::hours(8)
+        );
+    }
+
+    #[rstest]
+    #[case("Mon-Fri")]
+    #[case("Fri-Mon 09:00-17:00")]
+    #[case("Mon-Fry 09:00-17:00")]
+    #[case("Mon-Fri 17:00-09:00")]
+    #[case("Mon-Fri 9am-5pm")]
+    fn invalid(#[case] calendar: &str) {
+        assert!(calendar.parse::<WorkCalendar>().is_err());
+    }
+}
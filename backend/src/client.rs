@@ -0,0 +1,718 @@
+//! Typed client of the HTTP API of a running task server.
+//!
+//! Only built with the `client` feature. Requests which are safe to repeat
+//! are retried when the server can't be reached, doesn't respond in time, or
+//! is unavailable, after a random delay whose bound doubles with each
+//! attempt. Creating a task is made safe to repeat with an idempotency key,
+//! so the task is created once however many attempts reach the server.
+//!
+//! Callers without a tokio runtime can use the `blocking` client instead,
+//! with the `blocking` feature.
+
+use std::{fmt, time::Duration};
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::{Method, RequestBuilder, Response, StatusCode, header};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::{TaskDiff, import::ImportFormat};
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+/// Longest time to wait before the first retry.
+const BASE_DELAY: Duration = Duration::from_millis(100);
+/// Longest time to wait before any retry.
+const MAX_DELAY: Duration = Duration::from_secs(5);
+/// Number of times a request is retried by default.
+const DEFAULT_RETRIES: u32 = 3;
+/// Header making a request safe to repeat, as the server does what it asks
+/// at most once.
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+/// Number of history entries fetched per page by [`Client::history`].
+const HISTORY_PAGE_SIZE: u32 = 100;
+
+/// Error from a request to the server.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request couldn't be sent, or the response couldn't be read.
+    Http(reqwest::Error),
+    /// The server rejected the request body because of problems with its
+    /// fields.
+    Invalid {
+        /// Human-readable summary of the problems.
+        message: String,
+        /// Problems with the fields of the body.
+        errors: Vec<FieldError>,
+    },
+    /// The server responded with another unsuccessful status.
+    Status {
+        /// Status of the response.
+        status: StatusCode,
+        /// Body of the response, usually a human-readable message.
+        body: String,
+    },
+    /// The body of a successful response wasn't as expected.
+    Decode(String),
+}
+
+impl ClientError {
+    /// Get the status the server responded with, if it responded.
+    #[must_use]
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Http(e) => e.status(),
+            Self::Invalid { .. } => Some(StatusCode::UNPROCESSABLE_ENTITY),
+            Self::Status { status, .. } => Some(*status),
+            Self::Decode(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(e) => write!(f, "request failed: {e}"),
+            Self::Invalid { message, errors } => {
+                f.write_str(message)?;
+                for (i, error) in errors.iter().enumerate() {
+                    f.write_str(if i == 0 { ": " } else { ", " })?;
+                    if let Some(field) = &error.field {
+                        write!(f, "{field}: ")?;
+                    }
+                    f.write_str(&error.message)?;
+                }
+                Ok(())
+            }
+            Self::Status { status, body } => write!(f, "server responded with {status}: {body}"),
+            Self::Decode(e) => write!(f, "unexpected response: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Http(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Http(e)
+    }
+}
+
+/// Problem with a field of a request body, as reported by the server.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    /// Path to the field, such as `title` or `tags[0]`, or `None` if the
+    /// problem is with the body as a whole.
+    #[serde(default)]
+    pub field: Option<String>,
+    /// Human-readable description of the problem.
+    pub message: String,
+}
+
+/// Response body rejecting an invalid request body.
+#[derive(Deserialize, Debug)]
+struct InvalidBody {
+    message: String,
+    errors: Vec<FieldError>,
+}
+
+/// Task created by [`Client::create`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Created {
+    /// ID of the task.
+    pub id: Uuid,
+    /// Human-readable problems with the task, which was created regardless.
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+/// Version of a task in its history, as listed by [`Client::history`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// Number of the version, counting from 1.
+    pub version: i32,
+    /// Date & time at which the version was recorded.
+    pub recorded_at: DateTime<Utc>,
+    /// What recorded the version, such as `create` or `update`.
+    pub action: String,
+    /// Version restored by this version, if it was recorded by a revert.
+    #[serde(default)]
+    pub reverted_to: Option<i32>,
+    /// Changes from the previous version; empty for the first version.
+    pub changes: TaskDiff,
+}
+
+/// Item of an export which couldn't be imported, in [`ImportReport`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RejectedItem {
+    /// Position of the item among those imported, from 0.
+    pub index: usize,
+    /// Human-readable reason the item was rejected.
+    pub error: String,
+}
+
+/// Outcome of [`Client::import`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ImportReport {
+    /// IDs of the tasks created, in the order of the export.
+    pub created: Vec<Uuid>,
+    /// Items which weren't imported.
+    pub rejected: Vec<RejectedItem>,
+}
+
+/// Query parameters of [`Client::import`].
+#[derive(Serialize, Debug)]
+struct ImportQuery {
+    from: ImportFormat,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_due: Option<DateTime<Utc>>,
+}
+
+/// Client of the HTTP API of a task server.
+///
+/// Methods taking or returning tasks are generic over their type, so that
+/// callers can use [`TodoTask`](crate::TodoTask) or a type of their own,
+/// such as one keeping the fields it doesn't know about.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    /// Base URL of the server, without a trailing slash.
+    url: String,
+    /// Personal access token to authenticate with, if any.
+    token: Option<String>,
+    /// Owner to act as with the `X-Owner` header, if any.
+    owner: Option<String>,
+    /// Number of times to retry a request.
+    retries: u32,
+}
+
+impl Client {
+    /// Create a client of the server at the base URL `url`.
+    #[must_use]
+    pub fn new(url: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_owned(),
+            token: None,
+            owner: None,
+            retries: DEFAULT_RETRIES,
+        }
+    }
+
+    /// Authenticate with the personal access `token`.
+    #[must_use]
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Act as `owner` with the `X-Owner` header, if the server accepts it.
+    #[must_use]
+    pub fn with_owner(mut self, owner: impl Into<String>) -> Self {
+        self.owner = Some(owner.into());
+        self
+    }
+
+    /// Give up on each attempt at a request after `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the TLS backend can't be initialised.
+    pub fn with_timeout(mut self, timeout: Duration) -> Result<Self, ClientError> {
+        self.http = reqwest::Client::builder().timeout(timeout).build()?;
+        Ok(self)
+    }
+
+    /// Retry requests which are safe to repeat up to `retries` times.
+    #[must_use]
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Get the base URL of the server.
+    #[must_use]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// List the tasks matching the `filter` and `tags` expressions, if
+    /// given.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails, or the tasks can't be
+    /// deserialized as `T`.
+    pub async fn list<T: DeserializeOwned>(
+        &self,
+        filter: Option<&str>,
+        tags: Option<&str>,
+    ) -> Result<Vec<T>, ClientError> {
+        let query: Vec<_> = [("q", filter), ("tags", tags)]
+            .into_iter()
+            .filter_map(|(name, value)| Some((name, value?)))
+            .collect();
+        let response = self
+            .send(Method::GET, "/task", |request| request.query(&query))
+            .await?;
+        decode(response).await
+    }
+
+    /// Get the task with `id`, or `None` if there is no such task.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails, or the task can't be
+    /// deserialized as `T`.
+    pub async fn get<T: DeserializeOwned>(&self, id: Uuid) -> Result<Option<T>, ClientError> {
+        match self.send(Method::GET, &format!("/task/{id}"), |r| r).await {
+            Ok(response) => decode(response).await.map(Some),
+            Err(e) if e.status() == Some(StatusCode::NOT_FOUND) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Create a task.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClientError::Invalid`] if the server rejects the task, or
+    /// another error if the request fails.
+    pub async fn create<T: Serialize + ?Sized>(&self, task: &T) -> Result<Created, ClientError> {
+        // the same key on every attempt, so the task is only created once
+        let key = Uuid::new_v4().to_string();
+        let response = self
+            .send(Method::POST, "/task", |request| {
+                request.header(IDEMPOTENCY_KEY, &key).json(task)
+            })
+            .await?;
+        // the server responds with the plain ID unless it has warnings
+        let is_json = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+        if is_json {
+            return decode(response).await;
+        }
+        let body = response.text().await?;
+        let id = body
+            .trim()
+            .parse()
+            .map_err(|e| ClientError::Decode(format!("invalid task ID {body:?}: {e}")))?;
+        Ok(Created {
+            id,
+            warnings: Vec::new(),
+        })
+    }
+
+    /// Replace the task with `id`, returning whether there was one.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClientError::Invalid`] if the server rejects the task, or
+    /// another error if the request fails.
+    pub async fn update<T: Serialize + ?Sized>(
+        &self,
+        id: Uuid,
+        task: &T,
+    ) -> Result<bool, ClientError> {
+        match self
+            .send(Method::PUT, &format!("/task/{id}"), |request| {
+                request.json(task)
+            })
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) if e.status() == Some(StatusCode::NOT_FOUND) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get the history of the task with `id` a page at a time, `per_page`
+    /// versions to a page, oldest first.
+    #[must_use]
+    pub fn history_pages(&self, id: Uuid, per_page: u32) -> HistoryPages<'_> {
+        HistoryPages {
+            client: self,
+            id,
+            per_page,
+            page: Some(1),
+        }
+    }
+
+    /// Get the whole history of the task with `id`, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any request fails, including if there is no such
+    /// task.
+    pub async fn history(&self, id: Uuid) -> Result<Vec<HistoryEntry>, ClientError> {
+        let mut pages = self.history_pages(id, HISTORY_PAGE_SIZE);
+        let mut history = Vec::new();
+        while let Some(page) = pages.next_page().await {
+            history.extend(page?);
+        }
+        Ok(history)
+    }
+
+    /// Create tasks from the JSON `export` of another application, with
+    /// `default_due` as the due date of items which don't have one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails, including if the export is
+    /// malformed.
+    pub async fn import(
+        &self,
+        from: ImportFormat,
+        default_due: Option<DateTime<Utc>>,
+        export: Vec<u8>,
+    ) -> Result<ImportReport, ClientError> {
+        let query = ImportQuery { from, default_due };
+        let response = self
+            .send(Method::POST, "/task/import", |request| {
+                request
+                    .query(&query)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(export.clone())
+            })
+            .await?;
+        decode(response).await
+    }
+
+    /// Send a request to `path`, built by `build`, returning the response if
+    /// it is successful.
+    ///
+    /// Requests which are safe to repeat are retried while the server can't
+    /// be reached, doesn't respond in time, or is unavailable: those with an
+    /// idempotent `method`, and those with an idempotency key.
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        build: impl Fn(RequestBuilder) -> RequestBuilder,
+    ) -> Result<Response, ClientError> {
+        let mut attempt = 0;
+        loop {
+            let mut request = self
+                .http
+                .request(method.clone(), format!("{}{path}", self.url))
+                .header(header::ACCEPT, "application/json");
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+            if let Some(owner) = &self.owner {
+                request = request.header("x-owner", owner);
+            }
+            let request = build(request).build()?;
+            let repeatable =
+                method.is_idempotent() || request.headers().contains_key(IDEMPOTENCY_KEY);
+            let result = self.http.execute(request).await;
+
+            let retryable = match &result {
+                Ok(response) => matches!(
+                    response.status(),
+                    StatusCode::BAD_GATEWAY
+                        | StatusCode::SERVICE_UNAVAILABLE
+                        | StatusCode::GATEWAY_TIMEOUT
+                ),
+                Err(e) => e.is_connect() || e.is_timeout(),
+            };
+            if retryable && repeatable && attempt < self.retries {
+                let bound = BASE_DELAY
+                    .saturating_mul(1_u32 << attempt.min(16))
+                    .min(MAX_DELAY);
+                let delay = rand::thread_rng().gen_range(Duration::ZERO..=bound);
+                attempt += 1;
+                debug!(
+                    attempt,
+                    delay_ms = delay.as_millis(),
+                    %method,
+                    path,
+                    "retrying request"
+                );
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+            return check(result?).await;
+        }
+    }
+}
+
+/// Get the history of a task a page at a time, from
+/// [`Client::history_pages`].
+#[derive(Debug)]
+pub struct HistoryPages<'a> {
+    client: &'a Client,
+    /// ID of the task.
+    id: Uuid,
+    per_page: u32,
+    /// Number of the next page, or `None` if the last page has been fetched.
+    page: Option<u32>,
+}
+
+impl HistoryPages<'_> {
+    /// Fetch the next page of history, or `None` if there are no more.
+    ///
+    /// A failed page may be fetched again by calling this again.
+    pub async fn next_page(&mut self) -> Option<Result<Vec<HistoryEntry>, ClientError>> {
+        let page = self.page?;
+        let path = format!("/task/{}/history", self.id);
+        let query = [("page", page), ("per_page", self.per_page)];
+        let entries: Vec<HistoryEntry> = match self
+            .client
+            .send(Method::GET, &path, |request| request.query(&query))
+            .await
+        {
+            Ok(response) => match decode(response).await {
+                Ok(entries) => entries,
+                Err(e) => return Some(Err(e)),
+            },
+            Err(e) => return Some(Err(e)),
+        };
+        // a short page is the last one
+        self.page = (entries.len() >= self.per_page as usize).then_some(page + 1);
+        (page == 1 || !entries.is_empty()).then_some(Ok(entries))
+    }
+}
+
+/// Return `response` if it is successful, or the error it describes.
+async fn check(response: Response) -> Result<Response, ClientError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await?;
+    if status == StatusCode::UNPROCESSABLE_ENTITY {
+        if let Ok(InvalidBody { message, errors }) = serde_json::from_str(&body) {
+            return Err(ClientError::Invalid { message, errors });
+        }
+    }
+    Err(ClientError::Status { status, body })
+}
+
+/// Deserialize the JSON body of `response`.
+async fn decode<T: DeserializeOwned>(response: Response) -> Result<T, ClientError> {
+    let body = response.bytes().await?;
+    serde_json::from_slice(&body).map_err(|e| ClientError::Decode(e.to_string()))
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    };
+
+    use axum::{
+        Json, Router,
+        extract::{Path, Query},
+        http::HeaderMap,
+        routing::{get, post},
+    };
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    /// Serve `router` on a local port, returning a client of it.
+    async fn serve(router: Router) -> Client {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        Client::new(&url).with_owner("alice")
+    }
+
+    #[tokio::test]
+    async fn retries_unavailable() {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&attempts);
+        let router = Router::new().route(
+            "/task",
+            get(move |headers: HeaderMap| async move {
+                assert_eq!(headers["x-owner"], "alice");
+                if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                    Err(StatusCode::SERVICE_UNAVAILABLE)
+                } else {
+                    Ok(Json(json!([{"title": "Rota"}])))
+                }
+            }),
+        );
+        let client = serve(router).await;
+
+        let tasks: Vec<Value> = client.list(None, None).await.unwrap();
+        assert_eq!(tasks, [json!({"title": "Rota"})]);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        attempts.store(0, Ordering::SeqCst);
+        let error = client.with_retries(1).list::<Value>(None, None).await;
+        assert_eq!(
+            error.unwrap_err().status(),
+            Some(StatusCode::SERVICE_UNAVAILABLE)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        // importing tasks twice isn't safe
+        attempts.store(0, Ordering::SeqCst);
+        let counter = Arc::clone(&attempts);
+        let error = serve(Router::new().route(
+            "/task/import",
+            post(move || async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                StatusCode::SERVICE_UNAVAILABLE
+            }),
+        ))
+        .await
+        .import(ImportFormat::Trello, None, b"[]".to_vec())
+        .await;
+        assert_eq!(
+            error.unwrap_err().status(),
+            Some(StatusCode::SERVICE_UNAVAILABLE)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_create_with_key() {
+        let id = Uuid::new_v4();
+        let keys = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = Arc::clone(&keys);
+        let router = Router::new().route(
+            "/task",
+            post(move |headers: HeaderMap| async move {
+                let mut keys = seen.lock().unwrap();
+                keys.push(headers[IDEMPOTENCY_KEY].to_str().unwrap().to_owned());
+                match keys.len() {
+                    1 => Err(StatusCode::BAD_GATEWAY),
+                    2 => Err(StatusCode::GATEWAY_TIMEOUT),
+                    _ => Ok(id.to_string()),
+                }
+            }),
+        );
+        let client = serve(router).await;
+
+        assert_eq!(client.create(&json!({})).await.unwrap().id, id);
+        let keys = keys.lock().unwrap();
+        assert_eq!(keys.len(), 3);
+        assert!(keys[0].parse::<Uuid>().is_ok());
+        assert!(keys.iter().all(|key| *key == keys[0]));
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        let router = Router::new()
+            .route(
+                "/task",
+                post(|| async {
+                    let body = json!({
+                        "message": "invalid request body",
+                        "errors": [{"field": "title", "message": "title must not be empty"}],
+                    });
+                    (StatusCode::UNPROCESSABLE_ENTITY, Json(body))
+                }),
+            )
+            .route(
+                "/task/{task_id}",
+                get(|| async { StatusCode::NOT_FOUND }).put(|| async { StatusCode::NOT_FOUND }),
+            )
+            .route(
+                "/task/import",
+                post(|| async { (StatusCode::BAD_REQUEST, "malformed export") }),
+            );
+        let client = serve(router).await;
+
+        let error = client.create(&json!({"title": ""})).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid request body: title: title must not be empty"
+        );
+        assert!(client.get::<Value>(Uuid::nil()).await.unwrap().is_none());
+        assert!(!client.update(Uuid::nil(), &json!({})).await.unwrap());
+        let error = client
+            .import(ImportFormat::Trello, None, b"[]".to_vec())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "server responded with 400 Bad Request: malformed export"
+        );
+    }
+
+    #[tokio::test]
+    async fn created() {
+        let id = Uuid::new_v4();
+        let client =
+            serve(Router::new().route("/task", post(move || async move { id.to_string() }))).await;
+        let created = client.create(&json!({})).await.unwrap();
+        assert_eq!(created.id, id);
+        assert!(created.warnings.is_empty());
+
+        let client = serve(Router::new().route(
+            "/task",
+            post(move || async move {
+                let body = json!({"id": id, "warnings": ["due date has already passed"]});
+                (StatusCode::CREATED, Json(body))
+            }),
+        ))
+        .await;
+        let created = client.create(&json!({})).await.unwrap();
+        assert_eq!(created.id, id);
+        assert_eq!(created.warnings, ["due date has already passed"]);
+    }
+
+    #[tokio::test]
+    async fn history_pages() {
+        #[derive(Deserialize)]
+        struct Page {
+            page: u32,
+            per_page: u32,
+        }
+
+        // 5 versions
+        let router = Router::new().route(
+            "/task/{task_id}/history",
+            get(
+                |Path(_): Path<Uuid>, Query(Page { page, per_page }): Query<Page>| async move {
+                    let first = (page - 1) * per_page + 1;
+                    let entries: Vec<_> = (first..(first + per_page).min(6))
+                        .map(|version| {
+                            json!({
+                                "version": version,
+                                "recorded_at": "2026-10-16T12:00:00Z",
+                                "action": "update",
+                                "changes": [],
+                            })
+                        })
+                        .collect();
+                    Json(entries)
+                },
+            ),
+        );
+        let client = serve(router).await;
+
+        let mut pages = client.history_pages(Uuid::nil(), 2);
+        let mut sizes = Vec::new();
+        while let Some(page) = pages.next_page().await {
+            sizes.push(page.unwrap().len());
+        }
+        assert_eq!(sizes, [2, 2, 1]);
+
+        // the last page being full takes another request to find out
+        let mut pages = client.history_pages(Uuid::nil(), 5);
+        assert_eq!(pages.next_page().await.unwrap().unwrap().len(), 5);
+        assert!(pages.next_page().await.is_none());
+
+        let history = client.history(Uuid::nil()).await.unwrap();
+        let versions: Vec<_> = history.iter().map(|entry| entry.version).collect();
+        assert_eq!(versions, [1, 2, 3, 4, 5]);
+    }
+}
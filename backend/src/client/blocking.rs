@@ -0,0 +1,191 @@
+//! Blocking variant of the [`Client`](super::Client), for callers which
+//! don't run a tokio runtime of their own.
+//!
+//! Only built with the `blocking` feature. Each client runs the requests of
+//! the async client on a runtime of its own, on the calling thread, so it
+//! mustn't be used from within another runtime.
+
+use std::io;
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::runtime::{Builder, Runtime};
+use uuid::Uuid;
+
+use super::{ClientError, Created, HistoryEntry, ImportReport};
+use crate::import::ImportFormat;
+
+/// Client of the HTTP API of a task server, blocking on its requests.
+///
+/// See [`super::Client`] for the requests it makes.
+#[derive(Debug)]
+pub struct Client {
+    inner: super::Client,
+    runtime: Runtime,
+}
+
+impl Client {
+    /// Make the requests of `inner` blocking.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the runtime can't be started.
+    pub fn new(inner: super::Client) -> io::Result<Self> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// Get the async client whose requests are made.
+    #[must_use]
+    pub fn inner(&self) -> &super::Client {
+        &self.inner
+    }
+
+    /// List the tasks matching the `filter` and `tags` expressions, if
+    /// given, with [`super::Client::list`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails, or the tasks can't be
+    /// deserialized as `T`.
+    pub fn list<T: DeserializeOwned>(
+        &self,
+        filter: Option<&str>,
+        tags: Option<&str>,
+    ) -> Result<Vec<T>, ClientError> {
+        self.runtime.block_on(self.inner.list(filter, tags))
+    }
+
+    /// Get the task with `id`, or `None` if there is no such task.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails, or the task can't be
+    /// deserialized as `T`.
+    pub fn get<T: DeserializeOwned>(&self, id: Uuid) -> Result<Option<T>, ClientError> {
+        self.runtime.block_on(self.inner.get(id))
+    }
+
+    /// Create a task.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClientError::Invalid`] if the server rejects the task, or
+    /// another error if the request fails.
+    pub fn create<T: Serialize + ?Sized>(&self, task: &T) -> Result<Created, ClientError> {
+        self.runtime.block_on(self.inner.create(task))
+    }
+
+    /// Replace the task with `id`, returning whether there was one.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClientError::Invalid`] if the server rejects the task, or
+    /// another error if the request fails.
+    pub fn update<T: Serialize + ?Sized>(&self, id: Uuid, task: &T) -> Result<bool, ClientError> {
+        self.runtime.block_on(self.inner.update(id, task))
+    }
+
+    /// Iterate over the history of the task with `id` a page at a time,
+    /// `per_page` versions to a page, oldest first.
+    #[must_use]
+    pub fn history_pages(&self, id: Uuid, per_page: u32) -> HistoryPages<'_> {
+        HistoryPages {
+            runtime: &self.runtime,
+            pages: self.inner.history_pages(id, per_page),
+        }
+    }
+
+    /// Get the whole history of the task with `id`, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any request fails, including if there is no such
+    /// task.
+    pub fn history(&self, id: Uuid) -> Result<Vec<HistoryEntry>, ClientError> {
+        self.runtime.block_on(self.inner.history(id))
+    }
+
+    /// Create tasks from the JSON `export` of another application, with
+    /// `default_due` as the due date of items which don't have one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails, including if the export is
+    /// malformed.
+    pub fn import(
+        &self,
+        from: ImportFormat,
+        default_due: Option<DateTime<Utc>>,
+        export: Vec<u8>,
+    ) -> Result<ImportReport, ClientError> {
+        self.runtime
+            .block_on(self.inner.import(from, default_due, export))
+    }
+}
+
+/// Iterator over the pages of the history of a task, from
+/// [`Client::history_pages`].
+#[derive(Debug)]
+pub struct HistoryPages<'a> {
+    runtime: &'a Runtime,
+    pages: super::HistoryPages<'a>,
+}
+
+impl Iterator for HistoryPages<'_> {
+    type Item = Result<Vec<HistoryEntry>, ClientError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.pages.next_page())
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use axum::{Json, Router, routing::get};
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    #[test]
+    fn blocking() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let router = Router::new()
+            .route("/task", get(|| async { Json(json!([{"title": "Rota"}])) }))
+            .route(
+                "/task/{task_id}/history",
+                get(|| async {
+                    let entry = json!({
+                        "version": 1,
+                        "recorded_at": "2026-10-16T12:00:00Z",
+                        "action": "create",
+                        "changes": [],
+                    });
+                    Json(json!([entry]))
+                }),
+            );
+        // the server runs on a runtime of its own, as it would elsewhere
+        std::thread::spawn(move || {
+            Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(async {
+                    let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                    axum::serve(listener, router).await
+                })
+        });
+
+        let client = Client::new(super::super::Client::new(&url)).unwrap();
+        let tasks: Vec<Value> = client.list(None, None).unwrap();
+        assert_eq!(tasks, [json!({"title": "Rota"})]);
+        let pages: Vec<_> = client
+            .history_pages(Uuid::nil(), 10)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0][0].action, "create");
+    }
+}
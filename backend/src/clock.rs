@@ -0,0 +1,81 @@
+//! Source of the current time, so that behaviour depending on it can be
+//! tested at chosen times.
+//!
+//! Handlers and schedulers ask a [`Clock`] for the time, and pass it on to
+//! what they call, rather than calling [`Utc::now`] themselves.
+
+use std::{
+    fmt::Debug,
+    sync::{Mutex, PoisonError},
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+/// Source of the current time.
+pub trait Clock: Debug + Send + Sync {
+    /// The current time.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Clock telling the time of the system.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock whose time only changes when it is told to, for tests.
+#[derive(Debug)]
+pub struct TestClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl TestClock {
+    /// Create a clock stopped at `now`.
+    #[must_use]
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    /// Set the time to `now`, which may be earlier than the current time.
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) = now;
+    }
+
+    /// Move the time on by `by`, returning the new time.
+    pub fn advance(&self, by: TimeDelta) -> DateTime<Utc> {
+        let mut now = self.now.lock().unwrap_or_else(PoisonError::into_inner);
+        *now += by;
+        *now
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock() {
+        let start = "2026-10-16T12:00:00Z".parse().unwrap();
+        let clock = TestClock::new(start);
+        assert_eq!(clock.now(), start);
+        assert_eq!(clock.now(), start);
+
+        let later = clock.advance(TimeDelta::hours(2));
+        assert_eq!(later, start + TimeDelta::hours(2));
+        assert_eq!(clock.now(), later);
+        clock.set(start);
+        assert_eq!(clock.now(), start);
+    }
+}
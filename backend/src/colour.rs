@@ -0,0 +1,94 @@
+//! Colours which clients use to render [`TodoTask`](crate::TodoTask)s.
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "postgres")]
+use sqlx::prelude::Type;
+
+/// Names of the colours in the shared palette.
+///
+/// Clients choose how to render each of these, e.g. to suit a dark theme.
+pub const PALETTE: [&str; 9] = [
+    "red", "orange", "yellow", "green", "teal", "blue", "purple", "pink", "grey",
+];
+
+/// Presentation hint for a task: a name from the [`PALETTE`], or an RGB hex
+/// code like `#1e90ff`.
+///
+/// Parsing is case-insensitive, and colours are kept in lowercase so that
+/// equal colours compare equal.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "postgres", derive(Type))]
+#[serde(try_from = "String", into = "String")]
+#[cfg_attr(feature = "postgres", sqlx(transparent))]
+pub struct Colour(String);
+
+impl Colour {
+    /// Get the colour as a palette name or hex code.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Colour {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let colour = s.to_ascii_lowercase();
+        let valid = match colour.strip_prefix('#') {
+            Some(hex) => hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()),
+            None => PALETTE.contains(&colour.as_str()),
+        };
+        if valid {
+            Ok(Self(colour))
+        } else {
+            Err("colour must be a palette name or a hex code like #1e90ff")
+        }
+    }
+}
+
+impl TryFrom<String> for Colour {
+    type Error = &'static str;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Colour> for String {
+    fn from(colour: Colour) -> Self {
+        colour.0
+    }
+}
+
+impl fmt::Display for Colour {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("teal", "teal")]
+    #[case("Grey", "grey")]
+    #[case("#1E90ff", "#1e90ff")]
+    fn parse_colour(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(input.parse::<Colour>().unwrap().as_str(), expected);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("magenta")]
+    #[case("#fff")]
+    #[case("#12345g")]
+    #[case("1e90ff")]
+    fn invalid_colour(#[case] input: &str) {
+        assert!(input.parse::<Colour>().is_err());
+    }
+}
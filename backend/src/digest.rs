@@ -0,0 +1,277 @@
+//! Digests of overdue, due-soon and recently completed tasks, emailed to
+//! users on a schedule.
+//!
+//! Digests are rendered from a plain text template, in which `{{name}}`,
+//! `{{period}}`, `{{overdue}}`, `{{due_soon}}` and `{{completed}}` are
+//! replaced by the recipient's name, `daily` or `weekly`, and lists of the
+//! tasks in each section.
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{TaskRecord, TodoStatus, calendar::WorkCalendar};
+
+/// Template used when a deployment doesn't configure one.
+pub const DEFAULT_TEMPLATE: &str = "Hello {{name}},
+
+Here is your {{period}} digest of tasks.
+
+Overdue:
+{{overdue}}
+
+Due soon:
+{{due_soon}}
+
+Recently completed:
+{{completed}}
+";
+
+/// Placeholders which templates may use.
+const PLACEHOLDERS: [&str; 5] = ["name", "period", "overdue", "due_soon", "completed"];
+
+/// How often digests are sent, which is also how far ahead tasks are due
+/// soon and how far back they were recently completed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DigestPeriod {
+    /// Every day.
+    Daily,
+    /// Every week.
+    Weekly,
+}
+
+impl DigestPeriod {
+    /// Length of the period.
+    #[must_use]
+    pub fn length(self) -> TimeDelta {
+        match self {
+            Self::Daily => TimeDelta::days(1),
+            Self::Weekly => TimeDelta::weeks(1),
+        }
+    }
+
+    /// Name of the period, as used in templates.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+        }
+    }
+}
+
+/// Tasks to tell a user about in a digest.
+#[derive(Clone, Debug, Default)]
+pub struct Digest {
+    /// Open tasks which are past due.
+    pub overdue: Vec<TaskRecord>,
+    /// Open tasks due within the next period.
+    pub due_soon: Vec<TaskRecord>,
+    /// Tasks completed within the last period.
+    pub completed: Vec<TaskRecord>,
+}
+
+impl Digest {
+    /// Sort `tasks` into the sections of a digest sent at `now`, leaving out
+    /// tasks which belong in none.
+    ///
+    /// If only `working_hours` count towards tasks being overdue, tasks are
+    /// overdue once some working time has passed since they were due, as for
+    /// [`crate::TodoTask::past_due`].
+    #[must_use]
+    pub fn new(
+        tasks: Vec<TaskRecord>,
+        period: DigestPeriod,
+        working_hours: Option<&WorkCalendar>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let overdue_before = working_hours.map_or(now, |calendar| calendar.last_working_time(now));
+        let mut digest = Self::default();
+        for record in tasks {
+            let task = &record.task;
+            let open = !matches!(task.status, TodoStatus::Complete | TodoStatus::Cancelled);
+            if task
+                .completed_at()
+                .is_some_and(|at| *at > now - period.length())
+            {
+                digest.completed.push(record);
+            } else if open && *task.due() < overdue_before {
+                digest.overdue.push(record);
+            } else if open && *task.due() <= now + period.length() {
+                digest.due_soon.push(record);
+            }
+        }
+        for section in [&mut digest.overdue, &mut digest.due_soon] {
+            section.sort_by_key(|record| *record.task.due());
+        }
+        digest
+    }
+
+    /// Whether there are no tasks in the digest, so it needn't be sent.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.overdue.is_empty() && self.due_soon.is_empty() && self.completed.is_empty()
+    }
+}
+
+/// Template which digests are rendered from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DigestTemplate(String);
+
+impl Default for DigestTemplate {
+    fn default() -> Self {
+        Self(DEFAULT_TEMPLATE.to_owned())
+    }
+}
+
+impl DigestTemplate {
+    /// Check that `template` only uses known placeholders.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the first unknown or unclosed placeholder.
+    pub fn new(template: String) -> Result<Self, String> {
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find("{{") {
+            rest = &rest[start + 2..];
+            let end = rest
+                .find("}}")
+                .ok_or("template has a placeholder without a closing `}}`")?;
+            let name = rest[..end].trim();
+            if !PLACEHOLDERS.contains(&name) {
+                return Err(format!("template has unknown placeholder `{name}`"));
+            }
+            rest = &rest[end + 2..];
+        }
+        Ok(Self(template))
+    }
+
+    /// Render the `period` digest for the user called `name`.
+    #[must_use]
+    pub fn render(&self, name: &str, period: DigestPeriod, digest: &Digest) -> String {
+        let due = |record: &TaskRecord| {
+            format!(
+                "due {}",
+                record.task.due().format("%a %-d %b %Y, %H:%M UTC")
+            )
+        };
+        let completed = |record: &TaskRecord| {
+            record.task.completed_at().map_or_else(String::new, |at| {
+                format!("completed {}", at.format("%a %-d %b %Y"))
+            })
+        };
+
+        let mut rendered = String::with_capacity(self.0.len());
+        let mut rest = self.0.as_str();
+        while let Some(start) = rest.find("{{") {
+            rendered.push_str(&rest[..start]);
+            rest = &rest[start + 2..];
+            // checked to be closed when the template was created
+            let end = rest.find("}}").unwrap_or(rest.len());
+            match rest[..end].trim() {
+                "name" => rendered.push_str(name),
+                "period" => rendered.push_str(period.name()),
+                "overdue" => rendered.push_str(&list(&digest.overdue, due)),
+                "due_soon" => rendered.push_str(&list(&digest.due_soon, due)),
+                _ => rendered.push_str(&list(&digest.completed, completed)),
+            }
+            rest = rest.get(end + 2..).unwrap_or_default();
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+}
+
+/// List `tasks` one per line, each with its title and `detail`.
+fn list(tasks: &[TaskRecord], detail: impl Fn(&TaskRecord) -> String) -> String {
+    if tasks.is_empty() {
+        return "None.".to_owned();
+    }
+    tasks
+        .iter()
+        .map(|record| format!("- {} ({})", record.task.title(), detail(record)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TodoTask;
+    use rstest::rstest;
+    use uuid::Uuid;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn record(title: &str, status: TodoStatus, due: &str) -> TaskRecord {
+        let mut task = TodoTask::new(
+            title.to_owned(),
+            None,
+            TodoStatus::NotStarted,
+            &at(due),
+            at("2025-06-09T12:00:00Z"),
+        );
+        task.transition(status, at("2025-06-09T12:00:00Z"));
+        TaskRecord {
+            id: Uuid::new_v4(),
+            task,
+        }
+    }
+
+    #[rstest]
+    fn sections() {
+        // a Tuesday
+        let now = at("2025-06-10T08:00:00Z");
+        let tasks = vec![
+            record("later", TodoStatus::NotStarted, "2025-06-16T12:00:00Z"),
+            record("tomorrow", TodoStatus::InProgress, "2025-06-11T07:00:00Z"),
+            record("late", TodoStatus::Blocked, "2025-06-06T16:00:00Z"),
+            record("done", TodoStatus::Complete, "2025-06-06T16:00:00Z"),
+            record("dropped", TodoStatus::Cancelled, "2025-06-06T16:00:00Z"),
+        ];
+        let digest = Digest::new(tasks.clone(), DigestPeriod::Daily, None, now);
+        let titles = |section: &[TaskRecord]| -> Vec<String> {
+            section
+                .iter()
+                .map(|record| record.task.title().to_owned())
+                .collect()
+        };
+        assert_eq!(titles(&digest.overdue), ["late"]);
+        assert_eq!(titles(&digest.due_soon), ["tomorrow"]);
+        assert_eq!(titles(&digest.completed), ["done"]);
+
+        let digest = Digest::new(tasks, DigestPeriod::Weekly, None, now);
+        assert_eq!(titles(&digest.due_soon), ["tomorrow", "later"]);
+        assert!(Digest::new(Vec::new(), DigestPeriod::Daily, None, now).is_empty());
+    }
+
+    #[rstest]
+    fn render() {
+        let digest = Digest {
+            overdue: vec![record(
+                "File report",
+                TodoStatus::NotStarted,
+                "2025-06-06T16:00:00Z",
+            )],
+            ..Digest::default()
+        };
+        let template =
+            DigestTemplate::new("{{ name }}: {{overdue}}\n{{completed}}".to_owned()).unwrap();
+        assert_eq!(
+            template.render("Alice", DigestPeriod::Weekly, &digest),
+            "Alice: - File report (due Fri 6 Jun 2025, 16:00 UTC)\nNone."
+        );
+        assert!(
+            DigestTemplate::default()
+                .render("Alice", DigestPeriod::Daily, &digest)
+                .contains("your daily digest")
+        );
+    }
+
+    #[rstest]
+    #[case("Hello {{user}}")]
+    #[case("Hello {{name")]
+    fn invalid_template(#[case] template: &str) {
+        assert!(DigestTemplate::new(template.to_owned()).is_err());
+    }
+}
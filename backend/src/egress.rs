@@ -0,0 +1,313 @@
+//! Outbound HTTP requests, such as REST hook deliveries, notifications and
+//! syncs with other applications.
+//!
+//! Every request is made through an [`Egress`], so that in locked-down
+//! networks they can all be sent through one proxy and limited to the hosts
+//! the network allows. `https` URLs are reached with TLS from here, trusting
+//! the Mozilla root certificates, through a tunnel if there is a proxy.
+
+use std::{error::Error, fmt, sync::Arc, time::Duration};
+
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::{
+    Request, Response, Uri,
+    body::{Bytes, Incoming},
+    client::conn::http1,
+    header::{self, HeaderValue},
+    http::uri::{Authority, PathAndQuery},
+};
+use hyper_rustls::{ConfigBuilderExt, HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::{
+    client::legacy::{Client, connect::HttpConnector},
+    rt::{TokioExecutor, TokioIo},
+};
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    TlsConnector,
+    rustls::{ClientConfig, crypto::ring, pki_types::ServerName},
+};
+
+/// How long connecting to a server or the proxy may take.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a request may take, from connecting until the whole response has
+/// been received.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest response body which is read.
+const MAX_RESPONSE_BYTES: usize = 1024 * 1024;
+
+/// Error making an outbound request.
+#[derive(Debug)]
+pub enum EgressError {
+    /// The request's host isn't allowed.
+    Refused(String),
+    /// The request couldn't be made.
+    Http(String),
+}
+
+impl fmt::Display for EgressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Refused(host) => write!(f, "requests to {host} are not allowed"),
+            Self::Http(e) => f.write_str(e),
+        }
+    }
+}
+
+impl Error for EgressError {}
+
+/// Client of other servers, reaching them directly or through a proxy.
+#[derive(Clone)]
+pub struct Egress {
+    /// Client of servers reached directly.
+    http: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
+    /// Configuration of TLS connections through the proxy.
+    tls: Arc<ClientConfig>,
+    proxy: Option<Authority>,
+    /// Hosts which may be reached, or `None` for any host.
+    allowed_hosts: Option<Arc<[String]>>,
+}
+
+impl fmt::Debug for Egress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Egress")
+            .field("proxy", &self.proxy)
+            .field("allowed_hosts", &self.allowed_hosts)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Egress {
+    /// Client reaching any host directly.
+    fn default() -> Self {
+        Self::new(None, Vec::new())
+    }
+}
+
+impl Egress {
+    /// Create a client sending requests through `proxy` if given, only to
+    /// `allowed_hosts` unless that is empty.
+    ///
+    /// # Panics
+    ///
+    /// Never; ring supports the default TLS versions.
+    #[must_use]
+    pub fn new(proxy: Option<Authority>, allowed_hosts: Vec<String>) -> Self {
+        let tls = Arc::new(
+            ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .expect("ring supports the default TLS versions")
+                .with_webpki_roots()
+                .with_no_client_auth(),
+        );
+        let mut connector = HttpConnector::new();
+        connector.set_connect_timeout(Some(CONNECT_TIMEOUT));
+        connector.enforce_http(false);
+        let connector = HttpsConnectorBuilder::new()
+            .with_tls_config(ClientConfig::clone(&tls))
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(connector);
+        Self {
+            http: Client::builder(TokioExecutor::new()).build(connector),
+            tls,
+            proxy,
+            allowed_hosts: (!allowed_hosts.is_empty()).then(|| allowed_hosts.into()),
+        }
+    }
+
+    /// Same client, but sending requests through `proxy` instead, if given.
+    #[must_use]
+    pub fn with_proxy(&self, proxy: Option<Authority>) -> Self {
+        match proxy {
+            Some(proxy) => Self {
+                proxy: Some(proxy),
+                ..self.clone()
+            },
+            None => self.clone(),
+        }
+    }
+
+    /// Check that requests may be made to `uri`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if its host isn't allowed, or it isn't an `http` or
+    /// `https` URL.
+    pub fn check(&self, uri: &Uri) -> Result<(), EgressError> {
+        let host = uri.host().unwrap_or_default();
+        if let Some(allowed_hosts) = &self.allowed_hosts {
+            if !allowed_hosts
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(host))
+            {
+                return Err(EgressError::Refused(host.to_owned()));
+            }
+        }
+        match uri.scheme_str() {
+            Some("http" | "https") => Ok(()),
+            _ => Err(EgressError::Http(format!("{uri} is not an http URL"))),
+        }
+    }
+
+    /// Send `request`, whose URI must be absolute, returning the response
+    /// with its whole body.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request isn't allowed or couldn't be made, or
+    /// the response took too long or was too large.
+    pub async fn request(
+        &self,
+        request: Request<Full<Bytes>>,
+    ) -> Result<Response<Bytes>, EgressError> {
+        self.check(request.uri())?;
+        let exchange = async {
+            let response = match &self.proxy {
+                Some(proxy) => send_through(proxy, &self.tls, request).await?,
+                None => self.http.request(request).await?,
+            };
+            let (parts, body) = response.into_parts();
+            let body = Limited::new(body, MAX_RESPONSE_BYTES).collect().await?;
+            Ok::<_, Box<dyn Error + Send + Sync>>(Response::from_parts(parts, body.to_bytes()))
+        };
+        match tokio::time::timeout(REQUEST_TIMEOUT, exchange).await {
+            Ok(result) => result.map_err(|e| EgressError::Http(e.to_string())),
+            Err(_) => Err(EgressError::Http("request timed out".to_owned())),
+        }
+    }
+}
+
+/// Send `request` through `proxy`.
+///
+/// `https` requests are tunnelled with `CONNECT`, so the proxy can't read
+/// them; others are sent to the proxy to forward. Each request is sent on a
+/// connection of its own.
+async fn send_through(
+    proxy: &Authority,
+    tls: &Arc<ClientConfig>,
+    mut request: Request<Full<Bytes>>,
+) -> Result<Response<Incoming>, Box<dyn Error + Send + Sync>> {
+    let uri = request.uri().clone();
+    // proxies and servers are sent the host as for the server
+    if let Some(host) = uri.authority() {
+        let host = HeaderValue::from_str(host.as_str())?;
+        request.headers_mut().entry(header::HOST).or_insert(host);
+    }
+    let address = format!("{}:{}", proxy.host(), proxy.port_u16().unwrap_or(80));
+    let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(address))
+        .await
+        .map_err(|_| "connecting to the proxy timed out")??;
+    let (mut sender, connection) = http1::handshake(TokioIo::new(stream)).await?;
+    if uri.scheme_str() != Some("https") {
+        // forwarding proxies are sent the whole URL
+        tokio::spawn(connection);
+        return Ok(sender.send_request(request).await?);
+    }
+
+    tokio::spawn(connection.with_upgrades());
+    let host = uri.host().ok_or("URL has no host")?;
+    let target = format!("{host}:{}", uri.port_u16().unwrap_or(443));
+    let connect = Request::connect(target.as_str())
+        .header(header::HOST, target.as_str())
+        .body(Full::default())?;
+    let response = sender.send_request(connect).await?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "proxy refused to connect to {target}: {}",
+            response.status()
+        )
+        .into());
+    }
+    let tunnel = hyper::upgrade::on(response).await?;
+    let name = ServerName::try_from(host.trim_matches(['[', ']']).to_owned())?;
+    let stream = TlsConnector::from(Arc::clone(tls))
+        .connect(name, TokioIo::new(tunnel))
+        .await?;
+    let (mut sender, connection) = http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(connection);
+    // the server is only sent the path, as when it is reached directly
+    *request.uri_mut() = uri
+        .path_and_query()
+        .map_or("/", PathAndQuery::as_str)
+        .parse()?;
+    Ok(sender.send_request(request).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[rstest]
+    #[case(None, "http://api.example/", Ok(()))]
+    #[case(None, "https://api.example/", Ok(()))]
+    #[case(Some("egress:3128"), "https://API.example/", Ok(()))]
+    #[case(
+        Some("egress:3128"),
+        "https://internal.example/",
+        Err("requests to internal.example are not allowed")
+    )]
+    #[case(
+        Some("egress:3128"),
+        "ftp://api.example/",
+        Err("ftp://api.example/ is not an http URL")
+    )]
+    fn checks(#[case] proxy: Option<&str>, #[case] uri: &str, #[case] expected: Result<(), &str>) {
+        let egress = Egress::new(
+            proxy.map(|proxy| proxy.parse().unwrap()),
+            vec!["api.example".to_owned()],
+        );
+        assert_eq!(
+            egress
+                .check(&uri.parse().unwrap())
+                .map_err(|e| e.to_string()),
+            expected.map_err(str::to_owned)
+        );
+    }
+
+    #[tokio::test]
+    async fn tunnels_https() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut head = vec![0; 1024];
+            let read = stream.read(&mut head).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 403 Forbidden\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&head[..read]).into_owned()
+        });
+
+        let egress = Egress::new(Some(proxy.parse().unwrap()), Vec::new());
+        let request = Request::post("https://api.example/v1/messages?to=me")
+            .body(Full::from("secret"))
+            .unwrap();
+        let error = egress.request(request).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "proxy refused to connect to api.example:443: 403 Forbidden"
+        );
+        // the proxy is only told where to connect, not what is sent there
+        let head = server.await.unwrap();
+        assert!(
+            head.starts_with("CONNECT api.example:443 HTTP/1.1\r\n"),
+            "{head}"
+        );
+        assert!(!head.contains("secret") && !head.contains("messages"));
+    }
+
+    #[test]
+    fn any_host() {
+        let egress = Egress::default().with_proxy(Some("egress:3128".parse().unwrap()));
+        assert!(
+            egress
+                .check(&"https://anywhere.example/".parse().unwrap())
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! Conversion of incoming emails into tasks.
+//!
+//! The subject of an email becomes the task's title, without any `Re:` or
+//! `Fwd:` prefixes, and its body becomes the description. A line of the body
+//! starting with `due:` sets when the task is due, and is removed from the
+//! description if it's understood; see [`parse_due`] for the formats
+//! accepted.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+
+use crate::{DueWindow, TodoStatus, TodoTask, TodoTaskUnchecked, import::end_of_day};
+
+/// Senders allowed to create tasks by email.
+#[derive(Clone, Debug, Default)]
+pub struct SenderAllowList {
+    /// Lowercased addresses, or domains starting with `@`.
+    entries: Vec<String>,
+}
+
+impl SenderAllowList {
+    /// Create an allow-list of email addresses, and of whole domains given as
+    /// `@example.com`.
+    #[must_use]
+    pub fn new(entries: &[String]) -> Self {
+        Self {
+            entries: entries
+                .iter()
+                .map(|entry| entry.trim().to_lowercase())
+                .filter(|entry| !entry.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Check whether `sender` may create tasks.
+    ///
+    /// `sender` may be a bare address or include a display name, as in
+    /// `Alice <alice@example.com>`.
+    ///
+    /// ```
+    /// use dts_developer_challenge::email::SenderAllowList;
+    ///
+    /// let allowed = SenderAllowList::new(&["boss@example.com".into(), "@team.example".into()]);
+    /// assert!(allowed.allows("The Boss <Boss@example.com>"));
+    /// assert!(allowed.allows("anyone@team.example"));
+    /// assert!(!allowed.allows("anyone@example.com"));
+    /// ```
+    #[must_use]
+    pub fn allows(&self, sender: &str) -> bool {
+        let address = match (sender.rfind('<'), sender.rfind('>')) {
+            (Some(start), Some(end)) if start < end => &sender[start + 1..end],
+            _ => sender,
+        };
+        let address = address.trim().to_lowercase();
+        let Some((_, domain)) = address.rsplit_once('@') else {
+            return false;
+        };
+        self.entries.iter().any(|entry| {
+            entry
+                .strip_prefix('@')
+                .map_or(*entry == address, |allowed| allowed == domain)
+        })
+    }
+}
+
+/// Convert an email into a task.
+///
+/// Tasks without a `due:` line, or whose `due:` line isn't understood, are
+/// due `default_due` after the email was `received`, at the end of that day.
+///
+/// # Errors
+///
+/// Returns an error if the subject is blank, or the task is due outside
+/// `window` around when the email was received.
+pub fn to_task(
+    subject: &str,
+    body: &str,
+    received: DateTime<Utc>,
+    default_due: TimeDelta,
+    window: DueWindow,
+) -> Result<TodoTask, &'static str> {
+    let mut due = None;
+    let mut description = Vec::new();
+    for line in body.lines() {
+        let directive = line
+            .trim()
+            .get(..4)
+            .filter(|start| start.eq_ignore_ascii_case("due:"));
+        match directive.and_then(|_| parse_due(&line.trim()[4..], received)) {
+            Some(parsed) if due.is_none() => due = Some(parsed),
+            _ => description.push(line),
+        }
+    }
+    let description = description.join("\n").trim().to_string();
+
+    TodoTaskUnchecked::new(
+        strip_reply_prefixes(subject).to_string(),
+        Some(description).filter(|d| !d.is_empty()),
+        TodoStatus::NotStarted,
+        due.unwrap_or_else(|| end_of_day((received + default_due).date_naive())),
+        Vec::new(),
+    )
+    .check(window, received)
+}
+
+/// Parse the value of a `due:` directive in an email received at `received`.
+///
+/// Accepts `today`, `tomorrow`, a date like `2025-06-30` (due at the end of
+/// the day), a date & time in UTC like `2025-06-30 14:00`, or an RFC 3339
+/// date & time.
+#[must_use]
+pub fn parse_due(value: &str, received: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    match value.to_ascii_lowercase().as_str() {
+        "today" => return Some(end_of_day(received.date_naive())),
+        "tomorrow" => return Some(end_of_day(received.date_naive() + TimeDelta::days(1))),
+        _ => {}
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|due| due.to_utc())
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M")
+                .ok()
+                .map(|due| due.and_utc())
+        })
+        .or_else(|| value.parse::<NaiveDate>().ok().map(end_of_day))
+}
+
+/// Remove any number of `Re:`, `Fw:` and `Fwd:` prefixes from a subject.
+fn strip_reply_prefixes(subject: &str) -> &str {
+    let mut subject = subject.trim();
+    loop {
+        let prefix = ["re:", "fw:", "fwd:"].into_iter().find(|prefix| {
+            subject
+                .get(..prefix.len())
+                .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+        });
+        match prefix {
+            Some(prefix) => subject = subject[prefix.len()..].trim_start(),
+            None => return subject,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn received() -> DateTime<Utc> {
+        "2025-06-02T09:15:00Z".parse().unwrap()
+    }
+
+    fn week() -> TimeDelta {
+        TimeDelta::days(7)
+    }
+
+    fn window() -> DueWindow {
+        DueWindow::default()
+    }
+
+    #[test]
+    fn email_with_due_line() {
+        let body = "Please update the rota.\r\nDue: 2025-06-30\r\n\r\nThanks";
+        let task = to_task("RE: Fwd: Rota", body, received(), week(), window()).unwrap();
+        assert_eq!(task.title(), "Rota");
+        assert_eq!(
+            task.description(),
+            Some("Please update the rota.\n\nThanks")
+        );
+        assert_eq!(task.due().to_rfc3339(), "2025-06-30T23:59:59+00:00");
+    }
+
+    #[test]
+    fn email_without_due_line() {
+        let task = to_task("Call back", "due: whenever", received(), week(), window()).unwrap();
+        assert_eq!(task.description(), Some("due: whenever"));
+        assert_eq!(task.due().to_rfc3339(), "2025-06-09T23:59:59+00:00");
+
+        assert!(to_task(" Re: ", "", received(), week(), window()).is_err());
+    }
+
+    #[test]
+    fn email_due_outside_window() {
+        let body = "due: 2125-06-30";
+        assert_eq!(
+            to_task("Rota", body, received(), week(), window()).unwrap_err(),
+            "due date is too far in the past or future"
+        );
+    }
+
+    #[rstest]
+    #[case("tomorrow", "2025-06-03T23:59:59+00:00")]
+    #[case(" 2025-07-01 14:30", "2025-07-01T14:30:00+00:00")]
+    #[case("2025-07-01T14:30:00+01:00", "2025-07-01T13:30:00+00:00")]
+    fn due_directive(#[case] value: &str, #[case] expected: &str) {
+        assert_eq!(parse_due(value, received()).unwrap().to_rfc3339(), expected);
+    }
+}
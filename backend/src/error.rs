@@ -0,0 +1,219 @@
+//! Errors from storing and querying tasks, classified by what went wrong
+//! rather than how the database reported it.
+
+use std::fmt;
+
+#[cfg(feature = "server")]
+use axum::response::{IntoResponse, Response};
+use hyper::StatusCode;
+#[cfg(feature = "server")]
+use tracing::error;
+
+use crate::breaker::is_outage;
+
+/// Error from a [`TaskStore`](crate::store::TaskStore) operation.
+///
+/// Each kind keeps the database error it was classified from, for logging.
+#[derive(Debug)]
+pub enum StoreError {
+    /// Something the operation refers to doesn't exist, such as the other
+    /// task of a link.
+    NotFound(sqlx::Error),
+    /// The operation conflicts with the current state, such as a task ID
+    /// which is already taken.
+    Conflict(sqlx::Error),
+    /// The database refused a value as invalid.
+    Validation(sqlx::Error),
+    /// The database couldn't be reached.
+    Unavailable(sqlx::Error),
+    /// Any other failure.
+    Other(sqlx::Error),
+}
+
+impl StoreError {
+    /// The database error this was classified from.
+    #[must_use]
+    pub fn source_error(&self) -> &sqlx::Error {
+        match self {
+            Self::NotFound(e)
+            | Self::Conflict(e)
+            | Self::Validation(e)
+            | Self::Unavailable(e)
+            | Self::Other(e) => e,
+        }
+    }
+
+    /// Whether the operation lost a race with a concurrent transaction, by a
+    /// serialisation failure or a deadlock, so may succeed if retried.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Conflict(sqlx::Error::Database(e)) => {
+                matches!(e.code().as_deref(), Some("40001" | "40P01"))
+            }
+            _ => false,
+        }
+    }
+
+    /// Status code of the response to a request failing with this error.
+    #[must_use]
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Self::NotFound(_) => "not found",
+            Self::Conflict(_) => "conflict",
+            Self::Validation(_) => "invalid value",
+            Self::Unavailable(_) => "database unavailable",
+            Self::Other(_) => "database error",
+        };
+        write!(f, "{kind}: {}", self.source_error())
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source_error())
+    }
+}
+
+impl From<sqlx::Error> for StoreError {
+    fn from(e: sqlx::Error) -> Self {
+        if is_outage(&e) {
+            return Self::Unavailable(e);
+        }
+        let code = match &e {
+            sqlx::Error::RowNotFound => return Self::NotFound(e),
+            sqlx::Error::Database(db) => db.code().unwrap_or_default().into_owned(),
+            _ => return Self::Other(e),
+        };
+        match code.as_str() {
+            // foreign key violation
+            "23503" => Self::NotFound(e),
+            // unique or exclusion violation, or a transaction which lost a
+            // race with another
+            "23505" | "23P01" | "40001" | "40P01" => Self::Conflict(e),
+            // not-null or check violation, or data exceptions such as values
+            // out of range
+            "23502" | "23514" => Self::Validation(e),
+            code if code.starts_with("22") => Self::Validation(e),
+            _ => Self::Other(e),
+        }
+    }
+}
+
+/// Responds with the status of the error, and a description of it unless
+/// it is unexpected.
+///
+/// Unexpected errors are logged, within the span of the request.
+#[cfg(feature = "server")]
+impl IntoResponse for StoreError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        match self {
+            Self::NotFound(_) => status.into_response(),
+            Self::Conflict(_) => (status, "conflicts with the current state").into_response(),
+            Self::Validation(_) => (status, "value refused by the database").into_response(),
+            Self::Unavailable(e) => {
+                error!(error = format!("{e}"), "database unavailable");
+                (status, "database unavailable, try again later").into_response()
+            }
+            Self::Other(e) => {
+                error!(error = format!("{e}"), "database error");
+                status.into_response()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{borrow::Cow, error::Error};
+
+    use sqlx::error::{DatabaseError, ErrorKind};
+
+    use super::*;
+
+    /// Error reported by the database with an SQLSTATE code.
+    #[derive(Debug)]
+    struct CodedError(&'static str);
+
+    impl fmt::Display for CodedError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "error {}", self.0)
+        }
+    }
+
+    impl Error for CodedError {}
+
+    impl DatabaseError for CodedError {
+        fn message(&self) -> &str {
+            self.0
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(Cow::Borrowed(self.0))
+        }
+
+        fn as_error(&self) -> &(dyn Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn Error + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::Other
+        }
+    }
+
+    /// Error reported by the database with the SQLSTATE `code`.
+    pub(crate) fn database_error(code: &'static str) -> StoreError {
+        StoreError::from(sqlx::Error::Database(Box::new(CodedError(code))))
+    }
+
+    #[test]
+    fn retryable_errors() {
+        // serialisation failure and deadlock
+        assert!(database_error("40001").is_retryable());
+        assert!(database_error("40P01").is_retryable());
+        // unique violation
+        assert!(!database_error("23505").is_retryable());
+        assert!(!StoreError::from(sqlx::Error::PoolTimedOut).is_retryable());
+    }
+
+    #[test]
+    fn classifies_database_errors() {
+        assert!(matches!(
+            StoreError::from(sqlx::Error::PoolTimedOut),
+            StoreError::Unavailable(_)
+        ));
+        assert!(matches!(
+            StoreError::from(sqlx::Error::RowNotFound),
+            StoreError::NotFound(_)
+        ));
+        assert!(matches!(
+            StoreError::from(sqlx::Error::ColumnNotFound("title".to_owned())),
+            StoreError::Other(_)
+        ));
+        assert_eq!(
+            StoreError::from(sqlx::Error::PoolTimedOut).status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+}
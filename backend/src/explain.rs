@@ -0,0 +1,139 @@
+//! Inspection of the plans the database chooses for the store's queries, to
+//! catch queries needing an index as columns and filters accumulate.
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Plan chosen for one of the store's queries, as run with `EXPLAIN
+/// (ANALYZE)`.
+#[derive(Clone, Debug, Serialize)]
+pub struct QueryPlan {
+    /// Name of the store operation the query is run by.
+    pub name: &'static str,
+    /// Time the query took to run, in milliseconds.
+    pub execution_ms: f64,
+    /// Problems found in the plan.
+    pub warnings: Vec<String>,
+    /// The plan, as given by `EXPLAIN (FORMAT JSON)`.
+    pub plan: Value,
+}
+
+impl QueryPlan {
+    /// Inspect `explained`, the output of `EXPLAIN (ANALYZE, FORMAT JSON)`
+    /// for the query run by `name`, warning of scans reading at least
+    /// `min_rows` rows.
+    #[must_use]
+    pub fn new(name: &'static str, mut explained: Value, min_rows: f64) -> Self {
+        let mut explained = explained.get_mut(0).map(Value::take).unwrap_or_default();
+        let plan = explained
+            .get_mut("Plan")
+            .map(Value::take)
+            .unwrap_or_default();
+        let mut warnings = Vec::new();
+        inspect(&plan, min_rows, &mut warnings);
+        // subplans may appear more than once
+        warnings.dedup();
+        Self {
+            name,
+            execution_ms: number(&explained, "Execution Time"),
+            warnings,
+            plan,
+        }
+    }
+}
+
+/// Add the problems found in the plan `node` and its children to
+/// `warnings`.
+fn inspect(node: &Value, min_rows: f64, warnings: &mut Vec<String>) {
+    let kind = node.get("Node Type").and_then(Value::as_str);
+    let loops = number(node, "Actual Loops").max(1.0);
+    let kept = number(node, "Actual Rows") * loops;
+    let removed = number(node, "Rows Removed by Filter") * loops;
+
+    match kind {
+        // filtering a whole table is what indexes are for
+        Some("Seq Scan") if kept + removed >= min_rows && node.get("Filter").is_some() => {
+            warnings.push(format!(
+                "sequential scan of {} read {} rows to keep {}, filtering on {}; consider an index",
+                node.get("Relation Name")
+                    .and_then(Value::as_str)
+                    .unwrap_or("a table"),
+                kept + removed,
+                kept,
+                node.get("Filter").and_then(Value::as_str).unwrap_or("?"),
+            ));
+        }
+        Some("Sort")
+            if node
+                .get("Sort Space Type")
+                .and_then(Value::as_str)
+                .is_some_and(|space| space == "Disk") =>
+        {
+            warnings.push(format!(
+                "sort by {} spilled to disk",
+                node.get("Sort Key").unwrap_or(&Value::Null)
+            ));
+        }
+        _ => {}
+    }
+
+    for child in node
+        .get("Plans")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        inspect(child, min_rows, warnings);
+    }
+}
+
+/// The number in the `key` field of `node`, or 0 if there isn't one.
+fn number(node: &Value, key: &str) -> f64 {
+    node.get(key).and_then(Value::as_f64).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn warns_of_large_filtered_scans() {
+        let explained = json!([{
+            "Plan": {
+                "Node Type": "Sort",
+                "Sort Key": ["due"],
+                "Sort Space Type": "Memory",
+                "Plans": [
+                    {
+                        "Node Type": "Seq Scan",
+                        "Relation Name": "task_listing",
+                        "Filter": "(status = 'in_progress'::text)",
+                        "Actual Rows": 10,
+                        "Actual Loops": 1,
+                        "Rows Removed by Filter": 49990
+                    },
+                    {
+                        "Node Type": "Seq Scan",
+                        "Relation Name": "task_pins",
+                        "Filter": "(owner = 'a'::text)",
+                        "Actual Rows": 1,
+                        "Actual Loops": 10,
+                        "Rows Removed by Filter": 3
+                    }
+                ]
+            },
+            "Execution Time": 12.5
+        }]);
+
+        let plan = QueryPlan::new("list", explained, 10_000.0);
+        assert!((plan.execution_ms - 12.5).abs() < f64::EPSILON);
+        assert_eq!(
+            plan.warnings,
+            [
+                "sequential scan of task_listing read 50000 rows to keep 10, filtering on \
+                (status = 'in_progress'::text); consider an index"
+            ]
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Columnar export of tasks as Apache Parquet, for loading into analytics
+//! warehouses.
+//!
+//! Only available with the `parquet` feature.
+
+use std::sync::Arc;
+
+use arrow_array::{
+    ArrayRef, Int64Array, RecordBatch, StringArray, TimestampMicrosecondArray, UInt8Array,
+    builder::{ListBuilder, StringBuilder},
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use parquet::{
+    arrow::ArrowWriter, basic::Compression, errors::ParquetError,
+    file::properties::WriterProperties,
+};
+
+use crate::{Colour, TodoStatus, store::ExportedTask};
+
+/// Get the Arrow schema of exported tasks.
+///
+/// Statuses use the database's names, e.g. `in_progress`, and estimates are
+/// in whole seconds.
+#[must_use]
+pub fn schema() -> Schema {
+    let text = |name, nullable| Field::new(name, DataType::Utf8, nullable);
+    Schema::new(vec![
+        text("id", false),
+        text("owner", true),
+        text("assignee", true),
+        text("title", false),
+        text("description", true),
+        text("status", false),
+        Field::new(
+            "due",
+            DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            false,
+        ),
+        Field::new(
+            "tags",
+            DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+            false,
+        ),
+        Field::new("estimate_seconds", DataType::Int64, true),
+        Field::new("progress", DataType::UInt8, true),
+        text("colour", true),
+        text("title_cy", true),
+        text("description_cy", true),
+        text("status_reason", true),
+    ])
+}
+
+/// Parquet file of tasks, written incrementally.
+///
+/// Each call to [`Self::write`] encodes one row group and returns the bytes of
+/// the file which are ready, so large exports can be streamed without
+/// holding every task in memory.
+pub struct ParquetExport {
+    /// Writer buffering the bytes of the file not yet returned.
+    writer: ArrowWriter<Vec<u8>>,
+}
+
+impl ParquetExport {
+    /// Start a Snappy-compressed Parquet file of tasks.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file header can't be written.
+    pub fn new() -> Result<Self, ParquetError> {
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let writer = ArrowWriter::try_new(Vec::new(), Arc::new(schema()), Some(properties))?;
+        Ok(Self { writer })
+    }
+
+    /// Write `tasks` as a row group, returning the bytes of the file which
+    /// are ready to be sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tasks can't be encoded.
+    pub fn write(&mut self, tasks: &[ExportedTask]) -> Result<Vec<u8>, ParquetError> {
+        self.writer.write(&batch(tasks)?)?;
+        self.writer.flush()?;
+        Ok(std::mem::take(self.writer.inner_mut()))
+    }
+
+    /// Finish the file, returning its remaining bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file footer can't be written.
+    pub fn finish(self) -> Result<Vec<u8>, ParquetError> {
+        self.writer.into_inner()
+    }
+}
+
+/// Convert tasks into a record batch with the export [`schema`].
+fn batch(tasks: &[ExportedTask]) -> Result<RecordBatch, ParquetError> {
+    let text = |f: fn(&ExportedTask) -> Option<&str>| -> ArrayRef {
+        Arc::new(tasks.iter().map(f).collect::<StringArray>())
+    };
+    let mut tags = ListBuilder::new(StringBuilder::new());
+    for exported in tasks {
+        tags.append_value(exported.task.task.tags().iter().map(Some));
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(
+            tasks
+                .iter()
+                .map(|exported| Some(exported.task.id.to_string()))
+                .collect::<StringArray>(),
+        ),
+        text(|exported| exported.owner.as_deref()),
+        text(|exported| exported.assignee.as_deref()),
+        text(|exported| Some(exported.task.task.title())),
+        text(|exported| exported.task.task.description()),
+        text(|exported| Some(status_name(&exported.task.task.status))),
+        Arc::new(
+            tasks
+                .iter()
+                .map(|exported| Some(exported.task.task.due().timestamp_micros()))
+                .collect::<TimestampMicrosecondArray>()
+                .with_timezone("UTC"),
+        ),
+        Arc::new(tags.finish()),
+        Arc::new(
+            tasks
+                .iter()
+                .map(|exported| {
+                    exported
+                        .task
+                        .task
+                        .estimate()
+                        .map(|estimate| estimate.num_seconds())
+                })
+                .collect::<Int64Array>(),
+        ),
+        Arc::new(
+            tasks
+                .iter()
+                .map(|exported| exported.task.task.progress())
+                .collect::<UInt8Array>(),
+        ),
+        text(|exported| exported.task.task.colour().map(Colour::as_str)),
+        text(|exported| exported.task.task.title_cy()),
+        text(|exported| exported.task.task.description_cy()),
+        text(|exported| exported.task.task.status_reason()),
+    ];
+    Ok(RecordBatch::try_new(Arc::new(schema()), columns)?)
+}
+
+/// Get the database's name for a status, or the name of a custom status.
+fn status_name(status: &TodoStatus) -> &str {
+    match status {
+        TodoStatus::NotStarted => "not_started",
+        TodoStatus::InProgress => "in_progress",
+        TodoStatus::Complete => "complete",
+        TodoStatus::Cancelled => "cancelled",
+        TodoStatus::Blocked => "blocked",
+        TodoStatus::Custom(name) => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TaskRecord, TodoTask, TodoTaskUnchecked};
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use uuid::Uuid;
+
+    fn exported(title: &str) -> ExportedTask {
+        let task: TodoTask = TodoTaskUnchecked::new(
+            title.into(),
+            None,
+            TodoStatus::InProgress,
+            "2025-06-30T12:00:00Z".parse().unwrap(),
+            vec!["analytics".into()],
+        )
+        .try_into()
+        .unwrap();
+        ExportedTask {
+            task: TaskRecord {
+                id: Uuid::new_v4(),
+                task,
+            },
+            owner: Some("alice".into()),
+            assignee: None,
+        }
+    }
+
+    #[test]
+    fn streamed_file() {
+        let mut export = ParquetExport::new().unwrap();
+        let mut file = export.write(&[exported("One"), exported("Two")]).unwrap();
+        file.extend(export.write(&[exported("Three")]).unwrap());
+        file.extend(export.finish().unwrap());
+
+        let reader = SerializedFileReader::new(prost::bytes::Bytes::from(file)).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.num_row_groups(), 2);
+        assert_eq!(metadata.file_metadata().num_rows(), 3);
+        assert_eq!(
+            metadata.file_metadata().schema_descr().num_columns(),
+            schema().fields().len()
+        );
+    }
+}
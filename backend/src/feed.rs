@@ -0,0 +1,190 @@
+//! Atom feed of recent activity on tasks, for feed readers and intranet
+//! portals.
+
+use std::fmt::Write;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+#[cfg(feature = "postgres")]
+use sqlx::{FromRow, prelude::Type};
+
+use crate::{TaskRecord, i18n::Locale};
+
+/// What happened to a task, as listed by
+/// [`TaskStore::activity`](crate::store::TaskStore::activity).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "postgres", derive(Type))]
+#[cfg_attr(
+    feature = "postgres",
+    sqlx(type_name = "text", rename_all = "lowercase")
+)]
+pub enum ActivityKind {
+    /// The task was created.
+    Created,
+    /// The task was marked complete.
+    Completed,
+}
+
+impl ActivityKind {
+    /// Get a human-readable label for the activity in `locale`.
+    #[must_use]
+    pub fn label(self, locale: Locale) -> &'static str {
+        locale.translate(match self {
+            Self::Created => "Created",
+            Self::Completed => "Completed",
+        })
+    }
+}
+
+/// Creation or completion of a task.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct Activity {
+    /// What happened.
+    pub kind: ActivityKind,
+    /// Version of the task recorded by the activity.
+    pub version: i32,
+    /// Date & time at which it happened.
+    pub recorded_at: DateTime<Utc>,
+    /// State of the task as of the activity.
+    #[cfg_attr(feature = "postgres", sqlx(flatten))]
+    pub task: TaskRecord,
+}
+
+/// Render `activity`, newest first, as an Atom feed.
+///
+/// `base_url` is the public URL of the application, used to link to the
+/// tasks and to identify the feed and its entries. Each entry is identified
+/// by the URL of the version of the task it records.
+/// The feed was last updated with its newest activity, or at `now` if it has
+/// none.
+#[must_use]
+pub fn to_atom(
+    activity: &[Activity],
+    base_url: &str,
+    author: &str,
+    locale: Locale,
+    now: DateTime<Utc>,
+) -> String {
+    let base_url = base_url.trim_end_matches('/');
+    let updated = activity
+        .iter()
+        .map(|activity| activity.recorded_at)
+        .max()
+        .unwrap_or(now);
+
+    let mut feed = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+        <feed xmlns=\"http://www.w3.org/2005/Atom\" xml:lang=\"{}\">\
+        <id>{base}/feed.atom</id>\
+        <title>{}</title>\
+        <updated>{}</updated>\
+        <link rel=\"self\" href=\"{base}/feed.atom\"/>\
+        <author><name>{}</name></author>",
+        locale.tag(),
+        locale.translate("Task activity"),
+        timestamp(updated),
+        escape(author),
+        base = escape(base_url),
+    );
+    for activity in activity {
+        let TaskRecord { id, task } = &activity.task;
+        let task = task.clone().localised(locale);
+        let _ = write!(
+            feed,
+            "<entry>\
+            <id>{base}/task/{id}/history/{}</id>\
+            <title>{}: {}</title>\
+            <updated>{}</updated>\
+            <link rel=\"alternate\" href=\"{base}/task/{id}\"/>",
+            activity.version,
+            activity.kind.label(locale),
+            escape(task.title()),
+            timestamp(activity.recorded_at),
+            base = escape(base_url),
+        );
+        for tag in task.tags() {
+            let _ = write!(feed, "<category term=\"{}\"/>", escape(tag));
+        }
+        if let Some(description) = task.description() {
+            let _ = write!(
+                feed,
+                "<content type=\"text\">{}</content>",
+                escape(description)
+            );
+        }
+        feed.push_str("</entry>");
+    }
+    feed.push_str("</feed>");
+    feed
+}
+
+/// Format a date & time for Atom.
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Escape text for use in XML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TodoStatus, TodoTask, TodoTaskUnchecked};
+    use uuid::Uuid;
+
+    #[test]
+    fn feed() {
+        let mut task: TodoTask = TodoTaskUnchecked::new(
+            "Fish & chips".into(),
+            Some("Order <lots>".into()),
+            TodoStatus::Complete,
+            "2025-06-30T12:00:00Z".parse().unwrap(),
+            vec!["lunch".into()],
+        )
+        .try_into()
+        .unwrap();
+        task.set_welsh(Some("Pysgod a sglodion".into()), None);
+        let id = Uuid::nil();
+        let activity = [Activity {
+            kind: ActivityKind::Completed,
+            version: 3,
+            recorded_at: "2025-06-02T09:15:00.25Z".parse().unwrap(),
+            task: TaskRecord { id, task },
+        }];
+
+        let now = "2026-10-16T12:00:00Z".parse().unwrap();
+        let feed = to_atom(
+            &activity,
+            "https://tasks.example/",
+            "alice",
+            Locale::English,
+            now,
+        );
+        assert!(feed.contains("<id>https://tasks.example/feed.atom</id>"));
+        assert!(feed.contains("<updated>2025-06-02T09:15:00Z</updated>"));
+        assert!(feed.contains(&format!(
+            "<entry><id>https://tasks.example/task/{id}/history/3</id>\
+            <title>Completed: Fish &amp; chips</title>"
+        )));
+        assert!(feed.contains("<category term=\"lunch\"/>"));
+        assert!(feed.contains("<content type=\"text\">Order &lt;lots&gt;</content>"));
+        roxmltree::Document::parse(&feed).unwrap();
+
+        let feed = to_atom(
+            &activity,
+            "https://tasks.example",
+            "alice",
+            Locale::Welsh,
+            now,
+        );
+        assert!(feed.contains("<title>Cwblhawyd: Pysgod a sglodion</title>"));
+
+        let feed = to_atom(&[], "https://tasks.example", "alice", Locale::English, now);
+        assert!(feed.contains("<updated>2026-10-16T12:00:00Z</updated>"));
+    }
+}
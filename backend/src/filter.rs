@@ -0,0 +1,663 @@
+//! Compact filter expressions over [`TodoTask`](crate::TodoTask)s.
+//!
+//! Expressions are made of conditions on a task field, combined with `AND`,
+//! `OR`, `NOT` and parentheses:
+//!
+//! ```
+//! use dts_developer_challenge::FilterExpr;
+//!
+//! let filter: FilterExpr = "status:InProgress AND (title:hearing OR due<2025-07-01)"
+//!     .parse()
+//!     .unwrap();
+//! ```
+//!
+//! The supported conditions are:
+//!
+//! | Condition              | Meaning                                                 |
+//! |------------------------|---------------------------------------------------------|
+//! | `status:<status>`      | task has the given status                               |
+//! | `title:<text>`         | title contains the text, ignoring case                  |
+//! | `description:<text>`   | description contains the text, ignoring case            |
+//! | `tag:<tag>`            | task has the given tag                                  |
+//! | `due<op><date>`        | due date compares with `<op>` (`:`, `<`, `<=`, `>`, `>=`) |
+//! | `completed<op><date>`  | task is complete, and was completed as compared by `<op>` |
+//!
+//! Dates may be given as `YYYY-MM-DD` (a whole day in UTC) or as RFC 3339
+//! timestamps.
+//! Text containing whitespace or parentheses may be wrapped in double quotes,
+//! with `\"` and `\\` as escapes.
+//!
+//! [`FilterExpr::parse_tags`] accepts the same operators over bare tag names,
+//! e.g. `home AND NOT shopping`.
+
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+#[cfg(feature = "postgres")]
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::TodoStatus;
+
+/// Parsed filter expression.
+///
+/// Parse one with [`str::parse`], then compile it to SQL with
+/// [`FilterExpr::push_sql`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FilterExpr {
+    /// Both sub-expressions must match.
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    /// Either sub-expression must match.
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+    /// The sub-expression must not match.
+    Not(Box<FilterExpr>),
+    /// A single condition on a task field.
+    Condition(Condition),
+}
+
+/// Condition on a single field of a task.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    /// Task has exactly this status.
+    Status(TodoStatus),
+    /// Task title contains this text, ignoring case.
+    Title(String),
+    /// Task description contains this text, ignoring case.
+    Description(String),
+    /// Task has this tag.
+    Tag(String),
+    /// Task due date compares to this instant.
+    Due(Comparison, DateTime<Utc>),
+    /// Task is complete, and its completion time compares to this instant.
+    Completed(Comparison, DateTime<Utc>),
+}
+
+/// Comparison operator between a task field and a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    /// `:`
+    Eq,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+impl Comparison {
+    #[cfg(feature = "postgres")]
+    fn sql(self) -> &'static str {
+        match self {
+            Self::Eq => " = ",
+            Self::Lt => " < ",
+            Self::Le => " <= ",
+            Self::Gt => " > ",
+            Self::Ge => " >= ",
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Eq => ":",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+        })
+    }
+}
+
+/// Syntax error in a filter expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FilterError {
+    /// Column at which the error was found, counting characters from 1.
+    pub column: usize,
+    /// Description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "syntax error at column {}: {}",
+            self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+impl FromStr for FilterExpr {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Parser::new(s, false).parse()
+    }
+}
+
+impl FilterExpr {
+    /// Parse a tag expression, where every condition is a bare tag name.
+    ///
+    /// For example, `home AND NOT shopping` is equivalent to
+    /// `tag:home AND NOT tag:shopping`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`FilterError`] if `s` is not a valid tag expression.
+    pub fn parse_tags(s: &str) -> Result<Self, FilterError> {
+        Parser::new(s, true).parse()
+    }
+
+    /// Append this expression to `builder` as a SQL boolean expression.
+    ///
+    /// All values are passed as bind parameters, never interpolated into the
+    /// SQL text.
+    /// The expression refers to the columns of the `tasks` table.
+    #[cfg(feature = "postgres")]
+    pub fn push_sql(&self, builder: &mut QueryBuilder<'_, Postgres>) {
+        match self {
+            Self::And(left, right) => {
+                builder.push("(");
+                left.push_sql(builder);
+                builder.push(" AND ");
+                right.push_sql(builder);
+                builder.push(")");
+            }
+            Self::Or(left, right) => {
+                builder.push("(");
+                left.push_sql(builder);
+                builder.push(" OR ");
+                right.push_sql(builder);
+                builder.push(")");
+            }
+            Self::Not(inner) => {
+                builder.push("NOT ");
+                inner.push_sql(builder);
+            }
+            Self::Condition(condition) => condition.push_sql(builder),
+        }
+    }
+
+    fn and(left: Self, right: Self) -> Self {
+        Self::And(Box::new(left), Box::new(right))
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl Condition {
+    fn push_sql(&self, builder: &mut QueryBuilder<'_, Postgres>) {
+        match self {
+            Self::Status(TodoStatus::Custom(name)) => {
+                builder.push("custom_status = ").push_bind(name.clone());
+            }
+            Self::Status(status) => {
+                builder.push("status = ").push_bind(status.clone());
+            }
+            Self::Title(text) => {
+                builder.push("title ILIKE ").push_bind(like_pattern(text));
+            }
+            Self::Description(text) => {
+                // treat a missing description as empty so that `NOT` matches it
+                builder
+                    .push("coalesce(description, '') ILIKE ")
+                    .push_bind(like_pattern(text));
+            }
+            Self::Due(comparison, due) => {
+                builder.push("due").push(comparison.sql()).push_bind(*due);
+            }
+            Self::Completed(comparison, completed) => {
+                // incomplete tasks have no completion time, so never match
+                builder
+                    .push("completed_at")
+                    .push(comparison.sql())
+                    .push_bind(*completed);
+            }
+            Self::Tag(tag) => {
+                // containment rather than `= ANY` so the GIN index can be used
+                builder.push("tags @> ").push_bind(vec![tag.clone()]);
+            }
+        }
+    }
+}
+
+/// Build an `ILIKE` pattern matching any text containing `text`.
+#[cfg(feature = "postgres")]
+fn like_pattern(text: &str) -> String {
+    let mut pattern = String::with_capacity(text.len() + 2);
+    pattern.push('%');
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Recursive-descent parser over the raw expression text.
+///
+/// Grammar:
+///
+/// ```text
+/// or        := and ("OR" and)*
+/// and       := unary ("AND" unary)*
+/// unary     := "NOT" unary | primary
+/// primary   := "(" or ")" | condition
+/// condition := field operator value
+/// ```
+///
+/// When parsing tag expressions, `condition := value` instead.
+struct Parser<'a> {
+    input: &'a str,
+    /// Current byte offset into `input`.
+    pos: usize,
+    /// Whether conditions are bare tag names.
+    tags_only: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str, tags_only: bool) -> Self {
+        Self {
+            input,
+            pos: 0,
+            tags_only,
+        }
+    }
+
+    fn parse(mut self) -> Result<FilterExpr, FilterError> {
+        let expr = self.parse_or()?;
+        self.skip_whitespace();
+        if self.pos < self.input.len() {
+            return Err(self.error("expected `AND`, `OR` or end of expression"));
+        }
+        Ok(expr)
+    }
+
+    fn rest(&self) -> &str {
+        &self.input[self.pos..]
+    }
+
+    fn error_at(&self, pos: usize, message: impl Into<String>) -> FilterError {
+        FilterError {
+            column: self.input[..pos].chars().count() + 1,
+            message: message.into(),
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> FilterError {
+        self.error_at(self.pos, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consume `keyword` if it is the next word, ignoring case.
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let Some(candidate) = rest.get(..keyword.len()) else {
+            return false;
+        };
+        let at_boundary = rest[keyword.len()..]
+            .chars()
+            .next()
+            .is_none_or(|c| c.is_whitespace() || c == '(');
+        if candidate.eq_ignore_ascii_case(keyword) && at_boundary {
+            self.pos += keyword.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_char(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<FilterExpr, FilterError> {
+        let mut expr = self.parse_and()?;
+        while self.eat_keyword("OR") {
+            let right = self.parse_and()?;
+            expr = FilterExpr::Or(Box::new(expr), Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<FilterExpr, FilterError> {
+        let mut expr = self.parse_unary()?;
+        while self.eat_keyword("AND") {
+            let right = self.parse_unary()?;
+            expr = FilterExpr::and(expr, right);
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<FilterExpr, FilterError> {
+        if self.eat_keyword("NOT") {
+            Ok(FilterExpr::Not(Box::new(self.parse_unary()?)))
+        } else {
+            self.parse_primary()
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<FilterExpr, FilterError> {
+        let open_pos = self.pos;
+        if self.eat_char('(') {
+            let expr = self.parse_or()?;
+            if !self.eat_char(')') {
+                return Err(self.error(format!(
+                    "expected `)` to close `(` at column {}",
+                    self.error_at(open_pos, "").column
+                )));
+            }
+            Ok(expr)
+        } else {
+            self.parse_condition()
+        }
+    }
+
+    fn parse_condition(&mut self) -> Result<FilterExpr, FilterError> {
+        self.skip_whitespace();
+        if self.tags_only {
+            if self.rest().is_empty() {
+                return Err(self.error("unexpected end of expression, expected a tag"));
+            }
+            let value_pos = self.pos;
+            let tag = self.parse_value()?;
+            return Ok(FilterExpr::Condition(self.tag_condition(tag, value_pos)?));
+        }
+
+        let field_pos = self.pos;
+        let field_len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest().len());
+        if field_len == 0 {
+            return Err(self.error(if self.rest().is_empty() {
+                "unexpected end of expression, expected a condition"
+            } else {
+                "expected a field name"
+            }));
+        }
+        let field = self.rest()[..field_len].to_ascii_lowercase();
+        self.pos += field_len;
+
+        let operator_pos = self.pos;
+        let operator = self.parse_operator()?;
+
+        self.skip_whitespace();
+        let value_pos = self.pos;
+        let value = self.parse_value()?;
+
+        let text_only = |parser: &Self| {
+            if operator == Comparison::Eq {
+                Ok(())
+            } else {
+                Err(parser.error_at(
+                    operator_pos,
+                    format!("field `{field}` only supports the `:` operator"),
+                ))
+            }
+        };
+
+        let condition = match field.as_str() {
+            "status" => {
+                text_only(self)?;
+                let status = value.parse().map_err(|_| {
+                    self.error_at(
+                        value_pos,
+                        format!(
+                            "unknown status `{value}`, expected one of `NotStarted`, \
+                            `InProgress`, `Complete`, `Cancelled` or `Blocked`"
+                        ),
+                    )
+                })?;
+                Condition::Status(status)
+            }
+            "title" => {
+                text_only(self)?;
+                Condition::Title(value)
+            }
+            "description" => {
+                text_only(self)?;
+                Condition::Description(value)
+            }
+            "tag" => {
+                text_only(self)?;
+                self.tag_condition(value, value_pos)?
+            }
+            "due" => return self.date_condition(Condition::Due, operator, &value, value_pos),
+            "completed" => {
+                return self.date_condition(Condition::Completed, operator, &value, value_pos);
+            }
+            _ => {
+                return Err(self.error_at(
+                    field_pos,
+                    format!(
+                        "unknown field `{field}`, expected one of `status`, `title`, \
+                        `description`, `tag`, `due` or `completed`"
+                    ),
+                ));
+            }
+        };
+        Ok(FilterExpr::Condition(condition))
+    }
+
+    fn parse_operator(&mut self) -> Result<Comparison, FilterError> {
+        let rest = self.rest();
+        let (operator, len) = if rest.starts_with("<=") {
+            (Comparison::Le, 2)
+        } else if rest.starts_with(">=") {
+            (Comparison::Ge, 2)
+        } else if rest.starts_with('<') {
+            (Comparison::Lt, 1)
+        } else if rest.starts_with('>') {
+            (Comparison::Gt, 1)
+        } else if rest.starts_with(':') {
+            (Comparison::Eq, 1)
+        } else {
+            return Err(self.error("expected an operator: `:`, `<`, `<=`, `>` or `>=`"));
+        };
+        self.pos += len;
+        Ok(operator)
+    }
+
+    fn parse_value(&mut self) -> Result<String, FilterError> {
+        if self.rest().starts_with('"') {
+            let open_pos = self.pos;
+            self.pos += 1;
+            let mut value = String::new();
+            let mut chars = self.rest().char_indices();
+            while let Some((offset, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        self.pos += offset + 1;
+                        return Ok(value);
+                    }
+                    '\\' => match chars.next() {
+                        Some((_, escaped @ ('"' | '\\'))) => value.push(escaped),
+                        Some((escape_offset, _)) => {
+                            return Err(self.error_at(
+                                self.pos + escape_offset - 1,
+                                "invalid escape, expected `\\\"` or `\\\\`",
+                            ));
+                        }
+                        None => break,
+                    },
+                    _ => value.push(c),
+                }
+            }
+            Err(self.error_at(open_pos, "unterminated quoted value"))
+        } else {
+            let len = self
+                .rest()
+                .find(|c: char| c.is_whitespace() || c == '(' || c == ')')
+                .unwrap_or(self.rest().len());
+            if len == 0 {
+                return Err(self.error("expected a value"));
+            }
+            let value = self.rest()[..len].to_string();
+            self.pos += len;
+            Ok(value)
+        }
+    }
+
+    fn tag_condition(&self, tag: String, value_pos: usize) -> Result<Condition, FilterError> {
+        if tag.is_empty() || tag.contains(char::is_whitespace) {
+            Err(self.error_at(value_pos, "tags cannot be empty or contain whitespace"))
+        } else {
+            Ok(Condition::Tag(tag))
+        }
+    }
+
+    fn date_condition(
+        &self,
+        field: fn(Comparison, DateTime<Utc>) -> Condition,
+        operator: Comparison,
+        value: &str,
+        value_pos: usize,
+    ) -> Result<FilterExpr, FilterError> {
+        let compare = |comparison, instant| FilterExpr::Condition(field(comparison, instant));
+
+        if let Ok(instant) = DateTime::parse_from_rfc3339(value) {
+            return Ok(compare(operator, instant.with_timezone(&Utc)));
+        }
+
+        let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+            self.error_at(
+                value_pos,
+                format!("invalid date `{value}`, expected `YYYY-MM-DD` or an RFC 3339 timestamp"),
+            )
+        })?;
+        // a bare date covers the whole of that day in UTC
+        let start = date.and_time(chrono::NaiveTime::MIN).and_utc();
+        let end = start + TimeDelta::days(1);
+        Ok(match operator {
+            Comparison::Eq => {
+                FilterExpr::and(compare(Comparison::Ge, start), compare(Comparison::Lt, end))
+            }
+            Comparison::Lt => compare(Comparison::Lt, start),
+            Comparison::Le => compare(Comparison::Lt, end),
+            Comparison::Gt => compare(Comparison::Ge, end),
+            Comparison::Ge => compare(Comparison::Ge, start),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    fn sql(filter: &str) -> String {
+        let expr: FilterExpr = filter.parse().unwrap();
+        let mut builder = QueryBuilder::new("");
+        expr.push_sql(&mut builder);
+        builder.sql().to_string()
+    }
+
+    #[rstest]
+    #[case("status:InProgress", "status = $1")]
+    #[case("title:hearing", "title ILIKE $1")]
+    #[case("description:\"two words\"", "coalesce(description, '') ILIKE $1")]
+    #[case("due<2025-07-01T12:00:00Z", "due < $1")]
+    #[case("due:2025-07-01", "(due >= $1 AND due < $2)")]
+    #[case("completed<=2025-07-01", "completed_at < $1")]
+    #[case(
+        "status:InProgress AND title:x OR due>=2025-07-01",
+        "((status = $1 AND title ILIKE $2) OR due >= $3)"
+    )]
+    #[case(
+        "status:blocked AND (title:x OR NOT title:y)",
+        "(status = $1 AND (title ILIKE $2 OR NOT title ILIKE $3))"
+    )]
+    #[case("not  status:complete", "NOT status = $1")]
+    #[case("tag:urgent AND due<2025-07-01", "(tags @> $1 AND due < $2)")]
+    fn compiles(#[case] filter: &str, #[case] expected: &str) {
+        assert_eq!(sql(filter), expected);
+    }
+
+    #[rstest]
+    #[case("home", "tags @> $1")]
+    #[case("home AND NOT shopping", "(tags @> $1 AND NOT tags @> $2)")]
+    #[case("(a OR b) AND c", "((tags @> $1 OR tags @> $2) AND tags @> $3)")]
+    fn compiles_tags(#[case] filter: &str, #[case] expected: &str) {
+        let expr = FilterExpr::parse_tags(filter).unwrap();
+        let mut builder = QueryBuilder::new("");
+        expr.push_sql(&mut builder);
+        assert_eq!(builder.sql(), expected);
+    }
+
+    #[rstest]
+    #[case("", 1)]
+    #[case("home AND", 9)]
+    #[case("\"two words\"", 1)]
+    #[case("home shopping", 6)]
+    fn tag_error_columns(#[case] filter: &str, #[case] column: usize) {
+        let error = FilterExpr::parse_tags(filter).unwrap_err();
+        assert_eq!(error.column, column, "{error}");
+    }
+
+    #[rstest]
+    fn parses_conditions() {
+        let expr: FilterExpr = "status:in_progress AND title:\"say \\\"hi\\\"\""
+            .parse()
+            .unwrap();
+        assert_eq!(
+            expr,
+            FilterExpr::and(
+                FilterExpr::Condition(Condition::Status(TodoStatus::InProgress)),
+                FilterExpr::Condition(Condition::Title("say \"hi\"".to_string())),
+            )
+        );
+    }
+
+    #[rstest]
+    fn bare_date_bounds() {
+        let expr: FilterExpr = "due<=2025-07-01".parse().unwrap();
+        let end = NaiveDate::from_ymd_opt(2025, 7, 2)
+            .unwrap()
+            .and_time(chrono::NaiveTime::MIN)
+            .and_utc();
+        assert_eq!(
+            expr,
+            FilterExpr::Condition(Condition::Due(Comparison::Lt, end))
+        );
+    }
+
+    #[rstest]
+    #[case("", 1)]
+    #[case("status:Finished", 8)]
+    #[case("colour:red", 1)]
+    #[case("title<abc", 6)]
+    #[case("status:complete AND", 20)]
+    #[case("status:complete title:x", 17)]
+    #[case("(status:complete", 17)]
+    #[case("due>tomorrow", 5)]
+    #[case("title:\"open", 7)]
+    #[case("title", 6)]
+    fn error_columns(#[case] filter: &str, #[case] column: usize) {
+        let error = filter.parse::<FilterExpr>().unwrap_err();
+        assert_eq!(error.column, column, "{error}");
+    }
+
+    #[rstest]
+    fn like_pattern_escapes() {
+        assert_eq!(like_pattern("100%_\\"), "%100\\%\\_\\\\%");
+    }
+}
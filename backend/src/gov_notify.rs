@@ -0,0 +1,325 @@
+//! Notifications sent with GOV.UK Notify, the mandated way of emailing and
+//! texting citizens and staff from government services.
+//!
+//! Messages are rendered by Notify from templates managed in its admin
+//! interface, which may use these personalisation fields:
+//!
+//! - `name`: name of the user notified
+//! - `event`: `assigned`, `due_soon`, `mentioned` or `overdue`
+//! - `summary`: one-line description of the notification
+//! - `title`: title of the task
+//! - `due`: when the task is due, like `16 October 2026 at 14:00 UTC`
+//! - `task_id`: ID of the task
+
+use std::{fmt, sync::Arc};
+
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use http_body_util::Full;
+use hyper::{Request, body::Bytes, header};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::Sha256;
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::{
+    clock::{Clock, SystemClock},
+    egress::Egress,
+    notify::{Channel, Notification, Notifier},
+};
+
+/// Length of the service ID and secret at the end of API keys.
+const UUID_LENGTH: usize = 36;
+
+/// Client of the GOV.UK Notify API, for a single service.
+#[derive(Clone)]
+pub struct NotifyClient {
+    http: Egress,
+    /// Base URL of the API, e.g. `https://api.notifications.service.gov.uk`.
+    api_url: String,
+    service_id: Uuid,
+    secret: String,
+    /// Clock telling the time that requests are authenticated at.
+    clock: Arc<dyn Clock>,
+}
+
+impl fmt::Debug for NotifyClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NotifyClient")
+            .field("api_url", &self.api_url)
+            .field("service_id", &self.service_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Error response of the API.
+#[derive(Deserialize)]
+struct ErrorResponse {
+    errors: Vec<ErrorDetail>,
+}
+
+/// Single error in an [`ErrorResponse`].
+#[derive(Deserialize)]
+struct ErrorDetail {
+    message: String,
+}
+
+impl NotifyClient {
+    /// Create a client of the API at `api_url` through `http`,
+    /// authenticated with `api_key` as created in Notify's admin interface.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `api_key` isn't a Notify API key.
+    pub fn new(http: Egress, api_url: &str, api_key: &str) -> Result<Self, &'static str> {
+        // keys are the key's name, the service ID and the secret, joined by
+        // hyphens
+        let invalid = "API key must end with the service ID and secret";
+        let secret_start = api_key
+            .len()
+            .checked_sub(UUID_LENGTH)
+            .filter(|&start| start > UUID_LENGTH)
+            .ok_or(invalid)?;
+        let secret = api_key.get(secret_start..).ok_or(invalid)?;
+        let service_id = api_key
+            .get(secret_start - UUID_LENGTH - 1..secret_start - 1)
+            .and_then(|id| id.parse().ok())
+            .ok_or(invalid)?;
+        if secret.parse::<Uuid>().is_err() {
+            return Err(invalid);
+        }
+
+        Ok(Self {
+            http,
+            api_url: api_url.trim_end_matches('/').to_owned(),
+            service_id,
+            secret: secret.to_owned(),
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// Tell the time that requests are authenticated at by `clock`, rather
+    /// than by the system clock.
+    #[must_use]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Bearer token authenticating requests made at `now`.
+    fn token(&self, now: DateTime<Utc>) -> String {
+        let encode = |value: serde_json::Value| URL_SAFE_NO_PAD.encode(value.to_string());
+        let token = format!(
+            "{}.{}",
+            encode(json!({ "typ": "JWT", "alg": "HS256" })),
+            encode(json!({ "iss": self.service_id, "iat": now.timestamp() }))
+        );
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC keys can be any length");
+        mac.update(token.as_bytes());
+        format!(
+            "{token}.{}",
+            URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+        )
+    }
+
+    /// Build the request sending a message of `kind`, `email` or `sms`.
+    fn request(
+        &self,
+        kind: &str,
+        body: &serde_json::Value,
+        now: DateTime<Utc>,
+    ) -> Result<Request<Full<Bytes>>, String> {
+        Request::post(format!("{}/v2/notifications/{kind}", self.api_url))
+            .header(header::AUTHORIZATION, format!("Bearer {}", self.token(now)))
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCEPT, "application/json")
+            .body(Full::from(body.to_string()))
+            .map_err(|e| e.to_string())
+    }
+
+    /// Send a message of `kind`, `email` or `sms`, returning a description of
+    /// the problem if Notify didn't accept it.
+    async fn send(&self, kind: &str, body: &serde_json::Value) -> Result<(), String> {
+        let response = self
+            .http
+            .request(self.request(kind, body, self.clock.now())?)
+            .await
+            .map_err(|e| e.to_string())?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        // Notify explains what was wrong, e.g. a missing personalisation field
+        match serde_json::from_slice::<ErrorResponse>(response.body()) {
+            Ok(ErrorResponse { errors }) if !errors.is_empty() => Err(format!(
+                "Notify responded with {status}: {}",
+                errors
+                    .into_iter()
+                    .map(|error| error.message)
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            )),
+            _ => Err(format!("Notify responded with {status}")),
+        }
+    }
+}
+
+/// Notifier sending emails or text messages with GOV.UK Notify.
+#[derive(Clone, Debug)]
+pub struct NotifyNotifier {
+    client: NotifyClient,
+    channel: Channel,
+    /// ID of the template messages are rendered from.
+    template_id: Uuid,
+}
+
+impl NotifyNotifier {
+    /// Create a notifier emailing users' registered addresses, with the
+    /// email template `template_id`.
+    #[must_use]
+    pub fn email(client: NotifyClient, template_id: Uuid) -> Self {
+        Self {
+            client,
+            channel: Channel::Email,
+            template_id,
+        }
+    }
+
+    /// Create a notifier texting users' registered phone numbers, with the
+    /// text message template `template_id`.
+    #[must_use]
+    pub fn sms(client: NotifyClient, template_id: Uuid) -> Self {
+        Self {
+            client,
+            channel: Channel::Sms,
+            template_id,
+        }
+    }
+
+    /// Body of the request sending `notification` to `recipient`.
+    fn body(&self, recipient: &str, notification: &Notification) -> serde_json::Value {
+        let recipient_field = match self.channel {
+            Channel::Sms => "phone_number",
+            _ => "email_address",
+        };
+        json!({
+            recipient_field: recipient,
+            "template_id": self.template_id,
+            "reference": notification.id.to_string(),
+            "personalisation": {
+                "name": notification.display_name.as_deref().unwrap_or(&notification.user_id),
+                "event": notification.event,
+                "summary": notification.summary(),
+                "title": notification.title,
+                "due": notification.due.format("%-d %B %Y at %H:%M UTC").to_string(),
+                "task_id": notification.task_id,
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for NotifyNotifier {
+    fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<(), String> {
+        let (kind, recipient) = match self.channel {
+            Channel::Sms => ("sms", &notification.phone),
+            _ => ("email", &notification.email),
+        };
+        let Some(recipient) = recipient else {
+            debug!(user = notification.user_id, kind, "no address to notify");
+            return Ok(());
+        };
+        self.client
+            .send(kind, &self.body(recipient, notification))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::NotificationEvent;
+
+    const SERVICE_ID: &str = "26785a09-ab16-4eb0-8407-a37497a57506";
+    const SECRET: &str = "3d844edf-8d35-48ac-975b-e847b4f122b0";
+
+    fn client() -> NotifyClient {
+        NotifyClient::new(
+            Egress::default(),
+            "http://egress:3128/",
+            &format!("tasks_live-{SERVICE_ID}-{SECRET}"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn api_keys() {
+        let client = client();
+        assert_eq!(client.service_id.to_string(), SERVICE_ID);
+        assert_eq!(client.secret, SECRET);
+        assert!(!format!("{client:?}").contains(SECRET));
+        assert!(NotifyClient::new(Egress::default(), "http://egress", SECRET).is_err());
+        assert!(
+            NotifyClient::new(
+                Egress::default(),
+                "http://egress",
+                &format!("key-{SERVICE_ID}-secret")
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn token() {
+        let now = "2026-10-16T14:00:00Z".parse().unwrap();
+        let token = client().token(now);
+        let (signed, signature) = token.rsplit_once('.').unwrap();
+        let (_, claims) = signed.split_once('.').unwrap();
+        assert_eq!(
+            URL_SAFE_NO_PAD.decode(claims).unwrap(),
+            format!(r#"{{"iat":{},"iss":"{SERVICE_ID}"}}"#, now.timestamp()).as_bytes()
+        );
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(signed.as_bytes());
+        mac.verify_slice(&URL_SAFE_NO_PAD.decode(signature).unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn body() {
+        let notification = Notification {
+            id: 7,
+            event: NotificationEvent::Overdue,
+            task_id: Uuid::nil(),
+            title: "File report".to_owned(),
+            due: "2026-10-16T14:00:00Z".parse().unwrap(),
+            user_id: "bob".to_owned(),
+            display_name: None,
+            email: None,
+            phone: Some("+447700900123".to_owned()),
+        };
+        let notifier = NotifyNotifier::sms(client(), Uuid::nil());
+        let body = notifier.body("+447700900123", &notification);
+        assert_eq!(body["phone_number"], "+447700900123");
+        assert_eq!(body["reference"], "7");
+        assert_eq!(body["personalisation"]["name"], "bob");
+        assert_eq!(body["personalisation"]["event"], "overdue");
+        assert_eq!(
+            body["personalisation"]["due"],
+            "16 October 2026 at 14:00 UTC"
+        );
+
+        let request = client()
+            .request("sms", &body, "2026-10-16T12:00:00Z".parse().unwrap())
+            .unwrap();
+        assert_eq!(request.uri(), "http://egress:3128/v2/notifications/sms");
+    }
+}
@@ -0,0 +1,177 @@
+//! Dependency graphs of [`TodoTask`](crate::TodoTask)s, ready to render as
+//! Gantt charts.
+
+use std::{cmp::Reverse, collections::BinaryHeap, collections::HashMap};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+#[cfg(feature = "postgres")]
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::TodoStatus;
+
+/// Task in a [`TaskGraph`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct GraphNode {
+    /// ID of the task.
+    pub id: Uuid,
+    /// Title of the task.
+    pub title: String,
+    /// Current status of the task.
+    pub status: TodoStatus,
+    /// Date & time at which the task was created, which its bar starts from.
+    pub start: DateTime<Utc>,
+    /// Date & time at which the task is due.
+    pub due: DateTime<Utc>,
+    /// Number of seconds from `start` to `due`, negative if the task was
+    /// created after it was due.
+    pub duration_seconds: i64,
+}
+
+/// Dependency in a [`TaskGraph`], from a task to a task depending on it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct GraphEdge {
+    /// ID of the task which must be done first.
+    pub from: Uuid,
+    /// ID of the task depending on it.
+    pub to: Uuid,
+}
+
+/// Tasks and the dependencies between them.
+#[derive(Clone, Debug, Serialize)]
+pub struct TaskGraph {
+    /// Tasks in topological order, each after the tasks it depends on.
+    ///
+    /// Tasks in or depending on a cycle can't be ordered, so come last.
+    pub nodes: Vec<GraphNode>,
+    /// Dependencies between the tasks.
+    pub edges: Vec<GraphEdge>,
+    /// IDs of tasks forming a dependency cycle, in order, if there are any.
+    pub cycle: Option<Vec<Uuid>>,
+}
+
+impl TaskGraph {
+    /// Build a graph, ordering `nodes` topologically.
+    ///
+    /// Tasks which could come in either order are kept in the order given.
+    /// Edges from or to tasks not in `nodes` are dropped.
+    #[must_use]
+    pub fn new(nodes: Vec<GraphNode>, edges: Vec<GraphEdge>) -> Self {
+        let index: HashMap<Uuid, usize> =
+            nodes.iter().enumerate().map(|(i, n)| (n.id, i)).collect();
+        let edges: Vec<GraphEdge> = edges
+            .into_iter()
+            .filter(|e| index.contains_key(&e.from) && index.contains_key(&e.to))
+            .collect();
+
+        let mut dependents = vec![Vec::new(); nodes.len()];
+        let mut prerequisites = vec![Vec::new(); nodes.len()];
+        for edge in &edges {
+            dependents[index[&edge.from]].push(index[&edge.to]);
+            prerequisites[index[&edge.to]].push(index[&edge.from]);
+        }
+
+        // Kahn's algorithm, taking the earliest given of the ready tasks
+        let mut waiting_on: Vec<usize> = prerequisites.iter().map(Vec::len).collect();
+        let mut ready: BinaryHeap<_> = (0..nodes.len())
+            .filter(|&i| waiting_on[i] == 0)
+            .map(Reverse)
+            .collect();
+        let mut order = Vec::with_capacity(nodes.len());
+        let mut placed = vec![false; nodes.len()];
+        while let Some(Reverse(i)) = ready.pop() {
+            order.push(i);
+            placed[i] = true;
+            for &dependent in &dependents[i] {
+                waiting_on[dependent] -= 1;
+                if waiting_on[dependent] == 0 {
+                    ready.push(Reverse(dependent));
+                }
+            }
+        }
+
+        let cycle = find_cycle(&prerequisites, &placed)
+            .map(|cycle| cycle.into_iter().map(|i| nodes[i].id).collect());
+        order.extend((0..nodes.len()).filter(|&i| !placed[i]));
+
+        let mut nodes: Vec<Option<GraphNode>> = nodes.into_iter().map(Some).collect();
+        // each index is in the order exactly once
+        let nodes = order.into_iter().filter_map(|i| nodes[i].take()).collect();
+        Self {
+            nodes,
+            edges,
+            cycle,
+        }
+    }
+}
+
+/// Find a cycle among the tasks not `placed` in the topological order, in
+/// order of dependency.
+fn find_cycle(prerequisites: &[Vec<usize>], placed: &[bool]) -> Option<Vec<usize>> {
+    // every unplaced task has an unplaced prerequisite, so following them
+    // must eventually revisit a task, which closes a cycle
+    let mut current = placed.iter().position(|&placed| !placed)?;
+    let mut path = Vec::new();
+    let mut visited = vec![false; placed.len()];
+    while !visited[current] {
+        visited[current] = true;
+        path.push(current);
+        current = *prerequisites[current].iter().find(|&&p| !placed[p])?;
+    }
+    let start = path.iter().position(|&i| i == current)?;
+    // the path follows prerequisites, so reverse it to follow dependencies
+    Some(path[start..].iter().rev().copied().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(n: u128) -> GraphNode {
+        GraphNode {
+            id: Uuid::from_u128(n),
+            title: format!("task {n}"),
+            status: TodoStatus::NotStarted,
+            start: DateTime::UNIX_EPOCH,
+            due: DateTime::UNIX_EPOCH,
+            duration_seconds: 0,
+        }
+    }
+
+    fn edge(from: u128, to: u128) -> GraphEdge {
+        GraphEdge {
+            from: Uuid::from_u128(from),
+            to: Uuid::from_u128(to),
+        }
+    }
+
+    fn ids(graph: &TaskGraph) -> Vec<u128> {
+        graph.nodes.iter().map(|n| n.id.as_u128()).collect()
+    }
+
+    #[test]
+    fn orders_topologically() {
+        let graph = TaskGraph::new(
+            (1..=5).map(node).collect(),
+            vec![edge(4, 1), edge(3, 2), edge(1, 2), edge(9, 5)],
+        );
+        assert_eq!(ids(&graph), [3, 4, 1, 2, 5]);
+        // the edge from a task outside the graph is dropped
+        assert_eq!(graph.edges.len(), 3);
+        assert_eq!(graph.cycle, None);
+    }
+
+    #[test]
+    fn detects_cycles() {
+        let graph = TaskGraph::new(
+            (1..=4).map(node).collect(),
+            vec![edge(1, 2), edge(2, 3), edge(3, 2), edge(3, 4)],
+        );
+        assert_eq!(ids(&graph), [1, 2, 3, 4]);
+        let cycle: Vec<u128> = graph.cycle.unwrap().iter().map(Uuid::as_u128).collect();
+        assert!(cycle == [2, 3] || cycle == [3, 2]);
+    }
+}
@@ -0,0 +1,361 @@
+//! Changes between versions of a [`TodoTask`].
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Colour, TodoStatus, TodoTask,
+    tasks::{optional_seconds, stored},
+};
+
+/// Change to a single field of a task.
+///
+/// Serializes with a `field` tag naming the field, alongside its old (`from`)
+/// and new (`to`) values.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "field", rename_all = "snake_case")]
+pub enum FieldChange {
+    /// The title changed.
+    Title {
+        /// Previous title.
+        from: String,
+        /// New title.
+        to: String,
+    },
+    /// The description changed.
+    Description {
+        /// Previous description.
+        from: Option<String>,
+        /// New description.
+        to: Option<String>,
+    },
+    /// The status changed.
+    Status {
+        /// Previous status.
+        from: TodoStatus,
+        /// New status.
+        to: TodoStatus,
+    },
+    /// The reason work on the task stopped changed.
+    StatusReason {
+        /// Previous reason.
+        from: Option<String>,
+        /// New reason.
+        to: Option<String>,
+    },
+    /// The due date changed.
+    Due {
+        /// Previous due date.
+        from: DateTime<Utc>,
+        /// New due date.
+        to: DateTime<Utc>,
+    },
+    /// The tags changed.
+    Tags {
+        /// Previous tags.
+        from: Vec<String>,
+        /// New tags.
+        to: Vec<String>,
+    },
+    /// The estimate changed.
+    Estimate {
+        /// Previous estimate, in seconds.
+        #[serde(with = "optional_seconds")]
+        from: Option<TimeDelta>,
+        /// New estimate, in seconds.
+        #[serde(with = "optional_seconds")]
+        to: Option<TimeDelta>,
+    },
+    /// The manually set progress changed.
+    Progress {
+        /// Previous progress percentage.
+        from: Option<u8>,
+        /// New progress percentage.
+        to: Option<u8>,
+    },
+    /// The Welsh title changed.
+    TitleCy {
+        /// Previous Welsh title.
+        from: Option<String>,
+        /// New Welsh title.
+        to: Option<String>,
+    },
+    /// The Welsh description changed.
+    DescriptionCy {
+        /// Previous Welsh description.
+        from: Option<String>,
+        /// New Welsh description.
+        to: Option<String>,
+    },
+    /// The colour changed.
+    Colour {
+        /// Previous colour.
+        from: Option<Colour>,
+        /// New colour.
+        to: Option<Colour>,
+    },
+}
+
+/// Every field-level change between two versions of a task.
+///
+/// ```
+/// use chrono::Utc;
+/// use dts_developer_challenge::{TaskDiff, TodoStatus, TodoTask};
+///
+/// let now = Utc::now();
+/// let before = TodoTask::new("Title".to_string(), None, TodoStatus::NotStarted, &now, now);
+/// let mut after = before.clone();
+/// after.transition(TodoStatus::Complete, Utc::now());
+///
+/// let diff = TaskDiff::between(&before, &after);
+/// assert_eq!(diff.changes.len(), 1);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TaskDiff {
+    /// Changed fields, in declaration order of [`TodoTask`]'s fields.
+    pub changes: Vec<FieldChange>,
+}
+
+impl TaskDiff {
+    /// Compute the changes made to get from `old` to `new`.
+    #[must_use]
+    pub fn between(old: &TodoTask, new: &TodoTask) -> Self {
+        let mut changes = Vec::new();
+
+        if old.title() != new.title() {
+            changes.push(FieldChange::Title {
+                from: old.title().to_string(),
+                to: new.title().to_string(),
+            });
+        }
+        if old.description() != new.description() {
+            changes.push(FieldChange::Description {
+                from: old.description().map(str::to_string),
+                to: new.description().map(str::to_string),
+            });
+        }
+        if old.status != new.status {
+            changes.push(FieldChange::Status {
+                from: old.status.clone(),
+                to: new.status.clone(),
+            });
+        }
+        if old.status_reason() != new.status_reason() {
+            changes.push(FieldChange::StatusReason {
+                from: old.status_reason().map(str::to_string),
+                to: new.status_reason().map(str::to_string),
+            });
+        }
+        if old.due() != new.due() {
+            changes.push(FieldChange::Due {
+                from: *old.due(),
+                to: *new.due(),
+            });
+        }
+        if old.tags() != new.tags() {
+            changes.push(FieldChange::Tags {
+                from: old.tags().to_vec(),
+                to: new.tags().to_vec(),
+            });
+        }
+        if old.estimate() != new.estimate() {
+            changes.push(FieldChange::Estimate {
+                from: old.estimate(),
+                to: new.estimate(),
+            });
+        }
+        if old.progress() != new.progress() {
+            changes.push(FieldChange::Progress {
+                from: old.progress(),
+                to: new.progress(),
+            });
+        }
+        if old.title_cy() != new.title_cy() {
+            changes.push(FieldChange::TitleCy {
+                from: old.title_cy().map(str::to_string),
+                to: new.title_cy().map(str::to_string),
+            });
+        }
+        if old.description_cy() != new.description_cy() {
+            changes.push(FieldChange::DescriptionCy {
+                from: old.description_cy().map(str::to_string),
+                to: new.description_cy().map(str::to_string),
+            });
+        }
+        if old.colour() != new.colour() {
+            changes.push(FieldChange::Colour {
+                from: old.colour().cloned(),
+                to: new.colour().cloned(),
+            });
+        }
+
+        Self { changes }
+    }
+
+    /// Check whether there are no changes.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Apply the changes to `task`, as made at the instant `at`.
+    ///
+    /// Only the new value of each change is used, so `task` need not be in
+    /// the state the diff was computed from. A change of status is made with
+    /// [`TodoTask::transition`].
+    pub fn apply(&self, task: &mut TodoTask, at: DateTime<Utc>) {
+        for change in &self.changes {
+            match change {
+                FieldChange::Title { to, .. } => task.set_title(to.clone()),
+                FieldChange::Description { to, .. } => task.set_description(to.clone()),
+                FieldChange::Status { to, .. } => task.transition(to.clone(), at),
+                // after any change of status, which comes first
+                FieldChange::StatusReason { to, .. } => task.set_status_reason(to.clone()),
+                FieldChange::Due { to, .. } => task.set_due(to),
+                FieldChange::Tags { to, .. } => task.set_tags(to.clone()),
+                FieldChange::Estimate { to, .. } => task.set_estimate(*to),
+                FieldChange::Progress { to, .. } => task.set_progress(*to),
+                FieldChange::TitleCy { to, .. } => {
+                    task.set_welsh(to.clone(), task.description_cy().map(str::to_string));
+                }
+                FieldChange::DescriptionCy { to, .. } => {
+                    task.set_welsh(task.title_cy().map(str::to_string), to.clone());
+                }
+                FieldChange::Colour { to, .. } => task.set_colour(to.clone()),
+            }
+        }
+    }
+}
+
+/// Change to a task, as recorded by event-sourced storage.
+///
+/// Serializes with a `type` tag naming the kind of event.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaskEvent {
+    /// The task was created.
+    Created {
+        /// Initial state of the task.
+        #[serde(deserialize_with = "stored::deserialize_boxed")]
+        task: Box<TodoTask>,
+    },
+    /// Fields of the task were changed.
+    Changed {
+        /// The changes made.
+        changes: TaskDiff,
+    },
+}
+
+impl TaskEvent {
+    /// Apply this event, recorded at `recorded_at`, to `state`, the state of
+    /// a task before the event.
+    ///
+    /// `state` is `None` if the task did not exist before the event.
+    /// Changes to a task which doesn't exist are ignored.
+    #[must_use]
+    pub fn apply(self, state: Option<TodoTask>, recorded_at: DateTime<Utc>) -> Option<TodoTask> {
+        match self {
+            Self::Created { mut task } => {
+                // events stored before completion times were kept lack them
+                task.transition(task.status.clone(), recorded_at);
+                Some(*task)
+            }
+            Self::Changed { changes } => state.map(|mut task| {
+                changes.apply(&mut task, recorded_at);
+                task
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use rstest::*;
+
+    use super::*;
+
+    #[fixture]
+    fn sample_task() -> TodoTask {
+        let now = Utc::now();
+        let due = now + TimeDelta::hours(12);
+        TodoTask::new(
+            "my title".to_string(),
+            None,
+            TodoStatus::InProgress,
+            &due,
+            now,
+        )
+    }
+
+    #[rstest]
+    fn no_changes(sample_task: TodoTask) {
+        assert!(TaskDiff::between(&sample_task, &sample_task.clone()).is_empty());
+    }
+
+    #[rstest]
+    fn multiple_changes(sample_task: TodoTask) {
+        let mut new = sample_task.clone();
+        new.set_description(Some("now described".to_string()));
+        new.set_tags(vec!["urgent".to_string()]);
+
+        let diff = TaskDiff::between(&sample_task, &new);
+        assert_eq!(
+            diff.changes,
+            vec![
+                FieldChange::Description {
+                    from: None,
+                    to: Some("now described".to_string()),
+                },
+                FieldChange::Tags {
+                    from: Vec::new(),
+                    to: vec!["urgent".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[rstest]
+    fn apply_diff(sample_task: TodoTask) {
+        let mut new = sample_task.clone();
+        new.set_title("new title".to_string());
+        new.status = TodoStatus::Blocked;
+        new.set_status_reason(Some("waiting on legal".to_string()));
+        new.set_due(&(*sample_task.due() + TimeDelta::days(2)));
+
+        let mut applied = sample_task.clone();
+        TaskDiff::between(&sample_task, &new).apply(&mut applied, Utc::now());
+        assert!(TaskDiff::between(&applied, &new).is_empty());
+    }
+
+    #[rstest]
+    fn fold_events(sample_task: TodoTask) {
+        let mut completed = sample_task.clone();
+        completed.status = TodoStatus::Complete;
+        let completed_at = Utc::now() - TimeDelta::hours(1);
+        let events = [
+            TaskEvent::Created {
+                task: Box::new(sample_task.clone()),
+            },
+            TaskEvent::Changed {
+                changes: TaskDiff::between(&sample_task, &completed),
+            },
+        ];
+
+        let state = events
+            .into_iter()
+            .fold(None, |state, event| event.apply(state, completed_at))
+            .unwrap();
+        assert_eq!(state.status, TodoStatus::Complete);
+        assert_eq!(state.completed_at(), Some(&completed_at));
+    }
+
+    #[rstest]
+    fn change_to_missing_task() {
+        let event = TaskEvent::Changed {
+            changes: TaskDiff::default(),
+        };
+        assert!(event.apply(None, Utc::now()).is_none());
+    }
+}
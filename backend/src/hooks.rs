@@ -0,0 +1,286 @@
+//! REST hooks, which automation services such as Zapier subscribe to be sent
+//! new tasks.
+//!
+//! Hooks are posted [`NewTask`]s as JSON, unless they were subscribed with a
+//! payload template to post something else, e.g. a card for a chat service.
+//! Templates use Jinja syntax, with the fields of the [`NewTask`] as
+//! variables, and must render JSON. Variables are output as JSON, so strings
+//! are quoted and escaped: `{"text": {{ title }}}` renders
+//! `{"text": "File report"}`.
+//!
+//! Every attempt to deliver a task to a hook is recorded as a [`Delivery`],
+//! so integrators can see why deliveries failed and have them redelivered.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use http::Uri;
+use minijinja::{AutoEscape, Environment, UndefinedBehavior};
+use serde::Serialize;
+#[cfg(feature = "postgres")]
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::TaskRecord;
+
+/// Subscription of a URL to the new tasks of an owner.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct Hook {
+    /// ID of the hook, used to unsubscribe it.
+    pub id: Uuid,
+    /// Owner whose new tasks are sent.
+    pub owner: Option<String>,
+    /// URL which new tasks are posted to.
+    pub target_url: String,
+    /// Template of the payload posted, if not the new task itself.
+    pub payload_template: Option<String>,
+    /// Date & time at which the hook was subscribed.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Newly created task, as listed for polling and posted to hooks.
+///
+/// Serializes as the task's fields with additional `id` and `created_at`
+/// fields. The `id` is the task's, so services can use it to deduplicate
+/// tasks they've already seen.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct NewTask {
+    /// The task, as it is now.
+    #[serde(flatten)]
+    #[cfg_attr(feature = "postgres", sqlx(flatten))]
+    pub task: TaskRecord,
+    /// Date & time at which the task was created.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Number of the latest deliveries to each hook which are kept.
+pub const MAX_DELIVERIES_KEPT: i64 = 100;
+
+/// Most bytes of response bodies kept with deliveries.
+pub const RESPONSE_SNIPPET_LENGTH: usize = 1024;
+
+/// Longest payload template hooks may have, in bytes.
+pub const MAX_TEMPLATE_LENGTH: usize = 16 * 1024;
+
+/// Fuel rendering a payload template may use, bounding how much work
+/// templates can make.
+const TEMPLATE_FUEL: u64 = 50_000;
+
+/// Recorded attempt to deliver a task to a hook.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct Delivery {
+    /// ID of the delivery, used to redeliver it.
+    pub id: Uuid,
+    /// ID of the hook delivered to.
+    pub hook_id: Uuid,
+    /// ID of the task delivered.
+    pub task_id: Uuid,
+    /// ID of the delivery this one redelivered, if any.
+    pub redelivery_of: Option<Uuid>,
+    /// New task delivered, which hooks without a payload template are
+    /// posted.
+    pub payload: serde_json::Value,
+    /// Body posted to the hook, if its payload template rendered.
+    pub body: Option<serde_json::Value>,
+    /// Date & time at which the delivery was attempted.
+    pub attempted_at: DateTime<Utc>,
+    /// How long the hook took to respond, in milliseconds.
+    pub latency_ms: i32,
+    /// Status of the hook's response, if it responded.
+    pub response_status: Option<i16>,
+    /// Start of the body of the hook's response, if it responded.
+    pub response_snippet: Option<String>,
+    /// Why the hook didn't respond, if it didn't.
+    pub error: Option<String>,
+}
+
+/// Outcome of attempting to deliver a task to a hook.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeliveryOutcome {
+    /// How long the attempt took.
+    pub latency: Duration,
+    /// Status and start of the body of the response, or why there was no
+    /// response.
+    pub response: Result<(u16, String), String>,
+}
+
+impl DeliveryOutcome {
+    /// Whether the hook accepted the task.
+    #[must_use]
+    pub fn succeeded(&self) -> bool {
+        matches!(self.response, Ok((200..=299, _)))
+    }
+}
+
+/// Start of a response body to keep with a delivery, as text.
+#[must_use]
+pub fn response_snippet(body: &[u8]) -> String {
+    let mut end = body.len().min(RESPONSE_SNIPPET_LENGTH);
+    // don't cut a UTF-8 character in half
+    while end < body.len() && end > 0 && body[end] & 0b1100_0000 == 0b1000_0000 {
+        end -= 1;
+    }
+    String::from_utf8_lossy(&body[..end]).into_owned()
+}
+
+/// Environment rendering payload templates.
+fn template_environment() -> Environment<'static> {
+    let mut environment = Environment::new();
+    environment.set_auto_escape_callback(|_| AutoEscape::Json);
+    // misspelt variables are errors rather than rendering nothing
+    environment.set_undefined_behavior(UndefinedBehavior::Strict);
+    environment.set_fuel(Some(TEMPLATE_FUEL));
+    environment
+}
+
+/// Check that `template` may be given as a hook's payload template.
+///
+/// # Errors
+///
+/// Returns a description of why it may not, e.g. a syntax error.
+pub fn check_template(template: &str) -> Result<(), String> {
+    if template.len() > MAX_TEMPLATE_LENGTH {
+        return Err(format!(
+            "payload template must be at most {MAX_TEMPLATE_LENGTH} bytes long"
+        ));
+    }
+    template_environment()
+        .template_from_str(template)
+        .map(|_| ())
+        .map_err(|e| format!("payload template is invalid: {e}"))
+}
+
+/// Body to post to a hook with `template`, if any, for `payload`, a
+/// [`NewTask`].
+///
+/// # Errors
+///
+/// Returns a description of the problem if the template couldn't be
+/// rendered, or didn't render JSON.
+pub fn render_payload(
+    template: Option<&str>,
+    payload: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let Some(template) = template else {
+        return Ok(payload.clone());
+    };
+    let rendered = template_environment()
+        .render_str(template, payload)
+        .map_err(|e| format!("failed to render payload template: {e}"))?;
+    serde_json::from_str(&rendered).map_err(|e| format!("payload template didn't render JSON: {e}"))
+}
+
+/// Check that `url` may be subscribed as a hook, returning it parsed.
+///
+/// Only `http` and `https` URLs whose host is one of `allowed_hosts` are
+/// accepted, so hooks can't be used to make requests to internal services.
+///
+/// # Errors
+///
+/// Returns a description of why the URL is refused.
+pub fn check_target(url: &str, allowed_hosts: &[String]) -> Result<Uri, &'static str> {
+    let target: Uri = url.parse().map_err(|_| "hook URL is invalid")?;
+    if !matches!(target.scheme_str(), Some("http" | "https")) {
+        return Err("hook URL must use http or https");
+    }
+    let host = target.host().unwrap_or_default();
+    if !allowed_hosts
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(host))
+    {
+        return Err("hook URL host is not allowed");
+    }
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("https://hooks.zapier.com/hooks/standard/1/abc/", Ok(()))]
+    #[case("http://HOOKS.zapier.com/x", Ok(()))]
+    #[case("https://evil.example/hooks", Err("hook URL host is not allowed"))]
+    #[case(
+        "https://hooks.zapier.com.evil.example/",
+        Err("hook URL host is not allowed")
+    )]
+    #[case("ftp://hooks.zapier.com/", Err("hook URL must use http or https"))]
+    #[case("hooks.zapier.com/x", Err("hook URL is invalid"))]
+    #[case("https://hooks zapier", Err("hook URL is invalid"))]
+    fn target(#[case] url: &str, #[case] expected: Result<(), &str>) {
+        let allowed = ["hooks.zapier.com".to_string()];
+        assert_eq!(check_target(url, &allowed).map(|_| ()), expected);
+    }
+
+    #[test]
+    fn templates() {
+        let payload = serde_json::json!({
+            "id": "d3b1b1a2-0000-4000-8000-000000000000",
+            "title": "Say \"hello\"",
+            "tags": ["urgent"],
+        });
+        assert_eq!(render_payload(None, &payload), Ok(payload.clone()));
+        assert_eq!(
+            render_payload(
+                Some(
+                    r#"{"type": "message", "text": {{ title }},
+                    "urgent": {{ "urgent" in tags }}}"#
+                ),
+                &payload
+            ),
+            Ok(serde_json::json!({
+                "type": "message",
+                "text": "Say \"hello\"",
+                "urgent": true,
+            }))
+        );
+
+        assert!(check_template("{{ title }").is_err());
+        assert!(check_template(&" ".repeat(MAX_TEMPLATE_LENGTH + 1)).is_err());
+        let error = render_payload(Some("{{ titel }}"), &payload).unwrap_err();
+        assert!(
+            error.starts_with("failed to render payload template"),
+            "{error}"
+        );
+        let error = render_payload(Some("text: {{ title }}"), &payload).unwrap_err();
+        assert!(
+            error.starts_with("payload template didn't render JSON"),
+            "{error}"
+        );
+        let error = render_payload(
+            Some("{% for i in range(10000) %}{% for j in range(10000) %}{% endfor %}{% endfor %}"),
+            &payload,
+        )
+        .unwrap_err();
+        assert!(error.contains("fuel"), "{error}");
+    }
+
+    #[test]
+    fn snippets() {
+        assert_eq!(
+            response_snippet(b"{\"status\":\"success\"}"),
+            r#"{"status":"success"}"#
+        );
+        let long = "é".repeat(RESPONSE_SNIPPET_LENGTH);
+        let snippet = response_snippet(long.as_bytes());
+        assert_eq!(snippet.len(), RESPONSE_SNIPPET_LENGTH);
+        assert!(snippet.chars().all(|c| c == 'é'));
+        assert_eq!(response_snippet(b"\xff"), "\u{fffd}");
+    }
+
+    #[test]
+    fn outcomes() {
+        let outcome = |response| DeliveryOutcome {
+            latency: Duration::from_millis(40),
+            response,
+        };
+        assert!(outcome(Ok((200, String::new()))).succeeded());
+        assert!(!outcome(Ok((410, String::new()))).succeeded());
+        assert!(!outcome(Err("timed out".to_owned())).succeeded());
+    }
+}
@@ -0,0 +1,236 @@
+//! Translation of user-facing labels and messages.
+//!
+//! English and Welsh are supported, as UK public services must offer both.
+//! Translations are kept in simple per-locale maps keyed by the English text.
+
+use serde::Deserialize;
+
+use crate::TodoStatus;
+
+/// Language in which to present labels and messages.
+///
+/// Deserializes from a language tag, see [`Locale::from_tag`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Locale {
+    /// British English.
+    ///
+    /// This is the default value with [`Default::default`].
+    #[default]
+    English,
+    /// Welsh.
+    Welsh,
+}
+
+impl Locale {
+    /// Every supported locale.
+    pub const ALL: [Self; 2] = [Self::English, Self::Welsh];
+
+    /// Get the BCP 47 language tag of the locale.
+    #[must_use]
+    pub fn tag(self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::Welsh => "cy",
+        }
+    }
+
+    /// Find the locale for a language tag, ignoring any region, e.g. `en-GB`.
+    #[must_use]
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag.split('-').next().unwrap_or_default();
+        Self::ALL
+            .into_iter()
+            .find(|locale| locale.tag().eq_ignore_ascii_case(language))
+    }
+
+    /// Choose the supported locale most preferred by an `Accept-Language`
+    /// header value.
+    ///
+    /// Falls back to the default locale if none of the languages listed are
+    /// supported.
+    ///
+    /// ```
+    /// use dts_developer_challenge::i18n::Locale;
+    ///
+    /// assert_eq!(Locale::negotiate("fr, cy;q=0.8, en;q=0.5"), Locale::Welsh);
+    /// assert_eq!(Locale::negotiate("fr"), Locale::English);
+    /// ```
+    #[must_use]
+    pub fn negotiate(accept_language: &str) -> Self {
+        accept_language
+            .split(',')
+            .filter_map(|range| {
+                let mut params = range.split(';');
+                let locale = Self::from_tag(params.next()?.trim())?;
+                let quality = params
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+                Some((locale, quality))
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            // the first of equally preferred languages wins
+            .rev()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(locale, _)| locale)
+            .unwrap_or_default()
+    }
+
+    /// Translate an English `message` from this library into the locale.
+    ///
+    /// Messages without a translation are returned in English.
+    #[must_use]
+    pub fn translate(self, message: &'static str) -> &'static str {
+        match self {
+            Self::English => message,
+            Self::Welsh => WELSH_MESSAGES
+                .iter()
+                .find(|(english, _)| *english == message)
+                .map_or(message, |(_, welsh)| welsh),
+        }
+    }
+}
+
+impl TryFrom<String> for Locale {
+    type Error = &'static str;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::from_tag(&value).ok_or("unsupported language")
+    }
+}
+
+/// Welsh translations of validation messages and feed labels, keyed by their
+/// English text.
+const WELSH_MESSAGES: [(&str, &str); 24] = [
+    ("invalid request body", "corff cais annilys"),
+    ("unknown task status", "statws tasg anhysbys"),
+    (
+        "custom statuses cannot be blank or named after built-in statuses",
+        "ni all statwsau personol fod yn wag nac wedi'u henwi ar ôl statwsau adeiledig",
+    ),
+    (
+        "the workflow doesn't allow this change of status",
+        "nid yw'r llif gwaith yn caniatáu'r newid hwn i'r statws",
+    ),
+    ("title cannot be empty", "ni all y teitl fod yn wag"),
+    (
+        "description cannot be empty",
+        "ni all y disgrifiad fod yn wag",
+    ),
+    (
+        "status reason cannot be empty",
+        "ni all y rheswm dros y statws fod yn wag",
+    ),
+    (
+        "only cancelled or blocked tasks can have a status reason",
+        "dim ond tasgau sydd wedi'u canslo neu eu rhwystro all gael rheswm dros eu statws",
+    ),
+    (
+        "a reason is required for cancelled or blocked tasks",
+        "mae angen rheswm ar gyfer tasgau sydd wedi'u canslo neu eu rhwystro",
+    ),
+    (
+        "due date is too far in the past or future",
+        "mae'r dyddiad dyledus yn rhy bell yn y gorffennol neu'r dyfodol",
+    ),
+    (
+        "due date has already passed",
+        "mae'r dyddiad dyledus eisoes wedi mynd heibio",
+    ),
+    (
+        "tags cannot be empty or contain whitespace",
+        "ni all tagiau fod yn wag na chynnwys bylchau",
+    ),
+    (
+        "estimate cannot be negative or longer than 100000 hours",
+        "ni all yr amcangyfrif fod yn negatif nac yn hirach na 100000 awr",
+    ),
+    (
+        "progress cannot be more than 100 percent",
+        "ni all y cynnydd fod yn fwy na 100 y cant",
+    ),
+    (
+        "Welsh title cannot be empty",
+        "ni all y teitl Cymraeg fod yn wag",
+    ),
+    (
+        "Welsh description cannot be empty",
+        "ni all y disgrifiad Cymraeg fod yn wag",
+    ),
+    (
+        "Welsh description requires a description in the primary language",
+        "mae angen disgrifiad yn yr iaith gynradd ar gyfer disgrifiad Cymraeg",
+    ),
+    (
+        "item has no due date",
+        "nid oes dyddiad dyledus gan yr eitem",
+    ),
+    (
+        "item has an unrecognised due date",
+        "mae gan yr eitem ddyddiad dyledus anadnabyddus",
+    ),
+    (
+        "due date is missing or invalid",
+        "mae'r dyddiad dyledus ar goll neu'n annilys",
+    ),
+    ("Task activity", "Gweithgarwch tasgau"),
+    ("Created", "Crëwyd"),
+    ("Completed", "Cwblhawyd"),
+    (
+        "colour must be a palette name or a hex code like #1e90ff",
+        "rhaid i'r lliw fod yn enw o'r palet neu'n god hecs fel #1e90ff",
+    ),
+];
+
+impl TodoStatus {
+    /// Get a human-readable label for the status in `locale`.
+    ///
+    /// Custom statuses are labelled with their names in every locale.
+    #[must_use]
+    pub fn label(&self, locale: Locale) -> &str {
+        match (locale, self) {
+            (Locale::English, Self::NotStarted) => "Not started",
+            (Locale::English, Self::InProgress) => "In progress",
+            (Locale::English, Self::Complete) => "Complete",
+            (Locale::English, Self::Cancelled) => "Cancelled",
+            (Locale::English, Self::Blocked) => "Blocked",
+            (Locale::Welsh, Self::NotStarted) => "Heb ddechrau",
+            (Locale::Welsh, Self::InProgress) => "Ar y gweill",
+            (Locale::Welsh, Self::Complete) => "Wedi'i gwblhau",
+            (Locale::Welsh, Self::Cancelled) => "Wedi'i ganslo",
+            (Locale::Welsh, Self::Blocked) => "Wedi'i rwystro",
+            (_, Self::Custom(name)) => name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("", Locale::English)]
+    #[case("cy", Locale::Welsh)]
+    #[case("CY-gb", Locale::Welsh)]
+    #[case("en-GB,cy;q=0.9", Locale::English)]
+    #[case("en;q=0.4, cy;q=0.6", Locale::Welsh)]
+    #[case("cy;q=0, en;q=0.1", Locale::English)]
+    #[case("de, *;q=0.5", Locale::English)]
+    fn negotiate(#[case] header: &str, #[case] expected: Locale) {
+        assert_eq!(Locale::negotiate(header), expected);
+    }
+
+    #[test]
+    fn translate() {
+        assert_eq!(
+            Locale::Welsh.translate("title cannot be empty"),
+            "ni all y teitl fod yn wag"
+        );
+        assert_eq!(Locale::Welsh.translate("untranslated"), "untranslated");
+        assert_eq!(
+            Locale::English.translate("title cannot be empty"),
+            "title cannot be empty"
+        );
+    }
+}
@@ -0,0 +1,403 @@
+//! Conversion of tasks to and from iCalendar `VTODO` components.
+//!
+//! Only the properties which correspond to fields of a task are read:
+//! `SUMMARY`, `DESCRIPTION`, `STATUS`, `DUE`, `CATEGORIES` and
+//! `PERCENT-COMPLETE`. Times with a `TZID` are taken to be in UTC, as are
+//! floating times, and tasks due on a date are due at the end of that day.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use uuid::Uuid;
+
+use crate::{
+    DueWindow, TodoStatus, TodoTask, TodoTaskUnchecked,
+    import::{NO_DUE_DATE, end_of_day, to_tag},
+};
+
+/// Longest line allowed in iCalendar data, in octets, excluding the line
+/// break.
+const MAX_LINE_LENGTH: usize = 75;
+
+/// Render a task as an iCalendar object containing a single `VTODO`, whose
+/// UID is the task's ID, stamped as created at `now`.
+#[must_use]
+pub fn to_vcalendar(id: Uuid, task: &TodoTask, now: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//dts-developer-challenge//tasks//EN".to_string(),
+        "BEGIN:VTODO".to_string(),
+        format!("UID:{id}"),
+        format!("DTSTAMP:{}", format_utc(&now)),
+        format!("SUMMARY:{}", escape(task.title())),
+    ];
+    if let Some(description) = task.description() {
+        lines.push(format!("DESCRIPTION:{}", escape(description)));
+    }
+    lines.push(format!("STATUS:{}", status_name(&task.status)));
+    lines.push(format!("DUE:{}", format_utc(task.due())));
+    if !task.tags().is_empty() {
+        let tags: Vec<_> = task.tags().iter().map(|tag| escape(tag)).collect();
+        lines.push(format!("CATEGORIES:{}", tags.join(",")));
+    }
+    if let Some(progress) = task.progress() {
+        lines.push(format!("PERCENT-COMPLETE:{progress}"));
+    }
+    lines.push("END:VTODO".to_string());
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+/// Properties of a `VTODO` which correspond to fields of a task.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VTodo {
+    /// Unique ID of the to-do, if given.
+    pub uid: Option<String>,
+    /// Summary, which becomes the task's title.
+    pub summary: String,
+    /// Description of the to-do, if any.
+    pub description: Option<String>,
+    /// Status of the to-do, if given in a form which corresponds to one.
+    pub status: Option<TodoStatus>,
+    /// Date & time at which the to-do is due, if it has a due date.
+    pub due: Option<DateTime<Utc>>,
+    /// Categories, converted into tags.
+    pub categories: Vec<String>,
+    /// Percentage of the to-do which is done, if given.
+    pub percent_complete: Option<u8>,
+}
+
+impl VTodo {
+    /// Parse the first `VTODO` of an iCalendar object.
+    ///
+    /// Components nested in the `VTODO`, such as alarms, are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no `VTODO`, or a property corresponding
+    /// to a field of a task has an invalid value.
+    pub fn parse(calendar: &str) -> Result<Self, &'static str> {
+        let unfolded = calendar
+            .replace("\r\n ", "")
+            .replace("\r\n\t", "")
+            .replace("\n ", "")
+            .replace("\n\t", "");
+
+        let mut todo = None;
+        // depth of components nested in the `VTODO`
+        let mut depth = 0;
+        for line in unfolded.lines() {
+            let Some((name, params, value)) = split_property(line) else {
+                continue;
+            };
+            match (name.as_str(), value, &mut todo) {
+                ("BEGIN", v, None) if v.eq_ignore_ascii_case("VTODO") => {
+                    todo = Some(Self::default());
+                }
+                ("BEGIN", _, Some(_)) => depth += 1,
+                ("END", _, Some(_)) if depth > 0 => depth -= 1,
+                ("END", v, Some(_)) if v.eq_ignore_ascii_case("VTODO") => break,
+                (_, _, Some(todo)) if depth == 0 => todo.set_property(&name, params, value)?,
+                _ => {}
+            }
+        }
+        todo.ok_or("calendar object has no VTODO component")
+    }
+
+    /// Set the field corresponding to a property, if any.
+    fn set_property(&mut self, name: &str, params: &str, value: &str) -> Result<(), &'static str> {
+        match name {
+            "UID" => self.uid = Some(value.to_string()),
+            "SUMMARY" => self.summary = unescape(value),
+            "DESCRIPTION" => self.description = Some(unescape(value)),
+            "STATUS" => self.status = parse_status(value),
+            "DUE" => {
+                self.due =
+                    Some(parse_due(params, value).ok_or("item has an unrecognised due date")?);
+            }
+            "CATEGORIES" => self
+                .categories
+                .extend(split_list(value).iter().filter_map(|c| to_tag(c))),
+            "PERCENT-COMPLETE" => {
+                self.percent_complete = Some(
+                    value
+                        .trim()
+                        .parse()
+                        .ok()
+                        .filter(|percent| *percent <= 100)
+                        .ok_or("progress cannot be more than 100 percent")?,
+                );
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Convert the to-do into a task, replacing the `current` version of the
+    /// task if there is one.
+    ///
+    /// Fields of the current task which a `VTODO` doesn't carry, such as its
+    /// estimate, are kept, as is its due date and status if the to-do lacks
+    /// them. A [`Blocked`](TodoStatus::Blocked) task stays blocked while the
+    /// to-do still needs action, and a task with a custom status keeps it
+    /// while the to-do is in process.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the to-do isn't a valid task, is due outside
+    /// `window` around `now`, or is new and has no due date.
+    pub fn into_task(
+        self,
+        current: Option<&TodoTask>,
+        window: DueWindow,
+        now: DateTime<Utc>,
+    ) -> Result<TodoTask, &'static str> {
+        let current_status = current.map(|task| task.status.clone());
+        let status = match (self.status, current_status) {
+            (Some(TodoStatus::NotStarted), Some(TodoStatus::Blocked)) => TodoStatus::Blocked,
+            (Some(TodoStatus::InProgress), Some(custom @ TodoStatus::Custom(_))) => custom,
+            (Some(status), _) | (None, Some(status)) => status,
+            (None, None) => TodoStatus::NotStarted,
+        };
+        let due = self
+            .due
+            .or_else(|| current.map(|task| *task.due()))
+            .ok_or(NO_DUE_DATE)?;
+
+        let mut task: TodoTask = TodoTaskUnchecked::new(
+            self.summary,
+            self.description.filter(|d| !d.is_empty()),
+            status,
+            due,
+            self.categories,
+        )
+        .check(window, now)?;
+        task.set_progress(self.percent_complete);
+        if let Some(current) = current {
+            task.set_estimate(current.estimate());
+            task.set_colour(current.colour().cloned());
+            let description_cy = current
+                .description_cy()
+                .filter(|_| task.description().is_some());
+            task.set_welsh(
+                current.title_cy().map(str::to_string),
+                description_cy.map(str::to_string),
+            );
+            if task.status == current.status {
+                task.set_status_reason(current.status_reason().map(str::to_string));
+            }
+        }
+        Ok(task)
+    }
+}
+
+/// Get the `STATUS` value corresponding to a status.
+///
+/// iCalendar has no blocked status, so blocked tasks still need action, and
+/// no custom statuses, so tasks with them are in process.
+fn status_name(status: &TodoStatus) -> &'static str {
+    match status {
+        TodoStatus::NotStarted | TodoStatus::Blocked => "NEEDS-ACTION",
+        TodoStatus::InProgress | TodoStatus::Custom(_) => "IN-PROCESS",
+        TodoStatus::Complete => "COMPLETED",
+        TodoStatus::Cancelled => "CANCELLED",
+    }
+}
+
+/// Get the status corresponding to a `STATUS` value, if any.
+fn parse_status(value: &str) -> Option<TodoStatus> {
+    match value.trim().to_ascii_uppercase().as_str() {
+        "NEEDS-ACTION" => Some(TodoStatus::NotStarted),
+        "IN-PROCESS" => Some(TodoStatus::InProgress),
+        "COMPLETED" => Some(TodoStatus::Complete),
+        "CANCELLED" => Some(TodoStatus::Cancelled),
+        _ => None,
+    }
+}
+
+/// Parse a `DUE` value, which is a date if its parameters say so.
+fn parse_due(params: &str, value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    let is_date = params
+        .split(';')
+        .any(|param| param.eq_ignore_ascii_case("VALUE=DATE"))
+        || value.len() == 8;
+    if is_date {
+        NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .map(end_of_day)
+    } else {
+        NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|due| due.and_utc())
+    }
+}
+
+/// Format a date & time as an iCalendar UTC date-time.
+fn format_utc(moment: &DateTime<Utc>) -> String {
+    moment.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Split a content line into its upper-cased name, its parameters and its
+/// value.
+///
+/// Returns `None` for lines which aren't properties.
+fn split_property(line: &str) -> Option<(String, &str, &str)> {
+    // the value starts at the first colon outside of a quoted parameter value
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| {
+        match c {
+            '"' => quoted = !quoted,
+            ':' if !quoted => return Some(i),
+            _ => {}
+        }
+        None
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let (name, params) = head.split_once(';').unwrap_or((head, ""));
+    Some((name.trim().to_ascii_uppercase(), params, value))
+}
+
+/// Escape text for use as a property value.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Unescape a text property value.
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n' | 'N') => text.push('\n'),
+                Some(other) => text.push(other),
+                None => {}
+            }
+        } else {
+            text.push(c);
+        }
+    }
+    text
+}
+
+/// Split a list of text values on unescaped commas, unescaping each.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ',' => {
+                items.push(unescape(&value[start..i]));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(unescape(&value[start..]));
+    items
+}
+
+/// Fold a content line into lines of at most [`MAX_LINE_LENGTH`] octets,
+/// each ending with a line break.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            // the leading space counts towards the continuation's length
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+    use crate::TaskDiff;
+
+    fn window() -> DueWindow {
+        DueWindow::default()
+    }
+
+    #[test]
+    fn round_trip() {
+        let due = "2025-06-01T17:30:00Z".parse::<DateTime<Utc>>().unwrap();
+        let mut task = TodoTask::new(
+            "Plan; then act, quickly".to_string(),
+            Some(format!("{}\nSecond line", "long ".repeat(20))),
+            TodoStatus::InProgress,
+            &due,
+            due,
+        );
+        task.set_tags(vec!["work".to_string(), "q2".to_string()]);
+        task.set_progress(Some(40));
+        task.set_estimate(Some(TimeDelta::hours(2)));
+
+        let id = Uuid::new_v4();
+        let calendar = to_vcalendar(id, &task, due);
+        assert!(calendar.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert!(calendar.contains("DTSTAMP:20250601T173000Z\r\n"));
+        assert!(calendar.contains("SUMMARY:Plan\\; then act\\, quickly\r\n"));
+        assert!(calendar.contains("DUE:20250601T173000Z\r\n"));
+
+        let todo = VTodo::parse(&calendar).unwrap();
+        assert_eq!(todo.uid, Some(id.to_string()));
+        let parsed = todo.into_task(Some(&task), window(), due).unwrap();
+        assert!(TaskDiff::between(&parsed, &task).is_empty());
+    }
+
+    #[test]
+    fn client_todo() {
+        let calendar = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VTODO\r\n\
+            UID:abc\r\nSUMMARY:Buy\r\n  milk\r\nDUE;VALUE=DATE:20250310\r\n\
+            STATUS:NEEDS-ACTION\r\nCATEGORIES:Shopping,At home\r\n\
+            BEGIN:VALARM\r\nDESCRIPTION:Reminder\r\nEND:VALARM\r\n\
+            END:VTODO\r\nEND:VCALENDAR\r\n";
+        let todo = VTodo::parse(calendar).unwrap();
+        assert_eq!(todo.summary, "Buy milk");
+        assert_eq!(todo.description, None);
+
+        let now = "2025-03-01T12:00:00Z".parse().unwrap();
+        let mut blocked = todo.clone().into_task(None, window(), now).unwrap();
+        assert_eq!(blocked.due().to_rfc3339(), "2025-03-10T23:59:59+00:00");
+        assert_eq!(blocked.tags(), ["Shopping", "At-home"]);
+        blocked.status = TodoStatus::Blocked;
+        assert_eq!(
+            todo.into_task(Some(&blocked), window(), now)
+                .unwrap()
+                .status,
+            TodoStatus::Blocked
+        );
+    }
+
+    #[test]
+    fn invalid_todo() {
+        assert!(VTodo::parse("BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n").is_err());
+        let no_due = VTodo::parse("BEGIN:VTODO\nSUMMARY:x\nEND:VTODO\n").unwrap();
+        let now = "2025-03-01T12:00:00Z".parse().unwrap();
+        assert_eq!(
+            no_due.into_task(None, window(), now).unwrap_err(),
+            NO_DUE_DATE
+        );
+        let distant =
+            VTodo::parse("BEGIN:VTODO\nSUMMARY:x\nDUE:21250310T120000Z\nEND:VTODO\n").unwrap();
+        let error = distant.into_task(None, window(), now).unwrap_err();
+        assert_eq!(TodoTask::invalid_field(error), Some("due"));
+        assert!(VTodo::parse("BEGIN:VTODO\nDUE:tomorrow\nEND:VTODO\n").is_err());
+    }
+}
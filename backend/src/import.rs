@@ -0,0 +1,91 @@
+//! Conversion of tasks exported from other to-do applications.
+//!
+//! Each converter maps an export onto [`TodoTaskUnchecked`]s, which still need
+//! validating. Items which can't be represented as tasks at all, such as
+//! those without a due date when no default is given, are converted to an
+//! error instead.
+
+pub mod todoist;
+pub mod trello;
+
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::TodoTaskUnchecked;
+
+/// Application which tasks were exported from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    /// Todoist, as JSON from its REST or Sync API.
+    Todoist,
+    /// Trello, as a board exported to JSON.
+    Trello,
+}
+
+impl ImportFormat {
+    /// Convert the JSON `export` of this format into tasks, in the order they
+    /// appear in it.
+    ///
+    /// Items without a due date are due at `default_due`, if given.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `export` isn't valid JSON in this format.
+    pub fn convert(
+        self,
+        export: &str,
+        default_due: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Result<TodoTaskUnchecked, &'static str>>, serde_json::Error> {
+        match self {
+            Self::Todoist => todoist::convert(export, default_due),
+            Self::Trello => trello::convert(export, default_due),
+        }
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "todoist" => Ok(Self::Todoist),
+            "trello" => Ok(Self::Trello),
+            _ => Err("unknown import format"),
+        }
+    }
+}
+
+/// Error for items which have no due date, and no default was given.
+pub(crate) const NO_DUE_DATE: &str = "item has no due date";
+
+/// Convert a label from another application into a tag, replacing
+/// whitespace with `-`.
+///
+/// Returns `None` for blank labels.
+pub(crate) fn to_tag(label: &str) -> Option<String> {
+    let tag = label.split_whitespace().collect::<Vec<_>>().join("-");
+    (!tag.is_empty()).then_some(tag)
+}
+
+/// Get the last moment of `date`, in UTC, for items due on a day rather than
+/// at a time.
+pub(crate) fn end_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(NaiveTime::MIN).and_utc() + TimeDelta::days(1) - TimeDelta::seconds(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("urgent", Some("urgent"))]
+    #[case("  to do  later ", Some("to-do-later"))]
+    #[case(" ", None)]
+    fn label_to_tag(#[case] label: &str, #[case] expected: Option<&str>) {
+        assert_eq!(to_tag(label).as_deref(), expected);
+    }
+}
@@ -0,0 +1,142 @@
+//! Conversion of tasks exported from Todoist.
+//!
+//! Both a JSON array of tasks from the REST API and an object with an `items`
+//! array from the Sync API are accepted. Completed tasks are imported as
+//! [`Complete`](TodoStatus::Complete), and labels become tags.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::Deserialize;
+
+use super::{NO_DUE_DATE, end_of_day, to_tag};
+use crate::{TodoStatus, TodoTaskUnchecked};
+
+/// Todoist export, from either API.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Export {
+    Rest(Vec<Item>),
+    Sync { items: Vec<Item> },
+}
+
+/// Task in a Todoist export.
+#[derive(Deserialize)]
+struct Item {
+    content: String,
+    #[serde(default)]
+    description: String,
+    /// Whether the task is complete, in the REST API.
+    #[serde(default)]
+    is_completed: bool,
+    /// Whether the task is complete, in the Sync API.
+    #[serde(default)]
+    checked: bool,
+    due: Option<Due>,
+    #[serde(default)]
+    labels: Vec<String>,
+}
+
+/// Due date of a Todoist task.
+#[derive(Deserialize)]
+struct Due {
+    /// Date, or a floating date & time without a timezone.
+    date: String,
+    /// Date & time in UTC, for tasks due at a time, in the REST API.
+    datetime: Option<DateTime<Utc>>,
+}
+
+impl Due {
+    /// Get the moment the task is due, or `None` if it can't be parsed.
+    ///
+    /// Floating times are taken to be in UTC, and tasks due on a day are due
+    /// at its end.
+    fn resolve(&self) -> Option<DateTime<Utc>> {
+        self.datetime
+            .or_else(|| self.date.parse().ok())
+            .or_else(|| {
+                NaiveDateTime::parse_from_str(&self.date, "%Y-%m-%dT%H:%M:%S%.f")
+                    .ok()
+                    .map(|due| due.and_utc())
+            })
+            .or_else(|| self.date.parse::<NaiveDate>().ok().map(end_of_day))
+    }
+}
+
+/// Convert a Todoist `export` into tasks, see
+/// [`ImportFormat::convert`](super::ImportFormat::convert).
+///
+/// # Errors
+///
+/// Returns an error if `export` isn't a Todoist JSON export.
+pub fn convert(
+    export: &str,
+    default_due: Option<DateTime<Utc>>,
+) -> Result<Vec<Result<TodoTaskUnchecked, &'static str>>, serde_json::Error> {
+    let (Export::Rest(items) | Export::Sync { items }) = serde_json::from_str(export)?;
+    Ok(items
+        .into_iter()
+        .map(|item| {
+            let due = match &item.due {
+                Some(due) => due.resolve().ok_or("item has an unrecognised due date")?,
+                None => default_due.ok_or(NO_DUE_DATE)?,
+            };
+            let status = if item.is_completed || item.checked {
+                TodoStatus::Complete
+            } else {
+                TodoStatus::NotStarted
+            };
+            Ok(TodoTaskUnchecked::new(
+                item.content,
+                Some(item.description).filter(|d| !d.is_empty()),
+                status,
+                due,
+                item.labels.iter().filter_map(|l| to_tag(l)).collect(),
+            ))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TodoTask;
+
+    #[test]
+    fn rest_export() {
+        let export = r#"[
+            {"content": "Buy milk", "description": "", "is_completed": false,
+                "due": {"date": "2025-01-02", "datetime": "2025-01-02T09:30:00Z"},
+                "labels": ["shopping", "at home"]},
+            {"content": "File taxes", "description": "online", "is_completed": true,
+                "due": {"date": "2025-01-31"}, "labels": []},
+            {"content": "Someday", "labels": []}
+        ]"#;
+        let tasks = convert(export, None).unwrap();
+        assert_eq!(tasks.len(), 3);
+
+        let milk = TodoTask::try_from(tasks[0].clone().unwrap()).unwrap();
+        assert_eq!(milk.title(), "Buy milk");
+        assert_eq!(milk.description(), None);
+        assert_eq!(milk.due().to_rfc3339(), "2025-01-02T09:30:00+00:00");
+        assert_eq!(milk.tags(), ["shopping", "at-home"]);
+
+        let taxes = TodoTask::try_from(tasks[1].clone().unwrap()).unwrap();
+        assert_eq!(taxes.status, TodoStatus::Complete);
+        assert_eq!(taxes.due().to_rfc3339(), "2025-01-31T23:59:59+00:00");
+
+        assert_eq!(tasks[2].as_ref().unwrap_err(), &NO_DUE_DATE);
+    }
+
+    #[test]
+    fn sync_export() {
+        let export = r#"{"items": [
+            {"content": "Call back", "checked": true, "labels": [],
+                "due": {"date": "2025-03-04T15:00:00"}}
+        ]}"#;
+        let default_due = Utc::now();
+        let tasks = convert(export, Some(default_due)).unwrap();
+
+        let task = TodoTask::try_from(tasks[0].clone().unwrap()).unwrap();
+        assert_eq!(task.status, TodoStatus::Complete);
+        assert_eq!(task.due().to_rfc3339(), "2025-03-04T15:00:00+00:00");
+    }
+}
@@ -0,0 +1,140 @@
+//! Conversion of cards from a Trello board exported to JSON.
+//!
+//! Archived cards, and cards on archived lists, are skipped. Cards whose due
+//! date is marked complete are imported as
+//! [`Complete`](TodoStatus::Complete), and label names (or colours, for
+//! unnamed labels) become tags.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use super::{NO_DUE_DATE, to_tag};
+use crate::{TodoStatus, TodoTaskUnchecked};
+
+/// Trello board export.
+#[derive(Deserialize)]
+struct Board {
+    #[serde(default)]
+    lists: Vec<List>,
+    cards: Vec<Card>,
+}
+
+/// List of cards on a Trello board.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct List {
+    id: String,
+    #[serde(default)]
+    closed: bool,
+}
+
+/// Card on a Trello board.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Card {
+    name: String,
+    #[serde(default)]
+    desc: String,
+    due: Option<DateTime<Utc>>,
+    #[serde(default)]
+    due_complete: bool,
+    /// Whether the card is archived.
+    #[serde(default)]
+    closed: bool,
+    id_list: Option<String>,
+    #[serde(default)]
+    labels: Vec<Label>,
+}
+
+/// Label on a Trello card.
+#[derive(Deserialize)]
+struct Label {
+    #[serde(default)]
+    name: String,
+    color: Option<String>,
+}
+
+/// Convert a Trello board `export` into tasks, see
+/// [`ImportFormat::convert`](super::ImportFormat::convert).
+///
+/// # Errors
+///
+/// Returns an error if `export` isn't a Trello board JSON export.
+pub fn convert(
+    export: &str,
+    default_due: Option<DateTime<Utc>>,
+) -> Result<Vec<Result<TodoTaskUnchecked, &'static str>>, serde_json::Error> {
+    let board: Board = serde_json::from_str(export)?;
+    let archived_lists: HashSet<_> = board
+        .lists
+        .iter()
+        .filter(|list| list.closed)
+        .map(|list| list.id.as_str())
+        .collect();
+
+    Ok(board
+        .cards
+        .into_iter()
+        .filter(|card| {
+            !card.closed
+                && !card
+                    .id_list
+                    .as_deref()
+                    .is_some_and(|list| archived_lists.contains(list))
+        })
+        .map(|card| {
+            let status = if card.due_complete {
+                TodoStatus::Complete
+            } else {
+                TodoStatus::NotStarted
+            };
+            let tags = card
+                .labels
+                .iter()
+                .filter_map(|label| to_tag(&label.name).or_else(|| to_tag(label.color.as_deref()?)))
+                .collect();
+            Ok(TodoTaskUnchecked::new(
+                card.name,
+                Some(card.desc).filter(|d| !d.is_empty()),
+                status,
+                card.due.or(default_due).ok_or(NO_DUE_DATE)?,
+                tags,
+            ))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TodoTask;
+
+    #[test]
+    fn board_export() {
+        let export = r#"{
+            "name": "Team board",
+            "lists": [{"id": "l1", "name": "Doing", "closed": false},
+                {"id": "l2", "name": "Old", "closed": true}],
+            "cards": [
+                {"name": "Write report", "desc": "Quarterly", "due": "2025-02-01T17:00:00.000Z",
+                    "dueComplete": true, "closed": false, "idList": "l1",
+                    "labels": [{"name": "Needs review", "color": "red"}, {"name": "", "color": "green"}]},
+                {"name": "Archived card", "desc": "", "due": null, "closed": true, "idList": "l1"},
+                {"name": "On archived list", "desc": "", "due": null, "closed": false, "idList": "l2"},
+                {"name": "No due date", "desc": "", "due": null, "closed": false, "idList": "l1"}
+            ]
+        }"#;
+        let tasks = convert(export, None).unwrap();
+        assert_eq!(tasks.len(), 2);
+
+        let report = TodoTask::try_from(tasks[0].clone().unwrap()).unwrap();
+        assert_eq!(report.title(), "Write report");
+        assert_eq!(report.description(), Some("Quarterly"));
+        assert_eq!(report.status, TodoStatus::Complete);
+        assert_eq!(report.tags(), ["Needs-review", "green"]);
+
+        assert_eq!(tasks[1].as_ref().unwrap_err(), &NO_DUE_DATE);
+    }
+}
@@ -0,0 +1,87 @@
+//! Bulk operations run in the background, such as large imports, which
+//! clients follow by polling rather than holding a request open.
+//!
+//! Jobs are kept in the database, so any replica can report on them and
+//! cancel them, though only the replica which started a job runs it.
+//! Cancelling a job stops it between items, keeping the changes already
+//! made.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "postgres")]
+use sqlx::{FromRow, prelude::Type};
+use uuid::Uuid;
+
+/// Number of days finished jobs are kept for.
+pub const JOB_RETENTION_DAYS: i32 = 7;
+
+/// Kind of bulk operation a job runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "postgres", derive(Type))]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "postgres", sqlx(type_name = "job_kind"))]
+#[cfg_attr(feature = "postgres", sqlx(rename_all = "snake_case"))]
+pub enum JobKind {
+    /// Import of tasks from another application.
+    Import,
+    /// Change of the due dates of tasks matching filters.
+    Reschedule,
+}
+
+/// Stage a job has reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "postgres", derive(Type))]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "postgres", sqlx(type_name = "job_status"))]
+#[cfg_attr(feature = "postgres", sqlx(rename_all = "snake_case"))]
+pub enum JobStatus {
+    /// The job is still running.
+    Running,
+    /// The job finished, though some items may have been rejected.
+    Succeeded,
+    /// The job stopped early because of an error.
+    Failed,
+    /// The job was stopped early on request.
+    Cancelled,
+}
+
+/// Item a job couldn't process.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemError {
+    /// Position of the item among those processed, from 0.
+    pub index: usize,
+    /// Human-readable reason the item wasn't processed.
+    pub error: String,
+}
+
+/// Bulk operation run in the background.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct BulkJob {
+    /// ID of the job.
+    pub id: Uuid,
+    /// Owner who started the job.
+    pub owner: Option<String>,
+    /// Operation the job runs.
+    pub kind: JobKind,
+    /// Stage the job has reached.
+    pub status: JobStatus,
+    /// Number of items to process, if known yet.
+    pub total: Option<i32>,
+    /// Number of items processed so far.
+    pub processed: i32,
+    /// Items which couldn't be processed.
+    #[cfg_attr(feature = "postgres", sqlx(json))]
+    pub errors: Vec<ItemError>,
+    /// Result of the operation, as its synchronous endpoint would respond,
+    /// once the job has succeeded.
+    pub result: Option<serde_json::Value>,
+    /// Why the job failed, if it did.
+    pub error: Option<String>,
+    /// Whether the job has been asked to stop.
+    pub cancel_requested: bool,
+    /// Date & time at which the job was started.
+    pub created_at: DateTime<Utc>,
+    /// Date & time at which the job stopped, if it has.
+    pub finished_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,317 @@
+//! JSON Schema of tasks as given in request bodies, and validation of
+//! bodies against it.
+//!
+//! Validation reports every problem with a body at once, each with the JSON
+//! pointer of the value it is in, rather than only the first problem found by
+//! deserializing it. Only the keywords used by [`task`] are checked; any
+//! others are ignored, leaving their checks to deserialization.
+
+use std::fmt;
+
+use chrono::DateTime;
+use serde_json::{Map, Value, json};
+
+use crate::{MAX_ESTIMATE_HOURS, PALETTE, TodoTaskUnchecked, workflow::Workflow};
+
+/// Schema of a task as given in a request body, such as to `POST /task`,
+/// with the statuses of `workflow`.
+///
+/// Unknown fields are allowed by the schema; whether they are accepted is up
+/// to the server.
+///
+/// # Panics
+///
+/// Never; statuses always serialize.
+#[must_use]
+pub fn task(workflow: &Workflow) -> Value {
+    let statuses: Vec<_> = workflow
+        .all()
+        .map(|status| serde_json::to_value(status).expect("statuses serialize"))
+        .collect();
+    let optional_text = json!({ "type": ["string", "null"], "minLength": 1 });
+
+    let properties = json!({
+        "title": { "type": "string", "minLength": 1 },
+        "description": optional_text,
+        "status": { "enum": statuses },
+        "status_reason": {
+            "type": ["string", "null"],
+            "minLength": 1,
+            "description": "Why work stopped; only for Cancelled or Blocked tasks.",
+        },
+        "due": { "type": "string", "format": "date-time" },
+        "tags": {
+            "type": "array",
+            "items": { "type": "string", "minLength": 1 },
+            "description": "Labels, none of which may contain whitespace.",
+        },
+        "estimate": {
+            "type": ["integer", "null"],
+            "minimum": 0,
+            "maximum": MAX_ESTIMATE_HOURS * 3600,
+            "description": "Estimated effort, in seconds.",
+        },
+        "progress": { "type": ["integer", "null"], "minimum": 0, "maximum": 100 },
+        "colour": {
+            "type": ["string", "null"],
+            "description": format!(
+                "One of {}, or an RGB hex code like #1e90ff.",
+                PALETTE.join(", ")
+            ),
+        },
+        "title_cy": optional_text,
+        "description_cy": optional_text,
+    });
+    debug_assert!(
+        TodoTaskUnchecked::FIELDS
+            .iter()
+            .all(|field| properties.get(field).is_some())
+    );
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Task",
+        "type": "object",
+        "properties": properties,
+        "required": ["title", "status", "due"],
+    })
+}
+
+/// Problem found validating a value against a schema.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    /// JSON pointer to the value with the problem, which is empty for the
+    /// whole value.
+    pub path: String,
+    /// Description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "body"
+        } else {
+            &self.path
+        };
+        write!(f, "{path}: {}", self.message)
+    }
+}
+
+/// Validate `instance` against `schema`, returning every problem found.
+#[must_use]
+pub fn validate(schema: &Value, instance: &Value) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    check(schema, instance, "", &mut violations);
+    violations
+}
+
+/// Add the problems with `instance`, found at `path`, to `violations`.
+fn check(schema: &Value, instance: &Value, path: &str, violations: &mut Vec<Violation>) {
+    let mut violation = |message: String| {
+        violations.push(Violation {
+            path: path.to_owned(),
+            message,
+        });
+    };
+
+    if let Some(types) = schema.get("type") {
+        let types: Vec<_> = match types {
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            types => types.as_str().into_iter().collect(),
+        };
+        if !types.iter().any(|kind| is_type(instance, kind)) {
+            violation(format!(
+                "expected {}, found {}",
+                types.join(" or "),
+                type_name(instance)
+            ));
+            // the other keywords would only repeat the problem
+            return;
+        }
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(instance) {
+            let allowed: Vec<_> = allowed.iter().map(Value::to_string).collect();
+            violation(format!("expected one of {}", allowed.join(", ")));
+        }
+    }
+
+    match instance {
+        Value::String(string) => {
+            let length = string.chars().count();
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                if (length as u64) < min {
+                    violation(if min == 1 {
+                        "must not be empty".to_owned()
+                    } else {
+                        format!("must be at least {min} characters long")
+                    });
+                }
+            }
+            if schema.get("format").and_then(Value::as_str) == Some("date-time")
+                && DateTime::parse_from_rfc3339(string).is_err()
+            {
+                violation("not a valid RFC3339 timestamp".to_owned());
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+                if number < min {
+                    violation(format!("must be at least {min}"));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+                if number > max {
+                    violation(format!("must be at most {max}"));
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(item_schema, item, &format!("{path}/{i}"), violations);
+                }
+            }
+        }
+        Value::Object(members) => check_object(schema, members, path, violations),
+        Value::Null | Value::Bool(_) => {}
+    }
+}
+
+/// Add the problems with the members of the object `members`, found at
+/// `path`, to `violations`.
+fn check_object(
+    schema: &Value,
+    members: &Map<String, Value>,
+    path: &str,
+    violations: &mut Vec<Violation>,
+) {
+    for required in schema
+        .get("required")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+    {
+        if !members.contains_key(required) {
+            violations.push(Violation {
+                path: format!("{path}/{}", escape(required)),
+                message: "missing required field".to_owned(),
+            });
+        }
+    }
+    if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+        for (name, value) in members {
+            if let Some(property) = properties.get(name) {
+                check(
+                    property,
+                    value,
+                    &format!("{path}/{}", escape(name)),
+                    violations,
+                );
+            }
+        }
+    }
+}
+
+/// Whether `instance` is of the JSON Schema type `kind`.
+fn is_type(instance: &Value, kind: &str) -> bool {
+    match kind {
+        "null" => instance.is_null(),
+        "boolean" => instance.is_boolean(),
+        "string" => instance.is_string(),
+        "number" => instance.is_number(),
+        "integer" => instance.is_i64() || instance.is_u64(),
+        "array" => instance.is_array(),
+        "object" => instance.is_object(),
+        _ => false,
+    }
+}
+
+/// Name of the JSON Schema type of `instance`.
+fn type_name(instance: &Value) -> &'static str {
+    match instance {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Escape `name` for use as a reference token of a JSON pointer.
+fn escape(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_paths_of_invalid_fields() {
+        let body = json!({
+            "title": "",
+            "status": "Finished",
+            "due": "tomorrow",
+            "tags": ["urgent", 3],
+            "estimate": -60,
+            "progress": 50,
+            "a/b": true,
+        });
+
+        let mut messages: Vec<_> = validate(&task(&Workflow::default()), &body)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        messages.sort();
+        assert_eq!(
+            messages,
+            [
+                "/due: not a valid RFC3339 timestamp",
+                "/estimate: must be at least 0",
+                "/status: expected one of \"NotStarted\", \"InProgress\", \"Blocked\", \
+                \"Complete\", \"Cancelled\"",
+                "/tags/1: expected string, found integer",
+                "/title: must not be empty",
+            ]
+        );
+
+        assert_eq!(
+            validate(&task(&Workflow::default()), &json!({ "title": "Task" }))[0].to_string(),
+            "/status: missing required field"
+        );
+        assert!(
+            validate(
+                &task(&Workflow::default()),
+                &json!({
+                    "title": "Task",
+                    "description": null,
+                    "status": "InProgress",
+                    "due": "2025-05-01T12:00:00+01:00",
+                    "estimate": 3600,
+                })
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn allows_custom_statuses() {
+        let workflow = Workflow {
+            statuses: vec!["InReview".to_owned()],
+            transitions: None,
+        };
+        let body = json!({
+            "title": "Task",
+            "status": "InReview",
+            "due": "2025-05-01T12:00:00+01:00",
+        });
+        assert!(validate(&task(&workflow), &body).is_empty());
+        assert_eq!(validate(&task(&Workflow::default()), &body).len(), 1);
+    }
+}
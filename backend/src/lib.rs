@@ -0,0 +1,84 @@
+//! Library for modelling, validating and querying [`TodoTask`] objects.
+//!
+//! Storing tasks, and everything else needing the database, is behind the
+//! `postgres` feature, and the glue for serving them with axum behind the
+//! `server` feature, both on by default. Talking to other services, such as
+//! SMTP relays and SMS gateways, is behind the `net` feature, which
+//! `postgres` turns on. Without them, only the task model and what works
+//! with it offline is built, with neither tokio nor sqlx, so it also builds
+//! for `wasm32-wasip2` to validate tasks wherever they are received.
+
+#![deny(clippy::pedantic)]
+#![deny(missing_docs)]
+
+pub mod attachments;
+#[cfg(feature = "postgres")]
+pub mod breaker;
+pub mod calendar;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod clock;
+mod colour;
+pub mod digest;
+#[cfg(feature = "net")]
+pub mod egress;
+pub mod email;
+#[cfg(feature = "postgres")]
+mod error;
+pub mod explain;
+#[cfg(feature = "parquet")]
+pub mod export;
+pub mod feed;
+pub mod filter;
+#[cfg(feature = "net")]
+pub mod gov_notify;
+pub mod graph;
+mod history;
+pub mod hooks;
+pub mod i18n;
+pub mod ical;
+pub mod import;
+pub mod jobs;
+pub mod json_schema;
+mod links;
+pub mod loadgen;
+pub mod markdown;
+pub mod mentions;
+#[cfg(feature = "postgres")]
+pub mod metrics;
+pub mod notify;
+pub mod proto;
+pub mod scan;
+pub mod schedule;
+pub mod security;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod sla;
+#[cfg(feature = "net")]
+pub mod sms;
+pub mod smtp;
+pub mod stats;
+#[cfg(feature = "postgres")]
+pub mod store;
+pub mod sync;
+mod tasks;
+pub mod thumbnails;
+pub mod tokens;
+pub mod tracking;
+pub mod users;
+#[cfg(feature = "postgres")]
+pub mod webpush;
+#[cfg(feature = "net")]
+pub mod workers;
+pub mod workflow;
+
+pub use colour::{Colour, PALETTE};
+#[cfg(feature = "postgres")]
+pub use error::StoreError;
+pub use filter::FilterExpr;
+pub use history::{FieldChange, TaskDiff, TaskEvent};
+pub use links::{TaskLink, TaskLinkKind};
+pub use tasks::{
+    DEFAULT_DUE_WINDOW_YEARS, DueWindow, InvalidTask, MAX_ESTIMATE_HOURS, TaskRecord, TodoStatus,
+    TodoTask, TodoTaskUnchecked,
+};
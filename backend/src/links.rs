@@ -0,0 +1,34 @@
+//! Typed relationships between [`TodoTask`](crate::TodoTask)s.
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "postgres")]
+use sqlx::{FromRow, prelude::Type};
+use uuid::Uuid;
+
+/// Kind of relationship from one task to another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "postgres", derive(Type))]
+#[cfg_attr(feature = "postgres", sqlx(type_name = "task_link_kind"))]
+#[cfg_attr(feature = "postgres", sqlx(rename_all = "snake_case"))]
+pub enum TaskLinkKind {
+    /// The tasks are related in some unspecified way.
+    RelatesTo,
+    /// The source task is a duplicate of the target task.
+    Duplicates,
+    /// The source task replaces the target task.
+    Supersedes,
+    /// The source task can't be finished before the target task.
+    DependsOn,
+}
+
+/// Directed relationship between two tasks.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct TaskLink {
+    /// ID of the task the link is from.
+    pub source: Uuid,
+    /// ID of the task the link is to.
+    pub target: Uuid,
+    /// Kind of the relationship.
+    pub kind: TaskLinkKind,
+}
@@ -0,0 +1,369 @@
+//! Generation of realistic tasks in bulk, for datasets to test performance
+//! against.
+//!
+//! Tasks are generated from a seed, so a seed always gives the same tasks
+//! for the same starting time. Their statuses, due dates, tags and owners
+//! are skewed the way a real caseload's are: most work is open and due
+//! within weeks, a few tags are on most tasks, and a few owners have most
+//! of the work.
+
+#[cfg(feature = "postgres")]
+use std::sync::Arc;
+
+use chrono::{DateTime, TimeDelta, Timelike, Utc};
+use rand::{
+    Rng, SeedableRng,
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    seq::SliceRandom,
+};
+#[cfg(feature = "postgres")]
+use tokio::task::JoinSet;
+#[cfg(feature = "postgres")]
+use tracing::info;
+use uuid::{Builder, Uuid};
+
+#[cfg(feature = "postgres")]
+use crate::{
+    StoreError,
+    store::{TaskStore, act_as},
+};
+use crate::{TodoStatus, TodoTask};
+
+/// Prefix of the names of owners of generated tasks, so they can be told
+/// apart from real owners.
+pub const OWNER_PREFIX: &str = "loadgen-";
+
+/// Number of tasks stored between reports of progress.
+#[cfg(feature = "postgres")]
+const PROGRESS_INTERVAL: u64 = 1000;
+
+/// Statuses of generated tasks, with the relative number of tasks in each.
+const STATUSES: [(TodoStatus, u32); 5] = [
+    (TodoStatus::NotStarted, 35),
+    (TodoStatus::InProgress, 25),
+    (TodoStatus::Complete, 30),
+    (TodoStatus::Blocked, 5),
+    (TodoStatus::Cancelled, 5),
+];
+
+/// Tags of generated tasks, most common first.
+const TAGS: [&str; 12] = [
+    "urgent",
+    "hearing",
+    "review",
+    "bundle",
+    "appeal",
+    "family",
+    "civil",
+    "crime",
+    "tribunal",
+    "probate",
+    "listing",
+    "correspondence",
+];
+
+/// First words of the titles of generated tasks.
+const ACTIONS: [&str; 10] = [
+    "Review", "Prepare", "File", "Chase", "Update", "Schedule", "Draft", "Send", "Check", "Archive",
+];
+
+/// Last words of the titles of generated tasks.
+const SUBJECTS: [&str; 10] = [
+    "hearing bundle",
+    "case notes",
+    "witness statement",
+    "court order",
+    "application",
+    "payment",
+    "correspondence",
+    "listing request",
+    "evidence",
+    "judgment",
+];
+
+/// Estimates of generated tasks which are estimated, in hours.
+const ESTIMATE_HOURS: [i64; 6] = [1, 2, 4, 8, 16, 40];
+
+/// Reasons given for some stopped tasks being stopped.
+const STOP_REASONS: [&str; 3] = [
+    "Waiting for the applicant",
+    "Duplicate of another case",
+    "Hearing adjourned",
+];
+
+/// Task generated by a [`TaskGenerator`].
+#[derive(Clone, Debug)]
+pub struct GeneratedTask {
+    /// ID to store the task with.
+    pub id: Uuid,
+    /// Owner of the task, or `None` for the anonymous owner.
+    pub owner: Option<String>,
+    /// The task.
+    pub task: TodoTask,
+}
+
+/// Endless source of realistic tasks, generated from a seed.
+#[derive(Clone, Debug)]
+pub struct TaskGenerator {
+    rng: StdRng,
+    /// Time due dates and completions are relative to.
+    now: DateTime<Utc>,
+    statuses: WeightedIndex<u32>,
+    /// Weights of [`TAGS`] and owners, falling with their rank.
+    tags: WeightedIndex<f64>,
+    /// `None` if tasks are only owned by the anonymous owner.
+    owners: Option<WeightedIndex<f64>>,
+}
+
+/// Weights of `count` things, in proportion to the reciprocal of their rank
+/// as by Zipf's law.
+fn zipf(count: u32) -> Result<WeightedIndex<f64>, rand::distributions::WeightedError> {
+    WeightedIndex::new((1..=count).map(|rank| 1.0 / f64::from(rank)))
+}
+
+impl TaskGenerator {
+    /// Create a generator of tasks from `seed`, shared between `owners`
+    /// owners, or only the anonymous owner if 0, and due around `now`.
+    #[must_use]
+    pub fn new(seed: u64, owners: u32, now: DateTime<Utc>) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            now,
+            statuses: WeightedIndex::new(STATUSES.map(|(_, weight)| weight))
+                .unwrap_or_else(|_| unreachable!("status weights are positive")),
+            tags: zipf(TAGS.len().try_into().unwrap_or(u32::MAX))
+                .unwrap_or_else(|_| unreachable!("there are tags")),
+            owners: zipf(owners).ok(),
+        }
+    }
+
+    /// Instant within working hours, `days` days from now.
+    fn working_time(&mut self, days: i64) -> DateTime<Utc> {
+        let day = self.now + TimeDelta::days(days);
+        let minutes = self.rng.gen_range(9 * 4..17 * 4) * 15;
+        day.with_hour(0)
+            .and_then(|day| day.with_minute(0))
+            .and_then(|day| day.with_second(0))
+            .and_then(|day| day.with_nanosecond(0))
+            .unwrap_or(day)
+            + TimeDelta::minutes(minutes)
+    }
+
+    /// Generate the next task.
+    pub fn generate(&mut self) -> GeneratedTask {
+        let id = Builder::from_random_bytes(self.rng.r#gen()).into_uuid();
+        let status = STATUSES[self.statuses.sample(&mut self.rng)].0.clone();
+
+        // open work is mostly due in the next few weeks, and finished work
+        // was mostly due in the last few months
+        let (earliest, latest) = match status {
+            TodoStatus::Complete | TodoStatus::Cancelled => (-120, 14),
+            _ => (-30, 120),
+        };
+        let days =
+            (self.rng.gen_range(earliest..=latest) + self.rng.gen_range(earliest..=latest)) / 2;
+        let due = self.working_time(days);
+
+        let title = format!(
+            "{} {}",
+            ACTIONS.choose(&mut self.rng).unwrap_or(&ACTIONS[0]),
+            SUBJECTS.choose(&mut self.rng).unwrap_or(&SUBJECTS[0])
+        );
+        let description = self
+            .rng
+            .gen_bool(0.6)
+            .then(|| format!("Case {}", self.rng.gen_range(100_000..1_000_000)));
+        let mut task = TodoTask::new(title, description, TodoStatus::NotStarted, &due, self.now);
+
+        // tasks are completed up to a few days before they are due
+        let finished = (due - TimeDelta::hours(self.rng.gen_range(0..120))).min(self.now);
+        task.transition(status.clone(), finished);
+        if status.is_stopped() && self.rng.gen_bool(0.5) {
+            let reason = STOP_REASONS
+                .choose(&mut self.rng)
+                .unwrap_or(&STOP_REASONS[0]);
+            task.set_status_reason(Some((*reason).to_owned()));
+        }
+
+        let mut tags: Vec<String> = (0..self.rng.gen_range(0..=3))
+            .map(|_| TAGS[self.tags.sample(&mut self.rng)].to_owned())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        task.set_tags(tags);
+        if self.rng.gen_bool(0.5) {
+            let hours = ESTIMATE_HOURS.choose(&mut self.rng).unwrap_or(&1);
+            task.set_estimate(Some(TimeDelta::hours(*hours)));
+        }
+
+        let owner = self
+            .owners
+            .as_ref()
+            .map(|weights| format!("{OWNER_PREFIX}{:03}", weights.sample(&mut self.rng) + 1));
+        GeneratedTask { id, owner, task }
+    }
+}
+
+impl Iterator for TaskGenerator {
+    type Item = GeneratedTask;
+
+    fn next(&mut self) -> Option<GeneratedTask> {
+        Some(self.generate())
+    }
+}
+
+/// Number of generated tasks [loaded](load) into a store.
+#[cfg(feature = "postgres")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Loaded {
+    /// Tasks stored.
+    pub stored: u64,
+    /// Tasks already stored by an earlier load from the same seed.
+    pub existing: u64,
+}
+
+/// Store `count` tasks from `generator` in `store`, as their owners, with
+/// up to `concurrency` being stored at once.
+///
+/// Tasks already stored are skipped, so loading from the same seed again
+/// only adds any tasks beyond those loaded before.
+///
+/// # Errors
+///
+/// Returns the first error storing a task, after the tasks being stored
+/// alongside it are.
+#[cfg(feature = "postgres")]
+pub async fn load(
+    store: Arc<dyn TaskStore>,
+    generator: TaskGenerator,
+    count: u64,
+    concurrency: usize,
+) -> Result<Loaded, StoreError> {
+    let mut loaded = Loaded::default();
+    let mut pending = JoinSet::new();
+    let mut result = Ok(());
+    for GeneratedTask { id, owner, task } in
+        generator.take(usize::try_from(count).unwrap_or(usize::MAX))
+    {
+        while pending.len() >= concurrency {
+            result = result.and(join(&mut pending, &mut loaded).await);
+        }
+        if result.is_err() {
+            break;
+        }
+        let store = Arc::clone(&store);
+        pending.spawn(act_as(owner.clone(), async move {
+            store.create_with_id(id, &task, owner.as_deref()).await
+        }));
+    }
+    while !pending.is_empty() {
+        result = result.and(join(&mut pending, &mut loaded).await);
+    }
+    result.map(|()| loaded)
+}
+
+/// Wait for one of the `pending` tasks to be stored, counting it in
+/// `loaded`.
+#[cfg(feature = "postgres")]
+async fn join(
+    pending: &mut JoinSet<Result<(), StoreError>>,
+    loaded: &mut Loaded,
+) -> Result<(), StoreError> {
+    match pending.join_next().await {
+        Some(Ok(Ok(()))) => {
+            loaded.stored += 1;
+            if loaded.stored % PROGRESS_INTERVAL == 0 {
+                info!(stored = loaded.stored, "generated tasks stored");
+            }
+            Ok(())
+        }
+        // the ID is taken by the same task, loaded before
+        Some(Ok(Err(StoreError::Conflict(_)))) => {
+            loaded.existing += 1;
+            Ok(())
+        }
+        Some(Ok(Err(e))) => Err(e),
+        // storing a task panicked
+        Some(Err(e)) => std::panic::resume_unwind(e.into_panic()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DueWindow, store::MockTaskStore};
+
+    #[test]
+    fn generation() {
+        let now = "2026-10-16T12:00:00Z".parse().unwrap();
+        let tasks: Vec<_> = TaskGenerator::new(7, 20, now).take(1000).collect();
+        let again: Vec<_> = TaskGenerator::new(7, 20, now).take(1000).collect();
+        for (task, other) in tasks.iter().zip(&again) {
+            assert_eq!(task.id, other.id);
+            assert_eq!(task.owner, other.owner);
+            assert_eq!(task.task.title(), other.task.title());
+            assert_eq!(task.task.due(), other.task.due());
+        }
+
+        let complete = tasks
+            .iter()
+            .filter(|task| task.task.status == TodoStatus::Complete)
+            .count();
+        assert!((200..400).contains(&complete), "{complete} complete");
+        for GeneratedTask { owner, task, .. } in &tasks {
+            assert!(owner.as_deref().unwrap().starts_with(OWNER_PREFIX));
+            assert!(DueWindow::default().contains(*task.due(), now));
+            assert!((9..17).contains(&task.due().hour()));
+            if let Some(completed) = task.completed_at() {
+                assert!(*completed <= now);
+            }
+        }
+        // the first owner has the most tasks
+        let owned = |name: &str| {
+            tasks
+                .iter()
+                .filter(|task| task.owner.as_deref() == Some(name))
+                .count()
+        };
+        assert!(owned("loadgen-001") > owned("loadgen-020"));
+        assert!(
+            TaskGenerator::new(7, 0, now)
+                .take(10)
+                .all(|task| task.owner.is_none())
+        );
+    }
+
+    #[tokio::test]
+    async fn loading() {
+        let now = "2026-10-16T12:00:00Z".parse().unwrap();
+        let existing = TaskGenerator::new(7, 3, now).nth(2).unwrap().id;
+        let mut store = MockTaskStore::new();
+        store
+            .expect_create_with_id()
+            .times(5)
+            .returning(move |id, _, _| {
+                if id == existing {
+                    Err(StoreError::Conflict(sqlx::Error::RowNotFound))
+                } else {
+                    Ok(())
+                }
+            });
+        let loaded = load(Arc::new(store), TaskGenerator::new(7, 3, now), 5, 2).await;
+        assert_eq!(
+            loaded.unwrap(),
+            Loaded {
+                stored: 4,
+                existing: 1
+            }
+        );
+
+        let mut store = MockTaskStore::new();
+        store
+            .expect_create_with_id()
+            .returning(|_, _, _| Err(StoreError::Unavailable(sqlx::Error::PoolTimedOut)));
+        let loaded = load(Arc::new(store), TaskGenerator::new(7, 3, now), 5, 2).await;
+        assert!(matches!(loaded, Err(StoreError::Unavailable(_))));
+    }
+}
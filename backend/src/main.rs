@@ -3,23 +3,25 @@
 #![deny(clippy::pedantic)]
 #![deny(missing_docs)]
 
-mod cli;
-mod tasks;
+mod analyze;
+mod redact;
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
-use axum::{
-    Json, Router,
-    extract::{Path, State},
-    http::StatusCode,
-    routing::{get, post},
-};
+use chrono::{TimeDelta, Utc};
 use clap::Parser;
 use sqlx::postgres::PgPool;
-use tracing::{debug, error, info};
-use uuid::Uuid;
-
-use tasks::{TodoTask, TodoTaskUnchecked};
+use tracing::info;
+
+use dts_developer_challenge::{
+    clock::SystemClock,
+    loadgen::{self, TaskGenerator},
+    security::SecurityEventKind,
+    server::{self, cli, schema},
+    store::{PgTaskStore, SecurityLog, TokenStore},
+    tokens::TokenScope,
+};
+use redact::RedactingFields;
 
 #[tokio::main]
 #[tracing::instrument]
@@ -28,7 +30,13 @@ async fn main() {
     let opts = cli::Opt::parse();
 
     // initialise logging
-    tracing_subscriber::fmt().init();
+    if opts.log_payloads {
+        tracing_subscriber::fmt().init();
+    } else {
+        tracing_subscriber::fmt()
+            .fmt_fields(RedactingFields::new(opts.redact_fields.clone()))
+            .init();
+    }
 
     info!("starting application");
 
@@ -38,91 +46,84 @@ async fn main() {
         .expect("failed to connect to database");
     info!("database connection pool established",);
 
-    // run database migrations, if enabled
-    if opts.skip_migrations {
-        info!("skipping database migrations");
-    } else {
-        sqlx::migrate!("./migrations")
-            .run(&db_pool)
+    // run database migrations, if enabled, and check the schema is one we
+    // can serve
+    schema::prepare(&db_pool, !opts.skip_migrations)
+        .await
+        .unwrap_or_else(|e| panic!("{e}"));
+
+    if let Some(cli::Command::Analyze { min_rows, verbose }) = opts.command {
+        let warned = analyze::run(db_pool, min_rows, verbose)
             .await
-            .expect("migrations run failed");
-        info!("database migrations complete");
+            .expect("failed to explain queries");
+        std::process::exit(i32::from(warned));
+    }
+    if let Some(cli::Command::Loadgen {
+        tasks,
+        seed,
+        owners,
+        concurrency,
+    }) = opts.command
+    {
+        let store = PgTaskStore::new(db_pool).with_row_level_security(opts.row_level_security);
+        let start = Instant::now();
+        let loaded = loadgen::load(
+            Arc::new(store),
+            TaskGenerator::new(seed, owners, Utc::now()),
+            tasks,
+            usize::try_from(concurrency.get()).expect("u32 fits in usize"),
+        )
+        .await
+        .expect("failed to store generated tasks");
+        info!(
+            stored = loaded.stored,
+            existing = loaded.existing,
+            seconds = start.elapsed().as_secs_f64(),
+            "tasks generated"
+        );
+        return;
     }
 
-    let app = Router::new()
-        .route("/task/{task_id}", get(get_task))
-        .route("/task", post(post_task))
-        .with_state(Arc::new(db_pool));
+    if let Some(cli::Command::CreateToken {
+        owner,
+        name,
+        write,
+        expires_in_days,
+    }) = opts.command
+    {
+        let scopes = if write {
+            [TokenScope::Read, TokenScope::Write].as_slice()
+        } else {
+            [TokenScope::Read].as_slice()
+        };
+        let expires_at =
+            expires_in_days.map(|days| Utc::now() + TimeDelta::days(days.get().into()));
+        let (token, details) = TokenStore::new(db_pool.clone())
+            .create(&owner, &name, scopes, expires_at)
+            .await
+            .expect("failed to create access token");
+        SecurityLog::new(db_pool)
+            .record(
+                SecurityEventKind::TokenCreated,
+                Some(&owner),
+                &format!(
+                    "token {} ({}) created on the command line",
+                    details.id, details.name
+                ),
+            )
+            .await
+            .expect("failed to record access token creation");
+        println!("{token}");
+        return;
+    }
 
-    let listener = tokio::net::TcpListener::bind(opts.service_address)
+    let service_address = opts.service_address.clone();
+    let app = server::router(opts, db_pool, Arc::new(SystemClock)).await;
+
+    let listener = tokio::net::TcpListener::bind(service_address)
         .await
         .expect("failed to bind listen address");
     axum::serve(listener, app)
         .await
         .expect("application serve failure");
 }
-
-#[tracing::instrument]
-async fn get_task(
-    State(pool): State<Arc<PgPool>>,
-    Path(task_id): Path<Uuid>,
-) -> Result<Json<TodoTask>, StatusCode> {
-    let query = sqlx::query_as(
-        r#"SELECT title, description, status as "status: TodoStatus", due
-        FROM tasks
-        WHERE id = $1"#,
-    )
-    .bind(task_id);
-
-    match query.fetch_one(Arc::as_ref(&pool)).await {
-        Ok(task) => Ok(Json(task)),
-        // if the database returned no row, then the ID doesn't exist
-        Err(sqlx::Error::RowNotFound) => Err(StatusCode::NOT_FOUND),
-        Err(e) => {
-            error!(
-                task_id = format!("{task_id}"),
-                error = format!("{e}"),
-                "database error trying to get task"
-            );
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
-}
-
-#[tracing::instrument]
-async fn post_task(
-    State(pool): State<Arc<PgPool>>,
-    Json(task): Json<TodoTaskUnchecked>,
-) -> Result<String, StatusCode> {
-    // validate the task
-    let task = match TodoTask::try_from(task) {
-        Ok(t) => t,
-        Err(e) => {
-            debug!(error = format!("{e}"), "malformed task received");
-            return Err(StatusCode::BAD_REQUEST);
-        }
-    };
-
-    let task_id = Uuid::new_v4();
-    let status = task.status;
-    let query = sqlx::query!(
-        "INSERT INTO tasks (id, title, description, status, due)
-        VALUES ($1, $2, $3, $4, $5);",
-        task_id,
-        task.title(),
-        task.description(),
-        status as _,
-        task.due(),
-    );
-
-    match query.execute(Arc::as_ref(&pool)).await {
-        Ok(_) => Ok(format!("{task_id}")),
-        Err(e) => {
-            error!(
-                error = format!("{e}"),
-                "database error trying to create task"
-            );
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
-}
@@ -0,0 +1,36 @@
+//! Rendering of Markdown task descriptions to HTML.
+
+use pulldown_cmark::{Options, Parser, html};
+
+/// Render Markdown `source` to HTML which is safe to embed in a page.
+///
+/// Tables, strikethrough and task lists are supported. Raw HTML in the
+/// source is sanitised, so scripts, event handlers and the like are removed.
+#[must_use]
+pub fn to_html(source: &str) -> String {
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new_ext(source, options));
+    ammonia::clean(&unsafe_html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_markdown() {
+        assert_eq!(
+            to_html("Some **bold** text"),
+            "<p>Some <strong>bold</strong> text</p>\n"
+        );
+    }
+
+    #[test]
+    fn removes_scripts() {
+        let html = to_html("<script>alert(1)</script>\n\n[link](javascript:alert(1))");
+        assert!(!html.contains("script"));
+        assert!(!html.contains("alert"));
+    }
+}
@@ -0,0 +1,73 @@
+//! `@username` mentions of users in task descriptions.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+#[cfg(feature = "postgres")]
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Find the users mentioned in `text`, in the order they are first mentioned.
+///
+/// A mention is an `@` followed by a username of letters, digits, `_`, `-`
+/// and `.`. An `@` following one of those characters, as in an email
+/// address, isn't a mention, and trailing `.`s and `-`s are taken to be
+/// punctuation.
+///
+/// ```
+/// use dts_developer_challenge::mentions::extract_mentions;
+///
+/// let text = "@alice please ask bob@example.com, then tell @carol.";
+/// assert_eq!(extract_mentions(text), ["alice", "carol"]);
+/// ```
+#[must_use]
+pub fn extract_mentions(text: &str) -> Vec<String> {
+    let mut mentions: Vec<String> = Vec::new();
+    let mut previous = None;
+    for (i, c) in text.char_indices() {
+        if c == '@' && !previous.is_some_and(username_char) {
+            let rest = &text[i + 1..];
+            let end = rest.find(|c| !username_char(c)).unwrap_or(rest.len());
+            let username = rest[..end].trim_end_matches(['.', '-']);
+            if !username.is_empty() && !mentions.iter().any(|m| m == username) {
+                mentions.push(username.to_string());
+            }
+        }
+        previous = Some(c);
+    }
+    mentions
+}
+
+/// Check whether `c` may be part of a mentioned username.
+fn username_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.')
+}
+
+/// Mention of a user in a task's description.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct Mention {
+    /// ID of the task mentioning the user.
+    pub task_id: Uuid,
+    /// Title of the task.
+    pub title: String,
+    /// Date & time at which the user was first mentioned by the task.
+    pub mentioned_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("no mentions here", &[])]
+    #[case("@alice", &["alice"])]
+    #[case("(@alice) and @bob_2!", &["alice", "bob_2"])]
+    #[case("@alice, @alice and @bob", &["alice", "bob"])]
+    #[case("ask @j.smith-jones.", &["j.smith-jones"])]
+    #[case("mail alice@example.com", &[])]
+    #[case("just an @ sign", &[])]
+    fn mentions(#[case] text: &str, #[case] expected: &[&str]) {
+        assert_eq!(extract_mentions(text), expected);
+    }
+}
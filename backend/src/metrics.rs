@@ -0,0 +1,331 @@
+//! Registry of operational metrics, rendered in the Prometheus text format.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use sqlx::PgPool;
+use tokio::runtime::Handle;
+
+/// Upper bounds of the buckets durations are counted in, in seconds.
+const BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// How often database pools and the runtime are sampled.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest a sample waits for a database connection, beyond which the wait
+/// is recorded as this long.
+const SAMPLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Counts of durations in each of [`BUCKETS`], and overall.
+#[derive(Clone, Debug, Default)]
+struct Histogram {
+    /// Number of durations no longer than each bucket's upper bound.
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    /// Total of the durations, in seconds.
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    /// Write the histogram's series of the metric `name` to `text`, with
+    /// the label `label` if given.
+    fn write(&self, text: &mut String, name: &str, label: Option<(&str, &str)>) {
+        let (labels, prefix) = match label {
+            Some((key, value)) => (
+                format!("{{{key}=\"{value}\"}}"),
+                format!("{key}=\"{value}\","),
+            ),
+            None => (String::new(), String::new()),
+        };
+        for (count, bound) in self.buckets.iter().zip(BUCKETS) {
+            let _ = writeln!(text, "{name}_bucket{{{prefix}le=\"{bound}\"}} {count}");
+        }
+        let _ = writeln!(
+            text,
+            "{name}_bucket{{{prefix}le=\"+Inf\"}} {}\n\
+            {name}_sum{labels} {}\n\
+            {name}_count{labels} {}",
+            self.count, self.sum, self.count,
+        );
+    }
+}
+
+/// State of the async runtime the application runs on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct RuntimeStatus {
+    /// Number of worker threads.
+    pub workers: usize,
+    /// Number of tasks which haven't finished.
+    pub alive_tasks: usize,
+    /// Number of tasks waiting in the global queue for a worker.
+    pub global_queue_depth: usize,
+}
+
+impl RuntimeStatus {
+    /// State of the runtime the caller is running on, if any.
+    #[must_use]
+    pub fn current() -> Option<Self> {
+        let metrics = Handle::try_current().ok()?.metrics();
+        Some(Self {
+            workers: metrics.num_workers(),
+            alive_tasks: metrics.num_alive_tasks(),
+            global_queue_depth: metrics.global_queue_depth(),
+        })
+    }
+}
+
+/// Metrics collected while the application runs.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Durations of database queries, by query name.
+    queries: Mutex<BTreeMap<&'static str, Histogram>>,
+    /// Time taken to get a connection from each database pool, by name.
+    pool_waits: Mutex<BTreeMap<&'static str, Histogram>>,
+    /// Delays before newly spawned tasks were first run.
+    schedule_delays: Mutex<Histogram>,
+    /// Database pools whose connections are reported, by name.
+    pools: Mutex<Vec<(&'static str, PgPool)>>,
+}
+
+impl Metrics {
+    /// Create an empty registry.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the query called `name` took `duration`.
+    pub fn observe_query(&self, name: &'static str, duration: Duration) {
+        self.queries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(name)
+            .or_default()
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Record that getting a connection from the pool called `pool` took
+    /// `duration`.
+    pub fn observe_pool_wait(&self, pool: &'static str, duration: Duration) {
+        self.pool_waits
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(pool)
+            .or_default()
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Record that a spawned task waited `duration` before first running.
+    pub fn observe_schedule_delay(&self, duration: Duration) {
+        self.schedule_delays
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Report the connections of the database pool `pool` under `name`, and
+    /// sample how long queries wait for one of them in the background.
+    ///
+    /// Connections are taken from the pool every second, so the waits
+    /// recorded are those a query starting then would have had.
+    pub fn watch_pool(self: &Arc<Self>, name: &'static str, pool: PgPool) {
+        self.pools
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((name, pool.clone()));
+        let metrics = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let start = Instant::now();
+                match tokio::time::timeout(SAMPLE_TIMEOUT, pool.acquire()).await {
+                    // the database being down isn't a wait
+                    Ok(Err(_)) => {}
+                    Ok(Ok(_)) | Err(_) => metrics.observe_pool_wait(name, start.elapsed()),
+                }
+            }
+        });
+    }
+
+    /// Sample in the background how long newly spawned tasks wait to run,
+    /// which grows when the runtime's workers are blocked or saturated.
+    pub fn watch_runtime(self: &Arc<Self>) {
+        let metrics = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let start = Instant::now();
+                if let Ok(delay) = tokio::spawn(async move { start.elapsed() }).await {
+                    metrics.observe_schedule_delay(delay);
+                }
+            }
+        });
+    }
+
+    /// Render every metric in the Prometheus text exposition format.
+    #[must_use]
+    pub fn render(&self) -> String {
+        let queries = self
+            .queries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let mut text = String::from(
+            "# HELP dts_query_duration_seconds Time taken by task store queries.\n\
+            # TYPE dts_query_duration_seconds histogram\n",
+        );
+        for (name, histogram) in queries {
+            histogram.write(
+                &mut text,
+                "dts_query_duration_seconds",
+                Some(("query", name)),
+            );
+        }
+
+        let pool_waits = self
+            .pool_waits
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        text.push_str(
+            "# HELP dts_db_pool_wait_seconds Time taken to get a database connection, sampled every second.\n\
+            # TYPE dts_db_pool_wait_seconds histogram\n",
+        );
+        for (pool, histogram) in pool_waits {
+            histogram.write(&mut text, "dts_db_pool_wait_seconds", Some(("pool", pool)));
+        }
+        let pools = self
+            .pools
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let _ = writeln!(
+            text,
+            "# HELP dts_db_pool_connections Open database connections.\n\
+            # TYPE dts_db_pool_connections gauge"
+        );
+        for (name, pool) in &pools {
+            let _ = writeln!(
+                text,
+                "dts_db_pool_connections{{pool=\"{name}\"}} {}",
+                pool.size()
+            );
+        }
+        let _ = writeln!(
+            text,
+            "# HELP dts_db_pool_idle_connections Idle database connections.\n\
+            # TYPE dts_db_pool_idle_connections gauge"
+        );
+        for (name, pool) in &pools {
+            let _ = writeln!(
+                text,
+                "dts_db_pool_idle_connections{{pool=\"{name}\"}} {}",
+                pool.num_idle()
+            );
+        }
+
+        if let Some(runtime) = RuntimeStatus::current() {
+            for (name, help, value) in [
+                (
+                    "workers",
+                    "Number of runtime worker threads.",
+                    runtime.workers,
+                ),
+                (
+                    "alive_tasks",
+                    "Runtime tasks which haven't finished.",
+                    runtime.alive_tasks,
+                ),
+                (
+                    "global_queue_depth",
+                    "Runtime tasks waiting in the global queue.",
+                    runtime.global_queue_depth,
+                ),
+            ] {
+                let _ = writeln!(
+                    text,
+                    "# HELP dts_runtime_{name} {help}\n\
+                    # TYPE dts_runtime_{name} gauge\n\
+                    dts_runtime_{name} {value}"
+                );
+            }
+        }
+        text.push_str(
+            "# HELP dts_runtime_schedule_delay_seconds Time newly spawned tasks waited to run, sampled every second.\n\
+            # TYPE dts_runtime_schedule_delay_seconds histogram\n",
+        );
+        self.schedule_delays
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write(&mut text, "dts_runtime_schedule_delay_seconds", None);
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_histogram() {
+        let metrics = Metrics::new();
+        metrics.observe_query("list", Duration::from_millis(20));
+        metrics.observe_query("list", Duration::from_secs(20));
+        metrics.observe_query("get", Duration::from_micros(500));
+
+        let text = metrics.render();
+        assert!(text.contains("dts_query_duration_seconds_bucket{query=\"get\",le=\"0.001\"} 1\n"));
+        assert!(text.contains("dts_query_duration_seconds_bucket{query=\"list\",le=\"0.01\"} 0\n"));
+        assert!(
+            text.contains("dts_query_duration_seconds_bucket{query=\"list\",le=\"0.025\"} 1\n")
+        );
+        assert!(text.contains("dts_query_duration_seconds_bucket{query=\"list\",le=\"10\"} 1\n"));
+        assert!(text.contains("dts_query_duration_seconds_bucket{query=\"list\",le=\"+Inf\"} 2\n"));
+        assert!(text.contains("dts_query_duration_seconds_sum{query=\"list\"} 20.02\n"));
+        assert!(text.contains("dts_query_duration_seconds_count{query=\"list\"} 2\n"));
+        // queries are listed in order of name
+        assert!(text.find("query=\"get\"") < text.find("query=\"list\""));
+    }
+
+    #[tokio::test]
+    async fn pool_and_runtime() {
+        let metrics = Metrics::new();
+        metrics.observe_pool_wait("primary", Duration::from_millis(3));
+        metrics.observe_schedule_delay(Duration::from_micros(200));
+
+        let text = metrics.render();
+        assert!(
+            text.contains("dts_db_pool_wait_seconds_bucket{pool=\"primary\",le=\"0.001\"} 0\n")
+        );
+        assert!(
+            text.contains("dts_db_pool_wait_seconds_bucket{pool=\"primary\",le=\"0.005\"} 1\n")
+        );
+        assert!(text.contains("dts_db_pool_wait_seconds_count{pool=\"primary\"} 1\n"));
+        assert!(text.contains("dts_runtime_schedule_delay_seconds_bucket{le=\"0.001\"} 1\n"));
+        assert!(text.contains("dts_runtime_schedule_delay_seconds_count 1\n"));
+        // the test runtime has a single thread
+        assert!(text.contains("dts_runtime_workers 1\n"));
+        assert_eq!(RuntimeStatus::current().unwrap().global_queue_depth, 0);
+    }
+}
@@ -0,0 +1,354 @@
+//! Notifications of users about their tasks, and their dispatch over the
+//! channels the users chose.
+//!
+//! Notifications are raised in the database: when a task is assigned or a
+//! user is mentioned by triggers, and when tasks become due soon or overdue
+//! by [`Dispatcher::dispatch`]. They are then sent by each [`Notifier`] whose
+//! channel the user has enabled for the event.
+
+use std::fmt;
+
+use async_trait::async_trait;
+#[cfg(feature = "postgres")]
+use chrono::TimeDelta;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "postgres")]
+use sqlx::{FromRow, prelude::Type};
+#[cfg(feature = "net")]
+use tracing::debug;
+#[cfg(feature = "postgres")]
+use tracing::warn;
+use uuid::Uuid;
+
+#[cfg(feature = "net")]
+use crate::smtp::{Email, Mailer};
+#[cfg(feature = "postgres")]
+use crate::store::NotificationStore;
+
+/// Number of notifications sent in each batch.
+#[cfg(feature = "postgres")]
+const BATCH_SIZE: i64 = 100;
+
+/// Way of reaching users.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "postgres", derive(Type))]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "postgres", sqlx(type_name = "notification_channel"))]
+#[cfg_attr(feature = "postgres", sqlx(rename_all = "snake_case"))]
+pub enum Channel {
+    /// Email, to the user's registered address.
+    Email,
+    /// Slack.
+    Slack,
+    /// Web Push, to the browsers the user subscribed.
+    WebPush,
+    /// SMS, to the user's registered phone number.
+    Sms,
+}
+
+impl Channel {
+    /// Every channel.
+    pub const ALL: [Self; 4] = [Self::Email, Self::Slack, Self::WebPush, Self::Sms];
+
+    /// Whether users are notified of `event` over the channel unless they
+    /// choose otherwise.
+    ///
+    /// Email is used for every event, and so is Web Push, which users opt
+    /// into by subscribing their browsers. SMS is only used for tasks
+    /// becoming overdue, as the most urgent. Slack needs setting up by the
+    /// user.
+    #[must_use]
+    pub fn enabled_by_default(self, event: NotificationEvent) -> bool {
+        match self {
+            Self::Email | Self::WebPush => true,
+            Self::Slack => false,
+            Self::Sms => event == NotificationEvent::Overdue,
+        }
+    }
+}
+
+/// Happening which users are notified of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "postgres", derive(Type))]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "postgres", sqlx(type_name = "notification_event"))]
+#[cfg_attr(feature = "postgres", sqlx(rename_all = "snake_case"))]
+pub enum NotificationEvent {
+    /// A task was assigned to the user.
+    Assigned,
+    /// One of the user's open tasks will soon be due.
+    DueSoon,
+    /// The user was mentioned in a task's description.
+    Mentioned,
+    /// One of the user's open tasks became overdue.
+    Overdue,
+}
+
+impl NotificationEvent {
+    /// Every event.
+    pub const ALL: [Self; 4] = [
+        Self::Assigned,
+        Self::DueSoon,
+        Self::Mentioned,
+        Self::Overdue,
+    ];
+}
+
+/// Whether a user is notified of an event over a channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct Preference {
+    /// The channel.
+    pub channel: Channel,
+    /// The event.
+    pub event: NotificationEvent,
+    /// Whether the user is notified.
+    pub enabled: bool,
+}
+
+/// Preferences of a user, which default as [`Channel::enabled_by_default`]
+/// says for the events and channels they haven't chosen.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Preferences(pub Vec<Preference>);
+
+impl Preferences {
+    /// Whether the user is notified of `event` over `channel`.
+    #[must_use]
+    pub fn enabled(&self, channel: Channel, event: NotificationEvent) -> bool {
+        self.0
+            .iter()
+            .find(|preference| preference.channel == channel && preference.event == event)
+            .map_or_else(
+                || channel.enabled_by_default(event),
+                |preference| preference.enabled,
+            )
+    }
+
+    /// The preference for every event over every channel, defaults
+    /// included.
+    #[must_use]
+    pub fn all(&self) -> Vec<Preference> {
+        Channel::ALL
+            .into_iter()
+            .flat_map(|channel| {
+                NotificationEvent::ALL.map(|event| Preference {
+                    channel,
+                    event,
+                    enabled: self.enabled(channel, event),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Notification of a user about a task.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct Notification {
+    /// ID of the notification.
+    pub id: i64,
+    /// What happened.
+    pub event: NotificationEvent,
+    /// ID of the task it happened to.
+    pub task_id: Uuid,
+    /// Title of the task.
+    pub title: String,
+    /// When the task is due.
+    pub due: DateTime<Utc>,
+    /// ID of the user notified.
+    pub user_id: String,
+    /// Name to show for the user, if they are registered.
+    pub display_name: Option<String>,
+    /// Email address of the user, if known.
+    pub email: Option<String>,
+    /// Phone number of the user, if known.
+    pub phone: Option<String>,
+}
+
+impl Notification {
+    /// One-line description of the notification.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let due = self.due.format("%a %-d %b %Y, %H:%M UTC");
+        match self.event {
+            NotificationEvent::Assigned => format!("You were assigned \"{}\"", self.title),
+            NotificationEvent::DueSoon => format!("\"{}\" is due at {due}", self.title),
+            NotificationEvent::Mentioned => format!("You were mentioned in \"{}\"", self.title),
+            NotificationEvent::Overdue => format!("\"{}\" was due at {due}", self.title),
+        }
+    }
+}
+
+/// Sender of notifications over a channel.
+#[async_trait]
+pub trait Notifier: fmt::Debug + Send + Sync {
+    /// Channel the notifications are sent over.
+    fn channel(&self) -> Channel;
+
+    /// Send `notification` to its recipient, returning a description of the
+    /// problem if it couldn't be.
+    ///
+    /// Recipients who can't be reached over the channel, such as users
+    /// without an email address, are skipped.
+    async fn notify(&self, notification: &Notification) -> Result<(), String>;
+}
+
+/// Notifier sending emails.
+#[cfg(feature = "net")]
+#[derive(Clone, Debug)]
+pub struct EmailNotifier(pub Mailer);
+
+#[cfg(feature = "net")]
+#[async_trait]
+impl Notifier for EmailNotifier {
+    fn channel(&self) -> Channel {
+        Channel::Email
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<(), String> {
+        let Some(to) = &notification.email else {
+            debug!(user = notification.user_id, "no email address to notify");
+            return Ok(());
+        };
+        let summary = notification.summary();
+        let email = Email {
+            to: to.clone(),
+            body: format!("{summary}.\n\nTask ID: {}\n", notification.task_id),
+            subject: summary,
+        };
+        self.0.send(&email).await
+    }
+}
+
+/// Number of notifications sent by a [`Dispatcher::dispatch`].
+#[cfg(feature = "postgres")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DispatchReport {
+    /// Number sent, including any skipped as their recipients can't be
+    /// reached over the channel.
+    pub sent: u64,
+    /// Number which couldn't be sent.
+    pub failed: u64,
+}
+
+/// Dispatcher of notifications to the notifiers of the channels users chose.
+#[cfg(feature = "postgres")]
+#[derive(Debug)]
+pub struct Dispatcher {
+    store: NotificationStore,
+    notifiers: Vec<Box<dyn Notifier>>,
+    /// How long before tasks are due that they are due soon.
+    due_soon: TimeDelta,
+}
+
+#[cfg(feature = "postgres")]
+impl Dispatcher {
+    /// Create a dispatcher of the notifications in `store`, raising due-soon
+    /// notifications `due_soon` before tasks are due.
+    #[must_use]
+    pub fn new(store: NotificationStore, due_soon: TimeDelta) -> Self {
+        Self {
+            store,
+            notifiers: Vec::new(),
+            due_soon,
+        }
+    }
+
+    /// Send notifications with `notifier`, as well as any others.
+    #[must_use]
+    pub fn with_notifier(mut self, notifier: impl Notifier + 'static) -> Self {
+        self.notifiers.push(Box::new(notifier));
+        self
+    }
+
+    /// Raise notifications of tasks which have become due soon or overdue by
+    /// `now`, then send every pending notification.
+    ///
+    /// Each notification is sent at most once: ones which fail to send are
+    /// logged and not retried. Without any notifiers, pending notifications
+    /// are discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn dispatch(&self, now: DateTime<Utc>) -> Result<DispatchReport, sqlx::Error> {
+        let mut report = DispatchReport::default();
+        if !self.notifiers.is_empty() {
+            self.store.raise_deadlines(self.due_soon, now).await?;
+        }
+
+        loop {
+            let batch = self.store.claim_pending(BATCH_SIZE, now).await?;
+            if batch.is_empty() {
+                break;
+            }
+            let mut users: Vec<_> = batch.iter().map(|n| n.user_id.as_str()).collect();
+            users.sort_unstable();
+            users.dedup();
+            let preferences = self.store.preferences_of(&users).await?;
+
+            for notification in &batch {
+                let preferences = preferences
+                    .get(&notification.user_id)
+                    .cloned()
+                    .unwrap_or_default();
+                for notifier in &self.notifiers {
+                    if !preferences.enabled(notifier.channel(), notification.event) {
+                        continue;
+                    }
+                    match notifier.notify(notification).await {
+                        Ok(()) => report.sent += 1,
+                        Err(e) => {
+                            warn!(
+                                id = notification.id,
+                                channel = ?notifier.channel(),
+                                error = e,
+                                "failed to send notification"
+                            );
+                            report.failed += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.store.purge_dispatched(now).await?;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preferences() {
+        let preferences = Preferences(vec![
+            Preference {
+                channel: Channel::Email,
+                event: NotificationEvent::DueSoon,
+                enabled: false,
+            },
+            Preference {
+                channel: Channel::WebPush,
+                event: NotificationEvent::Assigned,
+                enabled: false,
+            },
+        ]);
+        assert!(!preferences.enabled(Channel::Email, NotificationEvent::DueSoon));
+        assert!(preferences.enabled(Channel::Email, NotificationEvent::Overdue));
+        assert!(!preferences.enabled(Channel::WebPush, NotificationEvent::Assigned));
+        assert!(preferences.enabled(Channel::WebPush, NotificationEvent::Overdue));
+        assert!(!preferences.enabled(Channel::Slack, NotificationEvent::Assigned));
+        assert!(preferences.enabled(Channel::Sms, NotificationEvent::Overdue));
+        assert!(!preferences.enabled(Channel::Sms, NotificationEvent::DueSoon));
+
+        let all = preferences.all();
+        assert_eq!(all.len(), Channel::ALL.len() * NotificationEvent::ALL.len());
+        assert_eq!(
+            all.iter().filter(|preference| preference.enabled).count(),
+            7
+        );
+    }
+}
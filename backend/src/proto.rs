@@ -0,0 +1,407 @@
+//! Protocol Buffers encoding of task payloads.
+//!
+//! These types match the schema in `proto/tasks.proto`, which is the
+//! reference for other consumers; they are written out by hand rather than
+//! generated, so that building doesn't need `protoc`. Encode and decode them
+//! with [`prost::Message`].
+
+use std::time::SystemTime;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use prost_types::Timestamp;
+
+use crate::{TodoStatus, TodoTask, TodoTaskUnchecked, sla};
+
+/// Status of a task, as `dts.tasks.v1.TaskStatus`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum TaskStatus {
+    /// No status was given, which is invalid.
+    Unspecified = 0,
+    /// See [`TodoStatus::NotStarted`].
+    NotStarted = 1,
+    /// See [`TodoStatus::InProgress`].
+    InProgress = 2,
+    /// See [`TodoStatus::Complete`].
+    Complete = 3,
+    /// See [`TodoStatus::Cancelled`].
+    Cancelled = 4,
+    /// See [`TodoStatus::Blocked`].
+    Blocked = 5,
+    /// See [`TodoStatus::Custom`], named by the task's `custom_status`.
+    Custom = 6,
+}
+
+/// Kind of relationship from one task to another, as
+/// `dts.tasks.v1.TaskLinkKind`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum TaskLinkKind {
+    /// No kind was given.
+    Unspecified = 0,
+    /// See [`crate::TaskLinkKind::RelatesTo`].
+    RelatesTo = 1,
+    /// See [`crate::TaskLinkKind::Duplicates`].
+    Duplicates = 2,
+    /// See [`crate::TaskLinkKind::Supersedes`].
+    Supersedes = 3,
+    /// See [`crate::TaskLinkKind::DependsOn`].
+    DependsOn = 4,
+}
+
+/// How a task is doing against a service level target, as
+/// `dts.tasks.v1.SlaState`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum SlaState {
+    /// No state was given.
+    Unspecified = 0,
+    /// See [`sla::SlaState::OnTrack`].
+    OnTrack = 1,
+    /// See [`sla::SlaState::AtRisk`].
+    AtRisk = 2,
+    /// See [`sla::SlaState::Breached`].
+    Breached = 3,
+}
+
+/// A task, as `dts.tasks.v1.Task`.
+///
+/// Convert it into a [`TodoTaskUnchecked`] to validate it.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Task {
+    /// See [`TodoTask::title`].
+    #[prost(string, tag = "1")]
+    pub title: String,
+    /// See [`TodoTask::description`].
+    #[prost(string, optional, tag = "2")]
+    pub description: Option<String>,
+    /// Status of the task, a [`TaskStatus`].
+    #[prost(enumeration = "TaskStatus", tag = "3")]
+    pub status: i32,
+    /// See [`TodoTask::due`]; required.
+    #[prost(message, optional, tag = "4")]
+    pub due: Option<Timestamp>,
+    /// See [`TodoTask::tags`].
+    #[prost(string, repeated, tag = "5")]
+    pub tags: Vec<String>,
+    /// See [`TodoTask::estimate`], in whole seconds.
+    #[prost(int64, optional, tag = "6")]
+    pub estimate_seconds: Option<i64>,
+    /// See [`TodoTask::progress`].
+    #[prost(uint32, optional, tag = "7")]
+    pub progress: Option<u32>,
+    /// See [`TodoTask::colour`].
+    #[prost(string, optional, tag = "8")]
+    pub colour: Option<String>,
+    /// See [`TodoTask::title_cy`].
+    #[prost(string, optional, tag = "9")]
+    pub title_cy: Option<String>,
+    /// See [`TodoTask::description_cy`].
+    #[prost(string, optional, tag = "10")]
+    pub description_cy: Option<String>,
+    /// See [`TodoTask::completed_at`]; ignored in requests.
+    #[prost(message, optional, tag = "11")]
+    pub completed_at: Option<Timestamp>,
+    /// See [`TodoTask::status_reason`].
+    #[prost(string, optional, tag = "12")]
+    pub status_reason: Option<String>,
+    /// Name of the status if it is [`TaskStatus::Custom`].
+    #[prost(string, optional, tag = "13")]
+    pub custom_status: Option<String>,
+}
+
+/// A stored task with its ID, as `dts.tasks.v1.TaskRecord`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TaskRecord {
+    /// UUID of the task, in hyphenated form.
+    #[prost(string, tag = "1")]
+    pub id: String,
+    /// The task itself.
+    #[prost(message, optional, tag = "2")]
+    pub task: Option<Task>,
+}
+
+/// List of tasks, as `dts.tasks.v1.TaskList`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TaskList {
+    /// The tasks listed.
+    #[prost(message, repeated, tag = "1")]
+    pub tasks: Vec<TaskRecord>,
+}
+
+/// Directed relationship between two tasks, as `dts.tasks.v1.TaskLink`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TaskLink {
+    /// UUID of the task the link is from.
+    #[prost(string, tag = "1")]
+    pub source: String,
+    /// UUID of the task the link is to.
+    #[prost(string, tag = "2")]
+    pub target: String,
+    /// Kind of the relationship, a [`TaskLinkKind`].
+    #[prost(enumeration = "TaskLinkKind", tag = "3")]
+    pub kind: i32,
+}
+
+/// A task with its links and tracking details, as `dts.tasks.v1.TaskDetail`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TaskDetail {
+    /// The task itself.
+    #[prost(message, optional, tag = "1")]
+    pub task: Option<Task>,
+    /// Links from and to the task.
+    #[prost(message, repeated, tag = "2")]
+    pub links: Vec<TaskLink>,
+    /// Total number of seconds tracked on the task.
+    #[prost(int64, tag = "3")]
+    pub tracked_seconds: i64,
+    /// Whether the owner making the request has pinned the task.
+    #[prost(bool, tag = "4")]
+    pub pinned: bool,
+    /// Progress of the task towards its service level targets, if it has
+    /// any.
+    #[prost(message, optional, tag = "5")]
+    pub sla: Option<Sla>,
+}
+
+/// Progress of a task towards a single service level target, as
+/// `dts.tasks.v1.SlaTarget`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SlaTarget {
+    /// See [`sla::SlaTarget::by`].
+    #[prost(message, optional, tag = "1")]
+    pub by: Option<Timestamp>,
+    /// See [`sla::SlaTarget::state`], an [`SlaState`].
+    #[prost(enumeration = "SlaState", tag = "2")]
+    pub state: i32,
+}
+
+/// Progress of a task towards the service level targets of its policy, as
+/// `dts.tasks.v1.Sla`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Sla {
+    /// See [`sla::SlaStatus::policy`].
+    #[prost(string, optional, tag = "1")]
+    pub policy: Option<String>,
+    /// See [`sla::SlaStatus::state`], an [`SlaState`].
+    #[prost(enumeration = "SlaState", tag = "2")]
+    pub state: i32,
+    /// See [`sla::SlaStatus::respond`].
+    #[prost(message, optional, tag = "3")]
+    pub respond: Option<SlaTarget>,
+    /// See [`sla::SlaStatus::resolve`].
+    #[prost(message, optional, tag = "4")]
+    pub resolve: Option<SlaTarget>,
+}
+
+impl From<&TodoStatus> for TaskStatus {
+    fn from(status: &TodoStatus) -> Self {
+        match status {
+            TodoStatus::NotStarted => Self::NotStarted,
+            TodoStatus::InProgress => Self::InProgress,
+            TodoStatus::Complete => Self::Complete,
+            TodoStatus::Cancelled => Self::Cancelled,
+            TodoStatus::Blocked => Self::Blocked,
+            TodoStatus::Custom(_) => Self::Custom,
+        }
+    }
+}
+
+impl From<crate::TaskLinkKind> for TaskLinkKind {
+    fn from(kind: crate::TaskLinkKind) -> Self {
+        match kind {
+            crate::TaskLinkKind::RelatesTo => Self::RelatesTo,
+            crate::TaskLinkKind::Duplicates => Self::Duplicates,
+            crate::TaskLinkKind::Supersedes => Self::Supersedes,
+            crate::TaskLinkKind::DependsOn => Self::DependsOn,
+        }
+    }
+}
+
+impl From<sla::SlaState> for SlaState {
+    fn from(state: sla::SlaState) -> Self {
+        match state {
+            sla::SlaState::OnTrack => Self::OnTrack,
+            sla::SlaState::AtRisk => Self::AtRisk,
+            sla::SlaState::Breached => Self::Breached,
+        }
+    }
+}
+
+impl From<&sla::SlaTarget> for SlaTarget {
+    fn from(target: &sla::SlaTarget) -> Self {
+        Self {
+            by: Some(SystemTime::from(target.by).into()),
+            state: SlaState::from(target.state).into(),
+        }
+    }
+}
+
+impl From<&sla::SlaStatus> for Sla {
+    fn from(status: &sla::SlaStatus) -> Self {
+        Self {
+            policy: status.policy.clone(),
+            state: SlaState::from(status.state).into(),
+            respond: status.respond.as_ref().map(Into::into),
+            resolve: status.resolve.as_ref().map(Into::into),
+        }
+    }
+}
+
+impl From<&TodoTask> for Task {
+    fn from(task: &TodoTask) -> Self {
+        Self {
+            title: task.title().to_owned(),
+            description: task.description().map(str::to_owned),
+            status: TaskStatus::from(&task.status).into(),
+            due: Some(SystemTime::from(*task.due()).into()),
+            tags: task.tags().to_vec(),
+            estimate_seconds: task.estimate().map(|estimate| estimate.num_seconds()),
+            progress: task.progress().map(u32::from),
+            colour: task.colour().map(|colour| colour.as_str().to_owned()),
+            title_cy: task.title_cy().map(str::to_owned),
+            description_cy: task.description_cy().map(str::to_owned),
+            completed_at: task
+                .completed_at()
+                .map(|completed_at| SystemTime::from(*completed_at).into()),
+            status_reason: task.status_reason().map(str::to_owned),
+            custom_status: task.status.custom_name().map(str::to_owned),
+        }
+    }
+}
+
+impl From<&crate::TaskRecord> for TaskRecord {
+    fn from(record: &crate::TaskRecord) -> Self {
+        Self {
+            id: record.id.to_string(),
+            task: Some((&record.task).into()),
+        }
+    }
+}
+
+impl From<&crate::TaskLink> for TaskLink {
+    fn from(link: &crate::TaskLink) -> Self {
+        Self {
+            source: link.source.to_string(),
+            target: link.target.to_string(),
+            kind: TaskLinkKind::from(link.kind).into(),
+        }
+    }
+}
+
+impl TryFrom<Task> for TodoTaskUnchecked {
+    type Error = &'static str;
+
+    fn try_from(task: Task) -> Result<Self, Self::Error> {
+        let status = match TaskStatus::try_from(task.status) {
+            Ok(TaskStatus::NotStarted) => TodoStatus::NotStarted,
+            Ok(TaskStatus::InProgress) => TodoStatus::InProgress,
+            Ok(TaskStatus::Complete) => TodoStatus::Complete,
+            Ok(TaskStatus::Cancelled) => TodoStatus::Cancelled,
+            Ok(TaskStatus::Blocked) => TodoStatus::Blocked,
+            Ok(TaskStatus::Custom) => {
+                TodoStatus::Custom(task.custom_status.ok_or("unknown task status")?)
+            }
+            Ok(TaskStatus::Unspecified) | Err(_) => return Err("unknown task status"),
+        };
+        let due = task
+            .due
+            .and_then(|due| SystemTime::try_from(due).ok())
+            .map(DateTime::<Utc>::from)
+            .ok_or("due date is missing or invalid")?;
+        let estimate = task
+            .estimate_seconds
+            .map(|seconds| {
+                TimeDelta::try_seconds(seconds)
+                    .ok_or("estimate cannot be negative or longer than 100000 hours")
+            })
+            .transpose()?;
+        let progress = task
+            .progress
+            .map(|progress| {
+                u8::try_from(progress).map_err(|_| "progress cannot be more than 100 percent")
+            })
+            .transpose()?;
+        Ok(Self {
+            title: task.title,
+            description: task.description,
+            status,
+            status_reason: task.status_reason,
+            due,
+            tags: task.tags,
+            estimate,
+            progress,
+            colour: task.colour.map(|colour| colour.parse()).transpose()?,
+            title_cy: task.title_cy,
+            description_cy: task.description_cy,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TaskDiff;
+    use prost::Message;
+    use rstest::*;
+
+    #[fixture]
+    fn sample_task() -> TodoTask {
+        let mut task: TodoTask = TodoTaskUnchecked::new(
+            "Rota".into(),
+            Some("Update the rota".into()),
+            TodoStatus::Blocked,
+            "2025-06-30T12:30:00.5Z".parse().unwrap(),
+            vec!["admin".into()],
+        )
+        .try_into()
+        .unwrap();
+        task.set_estimate(Some(TimeDelta::hours(2)));
+        task.set_progress(Some(40));
+        task.set_colour(Some("teal".parse().unwrap()));
+        task.set_welsh(Some("Rota Cymraeg".into()), None);
+        task.set_status_reason(Some("Waiting on HR".into()));
+        task
+    }
+
+    #[rstest]
+    fn round_trip(sample_task: TodoTask) {
+        let encoded = Task::from(&sample_task).encode_to_vec();
+        let decoded = Task::decode(encoded.as_slice()).unwrap();
+        let task: TodoTask = TodoTaskUnchecked::try_from(decoded)
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert!(TaskDiff::between(&sample_task, &task).is_empty());
+        assert_eq!(task.due(), sample_task.due());
+    }
+
+    #[test]
+    fn schema_tags() {
+        // title "Rota" (1), status in progress (3), due at 1 second (4)
+        let encoded = [
+            0x0a, 0x04, b'R', b'o', b't', b'a', 0x18, 0x02, 0x22, 0x02, 0x08, 0x01,
+        ];
+        let task = Task::decode(encoded.as_slice()).unwrap();
+        let task = TodoTaskUnchecked::try_from(task).unwrap();
+        assert_eq!(task.title, "Rota");
+        assert_eq!(task.status, TodoStatus::InProgress);
+        assert_eq!(task.due.timestamp(), 1);
+    }
+
+    #[rstest]
+    fn invalid_task(sample_task: TodoTask) {
+        let mut task = Task::from(&sample_task);
+        task.status = TaskStatus::Unspecified.into();
+        assert_eq!(
+            TodoTaskUnchecked::try_from(task.clone()).unwrap_err(),
+            "unknown task status"
+        );
+        task.status = TaskStatus::Complete.into();
+        task.due = None;
+        assert_eq!(
+            TodoTaskUnchecked::try_from(task).unwrap_err(),
+            "due date is missing or invalid"
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use std::{fmt, sync::Arc};
+
+use tracing::field::{Field, Visit};
+use tracing_subscriber::{
+    field::{MakeVisitor, VisitFmt, VisitOutput},
+    fmt::format::{DefaultVisitor, Writer},
+};
+
+/// Text logged in place of redacted values.
+const REDACTED: &str = "[redacted]";
+
+/// Log field formatter which redacts the values of sensitive fields.
+///
+/// Fields are redacted both when logged directly and when they appear as
+/// string fields within the `Debug` output of another value, such as a task
+/// logged as a whole.
+#[derive(Clone, Debug)]
+pub(crate) struct RedactingFields {
+    /// Names of the fields to redact.
+    fields: Arc<[String]>,
+}
+
+impl RedactingFields {
+    pub(crate) fn new(fields: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            fields: fields.into_iter().collect(),
+        }
+    }
+}
+
+impl<'a> MakeVisitor<Writer<'a>> for RedactingFields {
+    type Visitor = RedactingVisitor<'a>;
+
+    fn make_visitor(&self, target: Writer<'a>) -> Self::Visitor {
+        RedactingVisitor {
+            inner: DefaultVisitor::new(target, true),
+            fields: Arc::clone(&self.fields),
+        }
+    }
+}
+
+/// Visitor made by [`RedactingFields`], formatting fields as the default
+/// formatter does after redacting them.
+pub(crate) struct RedactingVisitor<'a> {
+    inner: DefaultVisitor<'a>,
+    fields: Arc<[String]>,
+}
+
+impl RedactingVisitor<'_> {
+    fn is_redacted(&self, field: &Field) -> bool {
+        self.fields.iter().any(|name| name == field.name())
+    }
+}
+
+impl Visit for RedactingVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if self.is_redacted(field) {
+            self.inner.record_str(field, REDACTED);
+        } else {
+            self.inner
+                .record_str(field, &redact_debug(value, &self.fields));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if self.is_redacted(field) {
+            self.inner.record_debug(field, &format_args!("{REDACTED}"));
+        } else {
+            let formatted = redact_debug(&format!("{value:?}"), &self.fields);
+            self.inner.record_debug(field, &format_args!("{formatted}"));
+        }
+    }
+}
+
+impl VisitOutput<fmt::Result> for RedactingVisitor<'_> {
+    fn finish(self) -> fmt::Result {
+        self.inner.finish()
+    }
+}
+
+impl VisitFmt for RedactingVisitor<'_> {
+    fn writer(&mut self) -> &mut dyn fmt::Write {
+        self.inner.writer()
+    }
+}
+
+/// Redact the string values of `fields` within `Debug` output.
+///
+/// Values look like `name: "value"` or `name: Some("value")`.
+fn redact_debug(text: &str, fields: &[String]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    'scan: while !rest.is_empty() {
+        for name in fields {
+            let Some(after_name) = rest.strip_prefix(name.as_str()) else {
+                continue;
+            };
+            // the name must be a whole identifier
+            let preceded_by_ident = out
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_');
+            let value = after_name
+                .strip_prefix(": \"")
+                .map(|v| (v, ": \""))
+                .or_else(|| {
+                    after_name
+                        .strip_prefix(": Some(\"")
+                        .map(|v| (v, ": Some(\""))
+                });
+            if let (false, Some((value, opening))) = (preceded_by_ident, value) {
+                if let Some(end) = closing_quote(value) {
+                    out.push_str(name);
+                    out.push_str(opening);
+                    out.push_str(REDACTED);
+                    rest = &value[end..];
+                    continue 'scan;
+                }
+            }
+        }
+
+        let mut chars = rest.chars();
+        out.extend(chars.next());
+        rest = chars.as_str();
+    }
+    out
+}
+
+/// Byte index of the quote closing a `Debug`-escaped string starting at the
+/// beginning of `s`.
+fn closing_quote(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields() -> Vec<String> {
+        vec!["title".to_owned(), "description".to_owned()]
+    }
+
+    #[test]
+    fn redacts_nested_fields() {
+        let text = r#"Json(TodoTaskUnchecked { title: "Call \"Bob\"", description: Some("at home"), subtitle: "x", due: 1 })"#;
+        assert_eq!(
+            redact_debug(text, &fields()),
+            r#"Json(TodoTaskUnchecked { title: "[redacted]", description: Some("[redacted]"), subtitle: "x", due: 1 })"#
+        );
+    }
+
+    #[test]
+    fn leaves_other_text() {
+        let text = r#"description: None, title_case: "x", a title: "#;
+        assert_eq!(redact_debug(text, &fields()), text);
+    }
+}
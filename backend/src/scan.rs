@@ -0,0 +1,125 @@
+//! Scanning of uploaded files for viruses, before they are stored.
+
+#[cfg(feature = "net")]
+use std::{fmt, time::Duration};
+
+#[cfg(feature = "net")]
+use async_trait::async_trait;
+#[cfg(feature = "net")]
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+/// Size of the chunks files are streamed to clamd in.
+#[cfg(feature = "net")]
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Outcome of scanning a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScanVerdict {
+    /// No threat was found.
+    Clean,
+    /// A threat was found, with the scanner's name for it.
+    Infected(String),
+}
+
+/// Scanner of files for viruses.
+#[cfg(feature = "net")]
+#[async_trait]
+pub trait VirusScanner: fmt::Debug + Send + Sync {
+    /// Name of the scanner, recorded with the results of its scans.
+    fn name(&self) -> &'static str;
+
+    /// Scan `content`, returning a description of the problem if it couldn't
+    /// be scanned.
+    async fn scan(&self, content: &[u8]) -> Result<ScanVerdict, String>;
+}
+
+/// Scanner using a `ClamAV` daemon listening on TCP.
+#[cfg(feature = "net")]
+#[derive(Clone, Debug)]
+pub struct ClamdScanner {
+    /// Host and port of the daemon.
+    address: String,
+    /// How long a scan may take, including connecting to the daemon.
+    timeout: Duration,
+}
+
+#[cfg(feature = "net")]
+impl ClamdScanner {
+    /// Create a scanner using the daemon at `address`, given as `host:port`,
+    /// giving up on scans taking longer than `timeout`.
+    #[must_use]
+    pub fn new(address: String, timeout: Duration) -> Self {
+        Self { address, timeout }
+    }
+
+    /// Stream `content` to the daemon with its `INSTREAM` command, returning
+    /// its reply.
+    async fn instream(&self, content: &[u8]) -> std::io::Result<String> {
+        let mut stream = TcpStream::connect(&self.address).await?;
+        // the `z` prefix has replies end with a NUL rather than a newline
+        stream.write_all(b"zINSTREAM\0").await?;
+        for chunk in content.chunks(CHUNK_SIZE) {
+            let length = u32::try_from(chunk.len()).expect("chunks are small");
+            stream.write_all(&length.to_be_bytes()).await?;
+            stream.write_all(chunk).await?;
+        }
+        stream.write_all(&0_u32.to_be_bytes()).await?;
+
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply).await?;
+        Ok(String::from_utf8_lossy(&reply)
+            .trim_end_matches(['\0', '\n'])
+            .to_owned())
+    }
+}
+
+/// Interpret the reply of clamd to a scan.
+#[cfg(feature = "net")]
+fn verdict(reply: &str) -> Result<ScanVerdict, String> {
+    match reply.strip_prefix("stream: ") {
+        Some("OK") => Ok(ScanVerdict::Clean),
+        Some(found) if found.ends_with(" FOUND") => Ok(ScanVerdict::Infected(
+            found.trim_end_matches(" FOUND").to_owned(),
+        )),
+        _ => Err(format!("clamd replied {reply:?}")),
+    }
+}
+
+#[cfg(feature = "net")]
+#[async_trait]
+impl VirusScanner for ClamdScanner {
+    fn name(&self) -> &'static str {
+        "clamav"
+    }
+
+    async fn scan(&self, content: &[u8]) -> Result<ScanVerdict, String> {
+        match tokio::time::timeout(self.timeout, self.instream(content)).await {
+            Ok(Ok(reply)) => verdict(&reply),
+            Ok(Err(e)) => Err(format!("failed to scan with clamd: {e}")),
+            Err(_) => Err("scanning with clamd timed out".to_owned()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "net"))]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("stream: OK", Ok(ScanVerdict::Clean))]
+    #[case(
+        "stream: Win.Test.EICAR_HDB-1 FOUND",
+        Ok(ScanVerdict::Infected("Win.Test.EICAR_HDB-1".to_owned()))
+    )]
+    #[case(
+        "INSTREAM size limit exceeded. ERROR",
+        Err("clamd replied \"INSTREAM size limit exceeded. ERROR\"".to_owned())
+    )]
+    fn verdicts(#[case] reply: &str, #[case] expected: Result<ScanVerdict, String>) {
+        assert_eq!(verdict(reply), expected);
+    }
+}
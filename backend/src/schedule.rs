@@ -0,0 +1,246 @@
+//! Schedules of background jobs, written as cron expressions.
+//!
+//! Expressions have the five usual fields, `minute hour day month weekday`,
+//! each a comma-separated list of `*`, values or ranges `a-b`, optionally
+//! with a step `/n`. Sunday is weekday 0 or 7. As in cron, if both the day
+//! and weekday are restricted then times matching either are chosen. The
+//! shorthands `@hourly`, `@daily` and `@weekly` are also understood. Times
+//! are in UTC.
+
+use std::{fmt, ops::RangeInclusive, str::FromStr};
+
+use chrono::{DateTime, Datelike, NaiveTime, TimeDelta, Timelike, Utc};
+
+/// Number of days searched for a matching time before giving up, which
+/// covers every combination of day and weekday in the Gregorian calendar.
+const SEARCH_DAYS: i64 = 366 * 28;
+
+/// When a background job runs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Schedule {
+    /// At the times matching a cron expression.
+    Cron(Box<CronFields>),
+    /// Repeatedly, with a fixed interval between runs.
+    Every(TimeDelta),
+}
+
+/// Parsed fields of a cron expression, as the values each allows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CronFields {
+    /// Expression the fields were parsed from.
+    expression: String,
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days: Vec<u32>,
+    months: Vec<u32>,
+    /// Weekdays, numbered from Sunday as 0.
+    weekdays: Vec<u32>,
+    /// Whether the day field was `*`.
+    any_day: bool,
+    /// Whether the weekday field was `*`.
+    any_weekday: bool,
+}
+
+impl Schedule {
+    /// The first time at which the job should run, if it is scheduled from
+    /// `now`.
+    ///
+    /// Jobs run at an interval start straight away.
+    #[must_use]
+    pub fn first(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Every(_) => Some(now),
+            Self::Cron(fields) => fields.next_after(now),
+        }
+    }
+
+    /// The first time after `after` at which the job should run, if there
+    /// is one.
+    #[must_use]
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Every(interval) => after.checked_add_signed(*interval),
+            Self::Cron(fields) => fields.next_after(after),
+        }
+    }
+}
+
+impl CronFields {
+    /// Whether the job runs at some time on the day of `time`.
+    fn matches_day(&self, time: DateTime<Utc>) -> bool {
+        if !self.months.contains(&time.month()) {
+            return false;
+        }
+        let day = self.days.contains(&time.day());
+        let weekday = self
+            .weekdays
+            .contains(&time.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day,
+            (true, false) => weekday,
+            (false, false) => day || weekday,
+        }
+    }
+
+    fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        let midnight = start.date_naive().and_time(NaiveTime::MIN).and_utc();
+        (0..SEARCH_DAYS)
+            .map(|days| midnight + TimeDelta::days(days))
+            .filter(|day| self.matches_day(*day))
+            .find_map(|day| {
+                self.hours
+                    .iter()
+                    .flat_map(|hour| {
+                        self.minutes.iter().map(move |minute| {
+                            day + TimeDelta::hours((*hour).into())
+                                + TimeDelta::minutes((*minute).into())
+                        })
+                    })
+                    .find(|time| *time >= start)
+            })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cron(fields) => f.write_str(&fields.expression),
+            Self::Every(interval) => write!(f, "every {}s", interval.num_seconds()),
+        }
+    }
+}
+
+/// Failure to parse a [`Schedule`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScheduleError(String);
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid schedule: {}", self.0)
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+impl FromStr for Schedule {
+    type Err = ScheduleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expression = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            expression => expression,
+        };
+        let fields: Vec<_> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(ScheduleError(format!(
+                "expected 5 fields, found {}",
+                fields.len()
+            )));
+        };
+        let any_day = days == "*";
+        let any_weekday = weekdays == "*";
+        let mut weekdays = parse_field(weekdays, 0..=7, "weekday")?;
+        // Sunday may be written as 7
+        for weekday in &mut weekdays {
+            *weekday %= 7;
+        }
+        weekdays.sort_unstable();
+        weekdays.dedup();
+        Ok(Self::Cron(Box::new(CronFields {
+            expression: s.trim().to_owned(),
+            minutes: parse_field(minutes, 0..=59, "minute")?,
+            hours: parse_field(hours, 0..=23, "hour")?,
+            days: parse_field(days, 1..=31, "day")?,
+            months: parse_field(months, 1..=12, "month")?,
+            weekdays,
+            any_day,
+            any_weekday,
+        })))
+    }
+}
+
+/// Parse the values allowed by the `name` field of a cron expression, which
+/// are within `range`, in ascending order.
+fn parse_field(
+    field: &str,
+    range: RangeInclusive<u32>,
+    name: &str,
+) -> Result<Vec<u32>, ScheduleError> {
+    let invalid = || ScheduleError(format!("{name} field {field:?} is invalid"));
+    let parse = |value: &str| {
+        value
+            .parse::<u32>()
+            .ok()
+            .filter(|value| range.contains(value))
+            .ok_or_else(invalid)
+    };
+
+    let mut values = Vec::new();
+    for part in field.split(',') {
+        let (span, step) = match part.split_once('/') {
+            Some((span, step)) => (span, step.parse::<usize>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+        let (first, last) = match span.split_once('-') {
+            _ if span == "*" => (*range.start(), *range.end()),
+            Some((first, last)) => (parse(first)?, parse(last)?),
+            None => (parse(span)?, parse(span)?),
+        };
+        if first > last {
+            return Err(invalid());
+        }
+        values.extend((first..=last).step_by(step));
+    }
+    values.sort_unstable();
+    values.dedup();
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn time(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[rstest]
+    #[case("*/15 * * * *", "2025-05-26T10:07:30Z", "2025-05-26T10:15:00Z")]
+    #[case("0 * * * *", "2025-05-26T10:00:00Z", "2025-05-26T11:00:00Z")]
+    #[case("@daily", "2025-05-26T10:00:00Z", "2025-05-27T00:00:00Z")]
+    #[case("30 9 * * 1-5", "2025-05-30T10:00:00Z", "2025-06-02T09:30:00Z")]
+    #[case("0 0 29 2 *", "2025-01-01T00:00:00Z", "2028-02-29T00:00:00Z")]
+    // either the day or the weekday matches
+    #[case("0 12 1 * 0", "2025-05-26T00:00:00Z", "2025-06-01T12:00:00Z")]
+    #[case("0 12 15 * 7", "2025-06-02T00:00:00Z", "2025-06-08T12:00:00Z")]
+    #[case("0,30 8-9 * * *", "2025-05-26T08:45:00Z", "2025-05-26T09:00:00Z")]
+    fn next_after(#[case] schedule: &str, #[case] after: &str, #[case] expected: &str) {
+        let schedule: Schedule = schedule.parse().unwrap();
+        assert_eq!(schedule.next_after(time(after)), Some(time(expected)));
+    }
+
+    #[test]
+    fn impossible_date() {
+        let schedule: Schedule = "0 0 31 2 *".parse().unwrap();
+        assert_eq!(schedule.next_after(time("2025-01-01T00:00:00Z")), None);
+    }
+
+    #[rstest]
+    #[case("* * * *")]
+    #[case("60 * * * *")]
+    #[case("* * 0 * *")]
+    #[case("*/0 * * * *")]
+    #[case("5-1 * * * *")]
+    #[case("* * * JAN *")]
+    fn invalid(#[case] schedule: &str) {
+        assert!(schedule.parse::<Schedule>().is_err());
+    }
+}
@@ -0,0 +1,48 @@
+//! Audit log of security-relevant events, such as logins and permission
+//! denials.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "postgres")]
+use sqlx::{FromRow, prelude::Type};
+
+/// Kind of a [`SecurityEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "postgres", derive(Type))]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "postgres", sqlx(type_name = "security_event_kind"))]
+#[cfg_attr(feature = "postgres", sqlx(rename_all = "snake_case"))]
+pub enum SecurityEventKind {
+    /// A browser logged in.
+    LoginSucceeded,
+    /// A browser failed to log in.
+    LoginFailed,
+    /// A request had an invalid access token or session.
+    AuthenticationFailed,
+    /// A personal access token was created.
+    TokenCreated,
+    /// A personal access token was revoked.
+    TokenRevoked,
+    /// A request was refused for lack of permission.
+    PermissionDenied,
+    /// A user was registered or changed by an administrator.
+    UserChanged,
+    /// An uploaded file was found to be infected.
+    InfectedUpload,
+}
+
+/// Entry of the security event log.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct SecurityEvent {
+    /// ID of the event, increasing in the order events were recorded.
+    pub id: i64,
+    /// What happened.
+    pub kind: SecurityEventKind,
+    /// Owner concerned, if known.
+    pub owner: Option<String>,
+    /// Human-readable description of what happened.
+    pub detail: String,
+    /// Date & time at which the event occurred.
+    pub occurred_at: DateTime<Utc>,
+}
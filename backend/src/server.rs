@@ -0,0 +1,4007 @@
+//! The HTTP API serving [`TodoTask`] objects, for mounting in any axum
+//! application.
+//!
+//! [`router`] sets up the API and the background work it relies on from the
+//! same [options](cli::Opt) as the `dts_developer_challenge` binary.
+
+#[cfg(test)]
+mod api_contract;
+#[cfg(test)]
+mod api_snapshots;
+mod auth;
+mod caldav;
+pub mod cli;
+#[cfg(test)]
+mod fast_forward;
+mod inbound_email;
+mod language;
+mod oidc;
+mod protobuf;
+mod quota;
+mod recovery;
+mod scheduler;
+pub mod schema;
+mod sync_worker;
+#[cfg(test)]
+mod test_database;
+mod validated;
+mod zapier;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::{self, Next},
+    response::{AppendHeaders, Html, IntoResponse, Redirect, Response},
+    routing::{delete, get, post, put},
+};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    services::{ServeDir, ServeFile},
+    timeout::TimeoutLayer,
+};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    DueWindow, FilterExpr, StoreError, TaskDiff, TaskLink, TaskLinkKind, TaskRecord, TodoStatus,
+    TodoTask,
+    attachments::{self, Attachment, DownloadSignature, DownloadSigner, NewAttachment},
+    breaker::CircuitBreaker,
+    calendar::WorkCalendar,
+    clock::Clock,
+    digest::DigestTemplate,
+    egress::Egress,
+    email::SenderAllowList,
+    feed,
+    filter::{Comparison, Condition},
+    gov_notify::{NotifyClient, NotifyNotifier},
+    graph::TaskGraph,
+    i18n::Locale,
+    import::ImportFormat,
+    jobs::{BulkJob, ItemError, JobKind, JobStatus},
+    json_schema, markdown,
+    mentions::Mention,
+    metrics::{Metrics, RuntimeStatus},
+    notify::{Dispatcher, EmailNotifier, Preference},
+    proto,
+    scan::{ClamdScanner, ScanVerdict, VirusScanner},
+    schedule::Schedule,
+    security::{SecurityEvent, SecurityEventKind},
+    sla::{SlaBreach, SlaPolicies, SlaState, SlaStatus},
+    sms::{self, SmsNotifier, TwilioGateway},
+    smtp::{self, Mailer},
+    stats::{
+        Bucket, BurndownBucket, EstimateGrouping, EstimateVariance, Refreshed, StorageUsage,
+        Workload,
+    },
+    store::{
+        self, EventTaskStore, GuardedTaskStore, HistoryErasure, HookStore, JobStore,
+        NotificationStore, PgTaskStore, PushSubscriptionStore, Reschedule, RescheduleOutcome,
+        RetryingTaskStore, SearchMatch, SecurityLog, SessionStore, TaskStore, TaskVersion,
+        TimedTaskStore, TokenStore, UserStore, UserUpdate,
+    },
+    thumbnails::{self, Thumbnail},
+    tokens::{AccessToken, TokenScope},
+    tracking::{TimeEntry, TimesheetEntry},
+    users::{Role, User},
+    webpush::{PushSubscription, Subscribed, VapidKey, WebPushNotifier},
+    workers::{PoolStatus, WorkerPool},
+    workflow::Workflow,
+};
+use auth::{
+    Credential, Owner, SESSION_COOKIE, act_as_owner, check_csrf, cookie, csrf_token, set_cookie,
+};
+use cli::{InfectedUploads, StorageMode};
+use inbound_email::EmailIngest;
+use language::Language;
+use oidc::{LoginAttempt, OidcClient, OidcError};
+use protobuf::BodyFormat;
+use quota::RateLimiter;
+use scheduler::{
+    DispatchNotifications, GenerateThumbnails, PurgeSessions, RefreshStats, Scheduler, SendDigests,
+};
+use schema::MIGRATOR;
+use sync_worker::SyncWorker;
+use validated::ValidatedJson;
+use zapier::HookSender;
+
+/// Time the database is given after a request times out before its
+/// statements are cancelled.
+const DEADLINE_GRACE: Duration = Duration::from_secs(1);
+
+/// Header making the creation of a task safe to repeat, see
+/// [`idempotency_key`].
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// Optional features the application was built with.
+const FEATURES: &[&str] = &[
+    #[cfg(feature = "parquet")]
+    "parquet",
+    #[cfg(feature = "tui")]
+    "tui",
+];
+
+/// State shared between request handlers.
+#[derive(Debug)]
+struct AppState {
+    /// Source of the current time.
+    clock: Arc<dyn Clock>,
+    /// Storage of tasks.
+    store: Arc<dyn TaskStore>,
+    /// Registry of operational metrics.
+    metrics: Arc<Metrics>,
+    /// Breaker tripped by database outages.
+    breaker: Arc<CircuitBreaker>,
+    /// How long requests may take.
+    request_timeout: Duration,
+    /// Default minimum similarity of fuzzy title search results.
+    search_threshold: f32,
+    /// Whether to check new tasks for duplicates by default.
+    detect_duplicates: bool,
+    /// Minimum title similarity of duplicate tasks.
+    duplicate_threshold: f32,
+    /// Maximum number of open tasks per owner.
+    max_open_tasks: Option<u32>,
+    /// Limit on how often each owner may create tasks.
+    creation_limiter: Option<RateLimiter>,
+    /// What to do with the history of tasks when erasing a user's data.
+    history_erasure: HistoryErasure,
+    /// What to do with unknown fields of tasks given as JSON.
+    unknown_fields: cli::UnknownFields,
+    /// Window around now which new due dates must be within.
+    due_window: DueWindow,
+    /// What to do with tasks created already overdue.
+    past_due_on_create: cli::PastDuePolicy,
+    /// Whether cancelled and blocked tasks must be given a status reason.
+    status_reason: cli::StatusReasonPolicy,
+    /// Statuses tasks may have, and the changes between them allowed.
+    workflow: Workflow,
+    /// Service level targets of tasks.
+    sla: SlaPolicies,
+    /// Working hours in which time towards service level targets counts.
+    working_hours: WorkCalendar,
+    /// Working hours in which time towards tasks being overdue counts, or
+    /// `None` if every hour does.
+    overdue_hours: Option<WorkCalendar>,
+    /// Schema to check tasks given as JSON against, if enabled.
+    task_schema: Option<serde_json::Value>,
+    /// Storage of personal access tokens.
+    tokens: TokenStore,
+    /// Whether requests must be authenticated with an access token or a
+    /// session.
+    require_tokens: bool,
+    /// Storage of browser login sessions.
+    sessions: SessionStore,
+    /// Provider to log browsers in with, if enabled.
+    oidc: Option<OidcClient>,
+    /// How long login sessions last.
+    session_lifetime: TimeDelta,
+    /// Audit log of authentication and authorisation decisions.
+    security_log: SecurityLog,
+    /// Owners allowed to use the administration endpoints.
+    admins: Vec<String>,
+    /// Storage of registered users.
+    users: UserStore,
+    /// Whether only active registered users may make requests.
+    registered_users_only: bool,
+    /// Settings for creating tasks from emails, if enabled.
+    email: Option<EmailIngest>,
+    /// Storage of REST hooks subscribed to new tasks.
+    hooks: HookStore,
+    /// Storage of bulk operations run in the background.
+    jobs: JobStore,
+    /// Workers running hook deliveries and bulk jobs.
+    workers: WorkerPool,
+    /// Sender of new tasks to hooks.
+    hook_sender: HookSender,
+    /// Storage of notifications and users' preferences for them.
+    notifications: NotificationStore,
+    /// Storage of browsers subscribed to Web Push notifications.
+    push_subscriptions: PushSubscriptionStore,
+    /// Public key which browsers subscribe to Web Push notifications with,
+    /// if enabled.
+    push_key: Option<String>,
+    /// Hosts of push services which browsers may subscribe with.
+    push_allowed_hosts: Vec<String>,
+    /// Scanner of uploaded attachments, if uploads are enabled.
+    scanner: Option<Arc<dyn VirusScanner>>,
+    /// What to do with uploaded attachments found to be infected.
+    infected_uploads: InfectedUploads,
+    /// Maximum total size of the files attached to each owner's tasks, if
+    /// limited.
+    max_storage_bytes: Option<u64>,
+    /// Signer of attachment download URLs.
+    download_signer: DownloadSigner,
+}
+
+impl AppState {
+    /// Record a security event, logging rather than failing the request if it
+    /// can't be stored.
+    async fn record(&self, kind: SecurityEventKind, owner: Option<&str>, detail: &str) {
+        if let Err(e) = self.security_log.record(kind, owner, detail).await {
+            error!(
+                ?kind,
+                error = format!("{e}"),
+                "database error trying to record security event"
+            );
+        }
+    }
+}
+
+/// Build the application's router from `opts`, serving tasks from the
+/// database behind `db_pool` at the times told by `clock`, and start the
+/// background work it relies on.
+///
+/// The router may be served alone, or nested in another at some path.
+///
+/// # Panics
+///
+/// Panics if any of the services configured by `opts` can't be set up,
+/// such as when a file they are configured by can't be read.
+// every service and route is set up here, in the order they depend on
+#[allow(clippy::too_many_lines)]
+pub async fn router(opts: cli::Opt, db_pool: PgPool, clock: Arc<dyn Clock>) -> Router {
+    let workflow = match opts.workflow.as_deref() {
+        Some(path) => {
+            let workflow: Workflow =
+                serde_json::from_slice(&std::fs::read(path).expect("failed to read workflow file"))
+                    .expect("failed to parse workflow file");
+            workflow
+                .validate()
+                .unwrap_or_else(|e| panic!("invalid workflow: {e}"));
+            workflow
+                .record(&db_pool)
+                .await
+                .expect("failed to record custom statuses");
+            info!(statuses = ?workflow.statuses, "custom workflow configured");
+            workflow
+        }
+        None => Workflow::default(),
+    };
+
+    let sla = match opts.sla.as_deref() {
+        Some(path) => {
+            let sla: SlaPolicies = serde_json::from_slice(
+                &std::fs::read(path).expect("failed to read service level targets file"),
+            )
+            .expect("failed to parse service level targets file");
+            sla.validate()
+                .unwrap_or_else(|e| panic!("invalid service level targets: {e}"));
+            info!(
+                policies = sla.0.len(),
+                working_hours = %opts.working_hours,
+                "service level targets configured"
+            );
+            sla
+        }
+        None => SlaPolicies::default(),
+    };
+
+    let egress = Egress::new(opts.egress_proxy.clone(), opts.egress_allowed_hosts.clone());
+    if let Some(proxy) = &opts.egress_proxy {
+        info!(%proxy, "outbound requests sent through proxy");
+    }
+
+    let oidc = match opts.oidc_issuer.as_deref() {
+        Some(issuer) => {
+            let client_secret = opts.oidc_client_secret_file.as_deref().map(|path| {
+                std::fs::read_to_string(path)
+                    .expect("failed to read OpenID Connect client secret file")
+                    .trim()
+                    .to_owned()
+            });
+            let client = OidcClient::discover(
+                egress.clone(),
+                issuer,
+                opts.oidc_client_id.clone().expect("required by clap"),
+                client_secret,
+                opts.oidc_redirect_url.clone().expect("required by clap"),
+                opts.oidc_owner_claim.clone(),
+            )
+            .await
+            .unwrap_or_else(|e| panic!("{e}"));
+            info!(issuer, "OpenID Connect login enabled");
+            Some(client)
+        }
+        None => None,
+    };
+
+    let overdue_hours =
+        (opts.overdue_hours == cli::OverdueHours::Working).then(|| opts.working_hours.clone());
+    let tokens = TokenStore::new(db_pool.clone());
+    let sessions = SessionStore::new(db_pool.clone());
+    let workers = WorkerPool::new(
+        usize::try_from(opts.workers.get()).expect("u32 fits in usize"),
+        usize::try_from(opts.worker_queue.get()).expect("u32 fits in usize"),
+    );
+    let mut scheduler = Scheduler::new(db_pool.clone(), Arc::clone(&clock));
+    scheduler.add(
+        opts.purge_sessions_schedule.clone(),
+        PurgeSessions {
+            sessions: sessions.clone(),
+            clock: Arc::clone(&clock),
+        },
+    );
+    scheduler.add(
+        opts.stats_refresh_schedule.clone(),
+        RefreshStats(PgTaskStore::new(db_pool.clone()).with_overdue_hours(overdue_hours.clone())),
+    );
+    let security_log = SecurityLog::new(db_pool.clone());
+    let users = UserStore::new(db_pool.clone());
+    let notifications = NotificationStore::new(db_pool.clone());
+    let mailer = opts.smtp_relay.as_ref().map(|relay| {
+        Mailer::new(
+            format!("{}:{}", relay.host(), relay.port_u16().unwrap_or(25)),
+            opts.mail_from.clone().expect("required by clap"),
+        )
+        .unwrap_or_else(|e| panic!("invalid sender address: {e}"))
+        .with_clock(Arc::clone(&clock))
+    });
+    let mut dispatcher = Dispatcher::new(
+        notifications.clone(),
+        TimeDelta::hours(opts.due_soon_hours.get().into()),
+    );
+    if opts.notify_by_email {
+        let mailer = mailer.clone().expect("required by clap");
+        dispatcher = dispatcher.with_notifier(EmailNotifier(mailer));
+        info!("email notifications enabled");
+    }
+    let push_subscriptions = PushSubscriptionStore::new(db_pool.clone());
+    let mut push_key = None;
+    if let Some(path) = opts.vapid_private_key_file.as_deref() {
+        let key = VapidKey::from_pem(
+            &std::fs::read_to_string(path).expect("failed to read VAPID private key file"),
+        )
+        .unwrap_or_else(|e| panic!("invalid VAPID private key: {e}"));
+        push_key = Some(key.public_key());
+        let http = egress.with_proxy(opts.push_proxy.clone());
+        dispatcher = dispatcher.with_notifier(WebPushNotifier {
+            subscriptions: push_subscriptions.clone(),
+            key,
+            subject: opts.vapid_subject.clone().expect("required by clap"),
+            http,
+            clock: Arc::clone(&clock),
+        });
+        info!("web push notifications enabled");
+    }
+    if let Some(provider) = opts.sms_gateway {
+        let api_url = opts.sms_api_url.as_deref().expect("required by clap");
+        let from = opts.sms_from.clone().expect("required by clap");
+        let gateway = match provider {
+            cli::SmsProvider::Twilio => {
+                let path = opts
+                    .twilio_auth_token_file
+                    .as_deref()
+                    .expect("required by clap");
+                let auth_token =
+                    std::fs::read_to_string(path).expect("failed to read Twilio auth token file");
+                TwilioGateway::new(
+                    egress.clone(),
+                    api_url,
+                    opts.twilio_account_sid.clone().expect("required by clap"),
+                    auth_token.trim().to_owned(),
+                    from,
+                )
+            }
+        }
+        .unwrap_or_else(|e| panic!("invalid SMS sender: {e}"));
+        dispatcher = dispatcher.with_notifier(SmsNotifier(Box::new(gateway)));
+        info!(?provider, "SMS notifications enabled");
+    }
+    if let Some(api_url) = opts.gov_notify_api_url.as_deref() {
+        let path = opts
+            .gov_notify_api_key_file
+            .as_deref()
+            .expect("required by clap");
+        let api_key = std::fs::read_to_string(path).expect("failed to read Notify API key file");
+        let client = NotifyClient::new(egress.clone(), api_url, api_key.trim())
+            .unwrap_or_else(|e| panic!("invalid Notify API key: {e}"))
+            .with_clock(Arc::clone(&clock));
+        if let Some(template_id) = opts.gov_notify_email_template {
+            dispatcher =
+                dispatcher.with_notifier(NotifyNotifier::email(client.clone(), template_id));
+            info!(%template_id, "email notifications with GOV.UK Notify enabled");
+        }
+        if let Some(template_id) = opts.gov_notify_sms_template {
+            dispatcher = dispatcher.with_notifier(NotifyNotifier::sms(client, template_id));
+            info!(%template_id, "SMS notifications with GOV.UK Notify enabled");
+        }
+    }
+    // run even without notifiers, to discard the notifications raised
+    scheduler.add(
+        opts.notifications_schedule.clone(),
+        DispatchNotifications {
+            dispatcher,
+            workers: workers.clone(),
+            clock: Arc::clone(&clock),
+        },
+    );
+    if let Some(frequency) = opts.digest {
+        let mailer = mailer.clone().expect("required by clap");
+        if let Some(to) = opts.digest_to.as_deref() {
+            smtp::check_address(to).unwrap_or_else(|e| panic!("invalid digest address: {e}"));
+        }
+        let template = match opts.digest_template.as_deref() {
+            Some(path) => DigestTemplate::new(
+                std::fs::read_to_string(path).expect("failed to read digest template file"),
+            )
+            .unwrap_or_else(|e| panic!("invalid digest template: {e}")),
+            None => DigestTemplate::default(),
+        };
+        let schedule = opts.digest_schedule.clone().unwrap_or_else(|| {
+            match frequency {
+                cli::DigestFrequency::Daily => "0 7 * * *",
+                cli::DigestFrequency::Weekly => "0 7 * * 1",
+            }
+            .parse()
+            .expect("default digest schedules are valid")
+        });
+        scheduler.add(
+            schedule,
+            SendDigests {
+                store: PgTaskStore::new(db_pool.clone()),
+                users: users.clone(),
+                mailer,
+                template,
+                period: frequency.into(),
+                to: opts.digest_to.clone(),
+                overdue_hours: overdue_hours.clone(),
+                clock: Arc::clone(&clock),
+            },
+        );
+    }
+    let hooks = HookStore::new(db_pool.clone());
+    let jobs = JobStore::new(db_pool.clone());
+    let scanner = opts.clamd_address.as_ref().map(|address| {
+        info!(%address, "attachment uploads enabled");
+        scheduler.add(
+            opts.thumbnail_schedule.clone(),
+            GenerateThumbnails {
+                store: PgTaskStore::new(db_pool.clone()),
+                workers: workers.clone(),
+            },
+        );
+        Arc::new(ClamdScanner::new(
+            address.to_string(),
+            Duration::from_secs(opts.scan_timeout_secs.get().into()),
+        )) as Arc<dyn VirusScanner>
+    });
+    let download_lifetime = TimeDelta::seconds(opts.download_url_secs.get().into());
+    let download_signer = match opts.download_signing_key_file.as_deref() {
+        Some(path) => {
+            let key = std::fs::read_to_string(path)
+                .expect("failed to read download URL signing key file");
+            DownloadSigner::new(key.trim().as_bytes().to_vec(), download_lifetime)
+        }
+        None => DownloadSigner::random(download_lifetime),
+    };
+    let breaker = Arc::new(CircuitBreaker::new(
+        opts.breaker_threshold,
+        Duration::from_secs(opts.breaker_cooldown_secs.get().into()),
+    ));
+    breaker.spawn_probe(db_pool.clone());
+    // the replica is connected to lazily, so reads fall back to the primary
+    // if it is down when starting
+    let replica = opts.db_replica_options().map(|options| {
+        info!("reading listings from replica");
+        PgPool::connect_lazy_with(options)
+    });
+    let metrics = Arc::new(Metrics::new());
+    metrics.watch_runtime();
+    metrics.watch_pool("primary", db_pool.clone());
+    if let Some(replica) = &replica {
+        metrics.watch_pool("replica", replica.clone());
+    }
+    let max_lag = Duration::from_secs(opts.db_replica_max_lag_secs);
+    let store: Arc<dyn TaskStore> = match opts.storage {
+        StorageMode::Table => {
+            let mut store = PgTaskStore::new(db_pool)
+                .with_row_level_security(opts.row_level_security)
+                .with_overdue_hours(overdue_hours.clone())
+                .with_clock(Arc::clone(&clock));
+            if let Some(replica) = replica {
+                store = store.with_replica(replica, max_lag);
+            }
+            Arc::new(store)
+        }
+        StorageMode::Events => {
+            let mut store = EventTaskStore::new(db_pool, opts.snapshot_interval)
+                .with_row_level_security(opts.row_level_security)
+                .with_overdue_hours(overdue_hours.clone())
+                .with_clock(Arc::clone(&clock));
+            if let Some(replica) = replica {
+                store = store.with_replica(replica, max_lag);
+            }
+            let imported = store
+                .import_untracked()
+                .await
+                .expect("failed to import tasks into event storage");
+            info!(imported, "event storage ready");
+            Arc::new(store)
+        }
+    };
+    let store: Arc<dyn TaskStore> =
+        Arc::new(RetryingTaskStore::new(store, opts.transaction_retries));
+    let store: Arc<dyn TaskStore> = Arc::new(TimedTaskStore::new(
+        store,
+        metrics.clone(),
+        Duration::from_millis(opts.slow_query_ms),
+    ));
+    let store: Arc<dyn TaskStore> = Arc::new(GuardedTaskStore::new(store, breaker.clone()));
+
+    if let Some(provider) = opts.sync_provider {
+        let path = opts
+            .sync_credentials_file
+            .as_deref()
+            .expect("required by clap");
+        let credentials = std::fs::read(path).expect("failed to read sync credentials file");
+        let worker = SyncWorker {
+            store: store.clone(),
+            http: egress.clone(),
+            provider: provider.into(),
+            owner: opts.sync_owner.clone().expect("required by clap"),
+            credentials: serde_json::from_slice(&credentials)
+                .expect("failed to parse sync credentials file"),
+            token_url: opts.sync_token_url.clone().expect("required by clap"),
+            api_url: opts.sync_api_url.clone().expect("required by clap"),
+            list: opts.sync_list.clone().expect("required by clap"),
+            rule: opts.sync_conflict.into(),
+            due_window: DueWindow::years(opts.due_window_years),
+            clock: Arc::clone(&clock),
+        };
+        let interval = TimeDelta::minutes(opts.sync_interval_minutes.get().into());
+        scheduler.add(Schedule::Every(interval), worker);
+        info!(?provider, "task sync enabled");
+    }
+
+    scheduler.spawn();
+
+    let email = opts.email_owner.clone().map(|owner| {
+        let path = opts
+            .email_signing_key_file
+            .as_deref()
+            .expect("required by clap");
+        let signing_key =
+            std::fs::read_to_string(path).expect("failed to read email webhook signing key file");
+        info!(owner, "task creation from emails enabled");
+        EmailIngest {
+            owner,
+            signing_key: signing_key.trim().as_bytes().to_vec(),
+            allowed: SenderAllowList::new(&opts.email_allowed_senders),
+            default_due: TimeDelta::days(opts.email_default_due_days.into()),
+        }
+    });
+
+    let state = AppState {
+        clock,
+        store,
+        metrics,
+        breaker,
+        request_timeout: Duration::from_secs(opts.request_timeout_secs.get().into()),
+        search_threshold: opts.search_threshold,
+        detect_duplicates: opts.detect_duplicates,
+        duplicate_threshold: opts.duplicate_threshold,
+        max_open_tasks: opts.max_open_tasks,
+        creation_limiter: opts
+            .max_creations_per_minute
+            .map(|limit| RateLimiter::new(limit, Duration::from_secs(60))),
+        history_erasure: opts.history_erasure.into(),
+        unknown_fields: opts.unknown_fields,
+        due_window: DueWindow::years(opts.due_window_years),
+        past_due_on_create: opts.past_due_on_create,
+        status_reason: opts.status_reason,
+        task_schema: opts
+            .validate_json_schema
+            .then(|| json_schema::task(&workflow)),
+        workflow,
+        sla,
+        working_hours: opts.working_hours,
+        overdue_hours,
+        tokens,
+        require_tokens: opts.require_tokens,
+        sessions,
+        oidc,
+        session_lifetime: TimeDelta::hours(opts.session_hours.get().into()),
+        security_log,
+        admins: opts.admins,
+        users,
+        registered_users_only: opts.registered_users_only,
+        email,
+        hooks,
+        jobs,
+        workers,
+        hook_sender: HookSender {
+            allowed_hosts: opts.hook_allowed_hosts,
+            http: egress.with_proxy(opts.hook_proxy),
+        },
+        notifications,
+        push_subscriptions,
+        push_key,
+        push_allowed_hosts: opts.push_allowed_hosts,
+        scanner,
+        infected_uploads: opts.infected_uploads,
+        max_storage_bytes: opts.max_storage_bytes,
+        download_signer,
+    };
+    let state = Arc::new(state);
+    let max_attachment_bytes =
+        usize::try_from(opts.max_attachment_bytes.get()).expect("u32 fits in usize");
+    let routes = Router::new()
+        .route(
+            "/task/{task_id}",
+            get(get_task).head(head_task).put(put_task),
+        )
+        .route("/task/{task_id}/exists", get(task_exists))
+        .route(
+            "/task/{task_id}/description.html",
+            get(get_description_html),
+        )
+        .route("/task/{task_id}/links", get(get_links).post(post_link))
+        .route("/task/{task_id}/links/{target}/{kind}", delete(delete_link))
+        .route(
+            "/task/{task_id}/attachments",
+            get(list_attachments)
+                .post(post_attachment)
+                .layer(DefaultBodyLimit::max(max_attachment_bytes)),
+        )
+        .route(
+            "/task/{task_id}/attachments/{attachment_id}",
+            get(get_attachment).delete(delete_attachment),
+        )
+        .route(
+            "/task/{task_id}/attachments/{attachment_id}/thumbnail",
+            get(get_thumbnail),
+        )
+        .route(
+            "/task/{task_id}/attachments/{attachment_id}/download-url",
+            post(post_download_url),
+        )
+        .route("/task/{task_id}/history", get(get_history))
+        .route(
+            "/task/{task_id}/history/{version}",
+            get(get_history_version),
+        )
+        .route("/task/{task_id}/revert/{version}", post(revert_task))
+        .route("/task/{task_id}/assignee", put(put_assignee))
+        .route("/task/{task_id}/pin", post(pin_task).delete(unpin_task))
+        .route("/task/{task_id}/timer/start", post(start_timer))
+        .route("/task/{task_id}/timer/stop", post(stop_timer))
+        .route("/task/search", get(search_tasks))
+        .route("/task/bulk/reschedule", post(reschedule_tasks))
+        .route("/task/lookup", post(lookup_tasks))
+        .route("/task/calendar", get(get_calendar))
+        .route("/task/graph", get(get_graph))
+        .route("/task", get(list_tasks).post(post_task))
+        .route("/task/import", post(import_tasks))
+        .route("/jobs/{job_id}", get(get_job))
+        .route("/jobs/{job_id}/cancel", post(cancel_job))
+        .route("/task/export", get(export_tasks))
+        .route("/statuses", get(get_statuses))
+        .route("/version", get(get_version))
+        .route("/schema/task.json", get(get_task_schema))
+        .route("/metrics", get(get_metrics))
+        .route("/debug/tasks", get(get_debug_tasks))
+        .route("/stats/burndown", get(get_burndown))
+        .route("/stats/workload", get(get_workload))
+        .route("/stats/estimates", get(get_estimate_variance))
+        .route("/stats/sla-breaches", get(get_sla_breaches))
+        .route("/stats/storage", get(get_storage))
+        .route("/users/{user_id}/export", get(export_user))
+        .route("/users/{user_id}/data", delete(erase_user))
+        .route("/users/{user_id}/timesheet", get(get_timesheet))
+        .route("/users/{user_id}/mentions", get(get_mentions))
+        .route("/users/{user_id}/digest", put(put_digest))
+        .route(
+            "/users/{user_id}/notifications",
+            get(get_notification_preferences).put(put_notification_preferences),
+        )
+        .route(
+            "/users/{user_id}/push-subscriptions",
+            get(list_push_subscriptions).post(post_push_subscription),
+        )
+        .route(
+            "/users/{user_id}/push-subscriptions/{subscription_id}",
+            delete(delete_push_subscription),
+        )
+        .route("/push/key", get(get_push_key))
+        .route("/feed.atom", get(get_feed))
+        .route("/auth/tokens", get(list_tokens).post(post_token))
+        .route("/auth/tokens/{token_id}", delete(revoke_token))
+        .route("/admin/security-events", get(get_security_events))
+        .route("/admin/users", get(list_users).post(post_user))
+        .route(
+            "/admin/users/{user_id}",
+            get(get_user).patch(patch_user).delete(deactivate_user),
+        )
+        .merge(caldav::routes())
+        .merge(zapier::routes())
+        .layer(middleware::from_fn_with_state(state.clone(), act_as_owner))
+        // logging in and out works whoever the client is
+        .route("/auth/login", get(login))
+        .route("/auth/callback", get(login_callback))
+        .route("/auth/logout", post(logout))
+        .route("/auth/csrf", get(get_csrf_token))
+        // webhook requests are authenticated by their signature
+        .route("/inbound/email", post(inbound_email::receive_email))
+        // as are attachment downloads
+        .route(
+            "/downloads/{task_id}/{attachment_id}",
+            get(download_attachment),
+        )
+        .layer(middleware::from_fn_with_state(state.clone(), check_csrf))
+        .layer(middleware::from_fn_with_state(state.clone(), fail_fast))
+        // readiness is checked however the database is doing
+        .route("/readyz", get(get_readiness));
+    // as is the frontend, which routes the paths it doesn't have files for,
+    // apart from mistyped API paths
+    let routes = match opts.frontend_dir {
+        Some(dir) => {
+            info!(dir = %dir.display(), "serving frontend");
+            let index = ServeFile::new(dir.join("index.html"));
+            let frontend = ServeDir::new(dir).fallback(index);
+            routes.fallback(|request: Request| async move {
+                if is_api_path(request.uri().path()) {
+                    return StatusCode::NOT_FOUND.into_response();
+                }
+                match frontend.clone().try_call(request).await {
+                    Ok(response) => response.into_response(),
+                    Err(e) => {
+                        error!(error = format!("{e}"), "failed to read frontend file");
+                        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                    }
+                }
+            })
+        }
+        None => routes,
+    };
+    routes
+        .layer(middleware::from_fn_with_state(state.clone(), set_deadline))
+        .layer(TimeoutLayer::with_status_code(
+            StatusCode::SERVICE_UNAVAILABLE,
+            state.request_timeout,
+        ))
+        .layer(CatchPanicLayer::custom(recovery::panicked))
+        .layer(middleware::from_fn(recovery::request_id))
+        .with_state(state)
+}
+
+/// First segments of the paths the API serves, which the frontend isn't
+/// served under.
+const API_PREFIXES: &[&str] = &[
+    ".well-known",
+    "admin",
+    "auth",
+    "caldav",
+    "debug",
+    "downloads",
+    "feed.atom",
+    "inbound",
+    "integrations",
+    "jobs",
+    "metrics",
+    "push",
+    "readyz",
+    "schema",
+    "stats",
+    "statuses",
+    "task",
+    "users",
+    "version",
+];
+
+/// Check whether `path` is within the paths the API serves.
+fn is_api_path(path: &str) -> bool {
+    let first = path.trim_start_matches('/').split('/').next();
+    first.is_some_and(|first| API_PREFIXES.contains(&first))
+}
+
+#[tracing::instrument]
+async fn get_task(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(task_id): Path<Uuid>,
+    Query(LanguageParams { lang }): Query<LanguageParams>,
+    format: BodyFormat,
+) -> Result<Response, Response> {
+    let query = async {
+        let (Some(version), Some(task)) = (
+            state.store.latest_version(task_id).await?,
+            state.store.get(task_id).await?,
+        ) else {
+            return Ok(None);
+        };
+        let links = state.store.links(task_id).await?.unwrap_or_default();
+        let tracked_seconds = state.store.tracked_seconds(task_id).await?;
+        let pinned = match owner.as_deref() {
+            Some(owner) => state.store.pinned(task_id, owner).await?,
+            None => false,
+        };
+        let sla = match state.sla.policy_for(&task) {
+            Some(_) => state
+                .store
+                .milestones(&[task_id])
+                .await?
+                .first()
+                .and_then(|milestones| {
+                    state
+                        .sla
+                        .evaluate(&state.working_hours, &task, milestones, state.clock.now())
+                }),
+            None => None,
+        };
+        let detail = TaskDetail {
+            task: task.localised(lang.unwrap_or_default()),
+            links,
+            tracked_seconds,
+            pinned,
+            sla,
+        };
+        Ok::<_, StoreError>(Some((version, detail)))
+    };
+
+    match query.await {
+        Ok(Some((version, detail))) => Ok((
+            [(header::ETAG, task_etag(version))],
+            format.respond(detail, TaskDetail::to_proto),
+        )
+            .into_response()),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Entity tag of a task, which changes with each new version of it.
+fn task_etag(version: i32) -> String {
+    format!("\"{version}\"")
+}
+
+/// Check a task exists, giving its entity tag without getting the task.
+#[tracing::instrument]
+async fn head_task(
+    State(state): State<Arc<AppState>>,
+    Path(task_id): Path<Uuid>,
+) -> Result<Response, Response> {
+    match state.store.latest_version(task_id).await {
+        Ok(Some(version)) => Ok([(header::ETAG, task_etag(version))].into_response()),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Response body of [`task_exists`].
+#[derive(Serialize, Debug)]
+struct Existence {
+    /// Whether the task exists.
+    exists: bool,
+}
+
+/// Check whether a task exists, without getting it.
+#[tracing::instrument]
+async fn task_exists(
+    State(state): State<Arc<AppState>>,
+    Path(task_id): Path<Uuid>,
+) -> Result<Json<Existence>, StoreError> {
+    let version = state.store.latest_version(task_id).await?;
+    Ok(Json(Existence {
+        exists: version.is_some(),
+    }))
+}
+
+#[tracing::instrument]
+async fn get_description_html(
+    State(state): State<Arc<AppState>>,
+    Path(task_id): Path<Uuid>,
+) -> Result<Html<String>, Response> {
+    match state.store.get(task_id).await {
+        Ok(Some(task)) => Ok(Html(
+            task.description()
+                .map(markdown::to_html)
+                .unwrap_or_default(),
+        )),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Response body of [`get_version`].
+#[derive(Serialize, Debug)]
+struct BuildInfo {
+    /// Version of the application.
+    version: &'static str,
+    /// Git commit the application was built from, suffixed with `-dirty` if
+    /// it had uncommitted changes, or `unknown`.
+    git_sha: &'static str,
+    /// Date & time at which the application was built.
+    built_at: Option<DateTime<Utc>>,
+    /// Optional features the application was built with.
+    features: &'static [&'static str],
+    /// Version of the latest database migration built in.
+    migration: Option<i64>,
+}
+
+#[tracing::instrument]
+async fn get_task_schema(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    Json(json_schema::task(&state.workflow))
+}
+
+#[tracing::instrument]
+async fn get_version() -> Json<BuildInfo> {
+    Json(BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("BUILD_GIT_SHA"),
+        built_at: env!("BUILD_TIMESTAMP")
+            .parse()
+            .ok()
+            .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0)),
+        features: FEATURES,
+        migration: MIGRATOR.iter().map(|migration| migration.version).max(),
+    })
+}
+
+/// Respond to requests with `503 Service Unavailable` while the database is
+/// unavailable, rather than have each wait for it to time out.
+async fn fail_fast(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    match state.breaker.retry_after() {
+        Some(wait) => unavailable(wait, "database unavailable, try again later"),
+        None => next.run(request).await,
+    }
+}
+
+/// Have the database give up on the request's statements once the request
+/// has timed out.
+async fn set_deadline(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // a little after the timeout, so the client is told the request timed out
+    // rather than that its statement was cancelled
+    let deadline = Instant::now() + state.request_timeout + DEADLINE_GRACE;
+    store::with_deadline(deadline, next.run(request)).await
+}
+
+/// Whether the application can serve requests, which it can't while the
+/// database is unavailable.
+#[tracing::instrument]
+async fn get_readiness(State(state): State<Arc<AppState>>) -> Response {
+    match state.breaker.retry_after() {
+        Some(wait) => unavailable(wait, "not ready"),
+        None => "ready".into_response(),
+    }
+}
+
+/// `503 Service Unavailable` response, saying to retry after `wait`.
+fn unavailable(wait: Duration, message: &'static str) -> Response {
+    // round up, so clients retrying after the delay aren't too early
+    let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, retry_after.to_string())],
+        message,
+    )
+        .into_response()
+}
+
+#[tracing::instrument]
+async fn get_metrics(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, credential): Owner,
+) -> Result<Response, Response> {
+    check_role(&state, owner.as_deref(), credential, &[Role::Admin]).await?;
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render() + &state.workers.render_metrics(),
+    )
+        .into_response())
+}
+
+/// Background work in progress, in [`get_debug_tasks`].
+#[derive(Serialize, Debug)]
+struct DebugTasks {
+    /// State of the async runtime, if known.
+    runtime: Option<RuntimeStatus>,
+    /// State of the pool running background work.
+    workers: PoolStatus,
+    /// Bulk operations still running, whoever started them.
+    jobs: Vec<BulkJob>,
+}
+
+/// List the background work in progress, for tuning the worker pool and
+/// runtime.
+#[tracing::instrument]
+async fn get_debug_tasks(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, credential): Owner,
+) -> Result<Json<DebugTasks>, Response> {
+    check_role(&state, owner.as_deref(), credential, &[Role::Admin]).await?;
+    let jobs = state.jobs.running().await.map_err(|e| {
+        error!(
+            error = format!("{e}"),
+            "database error trying to list running jobs"
+        );
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
+    Ok(Json(DebugTasks {
+        runtime: RuntimeStatus::current(),
+        workers: state.workers.status(),
+        jobs,
+    }))
+}
+
+/// Task status along with its label, in [`get_statuses`].
+#[derive(Serialize, Debug)]
+struct StatusLabel {
+    /// Human-readable name of the status, in the client's language.
+    label: String,
+    /// Statuses which tasks may change to from this one, if the workflow
+    /// restricts them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next: Option<Vec<TodoStatus>>,
+    status: TodoStatus,
+}
+
+#[tracing::instrument]
+async fn get_statuses(
+    State(state): State<Arc<AppState>>,
+    Language(locale): Language,
+) -> (Language, Json<Vec<StatusLabel>>) {
+    let statuses = state
+        .workflow
+        .all()
+        .map(|status| StatusLabel {
+            label: status.label(locale).to_owned(),
+            next: state
+                .workflow
+                .transitions
+                .as_ref()
+                .map(|transitions| transitions.get(&status).cloned().unwrap_or_default()),
+            status,
+        })
+        .collect();
+    (Language(locale), Json(statuses))
+}
+
+/// Query parameters of [`get_task`] and [`list_tasks`].
+#[derive(Deserialize, Debug)]
+struct LanguageParams {
+    /// Language to prefer the title and description in, where the task has
+    /// translations of them.
+    lang: Option<Locale>,
+}
+
+/// Query parameters of endpoints making changes, which can be previewed, such
+/// as by import UIs.
+#[derive(Deserialize, Debug)]
+struct DryRunParams {
+    /// Check the request as usual, but respond with what would change rather
+    /// than making the changes.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Response body of [`get_task`].
+#[derive(Serialize, Debug)]
+struct TaskDetail {
+    #[serde(flatten)]
+    task: TodoTask,
+    /// Links from and to the task.
+    links: Vec<TaskLink>,
+    /// Total number of seconds tracked on the task.
+    tracked_seconds: i64,
+    /// Whether the owner making the request has pinned the task.
+    pinned: bool,
+    /// Progress of the task towards its service level targets, if it has
+    /// any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sla: Option<SlaStatus>,
+}
+
+impl TaskDetail {
+    /// Convert the response to its Protocol Buffers message.
+    fn to_proto(&self) -> proto::TaskDetail {
+        proto::TaskDetail {
+            task: Some((&self.task).into()),
+            links: self.links.iter().map(Into::into).collect(),
+            tracked_seconds: self.tracked_seconds,
+            pinned: self.pinned,
+            sla: self.sla.as_ref().map(Into::into),
+        }
+    }
+}
+
+#[tracing::instrument]
+async fn get_links(
+    State(state): State<Arc<AppState>>,
+    Path(task_id): Path<Uuid>,
+) -> Result<Json<Vec<TaskLink>>, Response> {
+    match state.store.links(task_id).await {
+        Ok(Some(links)) => Ok(Json(links)),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Request body of [`post_link`].
+#[derive(Deserialize, Debug)]
+struct NewLink {
+    /// ID of the task to link to.
+    target: Uuid,
+    /// Kind of the relationship.
+    kind: TaskLinkKind,
+}
+
+#[tracing::instrument]
+async fn post_link(
+    State(state): State<Arc<AppState>>,
+    Path(task_id): Path<Uuid>,
+    Json(link): Json<NewLink>,
+) -> Response {
+    if link.target == task_id {
+        debug!("task link to itself received");
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    let link = TaskLink {
+        source: task_id,
+        target: link.target,
+        kind: link.kind,
+    };
+    // not found if one of the tasks doesn't exist, or a conflict if the link
+    // already does
+    match state.store.add_link(&link).await {
+        Ok(()) => StatusCode::CREATED.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[tracing::instrument]
+async fn delete_link(
+    State(state): State<Arc<AppState>>,
+    Path((task_id, target, kind)): Path<(Uuid, Uuid, TaskLinkKind)>,
+) -> Response {
+    let link = TaskLink {
+        source: task_id,
+        target,
+        kind,
+    };
+    match state.store.remove_link(&link).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[tracing::instrument]
+async fn list_attachments(
+    State(state): State<Arc<AppState>>,
+    Path(task_id): Path<Uuid>,
+) -> Result<Json<Vec<Attachment>>, Response> {
+    match state.store.attachments(task_id).await {
+        Ok(Some(attachments)) => Ok(Json(attachments)),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Query parameters of [`post_attachment`].
+#[derive(Deserialize, Debug)]
+struct AttachmentParams {
+    /// Name of the file uploaded.
+    filename: String,
+}
+
+/// Attach the file in the request body to a task, once it has been scanned
+/// for viruses.
+#[tracing::instrument(skip(headers, content))]
+async fn post_attachment(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(task_id): Path<Uuid>,
+    Query(AttachmentParams { filename }): Query<AttachmentParams>,
+    headers: HeaderMap,
+    content: Bytes,
+) -> Result<(StatusCode, Json<Attachment>), Response> {
+    let Some(scanner) = &state.scanner else {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    };
+    if let Err(e) = attachments::check_filename(&filename) {
+        debug!(e, "invalid attachment filename received");
+        return Err((StatusCode::BAD_REQUEST, e).into_response());
+    }
+    if let Some(max) = state.max_storage_bytes {
+        let uploading = u64::try_from(content.len()).unwrap_or(u64::MAX);
+        match state.store.storage_used(owner.as_deref()).await {
+            Ok(used) if u64::try_from(used).unwrap_or(0).saturating_add(uploading) <= max => {}
+            Ok(used) => {
+                debug!(owner, used, uploading, "storage quota exceeded by upload");
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    format!(
+                        "uploading would exceed the storage quota of {max} bytes, \
+                        of which {used} are used"
+                    ),
+                )
+                    .into_response());
+            }
+            Err(e) => return Err(e.into_response()),
+        }
+    }
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/octet-stream");
+
+    // nothing is stored without having been scanned
+    let verdict = match scanner.scan(&content).await {
+        Ok(verdict) => verdict,
+        Err(e) => {
+            error!(error = e, "failed to scan attachment");
+            return Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                "attachment could not be scanned",
+            )
+                .into_response());
+        }
+    };
+    if let ScanVerdict::Infected(threat) = &verdict {
+        warn!(%task_id, filename, threat, "infected attachment uploaded");
+        state
+            .record(
+                SecurityEventKind::InfectedUpload,
+                owner.as_deref(),
+                &format!("{filename} uploaded to task {task_id} is infected with {threat}"),
+            )
+            .await;
+        if state.infected_uploads == InfectedUploads::Reject {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("file is infected with {threat}"),
+            )
+                .into_response());
+        }
+    }
+
+    let attachment = NewAttachment {
+        filename,
+        content_type: content_type.to_owned(),
+        content: content.to_vec(),
+        uploaded_by: owner,
+        verdict,
+        scanned_by: scanner.name().to_owned(),
+        scanned_at: state.clock.now(),
+    };
+    match state.store.add_attachment(task_id, &attachment).await {
+        Ok(Some(attachment)) => Ok((StatusCode::CREATED, Json(attachment))),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+#[tracing::instrument]
+async fn get_attachment(
+    State(state): State<Arc<AppState>>,
+    Path((task_id, attachment_id)): Path<(Uuid, Uuid)>,
+) -> Response {
+    send_attachment(&state, task_id, attachment_id).await
+}
+
+/// Respond with the content of an attachment, unless it is quarantined.
+async fn send_attachment(state: &AppState, task_id: Uuid, attachment_id: Uuid) -> Response {
+    match state.store.attachment(task_id, attachment_id).await {
+        Ok(Some((attachment, _))) if attachment.quarantined => {
+            (StatusCode::FORBIDDEN, "attachment is quarantined").into_response()
+        }
+        Ok(Some((attachment, content))) => (
+            [
+                (header::CONTENT_TYPE, attachment.content_type),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!(
+                        "attachment; filename=\"{}\"",
+                        attachment.filename.replace('"', "")
+                    ),
+                ),
+            ],
+            content,
+        )
+            .into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Get the thumbnail of an image attachment, which is generated in the
+/// background after it is uploaded.
+#[tracing::instrument]
+async fn get_thumbnail(
+    State(state): State<Arc<AppState>>,
+    Path((task_id, attachment_id)): Path<(Uuid, Uuid)>,
+) -> Response {
+    match state.store.thumbnail(task_id, attachment_id).await {
+        Ok(Some(Thumbnail::Ready(png))) => (
+            [
+                (header::CONTENT_TYPE, thumbnails::THUMBNAIL_CONTENT_TYPE),
+                // thumbnails never change, though attachments may be removed
+                (header::CACHE_CONTROL, "private, max-age=86400"),
+            ],
+            png,
+        )
+            .into_response(),
+        Ok(Some(Thumbnail::Pending)) => (
+            StatusCode::ACCEPTED,
+            [(header::RETRY_AFTER, "60")],
+            "thumbnail is being generated",
+        )
+            .into_response(),
+        Ok(Some(Thumbnail::Unavailable)) => {
+            (StatusCode::NOT_FOUND, "attachment has no thumbnail").into_response()
+        }
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Response body of [`post_download_url`].
+#[derive(Serialize, Debug)]
+struct DownloadUrl {
+    /// Path to download the attachment from without credentials.
+    url: String,
+    /// Date & time after which the URL no longer works.
+    expires_at: DateTime<Utc>,
+}
+
+/// Sign a URL which the attachment can be downloaded from without
+/// credentials, e.g. by a browser following a link, until it expires.
+#[tracing::instrument]
+async fn post_download_url(
+    State(state): State<Arc<AppState>>,
+    Path((task_id, attachment_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<DownloadUrl>, Response> {
+    let attachment = match state.store.attachments(task_id).await {
+        Ok(attachments) => attachments
+            .into_iter()
+            .flatten()
+            .find(|attachment| attachment.id == attachment_id),
+        Err(e) => return Err(e.into_response()),
+    };
+    match attachment {
+        Some(attachment) if attachment.quarantined => {
+            Err((StatusCode::FORBIDDEN, "attachment is quarantined").into_response())
+        }
+        Some(_) => {
+            let signature = state
+                .download_signer
+                .sign(task_id, attachment_id, state.clock.now());
+            let query = serde_urlencoded::to_string(&signature)
+                .expect("download signatures can be encoded");
+            Ok(Json(DownloadUrl {
+                url: format!("/downloads/{task_id}/{attachment_id}?{query}"),
+                expires_at: DateTime::from_timestamp(signature.expires, 0)
+                    .expect("expiry is in range"),
+            }))
+        }
+        None => Err(StatusCode::NOT_FOUND.into_response()),
+    }
+}
+
+/// Download an attachment with a signed URL from [`post_download_url`].
+#[tracing::instrument]
+async fn download_attachment(
+    State(state): State<Arc<AppState>>,
+    Path((task_id, attachment_id)): Path<(Uuid, Uuid)>,
+    Query(signature): Query<DownloadSignature>,
+) -> Response {
+    if !state
+        .download_signer
+        .verify(task_id, attachment_id, &signature, state.clock.now())
+    {
+        debug!("invalid or expired download signature received");
+        return (
+            StatusCode::FORBIDDEN,
+            "download URL is invalid or has expired",
+        )
+            .into_response();
+    }
+    // the signature grants access to the attachment, whoever its owner
+    send_attachment(&state, task_id, attachment_id).await
+}
+
+#[tracing::instrument]
+async fn delete_attachment(
+    State(state): State<Arc<AppState>>,
+    Path((task_id, attachment_id)): Path<(Uuid, Uuid)>,
+) -> Response {
+    match state.store.remove_attachment(task_id, attachment_id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Query parameters of [`get_history`].
+#[derive(Deserialize, Debug)]
+struct HistoryParams {
+    /// Page of history to get, counting from 1.
+    #[serde(default = "HistoryParams::default_page")]
+    page: u32,
+    /// Number of versions per page.
+    #[serde(default = "HistoryParams::default_per_page")]
+    per_page: u32,
+}
+
+impl HistoryParams {
+    /// Largest allowed value of `per_page`.
+    const MAX_PER_PAGE: u32 = 100;
+
+    fn default_page() -> u32 {
+        1
+    }
+
+    fn default_per_page() -> u32 {
+        20
+    }
+}
+
+/// Single version in the response body of [`get_history`].
+#[derive(Serialize, Debug)]
+struct HistoryEntry {
+    version: i32,
+    recorded_at: DateTime<Utc>,
+    action: String,
+    /// Version restored by this version, if it was recorded by a revert.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reverted_to: Option<i32>,
+    /// Changes from the previous version; empty for the first version.
+    changes: TaskDiff,
+}
+
+#[tracing::instrument]
+async fn get_history(
+    State(state): State<Arc<AppState>>,
+    Path(task_id): Path<Uuid>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<Vec<HistoryEntry>>, Response> {
+    if params.page == 0 || params.per_page == 0 || params.per_page > HistoryParams::MAX_PER_PAGE {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "page must be at least 1 and per_page between 1 and {}",
+                HistoryParams::MAX_PER_PAGE
+            ),
+        )
+            .into_response());
+    }
+
+    // also fetch the version before the page, to diff the page's first entry
+    let first = i64::from(params.page - 1) * i64::from(params.per_page) + 1;
+    let last = first + i64::from(params.per_page) - 1;
+
+    let versions = match state.store.history(task_id, first - 1..=last).await {
+        Ok(versions) if versions.is_empty() && params.page == 1 => {
+            return Err(StatusCode::NOT_FOUND.into_response());
+        }
+        Ok(versions) => versions,
+        Err(e) => return Err(e.into_response()),
+    };
+
+    let mut previous: Option<&TodoTask> = None;
+    let mut entries = Vec::with_capacity(versions.len());
+    for version in &versions {
+        let changes =
+            previous.map_or_else(TaskDiff::default, |p| TaskDiff::between(p, &version.task));
+        previous = Some(&version.task);
+        if i64::from(version.version) >= first {
+            entries.push(HistoryEntry {
+                version: version.version,
+                recorded_at: version.recorded_at,
+                action: version.action.clone(),
+                reverted_to: version.reverted_to,
+                changes,
+            });
+        }
+    }
+
+    Ok(Json(entries))
+}
+
+/// Response body of [`get_history_version`].
+#[derive(Serialize, Debug)]
+struct HistoryVersion {
+    version: i32,
+    recorded_at: DateTime<Utc>,
+    action: String,
+    /// Version restored by this version, if it was recorded by a revert.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reverted_to: Option<i32>,
+    /// State of the task as of this version.
+    task: TodoTask,
+}
+
+impl From<TaskVersion> for HistoryVersion {
+    fn from(version: TaskVersion) -> Self {
+        Self {
+            version: version.version,
+            recorded_at: version.recorded_at,
+            action: version.action,
+            reverted_to: version.reverted_to,
+            task: version.task,
+        }
+    }
+}
+
+#[tracing::instrument]
+async fn get_history_version(
+    State(state): State<Arc<AppState>>,
+    Path((task_id, version)): Path<(Uuid, i32)>,
+) -> Result<Json<HistoryVersion>, Response> {
+    match state.store.version(task_id, version).await {
+        Ok(Some(version)) => Ok(Json(version.into())),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Response body of [`put_task`] for a dry run.
+#[derive(Serialize, Debug)]
+struct UpdatePreview {
+    /// Changes which would be made to the task.
+    changes: TaskDiff,
+}
+
+#[tracing::instrument]
+async fn put_task(
+    State(state): State<Arc<AppState>>,
+    Path(task_id): Path<Uuid>,
+    Language(locale): Language,
+    Query(DryRunParams { dry_run }): Query<DryRunParams>,
+    ValidatedJson(task): ValidatedJson<TodoTask>,
+) -> Result<Response, Response> {
+    if state.workflow.transitions.is_some() || dry_run {
+        let current = match state.store.get(task_id).await {
+            Ok(Some(current)) => current,
+            Ok(None) => return Err(StatusCode::NOT_FOUND.into_response()),
+            Err(e) => return Err(e.into_response()),
+        };
+        if !state.workflow.allows(&current.status, &task.status) {
+            debug!(from = ?current.status, to = ?task.status, "disallowed change of status");
+            return Err((
+                StatusCode::CONFLICT,
+                Language(locale),
+                locale.translate("the workflow doesn't allow this change of status"),
+            )
+                .into_response());
+        }
+        if dry_run {
+            let changes = TaskDiff::between(&current, &task);
+            return Ok(Json(UpdatePreview { changes }).into_response());
+        }
+    }
+
+    match state.store.update(task_id, &task).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Ok(false) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Request body of [`reschedule_tasks`], which must have exactly one of the
+/// fields.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct RescheduleRequest {
+    /// Number of seconds to move due dates by, which may be negative.
+    shift_seconds: Option<i64>,
+    /// Date & time to move due dates to.
+    due: Option<DateTime<Utc>>,
+}
+
+/// Response body of [`reschedule_tasks`].
+#[derive(Serialize, Debug)]
+struct Rescheduled {
+    /// IDs of the tasks rescheduled.
+    rescheduled: Vec<Uuid>,
+}
+
+/// Task which would be rescheduled, in [`ReschedulePreview`].
+#[derive(Serialize, Debug)]
+struct RescheduledTask {
+    /// ID of the task.
+    id: Uuid,
+    /// Due date of the task now.
+    from: DateTime<Utc>,
+    /// Due date the task would be moved to.
+    to: DateTime<Utc>,
+}
+
+/// Response body of [`reschedule_tasks`] for a dry run.
+#[derive(Serialize, Debug)]
+struct ReschedulePreview {
+    /// Tasks which would be rescheduled.
+    rescheduled: Vec<RescheduledTask>,
+}
+
+/// Move the due dates of every task matching the filters, such as when a
+/// hearing they lead up to moves.
+///
+/// Either all of the tasks are rescheduled or, if any would be due outside
+/// the due window, none are.
+#[tracing::instrument]
+async fn reschedule_tasks(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Language(locale): Language,
+    Query(params): Query<ListParams>,
+    Query(DryRunParams { dry_run }): Query<DryRunParams>,
+    headers: HeaderMap,
+    Json(request): Json<RescheduleRequest>,
+) -> Result<Response, Response> {
+    let filters = params.filters().map_err(IntoResponse::into_response)?;
+    if filters.is_empty() {
+        // almost certainly a mistake, rather than meaning every task
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "a filter is needed to reschedule tasks",
+        )
+            .into_response());
+    }
+    let reschedule = match (request.shift_seconds, request.due) {
+        (Some(seconds), None) => TimeDelta::try_seconds(seconds).map(Reschedule::Shift),
+        (None, Some(due)) => Some(Reschedule::To(due)),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            "give one of a shift_seconds in range or a due",
+        )
+            .into_response()
+    })?;
+
+    let out_of_window = |id: Uuid| {
+        debug!(%id, "rescheduled due date out of window");
+        (
+            StatusCode::BAD_REQUEST,
+            Language(locale),
+            locale.translate("due date is too far in the past or future"),
+        )
+            .into_response()
+    };
+
+    if dry_run {
+        let tasks = state
+            .store
+            .list(&filters, None)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let now = state.clock.now();
+        let mut rescheduled = Vec::with_capacity(tasks.len());
+        for TaskRecord { id, task } in tasks {
+            let to = reschedule
+                .apply(*task.due(), state.due_window, now)
+                .ok_or_else(|| out_of_window(id))?;
+            rescheduled.push(RescheduledTask {
+                id,
+                from: *task.due(),
+                to,
+            });
+        }
+        return Ok(Json(ReschedulePreview { rescheduled }).into_response());
+    }
+
+    if prefers_async(&headers) {
+        let job = start_job(&state, owner.as_deref(), JobKind::Reschedule, None, &[]).await?;
+        let job_id = job.id;
+        let work_state = Arc::clone(&state);
+        let work = store::act_as(owner, async move {
+            let state = work_state;
+            let (status, result, error) = match state
+                .store
+                .reschedule(&filters, reschedule, state.due_window)
+                .await
+            {
+                Ok(RescheduleOutcome::Rescheduled(rescheduled)) => {
+                    let total = rescheduled.len();
+                    let result = Rescheduled { rescheduled };
+                    (JobStatus::Succeeded, Some((total, result)), None)
+                }
+                Ok(RescheduleOutcome::OutOfWindow(id)) => {
+                    debug!(%id, "rescheduled due date out of window");
+                    let error = locale.translate("due date is too far in the past or future");
+                    (JobStatus::Failed, None, Some(error))
+                }
+                Err(e) => {
+                    error!(error = format!("{e}"), "database error in reschedule job");
+                    (JobStatus::Failed, None, Some("database error"))
+                }
+            };
+            let (total, result) = result.unzip();
+            finish_job(&state, job_id, status, total.unwrap_or(0), result, error).await;
+        });
+        return spawn_job(&state, job, work).await;
+    }
+
+    match state
+        .store
+        .reschedule(&filters, reschedule, state.due_window)
+        .await
+    {
+        Ok(RescheduleOutcome::Rescheduled(rescheduled)) => {
+            Ok(Json(Rescheduled { rescheduled }).into_response())
+        }
+        Ok(RescheduleOutcome::OutOfWindow(id)) => Err(out_of_window(id)),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+#[tracing::instrument]
+async fn revert_task(
+    State(state): State<Arc<AppState>>,
+    Language(locale): Language,
+    Path((task_id, version)): Path<(Uuid, i32)>,
+) -> Result<Json<TodoTask>, Response> {
+    // the version's due date may have left the due window since
+    match state.store.version(task_id, version).await {
+        Ok(Some(target)) => {
+            if let Err(e) = state.due_window.check(&target.task, state.clock.now()) {
+                debug!(%task_id, version, "reverted due date out of window");
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Language(locale),
+                    locale.translate(e),
+                )
+                    .into_response());
+            }
+        }
+        Ok(None) => return Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => return Err(e.into_response()),
+    }
+    match state.store.revert(task_id, version).await {
+        Ok(Some(task)) => Ok(Json(task)),
+        // either the task or the version doesn't exist
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Request body of [`put_assignee`].
+#[derive(Deserialize, Debug)]
+struct Assignment {
+    /// Who to assign the task to, or `None` to unassign it.
+    assignee: Option<String>,
+}
+
+#[tracing::instrument]
+async fn put_assignee(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(task_id): Path<Uuid>,
+    Json(assignment): Json<Assignment>,
+) -> Result<StatusCode, Response> {
+    let assignee = assignment.assignee.as_deref().map(str::trim);
+    if let Some(assignee) = assignee {
+        // assignees must be able to make requests themselves
+        let allowed = if assignee.is_empty() {
+            Ok(false)
+        } else if state.registered_users_only {
+            state
+                .users
+                .active_roles(assignee)
+                .await
+                .map(|roles| roles.is_some())
+        } else {
+            state.users.is_deactivated(assignee).await.map(|d| !d)
+        };
+        match allowed {
+            Ok(true) => {}
+            Ok(false) => {
+                debug!("invalid assignee received");
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "assignee must be a non-empty name of an active user",
+                )
+                    .into_response());
+            }
+            Err(e) => {
+                error!(
+                    error = format!("{e}"),
+                    "database error trying to check user registration"
+                );
+                return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+            }
+        }
+    }
+
+    // others' tasks look missing, as they do with row-level security
+    match state
+        .store
+        .assign(task_id, owner.as_deref(), assignee)
+        .await
+    {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+#[tracing::instrument]
+async fn pin_task(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(task_id): Path<Uuid>,
+) -> Result<StatusCode, Response> {
+    set_pinned(&state, owner, task_id, true).await
+}
+
+#[tracing::instrument]
+async fn unpin_task(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(task_id): Path<Uuid>,
+) -> Result<StatusCode, Response> {
+    set_pinned(&state, owner, task_id, false).await
+}
+
+/// Pin or unpin a task for the [`Owner`] making a request.
+async fn set_pinned(
+    state: &AppState,
+    owner: Option<String>,
+    task_id: Uuid,
+    pinned: bool,
+) -> Result<StatusCode, Response> {
+    let owner = owner.ok_or_else(anonymous_rejection)?;
+
+    match state.store.set_pinned(task_id, &owner, pinned).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+#[tracing::instrument]
+async fn start_timer(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(task_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<TimeEntry>), Response> {
+    let owner = owner.ok_or_else(anonymous_rejection)?;
+
+    match state.store.start_timer(task_id, &owner).await {
+        Ok(Some(entry)) => Ok((StatusCode::CREATED, Json(entry))),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(StoreError::Conflict(_)) => Err((
+            StatusCode::CONFLICT,
+            "a timer is already running on this task",
+        )
+            .into_response()),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+#[tracing::instrument]
+async fn stop_timer(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(task_id): Path<Uuid>,
+) -> Result<Json<TimeEntry>, Response> {
+    let owner = owner.ok_or_else(anonymous_rejection)?;
+
+    match state.store.stop_timer(task_id, &owner).await {
+        Ok(Some(entry)) => Ok(Json(entry)),
+        // either the task doesn't exist or no timer is running on it
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Query parameters of [`list_tasks`] and [`get_graph`].
+#[derive(Deserialize, Debug)]
+struct ListParams {
+    /// Filter expression, see [`crate::filter`].
+    q: Option<String>,
+    /// Tag expression, see [`FilterExpr::parse_tags`].
+    tags: Option<String>,
+}
+
+#[tracing::instrument]
+async fn list_tasks(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Query(params): Query<ListParams>,
+    Query(LanguageParams { lang }): Query<LanguageParams>,
+    format: BodyFormat,
+) -> Result<Response, Response> {
+    let filters = params.filters().map_err(IntoResponse::into_response)?;
+
+    match state.store.list(&filters, owner.as_deref()).await {
+        Ok(tasks) => {
+            let tasks: Vec<_> = tasks
+                .into_iter()
+                .map(|TaskRecord { id, task }| TaskRecord {
+                    id,
+                    task: task.localised(lang.unwrap_or_default()),
+                })
+                .collect();
+            Ok(format.respond(tasks, |tasks| proto::TaskList {
+                tasks: tasks.iter().map(Into::into).collect(),
+            }))
+        }
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+impl ListParams {
+    /// Parse the filter expressions given.
+    fn filters(&self) -> Result<Vec<FilterExpr>, (StatusCode, String)> {
+        let filter = self.q.as_deref().map(str::parse::<FilterExpr>);
+        let tag_filter = self.tags.as_deref().map(FilterExpr::parse_tags);
+        filter
+            .into_iter()
+            .chain(tag_filter)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                debug!(error = format!("{e}"), "malformed filter received");
+                (StatusCode::BAD_REQUEST, format!("{e}"))
+            })
+    }
+}
+
+/// Maximum number of tasks [`lookup_tasks`] looks up at once.
+const MAX_LOOKUP: usize = 1000;
+
+/// Response body of [`lookup_tasks`].
+#[derive(Serialize, Debug, Default)]
+struct Lookup {
+    /// The tasks which exist, in the order their IDs were given.
+    tasks: Vec<TaskRecord>,
+    /// IDs of the tasks which don't exist, in the order they were given.
+    missing: Vec<Uuid>,
+}
+
+/// Get the tasks with each of the IDs in a JSON array, in one round trip.
+///
+/// IDs given more than once are only looked up once.
+#[tracing::instrument]
+async fn lookup_tasks(
+    State(state): State<Arc<AppState>>,
+    Query(LanguageParams { lang }): Query<LanguageParams>,
+    Json(mut ids): Json<Vec<Uuid>>,
+) -> Result<Json<Lookup>, Response> {
+    let mut seen = HashSet::new();
+    ids.retain(|id| seen.insert(*id));
+    if ids.len() > MAX_LOOKUP {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("at most {MAX_LOOKUP} tasks may be looked up at once"),
+        )
+            .into_response());
+    }
+
+    match state.store.get_many(&ids).await {
+        Ok(found) => {
+            let mut found: HashMap<_, _> = found
+                .into_iter()
+                .map(|TaskRecord { id, task }| (id, task))
+                .collect();
+            let mut lookup = Lookup::default();
+            for id in ids {
+                match found.remove(&id) {
+                    Some(task) => lookup.tasks.push(TaskRecord {
+                        id,
+                        task: task.localised(lang.unwrap_or_default()),
+                    }),
+                    None => lookup.missing.push(id),
+                }
+            }
+            Ok(Json(lookup))
+        }
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Query parameters of [`export_tasks`].
+#[derive(Deserialize, Debug)]
+struct ExportParams {
+    /// File format to export tasks in.
+    format: ExportFormat,
+}
+
+/// File format of [`export_tasks`].
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+    /// Apache Parquet, if built with the `parquet` feature.
+    Parquet,
+}
+
+/// Number of tasks in each row group of Parquet exports.
+#[cfg(feature = "parquet")]
+const EXPORT_PAGE_SIZE: i64 = 10_000;
+
+/// Export every task for analytics, streaming it page by page.
+#[tracing::instrument]
+async fn export_tasks(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, credential): Owner,
+    Query(params): Query<ExportParams>,
+) -> Result<Response, Response> {
+    check_role(&state, owner.as_deref(), credential, &[Role::Admin]).await?;
+
+    match params.format {
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => Ok(stream_parquet(state, owner)),
+        #[cfg(not(feature = "parquet"))]
+        ExportFormat::Parquet => Err((
+            StatusCode::NOT_IMPLEMENTED,
+            "server was built without Parquet support",
+        )
+            .into_response()),
+    }
+}
+
+/// Respond with a Parquet file of every task, written while it's sent.
+///
+/// Errors part way through abort the response, so that clients can't
+/// mistake a truncated file for a complete one.
+#[cfg(feature = "parquet")]
+fn stream_parquet(state: Arc<AppState>, owner: Option<String>) -> Response {
+    use crate::{export::ParquetExport, store::act_as};
+    use tokio::sync::mpsc;
+
+    /// Send every task to `sender` as chunks of a Parquet file.
+    async fn export(
+        state: &AppState,
+        sender: &mpsc::Sender<std::io::Result<Vec<u8>>>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut export = ParquetExport::new()?;
+        let mut after = None;
+        loop {
+            let page = state.store.export_page(after, EXPORT_PAGE_SIZE).await?;
+            let Some(last) = page.last() else {
+                break;
+            };
+            after = Some(last.task.id);
+            if sender.send(Ok(export.write(&page)?)).await.is_err() {
+                debug!("task export abandoned by client");
+                return Ok(());
+            }
+        }
+        // the client may have gone away, which doesn't matter now
+        let _ = sender.send(Ok(export.finish()?)).await;
+        Ok(())
+    }
+
+    let (sender, receiver) = mpsc::channel(1);
+    tokio::spawn(act_as(owner, async move {
+        if let Err(e) = export(&state, &sender).await {
+            error!(error = format!("{e}"), "error trying to export tasks");
+            let _ = sender.send(Err(std::io::Error::other(e))).await;
+        }
+    }));
+
+    let chunks = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        let chunk = receiver.recv().await?;
+        Some((chunk, receiver))
+    });
+    (
+        [
+            (header::CONTENT_TYPE, "application/vnd.apache.parquet"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"tasks.parquet\"",
+            ),
+        ],
+        axum::body::Body::from_stream(chunks),
+    )
+        .into_response()
+}
+
+#[tracing::instrument]
+async fn get_graph(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ListParams>,
+) -> Result<Json<TaskGraph>, Response> {
+    let filters = params.filters().map_err(IntoResponse::into_response)?;
+
+    match state.store.graph(&filters).await {
+        Ok(graph) => Ok(Json(graph)),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Query parameters of [`get_calendar`].
+#[derive(Deserialize, Debug)]
+struct CalendarParams {
+    /// First day of the calendar, in UTC.
+    from: NaiveDate,
+    /// Last day of the calendar, in UTC.
+    to: NaiveDate,
+}
+
+impl CalendarParams {
+    /// Largest allowed number of days.
+    const MAX_DAYS: i64 = 366;
+}
+
+/// Day in the response body of [`get_calendar`].
+#[derive(Serialize, Debug)]
+struct CalendarDay {
+    /// Day on which the tasks are due, in UTC.
+    date: NaiveDate,
+    /// Tasks due on the day, in order of due date.
+    tasks: Vec<TaskRecord>,
+}
+
+#[tracing::instrument]
+async fn get_calendar(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CalendarParams>,
+) -> Result<Json<Vec<CalendarDay>>, Response> {
+    let days = (params.to - params.from).num_days() + 1;
+    if !(1..=CalendarParams::MAX_DAYS).contains(&days) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "to must not be before from, and cover at most {} days",
+                CalendarParams::MAX_DAYS
+            ),
+        )
+            .into_response());
+    }
+
+    let start = params.from.and_time(NaiveTime::MIN).and_utc();
+    let end = start + TimeDelta::days(days);
+    let filters = [
+        FilterExpr::Condition(Condition::Due(Comparison::Ge, start)),
+        FilterExpr::Condition(Condition::Due(Comparison::Lt, end)),
+    ];
+    match state.store.list(&filters, None).await {
+        Ok(tasks) => {
+            // tasks are listed in order of due date, so each day's are adjacent
+            let calendar = tasks
+                .chunk_by(|a, b| a.task.due().date_naive() == b.task.due().date_naive())
+                .map(|tasks| CalendarDay {
+                    date: tasks[0].task.due().date_naive(),
+                    tasks: tasks.to_vec(),
+                })
+                .collect();
+            Ok(Json(calendar))
+        }
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Query parameters of [`search_tasks`].
+#[derive(Deserialize, Debug)]
+struct SearchParams {
+    /// Text to fuzzily match against task titles.
+    title: String,
+    /// Minimum similarity of results, overriding the configured default.
+    threshold: Option<f32>,
+}
+
+#[tracing::instrument]
+async fn search_tasks(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<Vec<SearchMatch>>, Response> {
+    let threshold = params.threshold.unwrap_or(state.search_threshold);
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err((StatusCode::BAD_REQUEST, "threshold must be between 0 and 1").into_response());
+    }
+
+    match state.store.search(&params.title, threshold).await {
+        Ok(results) => Ok(Json(results)),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Query parameters of [`post_task`].
+#[derive(Deserialize, Debug)]
+struct CreateParams {
+    /// Check for duplicates, overriding the configured default.
+    detect_duplicates: Option<bool>,
+}
+
+/// Response body of [`post_task`] when configured to warn of problems with
+/// created tasks.
+#[derive(Serialize, Debug)]
+struct CreatedTask {
+    /// ID of the task.
+    id: Uuid,
+    /// Human-readable problems with the task, which was created regardless.
+    warnings: Vec<&'static str>,
+}
+
+/// Response body of [`post_task`] for a dry run.
+#[derive(Serialize, Debug)]
+struct CreatePreview {
+    /// Task which would be created.
+    task: TodoTask,
+    /// Human-readable problems with the task, which would be created
+    /// regardless.
+    warnings: Vec<&'static str>,
+}
+
+/// Response body of [`post_task`] when the task may be a duplicate.
+#[derive(Serialize, Debug)]
+struct DuplicatesFound {
+    /// Human-readable explanation of the conflict.
+    message: &'static str,
+    /// Existing open tasks which the new task may duplicate.
+    candidates: Vec<TaskRecord>,
+}
+
+#[tracing::instrument]
+async fn post_task(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Language(locale): Language,
+    Query(params): Query<CreateParams>,
+    Query(DryRunParams { dry_run }): Query<DryRunParams>,
+    headers: HeaderMap,
+    ValidatedJson(mut task): ValidatedJson<TodoTask>,
+) -> Result<Response, Response> {
+    let key = idempotency_key(&headers).map_err(IntoResponse::into_response)?;
+    let now = state.clock.now();
+    // stamped here too, for previews and hooks to show when it was completed
+    task.transition(task.status.clone(), now);
+    let mut warnings = Vec::new();
+    let overdue = task.past_due(state.overdue_hours.as_ref(), now)
+        && !matches!(task.status, TodoStatus::Complete | TodoStatus::Cancelled);
+    if overdue {
+        let message = "due date has already passed";
+        match state.past_due_on_create {
+            cli::PastDuePolicy::Reject => {
+                debug!("overdue task received");
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Language(locale),
+                    locale.translate(message),
+                )
+                    .into_response());
+            }
+            cli::PastDuePolicy::Allow => {}
+            cli::PastDuePolicy::AllowWithWarning => warnings.push(message),
+        }
+    }
+
+    if let Some(key) = key.filter(|_| !dry_run) {
+        if replay_create(&state, owner.as_deref(), key, &task).await? {
+            debug!(task_id = format!("{key}"), "repeated task creation");
+            return Ok(created_response(&state, key, warnings));
+        }
+    }
+
+    // previews don't count towards the rate limit
+    if dry_run {
+        check_open_quota(&state, owner.as_deref()).await?;
+    } else {
+        check_creation_limits(&state, owner.as_deref()).await?;
+    }
+
+    if params.detect_duplicates.unwrap_or(state.detect_duplicates) {
+        match state
+            .store
+            .find_duplicates(&task, state.duplicate_threshold)
+            .await
+        {
+            Ok(candidates) if candidates.is_empty() => {}
+            Ok(candidates) => {
+                debug!(count = candidates.len(), "possible duplicate task received");
+                let body = DuplicatesFound {
+                    message: "task may duplicate existing open tasks",
+                    candidates,
+                };
+                return Err((StatusCode::CONFLICT, Json(body)).into_response());
+            }
+            Err(e) => return Err(e.into_response()),
+        }
+    }
+
+    if dry_run {
+        return Ok(Json(CreatePreview { task, warnings }).into_response());
+    }
+
+    let created = match key {
+        Some(key) => match state
+            .store
+            .create_with_id(key, &task, owner.as_deref())
+            .await
+        {
+            Ok(()) => Ok(key),
+            // lost a race with the same request
+            Err(StoreError::Conflict(_))
+                if replay_create(&state, owner.as_deref(), key, &task).await? =>
+            {
+                return Ok(created_response(&state, key, warnings));
+            }
+            Err(e) => Err(e),
+        },
+        None => state.store.create(&task, owner.as_deref()).await,
+    };
+    match created {
+        Ok(task_id) => {
+            zapier::task_created(&state, owner.as_deref(), task_id, &task);
+            Ok(created_response(&state, task_id, warnings))
+        }
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Response to the creation of the task with `id`.
+fn created_response(state: &AppState, id: Uuid, warnings: Vec<&'static str>) -> Response {
+    // the plain ID has no room for warnings, so they come with a different
+    // response, only when asked for
+    if state.past_due_on_create == cli::PastDuePolicy::AllowWithWarning {
+        let body = CreatedTask { id, warnings };
+        (StatusCode::CREATED, Json(body)).into_response()
+    } else {
+        format!("{id}").into_response()
+    }
+}
+
+/// Get the `Idempotency-Key` of a request, if it has one.
+///
+/// Keys must be UUIDs, and become the ID of the task created, so a request
+/// repeated with the same key can't create another task.
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<Uuid>, (StatusCode, &'static str)> {
+    headers
+        .get(IDEMPOTENCY_KEY)
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .ok_or((StatusCode::BAD_REQUEST, "Idempotency-Key must be a UUID"))
+        })
+        .transpose()
+}
+
+/// Check whether `task` was already created by `owner` with the idempotency
+/// `key`, returning an error if the key was used to create a different task.
+///
+/// Keys used by other owners aren't replayed, so creating the task fails
+/// with a conflict instead.
+async fn replay_create(
+    state: &AppState,
+    owner: Option<&str>,
+    key: Uuid,
+    task: &TodoTask,
+) -> Result<bool, Response> {
+    let created = state
+        .store
+        .created(key, owner)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    match created {
+        Some(created) if TaskDiff::between(&created, task).changes.is_empty() => Ok(true),
+        Some(_) => Err((
+            StatusCode::CONFLICT,
+            "Idempotency-Key was already used to create a different task",
+        )
+            .into_response()),
+        None => Ok(false),
+    }
+}
+
+/// Query parameters of [`import_tasks`].
+#[derive(Deserialize, Debug)]
+struct ImportParams {
+    /// Application the tasks were exported from.
+    from: ImportFormat,
+    /// Due date of items which don't have one.
+    ///
+    /// Such items are rejected if this isn't given.
+    default_due: Option<DateTime<Utc>>,
+}
+
+/// Item of an export which couldn't be imported, in [`ImportReport`].
+#[derive(Serialize, Debug, Clone)]
+struct RejectedItem {
+    /// Position of the item among those imported, from 0.
+    index: usize,
+    /// Human-readable reason the item was rejected.
+    error: &'static str,
+}
+
+/// Response body of [`import_tasks`].
+#[derive(Serialize, Debug)]
+struct ImportReport {
+    /// IDs of the tasks created, in the order of the export.
+    created: Vec<Uuid>,
+    /// Items which weren't imported.
+    rejected: Vec<RejectedItem>,
+}
+
+/// Response body of [`import_tasks`] for a dry run.
+#[derive(Serialize, Debug)]
+struct ImportPreview {
+    /// Tasks which would be created, in the order of the export.
+    tasks: Vec<TodoTask>,
+    /// Items which wouldn't be imported.
+    rejected: Vec<RejectedItem>,
+}
+
+#[tracing::instrument(skip(export))]
+async fn import_tasks(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Language(locale): Language,
+    Query(params): Query<ImportParams>,
+    Query(DryRunParams { dry_run }): Query<DryRunParams>,
+    headers: HeaderMap,
+    export: String,
+) -> Result<Response, Response> {
+    let items = match params.from.convert(&export, params.default_due) {
+        Ok(items) => items,
+        Err(e) => {
+            debug!(error = format!("{e}"), "malformed export received");
+            return Err((StatusCode::BAD_REQUEST, format!("malformed export: {e}")).into_response());
+        }
+    };
+
+    // validate every item before creating any tasks
+    let now = state.clock.now();
+    let mut tasks = Vec::new();
+    let mut rejected = Vec::new();
+    for (index, item) in items.into_iter().enumerate() {
+        let task = item.and_then(|task| task.check(state.due_window, now));
+        match task {
+            Ok(task) => tasks.push(task),
+            Err(e) => rejected.push(RejectedItem {
+                index,
+                error: locale.translate(e),
+            }),
+        }
+    }
+
+    if let Some(max) = state.max_open_tasks {
+        let importing = tasks
+            .iter()
+            .filter(|t| !matches!(t.status, TodoStatus::Complete | TodoStatus::Cancelled))
+            .count();
+        match state.store.count_open(owner.as_deref()).await {
+            Ok(open) if open + i64::try_from(importing).unwrap_or(i64::MAX) <= i64::from(max) => {}
+            Ok(_) => {
+                debug!(owner, importing, "open task quota exceeded by import");
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    format!("importing would exceed the quota of {max} open tasks"),
+                )
+                    .into_response());
+            }
+            Err(e) => return Err(e.into_response()),
+        }
+    }
+
+    if dry_run {
+        let preview = ImportPreview { tasks, rejected };
+        return Ok((Language(locale), Json(preview)).into_response());
+    }
+
+    if prefers_async(&headers) {
+        let errors: Vec<_> = rejected
+            .iter()
+            .map(|item| ItemError {
+                index: item.index,
+                error: item.error.to_owned(),
+            })
+            .collect();
+        let total = i32::try_from(tasks.len()).unwrap_or(i32::MAX);
+        let job = start_job(
+            &state,
+            owner.as_deref(),
+            JobKind::Import,
+            Some(total),
+            &errors,
+        )
+        .await?;
+        let work = store::act_as(
+            owner.clone(),
+            run_import(Arc::clone(&state), job.id, owner, tasks, rejected),
+        );
+        return spawn_job(&state, job, work).await;
+    }
+
+    let mut created = Vec::with_capacity(tasks.len());
+    for task in &tasks {
+        match state.store.create(task, owner.as_deref()).await {
+            Ok(task_id) => {
+                zapier::task_created(&state, owner.as_deref(), task_id, task);
+                created.push(task_id);
+            }
+            Err(e) => {
+                // the tasks already created are kept
+                warn!(imported = created.len(), "import of tasks stopped early");
+                return Err(e.into_response());
+            }
+        }
+    }
+
+    info!(
+        owner,
+        created = created.len(),
+        rejected = rejected.len(),
+        "imported tasks"
+    );
+    Ok((
+        StatusCode::CREATED,
+        Language(locale),
+        Json(ImportReport { created, rejected }),
+    )
+        .into_response())
+}
+
+/// Import `tasks` for `owner` in the background, as the job with `job_id`,
+/// stopping early if it is cancelled.
+async fn run_import(
+    state: Arc<AppState>,
+    job_id: Uuid,
+    owner: Option<String>,
+    tasks: Vec<TodoTask>,
+    rejected: Vec<RejectedItem>,
+) {
+    let mut created = Vec::with_capacity(tasks.len());
+    let mut status = JobStatus::Succeeded;
+    let mut error = None;
+    for (index, task) in tasks.iter().enumerate() {
+        if index > 0 && index % JOB_PROGRESS_INTERVAL == 0 {
+            let processed = i32::try_from(index).unwrap_or(i32::MAX);
+            match state.jobs.progress(job_id, processed).await {
+                Ok(true) => {
+                    status = JobStatus::Cancelled;
+                    break;
+                }
+                Ok(false) => {}
+                // carry on, rather than abandon the import
+                Err(e) => error!(
+                    error = format!("{e}"),
+                    "database error trying to record job progress"
+                ),
+            }
+        }
+        match state.store.create(task, owner.as_deref()).await {
+            Ok(task_id) => {
+                zapier::task_created(&state, owner.as_deref(), task_id, task);
+                created.push(task_id);
+            }
+            Err(e) => {
+                // the tasks already created are kept
+                error!(error = format!("{e}"), "database error in import job");
+                status = JobStatus::Failed;
+                error = Some("database error, the tasks already created are kept");
+                break;
+            }
+        }
+    }
+
+    info!(
+        owner,
+        created = created.len(),
+        rejected = rejected.len(),
+        ?status,
+        "import job stopped"
+    );
+    let total = created.len();
+    let report = ImportReport { created, rejected };
+    finish_job(&state, job_id, status, total, Some(report), error).await;
+}
+
+/// Number of items bulk jobs process between recording their progress, and
+/// checking whether they have been cancelled.
+const JOB_PROGRESS_INTERVAL: usize = 20;
+
+/// Whether the client asked with `Prefer: respond-async` for an operation to
+/// be run in the background.
+fn prefers_async(headers: &HeaderMap) -> bool {
+    headers
+        .get_all("prefer")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|preference| preference.trim().eq_ignore_ascii_case("respond-async"))
+}
+
+/// Record the start of a job, with [`JobStore::start`].
+async fn start_job(
+    state: &AppState,
+    owner: Option<&str>,
+    kind: JobKind,
+    total: Option<i32>,
+    errors: &[ItemError],
+) -> Result<BulkJob, Response> {
+    match state.jobs.start(owner, kind, total, errors).await {
+        Ok(job) => {
+            info!(owner, job_id = %job.id, ?kind, "job started");
+            Ok(job)
+        }
+        Err(e) => {
+            error!(error = format!("{e}"), "database error trying to start job");
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Record that a job has stopped, having processed `total` items.
+async fn finish_job(
+    state: &AppState,
+    job_id: Uuid,
+    status: JobStatus,
+    total: usize,
+    result: Option<impl Serialize>,
+    error: Option<&str>,
+) {
+    let total = i32::try_from(total).unwrap_or(i32::MAX);
+    let result = result.map(|result| serde_json::to_value(result).expect("results serialize"));
+    if let Err(e) = state
+        .jobs
+        .finish(job_id, status, total, result.as_ref(), error)
+        .await
+    {
+        error!(
+            error = format!("{e}"),
+            %job_id,
+            "database error trying to record end of job"
+        );
+    }
+}
+
+/// Run `work` for `job` in the background, responding that it was accepted,
+/// or that the server is too busy if the background workers' queue is full.
+async fn spawn_job(
+    state: &AppState,
+    job: BulkJob,
+    work: impl Future<Output = ()> + Send + 'static,
+) -> Result<Response, Response> {
+    if let Err(e) = state.workers.spawn(work) {
+        warn!(job_id = %job.id, error = %e, "refused job");
+        let error = "the server was too busy to run the job";
+        finish_job(state, job.id, JobStatus::Failed, 0, None::<()>, Some(error)).await;
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, "60")],
+            "too busy to run the job, try again later",
+        )
+            .into_response());
+    }
+    Ok(accepted(job))
+}
+
+/// Response to a request whose operation is run in the background as `job`.
+fn accepted(job: BulkJob) -> Response {
+    (
+        StatusCode::ACCEPTED,
+        [
+            (header::LOCATION, format!("/jobs/{}", job.id)),
+            (
+                header::HeaderName::from_static("preference-applied"),
+                "respond-async".to_owned(),
+            ),
+        ],
+        Json(job),
+    )
+        .into_response()
+}
+
+/// Get one of the owner's jobs, to follow its progress.
+#[tracing::instrument]
+async fn get_job(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(job_id): Path<Uuid>,
+) -> Result<Json<BulkJob>, StatusCode> {
+    match state.jobs.get(job_id, owner.as_deref()).await {
+        Ok(Some(job)) => Ok(Json(job)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!(error = format!("{e}"), "database error trying to get job");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Ask one of the owner's jobs to stop, which it does between items.
+#[tracing::instrument]
+async fn cancel_job(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(job_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<BulkJob>), Response> {
+    match state.jobs.cancel(job_id, owner.as_deref()).await {
+        Ok(Some(job)) if job.status == JobStatus::Running => {
+            info!(owner, %job_id, "job cancelled");
+            Ok((StatusCode::ACCEPTED, Json(job)))
+        }
+        Ok(Some(_)) => Err((StatusCode::CONFLICT, "job has already stopped").into_response()),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to cancel job"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Check that `owner` may create another task, under the creation rate limit
+/// and the open task quota.
+async fn check_creation_limits(state: &AppState, owner: Option<&str>) -> Result<(), Response> {
+    if let Some(limiter) = &state.creation_limiter {
+        if let Err(wait) = limiter.check(owner, Instant::now()) {
+            debug!(owner, "task creation rate limit exceeded");
+            // round up, so clients retrying after the delay aren't too early
+            let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            return Err((
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                "too many tasks created recently, try again later",
+            )
+                .into_response());
+        }
+    }
+
+    check_open_quota(state, owner).await
+}
+
+/// Check that `owner` may have another open task, under the open task quota.
+async fn check_open_quota(state: &AppState, owner: Option<&str>) -> Result<(), Response> {
+    if let Some(max) = state.max_open_tasks {
+        match state.store.count_open(owner).await {
+            Ok(open) if open < i64::from(max) => {}
+            Ok(_) => {
+                debug!(owner, "open task quota exceeded");
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    format!("quota of {max} open tasks reached, complete or cancel some first"),
+                )
+                    .into_response());
+            }
+            Err(e) => return Err(e.into_response()),
+        }
+    }
+    Ok(())
+}
+
+/// Query parameters of [`get_burndown`].
+#[derive(Deserialize, Debug)]
+struct BurndownParams {
+    /// Start of the first bucket, which is aligned to the start of its period.
+    from: DateTime<Utc>,
+    /// Date & time within the last bucket.
+    to: DateTime<Utc>,
+    /// Length of each bucket.
+    #[serde(default)]
+    bucket: Bucket,
+}
+
+impl BurndownParams {
+    /// Largest allowed number of buckets.
+    const MAX_BUCKETS: i64 = 1000;
+}
+
+#[tracing::instrument]
+async fn get_burndown(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<BurndownParams>,
+) -> Result<Json<Vec<BurndownBucket>>, Response> {
+    let buckets = params.bucket.count(params.from, params.to);
+    if !(1..=BurndownParams::MAX_BUCKETS).contains(&buckets) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "to must not be before from, and cover at most {} buckets",
+                BurndownParams::MAX_BUCKETS
+            ),
+        )
+            .into_response());
+    }
+
+    match state
+        .store
+        .burndown(params.from, params.to, params.bucket)
+        .await
+    {
+        Ok(buckets) => Ok(Json(buckets)),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+#[tracing::instrument]
+async fn get_workload(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Refreshed<Vec<Workload>>>, StoreError> {
+    Ok(Json(state.store.workload().await?))
+}
+
+/// List the tasks matching the filters which have breached a service level
+/// target, including completed tasks.
+#[tracing::instrument]
+async fn get_sla_breaches(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ListParams>,
+) -> Result<Json<Vec<SlaBreach>>, Response> {
+    let filters = params.filters().map_err(IntoResponse::into_response)?;
+    if state.sla.0.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+
+    let query = async {
+        let tasks = state.store.list(&filters, None).await?;
+        let ids: Vec<_> = tasks
+            .iter()
+            .filter(|record| state.sla.policy_for(&record.task).is_some())
+            .map(|record| record.id)
+            .collect();
+        let milestones: HashMap<_, _> = state
+            .store
+            .milestones(&ids)
+            .await?
+            .into_iter()
+            .map(|milestones| (milestones.id, milestones))
+            .collect();
+        Ok::<_, StoreError>((tasks, milestones))
+    };
+    let (tasks, milestones) = query.await.map_err(IntoResponse::into_response)?;
+
+    let now = state.clock.now();
+    let breaches = tasks
+        .into_iter()
+        .filter_map(|task| {
+            let sla = state.sla.evaluate(
+                &state.working_hours,
+                &task.task,
+                milestones.get(&task.id)?,
+                now,
+            )?;
+            (sla.state == SlaState::Breached).then_some(SlaBreach { task, sla })
+        })
+        .collect();
+    Ok(Json(breaches))
+}
+
+/// Query parameters of [`get_estimate_variance`].
+#[derive(Deserialize, Debug)]
+struct EstimateVarianceParams {
+    /// What to group tasks by.
+    #[serde(default)]
+    group_by: EstimateGrouping,
+}
+
+#[tracing::instrument]
+async fn get_estimate_variance(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<EstimateVarianceParams>,
+) -> Result<Json<Refreshed<Vec<EstimateVariance>>>, StoreError> {
+    Ok(Json(state.store.estimate_variance(params.group_by).await?))
+}
+
+/// Response body of [`get_storage`].
+#[derive(Serialize, Debug)]
+struct StorageReport {
+    /// Maximum total size of each owner's attachments in bytes, if limited.
+    quota_bytes: Option<u64>,
+    /// Storage taken by each owner's attachments, largest first.
+    owners: Vec<StorageUsage>,
+}
+
+#[tracing::instrument]
+async fn get_storage(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<StorageReport>, StoreError> {
+    Ok(Json(StorageReport {
+        quota_bytes: state.max_storage_bytes,
+        owners: state.store.storage_usage().await?,
+    }))
+}
+
+/// Check that the [`Owner`] making a request is the user it concerns.
+async fn check_user(
+    state: &AppState,
+    owner: Option<&str>,
+    user_id: &str,
+) -> Result<(), (StatusCode, &'static str)> {
+    if owner == Some(user_id) {
+        Ok(())
+    } else {
+        debug!("request for another user's data received");
+        state
+            .record(
+                SecurityEventKind::PermissionDenied,
+                owner,
+                &format!("access to data of user {user_id}"),
+            )
+            .await;
+        Err((
+            StatusCode::FORBIDDEN,
+            "users may only access their own data",
+        ))
+    }
+}
+
+/// Request body of [`put_digest`].
+#[derive(Deserialize, Debug)]
+struct DigestPreference {
+    /// Whether to email the user digests of their tasks.
+    enabled: bool,
+}
+
+/// Opt a registered user in to or out of digest emails.
+#[tracing::instrument]
+async fn put_digest(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(user_id): Path<String>,
+    Json(preference): Json<DigestPreference>,
+) -> Result<StatusCode, Response> {
+    check_user(&state, owner.as_deref(), &user_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    match state.users.set_digest(&user_id, preference.enabled).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err((StatusCode::NOT_FOUND, "user is not registered").into_response()),
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to change digest preference"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Get whether a registered user is notified of each event over each
+/// channel.
+#[tracing::instrument]
+async fn get_notification_preferences(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(user_id): Path<String>,
+) -> Result<Json<Vec<Preference>>, Response> {
+    check_user(&state, owner.as_deref(), &user_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    match state.notifications.preferences(&user_id).await {
+        Ok(Some(preferences)) => Ok(Json(preferences.all())),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "user is not registered").into_response()),
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to get notification preferences"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Change whether a registered user is notified of some events over some
+/// channels, keeping their other preferences.
+#[tracing::instrument]
+async fn put_notification_preferences(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(user_id): Path<String>,
+    Json(preferences): Json<Vec<Preference>>,
+) -> Result<StatusCode, Response> {
+    check_user(&state, owner.as_deref(), &user_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    match state
+        .notifications
+        .set_preferences(&user_id, &preferences)
+        .await
+    {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err((StatusCode::NOT_FOUND, "user is not registered").into_response()),
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to set notification preferences"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Response body of [`get_push_key`].
+#[derive(Serialize, Debug)]
+struct PushKey {
+    /// Key which browsers subscribe with as the `applicationServerKey`.
+    public_key: String,
+}
+
+/// Get the public key which browsers subscribe to Web Push notifications
+/// with, or 404 if they aren't enabled.
+#[tracing::instrument]
+async fn get_push_key(State(state): State<Arc<AppState>>) -> Result<Json<PushKey>, StatusCode> {
+    state
+        .push_key
+        .clone()
+        .map(|public_key| Json(PushKey { public_key }))
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// List the browsers a user subscribed to Web Push notifications.
+#[tracing::instrument]
+async fn list_push_subscriptions(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(user_id): Path<String>,
+) -> Result<Json<Vec<Subscribed>>, Response> {
+    check_user(&state, owner.as_deref(), &user_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    match state.push_subscriptions.list(&user_id).await {
+        Ok(subscriptions) => Ok(Json(subscriptions)),
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to list push subscriptions"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Subscribe a browser of a registered user to Web Push notifications.
+#[tracing::instrument]
+async fn post_push_subscription(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(user_id): Path<String>,
+    Json(subscription): Json<PushSubscription>,
+) -> Result<(StatusCode, Json<Subscribed>), Response> {
+    check_user(&state, owner.as_deref(), &user_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    if let Err(e) = subscription.check(&state.push_allowed_hosts) {
+        debug!(reason = e, "refused push subscription");
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, e).into_response());
+    }
+
+    match state
+        .push_subscriptions
+        .subscribe(&user_id, &subscription)
+        .await
+    {
+        Ok(Some(subscribed)) => {
+            info!(user_id, subscription_id = %subscribed.id, "browser subscribed to push");
+            Ok((StatusCode::CREATED, Json(subscribed)))
+        }
+        Ok(None) => Err((StatusCode::NOT_FOUND, "user is not registered").into_response()),
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to subscribe browser to push"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Unsubscribe one of a user's browsers from Web Push notifications.
+#[tracing::instrument]
+async fn delete_push_subscription(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path((user_id, subscription_id)): Path<(String, Uuid)>,
+) -> Result<StatusCode, Response> {
+    check_user(&state, owner.as_deref(), &user_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    match state
+        .push_subscriptions
+        .unsubscribe(&user_id, subscription_id)
+        .await
+    {
+        Ok(true) => {
+            info!(user_id, %subscription_id, "browser unsubscribed from push");
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(false) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to unsubscribe browser from push"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Response body of [`export_user`].
+#[derive(Serialize, Debug)]
+struct UserExport {
+    user_id: String,
+    exported_at: DateTime<Utc>,
+    tasks: Vec<ExportedTask>,
+}
+
+/// Single task in the response body of [`export_user`].
+#[derive(Serialize, Debug)]
+struct ExportedTask {
+    #[serde(flatten)]
+    task: TaskRecord,
+    /// Every version of the task, oldest first.
+    history: Vec<HistoryVersion>,
+}
+
+#[tracing::instrument]
+async fn export_user(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(user_id): Path<String>,
+) -> Result<Json<UserExport>, Response> {
+    check_user(&state, owner.as_deref(), &user_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let query = async {
+        let mut tasks = Vec::new();
+        for task in state.store.owned(&user_id).await? {
+            let history = state
+                .store
+                .history(task.id, 1..=i64::MAX)
+                .await?
+                .into_iter()
+                .map(HistoryVersion::from)
+                .collect();
+            tasks.push(ExportedTask { task, history });
+        }
+        Ok::<_, StoreError>(tasks)
+    };
+
+    match query.await {
+        Ok(tasks) => Ok(Json(UserExport {
+            user_id,
+            exported_at: state.clock.now(),
+            tasks,
+        })),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Query parameters of [`get_timesheet`].
+#[derive(Deserialize, Debug)]
+struct TimesheetParams {
+    /// Only include entries started at or after this time.
+    from: DateTime<Utc>,
+    /// Only include entries started before this time.
+    to: DateTime<Utc>,
+}
+
+/// Response body of [`get_timesheet`].
+#[derive(Serialize, Debug)]
+struct Timesheet {
+    /// User whose time was tracked.
+    user_id: String,
+    /// Time entries, in the order they were started.
+    entries: Vec<TimesheetEntry>,
+    /// Total number of seconds of the entries.
+    total_seconds: i64,
+}
+
+#[tracing::instrument]
+async fn get_timesheet(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(user_id): Path<String>,
+    Query(params): Query<TimesheetParams>,
+) -> Result<Json<Timesheet>, Response> {
+    check_user(&state, owner.as_deref(), &user_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    match state
+        .store
+        .timesheet(&user_id, params.from, params.to)
+        .await
+    {
+        Ok(entries) => Ok(Json(Timesheet {
+            total_seconds: entries.iter().map(|e| e.seconds).sum(),
+            user_id,
+            entries,
+        })),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Number of entries in the activity feed.
+const FEED_LENGTH: i64 = 50;
+
+/// Serve an Atom feed of the latest creations and completions of the
+/// owner's tasks.
+#[tracing::instrument]
+async fn get_feed(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Language(locale): Language,
+    headers: HeaderMap,
+) -> Result<Response, Response> {
+    match state.store.activity(owner.as_deref(), FEED_LENGTH).await {
+        Ok(activity) => {
+            let author = owner.as_deref().unwrap_or("anonymous");
+            let feed = feed::to_atom(
+                &activity,
+                &public_url(&headers),
+                author,
+                locale,
+                state.clock.now(),
+            );
+            Ok((
+                Language(locale),
+                [(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+                feed,
+            )
+                .into_response())
+        }
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Get the URL of the application as requested by the client, from the
+/// `Host` header and any `X-Forwarded-Proto` header set by a proxy.
+fn public_url(headers: &HeaderMap) -> String {
+    let value = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let scheme = value("x-forwarded-proto").unwrap_or("http");
+    let host = value(header::HOST.as_str()).unwrap_or("localhost");
+    format!("{scheme}://{host}")
+}
+
+#[tracing::instrument]
+async fn get_mentions(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(user_id): Path<String>,
+) -> Result<Json<Vec<Mention>>, Response> {
+    check_user(&state, owner.as_deref(), &user_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    match state.store.mentions(&user_id).await {
+        Ok(mentions) => Ok(Json(mentions)),
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Response body of [`erase_user`].
+#[derive(Serialize, Debug)]
+struct Erasure {
+    /// Number of tasks erased.
+    erased_tasks: u64,
+}
+
+#[tracing::instrument]
+async fn erase_user(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(user_id): Path<String>,
+) -> Result<Json<Erasure>, Response> {
+    check_user(&state, owner.as_deref(), &user_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    match state.store.erase(&user_id, state.history_erasure).await {
+        Ok(erased_tasks) => {
+            info!(erased_tasks, "user data erased");
+            Ok(Json(Erasure { erased_tasks }))
+        }
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Response to a request which must be made by a named owner.
+fn anonymous_rejection() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        "only named owners can do this",
+    )
+        .into_response()
+}
+
+#[tracing::instrument]
+async fn list_tokens(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+) -> Result<Json<Vec<AccessToken>>, Response> {
+    let owner = owner.ok_or_else(anonymous_rejection)?;
+
+    match state.tokens.list(&owner).await {
+        Ok(tokens) => Ok(Json(tokens)),
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to list access tokens"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Request body of [`post_token`].
+#[derive(Deserialize, Debug)]
+struct NewToken {
+    /// Description of what the token is for.
+    name: String,
+    /// Operations which the token permits.
+    scopes: Vec<TokenScope>,
+    /// Number of days until the token expires; it never expires if omitted.
+    ///
+    /// Tokens expire with the token or session they were created with, if
+    /// that expires sooner.
+    expires_in_days: Option<u16>,
+}
+
+/// Response body of [`post_token`].
+#[derive(Serialize)]
+struct CreatedToken {
+    /// The token itself, which can't be retrieved again.
+    token: String,
+    #[serde(flatten)]
+    details: AccessToken,
+}
+
+#[tracing::instrument]
+async fn post_token(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, credential): Owner,
+    Json(new): Json<NewToken>,
+) -> Result<(StatusCode, Json<CreatedToken>), Response> {
+    let owner = owner.ok_or_else(anonymous_rejection)?;
+    // anyone can name any owner in the `X-Owner` header
+    if credential == Credential::Claimed {
+        debug!("access token request without access token or session received");
+        state
+            .record(
+                SecurityEventKind::PermissionDenied,
+                Some(&owner),
+                "access token creation without access token or session",
+            )
+            .await;
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            "an access token or session is required to create access tokens",
+        )
+            .into_response());
+    }
+    if new.name.trim().is_empty() || new.scopes.is_empty() || new.expires_in_days == Some(0) {
+        debug!("malformed access token request received");
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "name and scopes must not be empty, and expires_in_days must be at least 1",
+        )
+            .into_response());
+    }
+
+    let requested = new
+        .expires_in_days
+        .map(|days| state.clock.now() + TimeDelta::days(days.into()));
+    // tokens can't outlive the credential they were created with
+    let expires_at = match (requested, credential.expires_at()) {
+        (Some(requested), Some(limit)) => Some(requested.min(limit)),
+        (requested, limit) => requested.or(limit),
+    };
+    match state
+        .tokens
+        .create(&owner, new.name.trim(), &new.scopes, expires_at)
+        .await
+    {
+        Ok((token, details)) => {
+            state
+                .record(
+                    SecurityEventKind::TokenCreated,
+                    Some(&owner),
+                    &format!("token {} ({})", details.id, details.name),
+                )
+                .await;
+            Ok((StatusCode::CREATED, Json(CreatedToken { token, details })))
+        }
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to create access token"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+#[tracing::instrument]
+async fn revoke_token(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(token_id): Path<Uuid>,
+) -> Result<StatusCode, Response> {
+    let owner = owner.ok_or_else(anonymous_rejection)?;
+
+    match state.tokens.revoke(&owner, token_id).await {
+        Ok(true) => {
+            state
+                .record(
+                    SecurityEventKind::TokenRevoked,
+                    Some(&owner),
+                    &format!("token {token_id}"),
+                )
+                .await;
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(false) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => {
+            error!(
+                token_id = format!("{token_id}"),
+                error = format!("{e}"),
+                "database error trying to revoke access token"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Name of the cookie binding a login attempt to the browser making it.
+const LOGIN_COOKIE: &str = "oidc_login";
+
+#[tracing::instrument]
+async fn login(State(state): State<Arc<AppState>>) -> Response {
+    let Some(oidc) = &state.oidc else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let attempt = LoginAttempt::new();
+    let (Ok(login_cookie), Ok(url)) = (
+        set_cookie(
+            LOGIN_COOKIE,
+            &attempt.to_cookie(),
+            "/auth/callback",
+            TimeDelta::minutes(10),
+        ),
+        oidc.authorization_url(&attempt),
+    ) else {
+        error!("failed to encode login attempt");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    (
+        AppendHeaders([(header::SET_COOKIE, login_cookie)]),
+        Redirect::to(&url),
+    )
+        .into_response()
+}
+
+/// Query parameters of [`login_callback`].
+#[derive(Deserialize, Debug)]
+struct CallbackParams {
+    /// Authorization code to exchange for an ID token.
+    code: Option<String>,
+    /// State of the login attempt.
+    state: Option<String>,
+    /// Error code, if the provider didn't log the user in.
+    error: Option<String>,
+}
+
+#[tracing::instrument(skip(headers))]
+async fn login_callback(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CallbackParams>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(oidc) = &state.oidc else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if let Some(error) = params.error {
+        debug!(error, "login refused by provider");
+        state
+            .record(
+                SecurityEventKind::LoginFailed,
+                None,
+                &format!("provider refused login: {error}"),
+            )
+            .await;
+        return (StatusCode::UNAUTHORIZED, format!("login failed: {error}")).into_response();
+    }
+
+    // the state must match the attempt started by this browser, so that nobody
+    // else's login can be completed in it
+    let attempt = cookie(&headers, LOGIN_COOKIE).and_then(LoginAttempt::from_cookie);
+    let (Some(attempt), Some(code)) = (attempt, params.code) else {
+        return unrecognised_login(&state).await;
+    };
+    if params.state.as_deref() != Some(&attempt.state) {
+        return unrecognised_login(&state).await;
+    }
+
+    let owner = match oidc.exchange(&code, &attempt, state.clock.now()).await {
+        Ok(owner) => owner,
+        Err(e @ OidcError::Http(_)) => {
+            error!(error = format!("{e}"), "failed to complete login");
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+        Err(e @ OidcError::Invalid(_)) => {
+            debug!(error = format!("{e}"), "invalid login received");
+            state
+                .record(SecurityEventKind::LoginFailed, None, &format!("{e}"))
+                .await;
+            return (StatusCode::UNAUTHORIZED, "login failed").into_response();
+        }
+    };
+
+    match state
+        .sessions
+        .create(&owner, state.clock.now() + state.session_lifetime)
+        .await
+    {
+        Ok(secret) => {
+            let (Ok(session_cookie), Ok(login_cookie)) = (
+                set_cookie(SESSION_COOKIE, &secret, "/", state.session_lifetime),
+                set_cookie(LOGIN_COOKIE, "", "/auth/callback", TimeDelta::zero()),
+            ) else {
+                error!("failed to encode session cookie");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            };
+            info!(owner, "user logged in");
+            state
+                .record(
+                    SecurityEventKind::LoginSucceeded,
+                    Some(&owner),
+                    "logged in with OpenID Connect",
+                )
+                .await;
+            (
+                AppendHeaders([
+                    (header::SET_COOKIE, session_cookie),
+                    (header::SET_COOKIE, login_cookie),
+                ]),
+                Redirect::to("/"),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to create session"
+            );
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Response to a login callback which doesn't match the browser's attempt.
+async fn unrecognised_login(state: &AppState) -> Response {
+    debug!("unrecognised login callback received");
+    state
+        .record(
+            SecurityEventKind::LoginFailed,
+            None,
+            "login callback didn't match an attempt by the browser",
+        )
+        .await;
+    (StatusCode::BAD_REQUEST, "login attempt not recognised").into_response()
+}
+
+#[tracing::instrument(skip(headers))]
+async fn logout(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    if let Some(secret) = cookie(&headers, SESSION_COOKIE) {
+        if let Err(e) = state.sessions.delete(secret).await {
+            error!(
+                error = format!("{e}"),
+                "database error trying to delete session"
+            );
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    let Ok(session_cookie) = set_cookie(SESSION_COOKIE, "", "/", TimeDelta::zero()) else {
+        error!("failed to encode session cookie");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    (
+        StatusCode::NO_CONTENT,
+        AppendHeaders([(header::SET_COOKIE, session_cookie)]),
+    )
+        .into_response()
+}
+
+/// CSRF token of a login session.
+#[derive(Serialize, Debug)]
+struct CsrfToken {
+    /// Token to send in the `X-CSRF-Token` header of requests making changes.
+    token: String,
+}
+
+#[tracing::instrument(skip(headers))]
+async fn get_csrf_token(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    let Some(secret) = cookie(&headers, SESSION_COOKIE) else {
+        return (
+            StatusCode::UNAUTHORIZED,
+            "no session to issue a CSRF token for",
+        )
+            .into_response();
+    };
+    match state.sessions.owner(secret).await {
+        Ok(Some(_)) => (
+            [(header::CACHE_CONTROL, "no-store")],
+            Json(CsrfToken {
+                token: csrf_token(secret),
+            }),
+        )
+            .into_response(),
+        Ok(None) => (
+            StatusCode::UNAUTHORIZED,
+            "session has expired, log in again",
+        )
+            .into_response(),
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to authenticate session"
+            );
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Check that the [`Owner`] making a request has one of `roles`, having
+/// authenticated with `credential`.
+///
+/// Administrators named on the command line have every role. Roles are only
+/// granted to owners authenticated with an access token or session.
+async fn check_role(
+    state: &AppState,
+    owner: Option<&str>,
+    credential: Credential,
+    roles: &[Role],
+) -> Result<(), Response> {
+    let Some(owner) = owner else {
+        return Err(anonymous_rejection());
+    };
+    // anyone can claim to be an administrator in the `X-Owner` header
+    if credential == Credential::Claimed {
+        debug!("administration request without access token or session received");
+        state
+            .record(
+                SecurityEventKind::PermissionDenied,
+                Some(owner),
+                "use of administration endpoints without access token or session",
+            )
+            .await;
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            "an access token or session is required to do this",
+        )
+            .into_response());
+    }
+    let granted = if state.admins.iter().any(|admin| admin == owner) {
+        true
+    } else {
+        match state.users.active_roles(owner).await {
+            Ok(granted) => granted.is_some_and(|granted| roles.iter().any(|r| granted.contains(r))),
+            Err(e) => {
+                error!(
+                    error = format!("{e}"),
+                    "database error trying to get user roles"
+                );
+                return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+            }
+        }
+    };
+    if granted {
+        Ok(())
+    } else {
+        debug!("administration request from non-administrator received");
+        state
+            .record(
+                SecurityEventKind::PermissionDenied,
+                Some(owner),
+                "use of administration endpoints",
+            )
+            .await;
+        Err((StatusCode::FORBIDDEN, "only administrators may do this").into_response())
+    }
+}
+
+/// Query parameters of [`get_security_events`].
+#[derive(Deserialize, Debug)]
+struct SecurityEventParams {
+    /// Only include events of this kind.
+    kind: Option<SecurityEventKind>,
+    /// Only include events concerning this owner.
+    owner: Option<String>,
+    /// Only include events which occurred at or after this time.
+    since: Option<DateTime<Utc>>,
+    /// Only include events which occurred before this time.
+    until: Option<DateTime<Utc>>,
+    /// Maximum number of events to return.
+    #[serde(default = "SecurityEventParams::default_limit")]
+    limit: u16,
+}
+
+impl SecurityEventParams {
+    fn default_limit() -> u16 {
+        100
+    }
+}
+
+#[tracing::instrument]
+async fn get_security_events(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, credential): Owner,
+    Query(params): Query<SecurityEventParams>,
+) -> Result<Json<Vec<SecurityEvent>>, Response> {
+    check_role(
+        &state,
+        owner.as_deref(),
+        credential,
+        &[Role::Admin, Role::Auditor],
+    )
+    .await?;
+
+    match state
+        .security_log
+        .query(
+            params.kind,
+            params.owner.as_deref(),
+            params.since,
+            params.until,
+            params.limit.into(),
+        )
+        .await
+    {
+        Ok(events) => Ok(Json(events)),
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to query security events"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Request body of [`post_user`].
+#[derive(Deserialize, Debug)]
+struct NewUser {
+    /// ID of the user, which is also the owner of their tasks.
+    id: String,
+    /// Name to show for the user.
+    display_name: String,
+    /// Email address of the user.
+    email: Option<String>,
+    /// Phone number of the user, in E.164 format.
+    phone: Option<String>,
+    /// Roles to grant the user.
+    #[serde(default)]
+    roles: Vec<Role>,
+}
+
+/// Deserialise a field which is `None` if omitted and `Some(None)` if null.
+///
+/// Use with `#[serde(default)]`, so omitted fields aren't an error.
+#[allow(clippy::option_option)]
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
+}
+
+/// Request body of [`patch_user`]; omitted fields are left unchanged.
+// omitted and null contact details mean different things
+#[allow(clippy::option_option)]
+#[derive(Deserialize, Debug)]
+struct UserChanges {
+    /// New name to show for the user.
+    display_name: Option<String>,
+    /// New email address of the user, or `null` to remove it.
+    #[serde(default, deserialize_with = "nullable")]
+    email: Option<Option<String>>,
+    /// New phone number of the user, in E.164 format, or `null` to remove
+    /// it.
+    #[serde(default, deserialize_with = "nullable")]
+    phone: Option<Option<String>>,
+    /// Roles to grant the user, replacing their current roles.
+    roles: Option<Vec<Role>>,
+    /// Whether the user is active.
+    active: Option<bool>,
+}
+
+#[tracing::instrument]
+async fn list_users(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, credential): Owner,
+) -> Result<Json<Vec<User>>, Response> {
+    check_role(&state, owner.as_deref(), credential, &[Role::Admin]).await?;
+
+    match state.users.list().await {
+        Ok(users) => Ok(Json(users)),
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to list users"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+#[tracing::instrument]
+async fn post_user(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, credential): Owner,
+    Json(new): Json<NewUser>,
+) -> Result<(StatusCode, Json<User>), Response> {
+    check_role(&state, owner.as_deref(), credential, &[Role::Admin]).await?;
+    if new.id.trim().is_empty() || new.display_name.trim().is_empty() {
+        debug!("malformed user received");
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "id and display_name must not be empty",
+        )
+            .into_response());
+    }
+    if let Some(Err(e)) = new.phone.as_deref().map(sms::check_phone) {
+        debug!("malformed phone number received");
+        return Err((StatusCode::BAD_REQUEST, e).into_response());
+    }
+
+    match state
+        .users
+        .create(
+            new.id.trim(),
+            new.display_name.trim(),
+            new.email.as_deref(),
+            new.phone.as_deref(),
+            &new.roles,
+        )
+        .await
+    {
+        Ok(Some(user)) => {
+            state
+                .record(
+                    SecurityEventKind::UserChanged,
+                    owner.as_deref(),
+                    &format!("registered user {} with roles {:?}", user.id, user.roles),
+                )
+                .await;
+            Ok((StatusCode::CREATED, Json(user)))
+        }
+        Ok(None) => {
+            Err((StatusCode::CONFLICT, "a user with this id already exists").into_response())
+        }
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to create user"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+#[tracing::instrument]
+async fn get_user(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, credential): Owner,
+    Path(user_id): Path<String>,
+) -> Result<Json<User>, Response> {
+    check_role(&state, owner.as_deref(), credential, &[Role::Admin]).await?;
+
+    match state.users.get(&user_id).await {
+        Ok(Some(user)) => Ok(Json(user)),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => {
+            error!(error = format!("{e}"), "database error trying to get user");
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+#[tracing::instrument]
+async fn patch_user(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, credential): Owner,
+    Path(user_id): Path<String>,
+    Json(changes): Json<UserChanges>,
+) -> Result<Json<User>, Response> {
+    check_role(&state, owner.as_deref(), credential, &[Role::Admin]).await?;
+    if changes
+        .display_name
+        .as_ref()
+        .is_some_and(|name| name.trim().is_empty())
+    {
+        debug!("malformed user changes received");
+        return Err((StatusCode::BAD_REQUEST, "display_name must not be empty").into_response());
+    }
+    if let Some(Err(e)) = changes
+        .phone
+        .as_ref()
+        .and_then(Option::as_deref)
+        .map(sms::check_phone)
+    {
+        debug!("malformed phone number received");
+        return Err((StatusCode::BAD_REQUEST, e).into_response());
+    }
+
+    let update = UserUpdate {
+        display_name: changes.display_name.map(|name| name.trim().to_owned()),
+        email: changes.email,
+        phone: changes.phone,
+        roles: changes.roles,
+        active: changes.active,
+    };
+    update_user(&state, owner.as_deref(), &user_id, &update)
+        .await
+        .map(Json)
+}
+
+#[tracing::instrument]
+async fn deactivate_user(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, credential): Owner,
+    Path(user_id): Path<String>,
+) -> Result<StatusCode, Response> {
+    check_role(&state, owner.as_deref(), credential, &[Role::Admin]).await?;
+
+    let update = UserUpdate {
+        active: Some(false),
+        ..UserUpdate::default()
+    };
+    update_user(&state, owner.as_deref(), &user_id, &update)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+}
+
+/// Change a user on behalf of an administrator, recording the change.
+async fn update_user(
+    state: &AppState,
+    admin: Option<&str>,
+    user_id: &str,
+    update: &UserUpdate,
+) -> Result<User, Response> {
+    match state.users.update(user_id, update).await {
+        Ok(Some(user)) => {
+            state
+                .record(
+                    SecurityEventKind::UserChanged,
+                    admin,
+                    &format!(
+                        "changed user {}: roles {:?}, {}",
+                        user.id,
+                        user.roles,
+                        if user.deactivated_at.is_some() {
+                            "deactivated"
+                        } else {
+                            "active"
+                        }
+                    ),
+                )
+                .await;
+            Ok(user)
+        }
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to update user"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Contract tests checking that responses conform to what the API publishes
+//! about them, so clients built from it can rely on what they receive.
+//!
+//! Every task in a response is checked against the schema served at
+//! `/schema/task.json`, and must be accepted back as a request body. Error
+//! responses must be plain text or empty, other than conflicts with
+//! possible duplicates, which list them as JSON, and invalid bodies, which
+//! list the fields at fault as JSON. The API must work the
+//! same when nested at a path in another router.
+//!
+//! Like the snapshot tests, these need `TEST_DATABASE_URL`.
+
+use axum::{
+    http::{Method, StatusCode, header},
+    response::Response,
+};
+use http_body_util::BodyExt;
+use serde_json::{Value, json};
+
+use super::api_snapshots::{TestApp, task};
+use crate::json_schema;
+
+/// Status and body of `response`, whose body must be JSON.
+async fn json(response: Response) -> (StatusCode, Value) {
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = serde_json::from_slice(&body)
+        .unwrap_or_else(|e| panic!("{status} response isn't JSON ({e}): {body:?}"));
+    (status, body)
+}
+
+/// Check that each of `tasks`, found in the response to `request`, conforms
+/// to `schema`.
+fn check_tasks<'a>(schema: &Value, request: &str, tasks: impl IntoIterator<Item = &'a Value>) {
+    let mut checked = 0;
+    for task in tasks {
+        let violations = json_schema::validate(schema, task);
+        assert!(
+            violations.is_empty(),
+            "{request} responded with a task not conforming to its schema: {violations:?}\n{task}"
+        );
+        checked += 1;
+    }
+    assert!(checked > 0, "{request} responded without tasks");
+}
+
+#[tokio::test]
+async fn task_responses() {
+    let Some(app) = TestApp::start(&["--validate-json-schema"]).await else {
+        return;
+    };
+    let alice = Some("alice");
+    let (_, schema) = json(
+        app.send(Method::GET, "/schema/task.json", alice, None)
+            .await,
+    )
+    .await;
+
+    // tasks using every field the schema describes
+    let mut full = task("Review bundle");
+    full["estimate"] = 7200.into();
+    full["progress"] = 40.into();
+    full["colour"] = "teal".into();
+    full["title_cy"] = "Adolygu bwndel".into();
+    full["description_cy"] = "Gwirio'r bwndel".into();
+    let id = app.create("alice", full).await;
+    let mut blocked = task("Chase payment");
+    blocked["status"] = "Blocked".into();
+    blocked["status_reason"] = "Waiting for the applicant".into();
+    app.create("alice", blocked).await;
+    let mut complete = task("File order");
+    complete["status"] = "Complete".into();
+    app.create("alice", complete).await;
+
+    let get = format!("/task/{id}");
+    let (status, body) = json(app.send(Method::GET, &get, alice, None).await).await;
+    assert_eq!(status, StatusCode::OK);
+    check_tasks(&schema, &get, [&body]);
+    // what is received can be sent back unchanged
+    let response = app.send(Method::PUT, &get, alice, Some(body)).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let (_, body) = json(app.send(Method::GET, "/task", alice, None).await).await;
+    check_tasks(&schema, "/task", body.as_array().unwrap());
+    let search = "/task/search?title=review%20bundel";
+    let (_, body) = json(app.send(Method::GET, search, alice, None).await).await;
+    check_tasks(&schema, search, body.as_array().unwrap());
+    let (_, body) = json(
+        app.send(Method::POST, "/task/lookup", alice, Some(json!([id])))
+            .await,
+    )
+    .await;
+    check_tasks(&schema, "/task/lookup", body["tasks"].as_array().unwrap());
+    let version = format!("/task/{id}/history/1");
+    let (_, body) = json(app.send(Method::GET, &version, alice, None).await).await;
+    check_tasks(&schema, &version, [&body["task"]]);
+    let revert = format!("/task/{id}/revert/1");
+    let (_, body) = json(app.send(Method::POST, &revert, alice, None).await).await;
+    check_tasks(&schema, &revert, [&body]);
+    let preview = "/task?dry_run=true";
+    let (_, body) = json(
+        app.send(Method::POST, preview, alice, Some(task("Draft order")))
+            .await,
+    )
+    .await;
+    check_tasks(&schema, preview, [&body["task"]]);
+    let duplicate = "/task?detect_duplicates=true";
+    let (status, body) = json(
+        app.send(Method::POST, duplicate, alice, Some(task("Review bundle")))
+            .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    check_tasks(&schema, duplicate, body["candidates"].as_array().unwrap());
+
+    let export = "/users/alice/export";
+    let (_, body) = json(app.send(Method::GET, export, alice, None).await).await;
+    check_tasks(&schema, export, body["tasks"].as_array().unwrap());
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn error_responses() {
+    let Some(app) = TestApp::start(&["--validate-json-schema"]).await else {
+        return;
+    };
+    let id = app.create("alice", task("Review bundle")).await;
+    let mut schema_violation = task("");
+    schema_violation["progress"] = 101.into();
+
+    let requests = [
+        (Method::GET, format!("/task/{}", uuid::Uuid::nil()), None),
+        (Method::GET, "/task/not-an-id".to_owned(), None),
+        (Method::POST, "/task".to_owned(), Some(schema_violation)),
+        (
+            Method::POST,
+            "/task".to_owned(),
+            Some(json!({ "title": 7 })),
+        ),
+        (Method::GET, "/task?q=title:".to_owned(), None),
+        (
+            Method::GET,
+            "/task/search?title=a&threshold=2".to_owned(),
+            None,
+        ),
+        (
+            Method::POST,
+            format!("/task/{id}/links"),
+            Some(json!({ "target": id, "kind": "DependsOn" })),
+        ),
+        (Method::GET, "/admin/users".to_owned(), None),
+        (Method::GET, "/users/bob/export".to_owned(), None),
+    ];
+    for (method, uri, body) in requests {
+        let response = app.send(method.clone(), &uri, Some("alice"), body).await;
+        let status = response.status();
+        assert!(
+            status.is_client_error(),
+            "{method} {uri} responded with {status}"
+        );
+        let content_type = response.headers().get(header::CONTENT_TYPE).cloned();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        if body.is_empty() {
+            continue;
+        }
+        let content_type = content_type
+            .and_then(|value| value.to_str().ok().map(str::to_owned))
+            .unwrap_or_default();
+        // invalid bodies are rejected with the fields at fault
+        if status == StatusCode::UNPROCESSABLE_ENTITY && content_type == "application/json" {
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            assert!(body["message"].is_string());
+            assert!(
+                !body["errors"].as_array().unwrap().is_empty(),
+                "{method} {uri}"
+            );
+            continue;
+        }
+        assert!(
+            content_type.starts_with("text/plain"),
+            "{method} {uri} responded with {status} error of type {content_type:?}"
+        );
+        assert!(std::str::from_utf8(&body).is_ok());
+    }
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn nested() {
+    let Some(app) = TestApp::start(&[]).await else {
+        return;
+    };
+    let app = app.nest("/tasks");
+    let alice = Some("alice");
+    let response = app
+        .send(
+            Method::POST,
+            "/tasks/task",
+            alice,
+            Some(task("Review bundle")),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let (status, body) = json(app.send(Method::GET, "/tasks/task", alice, None).await).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body[0]["title"], "Review bundle");
+    let response = app.send(Method::GET, "/task", alice, None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    app.stop().await;
+}
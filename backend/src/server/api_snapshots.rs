@@ -0,0 +1,1248 @@
+//! Snapshot tests of the API's responses, so changes to their shape or to
+//! error bodies are seen in review before clients notice them.
+//!
+//! Each test serves the whole router against a database of its own, created
+//! on the Postgres server at `TEST_DATABASE_URL` and dropped when the test
+//! passes. The tests are skipped if `TEST_DATABASE_URL` isn't set.
+//!
+//! IDs, timestamps, durations and secrets vary between runs, so are replaced
+//! with placeholders in snapshots. Review changed snapshots with
+//! `cargo insta review`.
+
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{HeaderValue, Method, Request, header},
+    response::Response,
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{DateTime, TimeDelta, Utc};
+use clap::Parser;
+use http_body_util::BodyExt;
+use serde::Serialize;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use crate::{
+    TodoTask,
+    clock::{Clock, SystemClock, TestClock},
+    store::{PgTaskStore, SessionStore, TaskStore, TokenStore, UserStore},
+    tokens::TokenScope,
+};
+
+use super::{
+    auth::{CSRF_HEADER, SESSION_COOKIE, csrf_token},
+    cli, router,
+    test_database::TestDatabase,
+};
+
+/// Owner configured as an administrator, whose requests are made with an
+/// access token, as administrators' must be.
+const ADMIN: &str = "admin";
+
+/// Response as recorded in a snapshot.
+#[derive(Serialize, Debug)]
+struct Snapshot {
+    status: u16,
+    /// Body as JSON, or as a string if it isn't JSON.
+    body: Value,
+}
+
+/// Application served against a database of its own.
+pub(crate) struct TestApp {
+    router: Router,
+    database: TestDatabase,
+    /// Access token of [`ADMIN`].
+    admin_token: String,
+}
+
+impl TestApp {
+    /// Serve the application with the options `args`, or `None` if there is
+    /// no database server to test against.
+    pub(crate) async fn start(args: &[&str]) -> Option<Self> {
+        Self::start_with_clock(args, Arc::new(SystemClock)).await
+    }
+
+    /// Serve the application with the options `args`, telling the time by
+    /// `clock`.
+    pub(crate) async fn start_with_clock(args: &[&str], clock: Arc<dyn Clock>) -> Option<Self> {
+        let database = TestDatabase::create().await?;
+        let opts = cli::Opt::parse_from(
+            [
+                "dts_developer_challenge",
+                "--db-host",
+                "unused",
+                "--admin",
+                ADMIN,
+            ]
+            .iter()
+            .chain(args),
+        );
+        let router = router(opts, database.pool.clone(), clock).await;
+        let (admin_token, _) = TokenStore::new(database.pool.clone())
+            .create(ADMIN, "tests", &[TokenScope::Read, TokenScope::Write], None)
+            .await
+            .unwrap();
+        Some(Self {
+            router,
+            database,
+            admin_token,
+        })
+    }
+
+    /// Serve the application at `path` within another, as a gateway would.
+    pub(crate) fn nest(mut self, path: &str) -> Self {
+        self.router = Router::new().nest(path, self.router);
+        self
+    }
+
+    /// Send a request as `owner`, with `body` as JSON if given.
+    ///
+    /// Owners other than [`ADMIN`] name themselves in the `X-Owner` header.
+    pub(crate) async fn send(
+        &self,
+        method: Method,
+        uri: &str,
+        owner: Option<&str>,
+        body: Option<Value>,
+    ) -> Response {
+        let mut request = Request::builder().method(method).uri(uri);
+        match owner {
+            Some(ADMIN) => {
+                let bearer = format!("Bearer {}", self.admin_token);
+                request = request.header(header::AUTHORIZATION, bearer);
+            }
+            Some(owner) => request = request.header("x-owner", owner),
+            None => {}
+        }
+        let body = match body {
+            Some(body) => {
+                request = request.header(header::CONTENT_TYPE, "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        self.send_request(request.body(body).unwrap()).await
+    }
+
+    /// Send `request` as it is.
+    pub(crate) async fn send_request(&self, request: Request<Body>) -> Response {
+        self.router.clone().oneshot(request).await.unwrap()
+    }
+
+    /// Make a request as `owner`, with `body` as JSON if given, recording
+    /// its response.
+    async fn request(
+        &self,
+        method: Method,
+        uri: &str,
+        owner: Option<&str>,
+        body: Option<Value>,
+    ) -> Snapshot {
+        record(self.send(method, uri, owner, body).await).await
+    }
+
+    /// Send a request as `owner`, with `body` as JSON if given, returning
+    /// the body of its response as JSON, or `null` if it isn't JSON.
+    ///
+    /// Unlike [`Self::request`], times and IDs are kept.
+    async fn json(
+        &self,
+        method: Method,
+        uri: &str,
+        owner: Option<&str>,
+        body: Option<Value>,
+    ) -> Value {
+        let response = self.send(method, uri, owner, body).await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).unwrap_or_default()
+    }
+
+    /// Create `task` as `owner`, returning its ID.
+    pub(crate) async fn create(&self, owner: &str, task: Value) -> String {
+        let response = self
+            .send(Method::POST, "/task", Some(owner), Some(task))
+            .await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8_lossy(&body).into_owned()
+    }
+
+    /// Stop serving the application, and drop its database.
+    pub(crate) async fn stop(self) {
+        drop(self.router);
+        self.database.drop().await;
+    }
+}
+
+/// Record `response` as a snapshot.
+async fn record(response: Response) -> Snapshot {
+    let status = response.status().as_u16();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = serde_json::from_slice(&body)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into_owned()));
+    Snapshot {
+        status,
+        body: scrub(body),
+    }
+}
+
+/// Replace the IDs, timestamps, durations and secrets in `value`, which
+/// differ between runs.
+fn scrub(value: Value) -> Value {
+    match value {
+        Value::String(s) if s.parse::<Uuid>().is_ok() => "[id]".into(),
+        Value::String(s) if s.parse::<DateTime<Utc>>().is_ok() => "[timestamp]".into(),
+        Value::Array(values) => values.into_iter().map(scrub).collect(),
+        Value::Object(object) => object
+            .into_iter()
+            .map(|(key, value)| match key.as_str() {
+                "token" | "etag" => (key, "[secret]".into()),
+                // durations from when a task was created to when it is due
+                "duration_seconds" => (key, "[duration]".into()),
+                _ => (key, scrub(value)),
+            })
+            .collect(),
+        value => value,
+    }
+}
+
+/// Task due well within the due window, whenever the tests run.
+///
+/// Tasks are due at noon, so one updated with the same fields is unchanged.
+pub(crate) fn task(title: &str) -> Value {
+    let due = (Utc::now().date_naive() + chrono::Days::new(7))
+        .and_hms_opt(12, 0, 0)
+        .unwrap()
+        .and_utc();
+    json!({
+        "title": title,
+        "description": "Check the **bundle** before the hearing",
+        "status": "NotStarted",
+        "due": due.to_rfc3339(),
+        "tags": ["hearing", "urgent"],
+    })
+}
+
+#[tokio::test]
+async fn tasks() {
+    let Some(app) = TestApp::start(&[]).await else {
+        return;
+    };
+    let alice = Some("alice");
+
+    insta::assert_json_snapshot!(
+        "create_task",
+        app.request(Method::POST, "/task", alice, Some(task("Review bundle")))
+            .await
+    );
+    let id = app.create("alice", task("Prepare order")).await;
+
+    insta::assert_json_snapshot!(
+        "get_task",
+        app.request(Method::GET, &format!("/task/{id}"), alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "list_tasks",
+        app.request(Method::GET, "/task?q=title:order", alice, None)
+            .await
+    );
+    let mut update = task("Prepare order");
+    update["status"] = "InProgress".into();
+    insta::assert_json_snapshot!(
+        "update_task",
+        app.request(Method::PUT, &format!("/task/{id}"), alice, Some(update))
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "task_exists",
+        app.request(Method::GET, &format!("/task/{id}/exists"), alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "task_history",
+        app.request(Method::GET, &format!("/task/{id}/history"), alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "task_history_version",
+        app.request(Method::GET, &format!("/task/{id}/history/1"), alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "statuses",
+        app.request(Method::GET, "/statuses", alice, None).await
+    );
+    insta::assert_json_snapshot!(
+        "task_schema",
+        app.request(Method::GET, "/schema/task.json", alice, None)
+            .await
+    );
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn task_operations() {
+    let Some(app) = TestApp::start(&[]).await else {
+        return;
+    };
+    let alice = Some("alice");
+    let id = app.create("alice", task("Prepare order")).await;
+    let other = app.create("alice", task("File order")).await;
+
+    insta::assert_json_snapshot!(
+        "create_link",
+        app.request(
+            Method::POST,
+            &format!("/task/{id}/links"),
+            alice,
+            Some(json!({ "target": other, "kind": "DependsOn" })),
+        )
+        .await
+    );
+    insta::assert_json_snapshot!(
+        "task_links",
+        app.request(Method::GET, &format!("/task/{id}/links"), alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "task_graph",
+        app.request(Method::GET, "/task/graph", alice, None).await
+    );
+    insta::assert_json_snapshot!(
+        "search_tasks",
+        app.request(
+            Method::GET,
+            "/task/search?title=prepare%20ordr",
+            alice,
+            None
+        )
+        .await
+    );
+    insta::assert_json_snapshot!(
+        "lookup_tasks",
+        app.request(
+            Method::POST,
+            "/task/lookup",
+            alice,
+            Some(json!([id, Uuid::nil()])),
+        )
+        .await
+    );
+    insta::assert_json_snapshot!(
+        "reschedule_preview",
+        app.request(
+            Method::POST,
+            "/task/bulk/reschedule?q=title:order&dry_run=true",
+            alice,
+            Some(json!({ "shift_seconds": 86400 })),
+        )
+        .await
+    );
+    insta::assert_json_snapshot!(
+        "assign_task",
+        app.request(
+            Method::PUT,
+            &format!("/task/{id}/assignee"),
+            alice,
+            Some(json!({ "assignee": "bob" })),
+        )
+        .await
+    );
+    insta::assert_json_snapshot!(
+        "pin_task",
+        app.request(Method::POST, &format!("/task/{id}/pin"), alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "start_timer",
+        app.request(
+            Method::POST,
+            &format!("/task/{id}/timer/start"),
+            alice,
+            None
+        )
+        .await
+    );
+    insta::assert_json_snapshot!(
+        "stop_timer",
+        app.request(Method::POST, &format!("/task/{id}/timer/stop"), alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "revert_task",
+        app.request(Method::POST, &format!("/task/{id}/revert/1"), alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "list_attachments",
+        app.request(Method::GET, &format!("/task/{id}/attachments"), alice, None)
+            .await
+    );
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn unchanged_revert() {
+    for storage in ["table", "events"] {
+        let Some(app) = TestApp::start(&["--storage", storage]).await else {
+            return;
+        };
+        let alice = Some("alice");
+        let id = app.create("alice", task("Prepare order")).await;
+
+        let revert = app
+            .request(Method::POST, &format!("/task/{id}/revert/1"), alice, None)
+            .await;
+        assert_eq!(revert.status, 200, "{storage}");
+        let history = app
+            .request(Method::GET, &format!("/task/{id}/history"), alice, None)
+            .await;
+        let actions: Vec<_> = history
+            .body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|version| {
+                (
+                    version["version"].clone(),
+                    version["action"].clone(),
+                    version["reverted_to"].clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                (json!(1), json!("insert"), Value::Null),
+                (json!(2), json!("revert"), json!(1))
+            ],
+            "{storage}"
+        );
+        app.stop().await;
+    }
+}
+
+#[tokio::test]
+async fn clock_times() {
+    let start = Utc::now()
+        .date_naive()
+        .and_hms_opt(9, 0, 0)
+        .unwrap()
+        .and_utc();
+    for storage in ["table", "events"] {
+        let clock = Arc::new(TestClock::new(start));
+        let Some(app) = TestApp::start_with_clock(&["--storage", storage], clock.clone()).await
+        else {
+            return;
+        };
+        let alice = Some("alice");
+        let mut done = task("File order");
+        done["status"] = json!("Complete");
+        let id = app.create("alice", done).await;
+        let uri = format!("/task/{id}");
+        let get = || app.json(Method::GET, &uri, alice, None);
+        assert_eq!(get().await["completed_at"], json!(start), "{storage}");
+
+        app.json(Method::POST, &format!("{uri}/timer/start"), alice, None)
+            .await;
+        clock.advance(TimeDelta::minutes(30));
+        assert_eq!(get().await["tracked_seconds"], 1800, "{storage}");
+        let stopped = app
+            .json(Method::POST, &format!("{uri}/timer/stop"), alice, None)
+            .await;
+        assert_eq!(
+            stopped["stopped_at"],
+            json!(start + TimeDelta::minutes(30)),
+            "{storage}"
+        );
+
+        // reverting to the completed version completes the task again, now
+        app.json(Method::PUT, &uri, alice, Some(task("File order")))
+            .await;
+        assert_eq!(get().await["completed_at"], Value::Null, "{storage}");
+        let now = clock.advance(TimeDelta::hours(1));
+        app.json(Method::POST, &format!("{uri}/revert/1"), alice, None)
+            .await;
+        assert_eq!(get().await["completed_at"], json!(now), "{storage}");
+        app.stop().await;
+    }
+}
+
+#[tokio::test]
+async fn row_level_security() {
+    let Some(app) = TestApp::start(&["--row-level-security"]).await else {
+        return;
+    };
+    let (alice, bob) = (Some("alice"), Some("bob"));
+    let mut mention = task("Prepare order");
+    mention["description"] = json!("Ask @carol to check it");
+    let id = app.create("alice", mention).await;
+    let other = app.create("alice", task("File order")).await;
+
+    // writes touching the tables next to `tasks` are allowed
+    for (method, uri, body) in [
+        (
+            Method::PUT,
+            format!("/task/{id}/assignee"),
+            Some(json!({ "assignee": "bob" })),
+        ),
+        (Method::POST, format!("/task/{id}/pin"), None),
+        (Method::POST, format!("/task/{id}/timer/start"), None),
+        (Method::POST, format!("/task/{id}/timer/stop"), None),
+        (
+            Method::POST,
+            format!("/task/{id}/links"),
+            Some(json!({ "target": other, "kind": "DependsOn" })),
+        ),
+        (Method::POST, format!("/task/{id}/revert/1"), None),
+    ] {
+        let status = app.send(method, &uri, alice, body).await.status();
+        assert!(status.is_success(), "{uri}: {status}");
+    }
+
+    // other owners can't see them
+    let list = app.request(Method::GET, "/task", bob, None).await;
+    assert_eq!((list.status, list.body), (200, json!([])));
+    for uri in [format!("/task/{id}"), format!("/task/{id}/history")] {
+        let response = app.request(Method::GET, &uri, bob, None).await;
+        assert_eq!(response.status, 404, "{uri}");
+    }
+    let list = app.request(Method::GET, "/task", alice, None).await;
+    assert_eq!(list.body.as_array().map(Vec::len), Some(2));
+
+    // nor reach tables without policies
+    for table in ["sessions", "access_tokens", "users", "security_events"] {
+        let mut tx = app.database.pool.begin().await.unwrap();
+        sqlx::query("SET LOCAL ROLE tasks_rls")
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        let read = sqlx::query(&format!("SELECT 1 FROM {table}"))
+            .execute(&mut *tx)
+            .await;
+        assert!(read.is_err(), "{table} readable");
+    }
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn errors() {
+    let Some(app) = TestApp::start(&[]).await else {
+        return;
+    };
+    let alice = Some("alice");
+    let missing = Uuid::nil();
+
+    insta::assert_json_snapshot!(
+        "missing_task",
+        app.request(Method::GET, &format!("/task/{missing}"), alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "invalid_task_id",
+        app.request(Method::GET, "/task/not-an-id", alice, None)
+            .await
+    );
+    let mut untitled = task("");
+    untitled["title"] = "".into();
+    insta::assert_json_snapshot!(
+        "invalid_task",
+        app.request(Method::POST, "/task", alice, Some(untitled))
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "malformed_task",
+        app.request(Method::POST, "/task", alice, Some(json!({ "title": 7 })))
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "invalid_filter",
+        app.request(Method::GET, "/task?q=title:", alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "invalid_search_threshold",
+        app.request(Method::GET, "/task/search?title=x&threshold=2", alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "link_to_itself",
+        app.request(
+            Method::POST,
+            &format!("/task/{missing}/links"),
+            alice,
+            Some(json!({ "target": missing, "kind": "DependsOn" })),
+        )
+        .await
+    );
+    insta::assert_json_snapshot!(
+        "missing_job",
+        app.request(Method::GET, &format!("/jobs/{missing}"), alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "admin_only",
+        app.request(Method::GET, "/admin/users", alice, None).await
+    );
+    insta::assert_json_snapshot!(
+        "invalid_burndown",
+        app.request(
+            Method::GET,
+            "/stats/burndown?from=2026-01-01T00:00:00Z&to=2025-01-01T00:00:00Z",
+            alice,
+            None,
+        )
+        .await
+    );
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn idempotent_create() {
+    let Some(app) = TestApp::start(&[]).await else {
+        return;
+    };
+    let key = Uuid::new_v4().to_string();
+    let create = |key: &str, task: &Value| {
+        Request::post("/task")
+            .header("x-owner", "alice")
+            .header("idempotency-key", key)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(task.to_string()))
+            .unwrap()
+    };
+
+    let first = record(app.send_request(create(&key, &task("Review bundle"))).await).await;
+    insta::assert_json_snapshot!(
+        "repeated_create",
+        record(app.send_request(create(&key, &task("Review bundle"))).await).await
+    );
+    assert_eq!(first.body, "[id]");
+    let list = app.send(Method::GET, "/task", Some("alice"), None).await;
+    let list = list.into_body().collect().await.unwrap().to_bytes();
+    let list: Vec<Value> = serde_json::from_slice(&list).unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0]["id"], key);
+
+    insta::assert_json_snapshot!(
+        "reused_idempotency_key",
+        record(app.send_request(create(&key, &task("Prepare order"))).await).await
+    );
+    insta::assert_json_snapshot!(
+        "invalid_idempotency_key",
+        record(app.send_request(create("7", &task("Prepare order"))).await).await
+    );
+
+    // another owner's request with the same key isn't taken for a repeat
+    let mut request = create(&key, &task("Review bundle"));
+    request
+        .headers_mut()
+        .insert("x-owner", HeaderValue::from_static("bob"));
+    assert_eq!(app.send_request(request).await.status(), 409);
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn admin() {
+    let Some(app) = TestApp::start(&[]).await else {
+        return;
+    };
+    let admin = Some(ADMIN);
+    app.create(ADMIN, task("Chase payment")).await;
+
+    insta::assert_json_snapshot!(
+        "create_user",
+        app.request(
+            Method::POST,
+            "/admin/users",
+            admin,
+            Some(json!({
+                "id": "bob",
+                "display_name": "Bob",
+                "email": "bob@example.com",
+                "roles": ["auditor"],
+            })),
+        )
+        .await
+    );
+    insta::assert_json_snapshot!(
+        "list_users",
+        app.request(Method::GET, "/admin/users", admin, None).await
+    );
+    insta::assert_json_snapshot!(
+        "update_user",
+        app.request(
+            Method::PATCH,
+            "/admin/users/bob",
+            admin,
+            Some(json!({ "display_name": "Robert" })),
+        )
+        .await
+    );
+    insta::assert_json_snapshot!(
+        "security_events",
+        app.request(Method::GET, "/admin/security-events", admin, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "create_token",
+        app.request(
+            Method::POST,
+            "/auth/tokens",
+            admin,
+            Some(json!({ "name": "CI", "scopes": ["read"], "expires_in_days": 30 })),
+        )
+        .await
+    );
+    insta::assert_json_snapshot!(
+        "list_tokens",
+        app.request(Method::GET, "/auth/tokens", admin, None).await
+    );
+    insta::assert_json_snapshot!(
+        "burndown",
+        app.request(
+            Method::GET,
+            "/stats/burndown?from=2026-01-05T00:00:00Z&to=2026-01-19T00:00:00Z&bucket=week",
+            admin,
+            None,
+        )
+        .await
+    );
+    insta::assert_json_snapshot!(
+        "workload",
+        app.request(Method::GET, "/stats/workload", admin, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "estimate_variance",
+        app.request(Method::GET, "/stats/estimates", admin, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "sla_breaches",
+        app.request(Method::GET, "/stats/sla-breaches", admin, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "storage",
+        app.request(Method::GET, "/stats/storage", admin, None)
+            .await
+    );
+    // the runtime's figures depend on what else the tests are doing
+    let mut debug_tasks = app.request(Method::GET, "/debug/tasks", admin, None).await;
+    debug_tasks.body["runtime"] = "[runtime]".into();
+    insta::assert_json_snapshot!("debug_tasks", debug_tasks);
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn claimed_admin() {
+    let Some(app) = TestApp::start(&[]).await else {
+        return;
+    };
+    // anyone can name the administrator in the `X-Owner` header
+    for (method, uri) in [
+        (Method::GET, "/admin/users"),
+        (Method::POST, "/admin/users"),
+        (Method::GET, "/admin/users/alice"),
+        (Method::DELETE, "/admin/users/alice"),
+        (Method::GET, "/admin/security-events"),
+        (Method::GET, "/metrics"),
+        (Method::GET, "/debug/tasks"),
+        (Method::GET, "/task/export?format=parquet"),
+    ] {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("x-owner", ADMIN)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                json!({ "id": "eve", "display_name": "Eve" }).to_string(),
+            ))
+            .unwrap();
+        let status = app.send_request(request).await.status();
+        assert_eq!(status, 401, "{uri}");
+    }
+    app.stop().await;
+}
+
+#[cfg(feature = "parquet")]
+#[tokio::test]
+async fn export() {
+    use arrow_array::{Array, StringArray};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let Some(app) = TestApp::start(&[]).await else {
+        return;
+    };
+    app.create("alice", task("Review bundle")).await;
+    app.create("bob", task("Prepare order")).await;
+    app.create(ADMIN, task("Chase payment")).await;
+
+    let response = app
+        .send(
+            Method::GET,
+            "/task/export?format=parquet",
+            Some(ADMIN),
+            None,
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/vnd.apache.parquet"
+    );
+    let file = response.into_body().collect().await.unwrap().to_bytes();
+
+    // every owner's tasks are exported, not only the administrator's
+    let mut exported = Vec::new();
+    for batch in ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .build()
+        .unwrap()
+    {
+        let batch = batch.unwrap();
+        let column = |name| {
+            let column = batch.column_by_name(name).unwrap();
+            let column = column.as_any().downcast_ref::<StringArray>().unwrap();
+            (0..column.len())
+                .map(|i| column.value(i).to_owned())
+                .collect::<Vec<_>>()
+        };
+        exported.extend(column("owner").into_iter().zip(column("title")));
+    }
+    exported.sort();
+    assert_eq!(
+        exported,
+        [
+            ("admin".to_owned(), "Chase payment".to_owned()),
+            ("alice".to_owned(), "Review bundle".to_owned()),
+            ("bob".to_owned(), "Prepare order".to_owned()),
+        ]
+    );
+
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn csrf() {
+    let Some(app) = TestApp::start(&[]).await else {
+        return;
+    };
+    let secret = SessionStore::new(app.database.pool.clone())
+        .create("alice", Utc::now() + TimeDelta::hours(1))
+        .await
+        .unwrap();
+    let session = format!("{SESSION_COOKIE}={secret}");
+    let basic = format!(
+        "Basic {}",
+        STANDARD.encode(format!("{ADMIN}:{}", app.admin_token))
+    );
+    let bearer = format!("Bearer {}", app.admin_token);
+    let token = csrf_token(&secret);
+    for (method, authorization, sent, status) in [
+        (Method::POST, None, None, 403),
+        (Method::POST, None, Some("forged"), 403),
+        (Method::POST, None, Some(token.as_str()), 200),
+        (Method::GET, None, None, 200),
+        (Method::POST, Some(&bearer), None, 200),
+        (Method::POST, Some(&basic), None, 403),
+    ] {
+        let mut request = Request::builder()
+            .method(method.clone())
+            .uri("/task")
+            .header(header::COOKIE, &session)
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        if let Some(sent) = sent {
+            request = request.header(CSRF_HEADER, sent);
+        }
+        let body = Body::from(task("Pay invoice").to_string());
+        let response = app.send_request(request.body(body).unwrap()).await;
+        assert_eq!(
+            response.status(),
+            status,
+            "{method} with {authorization:?} and token {sent:?}"
+        );
+    }
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn basic_credentials() {
+    let Some(app) = TestApp::start(&[]).await else {
+        return;
+    };
+    let basic = format!(
+        "Basic {}",
+        STANDARD.encode(format!("{ADMIN}:{}", app.admin_token))
+    );
+    // browsers send cached basic credentials cross-site
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("/task")
+        .header(header::AUTHORIZATION, &basic)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(json!({ "title": "Forged" }).to_string()))
+        .unwrap();
+    assert_eq!(app.send_request(request).await.status(), 401);
+    let request = Request::builder()
+        .method("PROPFIND")
+        .uri("/caldav/")
+        .header(header::AUTHORIZATION, &basic)
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(app.send_request(request).await.status(), 207);
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn token_creation() {
+    let Some(app) = TestApp::start(&[]).await else {
+        return;
+    };
+    let body = json!({ "name": "CI", "scopes": ["read"] });
+    // anyone can name any owner in the `X-Owner` header
+    let status = app
+        .send(
+            Method::POST,
+            "/auth/tokens",
+            Some("alice"),
+            Some(body.clone()),
+        )
+        .await
+        .status();
+    assert_eq!(status, 401);
+
+    let created = |response: Response| async move {
+        assert_eq!(response.status(), 201);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice::<Value>(&body).unwrap()
+    };
+    let short = created(
+        app.send(
+            Method::POST,
+            "/auth/tokens",
+            Some(ADMIN),
+            Some(json!({ "name": "short", "scopes": ["read", "write"], "expires_in_days": 1 })),
+        )
+        .await,
+    )
+    .await;
+    let limit = &short["details"]["expires_at"];
+    let bearer = format!("Bearer {}", short["token"].as_str().unwrap());
+    // tokens can't outlive the one they were created with
+    for body in [
+        body.clone(),
+        json!({ "name": "CI", "scopes": ["read"], "expires_in_days": 30 }),
+    ] {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/auth/tokens")
+            .header(header::AUTHORIZATION, &bearer)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let details = created(app.send_request(request).await).await;
+        assert_eq!(&details["details"]["expires_at"], limit, "{body}");
+    }
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn users() {
+    let Some(app) = TestApp::start(&[]).await else {
+        return;
+    };
+    let alice = Some("alice");
+    app.request(
+        Method::POST,
+        "/admin/users",
+        Some(ADMIN),
+        Some(json!({ "id": "alice", "display_name": "Alice", "email": "alice@example.com" })),
+    )
+    .await;
+    app.create("alice", task("Draft judgment")).await;
+
+    insta::assert_json_snapshot!(
+        "notification_preferences",
+        app.request(Method::GET, "/users/alice/notifications", alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "update_notification_preferences",
+        app.request(
+            Method::PUT,
+            "/users/alice/notifications",
+            alice,
+            Some(json!([{ "event": "overdue", "channel": "email", "enabled": false }])),
+        )
+        .await
+    );
+    insta::assert_json_snapshot!(
+        "update_digest",
+        app.request(
+            Method::PUT,
+            "/users/alice/digest",
+            alice,
+            Some(json!({ "enabled": true })),
+        )
+        .await
+    );
+    insta::assert_json_snapshot!(
+        "push_subscriptions",
+        app.request(Method::GET, "/users/alice/push-subscriptions", alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "timesheet",
+        app.request(
+            Method::GET,
+            "/users/alice/timesheet?from=2026-01-01T00:00:00Z&to=2026-02-01T00:00:00Z",
+            alice,
+            None,
+        )
+        .await
+    );
+    insta::assert_json_snapshot!(
+        "mentions",
+        app.request(Method::GET, "/users/alice/mentions", alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "export_user",
+        app.request(Method::GET, "/users/alice/export", alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "other_user",
+        app.request(Method::GET, "/users/bob/export", alice, None)
+            .await
+    );
+    insta::assert_json_snapshot!(
+        "erase_user",
+        app.request(Method::DELETE, "/users/alice/data", alice, None)
+            .await
+    );
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn contact_details() {
+    let Some(app) = TestApp::start(&[]).await else {
+        return;
+    };
+    app.send(
+        Method::POST,
+        "/admin/users",
+        Some(ADMIN),
+        Some(json!({
+            "id": "bob",
+            "display_name": "Bob",
+            "email": "bob@example.com",
+            "phone": "+447700900123",
+        })),
+    )
+    .await;
+    let patch = |changes: Value| async {
+        let response = app
+            .send(
+                Method::PATCH,
+                "/admin/users/bob",
+                Some(ADMIN),
+                Some(changes),
+            )
+            .await;
+        assert_eq!(response.status(), 200);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice::<Value>(&body).unwrap()
+    };
+    // omitted details are left as they are
+    let user = patch(json!({ "display_name": "Robert" })).await;
+    assert_eq!(user["email"], "bob@example.com");
+    assert_eq!(user["phone"], "+447700900123");
+    let user = patch(json!({ "email": null })).await;
+    assert_eq!(user["email"], Value::Null);
+    assert_eq!(user["phone"], "+447700900123");
+    let user = patch(json!({ "phone": null })).await;
+    assert_eq!(user["phone"], Value::Null);
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn assignment() {
+    let Some(app) = TestApp::start(&[]).await else {
+        return;
+    };
+    let task_id = app.create("alice", task("Draft judgment")).await;
+    let uri = format!("/task/{task_id}/assignee");
+    for (owner, status) in [(Some("bob"), 404), (None, 404), (Some("alice"), 204)] {
+        let response = app
+            .send(Method::PUT, &uri, owner, Some(json!({ "assignee": "bob" })))
+            .await;
+        assert_eq!(response.status(), status, "{owner:?}");
+    }
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn registered_users_only() {
+    let Some(app) = TestApp::start(&["--registered-users-only"]).await else {
+        return;
+    };
+    let users = UserStore::new(app.database.pool.clone());
+    users
+        .create("alice", "Alice", None, None, &[])
+        .await
+        .unwrap();
+    users
+        .create("carol", "Carol", None, None, &[])
+        .await
+        .unwrap();
+    let status = app
+        .send(Method::POST, "/task", Some("mallory"), Some(task("Forged")))
+        .await
+        .status();
+    assert_eq!(status, 401);
+    let task_id = app.create("alice", task("Draft judgment")).await;
+    let uri = format!("/task/{task_id}/assignee");
+    for (assignee, status) in [("mallory", 400), ("carol", 204)] {
+        let response = app
+            .send(
+                Method::PUT,
+                &uri,
+                Some("alice"),
+                Some(json!({ "assignee": assignee })),
+            )
+            .await;
+        assert_eq!(response.status(), status, "{assignee}");
+    }
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn due_window() {
+    let Some(app) = TestApp::start(&["--due-window-years", "1"]).await else {
+        return;
+    };
+    let alice = Some("alice");
+    let mut distant = task("File appeal");
+    distant["due"] = json!((Utc::now() + TimeDelta::days(400)).to_rfc3339());
+    let response = app.send(Method::POST, "/task", alice, Some(distant)).await;
+    assert_eq!(response.status(), 422);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["errors"][0]["field"], "due");
+
+    app.create("alice", task("File order")).await;
+    for dry_run in [true, false] {
+        let response = app
+            .send(
+                Method::POST,
+                &format!("/task/bulk/reschedule?q=title:order&dry_run={dry_run}"),
+                Some("alice"),
+                Some(json!({ "shift_seconds": 400 * 86400 })),
+            )
+            .await;
+        assert_eq!(response.status(), 400, "dry run: {dry_run}");
+    }
+
+    // and through every other way of giving a task a due date
+    let distant = Utc::now() + TimeDelta::days(400);
+    let id = app.create("alice", task("File appeal")).await;
+    let mut update = task("File appeal");
+    update["due"] = json!(distant.to_rfc3339());
+    let response = app
+        .send(Method::PUT, &format!("/task/{id}"), alice, Some(update))
+        .await;
+    assert_eq!(response.status(), 422);
+
+    let export = json!([{
+        "content": "File appeal",
+        "due": { "date": distant.date_naive().to_string() },
+        "labels": [],
+    }]);
+    let report = app
+        .json(
+            Method::POST,
+            "/task/import?from=todoist",
+            alice,
+            Some(export),
+        )
+        .await;
+    assert_eq!(
+        report["rejected"][0]["error"],
+        "due date is too far in the past or future"
+    );
+
+    let calendar = format!(
+        "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nSUMMARY:File appeal\r\nDUE:{}\r\n\
+        END:VTODO\r\nEND:VCALENDAR\r\n",
+        distant.format("%Y%m%dT%H%M%SZ")
+    );
+    let request = Request::put(format!("/caldav/tasks/{}.ics", Uuid::new_v4()))
+        .header("x-owner", "alice")
+        .body(Body::from(calendar))
+        .unwrap();
+    assert_eq!(app.send_request(request).await.status(), 400);
+
+    // versions from before the window was narrowed can't be restored
+    let mut stored: TodoTask = serde_json::from_value(task("File appeal")).unwrap();
+    stored.set_due(&distant);
+    let id = PgTaskStore::new(app.database.pool.clone())
+        .create(&stored, Some("alice"))
+        .await
+        .unwrap();
+    app.send(
+        Method::PUT,
+        &format!("/task/{id}"),
+        alice,
+        Some(task("File appeal")),
+    )
+    .await;
+    let response = app
+        .send(Method::POST, &format!("/task/{id}/revert/1"), alice, None)
+        .await;
+    assert_eq!(response.status(), 400);
+    app.stop().await;
+}
+
+#[tokio::test]
+async fn frontend() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("assets")).unwrap();
+    std::fs::write(dir.path().join("index.html"), "<div id=app></div>").unwrap();
+    std::fs::write(dir.path().join("assets/app.js"), "mount()").unwrap();
+    let frontend_dir = dir.path().to_str().unwrap();
+    let Some(app) = TestApp::start(&["--frontend-dir", frontend_dir]).await else {
+        return;
+    };
+
+    for (uri, body) in [
+        ("/", "<div id=app></div>"),
+        ("/assets/app.js", "mount()"),
+        ("/tasks/overdue", "<div id=app></div>"),
+    ] {
+        let snapshot = app.request(Method::GET, uri, None, None).await;
+        assert_eq!(
+            (snapshot.status, snapshot.body),
+            (200, body.into()),
+            "{uri}"
+        );
+    }
+    // the API keeps its paths
+    let snapshot = app.request(Method::GET, "/task", Some("alice"), None).await;
+    assert_eq!((snapshot.status, snapshot.body), (200, json!([])));
+    // and mistyped ones aren't taken for the frontend's
+    for uri in ["/task/bulk/reshedule", "/stats/burndwn", "/admin"] {
+        let response = app.send(Method::GET, uri, Some("alice"), None).await;
+        assert_eq!(response.status(), 404, "{uri}");
+    }
+    app.stop().await;
+}
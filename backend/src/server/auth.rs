@@ -0,0 +1,397 @@
+use std::sync::Arc;
+
+use crate::{security::SecurityEventKind, store, tokens::TokenScope};
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{
+        HeaderMap, HeaderValue, Method, StatusCode, header, header::InvalidHeaderValue,
+        request::Parts,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{
+    Engine,
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use sha2::{Digest, Sha256};
+use tracing::{debug, error};
+
+use super::AppState;
+
+/// Name of the cookie holding the secret of a browser's login session.
+pub(crate) const SESSION_COOKIE: &str = "session";
+
+/// Name of the header in which browsers send their CSRF token.
+pub(crate) const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Identity of the client making a request.
+///
+/// Clients authenticate with a personal access token as a bearer token in the
+/// `Authorization` header, or, from CalDAV clients which only support those,
+/// as the password of basic credentials. Browsers
+/// authenticate with a login session cookie. Otherwise (unless
+/// authentication is required) simply name themselves in the `X-Owner`
+/// header.
+/// Requests with neither are made by the anonymous owner, `None`.
+/// Requests from deactivated users are refused.
+#[derive(Clone, Debug)]
+pub(crate) struct Owner(pub Option<String>, pub Credential);
+
+/// How the client making a request proved who it is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Credential {
+    /// Nothing: the client named itself in the `X-Owner` header, or is
+    /// anonymous, so may be anyone.
+    Claimed,
+    /// A personal access token, which expires at the time given, if ever.
+    Token(Option<DateTime<Utc>>),
+    /// A login session cookie, which expires at the time given.
+    Session(DateTime<Utc>),
+}
+
+impl Credential {
+    /// When the credential stops being accepted, if it ever does.
+    pub(crate) fn expires_at(self) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Claimed | Self::Token(None) => None,
+            Self::Token(Some(expires_at)) | Self::Session(expires_at) => Some(expires_at),
+        }
+    }
+}
+
+impl Owner {
+    /// Authenticate the client with a bearer token.
+    async fn from_token(state: &AppState, method: &Method, token: &str) -> Result<Self, Response> {
+        let result = state.tokens.authenticate(token).await;
+        state.breaker.record(&result);
+        let details = match result {
+            Ok(Some(details)) => details,
+            Ok(None) => {
+                debug!("invalid access token received");
+                state
+                    .record(
+                        SecurityEventKind::AuthenticationFailed,
+                        None,
+                        "invalid, expired or revoked access token",
+                    )
+                    .await;
+                return Err(unauthorized("access token is invalid, expired or revoked"));
+            }
+            Err(e) => {
+                error!(
+                    error = format!("{e}"),
+                    "database error trying to authenticate access token"
+                );
+                return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+            }
+        };
+
+        let scope = if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+            || matches!(method.as_str(), "PROPFIND" | "REPORT")
+        {
+            TokenScope::Read
+        } else {
+            TokenScope::Write
+        };
+        if !details.scopes.contains(&scope) {
+            debug!(?scope, "access token without required scope received");
+            state
+                .record(
+                    SecurityEventKind::PermissionDenied,
+                    Some(&details.owner),
+                    &format!("access token {} lacks {scope:?} scope", details.id),
+                )
+                .await;
+            return Err((
+                StatusCode::FORBIDDEN,
+                "access token lacks the required scope",
+            )
+                .into_response());
+        }
+
+        Ok(Self(
+            Some(details.owner),
+            Credential::Token(details.expires_at),
+        ))
+    }
+}
+
+impl FromRequestParts<Arc<AppState>> for Owner {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        // already identified by `act_as_owner`
+        if let Some(owner) = parts.extensions.get::<Self>() {
+            return Ok(owner.clone());
+        }
+
+        let owner = Self::identify(parts, state).await?;
+        owner.check_registered(state).await?;
+        Ok(owner)
+    }
+}
+
+impl Owner {
+    /// Identify the client making a request.
+    ///
+    /// Basic credentials are only accepted by CalDAV, whose clients can't
+    /// send bearer tokens. Browsers send cached basic credentials with
+    /// requests from other sites, so elsewhere they would allow forgery.
+    async fn identify(parts: &Parts, state: &AppState) -> Result<Self, Response> {
+        if let Some(authorization) = parts.headers.get(header::AUTHORIZATION) {
+            let caldav = parts.uri.path().starts_with("/caldav");
+            let Some(token) = authorization.to_str().ok().and_then(|value| {
+                value
+                    .strip_prefix("Bearer ")
+                    .map(str::to_owned)
+                    .or_else(|| {
+                        caldav
+                            .then(|| basic_password(value.strip_prefix("Basic ")?))
+                            .flatten()
+                    })
+            }) else {
+                let (detail, message) = if caldav {
+                    (
+                        "Authorization header not a bearer token or basic credentials",
+                        "Authorization header must be a bearer token or basic credentials",
+                    )
+                } else {
+                    (
+                        "Authorization header not a bearer token",
+                        "Authorization header must be a bearer token",
+                    )
+                };
+                state
+                    .record(SecurityEventKind::AuthenticationFailed, None, detail)
+                    .await;
+                return Err(unauthorized(message));
+            };
+            return Self::from_token(state, &parts.method, token.trim()).await;
+        }
+
+        if let Some(secret) = cookie(&parts.headers, SESSION_COOKIE) {
+            let result = state.sessions.owner(secret).await;
+            state.breaker.record(&result);
+            return match result {
+                Ok(Some((owner, expires_at))) => {
+                    Ok(Self(Some(owner), Credential::Session(expires_at)))
+                }
+                Ok(None) => {
+                    state
+                        .record(
+                            SecurityEventKind::AuthenticationFailed,
+                            None,
+                            "unknown or expired session",
+                        )
+                        .await;
+                    Err(unauthorized("session has expired, log in again"))
+                }
+                Err(e) => {
+                    error!(
+                        error = format!("{e}"),
+                        "database error trying to authenticate session"
+                    );
+                    Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+                }
+            };
+        }
+
+        if state.require_tokens {
+            state
+                .record(
+                    SecurityEventKind::AuthenticationFailed,
+                    None,
+                    "request without access token or session",
+                )
+                .await;
+            return Err(unauthorized("an access token or session is required"));
+        }
+        match parts.headers.get("x-owner").map(|v| v.to_str()) {
+            None => Ok(Self(None, Credential::Claimed)),
+            Some(Ok(owner)) if !owner.trim().is_empty() => {
+                Ok(Self(Some(owner.trim().to_owned()), Credential::Claimed))
+            }
+            Some(_) => Err((
+                StatusCode::BAD_REQUEST,
+                "X-Owner header must be visible ASCII",
+            )
+                .into_response()),
+        }
+    }
+
+    /// Check that the owner is a user who may make requests.
+    ///
+    /// Deactivated users are always refused; unregistered owners are only
+    /// refused if registration is required.
+    async fn check_registered(&self, state: &AppState) -> Result<(), Response> {
+        let allowed = match (&self.0, state.registered_users_only) {
+            (None, false) => Ok(true),
+            (None, true) => Ok(false),
+            (Some(owner), false) => state.users.is_deactivated(owner).await.map(|d| !d),
+            (Some(owner), true) => state
+                .users
+                .active_roles(owner)
+                .await
+                .map(|roles| roles.is_some()),
+        };
+        // only registered owners are looked up in the database
+        if self.0.is_some() {
+            state.breaker.record(&allowed);
+        }
+        match allowed {
+            Ok(true) => Ok(()),
+            Ok(false) => {
+                debug!("request from unregistered or deactivated user received");
+                state
+                    .record(
+                        SecurityEventKind::AuthenticationFailed,
+                        self.0.as_deref(),
+                        "unregistered or deactivated user",
+                    )
+                    .await;
+                Err(unauthorized(
+                    "user is not registered or has been deactivated",
+                ))
+            }
+            Err(e) => {
+                error!(
+                    error = format!("{e}"),
+                    "database error trying to check user registration"
+                );
+                Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+            }
+        }
+    }
+}
+
+/// Get the password from the encoded credentials of basic authentication.
+///
+/// The user name is ignored, as the password is an access token which
+/// identifies the owner.
+fn basic_password(credentials: &str) -> Option<String> {
+    let decoded = String::from_utf8(STANDARD.decode(credentials.trim()).ok()?).ok()?;
+    let (_, password) = decoded.split_once(':')?;
+    Some(password.to_owned())
+}
+
+/// Get the value of a cookie sent with a request.
+pub(crate) fn cookie<'h>(headers: &'h HeaderMap, name: &str) -> Option<&'h str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find_map(|(key, value)| (key == name).then_some(value))
+}
+
+/// `Set-Cookie` header value for a cookie which only the server can read.
+///
+/// The cookie is removed if `max_age` is zero. Fails if the value contains
+/// characters which can't be sent in a header.
+pub(crate) fn set_cookie(
+    name: &str,
+    value: &str,
+    path: &str,
+    max_age: TimeDelta,
+) -> Result<HeaderValue, InvalidHeaderValue> {
+    HeaderValue::try_from(format!(
+        "{name}={value}; Path={path}; Max-Age={}; HttpOnly; Secure; SameSite=Lax",
+        max_age.num_seconds()
+    ))
+}
+
+/// Response to a request which failed authentication.
+fn unauthorized(message: &'static str) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        message,
+    )
+        .into_response()
+}
+
+/// Run the rest of the request's handling on behalf of its [`Owner`].
+pub(crate) async fn act_as_owner(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (mut parts, body) = request.into_parts();
+    let owner = match Owner::from_request_parts(&mut parts, &state).await {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+    parts.extensions.insert(owner.clone());
+    store::act_as(owner.0, next.run(Request::from_parts(parts, body))).await
+}
+
+/// CSRF token which must accompany changes made with the session `secret`.
+///
+/// The token is derived from the secret, which pages on other sites can't
+/// read, so it can't be forged by them and needn't be stored.
+pub(crate) fn csrf_token(secret: &str) -> String {
+    let digest = Sha256::new()
+        .chain_update(b"csrf:")
+        .chain_update(secret.as_bytes())
+        .finalize();
+    URL_SAFE_NO_PAD.encode(digest)
+}
+
+/// Reject changes made with a session cookie but without its CSRF token.
+///
+/// Requests with bearer tokens can't be forged by other sites, since
+/// browsers don't send them or custom headers cross-site on their own. Basic
+/// credentials are sent cross-site, so don't exempt requests from the check.
+pub(crate) async fn check_csrf(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let safe = matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .is_some_and(|value| value.as_bytes().starts_with(b"Bearer "));
+    if let (false, false, Some(secret)) = (safe, bearer, cookie(request.headers(), SESSION_COOKIE))
+    {
+        let expected = csrf_token(secret);
+        let sent = request
+            .headers()
+            .get(CSRF_HEADER)
+            .map(HeaderValue::as_bytes);
+        // compare in constant time, so the token can't be guessed byte by byte
+        let matches = sent.is_some_and(|sent| {
+            sent.len() == expected.len()
+                && sent
+                    .iter()
+                    .zip(expected.as_bytes())
+                    .fold(0, |diff, (a, b)| diff | (a ^ b))
+                    == 0
+        });
+        if !matches {
+            debug!("request without valid CSRF token received");
+            state
+                .record(
+                    SecurityEventKind::PermissionDenied,
+                    None,
+                    &format!(
+                        "{} {} without valid CSRF token",
+                        request.method(),
+                        request.uri().path()
+                    ),
+                )
+                .await;
+            return (StatusCode::FORBIDDEN, "CSRF token is missing or invalid").into_response();
+        }
+    }
+    next.run(request).await
+}
@@ -0,0 +1,462 @@
+//! Access to each owner's tasks as a CalDAV calendar collection of `VTODO`s.
+//!
+//! Only the subset of WebDAV and CalDAV which clients need to read and update
+//! to-dos is supported: `PROPFIND` with a fixed set of properties, the
+//! `calendar-query` and `calendar-multiget` reports, and `GET` and `PUT` of
+//! single to-dos. Tasks can't be deleted, so `DELETE` is refused.
+//!
+//! Each owner has a single collection, at [`COLLECTION`], holding their
+//! tasks as resources named after the tasks' IDs. Clients must be
+//! authenticated as a named owner, which CalDAV clients generally do with
+//! basic credentials whose password is an access token.
+
+use std::{fmt::Write, sync::Arc};
+
+use crate::{StoreError, TaskRecord, ical};
+use axum::{
+    Router,
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, Method, StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+    routing::{any, get},
+};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use super::{AppState, auth::Owner, check_creation_limits, zapier};
+
+/// Path of the principal, which is also the calendar home.
+const PRINCIPAL: &str = "/caldav/";
+
+/// Path of the calendar collection of tasks.
+const COLLECTION: &str = "/caldav/tasks/";
+
+/// Content type of a single to-do.
+const TODO_CONTENT_TYPE: &str = "text/calendar; charset=utf-8; component=VTODO";
+
+/// Routes serving the CalDAV collection.
+pub(crate) fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/.well-known/caldav",
+            get(|| async { Redirect::permanent(PRINCIPAL) }),
+        )
+        .route("/caldav", any(principal))
+        .route(PRINCIPAL, any(principal))
+        .route("/caldav/tasks", any(collection))
+        .route(COLLECTION, any(collection))
+        .route("/caldav/tasks/{name}", any(resource))
+}
+
+/// Serve the principal.
+async fn principal(
+    method: Method,
+    Owner(owner, _): Owner,
+    headers: HeaderMap,
+) -> Result<Response, Response> {
+    let owner = owner.ok_or_else(login_required)?;
+    match method.as_str() {
+        "OPTIONS" => Ok(options()),
+        "PROPFIND" => {
+            let mut responses = vec![response(PRINCIPAL, &principal_props(&owner))];
+            if depth(&headers) > 0 {
+                responses.push(response(COLLECTION, &collection_props(None)));
+            }
+            Ok(multistatus(&responses))
+        }
+        _ => Err(StatusCode::METHOD_NOT_ALLOWED.into_response()),
+    }
+}
+
+/// Serve the collection of tasks.
+async fn collection(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    Owner(owner, _): Owner,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, Response> {
+    let owner = owner.ok_or_else(login_required)?;
+    match method.as_str() {
+        "OPTIONS" => Ok(options()),
+        "PROPFIND" => {
+            let tasks = owned(&state, &owner).await?;
+            let ctag = collection_tag(&tasks);
+            let mut responses = vec![response(COLLECTION, &collection_props(Some(&ctag)))];
+            if depth(&headers) > 0 {
+                responses.extend(
+                    tasks
+                        .iter()
+                        .map(|record| response(&href(record.id), &resource_props(record, None))),
+                );
+            }
+            Ok(multistatus(&responses))
+        }
+        "REPORT" => report(&state, &owner, &body).await,
+        _ => Err(StatusCode::METHOD_NOT_ALLOWED.into_response()),
+    }
+}
+
+/// Respond to a `calendar-query` or `calendar-multiget` report.
+///
+/// Queries are answered with every task, unless they only ask for
+/// components other than to-dos.
+async fn report(state: &AppState, owner: &str, body: &[u8]) -> Result<Response, Response> {
+    let bad_request = |message: &'static str| (StatusCode::BAD_REQUEST, message).into_response();
+    let body = std::str::from_utf8(body).map_err(|_| bad_request("report must be UTF-8"))?;
+    let document = roxmltree::Document::parse(body).map_err(|e| {
+        debug!(error = format!("{e}"), "malformed report received");
+        bad_request("report must be well-formed XML")
+    })?;
+    let root = document.root_element();
+    let caldav_elements = |name: &'static str| {
+        root.descendants().filter(move |node| {
+            node.tag_name().name() == name
+                && node.tag_name().namespace() == Some("urn:ietf:params:xml:ns:caldav")
+        })
+    };
+
+    let tasks = owned(state, owner).await?;
+    let now = state.clock.now();
+    let responses = match root.tag_name().name() {
+        "calendar-query" => {
+            let todos = caldav_elements("comp-filter")
+                .all(|filter| matches!(filter.attribute("name"), Some("VCALENDAR" | "VTODO")));
+            if todos {
+                tasks
+                    .iter()
+                    .map(|record| response(&href(record.id), &resource_props(record, Some(now))))
+                    .collect()
+            } else {
+                Vec::new()
+            }
+        }
+        "calendar-multiget" => root
+            .descendants()
+            .filter(|node| node.has_tag_name(("DAV:", "href")))
+            .filter_map(|node| node.text())
+            .map(|requested| {
+                let requested = requested.trim();
+                match resource_id(requested)
+                    .and_then(|id| tasks.iter().find(|record| record.id == id))
+                {
+                    Some(record) => response(requested, &resource_props(record, Some(now))),
+                    None => not_found(requested),
+                }
+            })
+            .collect(),
+        _ => {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "only calendar-query and calendar-multiget reports are supported",
+            )
+                .into_response());
+        }
+    };
+    Ok(multistatus(&responses))
+}
+
+/// Serve a single task.
+async fn resource(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    Owner(owner, _): Owner,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, Response> {
+    let owner = owner.ok_or_else(login_required)?;
+    if method == Method::OPTIONS {
+        return Ok(options());
+    }
+    let id = resource_id(&name).ok_or_else(|| StatusCode::NOT_FOUND.into_response())?;
+    let tasks = owned(&state, &owner).await?;
+    let current = tasks.into_iter().find(|record| record.id == id);
+
+    match (method.as_str(), current) {
+        ("GET" | "HEAD", Some(record)) => Ok((
+            [
+                (header::CONTENT_TYPE, TODO_CONTENT_TYPE.to_string()),
+                (header::ETAG, etag(&record)),
+            ],
+            ical::to_vcalendar(record.id, &record.task, state.clock.now()),
+        )
+            .into_response()),
+        ("PROPFIND", Some(record)) => Ok(multistatus(&[response(
+            &href(id),
+            &resource_props(&record, None),
+        )])),
+        ("PUT", current) => put_resource(&state, &owner, id, current, &headers, &body).await,
+        ("DELETE", Some(_)) => Err((
+            StatusCode::FORBIDDEN,
+            "tasks can't be deleted, mark them as cancelled instead",
+        )
+            .into_response()),
+        ("GET" | "HEAD" | "PROPFIND" | "DELETE", None) => {
+            Err(StatusCode::NOT_FOUND.into_response())
+        }
+        _ => Err(StatusCode::METHOD_NOT_ALLOWED.into_response()),
+    }
+}
+
+/// Create or update a task from a to-do.
+async fn put_resource(
+    state: &AppState,
+    owner: &str,
+    id: Uuid,
+    current: Option<TaskRecord>,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Response, Response> {
+    let header_value = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let precondition_failed = match (&current, header_value(header::IF_MATCH)) {
+        (Some(_), _) if header_value(header::IF_NONE_MATCH) == Some("*") => true,
+        (None, Some(_)) => true,
+        (Some(record), Some(expected)) => expected != "*" && expected != etag(record),
+        (_, None) => false,
+    };
+    if precondition_failed {
+        debug!(task_id = format!("{id}"), "CalDAV precondition failed");
+        return Err(StatusCode::PRECONDITION_FAILED.into_response());
+    }
+
+    let task = std::str::from_utf8(body)
+        .map_err(|_| "calendar object must be UTF-8")
+        .and_then(ical::VTodo::parse)
+        .and_then(|todo| {
+            let current = current.as_ref().map(|record| &record.task);
+            todo.into_task(current, state.due_window, state.clock.now())
+        })
+        .map_err(|e| {
+            debug!(error = e, "malformed to-do received");
+            (StatusCode::BAD_REQUEST, e).into_response()
+        })?;
+
+    let result = if current.is_some() {
+        state
+            .store
+            .update(id, &task)
+            .await
+            .map(|_| StatusCode::NO_CONTENT)
+    } else {
+        check_creation_limits(state, Some(owner)).await?;
+        state
+            .store
+            .create_with_id(id, &task, Some(owner))
+            .await
+            .map(|()| {
+                zapier::task_created(state, Some(owner), id, &task);
+                StatusCode::CREATED
+            })
+    };
+    match result {
+        Ok(status) => {
+            info!(owner, task_id = format!("{id}"), "stored task from CalDAV");
+            let record = TaskRecord { id, task };
+            Ok((status, [(header::ETAG, etag(&record))]).into_response())
+        }
+        Err(StoreError::Conflict(_)) => {
+            debug!(
+                task_id = format!("{id}"),
+                "CalDAV resource taken by another owner"
+            );
+            Err((StatusCode::CONFLICT, "resource name is already taken").into_response())
+        }
+        Err(e) => Err(e.into_response()),
+    }
+}
+
+/// Response to anonymous clients, asking them for basic credentials.
+fn login_required() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Basic realm=\"tasks\"")],
+        "log in with an access token as the password",
+    )
+        .into_response()
+}
+
+/// Get the tasks of `owner`.
+async fn owned(state: &AppState, owner: &str) -> Result<Vec<TaskRecord>, Response> {
+    state
+        .store
+        .owned(owner)
+        .await
+        .map_err(IntoResponse::into_response)
+}
+
+/// Get the value of the `Depth` header, which is 0 or 1 as infinite depth
+/// isn't supported.
+fn depth(headers: &HeaderMap) -> u8 {
+    match headers.get("depth").and_then(|v| v.to_str().ok()) {
+        Some("0") => 0,
+        _ => 1,
+    }
+}
+
+/// Response to `OPTIONS`, advertising CalDAV support.
+fn options() -> Response {
+    (
+        [
+            ("dav", "1, calendar-access"),
+            ("allow", "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, REPORT"),
+        ],
+        StatusCode::OK,
+    )
+        .into_response()
+}
+
+/// Get the path of the resource of a task.
+fn href(id: Uuid) -> String {
+    format!("{COLLECTION}{id}.ics")
+}
+
+/// Get the ID of the task named by the last segment of `href`.
+fn resource_id(href: &str) -> Option<Uuid> {
+    let name = href.trim_end_matches('/').rsplit('/').next()?;
+    name.strip_suffix(".ics").unwrap_or(name).parse().ok()
+}
+
+/// Entity tag of a task, which changes whenever the task does.
+fn etag(record: &TaskRecord) -> String {
+    let state = serde_json::to_vec(&record.task).unwrap_or_default();
+    let digest = Sha256::new()
+        .chain_update(record.id.as_bytes())
+        .chain_update(state)
+        .finalize();
+    format!("\"{}\"", hex(&digest[..16]))
+}
+
+/// Tag of the collection, which changes whenever any of its tasks do.
+fn collection_tag(tasks: &[TaskRecord]) -> String {
+    let digest = tasks
+        .iter()
+        .fold(Sha256::new(), |digest, record| {
+            digest.chain_update(etag(record))
+        })
+        .finalize();
+    hex(&digest[..16])
+}
+
+/// Encode bytes in lowercase hexadecimal.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// Properties of the principal.
+fn principal_props(owner: &str) -> String {
+    format!(
+        "<d:resourcetype><d:collection/><d:principal/></d:resourcetype>\
+        <d:displayname>{}</d:displayname>\
+        <d:current-user-principal><d:href>{PRINCIPAL}</d:href></d:current-user-principal>\
+        <d:principal-URL><d:href>{PRINCIPAL}</d:href></d:principal-URL>\
+        <c:calendar-home-set><d:href>{PRINCIPAL}</d:href></c:calendar-home-set>",
+        escape(owner)
+    )
+}
+
+/// Properties of the collection, including its tag if known.
+fn collection_props(ctag: Option<&str>) -> String {
+    let mut props = format!(
+        "<d:resourcetype><d:collection/><c:calendar/></d:resourcetype>\
+        <d:displayname>Tasks</d:displayname>\
+        <d:current-user-principal><d:href>{PRINCIPAL}</d:href></d:current-user-principal>\
+        <d:current-user-privilege-set>\
+        <d:privilege><d:read/></d:privilege>\
+        <d:privilege><d:write-content/></d:privilege>\
+        <d:privilege><d:bind/></d:privilege>\
+        </d:current-user-privilege-set>\
+        <d:supported-report-set>\
+        <d:supported-report><d:report><c:calendar-query/></d:report></d:supported-report>\
+        <d:supported-report><d:report><c:calendar-multiget/></d:report></d:supported-report>\
+        </d:supported-report-set>\
+        <c:supported-calendar-component-set><c:comp name=\"VTODO\"/></c:supported-calendar-component-set>"
+    );
+    if let Some(ctag) = ctag {
+        let _ = write!(props, "<cs:getctag>{ctag}</cs:getctag>");
+    }
+    props
+}
+
+/// Properties of the resource of a task, including its iCalendar data stamped
+/// at `now` if given.
+fn resource_props(record: &TaskRecord, now: Option<DateTime<Utc>>) -> String {
+    let mut props = format!(
+        "<d:resourcetype/><d:getetag>{}</d:getetag>\
+        <d:getcontenttype>{TODO_CONTENT_TYPE}</d:getcontenttype>",
+        escape(&etag(record))
+    );
+    if let Some(now) = now {
+        let data = ical::to_vcalendar(record.id, &record.task, now);
+        let _ = write!(
+            props,
+            "<c:calendar-data>{}</c:calendar-data>",
+            escape(&data)
+        );
+    }
+    props
+}
+
+/// Response element for a resource with `props`.
+fn response(href: &str, props: &str) -> String {
+    format!(
+        "<d:response><d:href>{}</d:href>\
+        <d:propstat><d:prop>{props}</d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>\
+        </d:response>",
+        escape(href)
+    )
+}
+
+/// Response element for a resource which doesn't exist.
+fn not_found(href: &str) -> String {
+    format!(
+        "<d:response><d:href>{}</d:href><d:status>HTTP/1.1 404 Not Found</d:status></d:response>",
+        escape(href)
+    )
+}
+
+/// Multi-status response made of `responses`.
+fn multistatus(responses: &[String]) -> Response {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+        <d:multistatus xmlns:d=\"DAV:\" xmlns:c=\"urn:ietf:params:xml:ns:caldav\" \
+        xmlns:cs=\"http://calendarserver.org/ns/\">{}</d:multistatus>",
+        responses.concat()
+    );
+    (
+        StatusCode::MULTI_STATUS,
+        [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+        body,
+    )
+        .into_response()
+}
+
+/// Escape text for use in XML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resource_ids() {
+        let id = Uuid::new_v4();
+        assert_eq!(resource_id(&href(id)), Some(id));
+        assert_eq!(
+            resource_id(&format!("https://example.com/caldav/tasks/{id}.ics")),
+            Some(id)
+        );
+        assert_eq!(resource_id("/caldav/tasks/not-a-task.ics"), None);
+    }
+}
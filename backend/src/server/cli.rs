@@ -0,0 +1,888 @@
+//! Options of the server, given on the command line of the
+//! `dts_developer_challenge` binary, which [`router`](super::router) is set
+//! up from.
+
+use std::{num::NonZeroU32, path::PathBuf};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use hyper::http::uri::Authority;
+use sqlx::{
+    ConnectOptions,
+    postgres::{PgConnectOptions, PgSslMode},
+};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::{
+    DEFAULT_DUE_WINDOW_YEARS, TodoTask,
+    calendar::WorkCalendar,
+    digest::DigestPeriod,
+    schedule::Schedule,
+    store::HistoryErasure,
+    sync::{ConflictRule, SyncProvider},
+};
+
+/// How tasks are persisted in the database.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageMode {
+    /// Keep the current state of each task in a table row.
+    Table,
+    /// Derive the state of each task from an append-only stream of events.
+    ///
+    /// Tasks stored in table mode are imported on startup.
+    /// Switching back to table mode is not supported.
+    Events,
+}
+
+/// Whether and how to secure the connection to Postgres with TLS.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SslMode {
+    /// Never use TLS.
+    Disable,
+    /// Use TLS only if the server requires it.
+    Allow,
+    /// Use TLS if the server supports it.
+    Prefer,
+    /// Always use TLS, without verifying the server's certificate.
+    Require,
+    /// Always use TLS, verifying the server's certificate is signed by a
+    /// trusted authority.
+    VerifyCa,
+    /// Always use TLS, verifying the server's certificate is signed by a
+    /// trusted authority and issued for the host connected to.
+    VerifyFull,
+}
+
+impl From<SslMode> for PgSslMode {
+    fn from(mode: SslMode) -> Self {
+        match mode {
+            SslMode::Disable => Self::Disable,
+            SslMode::Allow => Self::Allow,
+            SslMode::Prefer => Self::Prefer,
+            SslMode::Require => Self::Require,
+            SslMode::VerifyCa => Self::VerifyCa,
+            SslMode::VerifyFull => Self::VerifyFull,
+        }
+    }
+}
+
+/// What to do with the history of tasks when erasing a user's data.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErasureMode {
+    /// Keep the history as an audit trail, with personal content blanked.
+    Anonymise,
+    /// Delete the history.
+    Delete,
+}
+
+impl From<ErasureMode> for HistoryErasure {
+    fn from(mode: ErasureMode) -> Self {
+        match mode {
+            ErasureMode::Anonymise => Self::Anonymise,
+            ErasureMode::Delete => Self::Delete,
+        }
+    }
+}
+
+/// What to do with fields of tasks in JSON request bodies which tasks don't
+/// have.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownFields {
+    /// Reject the request with `400 Bad Request`, naming the field.
+    Strict,
+    /// Ignore the fields, logging their names.
+    Lenient,
+}
+
+/// What to do with tasks created already overdue.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PastDuePolicy {
+    /// Reject the task with `400 Bad Request`.
+    Reject,
+    /// Create the task.
+    Allow,
+    /// Create the task, responding with `201 Created` and a JSON body of its
+    /// ID and any warnings about it, such as that it is overdue.
+    AllowWithWarning,
+}
+
+/// Which hours count towards tasks being overdue.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverdueHours {
+    /// Tasks are overdue as soon as they're due.
+    All,
+    /// Tasks are overdue once some working hours have passed since they
+    /// were due, so tasks due at the end of a working week aren't overdue
+    /// until the next one starts.
+    Working,
+}
+
+/// How often to email digests of tasks.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestFrequency {
+    /// Every day, of tasks due in the next day and completed in the last.
+    Daily,
+    /// Every week, of tasks due in the next week and completed in the last.
+    Weekly,
+}
+
+impl From<DigestFrequency> for DigestPeriod {
+    fn from(frequency: DigestFrequency) -> Self {
+        match frequency {
+            DigestFrequency::Daily => Self::Daily,
+            DigestFrequency::Weekly => Self::Weekly,
+        }
+    }
+}
+
+/// Whether cancelled and blocked tasks must be given a status reason.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusReasonPolicy {
+    /// Allow a reason to be given.
+    Optional,
+    /// Reject tasks without a reason with `400 Bad Request`.
+    Required,
+}
+
+impl StatusReasonPolicy {
+    /// Check that `task` has a status reason if it needs one.
+    pub(crate) fn check(self, task: &TodoTask) -> Result<(), &'static str> {
+        if self == Self::Required && task.status.is_stopped() && task.status_reason().is_none() {
+            Err("a reason is required for cancelled or blocked tasks")
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Application to sync tasks from.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncSource {
+    /// Google Tasks.
+    Google,
+    /// Microsoft To Do, with the Microsoft Graph API.
+    Microsoft,
+}
+
+impl From<SyncSource> for SyncProvider {
+    fn from(source: SyncSource) -> Self {
+        match source {
+            SyncSource::Google => Self::Google,
+            SyncSource::Microsoft => Self::Microsoft,
+        }
+    }
+}
+
+/// What to do with uploaded attachments found to be infected.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InfectedUploads {
+    /// Refuse the upload, storing nothing.
+    Reject,
+    /// Store the file, but never let it be downloaded, so it can be
+    /// investigated.
+    Quarantine,
+}
+
+/// Gateway to send SMS through.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmsProvider {
+    /// Twilio's Programmable Messaging API.
+    Twilio,
+}
+
+/// Which version of a synced task is kept when it was changed on both sides.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictMode {
+    /// Overwrite local changes with the provider's.
+    RemoteWins,
+    /// Keep local changes, and skip the task until the provider's version
+    /// matches.
+    LocalWins,
+}
+
+impl From<ConflictMode> for ConflictRule {
+    fn from(mode: ConflictMode) -> Self {
+        match mode {
+            ConflictMode::RemoteWins => Self::RemoteWins,
+            ConflictMode::LocalWins => Self::LocalWins,
+        }
+    }
+}
+
+/// Parse a similarity of titles, which must be from 0 to 1.
+fn similarity(value: &str) -> Result<f32, String> {
+    let similarity: f32 = value.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=1.0).contains(&similarity) {
+        Ok(similarity)
+    } else {
+        Err("similarity must be between 0 and 1".to_owned())
+    }
+}
+
+/// Command-line arguments of the application.
+#[derive(Parser, Debug, Clone)]
+// each bool is an independent flag
+#[allow(clippy::struct_excessive_bools)]
+pub struct Opt {
+    /// Address at which to serve the application.
+    #[clap(default_value = "0.0.0.0:8080")]
+    pub service_address: String,
+    /// Directory of a built single-page frontend to serve alongside the API,
+    /// so both can be deployed as one container.
+    ///
+    /// Its files are served at paths the API doesn't use, and `index.html`
+    /// at any other path outside the API's, such as `/task/...`, for the
+    /// frontend to route itself. No frontend is served by default.
+    #[clap(long)]
+    pub frontend_dir: Option<PathBuf>,
+    /// Address to contact the Postgres server on.
+    #[clap(long)]
+    pub db_host: String,
+    /// Port to contact the Postgres server on.
+    #[clap(long, default_value_t = 5432)]
+    pub db_port: u16,
+    /// Name of the database to open in Postgres.
+    #[clap(long)]
+    pub db_name: Option<String>,
+    /// Username to access the Postgres database as.
+    #[clap(long, default_value = "postgres")]
+    pub db_user: String,
+    /// Password for write access to the database in Postgres.
+    ///
+    /// Connects without password by default.
+    #[clap(long)]
+    pub db_password_file: Option<PathBuf>,
+    /// Whether and how to secure database connections with TLS.
+    ///
+    /// Defaults to `prefer`, or for the replica to the mode in its connection
+    /// string.
+    #[clap(long, value_enum)]
+    pub db_ssl_mode: Option<SslMode>,
+    /// PEM file of the certificate authorities trusted to sign the database
+    /// server's certificate, in addition to the usual public ones.
+    #[clap(long)]
+    pub db_ssl_root_cert: Option<PathBuf>,
+    /// PEM file of the client certificate to present to the database server.
+    #[clap(long, requires = "db_ssl_client_key")]
+    pub db_ssl_client_cert: Option<PathBuf>,
+    /// PEM file of the private key of the client certificate.
+    #[clap(long, requires = "db_ssl_client_cert")]
+    pub db_ssl_client_key: Option<PathBuf>,
+    /// Connection string of a read-only replica to serve listings, searches
+    /// and statistics from, e.g. `postgres://app@replica:5432/app`.
+    ///
+    /// The password, if any, is read from the password file, as for the
+    /// primary.
+    #[clap(long)]
+    pub db_replica_url: Option<String>,
+    /// Number of seconds the replica may lag behind the primary before reads
+    /// are served by the primary instead.
+    #[clap(long, default_value = "5")]
+    pub db_replica_max_lag_secs: u64,
+    /// Number of milliseconds after which the database gives up on a
+    /// statement, or 0 to let statements run for as long as they take.
+    #[clap(long, default_value = "30000")]
+    pub statement_timeout_ms: u64,
+    /// Number of seconds after which a request is given up on with
+    /// `503 Service Unavailable`, and the database stops its statements.
+    #[clap(long, default_value = "30")]
+    pub request_timeout_secs: NonZeroU32,
+    /// Skip running the database migrations on startup.
+    #[clap(long, default_value_t = false)]
+    pub skip_migrations: bool,
+    /// Default minimum similarity, from 0 to 1, of fuzzy title search results.
+    ///
+    /// Clients may override this per search.
+    #[clap(long, default_value_t = 0.5, value_parser = similarity)]
+    pub search_threshold: f32,
+    /// Reject new tasks which look like duplicates of existing open tasks.
+    ///
+    /// Clients may override this per request.
+    #[clap(long, default_value_t = false)]
+    pub detect_duplicates: bool,
+    /// Minimum title similarity, from 0 to 1, for a task to be a duplicate.
+    #[clap(long, default_value_t = 0.6, value_parser = similarity)]
+    pub duplicate_threshold: f32,
+    /// How to persist tasks in the database.
+    #[clap(long, value_enum, default_value_t = StorageMode::Table)]
+    pub storage: StorageMode,
+    /// Number of events between snapshots of a task, in `events` storage mode.
+    #[clap(long, default_value = "50")]
+    pub snapshot_interval: NonZeroU32,
+    /// Restrict each request to its owner's tasks with Postgres row-level
+    /// security policies.
+    ///
+    /// Requests act as the `tasks_rls` database role, which the database user
+    /// is granted by the migrations.
+    #[clap(long, default_value_t = false)]
+    pub row_level_security: bool,
+    /// Number of milliseconds after which task store queries are logged as
+    /// slow.
+    #[clap(long, default_value = "500")]
+    pub slow_query_ms: u64,
+    /// Number of times to retry a write to the task store which fails with
+    /// a serialisation failure or deadlock, due to a concurrent write.
+    #[clap(long, default_value = "3")]
+    pub transaction_retries: u32,
+    /// Number of database connection failures in a row after which requests
+    /// fail straight away with `503 Service Unavailable`, until the database
+    /// answers again.
+    #[clap(long, default_value = "5")]
+    pub breaker_threshold: NonZeroU32,
+    /// Number of seconds between checks of whether the database is
+    /// available again, once requests are failing.
+    #[clap(long, default_value = "10")]
+    pub breaker_cooldown_secs: NonZeroU32,
+    /// Number of background workers, which deliver tasks to hooks, run bulk
+    /// jobs, send notifications and generate thumbnails.
+    #[clap(long, default_value = "4")]
+    pub workers: NonZeroU32,
+    /// Number of deliveries and bulk jobs which may wait for a background
+    /// worker, beyond which deliveries are dropped and bulk jobs refused with
+    /// `503 Service Unavailable`.
+    #[clap(long, default_value = "1000")]
+    pub worker_queue: NonZeroU32,
+    /// When to delete expired login sessions, as a cron expression in UTC.
+    #[clap(long, default_value = "@hourly")]
+    pub purge_sessions_schedule: Schedule,
+    /// When to recompute the statistics served by `/stats`, as a cron
+    /// expression in UTC.
+    ///
+    /// Statistics are served as of the last refresh, which responses give as
+    /// `refreshed_at`.
+    #[clap(long, default_value = "*/5 * * * *")]
+    pub stats_refresh_schedule: Schedule,
+    /// What to do with the history of tasks when erasing a user's data.
+    #[clap(long, value_enum, default_value_t = ErasureMode::Anonymise)]
+    pub history_erasure: ErasureMode,
+    /// What to do with fields of tasks given as JSON which tasks don't have.
+    ///
+    /// Rejecting them catches clients misspelling fields or relying on
+    /// fields this release doesn't know yet, while ignoring them lets newer
+    /// clients talk to older releases.
+    #[clap(long, value_enum, default_value_t = UnknownFields::Lenient)]
+    pub unknown_fields: UnknownFields,
+    /// Number of years before or after now that due dates of tasks may be.
+    ///
+    /// Due dates outside this window are rejected as likely mistakes, such as
+    /// a mistyped year.
+    #[clap(long, default_value_t = DEFAULT_DUE_WINDOW_YEARS)]
+    pub due_window_years: u32,
+    /// What to do with tasks created with `POST /task` which are already
+    /// overdue: due in the past, as counted in `--overdue-hours`, and neither
+    /// complete nor cancelled.
+    #[clap(long, value_enum, default_value_t = PastDuePolicy::Allow)]
+    pub past_due_on_create: PastDuePolicy,
+    /// Whether tasks created or replaced as cancelled or blocked must say
+    /// why, with a `status_reason`.
+    #[clap(long, value_enum, default_value_t = StatusReasonPolicy::Optional)]
+    pub status_reason: StatusReasonPolicy,
+    /// JSON file of the workflow: custom statuses, and the allowed
+    /// transitions between statuses.
+    ///
+    /// Without one, only the built-in statuses exist, and tasks may change
+    /// between them freely.
+    #[clap(long)]
+    pub workflow: Option<PathBuf>,
+    /// JSON file of service level targets: an array of policies, each with
+    /// an optional `tag`, `respond_within_hours` and
+    /// `resolve_within_hours`.
+    ///
+    /// Tasks follow the policy for the first of their tags which has one,
+    /// or else the policy without a tag. Without a file, tasks have no
+    /// targets.
+    #[clap(long)]
+    pub sla: Option<PathBuf>,
+    /// Working days and hours in UTC, like `Mon-Fri 09:00-17:00`, in which
+    /// time towards service level targets is counted.
+    #[clap(long, default_value = "Mon-Fri 09:00-17:00")]
+    pub working_hours: WorkCalendar,
+    /// Which hours count towards tasks being overdue, when creating tasks
+    /// and in statistics.
+    #[clap(long, value_enum, default_value_t = OverdueHours::All)]
+    pub overdue_hours: OverdueHours,
+    /// Check tasks given as JSON against their JSON Schema, served at
+    /// `/schema/task.json`, before deserializing them.
+    ///
+    /// Every problem with a task is then reported at once, each with the JSON
+    /// pointer of the field it is in.
+    #[clap(long)]
+    pub validate_json_schema: bool,
+    /// Names of fields whose values are redacted from logs.
+    #[clap(
+        long = "redact-field",
+        default_values = ["title", "description", "title_cy", "description_cy", "status_reason"]
+    )]
+    pub redact_fields: Vec<String>,
+    /// Log field values in full, without redaction.
+    ///
+    /// Logs may then contain personal data, so this is only intended for
+    /// development.
+    #[clap(long, default_value_t = false)]
+    pub log_payloads: bool,
+    /// Log each SQL statement run against the database, with its timing and
+    /// row counts.
+    ///
+    /// Statements are logged as sent, with placeholders in place of the
+    /// values bound to them, so the values themselves aren't logged. This is
+    /// only intended for development, as it makes logs much noisier.
+    #[clap(long, default_value_t = false)]
+    pub log_sql: bool,
+    /// Only accept requests authenticated with a personal access token or a
+    /// login session.
+    ///
+    /// Otherwise, clients may name themselves with the `X-Owner` header.
+    #[clap(long, default_value_t = false)]
+    pub require_tokens: bool,
+    /// Owner allowed to use the administration endpoints, whether or not they
+    /// are a registered user with the admin role; may be repeated.
+    ///
+    /// Administrators, like users with roles, must authenticate with an
+    /// access token or login session, rather than the `X-Owner` header.
+    #[clap(long = "admin")]
+    pub admins: Vec<String>,
+    /// Only accept requests from, and assign tasks to, active registered
+    /// users.
+    ///
+    /// Otherwise, owners and assignees are any names, such as those a
+    /// gateway gives in the `X-Owner` header, and only deactivated users are
+    /// refused. This is off by default so deployments which don't register
+    /// users keep working.
+    #[clap(long, default_value_t = false)]
+    pub registered_users_only: bool,
+    /// Issuer URL of the OIDC provider to log browsers in with.
+    ///
+    /// Must use `https`, unless the provider is on this host. Login is
+    /// disabled by default.
+    #[clap(long, requires_all = ["oidc_client_id", "oidc_redirect_url"])]
+    pub oidc_issuer: Option<String>,
+    /// Client ID registered with the OIDC provider.
+    #[clap(long)]
+    pub oidc_client_id: Option<String>,
+    /// File containing the client secret registered with the OIDC provider,
+    /// for confidential clients.
+    #[clap(long)]
+    pub oidc_client_secret_file: Option<PathBuf>,
+    /// Public URL of the `/auth/callback` endpoint, registered with the OIDC
+    /// provider.
+    #[clap(long)]
+    pub oidc_redirect_url: Option<String>,
+    /// Claim of ID tokens identifying the owner who logged in.
+    #[clap(long, default_value = "sub")]
+    pub oidc_owner_claim: String,
+    /// Number of hours a login session lasts.
+    #[clap(long, default_value = "8")]
+    pub session_hours: NonZeroU32,
+    /// Maximum number of open tasks each owner may have.
+    ///
+    /// Unlimited by default.
+    #[clap(long)]
+    pub max_open_tasks: Option<u32>,
+    /// Maximum number of tasks each owner may create per minute.
+    ///
+    /// Unlimited by default.
+    #[clap(long)]
+    pub max_creations_per_minute: Option<NonZeroU32>,
+    /// Application to sync a task list from into the tasks of `--sync-owner`.
+    ///
+    /// The sync is one-way: changes made locally are not sent back. It is
+    /// disabled by default.
+    #[clap(
+        long,
+        value_enum,
+        requires_all = [
+            "sync_owner",
+            "sync_credentials_file",
+            "sync_token_url",
+            "sync_api_url",
+            "sync_list",
+        ]
+    )]
+    pub sync_provider: Option<SyncSource>,
+    /// Owner of the tasks synced from the provider.
+    #[clap(long)]
+    pub sync_owner: Option<String>,
+    /// JSON file containing the `client_id`, `refresh_token` and optionally
+    /// `client_secret` and `scope` to get access tokens from the provider
+    /// with.
+    #[clap(long)]
+    pub sync_credentials_file: Option<PathBuf>,
+    /// URL of the provider's OAuth token endpoint.
+    #[clap(long)]
+    pub sync_token_url: Option<String>,
+    /// Base URL of the provider's API, e.g. `https://tasks.googleapis.com`
+    /// or `https://graph.microsoft.com`.
+    #[clap(long)]
+    pub sync_api_url: Option<String>,
+    /// Provider's ID for the task list to sync.
+    #[clap(long)]
+    pub sync_list: Option<String>,
+    /// Number of minutes between syncs.
+    #[clap(long, default_value = "15")]
+    pub sync_interval_minutes: NonZeroU32,
+    /// Which version of a task is kept when it was changed both locally and
+    /// by the provider since the last sync.
+    #[clap(long, value_enum, default_value_t = ConflictMode::LocalWins)]
+    pub sync_conflict: ConflictMode,
+    /// Owner of the tasks created from emails posted to the `/inbound/email`
+    /// webhook.
+    ///
+    /// Disabled by default.
+    #[clap(long, requires = "email_signing_key_file")]
+    pub email_owner: Option<String>,
+    /// File containing the key which inbound email webhook requests are
+    /// signed with.
+    #[clap(long)]
+    pub email_signing_key_file: Option<PathBuf>,
+    /// Address whose emails are turned into tasks, or `@` followed by a
+    /// domain to allow all of its addresses; may be repeated.
+    ///
+    /// Emails from other senders are refused.
+    #[clap(long = "email-allowed-sender")]
+    pub email_allowed_senders: Vec<String>,
+    /// Number of days after being received that tasks from emails without a
+    /// `due:` line are due.
+    #[clap(long, default_value_t = 7)]
+    pub email_default_due_days: u32,
+    /// How often to email users digests of their overdue, due-soon and
+    /// recently completed tasks.
+    ///
+    /// Users without an email address, or who have opted out, aren't sent
+    /// digests. Disabled by default.
+    #[clap(long, value_enum, requires = "smtp_relay")]
+    pub digest: Option<DigestFrequency>,
+    /// When to send digests, as a cron expression in UTC.
+    ///
+    /// Defaults to 07:00 every day for daily digests, or every Monday for
+    /// weekly digests.
+    #[clap(long)]
+    pub digest_schedule: Option<Schedule>,
+    /// Address to email one digest of every task to, instead of emailing
+    /// each user a digest of their own tasks.
+    #[clap(long)]
+    pub digest_to: Option<String>,
+    /// File containing the plain text template which digests are rendered
+    /// from.
+    ///
+    /// `{{name}}`, `{{period}}`, `{{overdue}}`, `{{due_soon}}` and
+    /// `{{completed}}` are replaced by the recipient's name, `daily` or
+    /// `weekly`, and the lists of tasks.
+    #[clap(long)]
+    pub digest_template: Option<PathBuf>,
+    /// Host and port of the SMTP relay to send emails through.
+    ///
+    /// Emails are relayed over plain SMTP without authentication, so the
+    /// relay should be on the local network, e.g. a sidecar which forwards
+    /// them on over TLS.
+    #[clap(long, requires = "mail_from")]
+    pub smtp_relay: Option<Authority>,
+    /// Address which emails are sent from.
+    #[clap(long)]
+    pub mail_from: Option<String>,
+    /// Notify users by email of tasks assigned to them, mentions of them,
+    /// and their tasks becoming due soon or overdue, unless they opt out.
+    #[clap(long, default_value_t = false, requires = "smtp_relay")]
+    pub notify_by_email: bool,
+    /// Number of hours before tasks are due that their assignees, or else
+    /// owners, are notified that they are due soon.
+    #[clap(long, default_value = "24")]
+    pub due_soon_hours: NonZeroU32,
+    /// When to send pending notifications, as a cron expression in UTC.
+    #[clap(long, default_value = "* * * * *")]
+    pub notifications_schedule: Schedule,
+    /// Host and port of an HTTP proxy which every outbound HTTP request is
+    /// sent through, such as REST hook deliveries, notifications, syncs and
+    /// logins.
+    ///
+    /// Requests to `https` URLs are tunnelled through the proxy with
+    /// `CONNECT`, so it can't read them, and others are forwarded by it.
+    /// Requests are made directly by default.
+    #[clap(long)]
+    pub egress_proxy: Option<Authority>,
+    /// Host which outbound HTTP requests may be made to; may be repeated.
+    ///
+    /// Requests to other hosts are refused. Any host may be reached by
+    /// default, though REST hooks and Web Push are limited to their own
+    /// allowed hosts too.
+    #[clap(long = "egress-allowed-host")]
+    pub egress_allowed_hosts: Vec<String>,
+    /// Host which REST hooks may be subscribed at, e.g. by Zapier; may be
+    /// repeated.
+    ///
+    /// Hooks at other hosts are refused, so they can't be used to reach
+    /// internal services.
+    #[clap(long = "hook-allowed-host", default_value = "hooks.zapier.com")]
+    pub hook_allowed_hosts: Vec<String>,
+    /// Host and port of an HTTP proxy to deliver REST hooks through, instead
+    /// of `--egress-proxy`.
+    #[clap(long)]
+    pub hook_proxy: Option<Authority>,
+    /// File containing the P-256 private key, in PKCS#8 PEM, identifying this
+    /// server to browsers' push services as in VAPID.
+    ///
+    /// Users are only notified with Web Push if this is given. Such a key is
+    /// made by `openssl genpkey -algorithm EC -pkeyopt
+    /// ec_paramgen_curve:P-256`.
+    #[clap(long, requires = "vapid_subject")]
+    pub vapid_private_key_file: Option<PathBuf>,
+    /// Contact for the operators of this server, a `mailto:` or `https:`
+    /// URL, which push services may use if there is a problem.
+    #[clap(long)]
+    pub vapid_subject: Option<String>,
+    /// Host and port of an HTTP proxy to deliver Web Push notifications
+    /// through, instead of `--egress-proxy`.
+    #[clap(long)]
+    pub push_proxy: Option<Authority>,
+    /// Host of a push service which browsers may subscribe with; may be
+    /// repeated.
+    ///
+    /// Defaults to the services of Chrome, Firefox and Safari.
+    #[clap(
+        long = "push-allowed-host",
+        default_values = [
+            "fcm.googleapis.com",
+            "updates.push.services.mozilla.com",
+            "web.push.apple.com",
+        ]
+    )]
+    pub push_allowed_hosts: Vec<String>,
+    /// Gateway to send SMS notifications through, to users with a phone
+    /// number.
+    ///
+    /// Users are only sent SMS about tasks becoming overdue unless they
+    /// choose otherwise. Disabled by default.
+    #[clap(long, value_enum, requires_all = ["sms_api_url", "sms_from"])]
+    pub sms_gateway: Option<SmsProvider>,
+    /// Base URL of the SMS gateway's API, e.g. `https://api.twilio.com`.
+    #[clap(long)]
+    pub sms_api_url: Option<String>,
+    /// Phone number which SMS are sent from, in E.164 format.
+    #[clap(long)]
+    pub sms_from: Option<String>,
+    /// Account SID to send SMS through Twilio with.
+    #[clap(long, required_if_eq("sms_gateway", "twilio"))]
+    pub twilio_account_sid: Option<String>,
+    /// File containing the auth token of the Twilio account.
+    #[clap(long, required_if_eq("sms_gateway", "twilio"))]
+    pub twilio_auth_token_file: Option<PathBuf>,
+    /// Base URL of the GOV.UK Notify API to send notifications with, e.g.
+    /// `https://api.notifications.service.gov.uk`.
+    ///
+    /// Notifications are only sent with Notify for the templates given.
+    #[clap(long, requires = "gov_notify_api_key_file")]
+    pub gov_notify_api_url: Option<String>,
+    /// File containing the Notify API key of the service to send with, e.g.
+    /// as mounted from a secrets store.
+    #[clap(long, requires = "gov_notify_api_url")]
+    pub gov_notify_api_key_file: Option<PathBuf>,
+    /// ID of the Notify email template to notify users by email with,
+    /// instead of the SMTP relay.
+    ///
+    /// Templates may use the personalisation fields `name`, `event`,
+    /// `summary`, `title`, `due` and `task_id`.
+    #[clap(
+        long,
+        requires = "gov_notify_api_url",
+        conflicts_with = "notify_by_email"
+    )]
+    pub gov_notify_email_template: Option<Uuid>,
+    /// ID of the Notify text message template to notify users by SMS with,
+    /// instead of an SMS gateway.
+    ///
+    /// Templates may use the same personalisation fields as email
+    /// templates.
+    #[clap(long, requires = "gov_notify_api_url", conflicts_with = "sms_gateway")]
+    pub gov_notify_sms_template: Option<Uuid>,
+    /// Host and port of a `clamd` virus scanner to scan uploaded attachments
+    /// with.
+    ///
+    /// Attachments can only be uploaded if this is given, so that no file
+    /// is stored without being scanned.
+    #[clap(long)]
+    pub clamd_address: Option<Authority>,
+    /// Number of seconds scanning an attachment may take before the upload
+    /// is refused.
+    #[clap(long, default_value = "30")]
+    pub scan_timeout_secs: NonZeroU32,
+    /// What to do with uploaded attachments found to be infected.
+    #[clap(long, value_enum, default_value_t = InfectedUploads::Reject)]
+    pub infected_uploads: InfectedUploads,
+    /// Largest attachment which may be uploaded, in bytes.
+    #[clap(long, default_value = "10485760")]
+    pub max_attachment_bytes: NonZeroU32,
+    /// Maximum total size of the files attached to each owner's tasks, in
+    /// bytes.
+    ///
+    /// Unlimited by default.
+    #[clap(long)]
+    pub max_storage_bytes: Option<u64>,
+    /// File containing the key which attachment download URLs are signed
+    /// with, which every instance of the server must share.
+    ///
+    /// Otherwise a random key is made on startup, so URLs only work with the
+    /// instance which signed them, until it restarts.
+    #[clap(long)]
+    pub download_signing_key_file: Option<PathBuf>,
+    /// When to generate thumbnails of newly uploaded image attachments, as a
+    /// cron expression in UTC.
+    #[clap(long, default_value = "* * * * *")]
+    pub thumbnail_schedule: Schedule,
+    /// Number of seconds signed attachment download URLs last.
+    #[clap(long, default_value = "300")]
+    pub download_url_secs: NonZeroU32,
+    /// Task to run instead of serving the application.
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Tasks run instead of serving the application.
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Report on the plans the database chooses for the listing, search and
+    /// statistics queries, running each with sample parameters.
+    ///
+    /// Exits with an error if any plan has scans which would benefit from an
+    /// index or sorts which spill to disk, so it can guard against query
+    /// regressions in CI.
+    Analyze {
+        /// Number of rows a filtered sequential scan must read to be warned
+        /// of.
+        #[clap(long, default_value_t = 10_000.0)]
+        min_rows: f64,
+        /// Print each plan in full, as JSON.
+        #[clap(long, default_value_t = false)]
+        verbose: bool,
+    },
+    /// Fill the database with realistic generated tasks, for performance
+    /// work to be reproducible against.
+    ///
+    /// The same seed always generates the same tasks, with due dates
+    /// relative to now. Their owners are named `loadgen-001` and so on.
+    Loadgen {
+        /// Number of tasks to generate.
+        #[clap(long, default_value_t = 10_000)]
+        tasks: u64,
+        /// Seed of the generated tasks.
+        #[clap(long, default_value_t = 0)]
+        seed: u64,
+        /// Number of owners to share the tasks between, or 0 to make them
+        /// all anonymous.
+        #[clap(long, default_value_t = 20)]
+        owners: u32,
+        /// Number of tasks to store at once.
+        #[clap(long, default_value = "8")]
+        concurrency: NonZeroU32,
+    },
+    /// Create a personal access token and print it.
+    ///
+    /// Tokens can only be created over HTTP with a token or session, so this
+    /// creates the first one where logins are not set up.
+    CreateToken {
+        /// Owner to authenticate requests with the token as.
+        #[clap(long)]
+        owner: String,
+        /// Description of what the token is for.
+        #[clap(long)]
+        name: String,
+        /// Permit creating, changing and erasing tasks as well as reading
+        /// them.
+        #[clap(long, default_value_t = false)]
+        write: bool,
+        /// Number of days until the token expires; it never expires if
+        /// omitted.
+        #[clap(long)]
+        expires_in_days: Option<NonZeroU32>,
+    },
+}
+
+impl Opt {
+    /// Options to connect to the database with.
+    #[must_use]
+    #[tracing::instrument]
+    pub fn db_options(&self) -> PgConnectOptions {
+        // assemble connection options
+        let mut db_options = PgConnectOptions::new()
+            .host(&self.db_host)
+            .port(self.db_port)
+            .username(&self.db_user);
+        if let Some(db_name) = self.db_name.as_deref() {
+            db_options = db_options.database(db_name);
+        }
+        if let Some(password) = self.db_password() {
+            db_options = db_options.password(&password);
+        }
+
+        self.with_connection_settings(db_options)
+    }
+
+    /// Options to connect to the read replica with, if one is configured.
+    pub(crate) fn db_replica_options(&self) -> Option<PgConnectOptions> {
+        let url = self.db_replica_url.as_deref()?;
+        let mut db_options: PgConnectOptions =
+            url.parse().expect("invalid replica connection string");
+        if let Some(password) = self.db_password() {
+            db_options = db_options.password(&password);
+        }
+        Some(self.with_connection_settings(db_options))
+    }
+
+    /// Apply the TLS settings, statement timeout and statement logging to
+    /// database connection options.
+    fn with_connection_settings(&self, mut db_options: PgConnectOptions) -> PgConnectOptions {
+        db_options =
+            db_options.options([("statement_timeout", self.statement_timeout_ms.to_string())]);
+        if self.log_sql {
+            // sqlx logs statements at debug level by default, below what is
+            // logged
+            db_options = db_options.log_statements(log::LevelFilter::Info);
+        }
+        if let Some(mode) = self.db_ssl_mode {
+            db_options = db_options.ssl_mode(mode.into());
+        }
+        if let Some(path) = &self.db_ssl_root_cert {
+            db_options = db_options.ssl_root_cert(path);
+        }
+        if let Some(path) = &self.db_ssl_client_cert {
+            db_options = db_options.ssl_client_cert(path);
+        }
+        if let Some(path) = &self.db_ssl_client_key {
+            db_options = db_options.ssl_client_key(path);
+        }
+        db_options
+    }
+
+    /// Password for the database, if a file containing it is given.
+    fn db_password(&self) -> Option<String> {
+        let path = self.db_password_file.as_deref()?;
+        debug!(
+            "read database password from {}",
+            path.as_os_str().to_string_lossy()
+        );
+        let password = std::fs::read_to_string(path).expect("failed to read DB password file");
+        Some(password.trim().to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("0", true)]
+    #[case("0.6", true)]
+    #[case("1", true)]
+    #[case("1.5", false)]
+    #[case("-0.1", false)]
+    #[case("NaN", false)]
+    fn duplicate_threshold(#[case] threshold: &str, #[case] valid: bool) {
+        let opts = Opt::try_parse_from([
+            "dts_developer_challenge",
+            "--db-host",
+            "localhost",
+            "--duplicate-threshold",
+            threshold,
+        ]);
+        assert_eq!(opts.is_ok(), valid);
+    }
+}
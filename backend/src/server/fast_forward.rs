@@ -0,0 +1,202 @@
+//! Running scheduled jobs in tests with time passing as fast as they can
+//! run, so what they do over hours or days is seen without waiting for it.
+//!
+//! A [`FastForward`] moves a [`TestClock`] on to each time a job is due,
+//! and runs the job then, as the leading replica would. Jobs must take the
+//! time from the clock, not from the database, to be fast-forwarded.
+
+use std::sync::Arc;
+
+use crate::clock::{Clock, TestClock};
+use chrono::{DateTime, TimeDelta, Utc};
+
+use super::scheduler::Scheduler;
+
+/// Scheduler whose time passes only when told to.
+pub(crate) struct FastForward {
+    scheduler: Scheduler,
+    clock: Arc<TestClock>,
+    /// When each job is next due.
+    due: Vec<Option<DateTime<Utc>>>,
+}
+
+impl FastForward {
+    /// Take over running the jobs of `scheduler`, whose clock is `clock`,
+    /// scheduling them from the clock's time.
+    pub(crate) fn new(scheduler: Scheduler, clock: Arc<TestClock>) -> Self {
+        let due = scheduler.first_due(clock.now());
+        Self {
+            scheduler,
+            clock,
+            due,
+        }
+    }
+
+    /// Move the clock on by `by`, stopping at each time jobs are due on the
+    /// way to run them, returning the names of the jobs run in order.
+    pub(crate) async fn advance(&mut self, by: TimeDelta) -> Vec<&'static str> {
+        let until = self.clock.now() + by;
+        let mut run = Vec::new();
+        while let Some(next) = self
+            .due
+            .iter()
+            .flatten()
+            .min()
+            .copied()
+            .filter(|&next| next <= until)
+        {
+            let now = next.max(self.clock.now());
+            self.clock.set(now);
+            run.extend(self.scheduler.run_due(&mut self.due, now, true).await);
+        }
+        self.clock.set(until);
+        run
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use crate::{
+        TodoStatus, TodoTask,
+        notify::{Channel, Dispatcher, Notification, NotificationEvent, Notifier},
+        schedule::Schedule,
+        store::{NotificationStore, PgTaskStore, SessionStore, TaskStore},
+        workers::WorkerPool,
+    };
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::server::{
+        scheduler::{DispatchNotifications, PurgeSessions},
+        test_database::TestDatabase,
+    };
+
+    /// Notifier recording the events it is told of.
+    #[derive(Debug, Default)]
+    struct Recorder(Arc<Mutex<Vec<NotificationEvent>>>);
+
+    #[async_trait]
+    impl Notifier for Recorder {
+        fn channel(&self) -> Channel {
+            Channel::Email
+        }
+
+        async fn notify(&self, notification: &Notification) -> Result<(), String> {
+            self.0.lock().unwrap().push(notification.event);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn purges_expired_sessions() {
+        let Some(database) = TestDatabase::create().await else {
+            return;
+        };
+        let start = Utc::now();
+        let clock = Arc::new(TestClock::new(start));
+        let sessions = SessionStore::new(database.pool.clone());
+        sessions
+            .create("alice", start + TimeDelta::hours(2))
+            .await
+            .unwrap();
+        sessions
+            .create("bob", start + TimeDelta::days(2))
+            .await
+            .unwrap();
+        let mut scheduler = Scheduler::new(database.pool.clone(), clock.clone());
+        scheduler.add(
+            Schedule::Every(TimeDelta::hours(1)),
+            PurgeSessions {
+                sessions,
+                clock: clock.clone(),
+            },
+        );
+        let count = || async {
+            sqlx::query_scalar::<_, i64>("SELECT count(*) FROM sessions")
+                .fetch_one(&database.pool)
+                .await
+                .unwrap()
+        };
+
+        let mut time = FastForward::new(scheduler, clock.clone());
+        assert_eq!(
+            time.advance(TimeDelta::hours(1)).await,
+            ["purge-sessions"; 2]
+        );
+        assert_eq!(count().await, 2);
+        time.advance(TimeDelta::hours(2)).await;
+        assert_eq!(count().await, 1);
+        time.advance(TimeDelta::days(2)).await;
+        assert_eq!(count().await, 0);
+        assert_eq!(clock.now(), start + TimeDelta::hours(51));
+        database.drop().await;
+    }
+
+    #[tokio::test]
+    async fn reminds_of_deadlines() {
+        let Some(database) = TestDatabase::create().await else {
+            return;
+        };
+        let start = Utc::now();
+        let clock = Arc::new(TestClock::new(start));
+        let store = PgTaskStore::new(database.pool.clone());
+        let due = start + TimeDelta::hours(36);
+        let task = TodoTask::new(
+            "File order".to_owned(),
+            None,
+            TodoStatus::NotStarted,
+            &due,
+            start,
+        );
+        store.create(&task, Some("alice")).await.unwrap();
+        let done = TodoTask::new(
+            "Send bundle".to_owned(),
+            None,
+            TodoStatus::NotStarted,
+            &due,
+            start,
+        );
+        let done_id = store.create(&done, Some("alice")).await.unwrap();
+
+        let sent = Arc::default();
+        let dispatcher = Dispatcher::new(
+            NotificationStore::new(database.pool.clone()),
+            TimeDelta::days(1),
+        )
+        .with_notifier(Recorder(Arc::clone(&sent)));
+        let mut scheduler = Scheduler::new(database.pool.clone(), clock.clone());
+        scheduler.add(
+            Schedule::Every(TimeDelta::hours(1)),
+            DispatchNotifications {
+                dispatcher,
+                workers: WorkerPool::new(1, 1),
+                clock: clock.clone(),
+            },
+        );
+        let sent = || sent.lock().unwrap().clone();
+
+        // the tasks aren't due within a day until 12 hours in
+        let mut time = FastForward::new(scheduler, clock);
+        time.advance(TimeDelta::hours(11)).await;
+        assert_eq!(sent(), []);
+        time.advance(TimeDelta::hours(2)).await;
+        assert_eq!(sent(), [NotificationEvent::DueSoon; 2]);
+
+        // only the task still open is overdue, once
+        let mut done = done;
+        done.transition(TodoStatus::Complete, start + TimeDelta::hours(20));
+        store.update(done_id, &done).await.unwrap();
+        time.advance(TimeDelta::days(2)).await;
+        assert_eq!(
+            sent(),
+            [
+                NotificationEvent::DueSoon,
+                NotificationEvent::DueSoon,
+                NotificationEvent::Overdue
+            ]
+        );
+        database.drop().await;
+    }
+}
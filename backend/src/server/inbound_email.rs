@@ -0,0 +1,218 @@
+//! Creation of tasks from emails, received through an inbound email webhook.
+//!
+//! The webhook follows Mailgun's form-encoded format for routes forwarding
+//! parsed messages, whose requests are signed with an HMAC of their timestamp
+//! and token. Messages with attachments, which are posted as multipart forms,
+//! are not supported.
+
+use std::sync::Arc;
+
+use crate::{email::SenderAllowList, security::SecurityEventKind, store};
+use axum::{
+    Form,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use tracing::{debug, info};
+
+use super::{AppState, check_creation_limits, zapier};
+
+/// How far a webhook request's timestamp may be from the current time.
+const MAX_CLOCK_SKEW: TimeDelta = TimeDelta::minutes(5);
+
+/// Settings for creating tasks from emails.
+#[derive(Debug)]
+pub(crate) struct EmailIngest {
+    /// Owner of the tasks created.
+    pub owner: String,
+    /// Key which webhook requests are signed with.
+    pub signing_key: Vec<u8>,
+    /// Senders whose emails are turned into tasks.
+    pub allowed: SenderAllowList,
+    /// How long after being received tasks are due, if their email doesn't
+    /// say.
+    pub default_due: TimeDelta,
+}
+
+/// Form fields of an inbound email webhook request.
+#[derive(Deserialize)]
+pub(crate) struct InboundEmail {
+    /// Unix time at which the request was signed.
+    timestamp: String,
+    /// Random string signed along with the timestamp.
+    token: String,
+    /// Hexadecimal HMAC-SHA256 of the timestamp and token.
+    signature: String,
+    /// Address the email was sent from.
+    sender: String,
+    /// Subject of the email.
+    #[serde(default)]
+    subject: String,
+    /// Plain text body of the email.
+    #[serde(default, rename = "body-plain")]
+    body_plain: String,
+    /// Plain text body without quoted replies or signature, if the provider
+    /// could find them.
+    #[serde(default, rename = "stripped-text")]
+    stripped_text: Option<String>,
+}
+
+// contents of emails are personal data, so are kept out of the logs
+impl std::fmt::Debug for InboundEmail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InboundEmail")
+            .field("timestamp", &self.timestamp)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Create a task from an email.
+///
+/// Emails from senders who aren't allowed are refused with
+/// `406 Not Acceptable`, which webhook providers take to mean the email
+/// shouldn't be retried.
+#[tracing::instrument(skip(state))]
+pub(crate) async fn receive_email(
+    State(state): State<Arc<AppState>>,
+    Form(email): Form<InboundEmail>,
+) -> Result<String, Response> {
+    let Some(ingest) = &state.email else {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    };
+
+    let now = state.clock.now();
+    if !verify(&ingest.signing_key, &email, now) {
+        debug!("inbound email with invalid signature received");
+        state
+            .record(
+                SecurityEventKind::AuthenticationFailed,
+                None,
+                "inbound email webhook request with invalid signature",
+            )
+            .await;
+        return Err(StatusCode::UNAUTHORIZED.into_response());
+    }
+
+    if !ingest.allowed.allows(&email.sender) {
+        debug!("inbound email from sender not allowed received");
+        state
+            .record(
+                SecurityEventKind::PermissionDenied,
+                Some(&ingest.owner),
+                "email from a sender not allowed to create tasks",
+            )
+            .await;
+        return Err((StatusCode::NOT_ACCEPTABLE, "sender not allowed").into_response());
+    }
+
+    let body = email
+        .stripped_text
+        .filter(|text| !text.trim().is_empty())
+        .unwrap_or(email.body_plain);
+    let task = crate::email::to_task(
+        &email.subject,
+        &body,
+        now,
+        ingest.default_due,
+        state.due_window,
+    )
+    .map_err(|e| {
+        debug!(error = e, "inbound email can't be made into a task");
+        (StatusCode::NOT_ACCEPTABLE, e).into_response()
+    })?;
+
+    let owner = Some(ingest.owner.clone());
+    store::act_as(owner.clone(), async {
+        check_creation_limits(&state, owner.as_deref()).await?;
+        match state.store.create(&task, owner.as_deref()).await {
+            Ok(task_id) => {
+                info!(%task_id, "task created from email");
+                zapier::task_created(&state, owner.as_deref(), task_id, &task);
+                Ok(format!("{task_id}"))
+            }
+            Err(e) => Err(e.into_response()),
+        }
+    })
+    .await
+}
+
+/// Check the signature of a webhook request, and that it was made near
+/// `now`.
+fn verify(key: &[u8], email: &InboundEmail, now: DateTime<Utc>) -> bool {
+    let fresh = email.timestamp.parse::<i64>().is_ok_and(|timestamp| {
+        now.timestamp().abs_diff(timestamp) <= MAX_CLOCK_SKEW.num_seconds().unsigned_abs()
+    });
+    let Some(signature) = decode_hex(&email.signature) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(key) else {
+        return false;
+    };
+    mac.update(email.timestamp.as_bytes());
+    mac.update(email.token.as_bytes());
+    // compare in constant time, even if the request is stale
+    mac.verify_slice(&signature).is_ok() && fresh
+}
+
+/// Decode a hexadecimal string.
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write;
+
+    fn signed(key: &[u8], timestamp: i64) -> InboundEmail {
+        let timestamp = timestamp.to_string();
+        let token = "0123456789abcdef".to_string();
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+        mac.update(timestamp.as_bytes());
+        mac.update(token.as_bytes());
+        let signature = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .fold(String::new(), |mut hex, byte| {
+                let _ = write!(hex, "{byte:02x}");
+                hex
+            });
+        InboundEmail {
+            timestamp,
+            token,
+            signature,
+            sender: "boss@example.com".into(),
+            subject: "Rota".into(),
+            body_plain: String::new(),
+            stripped_text: None,
+        }
+    }
+
+    #[test]
+    fn signature() {
+        let now: DateTime<Utc> = "2026-10-16T12:00:00Z".parse().unwrap();
+        let at = now.timestamp();
+        assert!(verify(b"key", &signed(b"key", at), now));
+        assert!(!verify(b"other key", &signed(b"key", at), now));
+        assert!(!verify(b"key", &signed(b"key", at - 3600), now));
+        // the timestamp isn't trusted until the signature is checked
+        assert!(!verify(b"key", &signed(b"key", i64::MIN), now));
+        assert!(!verify(b"key", &signed(b"key", i64::MAX), now));
+
+        let mut tampered = signed(b"key", at);
+        tampered.token.push('0');
+        assert!(!verify(b"key", &tampered, now));
+    }
+}
@@ -0,0 +1,43 @@
+//! Choice of the language of responses from the `Accept-Language` header.
+
+use std::convert::Infallible;
+
+use crate::i18n::Locale;
+use axum::{
+    extract::FromRequestParts,
+    http::{HeaderValue, header, request::Parts},
+    response::{IntoResponseParts, ResponseParts},
+};
+
+/// Language which the client making a request prefers, of those supported.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Language(pub Locale);
+
+impl<S: Send + Sync> FromRequestParts<S> for Language {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let locale = parts
+            .headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .map(Locale::negotiate)
+            .unwrap_or_default();
+        Ok(Self(locale))
+    }
+}
+
+/// Mark a response as being in the chosen language, and as varying with it.
+impl IntoResponseParts for Language {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        let headers = res.headers_mut();
+        headers.insert(
+            header::CONTENT_LANGUAGE,
+            HeaderValue::from_static(self.0.tag()),
+        );
+        headers.append(header::VARY, HeaderValue::from_static("accept-language"));
+        Ok(res)
+    }
+}
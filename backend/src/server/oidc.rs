@@ -0,0 +1,479 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::egress::Egress;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use http_body_util::Full;
+use hyper::{Request, Uri, body::Bytes, header};
+use rsa::{
+    BigUint, RsaPublicKey,
+    pkcs1v15::{Signature, VerifyingKey},
+    signature::Verifier,
+};
+use serde::{Deserialize, de::DeserializeOwned};
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+
+/// Leeway allowed for differences between our clock and the provider's.
+const CLOCK_SKEW: i64 = 60;
+
+/// Shortest time between fetches of the provider's signing keys, so that
+/// tokens naming unknown keys can't make us flood the provider.
+const KEYS_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Failure to log in with the `OpenID` Connect provider.
+#[derive(Debug)]
+pub(crate) enum OidcError {
+    /// The provider couldn't be contacted.
+    Http(String),
+    /// The provider gave an unexpected or invalid response.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for OidcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(e) => write!(f, "failed to contact OpenID Connect provider: {e}"),
+            Self::Invalid(e) => write!(f, "invalid response from OpenID Connect provider: {e}"),
+        }
+    }
+}
+
+/// Subset of the provider's discovery document.
+#[derive(Deserialize, Debug)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+/// Key in the provider's JSON Web Key Set.
+#[derive(Deserialize, Debug)]
+struct Jwk {
+    kty: String,
+    kid: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+}
+
+/// Signing keys of the provider.
+#[derive(Default)]
+struct Keys {
+    /// RSA keys, with their IDs.
+    keys: Vec<(Option<String>, RsaPublicKey)>,
+    /// When the keys were last fetched, if ever.
+    fetched: Option<Instant>,
+}
+
+/// Response of the provider's token endpoint.
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// Header of an ID token.
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+    kid: Option<String>,
+}
+
+/// Audience of an ID token, which may be one client or several.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Claims of an ID token which are checked.
+#[derive(Deserialize)]
+struct IdClaims {
+    iss: String,
+    aud: Audience,
+    exp: i64,
+    nonce: Option<String>,
+    #[serde(flatten)]
+    other: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Secrets binding a login attempt to the browser which started it.
+#[derive(Debug)]
+pub(crate) struct LoginAttempt {
+    /// Value of the `state` parameter, which the provider returns unchanged.
+    pub state: String,
+    /// Value which the provider includes in the ID token.
+    pub nonce: String,
+    /// PKCE code verifier.
+    pub verifier: String,
+}
+
+impl LoginAttempt {
+    pub(crate) fn new() -> Self {
+        let random = || URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>());
+        Self {
+            state: random(),
+            nonce: random(),
+            verifier: random(),
+        }
+    }
+
+    /// Encode the attempt as a cookie value.
+    pub(crate) fn to_cookie(&self) -> String {
+        format!("{}.{}.{}", self.state, self.nonce, self.verifier)
+    }
+
+    /// Decode an attempt from a cookie value.
+    pub(crate) fn from_cookie(value: &str) -> Option<Self> {
+        let mut parts = value.split('.');
+        let attempt = Self {
+            state: parts.next()?.to_owned(),
+            nonce: parts.next()?.to_owned(),
+            verifier: parts.next()?.to_owned(),
+        };
+        parts.next().is_none().then_some(attempt)
+    }
+}
+
+/// Client of an `OpenID` Connect provider, using the authorization code flow.
+pub(crate) struct OidcClient {
+    http: Egress,
+    discovery: Discovery,
+    /// Signing keys of the provider, refreshed when an unknown key is used.
+    keys: RwLock<Keys>,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_url: String,
+    /// Claim of the ID token identifying the owner who logged in.
+    owner_claim: String,
+}
+
+impl fmt::Debug for OidcClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OidcClient")
+            .field("issuer", &self.discovery.issuer)
+            .field("client_id", &self.client_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OidcClient {
+    /// Discover the configuration of the provider at `issuer`, reached
+    /// through `http`.
+    ///
+    /// The provider must be reached with TLS, unless it is on this host.
+    pub(crate) async fn discover(
+        http: Egress,
+        issuer: &str,
+        client_id: String,
+        client_secret: Option<String>,
+        redirect_url: String,
+        owner_claim: String,
+    ) -> Result<Self, OidcError> {
+        check_https(issuer)?;
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        );
+        let discovery: Discovery = get_json(&http, &url).await?;
+        if discovery.issuer.trim_end_matches('/') != issuer.trim_end_matches('/') {
+            return Err(OidcError::Invalid("discovered issuer doesn't match"));
+        }
+        check_https(&discovery.authorization_endpoint)?;
+        check_https(&discovery.token_endpoint)?;
+        check_https(&discovery.jwks_uri)?;
+
+        let client = Self {
+            http,
+            discovery,
+            keys: RwLock::default(),
+            client_id,
+            client_secret,
+            redirect_url,
+            owner_claim,
+        };
+        client.refresh_keys().await?;
+        Ok(client)
+    }
+
+    /// Fetch the provider's current signing keys, unless they were fetched
+    /// recently.
+    async fn refresh_keys(&self) -> Result<(), OidcError> {
+        #[derive(Deserialize)]
+        struct Jwks {
+            keys: Vec<Jwk>,
+        }
+
+        // concurrent refreshes wait for this one, then find it recent
+        let mut current = self.keys.write().await;
+        if current
+            .fetched
+            .is_some_and(|fetched| fetched.elapsed() < KEYS_REFRESH_INTERVAL)
+        {
+            return Ok(());
+        }
+        // failed fetches count too, so an unreachable provider isn't retried
+        // on every login
+        current.fetched = Some(Instant::now());
+        let jwks: Jwks = get_json(&self.http, &self.discovery.jwks_uri).await?;
+        let keys = jwks
+            .keys
+            .into_iter()
+            .filter(|key| key.kty == "RSA")
+            .filter_map(|key| {
+                let decode = |v: Option<String>| URL_SAFE_NO_PAD.decode(v?).ok();
+                let (n, e) = (decode(key.n)?, decode(key.e)?);
+                let public =
+                    RsaPublicKey::new(BigUint::from_bytes_be(&n), BigUint::from_bytes_be(&e))
+                        .ok()?;
+                Some((key.kid, public))
+            })
+            .collect();
+        current.keys = keys;
+        Ok(())
+    }
+
+    /// URL to send the browser to, to log in with the provider.
+    pub(crate) fn authorization_url(
+        &self,
+        attempt: &LoginAttempt,
+    ) -> Result<String, serde_urlencoded::ser::Error> {
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(attempt.verifier.as_bytes()));
+        let query = serde_urlencoded::to_string([
+            ("response_type", "code"),
+            ("client_id", &self.client_id),
+            ("redirect_uri", &self.redirect_url),
+            ("scope", "openid profile email"),
+            ("state", &attempt.state),
+            ("nonce", &attempt.nonce),
+            ("code_challenge", &challenge),
+            ("code_challenge_method", "S256"),
+        ])?;
+        let separator = if self.discovery.authorization_endpoint.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        Ok(format!(
+            "{}{separator}{query}",
+            self.discovery.authorization_endpoint
+        ))
+    }
+
+    /// Exchange an authorization code for an ID token, returning the owner
+    /// who logged in.
+    ///
+    /// The token must not have expired by `now`.
+    pub(crate) async fn exchange(
+        &self,
+        code: &str,
+        attempt: &LoginAttempt,
+        now: DateTime<Utc>,
+    ) -> Result<String, OidcError> {
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.redirect_url),
+            ("client_id", &self.client_id),
+            ("code_verifier", &attempt.verifier),
+        ];
+        if let Some(secret) = &self.client_secret {
+            form.push(("client_secret", secret));
+        }
+        let body = serde_urlencoded::to_string(form).map_err(|e| OidcError::Http(e.to_string()))?;
+        let request = Request::post(&self.discovery.token_endpoint)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(header::ACCEPT, "application/json")
+            .body(Full::from(body))
+            .map_err(|e| OidcError::Http(e.to_string()))?;
+        let response: TokenResponse = send_json(&self.http, request).await?;
+
+        let claims = self.verify(&response.id_token).await?;
+        if claims.iss != self.discovery.issuer {
+            return Err(OidcError::Invalid("ID token has the wrong issuer"));
+        }
+        let audience_ok = match &claims.aud {
+            Audience::One(aud) => *aud == self.client_id,
+            Audience::Many(auds) => auds.contains(&self.client_id),
+        };
+        if !audience_ok {
+            return Err(OidcError::Invalid("ID token has the wrong audience"));
+        }
+        if claims.exp + CLOCK_SKEW < now.timestamp() {
+            return Err(OidcError::Invalid("ID token has expired"));
+        }
+        if claims.nonce.as_deref() != Some(&attempt.nonce) {
+            return Err(OidcError::Invalid("ID token has the wrong nonce"));
+        }
+
+        match claims.other.get(&self.owner_claim) {
+            Some(serde_json::Value::String(owner)) if !owner.is_empty() => Ok(owner.clone()),
+            _ => Err(OidcError::Invalid("ID token lacks the owner claim")),
+        }
+    }
+
+    /// Check the signature of an ID token, returning its claims.
+    async fn verify(&self, token: &str) -> Result<IdClaims, OidcError> {
+        let invalid = || OidcError::Invalid("malformed ID token");
+        let (signing_input, signature) = token.rsplit_once('.').ok_or_else(invalid)?;
+        let (header, claims) = signing_input.split_once('.').ok_or_else(invalid)?;
+        let decode = |part: &str| URL_SAFE_NO_PAD.decode(part).map_err(|_| invalid());
+        let header: JwtHeader = serde_json::from_slice(&decode(header)?).map_err(|_| invalid())?;
+        if header.alg != "RS256" {
+            return Err(OidcError::Invalid("ID token isn't signed with RS256"));
+        }
+        let signature =
+            Signature::try_from(decode(signature)?.as_slice()).map_err(|_| invalid())?;
+
+        for refreshed in [false, true] {
+            if refreshed {
+                self.refresh_keys().await?;
+            }
+            let keys = self.keys.read().await;
+            let candidates = keys
+                .keys
+                .iter()
+                .filter(|(kid, _)| header.kid.is_none() || *kid == header.kid);
+            for (_, key) in candidates {
+                let verifier = VerifyingKey::<Sha256>::new(key.clone());
+                if verifier
+                    .verify(signing_input.as_bytes(), &signature)
+                    .is_ok()
+                {
+                    return serde_json::from_slice(&decode(claims)?).map_err(|_| invalid());
+                }
+            }
+        }
+        Err(OidcError::Invalid("ID token signature doesn't match"))
+    }
+}
+
+/// Check that `url` uses TLS, or is on this host.
+///
+/// Whoever can change the provider's responses can log in as anyone.
+fn check_https(url: &str) -> Result<(), OidcError> {
+    let parsed: Uri = url
+        .parse()
+        .map_err(|_| OidcError::Invalid("malformed provider URL"))?;
+    match (parsed.scheme_str(), parsed.host()) {
+        (Some("https"), _) | (Some("http"), Some("localhost" | "127.0.0.1" | "[::1]")) => Ok(()),
+        _ => Err(OidcError::Invalid("provider URLs must use https")),
+    }
+}
+
+/// Fetch a JSON document.
+async fn get_json<T: DeserializeOwned>(http: &Egress, url: &str) -> Result<T, OidcError> {
+    let request = Request::get(url)
+        .header(header::ACCEPT, "application/json")
+        .body(Full::default())
+        .map_err(|e| OidcError::Http(e.to_string()))?;
+    send_json(http, request).await
+}
+
+/// Send a request, parsing a successful JSON response.
+async fn send_json<T: DeserializeOwned>(
+    http: &Egress,
+    request: Request<Full<Bytes>>,
+) -> Result<T, OidcError> {
+    let response = http
+        .request(request)
+        .await
+        .map_err(|e| OidcError::Http(e.to_string()))?;
+    if !response.status().is_success() {
+        return Err(OidcError::Http(format!(
+            "provider responded with {}",
+            response.status()
+        )));
+    }
+    serde_json::from_slice(response.body()).map_err(|_| OidcError::Invalid("malformed JSON"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use rstest::rstest;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    #[rstest]
+    #[case("https://login.example/", true)]
+    #[case("http://login.example/", false)]
+    #[case("http://localhost:8080/realms/tasks", true)]
+    #[case("http://127.0.0.1/keys", true)]
+    #[case("ftp://login.example/", false)]
+    fn requires_https(#[case] url: &str, #[case] allowed: bool) {
+        assert_eq!(check_https(url).is_ok(), allowed);
+    }
+
+    #[tokio::test]
+    async fn limits_key_refreshes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let jwks_uri = format!("http://{}/keys", listener.local_addr().unwrap());
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let served = Arc::clone(&fetches);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let _ = stream.read(&mut [0; 1024]).await.unwrap();
+                served.fetch_add(1, Ordering::SeqCst);
+                let body = r#"{"keys":[]}"#;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-length: {}\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        let client = OidcClient {
+            http: Egress::default(),
+            discovery: Discovery {
+                issuer: "https://login.example".to_owned(),
+                authorization_endpoint: "https://login.example/authorize".to_owned(),
+                token_endpoint: "https://login.example/token".to_owned(),
+                jwks_uri,
+            },
+            keys: RwLock::default(),
+            client_id: "tasks".to_owned(),
+            client_secret: None,
+            redirect_url: "https://tasks.example/auth/callback".to_owned(),
+            owner_claim: "sub".to_owned(),
+        };
+        client.refresh_keys().await.unwrap();
+
+        // signed with a key the provider doesn't have
+        let encode = |part: &str| URL_SAFE_NO_PAD.encode(part);
+        let token = format!(
+            "{}.{}.{}",
+            encode(r#"{"alg":"RS256","kid":"forged"}"#),
+            encode(r#"{"sub":"admin"}"#),
+            encode("signature"),
+        );
+        for _ in 0..3 {
+            assert!(client.verify(&token).await.is_err());
+        }
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn login_attempt_cookie() {
+        let attempt = LoginAttempt::new();
+        let decoded = LoginAttempt::from_cookie(&attempt.to_cookie()).unwrap();
+        assert_eq!(decoded.state, attempt.state);
+        assert_eq!(decoded.nonce, attempt.nonce);
+        assert_eq!(decoded.verifier, attempt.verifier);
+
+        assert!(LoginAttempt::from_cookie("a.b").is_none());
+        assert!(LoginAttempt::from_cookie("a.b.c.d").is_none());
+    }
+}
@@ -0,0 +1,252 @@
+//! Negotiation of Protocol Buffers bodies on the task endpoints, as an
+//! alternative to JSON.
+
+use std::{convert::Infallible, sync::Arc};
+
+use crate::{TodoTaskUnchecked, i18n::Locale, json_schema, proto};
+use axum::{
+    Json,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{HeaderMap, HeaderValue, StatusCode, header, request::Parts},
+    response::{IntoResponse, Response},
+};
+use prost::Message;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tracing::{debug, info};
+
+use super::{
+    AppState,
+    cli::UnknownFields,
+    language::Language,
+    validated::{FieldError, reject},
+};
+
+/// Media type of Protocol Buffers bodies.
+pub(crate) const PROTOBUF: &str = "application/x-protobuf";
+
+/// Format of response bodies, chosen with the `Accept` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BodyFormat {
+    /// JSON, the default.
+    Json,
+    /// Protocol Buffers.
+    Protobuf,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for BodyFormat {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let accept = parts
+            .headers
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        Ok(Self::negotiate(accept))
+    }
+}
+
+impl BodyFormat {
+    /// Choose the format preferred by an `Accept` header value.
+    ///
+    /// Protocol Buffers are only chosen if preferred over JSON, where JSON's
+    /// quality is that of `application/json` if listed, or else of the best
+    /// matching wildcard.
+    fn negotiate(accept: &str) -> Self {
+        let mut protobuf = 0.0;
+        let mut json = None;
+        let mut wildcard: f32 = 0.0;
+        for range in accept.split(',') {
+            let mut params = range.split(';');
+            let media_type = params
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase();
+            let Some(quality) = params
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())
+            else {
+                continue;
+            };
+            match media_type.as_str() {
+                PROTOBUF => protobuf = quality,
+                "application/json" => json = Some(quality),
+                "application/*" | "*/*" => wildcard = wildcard.max(quality),
+                _ => {}
+            }
+        }
+        if protobuf > json.unwrap_or(wildcard) {
+            Self::Protobuf
+        } else {
+            Self::Json
+        }
+    }
+
+    /// Respond with `body` in this format, converting it to a message with
+    /// `to_message` for Protocol Buffers.
+    pub(crate) fn respond<T: Serialize, M: Message>(
+        self,
+        body: T,
+        to_message: impl FnOnce(&T) -> M,
+    ) -> Response {
+        let mut response = match self {
+            Self::Json => Json(body).into_response(),
+            Self::Protobuf => (
+                [(header::CONTENT_TYPE, PROTOBUF)],
+                to_message(&body).encode_to_vec(),
+            )
+                .into_response(),
+        };
+        response
+            .headers_mut()
+            .append(header::VARY, HeaderValue::from_static("accept"));
+        response
+    }
+}
+
+/// Task in a request body, as JSON or as a `dts.tasks.v1.Task` message
+/// depending on the `Content-Type` header.
+///
+/// Fields of JSON tasks which tasks don't have are dealt with as configured
+/// by [`UnknownFields`], and JSON tasks are checked against their schema if
+/// configured to be. Protocol Buffers messages are unaffected, as their
+/// unknown fields are always ignored.
+#[derive(Debug)]
+pub(crate) struct TaskBody(pub TodoTaskUnchecked);
+
+impl FromRequest<Arc<AppState>> for TaskBody {
+    type Rejection = Response;
+
+    async fn from_request(
+        request: Request,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let locale = request
+            .headers()
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .map(Locale::negotiate)
+            .unwrap_or_default();
+        if !is_protobuf(request.headers()) {
+            let Json(object) = Json::<Map<String, Value>>::from_request(request, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            let unknown: Vec<_> = TodoTaskUnchecked::unknown_fields(&object).collect();
+            match (unknown.first(), state.unknown_fields) {
+                (None, _) => {}
+                (Some(field), UnknownFields::Strict) => {
+                    debug!(field, "task with unknown field received");
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        format!(
+                            "unknown field `{field}`, expected one of `{}`",
+                            TodoTaskUnchecked::FIELDS.join("`, `")
+                        ),
+                    )
+                        .into_response());
+                }
+                (Some(_), UnknownFields::Lenient) => {
+                    info!(fields = ?unknown, "ignoring unknown fields of task");
+                }
+            }
+            let object = Value::Object(object);
+            if let Some(schema) = &state.task_schema {
+                let violations = json_schema::validate(schema, &object);
+                if !violations.is_empty() {
+                    debug!(?violations, "task failing its schema received");
+                    let errors = violations
+                        .into_iter()
+                        .map(|violation| FieldError {
+                            field: pointer_field(&violation.path),
+                            message: violation.message,
+                        })
+                        .collect();
+                    return Err(reject(locale, errors));
+                }
+            }
+            return serde_path_to_error::deserialize(object)
+                .map(Self)
+                .map_err(|e| {
+                    let path = e.path().to_string();
+                    let error = FieldError {
+                        field: Some(path).filter(|path| path != "."),
+                        message: e.into_inner().to_string(),
+                    };
+                    reject(locale, vec![error])
+                });
+        }
+
+        let body = axum::body::Bytes::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let message = proto::Task::decode(body).map_err(|e| {
+            debug!(error = format!("{e}"), "malformed protobuf task received");
+            (StatusCode::BAD_REQUEST, format!("{e}")).into_response()
+        })?;
+        message.try_into().map(Self).map_err(|e: &str| {
+            debug!(error = e, "invalid protobuf task received");
+            (
+                StatusCode::BAD_REQUEST,
+                Language(locale),
+                locale.translate(e),
+            )
+                .into_response()
+        })
+    }
+}
+
+/// Path to the field of a body at the JSON pointer `pointer`, in the form
+/// paths are given by serde, or `None` for the whole body.
+fn pointer_field(pointer: &str) -> Option<String> {
+    let mut field = String::new();
+    for segment in pointer.split('/').skip(1) {
+        if segment.parse::<usize>().is_ok() {
+            field.push('[');
+            field.push_str(segment);
+            field.push(']');
+        } else {
+            if !field.is_empty() {
+                field.push('.');
+            }
+            field.push_str(&segment.replace("~1", "/").replace("~0", "~"));
+        }
+    }
+    Some(field).filter(|field| !field.is_empty())
+}
+
+/// Check whether a request's body is Protocol Buffers.
+fn is_protobuf(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case(PROTOBUF))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("", BodyFormat::Json)]
+    #[case("application/x-protobuf", BodyFormat::Protobuf)]
+    #[case("application/x-protobuf, */*;q=0.1", BodyFormat::Protobuf)]
+    #[case("application/x-protobuf, application/json", BodyFormat::Json)]
+    #[case("application/json;q=0.5, application/x-protobuf", BodyFormat::Protobuf)]
+    #[case("application/x-protobuf;q=0.5, */*", BodyFormat::Json)]
+    fn negotiate(#[case] accept: &str, #[case] expected: BodyFormat) {
+        assert_eq!(BodyFormat::negotiate(accept), expected);
+    }
+
+    #[rstest]
+    #[case("", None)]
+    #[case("/title", Some("title"))]
+    #[case("/tags/0", Some("tags[0]"))]
+    #[case("/a~1b/c", Some("a/b.c"))]
+    fn pointer_field(#[case] pointer: &str, #[case] expected: Option<&str>) {
+        assert_eq!(super::pointer_field(pointer).as_deref(), expected);
+    }
+}
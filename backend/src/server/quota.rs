@@ -0,0 +1,95 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    num::NonZeroU32,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Sliding-window limit on how often each owner may perform an action.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    /// Number of actions allowed per window.
+    limit: NonZeroU32,
+    window: Duration,
+    /// Times of each owner's actions within the current window, oldest first.
+    recent: Mutex<HashMap<Option<String>, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: NonZeroU32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            recent: Mutex::default(),
+        }
+    }
+
+    /// Record an action by `owner` at `now`, if it is within the limit.
+    ///
+    /// Otherwise returns how long until `owner` may act again.
+    pub(crate) fn check(&self, owner: Option<&str>, now: Instant) -> Result<(), Duration> {
+        let mut recent = self.recent.lock().unwrap_or_else(PoisonError::into_inner);
+        // forget owners with no actions in the window, so the map stays small
+        recent.retain(|_, times| {
+            times
+                .back()
+                .is_some_and(|t| now.duration_since(*t) < self.window)
+        });
+
+        let times = recent.entry(owner.map(str::to_owned)).or_default();
+        while times
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= self.window)
+        {
+            times.pop_front();
+        }
+        if let Some(oldest) = times
+            .front()
+            .filter(|_| times.len() >= self.limit.get() as usize)
+        {
+            return Err(self.window.saturating_sub(now.duration_since(*oldest)));
+        }
+        times.push_back(now);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_each_owner() {
+        let limiter = RateLimiter::new(NonZeroU32::new(2).unwrap(), Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert_eq!(limiter.check(Some("a"), start), Ok(()));
+        assert_eq!(
+            limiter.check(Some("a"), start + Duration::from_secs(10)),
+            Ok(())
+        );
+        assert_eq!(
+            limiter.check(Some("a"), start + Duration::from_secs(20)),
+            Err(Duration::from_secs(40))
+        );
+        assert_eq!(
+            limiter.check(Some("b"), start + Duration::from_secs(20)),
+            Ok(())
+        );
+        assert_eq!(limiter.check(None, start + Duration::from_secs(20)), Ok(()));
+    }
+
+    #[test]
+    fn window_slides() {
+        let limiter = RateLimiter::new(NonZeroU32::new(1).unwrap(), Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert_eq!(limiter.check(None, start), Ok(()));
+        assert!(
+            limiter
+                .check(None, start + Duration::from_secs(59))
+                .is_err()
+        );
+        assert_eq!(limiter.check(None, start + Duration::from_secs(60)), Ok(()));
+    }
+}
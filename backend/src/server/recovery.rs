@@ -0,0 +1,118 @@
+//! Recovery from panics while handling requests.
+//!
+//! Each request is given an ID, which is echoed in the `X-Request-Id`
+//! response header. If handling it panics, the panic is logged with that ID
+//! and the client is sent a `500 Internal Server Error` problem report
+//! (RFC 9457) quoting it, so the two can be matched up.
+
+use std::any::Any;
+
+use axum::{
+    Json,
+    extract::Request,
+    http::{HeaderName, HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use tracing::error;
+use uuid::Uuid;
+
+/// Header carrying the ID of a request.
+pub(crate) static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest request ID accepted from a client.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+tokio::task_local! {
+    /// ID of the request being handled by the current task.
+    static REQUEST_ID: String;
+}
+
+/// Give the request an ID, which is the client's if it sent a reasonable
+/// one, and handle the rest of it with that ID known.
+pub(crate) async fn request_id(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LENGTH
+                && id.bytes().all(|b| b.is_ascii_graphic())
+        })
+        .map_or_else(|| Uuid::new_v4().to_string(), str::to_owned);
+
+    let mut response = REQUEST_ID.scope(id.clone(), next.run(request)).await;
+    if let Ok(value) = HeaderValue::try_from(id) {
+        response
+            .headers_mut()
+            .insert(REQUEST_ID_HEADER.clone(), value);
+    }
+    response
+}
+
+/// Problem report sent when handling a request panicked.
+#[derive(Serialize, Debug)]
+struct Problem {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: &'static str,
+    /// ID of the request, to find it in the logs.
+    request_id: Option<String>,
+}
+
+/// Log a panic while handling a request, and respond to it with a problem
+/// report.
+// the panic is passed by value because `CatchPanicLayer` requires it
+#[allow(clippy::needless_pass_by_value)]
+pub(crate) fn panicked(panic: Box<dyn Any + Send + 'static>) -> Response {
+    let message = panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic");
+    let request_id = REQUEST_ID.try_with(Clone::clone).ok();
+    error!(request_id, panic = message, "request handler panicked");
+
+    let problem = Problem {
+        kind: "about:blank",
+        title: "Internal Server Error",
+        status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+        detail: "The server failed to handle the request.",
+        request_id,
+    };
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        [(header::CONTENT_TYPE, "application/problem+json")],
+        Json(problem),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+
+    #[tokio::test]
+    async fn problem_report() {
+        let response = REQUEST_ID
+            .scope("abc".to_owned(), async {
+                panicked(Box::new("oops".to_owned()))
+            })
+            .await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/problem+json"
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["status"], 500);
+        assert_eq!(problem["request_id"], "abc");
+    }
+}
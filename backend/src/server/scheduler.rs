@@ -0,0 +1,390 @@
+//! Scheduler of background jobs.
+//!
+//! Every replica runs a scheduler, but only the one holding a Postgres
+//! advisory lock runs jobs, so each runs once however many replicas there
+//! are. The lock is held by a connection kept open for as long as the replica
+//! leads, so if the replica dies another takes over at its next tick.
+
+use std::{fmt, sync::Arc};
+
+use crate::{
+    calendar::WorkCalendar,
+    clock::Clock,
+    digest::{Digest, DigestPeriod, DigestTemplate},
+    notify::Dispatcher,
+    schedule::Schedule,
+    smtp::{Email, Mailer},
+    store::{PgTaskStore, SessionStore, UserStore},
+    thumbnails,
+    workers::WorkerPool,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool};
+use tracing::{debug, error, info, warn};
+
+/// Key of the advisory lock held by the leading replica.
+const LEADER_LOCK: i64 = 0x6474_735f_6a6f_6273;
+
+/// Job run in the background on a schedule.
+#[async_trait]
+pub(crate) trait Job: fmt::Debug + Send + Sync {
+    /// Name of the job, as logged.
+    fn name(&self) -> &'static str;
+
+    /// Run the job once.
+    async fn run(&self) -> Result<(), String>;
+}
+
+/// Scheduler running jobs on the replica which leads.
+#[derive(Debug)]
+pub(crate) struct Scheduler {
+    /// Database whose advisory lock elects the leader.
+    pool: PgPool,
+    /// Clock telling when jobs are due.
+    clock: Arc<dyn Clock>,
+    jobs: Vec<(Schedule, Box<dyn Job>)>,
+}
+
+impl Scheduler {
+    pub(crate) fn new(pool: PgPool, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            pool,
+            clock,
+            jobs: Vec::new(),
+        }
+    }
+
+    /// Run `job` on `schedule`.
+    pub(crate) fn add(&mut self, schedule: Schedule, job: impl Job + 'static) {
+        info!(job = job.name(), %schedule, "job scheduled");
+        self.jobs.push((schedule, Box::new(job)));
+    }
+
+    /// Run the jobs when they are due, in the background.
+    pub(crate) fn spawn(self) {
+        if !self.jobs.is_empty() {
+            tokio::spawn(self.run());
+        }
+    }
+
+    async fn run(self) {
+        let mut leadership = Leadership::default();
+        let mut due = self.first_due(self.clock.now());
+
+        while let Some(next) = due.iter().flatten().min().copied() {
+            let wait = (next - self.clock.now()).to_std().unwrap_or_default();
+            tokio::time::sleep(wait).await;
+
+            let leading = leadership.check(&self.pool).await;
+            self.run_due(&mut due, self.clock.now(), leading).await;
+        }
+    }
+
+    /// When each job is first due, if they are scheduled from `now`.
+    pub(crate) fn first_due(&self, now: DateTime<Utc>) -> Vec<Option<DateTime<Utc>>> {
+        self.jobs
+            .iter()
+            .map(|(schedule, _)| schedule.first(now))
+            .collect()
+    }
+
+    /// Run the jobs which are `due` by `now`, if `leading`, and work out
+    /// when they are next due, returning the names of those run.
+    pub(crate) async fn run_due(
+        &self,
+        due: &mut [Option<DateTime<Utc>>],
+        now: DateTime<Utc>,
+        leading: bool,
+    ) -> Vec<&'static str> {
+        let mut run = Vec::new();
+        for ((schedule, job), due) in self.jobs.iter().zip(due) {
+            if due.is_none_or(|due| due > now) {
+                continue;
+            }
+            if leading {
+                debug!(job = job.name(), "running job");
+                if let Err(e) = job.run().await {
+                    error!(job = job.name(), error = e, "job failed");
+                }
+                run.push(job.name());
+            }
+            *due = schedule.next_after(now);
+        }
+        run
+    }
+}
+
+/// Connection holding the leader's advisory lock, if this replica leads.
+#[derive(Debug, Default)]
+struct Leadership {
+    connection: Option<PgConnection>,
+}
+
+impl Leadership {
+    /// Check whether this replica leads, trying to take the lead if not.
+    async fn check(&mut self, pool: &PgPool) -> bool {
+        if let Some(connection) = &mut self.connection {
+            if sqlx::query("SELECT 1").execute(connection).await.is_ok() {
+                return true;
+            }
+            // the lock was released with the connection
+            warn!("lost connection holding scheduler leadership");
+            self.connection = None;
+        }
+
+        match Self::acquire(pool).await {
+            Ok(Some(connection)) => {
+                info!("leading the scheduling of jobs");
+                self.connection = Some(connection);
+                true
+            }
+            Ok(None) => false,
+            Err(e) => {
+                error!(
+                    error = format!("{e}"),
+                    "database error trying to lead the scheduling of jobs"
+                );
+                false
+            }
+        }
+    }
+
+    /// Take the leader's lock, if no other replica holds it, returning the
+    /// connection holding it.
+    async fn acquire(pool: &PgPool) -> Result<Option<PgConnection>, sqlx::Error> {
+        let mut connection = pool.acquire().await?;
+        let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+            .bind(LEADER_LOCK)
+            .fetch_one(&mut *connection)
+            .await?;
+        // the lock lasts as long as the connection, so it is taken out of the
+        // pool rather than being handed to requests
+        Ok(locked.then(|| connection.detach()))
+    }
+}
+
+/// Job deleting expired login sessions.
+#[derive(Debug)]
+pub(crate) struct PurgeSessions {
+    pub sessions: SessionStore,
+    /// Clock telling which sessions have expired.
+    pub clock: Arc<dyn Clock>,
+}
+
+#[async_trait]
+impl Job for PurgeSessions {
+    fn name(&self) -> &'static str {
+        "purge-sessions"
+    }
+
+    async fn run(&self) -> Result<(), String> {
+        let purged = self
+            .sessions
+            .purge_expired(self.clock.now())
+            .await
+            .map_err(|e| e.to_string())?;
+        info!(purged, "purged expired sessions");
+        Ok(())
+    }
+}
+
+/// Job recomputing the statistics served by `/stats`.
+#[derive(Debug)]
+pub(crate) struct RefreshStats(pub PgTaskStore);
+
+#[async_trait]
+impl Job for RefreshStats {
+    fn name(&self) -> &'static str {
+        "refresh-stats"
+    }
+
+    async fn run(&self) -> Result<(), String> {
+        let refreshed_at = self.0.refresh_stats().await.map_err(|e| e.to_string())?;
+        debug!(%refreshed_at, "refreshed statistics");
+        Ok(())
+    }
+}
+
+/// Job generating thumbnails of the image attachments which have none.
+#[derive(Debug)]
+pub(crate) struct GenerateThumbnails {
+    pub store: PgTaskStore,
+    /// Workers the thumbnails are generated by.
+    pub workers: WorkerPool,
+}
+
+impl GenerateThumbnails {
+    /// Number of images fetched at a time, bounding memory use.
+    const BATCH_SIZE: i64 = 10;
+}
+
+#[async_trait]
+impl Job for GenerateThumbnails {
+    fn name(&self) -> &'static str {
+        "generate-thumbnails"
+    }
+
+    async fn run(&self) -> Result<(), String> {
+        let (mut generated, mut failed) = (0, 0);
+        loop {
+            let pending = self
+                .store
+                .pending_thumbnails(Self::BATCH_SIZE)
+                .await
+                .map_err(|e| e.to_string())?;
+            if pending.is_empty() {
+                break;
+            }
+            for (attachment_id, content) in pending {
+                // decoding is CPU-bound, so is kept off the async workers
+                let thumbnail = self
+                    .workers
+                    .run(tokio::task::spawn_blocking(move || {
+                        thumbnails::generate(&content)
+                    }))
+                    .await
+                    .map_err(|e| e.to_string())?;
+                if let Err(e) = &thumbnail {
+                    debug!(%attachment_id, error = e, "failed to generate thumbnail");
+                    failed += 1;
+                } else {
+                    generated += 1;
+                }
+                self.store
+                    .save_thumbnail(attachment_id, thumbnail.as_deref().map_err(String::as_str))
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        if generated > 0 || failed > 0 {
+            info!(generated, failed, "generated thumbnails");
+        }
+        Ok(())
+    }
+}
+
+/// Job emailing digests of overdue, due-soon and recently completed tasks.
+#[derive(Debug)]
+pub(crate) struct SendDigests {
+    /// Store of the tasks.
+    pub store: PgTaskStore,
+    /// Store of the users sent digests.
+    pub users: UserStore,
+    pub mailer: Mailer,
+    pub template: DigestTemplate,
+    pub period: DigestPeriod,
+    /// Address to send one digest of every task to, instead of sending each
+    /// user a digest of their own tasks.
+    pub to: Option<String>,
+    /// Working hours, if only they count towards tasks being overdue.
+    pub overdue_hours: Option<WorkCalendar>,
+    /// Clock telling which tasks are overdue and due soon.
+    pub clock: Arc<dyn Clock>,
+}
+
+impl SendDigests {
+    /// Send `name` at `to` a digest of `user`'s tasks, or of every task if
+    /// `None`, returning whether there were any tasks to send.
+    async fn send(
+        &self,
+        to: &str,
+        name: &str,
+        user: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<bool, String> {
+        let length = self.period.length();
+        let tasks = self
+            .store
+            .digest_tasks(user, now - length, now + length)
+            .await
+            .map_err(|e| e.to_string())?;
+        let digest = Digest::new(tasks, self.period, self.overdue_hours.as_ref(), now);
+        if digest.is_empty() {
+            return Ok(false);
+        }
+        let email = Email {
+            to: to.to_owned(),
+            subject: format!("Your {} task digest", self.period.name()),
+            body: self.template.render(name, self.period, &digest),
+        };
+        self.mailer.send(&email).await?;
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl Job for SendDigests {
+    fn name(&self) -> &'static str {
+        "send-digests"
+    }
+
+    async fn run(&self) -> Result<(), String> {
+        let now = self.clock.now();
+        if let Some(to) = &self.to {
+            let sent = self.send(to, "everyone", None, now).await?;
+            info!(sent, "sent digest of every task");
+            return Ok(());
+        }
+
+        let users = self.users.list().await.map_err(|e| e.to_string())?;
+        let (mut sent, mut failed) = (0, 0);
+        for user in users
+            .iter()
+            .filter(|user| user.digest && user.deactivated_at.is_none())
+        {
+            let Some(address) = &user.email else {
+                continue;
+            };
+            match self
+                .send(address, &user.display_name, Some(&user.id), now)
+                .await
+            {
+                Ok(true) => sent += 1,
+                Ok(false) => {}
+                Err(e) => {
+                    // one bad address shouldn't stop everyone else's digests
+                    warn!(user = user.id, error = e, "failed to send digest");
+                    failed += 1;
+                }
+            }
+        }
+        info!(sent, failed, "sent digests");
+        if failed > 0 {
+            return Err(format!("{failed} digests couldn't be sent"));
+        }
+        Ok(())
+    }
+}
+
+/// Job sending pending notifications over the channels users chose.
+#[derive(Debug)]
+pub(crate) struct DispatchNotifications {
+    pub dispatcher: Dispatcher,
+    /// Workers the notifications are sent by.
+    pub workers: WorkerPool,
+    /// Clock telling which tasks are overdue and due soon.
+    pub clock: Arc<dyn Clock>,
+}
+
+#[async_trait]
+impl Job for DispatchNotifications {
+    fn name(&self) -> &'static str {
+        "dispatch-notifications"
+    }
+
+    async fn run(&self) -> Result<(), String> {
+        let report = self
+            .workers
+            .run(self.dispatcher.dispatch(self.clock.now()))
+            .await
+            .map_err(|e| e.to_string())?;
+        if report.sent > 0 || report.failed > 0 {
+            info!(report.sent, report.failed, "dispatched notifications");
+        }
+        if report.failed > 0 {
+            return Err(format!("{} notifications couldn't be sent", report.failed));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+//! Migration of the database schema at startup, safely when several replicas
+//! start at once.
+//!
+//! Replicas take turns holding an advisory lock while they check the schema
+//! and migrate it, and refuse to serve a schema other than the one they were
+//! built for: one with migrations they don't know, applied by a newer
+//! release, or one missing migrations they expect if they were told not to
+//! apply them.
+
+use std::fmt;
+
+use sqlx::{
+    Connection, PgConnection, PgPool,
+    migrate::{MigrateError, Migrator},
+};
+use tracing::info;
+
+/// Migrations of the schema the application is built for.
+pub(crate) static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Key of the advisory lock held while checking and migrating the schema.
+const MIGRATION_LOCK: i64 = 0x6474_735f_6d69_6772;
+
+/// Failure to prepare the schema for serving.
+#[derive(Debug)]
+pub enum SchemaError {
+    /// The database couldn't be queried.
+    Database(sqlx::Error),
+    /// The migrations failed.
+    Migrate(MigrateError),
+    /// The schema has migrations applied which this release doesn't know.
+    TooNew {
+        /// Latest migration applied to the schema.
+        database: i64,
+        /// Latest migration this release knows.
+        known: i64,
+    },
+    /// The schema lacks migrations which this release needs, and migrations
+    /// weren't to be applied.
+    TooOld {
+        /// Latest migration applied to the schema, if any are.
+        database: Option<i64>,
+        /// Latest migration this release needs.
+        known: i64,
+    },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Database(e) => write!(f, "database error checking schema: {e}"),
+            Self::Migrate(e) => write!(f, "migrations failed: {e}"),
+            Self::TooNew { database, known } => write!(
+                f,
+                "database schema is at migration {database}, newer than the latest known \
+                migration {known}; refusing to serve it"
+            ),
+            Self::TooOld { database, known } => write!(
+                f,
+                "database schema is at migration {}, older than the latest known migration \
+                {known}, and migrations are skipped; refusing to serve it",
+                database.map_or_else(|| "none".to_owned(), |version| version.to_string())
+            ),
+        }
+    }
+}
+
+impl From<sqlx::Error> for SchemaError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Database(e)
+    }
+}
+
+/// Check the schema of the database behind `pool` is one this release can
+/// serve, first applying any migrations it lacks if `migrate`.
+///
+/// # Errors
+///
+/// Returns an error if the schema can't be checked or migrated, or isn't
+/// one this release can serve.
+pub async fn prepare(pool: &PgPool, migrate: bool) -> Result<(), SchemaError> {
+    // the lock is released by closing the connection, however preparing the
+    // schema ends, so it is taken out of the pool
+    let mut connection = pool.acquire().await?.detach();
+    // migrations take as long as they take
+    sqlx::query("SET statement_timeout = 0")
+        .execute(&mut connection)
+        .await?;
+    sqlx::query("SELECT pg_advisory_lock($1)")
+        .bind(MIGRATION_LOCK)
+        .execute(&mut connection)
+        .await?;
+    let result = check_and_migrate(&mut connection, migrate).await;
+    connection.close().await?;
+    result
+}
+
+async fn check_and_migrate(
+    connection: &mut PgConnection,
+    migrate: bool,
+) -> Result<(), SchemaError> {
+    let known = MIGRATOR
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or_default();
+    let database = applied_version(connection).await?;
+    if let Some(database) = database.filter(|version| *version > known) {
+        return Err(SchemaError::TooNew { database, known });
+    }
+
+    if migrate {
+        MIGRATOR
+            .run(&mut *connection)
+            .await
+            .map_err(SchemaError::Migrate)?;
+        info!("database migrations complete");
+    } else if database < Some(known) {
+        return Err(SchemaError::TooOld { database, known });
+    } else {
+        info!("skipping database migrations");
+    }
+    Ok(())
+}
+
+/// Version of the latest migration applied to the database, if any.
+async fn applied_version(connection: &mut PgConnection) -> Result<Option<i64>, sqlx::Error> {
+    let table: Option<String> = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations')::text")
+        .fetch_one(&mut *connection)
+        .await?;
+    if table.is_none() {
+        return Ok(None);
+    }
+    sqlx::query_scalar("SELECT max(version) FROM _sqlx_migrations WHERE success")
+        .fetch_one(connection)
+        .await
+}
@@ -0,0 +1,224 @@
+//! Background sync of tasks from other to-do applications.
+
+use std::{fmt, sync::Arc};
+
+use async_trait::async_trait;
+use http_body_util::Full;
+use hyper::{Request, body::Bytes, header};
+use serde::Deserialize;
+use tracing::{debug, info};
+
+use crate::{
+    DueWindow, StoreError,
+    clock::Clock,
+    egress::Egress,
+    store::{TaskStore, act_as},
+    sync::{ConflictRule, SyncOutcome, SyncProvider},
+};
+
+use super::scheduler::Job;
+
+/// Failure to sync tasks from a provider.
+#[derive(Debug)]
+pub(crate) enum SyncError {
+    /// The provider couldn't be contacted.
+    Http(String),
+    /// The provider gave an unexpected or invalid response.
+    Invalid(String),
+    /// The synced tasks couldn't be stored.
+    Database(StoreError),
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(e) => write!(f, "failed to contact sync provider: {e}"),
+            Self::Invalid(e) => write!(f, "invalid response from sync provider: {e}"),
+            Self::Database(e) => write!(f, "failed to store synced tasks: {e}"),
+        }
+    }
+}
+
+impl From<StoreError> for SyncError {
+    fn from(e: StoreError) -> Self {
+        Self::Database(e)
+    }
+}
+
+/// OAuth client credentials and refresh token granted by the owner, as
+/// stored in the credentials file.
+#[derive(Deserialize)]
+pub(crate) struct Credentials {
+    client_id: String,
+    client_secret: Option<String>,
+    refresh_token: String,
+    /// Scopes to request access tokens for, which Microsoft requires.
+    scope: Option<String>,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("client_id", &self.client_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Response of the provider's token endpoint.
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// Number of remote tasks with each outcome in one sync.
+#[derive(Debug, Default)]
+struct SyncCounts {
+    created: u32,
+    updated: u32,
+    unchanged: u32,
+    skipped: u32,
+    invalid: u32,
+}
+
+/// Worker which periodically pulls a task list from a provider into the
+/// tasks of one owner.
+#[derive(Debug)]
+pub(crate) struct SyncWorker {
+    /// Storage to sync tasks into.
+    pub store: Arc<dyn TaskStore>,
+    /// Client reaching the provider.
+    pub http: Egress,
+    /// Application to sync tasks from.
+    pub provider: SyncProvider,
+    /// Owner of the synced tasks.
+    pub owner: String,
+    /// Credentials to get access tokens with.
+    pub credentials: Credentials,
+    /// URL of the provider's OAuth token endpoint.
+    pub token_url: String,
+    /// Base URL of the provider's API.
+    pub api_url: String,
+    /// Provider's ID for the task list to sync.
+    pub list: String,
+    /// Which version of tasks changed on both sides is kept.
+    pub rule: ConflictRule,
+    /// Window around now which due dates of remote tasks must be within.
+    pub due_window: DueWindow,
+    /// Source of the current time.
+    pub clock: Arc<dyn Clock>,
+}
+
+#[async_trait]
+impl Job for SyncWorker {
+    fn name(&self) -> &'static str {
+        "sync"
+    }
+
+    async fn run(&self) -> Result<(), String> {
+        let owner = Some(self.owner.clone());
+        let counts = act_as(owner, self.sync())
+            .await
+            .map_err(|e| e.to_string())?;
+        info!(
+            provider = self.provider.source(),
+            created = counts.created,
+            updated = counts.updated,
+            unchanged = counts.unchanged,
+            skipped = counts.skipped,
+            invalid = counts.invalid,
+            "synced tasks"
+        );
+        Ok(())
+    }
+}
+
+impl SyncWorker {
+    /// Pull every task in the list and store it.
+    async fn sync(&self) -> Result<SyncCounts, SyncError> {
+        let access_token = self.access_token().await?;
+        let mut counts = SyncCounts::default();
+        let mut path = Some(self.provider.tasks_path(&self.list));
+        while let Some(current) = path {
+            let request = Request::get(format!("{}{current}", self.api_url.trim_end_matches('/')))
+                .header(header::AUTHORIZATION, format!("Bearer {access_token}"))
+                .header(header::ACCEPT, "application/json")
+                .body(Full::default())
+                .map_err(|e| SyncError::Http(e.to_string()))?;
+            let body = send(&self.http, request).await?;
+            let page = self
+                .provider
+                .parse_page(&self.list, &body)
+                .map_err(|e| SyncError::Invalid(e.to_string()))?;
+
+            let now = self.clock.now();
+            for remote in page.tasks {
+                let task = match remote.to_task(self.due_window, now) {
+                    Ok(task) => task,
+                    Err(e) => {
+                        debug!(id = remote.id, reason = e, "skipping remote task");
+                        counts.invalid += 1;
+                        continue;
+                    }
+                };
+                let outcome = self
+                    .store
+                    .upsert_external(
+                        self.provider.source(),
+                        &remote.id,
+                        &task,
+                        Some(&self.owner),
+                        self.rule,
+                    )
+                    .await?;
+                match outcome {
+                    SyncOutcome::Created => counts.created += 1,
+                    SyncOutcome::Updated => counts.updated += 1,
+                    SyncOutcome::Unchanged => counts.unchanged += 1,
+                    SyncOutcome::Skipped => counts.skipped += 1,
+                }
+            }
+            path = page.next;
+        }
+        Ok(counts)
+    }
+
+    /// Exchange the refresh token for an access token.
+    async fn access_token(&self) -> Result<String, SyncError> {
+        let credentials = &self.credentials;
+        let mut form = vec![
+            ("grant_type", "refresh_token"),
+            ("refresh_token", &credentials.refresh_token),
+            ("client_id", &credentials.client_id),
+        ];
+        if let Some(secret) = &credentials.client_secret {
+            form.push(("client_secret", secret));
+        }
+        if let Some(scope) = &credentials.scope {
+            form.push(("scope", scope));
+        }
+        let body = serde_urlencoded::to_string(form).expect("form fields are strings");
+        let request = Request::post(&self.token_url)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(header::ACCEPT, "application/json")
+            .body(Full::from(body))
+            .map_err(|e| SyncError::Http(e.to_string()))?;
+        let response: TokenResponse = serde_json::from_slice(&send(&self.http, request).await?)
+            .map_err(|e| SyncError::Invalid(e.to_string()))?;
+        Ok(response.access_token)
+    }
+}
+
+/// Send a request, returning the body of a successful response.
+async fn send(http: &Egress, request: Request<Full<Bytes>>) -> Result<Bytes, SyncError> {
+    let response = http
+        .request(request)
+        .await
+        .map_err(|e| SyncError::Http(e.to_string()))?;
+    if !response.status().is_success() {
+        return Err(SyncError::Http(format!(
+            "provider responded with {}",
+            response.status()
+        )));
+    }
+    Ok(response.into_body())
+}
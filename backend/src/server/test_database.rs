@@ -0,0 +1,54 @@
+//! Databases of their own for tests needing Postgres, created on the server
+//! at `TEST_DATABASE_URL`.
+//!
+//! Tests are skipped if `TEST_DATABASE_URL` isn't set. It isn't
+//! `DATABASE_URL`, as that would turn off sqlx's offline query checking.
+
+use sqlx::{PgPool, postgres::PgConnectOptions};
+use uuid::Uuid;
+
+use super::schema;
+
+/// Prefix of the names of the databases tests create.
+const DATABASE_PREFIX: &str = "dts_test_";
+
+/// Database with the application's schema, dropped when the test is done.
+pub(crate) struct TestDatabase {
+    /// Connection to the server, to drop the database with.
+    server: PgPool,
+    name: String,
+    /// Connection to the database.
+    pub pool: PgPool,
+}
+
+impl TestDatabase {
+    /// Create a database, or return `None` if there is no database server to
+    /// test against.
+    pub(crate) async fn create() -> Option<Self> {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            eprintln!("skipped: TEST_DATABASE_URL isn't set");
+            return None;
+        };
+        let options: PgConnectOptions = url.parse().expect("invalid TEST_DATABASE_URL");
+        let server = PgPool::connect_with(options.clone()).await.unwrap();
+        let name = format!("{DATABASE_PREFIX}{}", Uuid::new_v4().simple());
+        sqlx::query(&format!("CREATE DATABASE {name}"))
+            .execute(&server)
+            .await
+            .unwrap();
+        let pool = PgPool::connect_with(options.database(&name)).await.unwrap();
+        schema::prepare(&pool, true).await.unwrap();
+        Some(Self { server, name, pool })
+    }
+
+    /// Drop the database.
+    ///
+    /// Databases of failed tests are kept, to look into.
+    pub(crate) async fn drop(self) {
+        self.pool.close().await;
+        sqlx::query(&format!("DROP DATABASE {} WITH (FORCE)", self.name))
+            .execute(&self.server)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,94 @@
+//! Extraction of valid tasks from request bodies, rejecting invalid ones
+//! with the fields at fault so clients can point users at them.
+
+use std::sync::Arc;
+
+use crate::{TodoTask, i18n::Locale};
+use axum::{
+    Json,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use tracing::debug;
+
+use super::{AppState, language::Language, protobuf::TaskBody};
+
+/// Problem with a field of a request body.
+#[derive(Serialize, Debug)]
+pub(crate) struct FieldError {
+    /// Path to the field, such as `title` or `tags[0]`, or `None` if the
+    /// problem is with the body as a whole.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// Human-readable description of the problem.
+    pub message: String,
+}
+
+/// Response body rejecting an invalid request body.
+#[derive(Serialize, Debug)]
+struct InvalidBody {
+    /// Human-readable summary of the problems.
+    message: &'static str,
+    /// Problems with the fields of the body.
+    errors: Vec<FieldError>,
+}
+
+/// Reject an invalid request body, because of `errors` described in the
+/// language of `locale`.
+pub(crate) fn reject(locale: Locale, errors: Vec<FieldError>) -> Response {
+    let body = InvalidBody {
+        message: locale.translate("invalid request body"),
+        errors,
+    };
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Language(locale),
+        Json(body),
+    )
+        .into_response()
+}
+
+/// Value from a request body which has been checked to be valid.
+///
+/// Tasks are taken as by [`TaskBody`], then validated, and checked against
+/// the due window, status reason policy and workflow.
+#[derive(Debug)]
+pub(crate) struct ValidatedJson<T>(pub T);
+
+impl FromRequest<Arc<AppState>> for ValidatedJson<TodoTask> {
+    type Rejection = Response;
+
+    async fn from_request(
+        request: Request,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let (mut parts, body) = request.into_parts();
+        let Ok(Language(locale)) = Language::from_request_parts(&mut parts, state).await;
+        let TaskBody(task) =
+            TaskBody::from_request(Request::from_parts(parts, body), state).await?;
+
+        task.check(state.due_window, state.clock.now())
+            .map_err(|e| (TodoTask::invalid_field(e), e))
+            .and_then(|task| {
+                state
+                    .status_reason
+                    .check(&task)
+                    .map_err(|e| (Some("status_reason"), e))?;
+                state
+                    .workflow
+                    .check(&task.status)
+                    .map_err(|e| (Some("status"), e))?;
+                Ok(Self(task))
+            })
+            .map_err(|(field, e)| {
+                debug!(field, error = e, "invalid task received");
+                let error = FieldError {
+                    field: field.map(str::to_owned),
+                    message: locale.translate(e).to_owned(),
+                };
+                reject(locale, vec![error])
+            })
+    }
+}
@@ -0,0 +1,388 @@
+//! Integration with automation services such as Zapier and IFTTT.
+//!
+//! Services authenticate with an access token, test it with `GET
+//! /integrations/zapier/me`, and are told about new tasks either by polling
+//! `GET /integrations/zapier/tasks`, which lists them newest first with the
+//! tasks' IDs to deduplicate them by, or by subscribing REST hooks which the
+//! same items are posted to as tasks are created.
+//!
+//! Deliveries to hooks are recorded, and listed by `GET
+//! /integrations/zapier/hooks/{hook_id}/deliveries` so services can see why
+//! they failed, and any of them can be redelivered by `POST
+//! /integrations/zapier/hooks/{hook_id}/deliveries/{delivery_id}/redeliver`.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    StoreError, TaskRecord, TodoTask,
+    egress::Egress,
+    hooks::{self, Delivery, DeliveryOutcome, Hook, NewTask},
+    store::HookStore,
+};
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+};
+use http_body_util::Full;
+use hyper::{Request, Uri, body::Bytes, header};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use super::{AppState, auth::Owner};
+
+/// Number of new tasks listed for polling, which is how many Zapier reads.
+const POLL_LENGTH: i64 = 100;
+
+/// How long delivering a task to a hook may take.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Routes serving the integration.
+pub(crate) fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/integrations/zapier/me", get(me))
+        .route("/integrations/zapier/tasks", get(poll_tasks))
+        .route("/integrations/zapier/hooks", post(subscribe))
+        .route("/integrations/zapier/hooks/{hook_id}", delete(unsubscribe))
+        .route(
+            "/integrations/zapier/hooks/{hook_id}/deliveries",
+            get(list_deliveries),
+        )
+        .route(
+            "/integrations/zapier/hooks/{hook_id}/deliveries/{delivery_id}/redeliver",
+            post(redeliver),
+        )
+}
+
+/// Sender of new tasks to hooks.
+#[derive(Clone, Debug)]
+pub(crate) struct HookSender {
+    /// Hosts which hooks may be subscribed at.
+    pub allowed_hosts: Vec<String>,
+    /// Client delivering to hooks, through a proxy if there is one.
+    pub http: Egress,
+}
+
+impl HookSender {
+    /// Check that `url` may be subscribed and delivered to, returning it
+    /// parsed.
+    fn check(&self, url: &str) -> Result<Uri, &'static str> {
+        hooks::check_target(url, &self.allowed_hosts)
+    }
+
+    /// Post `body` as JSON to `target`, returning the outcome.
+    async fn deliver(&self, target: &Uri, body: Vec<u8>) -> DeliveryOutcome {
+        let started = Instant::now();
+        let response = tokio::time::timeout(DELIVERY_TIMEOUT, self.post(target, body))
+            .await
+            .unwrap_or_else(|_| Err("timed out".to_owned()));
+        DeliveryOutcome {
+            latency: started.elapsed(),
+            response,
+        }
+    }
+
+    /// Post `body` as JSON to `target`, returning the status and start of
+    /// the body of the response.
+    async fn post(&self, target: &Uri, body: Vec<u8>) -> Result<(u16, String), String> {
+        let request = Request::post(target)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Full::<Bytes>::from(body))
+            .map_err(|e| e.to_string())?;
+        let response = self
+            .http
+            .request(request)
+            .await
+            .map_err(|e| e.to_string())?;
+        let status = response.status().as_u16();
+        Ok((status, hooks::response_snippet(response.body())))
+    }
+}
+
+/// Deliver `payload`, the task with `task_id`, to `hook` at `target`, and
+/// record the delivery.
+///
+/// The payload is rendered by the hook's payload template as it is now, so
+/// redeliveries use any fixes to it. Hooks whose target responds that they
+/// are gone are removed, along with their deliveries.
+async fn deliver(
+    hooks: &HookStore,
+    sender: &HookSender,
+    hook: &Hook,
+    target: &Uri,
+    task_id: Uuid,
+    payload: &serde_json::Value,
+    redelivery_of: Option<Uuid>,
+) -> Result<Delivery, sqlx::Error> {
+    let (body, outcome) = match hooks::render_payload(hook.payload_template.as_deref(), payload) {
+        Ok(body) => {
+            let json = serde_json::to_vec(&body).expect("JSON values serialize");
+            (Some(body), sender.deliver(target, json).await)
+        }
+        Err(e) => {
+            let outcome = DeliveryOutcome {
+                latency: Duration::ZERO,
+                response: Err(e),
+            };
+            (None, outcome)
+        }
+    };
+    match &outcome.response {
+        Ok((status, _)) if outcome.succeeded() => {
+            debug!(hook_id = %hook.id, %task_id, status, "delivered task to hook");
+        }
+        Ok((status, _)) => error!(hook_id = %hook.id, status, "hook refused task"),
+        Err(e) => error!(hook_id = %hook.id, error = e, "failed to deliver task to hook"),
+    }
+    let delivery = hooks
+        .record_delivery(
+            hook.id,
+            task_id,
+            payload,
+            body.as_ref(),
+            redelivery_of,
+            &outcome,
+        )
+        .await?;
+
+    // the service has unsubscribed, so stop sending to it
+    if matches!(outcome.response, Ok((410, _))) {
+        info!(hook_id = %hook.id, "removing hook gone from its target");
+        hooks.remove(hook.id).await?;
+    }
+    Ok(delivery)
+}
+
+/// Send a newly created task to the hooks subscribed by `owner`, in the
+/// background, unless the background workers are too busy.
+pub(crate) fn task_created(state: &AppState, owner: Option<&str>, id: Uuid, task: &TodoTask) {
+    let hooks = state.hooks.clone();
+    let sender = state.hook_sender.clone();
+    let owner = owner.map(str::to_owned);
+    let new_task = NewTask {
+        task: TaskRecord {
+            id,
+            task: task.clone(),
+        },
+        created_at: state.clock.now(),
+    };
+
+    let work = async move {
+        let subscribed = match hooks.for_owner(owner.as_deref()).await {
+            Ok(subscribed) => subscribed,
+            Err(e) => {
+                error!(
+                    error = format!("{e}"),
+                    "database error trying to list hooks"
+                );
+                return;
+            }
+        };
+        if subscribed.is_empty() {
+            return;
+        }
+        let payload = serde_json::to_value(&new_task).expect("tasks serialize to JSON");
+        for hook in subscribed {
+            let target = match sender.check(&hook.target_url) {
+                Ok(target) => target,
+                Err(e) => {
+                    debug!(hook_id = %hook.id, reason = e, "skipping refused hook");
+                    continue;
+                }
+            };
+            if let Err(e) = deliver(&hooks, &sender, &hook, &target, id, &payload, None).await {
+                error!(
+                    error = format!("{e}"),
+                    "database error trying to record hook delivery"
+                );
+            }
+        }
+    };
+    if let Err(e) = state.workers.spawn(work) {
+        warn!(task_id = %id, error = %e, "dropped delivery of task to hooks");
+    }
+}
+
+/// Response body of [`me`].
+#[derive(Serialize, Debug)]
+struct Me {
+    /// Owner whom the request is authenticated as.
+    owner: Option<String>,
+}
+
+/// Identify the owner whom a service is authenticated as, so it can test
+/// its credentials.
+#[tracing::instrument]
+async fn me(Owner(owner, _): Owner) -> Json<Me> {
+    Json(Me { owner })
+}
+
+/// List the most recently created tasks, newest first.
+#[tracing::instrument]
+async fn poll_tasks(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+) -> Result<Json<Vec<NewTask>>, StoreError> {
+    Ok(Json(
+        state.store.new_tasks(owner.as_deref(), POLL_LENGTH).await?,
+    ))
+}
+
+/// Request body of [`subscribe`], as sent by Zapier.
+#[derive(Deserialize, Debug)]
+struct Subscription {
+    /// URL to post new tasks to.
+    #[serde(rename = "hookUrl", alias = "target_url")]
+    hook_url: String,
+    /// Template of the payload to post, if not the new task itself.
+    #[serde(default, rename = "payloadTemplate", alias = "payload_template")]
+    payload_template: Option<String>,
+}
+
+/// Subscribe a hook to the owner's new tasks.
+#[tracing::instrument]
+async fn subscribe(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Json(subscription): Json<Subscription>,
+) -> Result<(StatusCode, Json<Hook>), Response> {
+    if let Err(e) = state.hook_sender.check(&subscription.hook_url) {
+        debug!(reason = e, "refused hook subscription");
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, e).into_response());
+    }
+    if let Some(template) = &subscription.payload_template {
+        hooks::check_template(template)
+            .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e).into_response())?;
+    }
+    match state
+        .hooks
+        .subscribe(
+            owner.as_deref(),
+            &subscription.hook_url,
+            subscription.payload_template.as_deref(),
+        )
+        .await
+    {
+        Ok(hook) => {
+            info!(owner, hook_id = %hook.id, "hook subscribed");
+            Ok((StatusCode::CREATED, Json(hook)))
+        }
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to subscribe hook"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Unsubscribe one of the owner's hooks.
+#[tracing::instrument]
+async fn unsubscribe(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(hook_id): Path<Uuid>,
+) -> StatusCode {
+    match state.hooks.unsubscribe(hook_id, owner.as_deref()).await {
+        Ok(true) => {
+            info!(owner, %hook_id, "hook unsubscribed");
+            StatusCode::NO_CONTENT
+        }
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to unsubscribe hook"
+            );
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Get one of the owner's hooks, responding with an error if it doesn't
+/// exist.
+async fn owned_hook(
+    state: &AppState,
+    owner: Option<&str>,
+    hook_id: Uuid,
+) -> Result<Hook, StatusCode> {
+    match state.hooks.get(hook_id, owner).await {
+        Ok(Some(hook)) => Ok(hook),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!(error = format!("{e}"), "database error trying to get hook");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// List the latest deliveries to one of the owner's hooks, newest first.
+#[tracing::instrument]
+async fn list_deliveries(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path(hook_id): Path<Uuid>,
+) -> Result<Json<Vec<Delivery>>, StatusCode> {
+    let hook = owned_hook(&state, owner.as_deref(), hook_id).await?;
+    match state.hooks.deliveries(hook.id).await {
+        Ok(deliveries) => Ok(Json(deliveries)),
+        Err(e) => {
+            error!(
+                error = format!("{e}"),
+                "database error trying to list hook deliveries"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Deliver the payload of one of the deliveries to one of the owner's hooks
+/// again, responding with the new delivery.
+#[tracing::instrument]
+async fn redeliver(
+    State(state): State<Arc<AppState>>,
+    Owner(owner, _): Owner,
+    Path((hook_id, delivery_id)): Path<(Uuid, Uuid)>,
+) -> Result<(StatusCode, Json<Delivery>), Response> {
+    let hook = owned_hook(&state, owner.as_deref(), hook_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let target = state.hook_sender.check(&hook.target_url).map_err(|e| {
+        debug!(reason = e, "refused redelivery");
+        (StatusCode::UNPROCESSABLE_ENTITY, e).into_response()
+    })?;
+    let database_error = |e: sqlx::Error| {
+        error!(
+            error = format!("{e}"),
+            "database error trying to redeliver to hook"
+        );
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    };
+    let original = state
+        .hooks
+        .delivery(hook.id, delivery_id)
+        .await
+        .map_err(database_error)?
+        .ok_or_else(|| StatusCode::NOT_FOUND.into_response())?;
+
+    let delivery = deliver(
+        &state.hooks,
+        &state.hook_sender,
+        &hook,
+        &target,
+        original.task_id,
+        &original.payload,
+        Some(original.id),
+    )
+    .await
+    .map_err(database_error)?;
+    info!(owner, %hook_id, %delivery_id, "redelivered to hook");
+    Ok((StatusCode::CREATED, Json(delivery)))
+}
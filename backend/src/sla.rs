@@ -0,0 +1,354 @@
+//! Service level targets for responding to and resolving tasks, measured in
+//! working hours.
+//!
+//! A task is responded to when its status first changes from
+//! [`TodoStatus::NotStarted`], and resolved when it is completed. Cancelled
+//! tasks have no targets.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "postgres")]
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::{TaskRecord, TodoStatus, TodoTask, calendar::WorkCalendar};
+
+/// Targets for tasks with a tag, or for tasks without any tag which has
+/// targets.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SlaPolicy {
+    /// Tag of the tasks the targets are for, or `None` for the default
+    /// targets.
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Working hours after creation within which tasks should be started.
+    #[serde(default)]
+    pub respond_within_hours: Option<u32>,
+    /// Working hours after creation within which tasks should be completed.
+    #[serde(default)]
+    pub resolve_within_hours: Option<u32>,
+}
+
+/// Service level targets configured by a deployment.
+///
+/// Read from a JSON array of [`SlaPolicy`]s like:
+///
+/// ```
+/// use dts_developer_challenge::sla::SlaPolicies;
+///
+/// let policies: SlaPolicies = serde_json::from_str(
+///     r#"[
+///         { "tag": "urgent", "respond_within_hours": 1, "resolve_within_hours": 8 },
+///         { "resolve_within_hours": 40 }
+///     ]"#,
+/// )
+/// .unwrap();
+/// assert!(policies.validate().is_ok());
+/// ```
+///
+/// Tasks follow the policy of the first of their tags which has one, or else
+/// the default policy, if there is one.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct SlaPolicies(pub Vec<SlaPolicy>);
+
+/// How a task is doing against a target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlaState {
+    /// The target was met, or there is time left to meet it.
+    OnTrack,
+    /// Most of the time to meet the target has passed.
+    AtRisk,
+    /// The target was missed.
+    Breached,
+}
+
+/// Progress of a task towards a single target.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SlaTarget {
+    /// When the target must be met by.
+    pub by: DateTime<Utc>,
+    /// How the task is doing against the target.
+    pub state: SlaState,
+}
+
+/// Progress of a task towards the targets of its policy.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SlaStatus {
+    /// Tag of the policy followed, or `None` for the default policy.
+    pub policy: Option<String>,
+    /// Worst state of the targets.
+    pub state: SlaState,
+    /// Target for starting the task, if the policy has one.
+    pub respond: Option<SlaTarget>,
+    /// Target for completing the task, if the policy has one.
+    pub resolve: Option<SlaTarget>,
+}
+
+/// Times at which a task reached the points its targets are measured from
+/// and to, other than its completion.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct TaskMilestones {
+    /// ID of the task.
+    pub id: Uuid,
+    /// Date & time at which the task was created.
+    pub created_at: DateTime<Utc>,
+    /// Date & time at which the task's status first changed from not
+    /// started, if it has.
+    pub responded_at: Option<DateTime<Utc>>,
+}
+
+/// Task which breached a target, as listed by the breaches report.
+#[derive(Clone, Debug, Serialize)]
+pub struct SlaBreach {
+    /// The task.
+    #[serde(flatten)]
+    pub task: TaskRecord,
+    /// Progress of the task towards its targets.
+    pub sla: SlaStatus,
+}
+
+impl SlaPolicies {
+    /// Check that the policies have targets, and are for distinct, valid
+    /// tags.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the first problem found.
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, policy) in self.0.iter().enumerate() {
+            let name = policy.tag.as_deref().unwrap_or("default");
+            if policy
+                .tag
+                .as_deref()
+                .is_some_and(|tag| tag.is_empty() || tag.chars().any(char::is_whitespace))
+            {
+                return Err(format!("policy tag {name:?} is not a valid tag"));
+            }
+            if self.0[..i].iter().any(|other| other.tag == policy.tag) {
+                return Err(format!("{name} policy is defined twice"));
+            }
+            let targets = [policy.respond_within_hours, policy.resolve_within_hours];
+            if targets.iter().all(Option::is_none) {
+                return Err(format!("{name} policy has no targets"));
+            }
+            if targets.contains(&Some(0)) {
+                return Err(format!("{name} policy has a target of no time"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the policy which `task` follows, if any.
+    #[must_use]
+    pub fn policy_for(&self, task: &TodoTask) -> Option<&SlaPolicy> {
+        task.tags()
+            .iter()
+            .find_map(|tag| {
+                self.0
+                    .iter()
+                    .find(|policy| policy.tag.as_ref() == Some(tag))
+            })
+            .or_else(|| self.0.iter().find(|policy| policy.tag.is_none()))
+    }
+
+    /// Evaluate the progress of `task`, which reached `milestones`, towards
+    /// its targets as of `now`.
+    ///
+    /// Returns `None` if the task has no targets, including if it was
+    /// cancelled.
+    #[must_use]
+    pub fn evaluate(
+        &self,
+        calendar: &WorkCalendar,
+        task: &TodoTask,
+        milestones: &TaskMilestones,
+        now: DateTime<Utc>,
+    ) -> Option<SlaStatus> {
+        if task.status == TodoStatus::Cancelled {
+            return None;
+        }
+        let policy = self.policy_for(task)?;
+        let target = |hours: Option<u32>, reached_at: Option<DateTime<Utc>>| {
+            let within = TimeDelta::hours(hours?.into());
+            let created_at = milestones.created_at;
+            let by = calendar.add_working_time(created_at, within);
+            let state = if reached_at.unwrap_or(now) > by {
+                SlaState::Breached
+            } else if reached_at.is_none()
+                // at risk once three quarters of the time has passed
+                && calendar.working_time(created_at, now) * 4 >= within * 3
+            {
+                SlaState::AtRisk
+            } else {
+                SlaState::OnTrack
+            };
+            Some(SlaTarget { by, state })
+        };
+
+        let respond = target(policy.respond_within_hours, milestones.responded_at);
+        let resolve = target(policy.resolve_within_hours, task.completed_at().copied());
+        Some(SlaStatus {
+            policy: policy.tag.clone(),
+            state: [&respond, &resolve]
+                .into_iter()
+                .flatten()
+                .map(|target| target.state)
+                .max()
+                .unwrap_or(SlaState::OnTrack),
+            respond,
+            resolve,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[fixture]
+    fn policies() -> SlaPolicies {
+        serde_json::from_str(
+            r#"[
+                { "tag": "urgent", "respond_within_hours": 1, "resolve_within_hours": 8 },
+                { "resolve_within_hours": 40 }
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    fn task(tags: &[&str], status: TodoStatus) -> TodoTask {
+        let mut task = TodoTask::new(
+            "task".to_string(),
+            None,
+            TodoStatus::NotStarted,
+            &at("2025-06-20T12:00:00Z"),
+            at("2025-06-09T12:00:00Z"),
+        );
+        task.set_tags(tags.iter().map(ToString::to_string).collect());
+        task.transition(status, at("2025-06-10T09:00:00Z"));
+        task
+    }
+
+    #[fixture]
+    fn milestones() -> TaskMilestones {
+        // a Monday
+        TaskMilestones {
+            id: Uuid::nil(),
+            created_at: at("2025-06-09T09:00:00Z"),
+            responded_at: None,
+        }
+    }
+
+    #[rstest]
+    // two working hours after creation
+    #[case(
+        TodoStatus::NotStarted,
+        None,
+        "2025-06-09T11:00:00Z",
+        SlaState::Breached
+    )]
+    #[case(
+        TodoStatus::InProgress,
+        Some("2025-06-09T09:30:00Z"),
+        "2025-06-09T11:00:00Z",
+        SlaState::OnTrack
+    )]
+    // late response
+    #[case(
+        TodoStatus::InProgress,
+        Some("2025-06-09T10:30:00Z"),
+        "2025-06-09T11:00:00Z",
+        SlaState::Breached
+    )]
+    // six working hours in, resolution at risk
+    #[case(
+        TodoStatus::InProgress,
+        Some("2025-06-09T09:30:00Z"),
+        "2025-06-09T15:00:00Z",
+        SlaState::AtRisk
+    )]
+    // completed on the Tuesday, a day late
+    #[case(
+        TodoStatus::Complete,
+        Some("2025-06-09T09:30:00Z"),
+        "2025-06-11T09:00:00Z",
+        SlaState::Breached
+    )]
+    fn evaluate(
+        policies: SlaPolicies,
+        mut milestones: TaskMilestones,
+        #[case] status: TodoStatus,
+        #[case] responded_at: Option<&str>,
+        #[case] now: &str,
+        #[case] expected: SlaState,
+    ) {
+        milestones.responded_at = responded_at.map(at);
+        let status = policies
+            .evaluate(
+                &WorkCalendar::default(),
+                &task(&["urgent"], status),
+                &milestones,
+                at(now),
+            )
+            .unwrap();
+        assert_eq!(status.policy.as_deref(), Some("urgent"));
+        assert_eq!(status.state, expected);
+    }
+
+    #[rstest]
+    fn default_policy(policies: SlaPolicies, milestones: TaskMilestones) {
+        let calendar = WorkCalendar::default();
+        let status = policies
+            .evaluate(
+                &calendar,
+                &task(&["minor"], TodoStatus::NotStarted),
+                &milestones,
+                at("2025-06-09T12:00:00Z"),
+            )
+            .unwrap();
+        assert_eq!(status.policy, None);
+        assert_eq!(status.respond, None);
+        // a working week after creation
+        assert_eq!(
+            status.resolve,
+            Some(SlaTarget {
+                by: at("2025-06-13T17:00:00Z"),
+                state: SlaState::OnTrack,
+            })
+        );
+
+        let cancelled = task(&[], TodoStatus::Cancelled);
+        assert_eq!(
+            policies.evaluate(&calendar, &cancelled, &milestones, Utc::now()),
+            None
+        );
+        assert_eq!(
+            SlaPolicies::default().evaluate(
+                &calendar,
+                &task(&[], TodoStatus::NotStarted),
+                &milestones,
+                Utc::now()
+            ),
+            None
+        );
+    }
+
+    #[rstest]
+    #[case(r#"[{ "tag": "urgent" }]"#)]
+    #[case(r#"[{ "tag": "two words", "resolve_within_hours": 1 }]"#)]
+    #[case(r#"[{ "resolve_within_hours": 0 }]"#)]
+    #[case(r#"[{ "resolve_within_hours": 1 }, { "respond_within_hours": 1 }]"#)]
+    fn invalid(#[case] json: &str) {
+        let policies: SlaPolicies = serde_json::from_str(json).unwrap();
+        assert!(policies.validate().is_err());
+    }
+}
@@ -0,0 +1,181 @@
+//! Notifications sent by SMS, for urgent notices which email may not be read
+//! quickly enough for, such as tasks becoming overdue.
+//!
+//! Messages are sent through a [`SmsGateway`], chosen per deployment.
+
+use std::fmt;
+
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use http_body_util::Full;
+use hyper::{Request, body::Bytes, header};
+use tracing::debug;
+
+use crate::{
+    egress::Egress,
+    notify::{Channel, Notification, Notifier},
+};
+
+/// Check that `number` is a phone number in E.164 format, like
+/// `+447700900123`, as SMS gateways expect.
+///
+/// # Errors
+///
+/// Returns a description of the problem if it isn't.
+pub fn check_phone(number: &str) -> Result<(), &'static str> {
+    match number.strip_prefix('+') {
+        Some(digits)
+            if (8..=15).contains(&digits.len())
+                && !digits.starts_with('0')
+                && digits.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            Ok(())
+        }
+        _ => Err("phone number must be in international format, like +447700900123"),
+    }
+}
+
+/// Service which SMS are sent through.
+#[async_trait]
+pub trait SmsGateway: fmt::Debug + Send + Sync {
+    /// Send `message` to the phone number `to`, returning a description of
+    /// the problem if the gateway didn't accept it.
+    async fn send(&self, to: &str, message: &str) -> Result<(), String>;
+}
+
+/// Gateway sending SMS with Twilio's Programmable Messaging API.
+pub struct TwilioGateway {
+    http: Egress,
+    /// Base URL of the API, e.g. `https://api.twilio.com`.
+    api_url: String,
+    account_sid: String,
+    auth_token: String,
+    /// Phone number which messages are sent from.
+    from: String,
+}
+
+impl fmt::Debug for TwilioGateway {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TwilioGateway")
+            .field("api_url", &self.api_url)
+            .field("account_sid", &self.account_sid)
+            .field("from", &self.from)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TwilioGateway {
+    /// Create a gateway using the API at `api_url` through `http` as the
+    /// account `account_sid`, sending messages from the phone number `from`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `from` isn't a valid phone number.
+    pub fn new(
+        http: Egress,
+        api_url: &str,
+        account_sid: String,
+        auth_token: String,
+        from: String,
+    ) -> Result<Self, &'static str> {
+        check_phone(&from)?;
+        Ok(Self {
+            http,
+            api_url: api_url.trim_end_matches('/').to_owned(),
+            account_sid,
+            auth_token,
+            from,
+        })
+    }
+
+    /// Build the request sending `message` to `to`.
+    fn request(&self, to: &str, message: &str) -> Result<Request<Full<Bytes>>, String> {
+        let body =
+            serde_urlencoded::to_string([("To", to), ("From", &self.from), ("Body", message)])
+                .map_err(|e| e.to_string())?;
+        let credentials = STANDARD.encode(format!("{}:{}", self.account_sid, self.auth_token));
+        Request::post(format!(
+            "{}/2010-04-01/Accounts/{}/Messages.json",
+            self.api_url, self.account_sid
+        ))
+        .header(header::AUTHORIZATION, format!("Basic {credentials}"))
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .header(header::ACCEPT, "application/json")
+        .body(Full::from(body))
+        .map_err(|e| e.to_string())
+    }
+}
+
+#[async_trait]
+impl SmsGateway for TwilioGateway {
+    async fn send(&self, to: &str, message: &str) -> Result<(), String> {
+        let response = self
+            .http
+            .request(self.request(to, message)?)
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("Twilio responded with {}", response.status()))
+        }
+    }
+}
+
+/// Notifier sending SMS to users' registered phone numbers.
+#[derive(Debug)]
+pub struct SmsNotifier(pub Box<dyn SmsGateway>);
+
+#[async_trait]
+impl Notifier for SmsNotifier {
+    fn channel(&self) -> Channel {
+        Channel::Sms
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<(), String> {
+        let Some(to) = &notification.phone else {
+            debug!(user = notification.user_id, "no phone number to notify");
+            return Ok(());
+        };
+        self.0.send(to, &notification.summary()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("+447700900123", true)]
+    #[case("+15005550006", true)]
+    #[case("07700900123", false)]
+    #[case("+44 7700 900123", false)]
+    #[case("+0447700900123", false)]
+    #[case("+1234", false)]
+    fn phone_numbers(#[case] number: &str, #[case] valid: bool) {
+        assert_eq!(check_phone(number).is_ok(), valid);
+    }
+
+    #[test]
+    fn twilio_request() {
+        let gateway = TwilioGateway::new(
+            Egress::default(),
+            "http://egress:3128/",
+            "AC123".to_owned(),
+            "secret".to_owned(),
+            "+15005550006".to_owned(),
+        )
+        .unwrap();
+        let request = gateway.request("+447700900123", "Task & more").unwrap();
+        assert_eq!(
+            request.uri(),
+            "http://egress:3128/2010-04-01/Accounts/AC123/Messages.json"
+        );
+        assert_eq!(
+            request.headers()[header::AUTHORIZATION],
+            "Basic QUMxMjM6c2VjcmV0"
+        );
+        assert!(!format!("{gateway:?}").contains("secret"));
+    }
+}
@@ -0,0 +1,240 @@
+//! Sending of plain text emails through an SMTP relay.
+//!
+//! Only unauthenticated, unencrypted SMTP is spoken, so the relay should be a
+//! mail server on the local network, such as a sidecar which forwards mail on
+//! over TLS.
+
+#[cfg(feature = "net")]
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+#[cfg(feature = "net")]
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+use uuid::Uuid;
+
+#[cfg(feature = "net")]
+use crate::clock::{Clock, SystemClock};
+
+/// Email to send with [`Mailer::send`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Email {
+    /// Address to send the email to.
+    pub to: String,
+    /// Subject line.
+    pub subject: String,
+    /// Plain text body.
+    pub body: String,
+}
+
+impl Email {
+    /// Format the email as a message from `from`, sent at `date`, ready to be
+    /// given to SMTP's `DATA` command.
+    ///
+    /// Line endings are normalised to CRLF and lines starting with `.` are
+    /// escaped; the message ends with the line ending the `DATA` command.
+    #[must_use]
+    pub fn message(&self, from: &str, date: DateTime<Utc>) -> String {
+        let mut message = format!(
+            "From: {from}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: <{}@{}>\r\n\
+            MIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\n\
+            Content-Transfer-Encoding: 8bit\r\n\r\n",
+            self.to,
+            self.subject,
+            date.to_rfc2822(),
+            Uuid::new_v4(),
+            domain(from),
+        );
+        for line in self.body.lines() {
+            if line.starts_with('.') {
+                message.push('.');
+            }
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        message.push_str(".\r\n");
+        message
+    }
+}
+
+/// Domain of `address`, or `localhost` if it has none.
+fn domain(address: &str) -> &str {
+    address
+        .rsplit_once('@')
+        .map_or("localhost", |(_, domain)| domain.trim_end_matches('>'))
+}
+
+/// Check that `address` looks like an email address which can be put in
+/// headers and SMTP commands as is.
+///
+/// # Errors
+///
+/// Returns a description of the problem if it doesn't.
+pub fn check_address(address: &str) -> Result<(), &'static str> {
+    if address
+        .chars()
+        .any(|c| c.is_whitespace() || "<>,;".contains(c))
+    {
+        return Err("email address contains whitespace or punctuation it can't have");
+    }
+    match address.split_once('@') {
+        Some((local, domain)) if !local.is_empty() && !domain.is_empty() => Ok(()),
+        _ => Err("email address must be like name@example.com"),
+    }
+}
+
+/// Client of an SMTP relay, sending emails from a single address.
+#[cfg(feature = "net")]
+#[derive(Clone, Debug)]
+pub struct Mailer {
+    /// Host and port of the relay.
+    relay: String,
+    /// Address which emails are sent from.
+    from: String,
+    /// Clock telling the time that emails are sent at.
+    clock: Arc<dyn Clock>,
+}
+
+#[cfg(feature = "net")]
+impl Mailer {
+    /// Create a client of the relay at `relay`, given as `host:port`, sending
+    /// emails from `from`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `from` isn't a valid address.
+    pub fn new(relay: String, from: String) -> Result<Self, &'static str> {
+        check_address(&from)?;
+        Ok(Self {
+            relay,
+            from,
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// Date emails by `clock`, rather than by the system clock.
+    #[must_use]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Send `email`, returning a description of the problem if the relay
+    /// can't be reached or doesn't accept it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the email wasn't accepted for delivery.
+    pub async fn send(&self, email: &Email) -> Result<(), String> {
+        check_address(&email.to)?;
+        if email.subject.contains(['\r', '\n']) {
+            return Err("email subject must be a single line".to_owned());
+        }
+
+        let stream = TcpStream::connect(&self.relay)
+            .await
+            .map_err(|e| format!("failed to connect to SMTP relay: {e}"))?;
+        let mut session = Session {
+            stream: BufReader::new(stream),
+        };
+        session.expect(220).await?;
+        session
+            .command(&format!("HELO {}", domain(&self.from)), 250)
+            .await?;
+        session
+            .command(&format!("MAIL FROM:<{}>", self.from), 250)
+            .await?;
+        session
+            .command(&format!("RCPT TO:<{}>", email.to), 250)
+            .await?;
+        session.command("DATA", 354).await?;
+        session
+            .write(&email.message(&self.from, self.clock.now()))
+            .await?;
+        session.expect(250).await?;
+        // the email is accepted, so a failure to end politely doesn't matter
+        let _ = session.command("QUIT", 221).await;
+        Ok(())
+    }
+}
+
+/// Connection to an SMTP relay.
+#[cfg(feature = "net")]
+struct Session {
+    stream: BufReader<TcpStream>,
+}
+
+#[cfg(feature = "net")]
+impl Session {
+    /// Send `command` and check the reply has the code `expected`.
+    async fn command(&mut self, command: &str, expected: u16) -> Result<(), String> {
+        self.write(&format!("{command}\r\n")).await?;
+        self.expect(expected).await
+    }
+
+    async fn write(&mut self, data: &str) -> Result<(), String> {
+        self.stream
+            .get_mut()
+            .write_all(data.as_bytes())
+            .await
+            .map_err(|e| format!("failed to write to SMTP relay: {e}"))
+    }
+
+    /// Read a reply, which may span several lines, and check it has the code
+    /// `expected`.
+    async fn expect(&mut self, expected: u16) -> Result<(), String> {
+        loop {
+            let mut line = String::new();
+            let read = self
+                .stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| format!("failed to read from SMTP relay: {e}"))?;
+            if read == 0 {
+                return Err("SMTP relay closed the connection".to_owned());
+            }
+            let line = line.trim_end();
+            let code = line.get(..3).and_then(|code| code.parse::<u16>().ok());
+            if code != Some(expected) {
+                return Err(format!("SMTP relay replied {line:?}"));
+            }
+            // lines but the last of a reply have a hyphen after the code
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn message() {
+        let email = Email {
+            to: "alice@example.com".to_owned(),
+            subject: "Your digest".to_owned(),
+            body: "Hello\n.hidden\r\nBye".to_owned(),
+        };
+        let message = email.message("tasks@example.org", "2025-06-10T07:00:00Z".parse().unwrap());
+        assert!(message.starts_with(
+            "From: tasks@example.org\r\nTo: alice@example.com\r\nSubject: Your digest\r\n\
+            Date: Tue, 10 Jun 2025 07:00:00 +0000\r\nMessage-ID: <"
+        ));
+        assert!(message.ends_with("\r\n\r\nHello\r\n..hidden\r\nBye\r\n.\r\n"));
+    }
+
+    #[rstest]
+    #[case("alice@example.com", true)]
+    #[case("alice", false)]
+    #[case("@example.com", false)]
+    #[case("alice@example.com\r\nBcc: eve@example.com", false)]
+    #[case("Alice <alice@example.com>", false)]
+    fn addresses(#[case] address: &str, #[case] valid: bool) {
+        assert_eq!(check_address(address).is_ok(), valid);
+    }
+}
@@ -0,0 +1,172 @@
+//! Reporting statistics over [`TodoTask`](crate::TodoTask)s.
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "postgres")]
+use sqlx::FromRow;
+
+/// Length of the buckets which statistics over time are grouped into.
+///
+/// Buckets are aligned to the start of their period in UTC, with weeks
+/// starting on Monday.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    /// One calendar day.
+    #[default]
+    Day,
+    /// One ISO week.
+    Week,
+    /// One calendar month.
+    Month,
+}
+
+impl Bucket {
+    /// Name of the bucket's unit, as understood by Postgres' `date_trunc`.
+    #[must_use]
+    pub fn unit(self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+        }
+    }
+
+    /// Number of buckets covering the period from `from` to `to`, inclusive.
+    ///
+    /// Returns 0 if `to` is before `from`.
+    #[must_use]
+    pub fn count(self, from: DateTime<Utc>, to: DateTime<Utc>) -> i64 {
+        let (from, to) = (from.date_naive(), to.date_naive());
+        if to < from {
+            return 0;
+        }
+        match self {
+            Self::Day => (to - from).num_days() + 1,
+            Self::Week => (week_start(to) - week_start(from)).num_days() / 7 + 1,
+            Self::Month => {
+                let months = |d: NaiveDate| i64::from(d.year()) * 12 + i64::from(d.month0());
+                months(to) - months(from) + 1
+            }
+        }
+    }
+}
+
+/// Monday of the week containing `date`.
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - chrono::Days::new(u64::from(date.weekday().num_days_from_monday()))
+}
+
+/// Counts of task activity within one [`Bucket`] of time.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct BurndownBucket {
+    /// Start of the bucket.
+    pub start: DateTime<Utc>,
+    /// Number of tasks created during the bucket.
+    pub created: i64,
+    /// Number of tasks completed during the bucket, which are still complete.
+    pub completed: i64,
+    /// Number of tasks overdue and not yet completed at the end of the
+    /// bucket, excluding those now cancelled.
+    pub overdue: i64,
+}
+
+/// What to group tasks by when comparing estimates with actual effort.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EstimateGrouping {
+    /// Each tag of the tasks, so tasks with several tags count towards each.
+    #[default]
+    Tag,
+    /// The assignee of the tasks.
+    Assignee,
+}
+
+/// Estimated against actual effort of the estimated tasks in one group.
+///
+/// Cancelled tasks are excluded.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct EstimateVariance {
+    /// Tag or assignee of the tasks, or `None` for untagged or unassigned
+    /// tasks.
+    pub group: Option<String>,
+    /// Number of tasks with an estimate.
+    pub tasks: i64,
+    /// Total estimated effort, in seconds.
+    pub estimated_seconds: i64,
+    /// Total time tracked on the tasks, in seconds.
+    pub tracked_seconds: i64,
+    /// Total time from creation to completion of the tasks (or until now if
+    /// they aren't complete), in seconds.
+    pub elapsed_seconds: i64,
+    /// Tracked minus estimated seconds; positive if the tasks took longer than
+    /// estimated.
+    pub variance_seconds: i64,
+}
+
+/// Statistics as they were when last refreshed.
+///
+/// Statistics served from materialised views are only as fresh as the views,
+/// which are refreshed on a schedule.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Refreshed<T> {
+    /// When the statistics were computed.
+    pub refreshed_at: DateTime<Utc>,
+    /// The statistics.
+    pub stats: T,
+}
+
+/// Open tasks assigned to one assignee.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct Workload {
+    /// Assignee of the tasks, or `None` for unassigned tasks.
+    pub assignee: Option<String>,
+    /// Number of tasks which are neither complete nor cancelled.
+    pub open: i64,
+    /// Number of those tasks which are overdue.
+    pub overdue: i64,
+    /// Earliest due date of those tasks.
+    pub next_due: DateTime<Utc>,
+}
+
+/// Storage taken by the files attached to one owner's tasks.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct StorageUsage {
+    /// Owner of the tasks, or `None` for tasks without one.
+    pub owner: Option<String>,
+    /// Number of files attached, including quarantined files.
+    pub files: i64,
+    /// Total size of the files, in bytes.
+    pub bytes: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case::same_day(Bucket::Day, (2025, 5, 1), (2025, 5, 1), 1)]
+    #[case::days(Bucket::Day, (2025, 4, 30), (2025, 5, 2), 3)]
+    #[case::reversed(Bucket::Day, (2025, 5, 2), (2025, 5, 1), 0)]
+    // 2025-05-04 is a Sunday
+    #[case::same_week(Bucket::Week, (2025, 4, 28), (2025, 5, 4), 1)]
+    #[case::weeks(Bucket::Week, (2025, 5, 4), (2025, 5, 5), 2)]
+    #[case::same_month(Bucket::Month, (2025, 5, 1), (2025, 5, 31), 1)]
+    #[case::months(Bucket::Month, (2024, 12, 31), (2025, 2, 1), 3)]
+    fn count(
+        #[case] bucket: Bucket,
+        #[case] from: (i32, u32, u32),
+        #[case] to: (i32, u32, u32),
+        #[case] expected: i64,
+    ) {
+        let at = |(y, m, d)| Utc.with_ymd_and_hms(y, m, d, 12, 0, 0).unwrap();
+        assert_eq!(bucket.count(at(from), at(to)), expected);
+    }
+}
@@ -0,0 +1,490 @@
+//! Persistence of [`TodoTask`]s.
+//!
+//! All database access goes through the [`TaskStore`] trait, which has two
+//! implementations:
+//! - [`PgTaskStore`] keeps each task's current state in a row of the `tasks`
+//!   table.
+//! - [`EventTaskStore`] derives each task's state from an append-only stream
+//!   of [`TaskEvent`](crate::TaskEvent)s, keeping the `tasks` table as a
+//!   projection to serve queries from.
+//!
+//! Both can enforce which owner's tasks are accessible with the database's
+//! row-level security policies, for operations run within [`act_as`].
+//! Either can be wrapped in a [`TimedTaskStore`] to measure its operations,
+//! and in a [`RetryingTaskStore`] to retry writes which lose races with
+//! concurrent transactions.
+
+mod events;
+mod guarded;
+mod hooks;
+mod jobs;
+mod notifications;
+mod postgres;
+mod push;
+mod retrying;
+mod security;
+mod sessions;
+mod timed;
+mod tokens;
+mod users;
+
+use std::{fmt::Debug, future::Future, ops::RangeInclusive, time::Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::{
+    DueWindow, FilterExpr, StoreError, TaskLink, TaskRecord, TodoTask,
+    attachments::{Attachment, NewAttachment},
+    feed::Activity,
+    graph::TaskGraph,
+    hooks::NewTask,
+    mentions::Mention,
+    sla::TaskMilestones,
+    stats::{
+        Bucket, BurndownBucket, EstimateGrouping, EstimateVariance, Refreshed, StorageUsage,
+        Workload,
+    },
+    sync::{ConflictRule, SyncOutcome},
+    thumbnails::Thumbnail,
+    tracking::{TimeEntry, TimesheetEntry},
+};
+
+pub use events::EventTaskStore;
+pub use guarded::GuardedTaskStore;
+pub use hooks::HookStore;
+pub use jobs::JobStore;
+pub use notifications::NotificationStore;
+pub use postgres::PgTaskStore;
+pub use push::PushSubscriptionStore;
+pub use retrying::RetryingTaskStore;
+pub use security::SecurityLog;
+pub use sessions::SessionStore;
+pub use timed::TimedTaskStore;
+pub use tokens::TokenStore;
+pub use users::{UserStore, UserUpdate};
+
+tokio::task_local! {
+    /// Owner on whose behalf store operations in the current task are run.
+    static CURRENT_OWNER: Option<String>;
+    /// Time by which store operations in the current task must finish.
+    static DEADLINE: Instant;
+}
+
+/// Run `f`, with the store operations it makes acting on behalf of `owner`.
+///
+/// This only restricts stores with row-level security enabled; `None` is the
+/// anonymous owner.
+pub async fn act_as<F: Future>(owner: Option<String>, f: F) -> F::Output {
+    CURRENT_OWNER.scope(owner, f).await
+}
+
+/// Run `f`, with the store operations it makes given up on by the database
+/// if they are still running at `deadline`.
+///
+/// Dropping an operation only stops waiting for it, so this keeps the
+/// database from carrying on with statements nobody will read the results
+/// of.
+pub async fn with_deadline<F: Future>(deadline: Instant, f: F) -> F::Output {
+    DEADLINE.scope(deadline, f).await
+}
+
+/// Task matched by [`TaskStore::search`].
+#[derive(Clone, Debug, Serialize, FromRow)]
+pub struct SearchMatch {
+    /// The matching task.
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub task: TaskRecord,
+    /// Similarity of the task's title to the search text, from 0 to 1.
+    pub score: f32,
+}
+
+/// Task with who it belongs to, as exported by [`TaskStore::export_page`].
+#[derive(Clone, Debug, FromRow)]
+pub struct ExportedTask {
+    /// The task.
+    #[sqlx(flatten)]
+    pub task: TaskRecord,
+    /// Owner who created the task, if any.
+    pub owner: Option<String>,
+    /// Owner the task is assigned to, if any.
+    pub assignee: Option<String>,
+}
+
+/// Version of a task, as recorded in its history.
+#[derive(Clone, Debug, FromRow)]
+pub struct TaskVersion {
+    /// Number of the version, counting from 1.
+    pub version: i32,
+    /// Date & time at which the version was recorded.
+    pub recorded_at: DateTime<Utc>,
+    /// What caused the version to be recorded, e.g. `insert` or `revert`.
+    pub action: String,
+    /// Version restored by this version, if it was recorded by a revert.
+    pub reverted_to: Option<i32>,
+    /// State of the task as of this version.
+    #[sqlx(flatten)]
+    pub task: TodoTask,
+}
+
+/// What to do with the history of tasks whose owner's data is erased.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryErasure {
+    /// Keep each version of the tasks, with the title, description and tags
+    /// blanked, as an audit trail.
+    Anonymise,
+    /// Delete every version of the tasks.
+    Delete,
+}
+
+/// Change to the due dates of tasks, made by [`TaskStore::reschedule`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reschedule {
+    /// Move each due date by an offset, which may be negative.
+    Shift(TimeDelta),
+    /// Set every due date to the same date & time.
+    To(DateTime<Utc>),
+}
+
+impl Reschedule {
+    /// The new due date of a task currently due at `due`, if it is within
+    /// `window` around `now`.
+    #[must_use]
+    pub fn apply(
+        self,
+        due: DateTime<Utc>,
+        window: DueWindow,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Shift(offset) => due.checked_add_signed(offset),
+            Self::To(due) => Some(due),
+        }
+        .filter(|due| window.contains(*due, now))
+    }
+}
+
+/// Result of [`TaskStore::reschedule`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RescheduleOutcome {
+    /// The tasks with these IDs were rescheduled.
+    Rescheduled(Vec<Uuid>),
+    /// No tasks were rescheduled, as the new due date of the task with this ID
+    /// would be outside the due window.
+    OutOfWindow(Uuid),
+}
+
+/// Storage backend for tasks.
+///
+/// Methods returning an `Option` give `None` when the task (or the version
+/// of it) doesn't exist. Failures are classified as [`StoreError`]s, which
+/// handlers can respond with directly.
+///
+/// With the `test-util` feature, a [`MockTaskStore`] can stand in for a store
+/// in tests, without a database. Lifetimes of optional references are named
+/// so that it can be generated.
+#[cfg_attr(any(test, feature = "test-util"), mockall::automock)]
+// the mock takes references to optional references
+#[allow(clippy::ref_option_ref)]
+#[async_trait]
+pub trait TaskStore: Debug + Send + Sync {
+    /// Store a new task created by `owner`, returning its ID.
+    async fn create<'a>(&self, task: &TodoTask, owner: Option<&'a str>)
+    -> Result<Uuid, StoreError>;
+
+    /// Store a new task created by `owner` with an ID chosen by the client.
+    ///
+    /// Fails with a unique violation if a task already has the ID.
+    async fn create_with_id<'a>(
+        &self,
+        id: Uuid,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+    ) -> Result<(), StoreError>;
+
+    /// Overwrite a task with a new version of it, returning whether the task
+    /// exists.
+    async fn update(&self, id: Uuid, task: &TodoTask) -> Result<bool, StoreError>;
+
+    /// Change the due date of every task matching all of `filters` as
+    /// `reschedule` says, all in one transaction, unless any new due date
+    /// would be outside `window`.
+    ///
+    /// Each change is recorded in the task's history as a `reschedule`.
+    async fn reschedule(
+        &self,
+        filters: &[FilterExpr],
+        reschedule: Reschedule,
+        window: DueWindow,
+    ) -> Result<RescheduleOutcome, StoreError>;
+
+    /// Store the version of a task kept as `external_id` by another
+    /// application, `source`.
+    ///
+    /// The first time an item is synced, a task is created for it on behalf
+    /// of `owner`. After that, the fields of the task which are synced are
+    /// updated, with `rule` deciding between the two versions if the task has
+    /// also been changed locally since it was last synced.
+    async fn upsert_external<'a>(
+        &self,
+        source: &str,
+        external_id: &str,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+        rule: ConflictRule,
+    ) -> Result<SyncOutcome, StoreError>;
+
+    /// Count the tasks created by `owner` which are neither complete nor
+    /// cancelled.
+    async fn count_open<'a>(&self, owner: Option<&'a str>) -> Result<i64, StoreError>;
+
+    /// List all tasks created by `owner`, ordered by due date.
+    async fn owned(&self, owner: &str) -> Result<Vec<TaskRecord>, StoreError>;
+
+    /// Erase all tasks created by `owner`, returning how many there were.
+    ///
+    /// Their links, events and snapshots are deleted, and their history is
+    /// dealt with according to `history`. Other tasks assigned to `owner` are
+    /// unassigned, and time `owner` tracked on them, their pins and mentions
+    /// of them are deleted.
+    async fn erase(&self, owner: &str, history: HistoryErasure) -> Result<u64, StoreError>;
+
+    /// Get the current state of a task.
+    async fn get(&self, id: Uuid) -> Result<Option<TodoTask>, StoreError>;
+
+    /// Get the current state of each of the tasks `ids` which exist, in no
+    /// particular order.
+    async fn get_many(&self, ids: &[Uuid]) -> Result<Vec<TaskRecord>, StoreError>;
+
+    /// Get the milestones reached by each of the tasks `ids` which exist, in
+    /// no particular order.
+    async fn milestones(&self, ids: &[Uuid]) -> Result<Vec<TaskMilestones>, StoreError>;
+
+    /// List all tasks matching every one of `filters`, ordered by due date.
+    ///
+    /// Tasks pinned by `pinned_by` come first.
+    async fn list<'a>(
+        &self,
+        filters: &[FilterExpr],
+        pinned_by: Option<&'a str>,
+    ) -> Result<Vec<TaskRecord>, StoreError>;
+
+    /// List up to `limit` tasks, in order of ID, starting after the task with
+    /// ID `after`, if any.
+    ///
+    /// Every task can be exported by paging through them this way, without
+    /// holding them all in memory.
+    async fn export_page(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<ExportedTask>, StoreError>;
+
+    /// Fuzzily search task titles for `title`, best matches first.
+    ///
+    /// Only tasks with a similarity of at least `threshold` are returned.
+    async fn search(&self, title: &str, threshold: f32) -> Result<Vec<SearchMatch>, StoreError>;
+
+    /// Find open tasks which `task` may duplicate, most similar first.
+    ///
+    /// Candidates have a title similarity of at least `threshold` and are due
+    /// within a day of `task`.
+    async fn find_duplicates(
+        &self,
+        task: &TodoTask,
+        threshold: f32,
+    ) -> Result<Vec<TaskRecord>, StoreError>;
+
+    /// Get all links from or to a task.
+    async fn links(&self, id: Uuid) -> Result<Option<Vec<TaskLink>>, StoreError>;
+
+    /// Store a new link between tasks.
+    async fn add_link(&self, link: &TaskLink) -> Result<(), StoreError>;
+
+    /// Remove a link between tasks, returning whether it existed.
+    async fn remove_link(&self, link: &TaskLink) -> Result<bool, StoreError>;
+
+    /// Get the versions of a task numbered within `versions`, in order.
+    async fn history(
+        &self,
+        id: Uuid,
+        versions: RangeInclusive<i64>,
+    ) -> Result<Vec<TaskVersion>, StoreError>;
+
+    /// Get a single version of a task.
+    async fn version(&self, id: Uuid, version: i32) -> Result<Option<TaskVersion>, StoreError>;
+
+    /// Get the number of the latest version of a task, if it exists.
+    ///
+    /// This is much cheaper than getting the task itself.
+    async fn latest_version(&self, id: Uuid) -> Result<Option<i32>, StoreError>;
+
+    /// Get a task as it was created, if it still exists and was created by
+    /// `owner`.
+    async fn created<'a>(
+        &self,
+        id: Uuid,
+        owner: Option<&'a str>,
+    ) -> Result<Option<TodoTask>, StoreError>;
+
+    /// Assign a task created by `owner` to `assignee`, or unassign it if
+    /// `None`, returning whether `owner` has such a task.
+    ///
+    /// Assignments aren't recorded in the task's history.
+    async fn assign<'a>(
+        &self,
+        id: Uuid,
+        owner: Option<&'a str>,
+        assignee: Option<&'a str>,
+    ) -> Result<bool, StoreError>;
+
+    /// Pin a task for `owner`, or unpin it if `pinned` is false, returning
+    /// whether the task exists.
+    async fn set_pinned(&self, id: Uuid, owner: &str, pinned: bool) -> Result<bool, StoreError>;
+
+    /// Check whether `owner` has pinned a task.
+    async fn pinned(&self, id: Uuid, owner: &str) -> Result<bool, StoreError>;
+
+    /// List the most recent `limit` creations and completions of tasks
+    /// created by `owner`, newest first.
+    async fn activity<'a>(
+        &self,
+        owner: Option<&'a str>,
+        limit: i64,
+    ) -> Result<Vec<Activity>, StoreError>;
+
+    /// List the `limit` most recently created tasks of `owner`, newest
+    /// first.
+    async fn new_tasks<'a>(
+        &self,
+        owner: Option<&'a str>,
+        limit: i64,
+    ) -> Result<Vec<NewTask>, StoreError>;
+
+    /// List the tasks whose descriptions mention `user`, most recently
+    /// mentioned first.
+    async fn mentions(&self, user: &str) -> Result<Vec<Mention>, StoreError>;
+
+    /// Start a timer tracking time spent by `owner` on a task, returning the
+    /// running entry.
+    ///
+    /// Fails with a unique violation if `owner` already has a timer running
+    /// on the task.
+    async fn start_timer(&self, id: Uuid, owner: &str) -> Result<Option<TimeEntry>, StoreError>;
+
+    /// Stop `owner`'s timer on a task, returning the finished entry, or `None`
+    /// if no timer was running.
+    async fn stop_timer(&self, id: Uuid, owner: &str) -> Result<Option<TimeEntry>, StoreError>;
+
+    /// Total number of seconds tracked on a task by anyone, including running
+    /// timers up to now.
+    async fn tracked_seconds(&self, id: Uuid) -> Result<i64, StoreError>;
+
+    /// Get the time entries of `owner` started from `from` until `to`, in
+    /// order.
+    async fn timesheet(
+        &self,
+        owner: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<TimesheetEntry>, StoreError>;
+
+    /// Attach a scanned file to a task, quarantining it if it is infected,
+    /// or return `None` if the task doesn't exist.
+    async fn add_attachment(
+        &self,
+        id: Uuid,
+        attachment: &NewAttachment,
+    ) -> Result<Option<Attachment>, StoreError>;
+
+    /// List the files attached to a task, oldest first, or `None` if the task
+    /// doesn't exist.
+    async fn attachments(&self, id: Uuid) -> Result<Option<Vec<Attachment>>, StoreError>;
+
+    /// Get a file attached to a task, with its content.
+    async fn attachment(
+        &self,
+        id: Uuid,
+        attachment_id: Uuid,
+    ) -> Result<Option<(Attachment, Vec<u8>)>, StoreError>;
+
+    /// Remove a file attached to a task, returning whether it was.
+    async fn remove_attachment(&self, id: Uuid, attachment_id: Uuid) -> Result<bool, StoreError>;
+
+    /// Get the thumbnail of a file attached to a task, or `None` if the
+    /// file doesn't exist.
+    async fn thumbnail(
+        &self,
+        id: Uuid,
+        attachment_id: Uuid,
+    ) -> Result<Option<Thumbnail>, StoreError>;
+
+    /// Summarise the storage taken by each owner's attachments, largest
+    /// first.
+    async fn storage_usage(&self) -> Result<Vec<StorageUsage>, StoreError>;
+
+    /// Total size in bytes of the files attached to tasks created by
+    /// `owner`.
+    async fn storage_used<'a>(&self, owner: Option<&'a str>) -> Result<i64, StoreError>;
+
+    /// Restore a task to the state it had as of `version`.
+    ///
+    /// The revert is recorded in the task's history as a new version.
+    /// Returns the restored state.
+    async fn revert(&self, id: Uuid, version: i32) -> Result<Option<TodoTask>, StoreError>;
+
+    /// Count task activity in each `bucket` of time from `from` to `to`, in
+    /// order.
+    async fn burndown(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        bucket: Bucket,
+    ) -> Result<Vec<BurndownBucket>, StoreError>;
+
+    /// Get the dependency graph of the tasks matching every one of `filters`.
+    async fn graph(&self, filters: &[FilterExpr]) -> Result<TaskGraph, StoreError>;
+
+    /// Summarise the open tasks of each assignee, busiest first, as of the
+    /// last refresh of the statistics.
+    async fn workload(&self) -> Result<Refreshed<Vec<Workload>>, StoreError>;
+
+    /// Compare the estimated with the actual effort of tasks in each group,
+    /// ordered by group, as of the last refresh of the statistics.
+    async fn estimate_variance(
+        &self,
+        grouping: EstimateGrouping,
+    ) -> Result<Refreshed<Vec<EstimateVariance>>, StoreError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reschedule() {
+        let due = "2026-10-16T12:00:00Z".parse().unwrap();
+        let window = DueWindow::years(1);
+        assert_eq!(
+            Reschedule::Shift(TimeDelta::days(-2)).apply(due, window, due),
+            Some(due - TimeDelta::days(2))
+        );
+        assert_eq!(
+            Reschedule::To(due).apply(due + TimeDelta::days(7), window, due),
+            Some(due)
+        );
+        // outside the due window
+        assert_eq!(
+            Reschedule::Shift(TimeDelta::days(367)).apply(due, window, due),
+            None
+        );
+        assert_eq!(
+            Reschedule::Shift(TimeDelta::MAX).apply(due, window, due),
+            None
+        );
+    }
+}
@@ -0,0 +1,594 @@
+use std::{num::NonZeroU32, ops::RangeInclusive, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool, types::Json};
+use uuid::Uuid;
+
+use super::{
+    ExportedTask, HistoryErasure, Reschedule, RescheduleOutcome, SearchMatch, TaskStore,
+    TaskVersion,
+    postgres::{
+        PgTaskStore, describe_reschedule, describe_revert, fetch_version, find_external,
+        insert_task, lock_matching, lock_task, record_external, stamped, update_task,
+    },
+};
+use crate::{
+    DueWindow, FilterExpr, StoreError, TaskDiff, TaskEvent, TaskLink, TaskRecord, TodoTask,
+    attachments::{Attachment, NewAttachment},
+    calendar::WorkCalendar,
+    clock::Clock,
+    feed::Activity,
+    graph::TaskGraph,
+    hooks::NewTask,
+    mentions::Mention,
+    sla::TaskMilestones,
+    stats::{
+        Bucket, BurndownBucket, EstimateGrouping, EstimateVariance, Refreshed, StorageUsage,
+        Workload,
+    },
+    sync::{ConflictRule, SyncOutcome, reconcile, synced_changes},
+    tasks::StoredTask,
+    thumbnails::Thumbnail,
+    tracking::{TimeEntry, TimesheetEntry},
+};
+
+/// [`TaskStore`] deriving each task's state from an append-only stream of
+/// [`TaskEvent`]s in the `task_events` table.
+///
+/// Each event is hashed together with the hash of the event before it, so
+/// any modification of a task's stream is evident.
+/// The state of a task is snapshotted every `snapshot_interval` events to
+/// bound how many events must be replayed to read it.
+///
+/// Queries over many tasks are served from the `tasks` table, which is kept
+/// as a projection of the latest state of each task in the same transaction
+/// as each event is appended.
+#[derive(Clone, Debug)]
+pub struct EventTaskStore {
+    /// Store over the `tasks` projection.
+    projection: PgTaskStore,
+    /// Number of events between snapshots of a task's state.
+    snapshot_interval: NonZeroU32,
+}
+
+impl EventTaskStore {
+    /// Create a store using the database behind `pool`.
+    #[must_use]
+    pub fn new(pool: PgPool, snapshot_interval: NonZeroU32) -> Self {
+        Self {
+            projection: PgTaskStore::new(pool),
+            snapshot_interval,
+        }
+    }
+
+    /// Enable or disable row-level security.
+    ///
+    /// See [`PgTaskStore::with_row_level_security`].
+    #[must_use]
+    pub fn with_row_level_security(mut self, enabled: bool) -> Self {
+        self.projection = self.projection.with_row_level_security(enabled);
+        self
+    }
+
+    /// Count tasks as overdue in working hours only.
+    ///
+    /// See [`PgTaskStore::with_overdue_hours`].
+    #[must_use]
+    pub fn with_overdue_hours(mut self, hours: Option<WorkCalendar>) -> Self {
+        self.projection = self.projection.with_overdue_hours(hours);
+        self
+    }
+
+    /// Tell the time that changes are made at by `clock`.
+    ///
+    /// See [`PgTaskStore::with_clock`].
+    #[must_use]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.projection = self.projection.with_clock(clock);
+        self
+    }
+
+    /// Read from a replica.
+    ///
+    /// See [`PgTaskStore::with_replica`].
+    #[must_use]
+    pub fn with_replica(mut self, pool: PgPool, max_lag: Duration) -> Self {
+        self.projection = self.projection.with_replica(pool, max_lag);
+        self
+    }
+
+    /// Start event streams for tasks which don't have one.
+    ///
+    /// Tasks written with [`PgTaskStore`] have no events; this records the
+    /// current state of each such task as its creation event.
+    /// Returns the number of streams started.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn import_untracked(&self) -> Result<u64, sqlx::Error> {
+        let untracked: Vec<TaskRecord> = sqlx::query_as(
+            "SELECT id, title, description, status, due, tags, estimate, progress, colour, title_cy, description_cy,
+                completed_at, status_reason, custom_status
+            FROM tasks
+            WHERE NOT EXISTS (SELECT 1 FROM task_events WHERE task_id = tasks.id)",
+        )
+        .fetch_all(&mut *self.projection.begin().await?)
+        .await?;
+
+        let mut tx = self.projection.begin().await?;
+        for TaskRecord { id, task } in &untracked {
+            let event = TaskEvent::Created {
+                task: Box::new(task.clone()),
+            };
+            self.append(&mut tx, *id, 1, &event, task).await?;
+        }
+        tx.commit().await?;
+
+        Ok(untracked.len() as u64)
+    }
+
+    /// Load the current state of a task and the sequence number of its latest
+    /// event.
+    async fn load(
+        conn: &mut PgConnection,
+        id: Uuid,
+    ) -> Result<Option<(TodoTask, i32)>, sqlx::Error> {
+        let snapshot: Option<(i32, Json<StoredTask>)> = sqlx::query_as(
+            "SELECT sequence, state
+            FROM task_snapshots
+            WHERE task_id = $1
+            ORDER BY sequence DESC
+            LIMIT 1",
+        )
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?;
+        let (mut sequence, mut state) = match snapshot {
+            Some((sequence, Json(StoredTask(task)))) => (sequence, Some(task)),
+            None => (0, None),
+        };
+
+        let events: Vec<(i32, Json<TaskEvent>, DateTime<Utc>)> = sqlx::query_as(
+            "SELECT sequence, payload, recorded_at
+            FROM task_events
+            WHERE task_id = $1 AND sequence > $2
+            ORDER BY sequence",
+        )
+        .bind(id)
+        .bind(sequence)
+        .fetch_all(&mut *conn)
+        .await?;
+        for (event_sequence, Json(event), recorded_at) in events {
+            state = event.apply(state, recorded_at);
+            sequence = event_sequence;
+        }
+
+        Ok(state.map(|task| (task, sequence)))
+    }
+
+    /// Append `event` to a task's stream as number `sequence`.
+    ///
+    /// `state` is the state of the task after the event, which is
+    /// snapshotted if one is due.
+    async fn append(
+        &self,
+        conn: &mut PgConnection,
+        id: Uuid,
+        sequence: i32,
+        event: &TaskEvent,
+        state: &TodoTask,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO task_events (task_id, sequence, payload, hash, recorded_at)
+            SELECT $1, $2, $3, sha256(
+                coalesce(
+                    (SELECT hash FROM task_events WHERE task_id = $1 AND sequence = $2 - 1),
+                    ''::bytea
+                ) || convert_to($3::text, 'UTF8')
+            ), $4",
+        )
+        .bind(id)
+        .bind(sequence)
+        .bind(Json(event))
+        .bind(self.projection.clock.now())
+        .execute(&mut *conn)
+        .await?;
+
+        if sequence.unsigned_abs() % self.snapshot_interval == 0 {
+            sqlx::query(
+                "INSERT INTO task_snapshots (task_id, sequence, state) VALUES ($1, $2, $3)",
+            )
+            .bind(id)
+            .bind(sequence)
+            .bind(Json(state))
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TaskStore for EventTaskStore {
+    async fn create<'a>(
+        &self,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+    ) -> Result<Uuid, StoreError> {
+        let id = Uuid::new_v4();
+        self.create_with_id(id, task, owner).await?;
+        Ok(id)
+    }
+
+    async fn create_with_id<'a>(
+        &self,
+        id: Uuid,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+    ) -> Result<(), StoreError> {
+        let task = stamped(task, self.projection.clock.now());
+        let mut tx = self.projection.begin().await?;
+        // the task must exist before its events, for row-level security
+        insert_task(&mut tx, id, &task, owner).await?;
+        let event = TaskEvent::Created {
+            task: Box::new(task.clone()),
+        };
+        self.append(&mut tx, id, 1, &event, &task).await?;
+        Ok(tx.commit().await?)
+    }
+
+    async fn update(&self, id: Uuid, task: &TodoTask) -> Result<bool, StoreError> {
+        let mut tx = self.projection.begin().await?;
+
+        // lock the task so that concurrent appends to its stream serialize
+        let locked = lock_task(&mut tx, id).await?;
+        let (true, Some((mut current, sequence))) = (locked, Self::load(&mut tx, id).await?) else {
+            return Ok(false);
+        };
+
+        let changes = TaskDiff::between(&current, task);
+        if !changes.is_empty() {
+            changes.apply(&mut current, self.projection.clock.now());
+            let event = TaskEvent::Changed { changes };
+            self.append(&mut tx, id, sequence + 1, &event, &current)
+                .await?;
+            update_task(&mut tx, id, &current).await?;
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn reschedule(
+        &self,
+        filters: &[FilterExpr],
+        reschedule: Reschedule,
+        window: DueWindow,
+    ) -> Result<RescheduleOutcome, StoreError> {
+        let mut tx = self.projection.begin().await?;
+
+        // locking the tasks serializes appends to their streams
+        let ids: Vec<_> = lock_matching(&mut tx, filters)
+            .await?
+            .into_iter()
+            .map(|record| record.id)
+            .collect();
+        describe_reschedule(&mut tx).await?;
+        let now = self.projection.clock.now();
+        for &id in &ids {
+            let Some((mut current, sequence)) = Self::load(&mut tx, id).await? else {
+                continue;
+            };
+            let Some(due) = reschedule.apply(*current.due(), window, now) else {
+                return Ok(RescheduleOutcome::OutOfWindow(id));
+            };
+            let mut task = current.clone();
+            task.set_due(&due);
+            let changes = TaskDiff::between(&current, &task);
+            if !changes.is_empty() {
+                changes.apply(&mut current, now);
+                let event = TaskEvent::Changed { changes };
+                self.append(&mut tx, id, sequence + 1, &event, &current)
+                    .await?;
+                update_task(&mut tx, id, &current).await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(RescheduleOutcome::Rescheduled(ids))
+    }
+
+    async fn upsert_external<'a>(
+        &self,
+        source: &str,
+        external_id: &str,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+        rule: ConflictRule,
+    ) -> Result<SyncOutcome, StoreError> {
+        let mut tx = self.projection.begin().await?;
+        let Some((id, synced_version)) = find_external(&mut tx, source, external_id).await? else {
+            let id = Uuid::new_v4();
+            let task = stamped(task, self.projection.clock.now());
+            insert_task(&mut tx, id, &task, owner).await?;
+            let event = TaskEvent::Created {
+                task: Box::new(task.clone()),
+            };
+            self.append(&mut tx, id, 1, &event, &task).await?;
+            record_external(&mut tx, id, source, external_id).await?;
+            tx.commit().await?;
+            return Ok(SyncOutcome::Created);
+        };
+
+        // lock the task so that concurrent appends to its stream serialize
+        let locked = lock_task(&mut tx, id).await?;
+        let (true, Some((mut current, sequence))) = (locked, Self::load(&mut tx, id).await?) else {
+            return Ok(SyncOutcome::Skipped);
+        };
+        let base = fetch_version(&mut tx, id, synced_version)
+            .await?
+            .map_or_else(|| current.clone(), |version| version.task);
+
+        let (outcome, changes) = reconcile(&base, &current, task, rule);
+        if outcome == SyncOutcome::Updated {
+            changes.apply(&mut current, self.projection.clock.now());
+            let event = TaskEvent::Changed { changes };
+            self.append(&mut tx, id, sequence + 1, &event, &current)
+                .await?;
+            update_task(&mut tx, id, &current).await?;
+        }
+        if synced_changes(&current, task).is_empty() {
+            record_external(&mut tx, id, source, external_id).await?;
+        }
+        tx.commit().await?;
+        Ok(outcome)
+    }
+
+    async fn count_open<'a>(&self, owner: Option<&'a str>) -> Result<i64, StoreError> {
+        self.projection.count_open(owner).await
+    }
+
+    async fn owned(&self, owner: &str) -> Result<Vec<TaskRecord>, StoreError> {
+        self.projection.owned(owner).await
+    }
+
+    async fn erase(&self, owner: &str, history: HistoryErasure) -> Result<u64, StoreError> {
+        self.projection.erase(owner, history).await
+    }
+
+    async fn get(&self, id: Uuid) -> Result<Option<TodoTask>, StoreError> {
+        let loaded = Self::load(&mut *self.projection.begin().await?, id).await?;
+        Ok(loaded.map(|(task, _)| task))
+    }
+
+    async fn get_many(&self, ids: &[Uuid]) -> Result<Vec<TaskRecord>, StoreError> {
+        // the projection is written along with each event
+        self.projection.get_many(ids).await
+    }
+
+    async fn milestones(&self, ids: &[Uuid]) -> Result<Vec<TaskMilestones>, StoreError> {
+        self.projection.milestones(ids).await
+    }
+
+    async fn list<'a>(
+        &self,
+        filters: &[FilterExpr],
+        pinned_by: Option<&'a str>,
+    ) -> Result<Vec<TaskRecord>, StoreError> {
+        self.projection.list(filters, pinned_by).await
+    }
+
+    async fn export_page(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<ExportedTask>, StoreError> {
+        self.projection.export_page(after, limit).await
+    }
+
+    async fn search(&self, title: &str, threshold: f32) -> Result<Vec<SearchMatch>, StoreError> {
+        self.projection.search(title, threshold).await
+    }
+
+    async fn find_duplicates(
+        &self,
+        task: &TodoTask,
+        threshold: f32,
+    ) -> Result<Vec<TaskRecord>, StoreError> {
+        self.projection.find_duplicates(task, threshold).await
+    }
+
+    async fn links(&self, id: Uuid) -> Result<Option<Vec<TaskLink>>, StoreError> {
+        self.projection.links(id).await
+    }
+
+    async fn add_link(&self, link: &TaskLink) -> Result<(), StoreError> {
+        self.projection.add_link(link).await
+    }
+
+    async fn remove_link(&self, link: &TaskLink) -> Result<bool, StoreError> {
+        self.projection.remove_link(link).await
+    }
+
+    async fn history(
+        &self,
+        id: Uuid,
+        versions: RangeInclusive<i64>,
+    ) -> Result<Vec<TaskVersion>, StoreError> {
+        self.projection.history(id, versions).await
+    }
+
+    async fn version(&self, id: Uuid, version: i32) -> Result<Option<TaskVersion>, StoreError> {
+        self.projection.version(id, version).await
+    }
+
+    async fn latest_version(&self, id: Uuid) -> Result<Option<i32>, StoreError> {
+        self.projection.latest_version(id).await
+    }
+
+    async fn created<'a>(
+        &self,
+        id: Uuid,
+        owner: Option<&'a str>,
+    ) -> Result<Option<TodoTask>, StoreError> {
+        self.projection.created(id, owner).await
+    }
+
+    async fn assign<'a>(
+        &self,
+        id: Uuid,
+        owner: Option<&'a str>,
+        assignee: Option<&'a str>,
+    ) -> Result<bool, StoreError> {
+        // assignments are kept in the projection, not the event stream
+        self.projection.assign(id, owner, assignee).await
+    }
+
+    async fn set_pinned(&self, id: Uuid, owner: &str, pinned: bool) -> Result<bool, StoreError> {
+        // pins are kept alongside the projection, not in the event stream
+        self.projection.set_pinned(id, owner, pinned).await
+    }
+
+    async fn pinned(&self, id: Uuid, owner: &str) -> Result<bool, StoreError> {
+        self.projection.pinned(id, owner).await
+    }
+
+    async fn activity<'a>(
+        &self,
+        owner: Option<&'a str>,
+        limit: i64,
+    ) -> Result<Vec<Activity>, StoreError> {
+        self.projection.activity(owner, limit).await
+    }
+
+    async fn new_tasks<'a>(
+        &self,
+        owner: Option<&'a str>,
+        limit: i64,
+    ) -> Result<Vec<NewTask>, StoreError> {
+        self.projection.new_tasks(owner, limit).await
+    }
+
+    async fn mentions(&self, user: &str) -> Result<Vec<Mention>, StoreError> {
+        self.projection.mentions(user).await
+    }
+
+    async fn start_timer(&self, id: Uuid, owner: &str) -> Result<Option<TimeEntry>, StoreError> {
+        // time entries are kept alongside the projection, not in the event
+        // stream
+        self.projection.start_timer(id, owner).await
+    }
+
+    async fn stop_timer(&self, id: Uuid, owner: &str) -> Result<Option<TimeEntry>, StoreError> {
+        self.projection.stop_timer(id, owner).await
+    }
+
+    async fn tracked_seconds(&self, id: Uuid) -> Result<i64, StoreError> {
+        self.projection.tracked_seconds(id).await
+    }
+
+    async fn timesheet(
+        &self,
+        owner: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<TimesheetEntry>, StoreError> {
+        self.projection.timesheet(owner, from, to).await
+    }
+
+    async fn add_attachment(
+        &self,
+        id: Uuid,
+        attachment: &NewAttachment,
+    ) -> Result<Option<Attachment>, StoreError> {
+        // attachments are kept alongside the projection, not in the event
+        // stream
+        self.projection.add_attachment(id, attachment).await
+    }
+
+    async fn attachments(&self, id: Uuid) -> Result<Option<Vec<Attachment>>, StoreError> {
+        self.projection.attachments(id).await
+    }
+
+    async fn attachment(
+        &self,
+        id: Uuid,
+        attachment_id: Uuid,
+    ) -> Result<Option<(Attachment, Vec<u8>)>, StoreError> {
+        self.projection.attachment(id, attachment_id).await
+    }
+
+    async fn remove_attachment(&self, id: Uuid, attachment_id: Uuid) -> Result<bool, StoreError> {
+        self.projection.remove_attachment(id, attachment_id).await
+    }
+
+    async fn thumbnail(
+        &self,
+        id: Uuid,
+        attachment_id: Uuid,
+    ) -> Result<Option<Thumbnail>, StoreError> {
+        self.projection.thumbnail(id, attachment_id).await
+    }
+
+    async fn storage_usage(&self) -> Result<Vec<StorageUsage>, StoreError> {
+        self.projection.storage_usage().await
+    }
+
+    async fn storage_used<'a>(&self, owner: Option<&'a str>) -> Result<i64, StoreError> {
+        self.projection.storage_used(owner).await
+    }
+
+    async fn revert(&self, id: Uuid, version: i32) -> Result<Option<TodoTask>, StoreError> {
+        let mut tx = self.projection.begin().await?;
+
+        // lock the task so that concurrent appends to its stream serialize
+        let locked = lock_task(&mut tx, id).await?;
+        let Some(target) = fetch_version(&mut tx, id, version).await? else {
+            return Ok(None);
+        };
+        let (true, Some((mut current, sequence))) = (locked, Self::load(&mut tx, id).await?) else {
+            return Ok(None);
+        };
+
+        let changes = TaskDiff::between(&current, &target.task);
+        if !changes.is_empty() {
+            changes.apply(&mut current, self.projection.clock.now());
+            let event = TaskEvent::Changed { changes };
+            self.append(&mut tx, id, sequence + 1, &event, &current)
+                .await?;
+        }
+        // the projection is updated even if unchanged, to record the revert
+        describe_revert(&mut tx, version).await?;
+        update_task(&mut tx, id, &current).await?;
+
+        tx.commit().await?;
+        Ok(Some(current))
+    }
+
+    async fn burndown(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        bucket: Bucket,
+    ) -> Result<Vec<BurndownBucket>, StoreError> {
+        self.projection.burndown(from, to, bucket).await
+    }
+
+    async fn graph(&self, filters: &[FilterExpr]) -> Result<TaskGraph, StoreError> {
+        self.projection.graph(filters).await
+    }
+
+    async fn workload(&self) -> Result<Refreshed<Vec<Workload>>, StoreError> {
+        self.projection.workload().await
+    }
+
+    async fn estimate_variance(
+        &self,
+        grouping: EstimateGrouping,
+    ) -> Result<Refreshed<Vec<EstimateVariance>>, StoreError> {
+        self.projection.estimate_variance(grouping).await
+    }
+}
@@ -0,0 +1,327 @@
+use std::{future::Future, ops::RangeInclusive, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::{
+    ExportedTask, HistoryErasure, Reschedule, RescheduleOutcome, SearchMatch, TaskStore,
+    TaskVersion,
+};
+use crate::{
+    DueWindow, FilterExpr, StoreError, TaskLink, TaskRecord, TodoTask,
+    attachments::{Attachment, NewAttachment},
+    breaker::CircuitBreaker,
+    feed::Activity,
+    graph::TaskGraph,
+    hooks::NewTask,
+    mentions::Mention,
+    sla::TaskMilestones,
+    stats::{
+        Bucket, BurndownBucket, EstimateGrouping, EstimateVariance, Refreshed, StorageUsage,
+        Workload,
+    },
+    sync::{ConflictRule, SyncOutcome},
+    thumbnails::Thumbnail,
+    tracking::{TimeEntry, TimesheetEntry},
+};
+
+/// [`TaskStore`] guarding another store with a [`CircuitBreaker`].
+///
+/// The result of each operation is recorded in the breaker, and while it is
+/// open operations fail straight away with [`StoreError::Unavailable`]
+/// rather than waiting for the database.
+#[derive(Debug)]
+pub struct GuardedTaskStore {
+    /// Store whose operations are guarded.
+    inner: Arc<dyn TaskStore>,
+    /// Breaker recording the outcome of operations.
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl GuardedTaskStore {
+    /// Guard the operations of `inner` with `breaker`.
+    #[must_use]
+    pub fn new(inner: Arc<dyn TaskStore>, breaker: Arc<CircuitBreaker>) -> Self {
+        Self { inner, breaker }
+    }
+
+    /// Run an operation, unless the breaker is open, recording its result.
+    async fn guard<T>(
+        &self,
+        operation: impl Future<Output = Result<T, StoreError>> + Send,
+    ) -> Result<T, StoreError> {
+        if self.breaker.is_open() {
+            return Err(StoreError::Unavailable(sqlx::Error::PoolTimedOut));
+        }
+        let result = operation.await;
+        self.breaker
+            .record_outage(matches!(result, Err(StoreError::Unavailable(_))));
+        result
+    }
+}
+
+#[async_trait]
+impl TaskStore for GuardedTaskStore {
+    async fn create<'a>(
+        &self,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+    ) -> Result<Uuid, StoreError> {
+        self.guard(self.inner.create(task, owner)).await
+    }
+
+    async fn create_with_id<'a>(
+        &self,
+        id: Uuid,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+    ) -> Result<(), StoreError> {
+        self.guard(self.inner.create_with_id(id, task, owner)).await
+    }
+
+    async fn update(&self, id: Uuid, task: &TodoTask) -> Result<bool, StoreError> {
+        self.guard(self.inner.update(id, task)).await
+    }
+
+    async fn reschedule(
+        &self,
+        filters: &[FilterExpr],
+        reschedule: Reschedule,
+        window: DueWindow,
+    ) -> Result<RescheduleOutcome, StoreError> {
+        self.guard(self.inner.reschedule(filters, reschedule, window))
+            .await
+    }
+
+    async fn upsert_external<'a>(
+        &self,
+        source: &str,
+        external_id: &str,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+        rule: ConflictRule,
+    ) -> Result<SyncOutcome, StoreError> {
+        self.guard(
+            self.inner
+                .upsert_external(source, external_id, task, owner, rule),
+        )
+        .await
+    }
+
+    async fn count_open<'a>(&self, owner: Option<&'a str>) -> Result<i64, StoreError> {
+        self.guard(self.inner.count_open(owner)).await
+    }
+
+    async fn owned(&self, owner: &str) -> Result<Vec<TaskRecord>, StoreError> {
+        self.guard(self.inner.owned(owner)).await
+    }
+
+    async fn erase(&self, owner: &str, history: HistoryErasure) -> Result<u64, StoreError> {
+        self.guard(self.inner.erase(owner, history)).await
+    }
+
+    async fn get(&self, id: Uuid) -> Result<Option<TodoTask>, StoreError> {
+        self.guard(self.inner.get(id)).await
+    }
+
+    async fn get_many(&self, ids: &[Uuid]) -> Result<Vec<TaskRecord>, StoreError> {
+        self.guard(self.inner.get_many(ids)).await
+    }
+
+    async fn milestones(&self, ids: &[Uuid]) -> Result<Vec<TaskMilestones>, StoreError> {
+        self.guard(self.inner.milestones(ids)).await
+    }
+
+    async fn list<'a>(
+        &self,
+        filters: &[FilterExpr],
+        pinned_by: Option<&'a str>,
+    ) -> Result<Vec<TaskRecord>, StoreError> {
+        self.guard(self.inner.list(filters, pinned_by)).await
+    }
+
+    async fn export_page(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<ExportedTask>, StoreError> {
+        self.guard(self.inner.export_page(after, limit)).await
+    }
+
+    async fn search(&self, title: &str, threshold: f32) -> Result<Vec<SearchMatch>, StoreError> {
+        self.guard(self.inner.search(title, threshold)).await
+    }
+
+    async fn find_duplicates(
+        &self,
+        task: &TodoTask,
+        threshold: f32,
+    ) -> Result<Vec<TaskRecord>, StoreError> {
+        self.guard(self.inner.find_duplicates(task, threshold))
+            .await
+    }
+
+    async fn links(&self, id: Uuid) -> Result<Option<Vec<TaskLink>>, StoreError> {
+        self.guard(self.inner.links(id)).await
+    }
+
+    async fn add_link(&self, link: &TaskLink) -> Result<(), StoreError> {
+        self.guard(self.inner.add_link(link)).await
+    }
+
+    async fn remove_link(&self, link: &TaskLink) -> Result<bool, StoreError> {
+        self.guard(self.inner.remove_link(link)).await
+    }
+
+    async fn history(
+        &self,
+        id: Uuid,
+        versions: RangeInclusive<i64>,
+    ) -> Result<Vec<TaskVersion>, StoreError> {
+        self.guard(self.inner.history(id, versions)).await
+    }
+
+    async fn version(&self, id: Uuid, version: i32) -> Result<Option<TaskVersion>, StoreError> {
+        self.guard(self.inner.version(id, version)).await
+    }
+
+    async fn latest_version(&self, id: Uuid) -> Result<Option<i32>, StoreError> {
+        self.guard(self.inner.latest_version(id)).await
+    }
+
+    async fn created<'a>(
+        &self,
+        id: Uuid,
+        owner: Option<&'a str>,
+    ) -> Result<Option<TodoTask>, StoreError> {
+        self.guard(self.inner.created(id, owner)).await
+    }
+
+    async fn assign<'a>(
+        &self,
+        id: Uuid,
+        owner: Option<&'a str>,
+        assignee: Option<&'a str>,
+    ) -> Result<bool, StoreError> {
+        self.guard(self.inner.assign(id, owner, assignee)).await
+    }
+
+    async fn set_pinned(&self, id: Uuid, owner: &str, pinned: bool) -> Result<bool, StoreError> {
+        self.guard(self.inner.set_pinned(id, owner, pinned)).await
+    }
+
+    async fn pinned(&self, id: Uuid, owner: &str) -> Result<bool, StoreError> {
+        self.guard(self.inner.pinned(id, owner)).await
+    }
+
+    async fn activity<'a>(
+        &self,
+        owner: Option<&'a str>,
+        limit: i64,
+    ) -> Result<Vec<Activity>, StoreError> {
+        self.guard(self.inner.activity(owner, limit)).await
+    }
+
+    async fn new_tasks<'a>(
+        &self,
+        owner: Option<&'a str>,
+        limit: i64,
+    ) -> Result<Vec<NewTask>, StoreError> {
+        self.guard(self.inner.new_tasks(owner, limit)).await
+    }
+
+    async fn mentions(&self, user: &str) -> Result<Vec<Mention>, StoreError> {
+        self.guard(self.inner.mentions(user)).await
+    }
+
+    async fn start_timer(&self, id: Uuid, owner: &str) -> Result<Option<TimeEntry>, StoreError> {
+        self.guard(self.inner.start_timer(id, owner)).await
+    }
+
+    async fn stop_timer(&self, id: Uuid, owner: &str) -> Result<Option<TimeEntry>, StoreError> {
+        self.guard(self.inner.stop_timer(id, owner)).await
+    }
+
+    async fn tracked_seconds(&self, id: Uuid) -> Result<i64, StoreError> {
+        self.guard(self.inner.tracked_seconds(id)).await
+    }
+
+    async fn timesheet(
+        &self,
+        owner: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<TimesheetEntry>, StoreError> {
+        self.guard(self.inner.timesheet(owner, from, to)).await
+    }
+
+    async fn add_attachment(
+        &self,
+        id: Uuid,
+        attachment: &NewAttachment,
+    ) -> Result<Option<Attachment>, StoreError> {
+        self.guard(self.inner.add_attachment(id, attachment)).await
+    }
+
+    async fn attachments(&self, id: Uuid) -> Result<Option<Vec<Attachment>>, StoreError> {
+        self.guard(self.inner.attachments(id)).await
+    }
+
+    async fn attachment(
+        &self,
+        id: Uuid,
+        attachment_id: Uuid,
+    ) -> Result<Option<(Attachment, Vec<u8>)>, StoreError> {
+        self.guard(self.inner.attachment(id, attachment_id)).await
+    }
+
+    async fn remove_attachment(&self, id: Uuid, attachment_id: Uuid) -> Result<bool, StoreError> {
+        self.guard(self.inner.remove_attachment(id, attachment_id))
+            .await
+    }
+
+    async fn thumbnail(
+        &self,
+        id: Uuid,
+        attachment_id: Uuid,
+    ) -> Result<Option<Thumbnail>, StoreError> {
+        self.guard(self.inner.thumbnail(id, attachment_id)).await
+    }
+
+    async fn storage_usage(&self) -> Result<Vec<StorageUsage>, StoreError> {
+        self.guard(self.inner.storage_usage()).await
+    }
+
+    async fn storage_used<'a>(&self, owner: Option<&'a str>) -> Result<i64, StoreError> {
+        self.guard(self.inner.storage_used(owner)).await
+    }
+
+    async fn revert(&self, id: Uuid, version: i32) -> Result<Option<TodoTask>, StoreError> {
+        self.guard(self.inner.revert(id, version)).await
+    }
+
+    async fn burndown(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        bucket: Bucket,
+    ) -> Result<Vec<BurndownBucket>, StoreError> {
+        self.guard(self.inner.burndown(from, to, bucket)).await
+    }
+
+    async fn graph(&self, filters: &[FilterExpr]) -> Result<TaskGraph, StoreError> {
+        self.guard(self.inner.graph(filters)).await
+    }
+
+    async fn workload(&self) -> Result<Refreshed<Vec<Workload>>, StoreError> {
+        self.guard(self.inner.workload()).await
+    }
+
+    async fn estimate_variance(
+        &self,
+        grouping: EstimateGrouping,
+    ) -> Result<Refreshed<Vec<EstimateVariance>>, StoreError> {
+        self.guard(self.inner.estimate_variance(grouping)).await
+    }
+}
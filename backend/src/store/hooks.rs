@@ -0,0 +1,198 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::hooks::{Delivery, DeliveryOutcome, Hook, MAX_DELIVERIES_KEPT};
+
+/// Columns of `hook_deliveries` making up a [`Delivery`].
+const DELIVERY_COLUMNS: &str = "id, hook_id, task_id, redelivery_of, payload, body, attempted_at,
+    latency_ms, response_status, response_snippet, error";
+
+/// Storage of REST hook subscriptions, in the `hooks` table.
+#[derive(Clone, Debug)]
+pub struct HookStore {
+    pool: PgPool,
+}
+
+impl HookStore {
+    /// Create a store using the database behind `pool`.
+    #[must_use]
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Subscribe `target_url` to the new tasks of `owner`, posting them as
+    /// rendered by `payload_template` if given.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn subscribe(
+        &self,
+        owner: Option<&str>,
+        target_url: &str,
+        payload_template: Option<&str>,
+    ) -> Result<Hook, sqlx::Error> {
+        sqlx::query_as(
+            "INSERT INTO hooks (id, owner, target_url, payload_template) VALUES ($1, $2, $3, $4)
+            RETURNING id, owner, target_url, payload_template, created_at",
+        )
+        .bind(Uuid::new_v4())
+        .bind(owner)
+        .bind(target_url)
+        .bind(payload_template)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Unsubscribe the hook with `id`, if it belongs to `owner`, returning
+    /// whether it did.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn unsubscribe(&self, id: Uuid, owner: Option<&str>) -> Result<bool, sqlx::Error> {
+        let result =
+            sqlx::query("DELETE FROM hooks WHERE id = $1 AND owner IS NOT DISTINCT FROM $2")
+                .bind(id)
+                .bind(owner)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Get the hook with `id`, if it belongs to `owner`.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn get(&self, id: Uuid, owner: Option<&str>) -> Result<Option<Hook>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, owner, target_url, payload_template, created_at FROM hooks
+            WHERE id = $1 AND owner IS NOT DISTINCT FROM $2",
+        )
+        .bind(id)
+        .bind(owner)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// List the hooks subscribed to the new tasks of `owner`.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn for_owner(&self, owner: Option<&str>) -> Result<Vec<Hook>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, owner, target_url, payload_template, created_at FROM hooks
+            WHERE owner IS NOT DISTINCT FROM $1",
+        )
+        .bind(owner)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Remove the hook with `id`, whoever it belongs to, e.g. because its
+    /// target said it is gone.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn remove(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM hooks WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Record an attempt to deliver `payload`, the task with `task_id`, to
+    /// the hook with `hook_id` by posting `body`, forgetting all but the
+    /// latest deliveries to it.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn record_delivery(
+        &self,
+        hook_id: Uuid,
+        task_id: Uuid,
+        payload: &serde_json::Value,
+        body: Option<&serde_json::Value>,
+        redelivery_of: Option<Uuid>,
+        outcome: &DeliveryOutcome,
+    ) -> Result<Delivery, sqlx::Error> {
+        let (status, snippet, error) = match &outcome.response {
+            Ok((status, snippet)) => (
+                Some(i16::try_from(*status).unwrap_or(i16::MAX)),
+                Some(snippet.as_str()),
+                None,
+            ),
+            Err(e) => (None, None, Some(e.as_str())),
+        };
+        let mut tx = self.pool.begin().await?;
+        let delivery = sqlx::query_as(&format!(
+            "INSERT INTO hook_deliveries (id, hook_id, task_id, redelivery_of, payload, body,
+                latency_ms, response_status, response_snippet, error)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            RETURNING {DELIVERY_COLUMNS}"
+        ))
+        .bind(Uuid::new_v4())
+        .bind(hook_id)
+        .bind(task_id)
+        .bind(redelivery_of)
+        .bind(payload)
+        .bind(body)
+        .bind(i32::try_from(outcome.latency.as_millis()).unwrap_or(i32::MAX))
+        .bind(status)
+        .bind(snippet)
+        .bind(error)
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM hook_deliveries
+            WHERE hook_id = $1 AND id NOT IN (
+                SELECT id FROM hook_deliveries
+                WHERE hook_id = $1
+                ORDER BY attempted_at DESC
+                LIMIT $2
+            )",
+        )
+        .bind(hook_id)
+        .bind(MAX_DELIVERIES_KEPT)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(delivery)
+    }
+
+    /// List the latest deliveries to the hook with `hook_id`, newest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn deliveries(&self, hook_id: Uuid) -> Result<Vec<Delivery>, sqlx::Error> {
+        sqlx::query_as(&format!(
+            "SELECT {DELIVERY_COLUMNS} FROM hook_deliveries
+            WHERE hook_id = $1
+            ORDER BY attempted_at DESC"
+        ))
+        .bind(hook_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Get the delivery with `id` to the hook with `hook_id`.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn delivery(&self, hook_id: Uuid, id: Uuid) -> Result<Option<Delivery>, sqlx::Error> {
+        sqlx::query_as(&format!(
+            "SELECT {DELIVERY_COLUMNS} FROM hook_deliveries WHERE hook_id = $1 AND id = $2"
+        ))
+        .bind(hook_id)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,154 @@
+use sqlx::{PgPool, types::Json};
+use uuid::Uuid;
+
+use crate::jobs::{BulkJob, ItemError, JOB_RETENTION_DAYS, JobKind, JobStatus};
+
+/// Columns of `jobs` making up a [`BulkJob`].
+const COLUMNS: &str = "id, owner, kind, status, total, processed, errors, result, error,
+    cancel_requested, created_at, finished_at";
+
+/// Storage of bulk operations run in the background, in the `jobs` table.
+#[derive(Clone, Debug)]
+pub struct JobStore {
+    pool: PgPool,
+}
+
+impl JobStore {
+    /// Create a store using the database behind `pool`.
+    #[must_use]
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record the start of a job of `kind` for `owner`, with `total` items
+    /// to process if known and `errors` in items already rejected.
+    ///
+    /// Jobs which finished long enough ago are forgotten.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn start(
+        &self,
+        owner: Option<&str>,
+        kind: JobKind,
+        total: Option<i32>,
+        errors: &[ItemError],
+    ) -> Result<BulkJob, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM jobs WHERE finished_at < now() - make_interval(days => $1)")
+            .bind(JOB_RETENTION_DAYS)
+            .execute(&mut *tx)
+            .await?;
+        let job = sqlx::query_as(&format!(
+            "INSERT INTO jobs (id, owner, kind, total, errors) VALUES ($1, $2, $3, $4, $5)
+            RETURNING {COLUMNS}"
+        ))
+        .bind(Uuid::new_v4())
+        .bind(owner)
+        .bind(kind)
+        .bind(total)
+        .bind(Json(errors))
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(job)
+    }
+
+    /// Get the job with `id`, if it was started by `owner`.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn get(&self, id: Uuid, owner: Option<&str>) -> Result<Option<BulkJob>, sqlx::Error> {
+        sqlx::query_as(&format!(
+            "SELECT {COLUMNS} FROM jobs WHERE id = $1 AND owner IS NOT DISTINCT FROM $2"
+        ))
+        .bind(id)
+        .bind(owner)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Record that the job with `id` has processed `processed` items,
+    /// returning whether it has been asked to stop.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn progress(&self, id: Uuid, processed: i32) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar(
+            "UPDATE jobs SET processed = $2 WHERE id = $1 RETURNING cancel_requested",
+        )
+        .bind(id)
+        .bind(processed)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Record that the job with `id` has stopped with `status`, having
+    /// processed `total` items, with `result` if it succeeded or `error`
+    /// explaining why it failed.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn finish(
+        &self,
+        id: Uuid,
+        status: JobStatus,
+        total: i32,
+        result: Option<&serde_json::Value>,
+        error: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE jobs
+            SET status = $2, total = coalesce(total, $3), processed = $3, result = $4, error = $5,
+                finished_at = now()
+            WHERE id = $1",
+        )
+        .bind(id)
+        .bind(status)
+        .bind(total)
+        .bind(result)
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Ask the job with `id` to stop, if it was started by `owner` and is
+    /// still running, returning it if it exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn cancel(
+        &self,
+        id: Uuid,
+        owner: Option<&str>,
+    ) -> Result<Option<BulkJob>, sqlx::Error> {
+        sqlx::query_as(&format!(
+            "UPDATE jobs SET cancel_requested = cancel_requested OR status = 'running'
+            WHERE id = $1 AND owner IS NOT DISTINCT FROM $2
+            RETURNING {COLUMNS}"
+        ))
+        .bind(id)
+        .bind(owner)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// List the jobs still running, whoever started them, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn running(&self) -> Result<Vec<BulkJob>, sqlx::Error> {
+        sqlx::query_as(&format!(
+            "SELECT {COLUMNS} FROM jobs WHERE status = 'running' ORDER BY created_at"
+        ))
+        .fetch_all(&self.pool)
+        .await
+    }
+}
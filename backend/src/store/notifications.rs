@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use sqlx::{PgPool, postgres::types::PgInterval};
+
+use crate::notify::{Channel, Notification, NotificationEvent, Preference, Preferences};
+
+/// Number of days dispatched notifications are kept for.
+const KEEP_DAYS: i32 = 30;
+
+/// Storage of notifications and users' preferences for them, in the
+/// `notifications` and `notification_preferences` tables.
+#[derive(Clone, Debug)]
+pub struct NotificationStore {
+    pool: PgPool,
+}
+
+impl NotificationStore {
+    /// Create a store using the database behind `pool`.
+    #[must_use]
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Get the preferences of a user, or `None` if they aren't registered.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn preferences(&self, user_id: &str) -> Result<Option<Preferences>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let registered: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT FROM users WHERE id = $1)")
+                .bind(user_id)
+                .fetch_one(&mut *tx)
+                .await?;
+        if !registered {
+            return Ok(None);
+        }
+        let preferences = sqlx::query_as(
+            "SELECT channel, event, enabled FROM notification_preferences WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        Ok(Some(Preferences(preferences)))
+    }
+
+    /// Get the preferences of each of `user_ids`, leaving out users with
+    /// only default preferences.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn preferences_of(
+        &self,
+        user_ids: &[&str],
+    ) -> Result<HashMap<String, Preferences>, sqlx::Error> {
+        let rows: Vec<(String, Channel, NotificationEvent, bool)> = sqlx::query_as(
+            "SELECT user_id, channel, event, enabled
+            FROM notification_preferences
+            WHERE user_id = ANY($1)",
+        )
+        .bind(user_ids)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut preferences: HashMap<_, Preferences> = HashMap::new();
+        for (user_id, channel, event, enabled) in rows {
+            preferences.entry(user_id).or_default().0.push(Preference {
+                channel,
+                event,
+                enabled,
+            });
+        }
+        Ok(preferences)
+    }
+
+    /// Set some of a user's preferences, keeping the rest, returning whether
+    /// the user is registered.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn set_preferences(
+        &self,
+        user_id: &str,
+        preferences: &[Preference],
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let registered: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT FROM users WHERE id = $1 FOR KEY SHARE)")
+                .bind(user_id)
+                .fetch_one(&mut *tx)
+                .await?;
+        if !registered {
+            return Ok(false);
+        }
+        let (channels, (events, enabled)): (Vec<_>, (Vec<_>, Vec<_>)) = preferences
+            .iter()
+            .map(|preference| (preference.channel, (preference.event, preference.enabled)))
+            .unzip();
+        sqlx::query(
+            "INSERT INTO notification_preferences (user_id, channel, event, enabled)
+            SELECT $1, * FROM unnest($2::notification_channel[], $3::notification_event[], $4::boolean[])
+            ON CONFLICT (user_id, channel, event) DO UPDATE SET enabled = excluded.enabled",
+        )
+        .bind(user_id)
+        .bind(channels)
+        .bind(events)
+        .bind(enabled)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Raise notifications of open tasks which are due within `due_soon` of
+    /// `now`, or became overdue in the `due_soon` before it, for their
+    /// assignees or else their owners.
+    ///
+    /// Each is raised once per due date of the task, however often this is
+    /// called.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn raise_deadlines(
+        &self,
+        due_soon: TimeDelta,
+        now: DateTime<Utc>,
+    ) -> Result<u64, sqlx::Error> {
+        let window = PgInterval::try_from(due_soon).map_err(sqlx::Error::Encode)?;
+        let result = sqlx::query(
+            "INSERT INTO notifications (user_id, event, task_id, due)
+            SELECT coalesce(assignee, owner),
+                CASE WHEN due <= $2 THEN 'overdue' ELSE 'due_soon' END::notification_event,
+                id,
+                due
+            FROM tasks
+            WHERE status NOT IN ('complete', 'cancelled')
+                AND coalesce(assignee, owner) IS NOT NULL
+                AND due BETWEEN $2 - $1 AND $2 + $1
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(window)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Take up to `limit` pending notifications to send, oldest first,
+    /// marking them dispatched at `now`.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn claim_pending(
+        &self,
+        limit: i64,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Notification>, sqlx::Error> {
+        sqlx::query_as(
+            "WITH claimed AS (
+                UPDATE notifications SET dispatched_at = $2
+                WHERE id IN (
+                    SELECT id FROM notifications
+                    WHERE dispatched_at IS NULL
+                    ORDER BY id
+                    LIMIT $1
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING id, event, task_id, user_id
+            )
+            SELECT c.id, c.event, c.task_id, t.title, t.due, c.user_id, u.display_name, u.email, u.phone
+            FROM claimed AS c
+            JOIN tasks AS t ON t.id = c.task_id
+            LEFT JOIN users AS u ON u.id = c.user_id AND u.deactivated_at IS NULL
+            ORDER BY c.id",
+        )
+        .bind(limit)
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Delete notifications dispatched long enough before `now` that they
+    /// needn't be kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn purge_dispatched(&self, now: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "DELETE FROM notifications WHERE dispatched_at < $2 - make_interval(days => $1)",
+        )
+        .bind(KEEP_DAYS)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,1673 @@
+use std::{
+    ops::RangeInclusive,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveTime, TimeDelta, Utc};
+use sqlx::{Connection, FromRow, PgConnection, PgPool, Postgres, QueryBuilder, Transaction};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::{
+    CURRENT_OWNER, DEADLINE, ExportedTask, HistoryErasure, Reschedule, RescheduleOutcome,
+    SearchMatch, TaskStore, TaskVersion,
+};
+use crate::{
+    Colour, DueWindow, FilterExpr, StoreError, TaskLink, TaskRecord, TodoTask,
+    attachments::{Attachment, NewAttachment, ScanResult},
+    calendar::WorkCalendar,
+    clock::{Clock, SystemClock},
+    explain::QueryPlan,
+    feed::Activity,
+    graph::TaskGraph,
+    hooks::NewTask,
+    mentions::{Mention, extract_mentions},
+    scan::ScanVerdict,
+    sla::TaskMilestones,
+    stats::{
+        Bucket, BurndownBucket, EstimateGrouping, EstimateVariance, Refreshed, StorageUsage,
+        Workload,
+    },
+    sync::{ConflictRule, SyncOutcome, reconcile, synced_changes},
+    thumbnails::{self, Thumbnail},
+    tracking::{TimeEntry, TimesheetEntry},
+};
+
+/// How long a replica's lag is trusted for before it is checked again.
+const LAG_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long checking a replica's lag may take before it is given up on.
+const LAG_CHECK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// [`TaskStore`] keeping each task's current state in a row of the `tasks`
+/// table.
+///
+/// Every change to the table is recorded in `task_history` by trigger, which
+/// in turn refreshes the `task_listing` read model that listings are served
+/// from.
+#[derive(Clone, Debug)]
+pub struct PgTaskStore {
+    pool: PgPool,
+    /// Replica which listings, searches and statistics are read from, if any.
+    replica: Option<Replica>,
+    /// Whether to rely on the database's row-level security policies to
+    /// scope operations to the current owner.
+    row_level_security: bool,
+    /// Working hours which time overdue is counted in, or `None` to count
+    /// every hour.
+    overdue_hours: Option<WorkCalendar>,
+    /// Clock telling the time that changes are made at.
+    pub(super) clock: Arc<dyn Clock>,
+}
+
+/// Read-only replica of the database.
+#[derive(Clone, Debug)]
+struct Replica {
+    pool: PgPool,
+    /// Lag behind the primary beyond which reads go to the primary instead.
+    max_lag: Duration,
+    /// When the lag was last checked, and whether it was within `max_lag`.
+    checked: Arc<Mutex<Option<(Instant, bool)>>>,
+}
+
+impl Replica {
+    /// Whether the replica is reachable and within `max_lag` of the primary,
+    /// checking no more often than [`LAG_CHECK_INTERVAL`].
+    async fn usable(&self) -> bool {
+        let last = *self.checked.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((at, usable)) = last {
+            if at.elapsed() < LAG_CHECK_INTERVAL {
+                return usable;
+            }
+        }
+
+        // a replica which has replayed everything it received isn't behind,
+        // however long ago the last transaction was; a primary has no lag
+        let check = sqlx::query_scalar(
+            "SELECT coalesce(
+                CASE WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0
+                    ELSE extract(epoch FROM now() - pg_last_xact_replay_timestamp())
+                END,
+                0
+            )::float8",
+        )
+        .fetch_one(&self.pool);
+        let lag: Result<f64, _> = tokio::time::timeout(LAG_CHECK_TIMEOUT, check)
+            .await
+            .unwrap_or(Err(sqlx::Error::PoolTimedOut));
+        let usable = match lag {
+            Ok(lag) => lag <= self.max_lag.as_secs_f64(),
+            Err(ref e) => {
+                warn!(error = format!("{e}"), "failed to check replica lag");
+                false
+            }
+        };
+        match (last.map(|(_, usable)| usable), usable) {
+            (Some(true) | None, false) => {
+                warn!(
+                    lag = lag.ok(),
+                    "reading from the primary while the replica lags"
+                );
+            }
+            (Some(false), true) => info!("reading from the replica again"),
+            _ => {}
+        }
+        *self.checked.lock().unwrap_or_else(PoisonError::into_inner) =
+            Some((Instant::now(), usable));
+        usable
+    }
+}
+
+impl PgTaskStore {
+    /// Create a store using the database behind `pool`.
+    #[must_use]
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            replica: None,
+            row_level_security: false,
+            overdue_hours: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Read listings, searches and statistics from the replica behind
+    /// `pool`, unless it lags more than `max_lag` behind the primary.
+    ///
+    /// Everything else, including reads of single tasks which clients may
+    /// have just changed, is still served by the primary.
+    #[must_use]
+    pub fn with_replica(mut self, pool: PgPool, max_lag: Duration) -> Self {
+        self.replica = Some(Replica {
+            pool,
+            max_lag,
+            checked: Arc::default(),
+        });
+        self
+    }
+
+    /// Enable or disable row-level security.
+    ///
+    /// When enabled, operations run within [`act_as`](super::act_as) are
+    /// restricted by the database to the tasks of that owner, by assuming the
+    /// `tasks_rls` role and setting `app.current_user` for each transaction.
+    /// Operations outside of it are not restricted.
+    #[must_use]
+    pub fn with_row_level_security(mut self, enabled: bool) -> Self {
+        self.row_level_security = enabled;
+        self
+    }
+
+    /// Count tasks as overdue in statistics only once some of `hours` have
+    /// passed since they were due, or as soon as they are due if `None`.
+    #[must_use]
+    pub fn with_overdue_hours(mut self, hours: Option<WorkCalendar>) -> Self {
+        self.overdue_hours = hours;
+        self
+    }
+
+    /// Tell the time that changes are made at by `clock`, rather than by the
+    /// system clock.
+    #[must_use]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Working weekdays and hours to bind to `last_working_time` in SQL,
+    /// which are all `None` if every hour counts towards being overdue.
+    fn overdue_bounds(&self) -> (Option<Vec<i32>>, Option<NaiveTime>, Option<NaiveTime>) {
+        match &self.overdue_hours {
+            Some(calendar) => {
+                let (start, end) = calendar.hours();
+                (Some(calendar.iso_weekdays()), Some(start), Some(end))
+            }
+            None => (None, None, None),
+        }
+    }
+
+    /// Begin a transaction, scoped to the current owner if row-level security
+    /// is enabled.
+    ///
+    /// Transactions which only read may be dropped rather than committed.
+    pub(super) async fn begin(&self) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        self.begin_on(&self.pool).await
+    }
+
+    /// Begin a transaction which only reads, on the replica if it is usable.
+    async fn begin_read(&self) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        match &self.replica {
+            Some(replica) if replica.usable().await => self.begin_on(&replica.pool).await,
+            _ => self.begin().await,
+        }
+    }
+
+    /// Recompute the statistics views, returning when they were refreshed.
+    ///
+    /// Reads of the statistics aren't blocked while they are recomputed.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn refresh_stats(&self) -> Result<DateTime<Utc>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        // refreshing takes as long as it takes
+        sqlx::query("SET LOCAL statement_timeout = 0")
+            .execute(&mut *tx)
+            .await?;
+        // read by the workload view, which can't take parameters
+        let (days, start, end) = self.overdue_bounds();
+        sqlx::query(
+            "SELECT set_config('app.working_days', coalesce($1::integer[]::text, ''), true),
+                set_config('app.working_day_start', coalesce($2::time::text, ''), true),
+                set_config('app.working_day_end', coalesce($3::time::text, ''), true)",
+        )
+        .bind(days)
+        .bind(start)
+        .bind(end)
+        .execute(&mut *tx)
+        .await?;
+        for query in [
+            "REFRESH MATERIALIZED VIEW CONCURRENTLY workload_stats",
+            "REFRESH MATERIALIZED VIEW CONCURRENTLY estimate_stats",
+        ] {
+            sqlx::query(query).execute(&mut *tx).await?;
+        }
+        let refreshed_at = sqlx::query_scalar(
+            "UPDATE stats_refreshes SET refreshed_at = now() RETURNING refreshed_at",
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(refreshed_at)
+    }
+
+    /// Get up to `limit` image attachments which have no thumbnail yet, oldest
+    /// first, with their content.
+    ///
+    /// Quarantined attachments are never given thumbnails.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn pending_thumbnails(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<(Uuid, Vec<u8>)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, content FROM attachments a
+            WHERE content_type = ANY($1) AND NOT quarantined
+                AND NOT EXISTS (
+                    SELECT 1 FROM attachment_thumbnails WHERE attachment_id = a.id
+                )
+            ORDER BY uploaded_at
+            LIMIT $2",
+        )
+        .bind(&thumbnails::IMAGE_CONTENT_TYPES[..])
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Record the thumbnail generated for an attachment, or the reason one
+    /// couldn't be, so it isn't tried again.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn save_thumbnail(
+        &self,
+        attachment_id: Uuid,
+        thumbnail: Result<&[u8], &str>,
+    ) -> Result<(), sqlx::Error> {
+        // the attachment may have been removed meanwhile
+        sqlx::query(
+            "INSERT INTO attachment_thumbnails (attachment_id, content, error)
+            SELECT id, $2, $3 FROM attachments WHERE id = $1
+            ON CONFLICT (attachment_id) DO NOTHING",
+        )
+        .bind(attachment_id)
+        .bind(thumbnail.ok())
+        .bind(thumbnail.err())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Get the tasks which may belong in a digest of `user`'s tasks, or of
+    /// every task if `None`: open tasks due up to `until`, and tasks
+    /// completed after `since`.
+    ///
+    /// Users' tasks are those they own or are assigned.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn digest_tasks(
+        &self,
+        user: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<TaskRecord>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, title, description, status, due, tags, estimate, progress, colour, title_cy, description_cy,
+                completed_at, status_reason, custom_status
+            FROM tasks
+            WHERE ($1::text IS NULL OR owner = $1 OR assignee = $1)
+                AND (
+                    completed_at > $2
+                    OR (status NOT IN ('complete', 'cancelled') AND due <= $3)
+                )
+            ORDER BY due",
+        )
+        .bind(user)
+        .bind(since)
+        .bind(until)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Whether statistics cover every owner's tasks, and if not the owner
+    /// whose tasks they cover.
+    ///
+    /// Statistics are read from materialised views, which row-level security
+    /// doesn't apply to, so they are limited to the current owner by the
+    /// queries instead.
+    fn stats_scope(&self) -> (bool, Option<String>) {
+        match CURRENT_OWNER.try_with(Clone::clone) {
+            Ok(owner) if self.row_level_security => (false, owner),
+            _ => (true, None),
+        }
+    }
+
+    /// Run the queries serving listings, searches and statistics with
+    /// `EXPLAIN (ANALYZE)` against sample parameters, warning of scans
+    /// reading at least `min_rows` rows.
+    ///
+    /// The queries are run on the primary, and nothing they do is committed.
+    ///
+    /// # Errors
+    ///
+    /// Fails if any of the queries can't be explained.
+    pub async fn explain(&self, min_rows: f64) -> Result<Vec<QueryPlan>, sqlx::Error> {
+        const EXPLAIN: &str = "EXPLAIN (ANALYZE, FORMAT JSON) ";
+        let filters: Vec<FilterExpr> = "status:InProgress AND tag:urgent"
+            .parse()
+            .into_iter()
+            .collect();
+        let now = self.clock.now();
+        let mut plans = Vec::new();
+
+        let mut query = QueryBuilder::new(EXPLAIN);
+        push_list_query(&mut query, &filters, Some("analyze"));
+        let explained = query
+            .build_query_scalar()
+            .fetch_one(&mut *self.begin().await?)
+            .await?;
+        plans.push(QueryPlan::new("list", explained, min_rows));
+
+        let mut tx = self.begin().await?;
+        set_search_threshold(&mut tx, 0.5).await?;
+        let explained = sqlx::query_scalar(&format!("{EXPLAIN}{SEARCH_QUERY}"))
+            .bind("report")
+            .fetch_one(&mut *tx)
+            .await?;
+        plans.push(QueryPlan::new("search", explained, min_rows));
+
+        let explained = sqlx::query_scalar(&format!("{EXPLAIN}{BURNDOWN_QUERY}"))
+            .bind(Bucket::Day.unit())
+            .bind(now - TimeDelta::days(30))
+            .bind(now)
+            .fetch_one(&mut *self.begin().await?)
+            .await?;
+        plans.push(QueryPlan::new("burndown", explained, min_rows));
+
+        let explained = sqlx::query_scalar(&format!("{EXPLAIN}{WORKLOAD_QUERY}"))
+            .bind(false)
+            .bind("analyze")
+            .fetch_one(&mut *self.begin().await?)
+            .await?;
+        plans.push(QueryPlan::new("workload", explained, min_rows));
+
+        for (name, grouping) in [
+            ("estimate-variance-by-tag", EstimateGrouping::Tag),
+            ("estimate-variance-by-assignee", EstimateGrouping::Assignee),
+        ] {
+            let explained = sqlx::query_scalar(&format!("{EXPLAIN}{ESTIMATE_VARIANCE_QUERY}"))
+                .bind(grouping_name(grouping))
+                .bind(false)
+                .bind("analyze")
+                .fetch_one(&mut *self.begin().await?)
+                .await?;
+            plans.push(QueryPlan::new(name, explained, min_rows));
+        }
+        Ok(plans)
+    }
+
+    async fn begin_on(&self, pool: &PgPool) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        let deadline = DEADLINE.try_with(|deadline| *deadline).ok();
+        let mut tx = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                tokio::time::timeout(remaining, pool.begin())
+                    .await
+                    .unwrap_or(Err(sqlx::Error::PoolTimedOut))?
+            }
+            None => pool.begin().await?,
+        };
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            // zero would disable the timeout
+            sqlx::query("SELECT set_config('statement_timeout', $1, true)")
+                .bind(remaining.as_millis().max(1).to_string())
+                .execute(&mut *tx)
+                .await?;
+        }
+        if self.row_level_security {
+            if let Ok(owner) = CURRENT_OWNER.try_with(Clone::clone) {
+                sqlx::query(
+                    "SELECT set_config('role', 'tasks_rls', true),
+                        set_config('app.current_user', $1, true)",
+                )
+                .bind(owner.unwrap_or_default())
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        Ok(tx)
+    }
+}
+
+/// Get `task` as stored at `now`, completed then if it's complete and wasn't
+/// already, as by [`TodoTask::transition`].
+pub(super) fn stamped(task: &TodoTask, now: DateTime<Utc>) -> TodoTask {
+    let mut task = task.clone();
+    task.transition(task.status.clone(), now);
+    task
+}
+
+/// Insert a row for a new task into the `tasks` table.
+pub(super) async fn insert_task(
+    conn: &mut PgConnection,
+    id: Uuid,
+    task: &TodoTask,
+    owner: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let status = task.status.clone();
+    sqlx::query!(
+        "INSERT INTO tasks
+            (id, title, description, status, due, tags, estimate, progress, colour, title_cy,
+                description_cy, completed_at, status_reason, custom_status, owner)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15);",
+        id,
+        task.title(),
+        task.description(),
+        status as _,
+        task.due(),
+        task.tags(),
+        task.estimate() as _,
+        task.progress().map(i16::from),
+        task.colour().map(Colour::as_str),
+        task.title_cy(),
+        task.description_cy(),
+        task.completed_at(),
+        task.status_reason(),
+        task.status.custom_name(),
+        owner,
+    )
+    .execute(&mut *conn)
+    .await?;
+    record_mentions(conn, id, task).await
+}
+
+/// Number of times locking a task is attempted while concurrent updates keep
+/// moving its row.
+const LOCK_ATTEMPTS: u32 = 3;
+
+/// Lock the row of a task in the `tasks` table for the rest of the
+/// transaction, returning whether the task exists.
+///
+/// Waiting on a concurrent update which moves the row to another partition,
+/// by opening or closing the task, fails with a serialization failure; the
+/// lock is then taken again on the row where it now is.
+pub(super) async fn lock_task(conn: &mut PgConnection, id: Uuid) -> Result<bool, sqlx::Error> {
+    let mut attempts = 1;
+    loop {
+        let mut savepoint = conn.begin().await?;
+        let result = sqlx::query("SELECT 1 FROM tasks WHERE id = $1 FOR UPDATE")
+            .bind(id)
+            .fetch_optional(&mut *savepoint)
+            .await;
+        match result {
+            Ok(row) => {
+                savepoint.commit().await?;
+                return Ok(row.is_some());
+            }
+            Err(sqlx::Error::Database(e))
+                if attempts < LOCK_ATTEMPTS && e.code().as_deref() == Some("40001") =>
+            {
+                savepoint.rollback().await?;
+                attempts += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Overwrite the row of a task in the `tasks` table.
+pub(super) async fn update_task(
+    conn: &mut PgConnection,
+    id: Uuid,
+    task: &TodoTask,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE tasks
+        SET title = $2, description = $3, status = $4, due = $5, tags = $6,
+            estimate = $7, progress = $8, colour = $9, title_cy = $10, description_cy = $11,
+            -- a task which was already complete keeps its completion time, as
+            -- by `TodoTask::transition`
+            completed_at = CASE WHEN $4 = 'complete' THEN coalesce(completed_at, $12) END,
+            status_reason = $13, custom_status = $14
+        WHERE id = $1",
+    )
+    .bind(id)
+    .bind(task.title())
+    .bind(task.description())
+    .bind(task.status.clone())
+    .bind(task.due())
+    .bind(task.tags())
+    .bind(task.estimate())
+    .bind(task.progress().map(i16::from))
+    .bind(task.colour())
+    .bind(task.title_cy())
+    .bind(task.description_cy())
+    .bind(task.completed_at())
+    .bind(task.status_reason())
+    .bind(task.status.custom_name())
+    .execute(&mut *conn)
+    .await?;
+    record_mentions(conn, id, task).await
+}
+
+/// Bring the users recorded as mentioned by a task up to date with its
+/// description.
+///
+/// Users who were already mentioned keep the time they were first mentioned.
+async fn record_mentions(
+    conn: &mut PgConnection,
+    id: Uuid,
+    task: &TodoTask,
+) -> Result<(), sqlx::Error> {
+    let users = task.description().map(extract_mentions).unwrap_or_default();
+    sqlx::query("DELETE FROM task_mentions WHERE task_id = $1 AND NOT user_id = ANY($2)")
+        .bind(id)
+        .bind(&users)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(
+        "INSERT INTO task_mentions (task_id, user_id)
+        SELECT $1, unnest($2::text[])
+        ON CONFLICT DO NOTHING",
+    )
+    .bind(id)
+    .bind(&users)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Find the task synced from an item in another application, and the version
+/// of it as of the last sync, locking the reference to it.
+pub(super) async fn find_external(
+    conn: &mut PgConnection,
+    source: &str,
+    external_id: &str,
+) -> Result<Option<(Uuid, i32)>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT task_id, synced_version
+        FROM external_refs
+        WHERE source = $1 AND external_id = $2
+        FOR UPDATE",
+    )
+    .bind(source)
+    .bind(external_id)
+    .fetch_optional(conn)
+    .await
+}
+
+/// Record that a task is in sync with an item in another application, as of
+/// its latest version.
+pub(super) async fn record_external(
+    conn: &mut PgConnection,
+    id: Uuid,
+    source: &str,
+    external_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO external_refs (task_id, source, external_id, synced_version)
+        SELECT $1, $2, $3, coalesce(max(version), 0)
+        FROM task_history
+        WHERE task_id = $1
+        ON CONFLICT (source, external_id) DO UPDATE
+        SET synced_version = excluded.synced_version, synced_at = now()",
+    )
+    .bind(id)
+    .bind(source)
+    .bind(external_id)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Describe the rest of the transaction's changes to the history trigger as a
+/// revert to `version`.
+pub(super) async fn describe_revert(
+    conn: &mut PgConnection,
+    version: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "SELECT set_config('app.history_action', 'revert', true),
+            set_config('app.history_reverted_to', $1::text, true)",
+    )
+    .bind(version)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Describe the rest of the transaction's changes to the history trigger as
+/// rescheduling.
+pub(super) async fn describe_reschedule(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT set_config('app.history_action', 'reschedule', true)")
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Lock the tasks matching every one of `filters`, returning them in order of
+/// ID.
+pub(super) async fn lock_matching(
+    conn: &mut PgConnection,
+    filters: &[FilterExpr],
+) -> Result<Vec<TaskRecord>, sqlx::Error> {
+    let mut query = QueryBuilder::new(
+        "SELECT id, title, description, status, due, tags, estimate, progress, colour, title_cy, description_cy,
+            completed_at, status_reason, custom_status
+        FROM tasks
+        WHERE id IN (SELECT id FROM task_listing",
+    );
+    for (i, filter) in filters.iter().enumerate() {
+        query.push(if i == 0 { " WHERE " } else { " AND " });
+        filter.push_sql(&mut query);
+    }
+    // locked in a consistent order, so concurrent reschedules don't deadlock
+    query.push(") ORDER BY id FOR UPDATE");
+    query.build_query_as().fetch_all(conn).await
+}
+
+/// Get a single version of a task from the `task_history` table.
+pub(super) async fn fetch_version(
+    conn: &mut PgConnection,
+    id: Uuid,
+    version: i32,
+) -> Result<Option<TaskVersion>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT version, recorded_at, action, reverted_to,
+            title, description, status, due, tags, estimate, progress, colour, title_cy, description_cy,
+            completed_at, status_reason, custom_status
+        FROM task_history
+        WHERE task_id = $1 AND version = $2",
+    )
+    .bind(id)
+    .bind(version)
+    .fetch_optional(conn)
+    .await
+}
+
+/// Query listing tasks, to be followed by filters and ordering.
+///
+/// Tasks without a manually set progress are done if complete, or otherwise
+/// as done as the tasks they depend on.
+const LIST_QUERY: &str =
+    "SELECT l.id, l.title, l.description, l.status, l.due, l.tags, l.estimate, l.colour,
+        l.title_cy, l.description_cy, l.completed_at, l.status_reason, l.custom_status,
+        coalesce(
+            l.progress,
+            CASE WHEN l.status = 'complete' THEN 100 END,
+            (
+                SELECT round(
+                    100.0 * count(*) FILTER (WHERE d.status = 'complete')
+                        / nullif(count(*), 0)
+                )
+                FROM task_links AS k
+                JOIN task_listing AS d ON d.id = k.target
+                WHERE k.source = l.id
+                    AND k.kind = 'depends_on'
+                    AND d.status <> 'cancelled'
+            ),
+            0
+        )::smallint AS progress
+    FROM task_listing AS l";
+
+/// Append the query listing the tasks matching every one of `filters` to
+/// `query`, with those pinned by `pinned_by` first.
+fn push_list_query<'a>(
+    query: &mut QueryBuilder<'a, Postgres>,
+    filters: &[FilterExpr],
+    pinned_by: Option<&'a str>,
+) {
+    // served from the read model, to keep listings off the write path
+    query.push(LIST_QUERY);
+    for (i, filter) in filters.iter().enumerate() {
+        query.push(if i == 0 { " WHERE " } else { " AND " });
+        filter.push_sql(query);
+    }
+    query.push(
+        " ORDER BY EXISTS (SELECT 1 FROM task_pins AS p WHERE p.task_id = l.id AND p.owner = ",
+    );
+    query.push_bind(pinned_by);
+    query.push(") DESC, due");
+}
+
+/// Query searching task titles for `$1`, once the threshold is set with
+/// [`set_search_threshold`].
+///
+/// Word similarity matches the search text against the best-matching portion
+/// of the title, so short searches still match long titles.
+const SEARCH_QUERY: &str = "SELECT id, title, description, status, due, tags, estimate, progress, colour, title_cy, description_cy,
+        completed_at, status_reason, custom_status, word_similarity($1, title) AS score
+    FROM tasks
+    WHERE $1 <% title
+    ORDER BY score DESC, due";
+
+/// Set the minimum word similarity of [`SEARCH_QUERY`] results for the rest
+/// of `tx`.
+async fn set_search_threshold(
+    tx: &mut Transaction<'static, Postgres>,
+    threshold: f32,
+) -> Result<(), sqlx::Error> {
+    // the `<%` operator compares against this setting, which (unlike
+    // comparing `word_similarity` directly) lets the trigram index be used
+    sqlx::query("SELECT set_config('pg_trgm.word_similarity_threshold', $1, true)")
+        .bind(threshold.to_string())
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Query counting tasks in buckets of `$1` from `$2` to `$3`, with tasks
+/// overdue once working time passes on the ISO weekdays `$4` from `$5` to
+/// `$6`, or as soon as they're due if those are null.
+const BURNDOWN_QUERY: &str = "WITH buckets AS (
+        -- bucket in UTC, whatever the session's time zone
+        SELECT local.start AT TIME ZONE 'UTC' AS start,
+            (local.start + s.step) AT TIME ZONE 'UTC' AS finish
+        FROM (SELECT CAST('1 ' || $1 AS interval) AS step) AS s,
+            generate_series(
+                date_trunc($1, $2 AT TIME ZONE 'UTC'),
+                $3 AT TIME ZONE 'UTC',
+                s.step
+            ) AS local (start)
+    )
+    SELECT b.start,
+        count(l.id) FILTER (
+            WHERE l.created_at >= b.start
+        ) AS created,
+        count(l.id) FILTER (
+            WHERE l.completed_at >= b.start AND l.completed_at < b.finish
+        ) AS completed,
+        count(l.id) FILTER (
+            WHERE l.due < last_working_time(b.finish, $4, $5, $6)
+                AND l.status <> 'cancelled'
+                AND (l.completed_at IS NULL OR l.completed_at >= b.finish)
+        ) AS overdue
+    FROM buckets AS b
+    LEFT JOIN task_listing AS l ON l.created_at < b.finish
+    GROUP BY b.start
+    ORDER BY b.start";
+
+/// Columns of the `attachments` table, in the order of [`Attachment`]'s
+/// fields.
+const ATTACHMENT_COLUMNS: &str = "id, task_id, filename, content_type, size, sha256, uploaded_by,
+    uploaded_at, scan_result, threat, scanned_by, scanned_at, quarantined";
+
+/// Row of the `attachments` table, with the file's content.
+#[derive(FromRow)]
+struct AttachmentRow {
+    #[sqlx(flatten)]
+    attachment: Attachment,
+    content: Vec<u8>,
+}
+
+/// Query counting the open tasks of each assignee, of the owner `$2` only
+/// unless `$1`.
+const WORKLOAD_QUERY: &str = "SELECT assignee,
+        sum(open)::bigint AS open,
+        sum(overdue)::bigint AS overdue,
+        min(next_due) AS next_due
+    FROM workload_stats
+    WHERE $1 OR owner IS NOT DISTINCT FROM $2
+    GROUP BY assignee
+    ORDER BY open DESC, next_due, assignee";
+
+/// Query comparing the estimated and tracked time of tasks grouped by `$1`,
+/// of the owner `$3` only unless `$2`.
+const ESTIMATE_VARIANCE_QUERY: &str = "SELECT \"group\",
+        sum(tasks)::bigint AS tasks,
+        sum(estimated_seconds)::bigint AS estimated_seconds,
+        sum(tracked_seconds)::bigint AS tracked_seconds,
+        sum(elapsed_seconds)::bigint AS elapsed_seconds,
+        (sum(tracked_seconds) - sum(estimated_seconds))::bigint AS variance_seconds
+    FROM estimate_stats
+    WHERE grouping = $1 AND ($2 OR owner IS NOT DISTINCT FROM $3)
+    GROUP BY \"group\"
+    ORDER BY \"group\" NULLS LAST";
+
+/// Name of `grouping` in the `estimate_stats` view.
+fn grouping_name(grouping: EstimateGrouping) -> &'static str {
+    match grouping {
+        EstimateGrouping::Tag => "tag",
+        EstimateGrouping::Assignee => "assignee",
+    }
+}
+
+/// When the statistics views were last refreshed.
+async fn stats_refreshed_at(conn: &mut PgConnection) -> Result<DateTime<Utc>, sqlx::Error> {
+    sqlx::query_scalar("SELECT refreshed_at FROM stats_refreshes")
+        .fetch_one(conn)
+        .await
+}
+
+#[async_trait]
+impl TaskStore for PgTaskStore {
+    async fn create<'a>(
+        &self,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+    ) -> Result<Uuid, StoreError> {
+        let id = Uuid::new_v4();
+        self.create_with_id(id, task, owner).await?;
+        Ok(id)
+    }
+
+    async fn create_with_id<'a>(
+        &self,
+        id: Uuid,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+    ) -> Result<(), StoreError> {
+        let mut tx = self.begin().await?;
+        insert_task(&mut tx, id, &stamped(task, self.clock.now()), owner).await?;
+        Ok(tx.commit().await?)
+    }
+
+    async fn update(&self, id: Uuid, task: &TodoTask) -> Result<bool, StoreError> {
+        let mut tx = self.begin().await?;
+        let exists = lock_task(&mut tx, id).await?;
+        if exists {
+            update_task(&mut tx, id, &stamped(task, self.clock.now())).await?;
+        }
+        tx.commit().await?;
+        Ok(exists)
+    }
+
+    async fn reschedule(
+        &self,
+        filters: &[FilterExpr],
+        reschedule: Reschedule,
+        window: DueWindow,
+    ) -> Result<RescheduleOutcome, StoreError> {
+        let now = self.clock.now();
+        let mut tx = self.begin().await?;
+        let tasks = lock_matching(&mut tx, filters).await?;
+        describe_reschedule(&mut tx).await?;
+        let mut rescheduled = Vec::with_capacity(tasks.len());
+        for TaskRecord { id, mut task } in tasks {
+            let Some(due) = reschedule.apply(*task.due(), window, now) else {
+                return Ok(RescheduleOutcome::OutOfWindow(id));
+            };
+            if due != *task.due() {
+                task.set_due(&due);
+                update_task(&mut tx, id, &task).await?;
+            }
+            rescheduled.push(id);
+        }
+        tx.commit().await?;
+        Ok(RescheduleOutcome::Rescheduled(rescheduled))
+    }
+
+    async fn upsert_external<'a>(
+        &self,
+        source: &str,
+        external_id: &str,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+        rule: ConflictRule,
+    ) -> Result<SyncOutcome, StoreError> {
+        let mut tx = self.begin().await?;
+        let Some((id, synced_version)) = find_external(&mut tx, source, external_id).await? else {
+            let id = Uuid::new_v4();
+            insert_task(&mut tx, id, &stamped(task, self.clock.now()), owner).await?;
+            record_external(&mut tx, id, source, external_id).await?;
+            tx.commit().await?;
+            return Ok(SyncOutcome::Created);
+        };
+
+        if !lock_task(&mut tx, id).await? {
+            return Ok(SyncOutcome::Skipped);
+        }
+        let mut current: TodoTask = sqlx::query_as(
+            "SELECT title, description, status, due, tags, estimate, progress, colour, title_cy, description_cy,
+                completed_at, status_reason, custom_status
+            FROM tasks
+            WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+        let base = fetch_version(&mut tx, id, synced_version)
+            .await?
+            .map_or_else(|| current.clone(), |version| version.task);
+
+        let (outcome, changes) = reconcile(&base, &current, task, rule);
+        if outcome == SyncOutcome::Updated {
+            changes.apply(&mut current, self.clock.now());
+            update_task(&mut tx, id, &current).await?;
+        }
+        if synced_changes(&current, task).is_empty() {
+            record_external(&mut tx, id, source, external_id).await?;
+        }
+        tx.commit().await?;
+        Ok(outcome)
+    }
+
+    async fn count_open<'a>(&self, owner: Option<&'a str>) -> Result<i64, StoreError> {
+        sqlx::query_scalar(
+            "SELECT count(*)
+            FROM tasks
+            WHERE owner IS NOT DISTINCT FROM $1
+                AND status NOT IN ('complete', 'cancelled')",
+        )
+        .bind(owner)
+        .fetch_one(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn owned(&self, owner: &str) -> Result<Vec<TaskRecord>, StoreError> {
+        sqlx::query_as(
+            "SELECT id, title, description, status, due, tags, estimate, progress, colour, title_cy, description_cy,
+                completed_at, status_reason, custom_status
+            FROM tasks
+            WHERE owner = $1
+            ORDER BY due",
+        )
+        .bind(owner)
+        .fetch_all(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn erase(&self, owner: &str, history: HistoryErasure) -> Result<u64, StoreError> {
+        let mut tx = self.begin().await?;
+        // allow the deletion of events, which are otherwise append-only
+        sqlx::query("SELECT set_config('app.erasure', 'on', true)")
+            .execute(&mut *tx)
+            .await?;
+
+        let ids: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM tasks WHERE owner = $1 FOR UPDATE")
+            .bind(owner)
+            .fetch_all(&mut *tx)
+            .await?;
+        // history is dealt with before the tasks are deleted, while row-level
+        // security still lets it be seen
+        let history_query = match history {
+            HistoryErasure::Anonymise => {
+                "UPDATE task_history
+                SET title = '[erased]', description = NULL, tags = '{}',
+                    title_cy = NULL, description_cy = NULL, status_reason = NULL
+                WHERE task_id = ANY($1)"
+            }
+            HistoryErasure::Delete => "DELETE FROM task_history WHERE task_id = ANY($1)",
+        };
+        for query in [
+            history_query,
+            "DELETE FROM task_snapshots WHERE task_id = ANY($1)",
+            "DELETE FROM task_events WHERE task_id = ANY($1)",
+            "DELETE FROM task_listing WHERE id = ANY($1)",
+            "DELETE FROM tasks WHERE id = ANY($1)",
+        ] {
+            sqlx::query(query).bind(&ids).execute(&mut *tx).await?;
+        }
+        // with row-level security, this only affects tasks visible to the owner
+        for query in [
+            "UPDATE tasks SET assignee = NULL WHERE assignee = $1",
+            "DELETE FROM time_entries WHERE owner = $1",
+            "DELETE FROM task_pins WHERE owner = $1",
+            "DELETE FROM task_mentions WHERE user_id = $1",
+        ] {
+            sqlx::query(query).bind(owner).execute(&mut *tx).await?;
+        }
+
+        tx.commit().await?;
+        Ok(ids.len() as u64)
+    }
+
+    async fn get(&self, id: Uuid) -> Result<Option<TodoTask>, StoreError> {
+        sqlx::query_as(
+            "SELECT title, description, status, due, tags, estimate, progress, colour, title_cy, description_cy,
+                completed_at, status_reason, custom_status
+            FROM tasks
+            WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn get_many(&self, ids: &[Uuid]) -> Result<Vec<TaskRecord>, StoreError> {
+        sqlx::query_as(
+            "SELECT id, title, description, status, due, tags, estimate, progress, colour, title_cy, description_cy,
+                completed_at, status_reason, custom_status
+            FROM tasks
+            WHERE id = ANY($1)",
+        )
+        .bind(ids)
+        .fetch_all(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn milestones(&self, ids: &[Uuid]) -> Result<Vec<TaskMilestones>, StoreError> {
+        sqlx::query_as(
+            "SELECT id, created_at, responded_at
+            FROM task_listing
+            WHERE id = ANY($1)",
+        )
+        .bind(ids)
+        .fetch_all(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn list<'a>(
+        &self,
+        filters: &[FilterExpr],
+        pinned_by: Option<&'a str>,
+    ) -> Result<Vec<TaskRecord>, StoreError> {
+        let mut query = QueryBuilder::new("");
+        push_list_query(&mut query, filters, pinned_by);
+        query
+            .build_query_as()
+            .fetch_all(&mut *self.begin_read().await?)
+            .await
+            .map_err(StoreError::from)
+    }
+
+    async fn export_page(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<ExportedTask>, StoreError> {
+        sqlx::query_as(
+            "SELECT id, title, description, status, due, tags, estimate, progress, colour, title_cy,
+                description_cy, completed_at, status_reason, custom_status, owner, assignee
+            FROM tasks
+            WHERE $1::uuid IS NULL OR id > $1
+            ORDER BY id
+            LIMIT $2",
+        )
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn search(&self, title: &str, threshold: f32) -> Result<Vec<SearchMatch>, StoreError> {
+        let mut tx = self.begin_read().await?;
+        set_search_threshold(&mut tx, threshold).await?;
+        let results = sqlx::query_as(SEARCH_QUERY)
+            .bind(title)
+            .fetch_all(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(results)
+    }
+
+    async fn find_duplicates(
+        &self,
+        task: &TodoTask,
+        threshold: f32,
+    ) -> Result<Vec<TaskRecord>, StoreError> {
+        sqlx::query_as(
+            "SELECT id, title, description, status, due, tags, estimate, progress, colour, title_cy, description_cy,
+                completed_at, status_reason, custom_status
+            FROM tasks
+            WHERE status NOT IN ('complete', 'cancelled')
+                AND similarity(title, $1) >= $2
+                AND due BETWEEN $3 - interval '1 day' AND $3 + interval '1 day'
+            ORDER BY similarity(title, $1) DESC",
+        )
+        .bind(task.title())
+        .bind(threshold)
+        .bind(task.due())
+        .fetch_all(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn links(&self, id: Uuid) -> Result<Option<Vec<TaskLink>>, StoreError> {
+        let mut tx = self.begin().await?;
+        let exists = sqlx::query("SELECT 1 FROM tasks WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if exists.is_none() {
+            return Ok(None);
+        }
+
+        sqlx::query_as(
+            "SELECT source, target, kind
+            FROM task_links
+            WHERE source = $1 OR target = $1",
+        )
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await
+        .map(Some)
+        .map_err(StoreError::from)
+    }
+
+    async fn add_link(&self, link: &TaskLink) -> Result<(), StoreError> {
+        let mut tx = self.begin().await?;
+        sqlx::query("INSERT INTO task_links (source, target, kind) VALUES ($1, $2, $3)")
+            .bind(link.source)
+            .bind(link.target)
+            .bind(link.kind)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn remove_link(&self, link: &TaskLink) -> Result<bool, StoreError> {
+        let mut tx = self.begin().await?;
+        let result =
+            sqlx::query("DELETE FROM task_links WHERE source = $1 AND target = $2 AND kind = $3")
+                .bind(link.source)
+                .bind(link.target)
+                .bind(link.kind)
+                .execute(&mut *tx)
+                .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn history(
+        &self,
+        id: Uuid,
+        versions: RangeInclusive<i64>,
+    ) -> Result<Vec<TaskVersion>, StoreError> {
+        sqlx::query_as(
+            "SELECT version, recorded_at, action, reverted_to,
+                title, description, status, due, tags, estimate, progress, colour, title_cy, description_cy,
+                completed_at, status_reason, custom_status
+            FROM task_history
+            WHERE task_id = $1 AND version BETWEEN $2 AND $3
+            ORDER BY version",
+        )
+        .bind(id)
+        .bind(versions.start())
+        .bind(versions.end())
+        .fetch_all(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn version(&self, id: Uuid, version: i32) -> Result<Option<TaskVersion>, StoreError> {
+        Ok(fetch_version(&mut *self.begin().await?, id, version).await?)
+    }
+
+    async fn latest_version(&self, id: Uuid) -> Result<Option<i32>, StoreError> {
+        sqlx::query_scalar(
+            "SELECT l.version
+            FROM tasks AS t
+            JOIN task_listing AS l ON l.id = t.id
+            WHERE t.id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn created<'a>(
+        &self,
+        id: Uuid,
+        owner: Option<&'a str>,
+    ) -> Result<Option<TodoTask>, StoreError> {
+        sqlx::query_as(
+            "SELECT h.title, h.description, h.status, h.due, h.tags, h.estimate, h.progress, h.colour,
+                h.title_cy, h.description_cy, h.completed_at, h.status_reason, h.custom_status
+            FROM tasks AS t
+            JOIN task_history AS h ON h.task_id = t.id AND h.version = 1
+            WHERE t.id = $1 AND t.owner IS NOT DISTINCT FROM $2",
+        )
+        .bind(id)
+        .bind(owner)
+        .fetch_optional(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn assign<'a>(
+        &self,
+        id: Uuid,
+        owner: Option<&'a str>,
+        assignee: Option<&'a str>,
+    ) -> Result<bool, StoreError> {
+        let mut tx = self.begin().await?;
+        let result = sqlx::query(
+            "UPDATE tasks SET assignee = $3 WHERE id = $1 AND owner IS NOT DISTINCT FROM $2",
+        )
+        .bind(id)
+        .bind(owner)
+        .bind(assignee)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_pinned(&self, id: Uuid, owner: &str, pinned: bool) -> Result<bool, StoreError> {
+        let mut tx = self.begin().await?;
+        let exists = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM tasks WHERE id = $1)")
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if exists {
+            sqlx::query(if pinned {
+                "INSERT INTO task_pins (task_id, owner) VALUES ($1, $2) ON CONFLICT DO NOTHING"
+            } else {
+                "DELETE FROM task_pins WHERE task_id = $1 AND owner = $2"
+            })
+            .bind(id)
+            .bind(owner)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(exists)
+    }
+
+    async fn pinned(&self, id: Uuid, owner: &str) -> Result<bool, StoreError> {
+        sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM task_pins WHERE task_id = $1 AND owner = $2)",
+        )
+        .bind(id)
+        .bind(owner)
+        .fetch_one(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn activity<'a>(
+        &self,
+        owner: Option<&'a str>,
+        limit: i64,
+    ) -> Result<Vec<Activity>, StoreError> {
+        // completions are versions which changed the status to complete
+        sqlx::query_as(
+            "SELECT CASE WHEN h.version = 1 THEN 'created' ELSE 'completed' END AS kind,
+                h.version, h.recorded_at, h.task_id AS id, h.title, h.description, h.status, h.due,
+                h.tags, h.estimate, h.progress, h.colour, h.title_cy, h.description_cy,
+                h.completed_at, h.status_reason, h.custom_status
+            FROM task_history AS h
+            JOIN tasks AS t ON t.id = h.task_id
+            LEFT JOIN task_history AS p ON p.task_id = h.task_id AND p.version = h.version - 1
+            WHERE t.owner IS NOT DISTINCT FROM $1
+                AND (h.version = 1 OR (h.status = 'complete' AND p.status <> 'complete'))
+            ORDER BY h.recorded_at DESC, h.version DESC
+            LIMIT $2",
+        )
+        .bind(owner)
+        .bind(limit)
+        .fetch_all(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn new_tasks<'a>(
+        &self,
+        owner: Option<&'a str>,
+        limit: i64,
+    ) -> Result<Vec<NewTask>, StoreError> {
+        sqlx::query_as(
+            "SELECT t.id, t.title, t.description, t.status, t.due, t.tags, t.estimate,
+                t.progress, t.colour, t.title_cy, t.description_cy, t.completed_at,
+                t.status_reason, t.custom_status,
+                l.created_at
+            FROM tasks AS t
+            JOIN task_listing AS l ON l.id = t.id
+            WHERE t.owner IS NOT DISTINCT FROM $1
+            ORDER BY l.created_at DESC, t.id DESC
+            LIMIT $2",
+        )
+        .bind(owner)
+        .bind(limit)
+        .fetch_all(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn mentions(&self, user: &str) -> Result<Vec<Mention>, StoreError> {
+        sqlx::query_as(
+            "SELECT m.task_id, t.title, m.mentioned_at
+            FROM task_mentions AS m
+            JOIN tasks AS t ON t.id = m.task_id
+            WHERE m.user_id = $1
+            ORDER BY m.mentioned_at DESC",
+        )
+        .bind(user)
+        .fetch_all(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn start_timer(&self, id: Uuid, owner: &str) -> Result<Option<TimeEntry>, StoreError> {
+        let mut tx = self.begin().await?;
+        let entry = sqlx::query_as(
+            "INSERT INTO time_entries (id, task_id, owner, started_at)
+            SELECT $1, id, $3, $4 FROM tasks WHERE id = $2
+            RETURNING id, task_id, owner, started_at, stopped_at",
+        )
+        .bind(Uuid::new_v4())
+        .bind(id)
+        .bind(owner)
+        .bind(self.clock.now())
+        .fetch_optional(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(entry)
+    }
+
+    async fn stop_timer(&self, id: Uuid, owner: &str) -> Result<Option<TimeEntry>, StoreError> {
+        let mut tx = self.begin().await?;
+        let entry = sqlx::query_as(
+            "UPDATE time_entries
+            SET stopped_at = greatest($3, started_at)
+            WHERE task_id = $1 AND owner = $2 AND stopped_at IS NULL
+            RETURNING id, task_id, owner, started_at, stopped_at",
+        )
+        .bind(id)
+        .bind(owner)
+        .bind(self.clock.now())
+        .fetch_optional(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(entry)
+    }
+
+    async fn tracked_seconds(&self, id: Uuid) -> Result<i64, StoreError> {
+        sqlx::query_scalar(
+            "SELECT coalesce(
+                sum(extract(epoch FROM coalesce(stopped_at, $2) - started_at)),
+                0
+            )::bigint
+            FROM time_entries
+            WHERE task_id = $1",
+        )
+        .bind(id)
+        .bind(self.clock.now())
+        .fetch_one(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn timesheet(
+        &self,
+        owner: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<TimesheetEntry>, StoreError> {
+        sqlx::query_as(
+            "SELECT e.id, e.task_id, e.owner, e.started_at, e.stopped_at, t.title,
+                extract(epoch FROM coalesce(e.stopped_at, $4) - e.started_at)::bigint
+                    AS seconds
+            FROM time_entries AS e
+            JOIN tasks AS t ON t.id = e.task_id
+            WHERE e.owner = $1 AND e.started_at >= $2 AND e.started_at < $3
+            ORDER BY e.started_at",
+        )
+        .bind(owner)
+        .bind(from)
+        .bind(to)
+        .bind(self.clock.now())
+        .fetch_all(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn add_attachment(
+        &self,
+        id: Uuid,
+        attachment: &NewAttachment,
+    ) -> Result<Option<Attachment>, StoreError> {
+        let (scan_result, threat) = match &attachment.verdict {
+            ScanVerdict::Clean => (ScanResult::Clean, None),
+            ScanVerdict::Infected(threat) => (ScanResult::Infected, Some(threat)),
+        };
+        let size = i64::try_from(attachment.content.len()).unwrap_or(i64::MAX);
+        let mut tx = self.begin().await?;
+        let added = sqlx::query_as(&format!(
+            "INSERT INTO attachments (id, task_id, filename, content_type, size, sha256,
+                uploaded_by, scan_result, threat, scanned_by, scanned_at, quarantined, content)
+            SELECT $1, id, $3, $4, $5, $6, $7, $8, $9, $10, $11, $8 = 'infected', $12
+            FROM tasks WHERE id = $2
+            RETURNING {ATTACHMENT_COLUMNS}"
+        ))
+        .bind(Uuid::new_v4())
+        .bind(id)
+        .bind(&attachment.filename)
+        .bind(&attachment.content_type)
+        .bind(size)
+        .bind(attachment.sha256())
+        .bind(&attachment.uploaded_by)
+        .bind(scan_result)
+        .bind(threat)
+        .bind(&attachment.scanned_by)
+        .bind(attachment.scanned_at)
+        .bind(&attachment.content)
+        .fetch_optional(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(added)
+    }
+
+    async fn attachments(&self, id: Uuid) -> Result<Option<Vec<Attachment>>, StoreError> {
+        let mut tx = self.begin().await?;
+        let exists = sqlx::query("SELECT 1 FROM tasks WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if exists.is_none() {
+            return Ok(None);
+        }
+
+        sqlx::query_as(&format!(
+            "SELECT {ATTACHMENT_COLUMNS} FROM attachments
+            WHERE task_id = $1
+            ORDER BY uploaded_at, id"
+        ))
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await
+        .map(Some)
+        .map_err(StoreError::from)
+    }
+
+    async fn attachment(
+        &self,
+        id: Uuid,
+        attachment_id: Uuid,
+    ) -> Result<Option<(Attachment, Vec<u8>)>, StoreError> {
+        let row: Option<AttachmentRow> = sqlx::query_as(&format!(
+            "SELECT {ATTACHMENT_COLUMNS}, content FROM attachments
+            WHERE task_id = $1 AND id = $2"
+        ))
+        .bind(id)
+        .bind(attachment_id)
+        .fetch_optional(&mut *self.begin().await?)
+        .await?;
+        Ok(row.map(|row| (row.attachment, row.content)))
+    }
+
+    async fn remove_attachment(&self, id: Uuid, attachment_id: Uuid) -> Result<bool, StoreError> {
+        let mut tx = self.begin().await?;
+        let result = sqlx::query("DELETE FROM attachments WHERE task_id = $1 AND id = $2")
+            .bind(id)
+            .bind(attachment_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn thumbnail(
+        &self,
+        id: Uuid,
+        attachment_id: Uuid,
+    ) -> Result<Option<Thumbnail>, StoreError> {
+        let row: Option<(String, bool, bool, Option<Vec<u8>>)> = sqlx::query_as(
+            "SELECT a.content_type, a.quarantined, t.attachment_id IS NOT NULL, t.content
+            FROM attachments a
+            LEFT JOIN attachment_thumbnails t ON t.attachment_id = a.id
+            WHERE a.task_id = $1 AND a.id = $2",
+        )
+        .bind(id)
+        .bind(attachment_id)
+        .fetch_optional(&mut *self.begin().await?)
+        .await?;
+        Ok(row.map(|(content_type, quarantined, generated, png)| {
+            match png {
+                _ if quarantined || !thumbnails::is_image(&content_type) => Thumbnail::Unavailable,
+                Some(png) => Thumbnail::Ready(png),
+                // images which couldn't be decoded have no thumbnail
+                None if generated => Thumbnail::Unavailable,
+                None => Thumbnail::Pending,
+            }
+        }))
+    }
+
+    async fn storage_usage(&self) -> Result<Vec<StorageUsage>, StoreError> {
+        sqlx::query_as(
+            "SELECT t.owner, count(*) AS files, sum(a.size)::bigint AS bytes
+            FROM attachments a
+            JOIN tasks t ON t.id = a.task_id
+            GROUP BY t.owner
+            ORDER BY bytes DESC, t.owner",
+        )
+        .fetch_all(&mut *self.begin_read().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn storage_used<'a>(&self, owner: Option<&'a str>) -> Result<i64, StoreError> {
+        sqlx::query_scalar(
+            "SELECT coalesce(sum(a.size), 0)::bigint
+            FROM attachments a
+            JOIN tasks t ON t.id = a.task_id
+            WHERE t.owner IS NOT DISTINCT FROM $1",
+        )
+        .bind(owner)
+        .fetch_one(&mut *self.begin().await?)
+        .await
+        .map_err(StoreError::from)
+    }
+
+    async fn revert(&self, id: Uuid, version: i32) -> Result<Option<TodoTask>, StoreError> {
+        let mut tx = self.begin().await?;
+        if !lock_task(&mut tx, id).await? {
+            return Ok(None);
+        }
+        describe_revert(&mut tx, version).await?;
+        let task: Option<TodoTask> = sqlx::query_as(
+            "UPDATE tasks
+            SET title = h.title,
+                description = h.description,
+                status = h.status,
+                due = h.due,
+                tags = h.tags,
+                estimate = h.estimate,
+                progress = h.progress,
+                colour = h.colour,
+                title_cy = h.title_cy,
+                description_cy = h.description_cy,
+                status_reason = h.status_reason,
+                custom_status = h.custom_status,
+                -- as by `TodoTask::transition`
+                completed_at = CASE
+                    WHEN h.status <> 'complete' THEN NULL
+                    WHEN tasks.status = 'complete' THEN tasks.completed_at
+                    ELSE $3
+                END
+            FROM task_history AS h
+            WHERE tasks.id = $1 AND h.task_id = $1 AND h.version = $2
+            RETURNING tasks.title, tasks.description, tasks.status, tasks.due, tasks.tags,
+                tasks.estimate, tasks.progress, tasks.colour, tasks.title_cy,
+                tasks.description_cy, tasks.completed_at, tasks.status_reason,
+                tasks.custom_status",
+        )
+        .bind(id)
+        .bind(version)
+        .bind(self.clock.now())
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(task) = &task {
+            record_mentions(&mut tx, id, task).await?;
+        }
+        tx.commit().await?;
+        Ok(task)
+    }
+
+    async fn burndown(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        bucket: Bucket,
+    ) -> Result<Vec<BurndownBucket>, StoreError> {
+        let (days, start, end) = self.overdue_bounds();
+        sqlx::query_as(BURNDOWN_QUERY)
+            .bind(bucket.unit())
+            .bind(from)
+            .bind(to)
+            .bind(days)
+            .bind(start)
+            .bind(end)
+            .fetch_all(&mut *self.begin_read().await?)
+            .await
+            .map_err(StoreError::from)
+    }
+
+    async fn graph(&self, filters: &[FilterExpr]) -> Result<TaskGraph, StoreError> {
+        let mut tx = self.begin().await?;
+        let mut query = QueryBuilder::new(
+            "SELECT id, title, coalesce(custom_status, status::text) AS status,
+                created_at AS start, due,
+                extract(epoch FROM due - created_at)::bigint AS duration_seconds
+            FROM task_listing",
+        );
+        for (i, filter) in filters.iter().enumerate() {
+            query.push(if i == 0 { " WHERE " } else { " AND " });
+            filter.push_sql(&mut query);
+        }
+        // ties in the topological order are broken by due date
+        query.push(" ORDER BY due, id");
+        let nodes = query.build_query_as().fetch_all(&mut *tx).await?;
+
+        let edges = sqlx::query_as(
+            "SELECT target AS \"from\", source AS \"to\"
+            FROM task_links
+            WHERE kind = 'depends_on'",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        Ok(TaskGraph::new(nodes, edges))
+    }
+
+    async fn workload(&self) -> Result<Refreshed<Vec<Workload>>, StoreError> {
+        let (all, owner) = self.stats_scope();
+        let mut tx = self.begin_read().await?;
+        let stats = sqlx::query_as(WORKLOAD_QUERY)
+            .bind(all)
+            .bind(owner)
+            .fetch_all(&mut *tx)
+            .await?;
+        Ok(Refreshed {
+            refreshed_at: stats_refreshed_at(&mut tx).await?,
+            stats,
+        })
+    }
+
+    async fn estimate_variance(
+        &self,
+        grouping: EstimateGrouping,
+    ) -> Result<Refreshed<Vec<EstimateVariance>>, StoreError> {
+        let (all, owner) = self.stats_scope();
+        let mut tx = self.begin_read().await?;
+        let stats = sqlx::query_as(ESTIMATE_VARIANCE_QUERY)
+            .bind(grouping_name(grouping))
+            .bind(all)
+            .bind(owner)
+            .fetch_all(&mut *tx)
+            .await?;
+        Ok(Refreshed {
+            refreshed_at: stats_refreshed_at(&mut tx).await?,
+            stats,
+        })
+    }
+}
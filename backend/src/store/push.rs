@@ -0,0 +1,116 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::webpush::{PushSubscription, Subscribed, SubscriptionKeys};
+
+/// Storage of browsers' subscriptions to Web Push notifications, in the
+/// `push_subscriptions` table.
+#[derive(Clone, Debug)]
+pub struct PushSubscriptionStore {
+    pool: PgPool,
+}
+
+impl PushSubscriptionStore {
+    /// Create a store using the database behind `pool`.
+    #[must_use]
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Subscribe a browser of a user, replacing the keys of any subscription
+    /// it already has, or return `None` if the user isn't registered.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn subscribe(
+        &self,
+        user_id: &str,
+        subscription: &PushSubscription,
+    ) -> Result<Option<Subscribed>, sqlx::Error> {
+        sqlx::query_as(
+            "INSERT INTO push_subscriptions (id, user_id, endpoint, p256dh, auth)
+            SELECT $1, id, $3, $4, $5 FROM users WHERE id = $2
+            ON CONFLICT (user_id, endpoint)
+                DO UPDATE SET p256dh = excluded.p256dh, auth = excluded.auth
+            RETURNING id, endpoint, created_at",
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(&subscription.endpoint)
+        .bind(&subscription.keys.p256dh)
+        .bind(&subscription.keys.auth)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// List the browsers a user subscribed.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn list(&self, user_id: &str) -> Result<Vec<Subscribed>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, endpoint, created_at FROM push_subscriptions
+            WHERE user_id = $1
+            ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Get the subscriptions of a user to push notifications to, with their
+    /// IDs.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<(Uuid, PushSubscription)>, sqlx::Error> {
+        let rows: Vec<(Uuid, String, String, String)> = sqlx::query_as(
+            "SELECT id, endpoint, p256dh, auth FROM push_subscriptions WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(id, endpoint, p256dh, auth)| {
+                let keys = SubscriptionKeys { p256dh, auth };
+                (id, PushSubscription { endpoint, keys })
+            })
+            .collect())
+    }
+
+    /// Unsubscribe the browser with `id`, if it belongs to the user,
+    /// returning whether it did.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn unsubscribe(&self, user_id: &str, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM push_subscriptions WHERE id = $1 AND user_id = $2")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Remove the subscription with `id`, e.g. because its push service said
+    /// it has expired.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn remove(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM push_subscriptions WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,425 @@
+use std::{future::Future, ops::RangeInclusive, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use tracing::debug;
+use uuid::Uuid;
+
+use super::{
+    ExportedTask, HistoryErasure, Reschedule, RescheduleOutcome, SearchMatch, TaskStore,
+    TaskVersion,
+};
+use crate::{
+    DueWindow, FilterExpr, StoreError, TaskLink, TaskRecord, TodoTask,
+    attachments::{Attachment, NewAttachment},
+    feed::Activity,
+    graph::TaskGraph,
+    hooks::NewTask,
+    mentions::Mention,
+    sla::TaskMilestones,
+    stats::{
+        Bucket, BurndownBucket, EstimateGrouping, EstimateVariance, Refreshed, StorageUsage,
+        Workload,
+    },
+    sync::{ConflictRule, SyncOutcome},
+    thumbnails::Thumbnail,
+    tracking::{TimeEntry, TimesheetEntry},
+};
+
+/// Longest time to wait before the first retry.
+const BASE_DELAY: Duration = Duration::from_millis(10);
+/// Longest time to wait before any retry.
+const MAX_DELAY: Duration = Duration::from_millis(500);
+
+/// [`TaskStore`] retrying the writes of another store which lose a race with
+/// a concurrent transaction.
+///
+/// Writes failing with a serialisation failure or a deadlock are retried up
+/// to a number of times, after a random delay whose bound doubles with each
+/// attempt, so that transactions which collided don't collide again. Each
+/// write runs in a transaction of its own, which is rolled back when it
+/// fails, so it can be retried from scratch. Reads aren't retried.
+#[derive(Debug)]
+pub struct RetryingTaskStore {
+    /// Store whose writes are retried.
+    inner: Arc<dyn TaskStore>,
+    /// Number of times to retry a write.
+    retries: u32,
+}
+
+impl RetryingTaskStore {
+    /// Retry the writes of `inner` up to `retries` times.
+    #[must_use]
+    pub fn new(inner: Arc<dyn TaskStore>, retries: u32) -> Self {
+        Self { inner, retries }
+    }
+
+    /// Run an operation, retrying it while it loses races with concurrent
+    /// transactions.
+    async fn retry<T, F>(&self, operation: impl Fn() -> F + Send + Sync) -> Result<T, StoreError>
+    where
+        F: Future<Output = Result<T, StoreError>> + Send,
+    {
+        let mut attempt = 0;
+        loop {
+            match operation().await {
+                Err(e) if e.is_retryable() && attempt < self.retries => {
+                    let delay = rand::thread_rng().gen_range(delay_range(attempt));
+                    attempt += 1;
+                    debug!(
+                        attempt,
+                        delay_ms = delay.as_millis(),
+                        error = format!("{e}"),
+                        "retrying store operation"
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Range of times to wait before retrying after `attempt` earlier retries.
+fn delay_range(attempt: u32) -> RangeInclusive<Duration> {
+    Duration::ZERO
+        ..=BASE_DELAY
+            .saturating_mul(1_u32 << attempt.min(16))
+            .min(MAX_DELAY)
+}
+
+#[async_trait]
+impl TaskStore for RetryingTaskStore {
+    async fn create<'a>(
+        &self,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+    ) -> Result<Uuid, StoreError> {
+        self.retry(|| self.inner.create(task, owner)).await
+    }
+
+    async fn create_with_id<'a>(
+        &self,
+        id: Uuid,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+    ) -> Result<(), StoreError> {
+        self.retry(|| self.inner.create_with_id(id, task, owner))
+            .await
+    }
+
+    async fn update(&self, id: Uuid, task: &TodoTask) -> Result<bool, StoreError> {
+        self.retry(|| self.inner.update(id, task)).await
+    }
+
+    async fn reschedule(
+        &self,
+        filters: &[FilterExpr],
+        reschedule: Reschedule,
+        window: DueWindow,
+    ) -> Result<RescheduleOutcome, StoreError> {
+        self.retry(|| self.inner.reschedule(filters, reschedule, window))
+            .await
+    }
+
+    async fn upsert_external<'a>(
+        &self,
+        source: &str,
+        external_id: &str,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+        rule: ConflictRule,
+    ) -> Result<SyncOutcome, StoreError> {
+        self.retry(|| {
+            self.inner
+                .upsert_external(source, external_id, task, owner, rule)
+        })
+        .await
+    }
+
+    async fn count_open<'a>(&self, owner: Option<&'a str>) -> Result<i64, StoreError> {
+        self.inner.count_open(owner).await
+    }
+
+    async fn owned(&self, owner: &str) -> Result<Vec<TaskRecord>, StoreError> {
+        self.inner.owned(owner).await
+    }
+
+    async fn erase(&self, owner: &str, history: HistoryErasure) -> Result<u64, StoreError> {
+        self.retry(|| self.inner.erase(owner, history)).await
+    }
+
+    async fn get(&self, id: Uuid) -> Result<Option<TodoTask>, StoreError> {
+        self.inner.get(id).await
+    }
+
+    async fn get_many(&self, ids: &[Uuid]) -> Result<Vec<TaskRecord>, StoreError> {
+        self.inner.get_many(ids).await
+    }
+
+    async fn milestones(&self, ids: &[Uuid]) -> Result<Vec<TaskMilestones>, StoreError> {
+        self.inner.milestones(ids).await
+    }
+
+    async fn list<'a>(
+        &self,
+        filters: &[FilterExpr],
+        pinned_by: Option<&'a str>,
+    ) -> Result<Vec<TaskRecord>, StoreError> {
+        self.inner.list(filters, pinned_by).await
+    }
+
+    async fn export_page(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<ExportedTask>, StoreError> {
+        self.inner.export_page(after, limit).await
+    }
+
+    async fn search(&self, title: &str, threshold: f32) -> Result<Vec<SearchMatch>, StoreError> {
+        self.inner.search(title, threshold).await
+    }
+
+    async fn find_duplicates(
+        &self,
+        task: &TodoTask,
+        threshold: f32,
+    ) -> Result<Vec<TaskRecord>, StoreError> {
+        self.inner.find_duplicates(task, threshold).await
+    }
+
+    async fn links(&self, id: Uuid) -> Result<Option<Vec<TaskLink>>, StoreError> {
+        self.inner.links(id).await
+    }
+
+    async fn add_link(&self, link: &TaskLink) -> Result<(), StoreError> {
+        self.retry(|| self.inner.add_link(link)).await
+    }
+
+    async fn remove_link(&self, link: &TaskLink) -> Result<bool, StoreError> {
+        self.retry(|| self.inner.remove_link(link)).await
+    }
+
+    async fn history(
+        &self,
+        id: Uuid,
+        versions: RangeInclusive<i64>,
+    ) -> Result<Vec<TaskVersion>, StoreError> {
+        self.inner.history(id, versions).await
+    }
+
+    async fn version(&self, id: Uuid, version: i32) -> Result<Option<TaskVersion>, StoreError> {
+        self.inner.version(id, version).await
+    }
+
+    async fn latest_version(&self, id: Uuid) -> Result<Option<i32>, StoreError> {
+        self.inner.latest_version(id).await
+    }
+
+    async fn created<'a>(
+        &self,
+        id: Uuid,
+        owner: Option<&'a str>,
+    ) -> Result<Option<TodoTask>, StoreError> {
+        self.inner.created(id, owner).await
+    }
+
+    async fn assign<'a>(
+        &self,
+        id: Uuid,
+        owner: Option<&'a str>,
+        assignee: Option<&'a str>,
+    ) -> Result<bool, StoreError> {
+        self.retry(|| self.inner.assign(id, owner, assignee)).await
+    }
+
+    async fn set_pinned(&self, id: Uuid, owner: &str, pinned: bool) -> Result<bool, StoreError> {
+        self.retry(|| self.inner.set_pinned(id, owner, pinned))
+            .await
+    }
+
+    async fn pinned(&self, id: Uuid, owner: &str) -> Result<bool, StoreError> {
+        self.inner.pinned(id, owner).await
+    }
+
+    async fn activity<'a>(
+        &self,
+        owner: Option<&'a str>,
+        limit: i64,
+    ) -> Result<Vec<Activity>, StoreError> {
+        self.inner.activity(owner, limit).await
+    }
+
+    async fn new_tasks<'a>(
+        &self,
+        owner: Option<&'a str>,
+        limit: i64,
+    ) -> Result<Vec<NewTask>, StoreError> {
+        self.inner.new_tasks(owner, limit).await
+    }
+
+    async fn mentions(&self, user: &str) -> Result<Vec<Mention>, StoreError> {
+        self.inner.mentions(user).await
+    }
+
+    async fn start_timer(&self, id: Uuid, owner: &str) -> Result<Option<TimeEntry>, StoreError> {
+        self.retry(|| self.inner.start_timer(id, owner)).await
+    }
+
+    async fn stop_timer(&self, id: Uuid, owner: &str) -> Result<Option<TimeEntry>, StoreError> {
+        self.retry(|| self.inner.stop_timer(id, owner)).await
+    }
+
+    async fn tracked_seconds(&self, id: Uuid) -> Result<i64, StoreError> {
+        self.inner.tracked_seconds(id).await
+    }
+
+    async fn timesheet(
+        &self,
+        owner: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<TimesheetEntry>, StoreError> {
+        self.inner.timesheet(owner, from, to).await
+    }
+
+    async fn add_attachment(
+        &self,
+        id: Uuid,
+        attachment: &NewAttachment,
+    ) -> Result<Option<Attachment>, StoreError> {
+        self.retry(|| self.inner.add_attachment(id, attachment))
+            .await
+    }
+
+    async fn attachments(&self, id: Uuid) -> Result<Option<Vec<Attachment>>, StoreError> {
+        self.inner.attachments(id).await
+    }
+
+    async fn attachment(
+        &self,
+        id: Uuid,
+        attachment_id: Uuid,
+    ) -> Result<Option<(Attachment, Vec<u8>)>, StoreError> {
+        self.inner.attachment(id, attachment_id).await
+    }
+
+    async fn remove_attachment(&self, id: Uuid, attachment_id: Uuid) -> Result<bool, StoreError> {
+        self.retry(|| self.inner.remove_attachment(id, attachment_id))
+            .await
+    }
+
+    async fn thumbnail(
+        &self,
+        id: Uuid,
+        attachment_id: Uuid,
+    ) -> Result<Option<Thumbnail>, StoreError> {
+        self.inner.thumbnail(id, attachment_id).await
+    }
+
+    async fn storage_usage(&self) -> Result<Vec<StorageUsage>, StoreError> {
+        self.inner.storage_usage().await
+    }
+
+    async fn storage_used<'a>(&self, owner: Option<&'a str>) -> Result<i64, StoreError> {
+        self.inner.storage_used(owner).await
+    }
+
+    async fn revert(&self, id: Uuid, version: i32) -> Result<Option<TodoTask>, StoreError> {
+        self.retry(|| self.inner.revert(id, version)).await
+    }
+
+    async fn burndown(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        bucket: Bucket,
+    ) -> Result<Vec<BurndownBucket>, StoreError> {
+        self.inner.burndown(from, to, bucket).await
+    }
+
+    async fn graph(&self, filters: &[FilterExpr]) -> Result<TaskGraph, StoreError> {
+        self.inner.graph(filters).await
+    }
+
+    async fn workload(&self) -> Result<Refreshed<Vec<Workload>>, StoreError> {
+        self.inner.workload().await
+    }
+
+    async fn estimate_variance(
+        &self,
+        grouping: EstimateGrouping,
+    ) -> Result<Refreshed<Vec<EstimateVariance>>, StoreError> {
+        self.inner.estimate_variance(grouping).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+    use crate::{TodoStatus, error::tests::database_error, store::MockTaskStore};
+
+    fn task() -> TodoTask {
+        let now = Utc::now();
+        let due = now + TimeDelta::days(7);
+        TodoTask::new(
+            "File order".to_owned(),
+            None,
+            TodoStatus::NotStarted,
+            &due,
+            now,
+        )
+    }
+
+    #[test]
+    fn backoff() {
+        assert_eq!(delay_range(0), Duration::ZERO..=BASE_DELAY);
+        assert_eq!(delay_range(2), Duration::ZERO..=BASE_DELAY * 4);
+        assert_eq!(delay_range(10), Duration::ZERO..=MAX_DELAY);
+        // the bound can't overflow however many retries there are
+        assert_eq!(delay_range(u32::MAX), Duration::ZERO..=MAX_DELAY);
+    }
+
+    #[tokio::test]
+    async fn retries_lost_races() {
+        let mut inner = MockTaskStore::new();
+        let mut failures = ["40001", "40P01"].into_iter();
+        inner.expect_create().times(3).returning(move |_, _| {
+            // each attempt's transaction was rolled back, so creating the
+            // task again doesn't duplicate it
+            failures
+                .next()
+                .map_or(Ok(Uuid::nil()), |code| Err(database_error(code)))
+        });
+        let store = RetryingTaskStore::new(Arc::new(inner), 3);
+        assert_eq!(store.create(&task(), None).await.unwrap(), Uuid::nil());
+    }
+
+    #[tokio::test]
+    async fn gives_up() {
+        let mut inner = MockTaskStore::new();
+        inner
+            .expect_create()
+            .times(3)
+            .returning(|_, _| Err(database_error("40001")));
+        let store = RetryingTaskStore::new(Arc::new(inner), 2);
+        let result = store.create(&task(), None).await;
+        assert!(result.is_err_and(|e| e.is_retryable()));
+    }
+
+    #[tokio::test]
+    async fn doesnt_retry_other_errors() {
+        let mut inner = MockTaskStore::new();
+        inner
+            .expect_create()
+            .times(1)
+            .returning(|_, _| Err(database_error("23505")));
+        let store = RetryingTaskStore::new(Arc::new(inner), 3);
+        let result = store.create(&task(), None).await;
+        assert!(matches!(result, Err(StoreError::Conflict(_))));
+    }
+}
@@ -0,0 +1,71 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::security::{SecurityEvent, SecurityEventKind};
+
+/// Storage of the security event log, in the `security_events` table.
+#[derive(Clone, Debug)]
+pub struct SecurityLog {
+    pool: PgPool,
+}
+
+impl SecurityLog {
+    /// Create a log using the database behind `pool`.
+    #[must_use]
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record an event.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn record(
+        &self,
+        kind: SecurityEventKind,
+        owner: Option<&str>,
+        detail: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO security_events (kind, owner, detail) VALUES ($1, $2, $3)")
+            .bind(kind)
+            .bind(owner)
+            .bind(detail)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Get up to `limit` events, newest first, optionally only those of one
+    /// `kind`, concerning one `owner` or in a period of time.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn query(
+        &self,
+        kind: Option<SecurityEventKind>,
+        owner: Option<&str>,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> Result<Vec<SecurityEvent>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, kind, owner, detail, occurred_at
+            FROM security_events
+            WHERE ($1::security_event_kind IS NULL OR kind = $1)
+                AND ($2::text IS NULL OR owner = $2)
+                AND ($3::timestamptz IS NULL OR occurred_at >= $3)
+                AND ($4::timestamptz IS NULL OR occurred_at < $4)
+            ORDER BY id DESC
+            LIMIT $5",
+        )
+        .bind(kind)
+        .bind(owner)
+        .bind(since)
+        .bind(until)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
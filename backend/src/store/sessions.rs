@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::tokens;
+
+/// Storage of browser sessions, in the `sessions` table.
+///
+/// Sessions are identified by a random secret, which is given to the browser
+/// as a cookie; only hashes of these are stored.
+#[derive(Clone, Debug)]
+pub struct SessionStore {
+    pool: PgPool,
+}
+
+impl SessionStore {
+    /// Create a store using the database behind `pool`.
+    #[must_use]
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Start a session for `owner` lasting until `expires_at`, returning its
+    /// secret.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn create(
+        &self,
+        owner: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<String, sqlx::Error> {
+        let secret = tokens::generate();
+        sqlx::query("INSERT INTO sessions (id_hash, owner, expires_at) VALUES ($1, $2, $3)")
+            .bind(tokens::hash(&secret))
+            .bind(owner)
+            .bind(expires_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(secret)
+    }
+
+    /// Get the owner of the session with `secret`, and when the session
+    /// expires, if it hasn't already.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn owner(
+        &self,
+        secret: &str,
+    ) -> Result<Option<(String, DateTime<Utc>)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT owner, expires_at FROM sessions WHERE id_hash = $1 AND expires_at > now()",
+        )
+        .bind(tokens::hash(secret))
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// End the session with `secret`, and any expired sessions.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn delete(&self, secret: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM sessions WHERE id_hash = $1 OR expires_at <= now()")
+            .bind(tokens::hash(secret))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete every session expired at `now`, returning how many there were.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM sessions WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
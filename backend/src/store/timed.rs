@@ -0,0 +1,375 @@
+use std::{
+    future::Future,
+    ops::RangeInclusive,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tracing::warn;
+use uuid::Uuid;
+
+use super::{
+    ExportedTask, HistoryErasure, Reschedule, RescheduleOutcome, SearchMatch, TaskStore,
+    TaskVersion,
+};
+use crate::{
+    DueWindow, FilterExpr, StoreError, TaskLink, TaskRecord, TodoTask,
+    attachments::{Attachment, NewAttachment},
+    feed::Activity,
+    graph::TaskGraph,
+    hooks::NewTask,
+    mentions::Mention,
+    metrics::Metrics,
+    sla::TaskMilestones,
+    stats::{
+        Bucket, BurndownBucket, EstimateGrouping, EstimateVariance, Refreshed, StorageUsage,
+        Workload,
+    },
+    sync::{ConflictRule, SyncOutcome},
+    thumbnails::Thumbnail,
+    tracking::{TimeEntry, TimesheetEntry},
+};
+
+/// [`TaskStore`] timing the operations of another store.
+///
+/// The duration of each operation is recorded in [`Metrics`] under the name
+/// of the method, and operations slower than a threshold are logged as
+/// warnings by that name, so the SQL they run is never logged.
+#[derive(Debug)]
+pub struct TimedTaskStore {
+    /// Store whose operations are timed.
+    inner: Arc<dyn TaskStore>,
+    /// Registry to record durations in.
+    metrics: Arc<Metrics>,
+    /// Duration above which an operation is logged as slow.
+    slow_threshold: Duration,
+}
+
+impl TimedTaskStore {
+    /// Time the operations of `inner`, recording them in `metrics` and
+    /// logging those taking longer than `slow_threshold`.
+    #[must_use]
+    pub fn new(inner: Arc<dyn TaskStore>, metrics: Arc<Metrics>, slow_threshold: Duration) -> Self {
+        Self {
+            inner,
+            metrics,
+            slow_threshold,
+        }
+    }
+
+    /// Run the operation called `name`, recording how long it takes.
+    async fn time<T>(
+        &self,
+        name: &'static str,
+        operation: impl Future<Output = Result<T, StoreError>> + Send,
+    ) -> Result<T, StoreError> {
+        let start = Instant::now();
+        let result = operation.await;
+        let elapsed = start.elapsed();
+        self.metrics.observe_query(name, elapsed);
+        if elapsed > self.slow_threshold {
+            warn!(
+                query = name,
+                duration_ms = elapsed.as_millis(),
+                failed = result.is_err(),
+                "slow query"
+            );
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl TaskStore for TimedTaskStore {
+    async fn create<'a>(
+        &self,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+    ) -> Result<Uuid, StoreError> {
+        self.time("create", self.inner.create(task, owner)).await
+    }
+
+    async fn create_with_id<'a>(
+        &self,
+        id: Uuid,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+    ) -> Result<(), StoreError> {
+        self.time("create_with_id", self.inner.create_with_id(id, task, owner))
+            .await
+    }
+
+    async fn update(&self, id: Uuid, task: &TodoTask) -> Result<bool, StoreError> {
+        self.time("update", self.inner.update(id, task)).await
+    }
+
+    async fn reschedule(
+        &self,
+        filters: &[FilterExpr],
+        reschedule: Reschedule,
+        window: DueWindow,
+    ) -> Result<RescheduleOutcome, StoreError> {
+        self.time(
+            "reschedule",
+            self.inner.reschedule(filters, reschedule, window),
+        )
+        .await
+    }
+
+    async fn upsert_external<'a>(
+        &self,
+        source: &str,
+        external_id: &str,
+        task: &TodoTask,
+        owner: Option<&'a str>,
+        rule: ConflictRule,
+    ) -> Result<SyncOutcome, StoreError> {
+        self.time(
+            "upsert_external",
+            self.inner
+                .upsert_external(source, external_id, task, owner, rule),
+        )
+        .await
+    }
+
+    async fn count_open<'a>(&self, owner: Option<&'a str>) -> Result<i64, StoreError> {
+        self.time("count_open", self.inner.count_open(owner)).await
+    }
+
+    async fn owned(&self, owner: &str) -> Result<Vec<TaskRecord>, StoreError> {
+        self.time("owned", self.inner.owned(owner)).await
+    }
+
+    async fn erase(&self, owner: &str, history: HistoryErasure) -> Result<u64, StoreError> {
+        self.time("erase", self.inner.erase(owner, history)).await
+    }
+
+    async fn get(&self, id: Uuid) -> Result<Option<TodoTask>, StoreError> {
+        self.time("get", self.inner.get(id)).await
+    }
+
+    async fn get_many(&self, ids: &[Uuid]) -> Result<Vec<TaskRecord>, StoreError> {
+        self.time("get_many", self.inner.get_many(ids)).await
+    }
+
+    async fn milestones(&self, ids: &[Uuid]) -> Result<Vec<TaskMilestones>, StoreError> {
+        self.time("milestones", self.inner.milestones(ids)).await
+    }
+
+    async fn list<'a>(
+        &self,
+        filters: &[FilterExpr],
+        pinned_by: Option<&'a str>,
+    ) -> Result<Vec<TaskRecord>, StoreError> {
+        self.time("list", self.inner.list(filters, pinned_by)).await
+    }
+
+    async fn export_page(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<ExportedTask>, StoreError> {
+        self.time("export_page", self.inner.export_page(after, limit))
+            .await
+    }
+
+    async fn search(&self, title: &str, threshold: f32) -> Result<Vec<SearchMatch>, StoreError> {
+        self.time("search", self.inner.search(title, threshold))
+            .await
+    }
+
+    async fn find_duplicates(
+        &self,
+        task: &TodoTask,
+        threshold: f32,
+    ) -> Result<Vec<TaskRecord>, StoreError> {
+        self.time(
+            "find_duplicates",
+            self.inner.find_duplicates(task, threshold),
+        )
+        .await
+    }
+
+    async fn links(&self, id: Uuid) -> Result<Option<Vec<TaskLink>>, StoreError> {
+        self.time("links", self.inner.links(id)).await
+    }
+
+    async fn add_link(&self, link: &TaskLink) -> Result<(), StoreError> {
+        self.time("add_link", self.inner.add_link(link)).await
+    }
+
+    async fn remove_link(&self, link: &TaskLink) -> Result<bool, StoreError> {
+        self.time("remove_link", self.inner.remove_link(link)).await
+    }
+
+    async fn history(
+        &self,
+        id: Uuid,
+        versions: RangeInclusive<i64>,
+    ) -> Result<Vec<TaskVersion>, StoreError> {
+        self.time("history", self.inner.history(id, versions)).await
+    }
+
+    async fn version(&self, id: Uuid, version: i32) -> Result<Option<TaskVersion>, StoreError> {
+        self.time("version", self.inner.version(id, version)).await
+    }
+
+    async fn latest_version(&self, id: Uuid) -> Result<Option<i32>, StoreError> {
+        self.time("latest_version", self.inner.latest_version(id))
+            .await
+    }
+
+    async fn created<'a>(
+        &self,
+        id: Uuid,
+        owner: Option<&'a str>,
+    ) -> Result<Option<TodoTask>, StoreError> {
+        self.time("created", self.inner.created(id, owner)).await
+    }
+
+    async fn assign<'a>(
+        &self,
+        id: Uuid,
+        owner: Option<&'a str>,
+        assignee: Option<&'a str>,
+    ) -> Result<bool, StoreError> {
+        self.time("assign", self.inner.assign(id, owner, assignee))
+            .await
+    }
+
+    async fn set_pinned(&self, id: Uuid, owner: &str, pinned: bool) -> Result<bool, StoreError> {
+        self.time("set_pinned", self.inner.set_pinned(id, owner, pinned))
+            .await
+    }
+
+    async fn pinned(&self, id: Uuid, owner: &str) -> Result<bool, StoreError> {
+        self.time("pinned", self.inner.pinned(id, owner)).await
+    }
+
+    async fn activity<'a>(
+        &self,
+        owner: Option<&'a str>,
+        limit: i64,
+    ) -> Result<Vec<Activity>, StoreError> {
+        self.time("activity", self.inner.activity(owner, limit))
+            .await
+    }
+
+    async fn new_tasks<'a>(
+        &self,
+        owner: Option<&'a str>,
+        limit: i64,
+    ) -> Result<Vec<NewTask>, StoreError> {
+        self.time("new_tasks", self.inner.new_tasks(owner, limit))
+            .await
+    }
+
+    async fn mentions(&self, user: &str) -> Result<Vec<Mention>, StoreError> {
+        self.time("mentions", self.inner.mentions(user)).await
+    }
+
+    async fn start_timer(&self, id: Uuid, owner: &str) -> Result<Option<TimeEntry>, StoreError> {
+        self.time("start_timer", self.inner.start_timer(id, owner))
+            .await
+    }
+
+    async fn stop_timer(&self, id: Uuid, owner: &str) -> Result<Option<TimeEntry>, StoreError> {
+        self.time("stop_timer", self.inner.stop_timer(id, owner))
+            .await
+    }
+
+    async fn tracked_seconds(&self, id: Uuid) -> Result<i64, StoreError> {
+        self.time("tracked_seconds", self.inner.tracked_seconds(id))
+            .await
+    }
+
+    async fn timesheet(
+        &self,
+        owner: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<TimesheetEntry>, StoreError> {
+        self.time("timesheet", self.inner.timesheet(owner, from, to))
+            .await
+    }
+
+    async fn add_attachment(
+        &self,
+        id: Uuid,
+        attachment: &NewAttachment,
+    ) -> Result<Option<Attachment>, StoreError> {
+        self.time("add_attachment", self.inner.add_attachment(id, attachment))
+            .await
+    }
+
+    async fn attachments(&self, id: Uuid) -> Result<Option<Vec<Attachment>>, StoreError> {
+        self.time("attachments", self.inner.attachments(id)).await
+    }
+
+    async fn attachment(
+        &self,
+        id: Uuid,
+        attachment_id: Uuid,
+    ) -> Result<Option<(Attachment, Vec<u8>)>, StoreError> {
+        self.time("attachment", self.inner.attachment(id, attachment_id))
+            .await
+    }
+
+    async fn remove_attachment(&self, id: Uuid, attachment_id: Uuid) -> Result<bool, StoreError> {
+        self.time(
+            "remove_attachment",
+            self.inner.remove_attachment(id, attachment_id),
+        )
+        .await
+    }
+
+    async fn thumbnail(
+        &self,
+        id: Uuid,
+        attachment_id: Uuid,
+    ) -> Result<Option<Thumbnail>, StoreError> {
+        self.time("thumbnail", self.inner.thumbnail(id, attachment_id))
+            .await
+    }
+
+    async fn storage_usage(&self) -> Result<Vec<StorageUsage>, StoreError> {
+        self.time("storage_usage", self.inner.storage_usage()).await
+    }
+
+    async fn storage_used<'a>(&self, owner: Option<&'a str>) -> Result<i64, StoreError> {
+        self.time("storage_used", self.inner.storage_used(owner))
+            .await
+    }
+
+    async fn revert(&self, id: Uuid, version: i32) -> Result<Option<TodoTask>, StoreError> {
+        self.time("revert", self.inner.revert(id, version)).await
+    }
+
+    async fn burndown(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        bucket: Bucket,
+    ) -> Result<Vec<BurndownBucket>, StoreError> {
+        self.time("burndown", self.inner.burndown(from, to, bucket))
+            .await
+    }
+
+    async fn graph(&self, filters: &[FilterExpr]) -> Result<TaskGraph, StoreError> {
+        self.time("graph", self.inner.graph(filters)).await
+    }
+
+    async fn workload(&self) -> Result<Refreshed<Vec<Workload>>, StoreError> {
+        self.time("workload", self.inner.workload()).await
+    }
+
+    async fn estimate_variance(
+        &self,
+        grouping: EstimateGrouping,
+    ) -> Result<Refreshed<Vec<EstimateVariance>>, StoreError> {
+        self.time("estimate_variance", self.inner.estimate_variance(grouping))
+            .await
+    }
+}
@@ -0,0 +1,105 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::tokens::{self, AccessToken, TokenScope};
+
+/// Storage of personal access tokens, in the `access_tokens` table.
+///
+/// Only hashes of tokens are stored.
+#[derive(Clone, Debug)]
+pub struct TokenStore {
+    pool: PgPool,
+}
+
+impl TokenStore {
+    /// Create a store using the database behind `pool`.
+    #[must_use]
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Create a new token for `owner`, returning the token and its details.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn create(
+        &self,
+        owner: &str,
+        name: &str,
+        scopes: &[TokenScope],
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(String, AccessToken), sqlx::Error> {
+        let token = tokens::generate();
+        let details = sqlx::query_as(
+            "INSERT INTO access_tokens (id, owner, name, token_hash, scopes, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, owner, name, scopes, created_at, expires_at, last_used_at, revoked_at",
+        )
+        .bind(Uuid::new_v4())
+        .bind(owner)
+        .bind(name)
+        .bind(tokens::hash(&token))
+        .bind(scopes)
+        .bind(expires_at)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok((token, details))
+    }
+
+    /// List the tokens of `owner`, newest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn list(&self, owner: &str) -> Result<Vec<AccessToken>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, owner, name, scopes, created_at, expires_at, last_used_at, revoked_at
+            FROM access_tokens
+            WHERE owner = $1
+            ORDER BY created_at DESC",
+        )
+        .bind(owner)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Revoke a token of `owner`, returning whether it existed and wasn't
+    /// already revoked.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn revoke(&self, owner: &str, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE access_tokens
+            SET revoked_at = now()
+            WHERE id = $1 AND owner = $2 AND revoked_at IS NULL",
+        )
+        .bind(id)
+        .bind(owner)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Get the details of `token` if it is valid, recording that it was used.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn authenticate(&self, token: &str) -> Result<Option<AccessToken>, sqlx::Error> {
+        sqlx::query_as(
+            "UPDATE access_tokens
+            SET last_used_at = now()
+            WHERE token_hash = $1
+                AND revoked_at IS NULL
+                AND (expires_at IS NULL OR expires_at > now())
+            RETURNING id, owner, name, scopes, created_at, expires_at, last_used_at, revoked_at",
+        )
+        .bind(tokens::hash(token))
+        .fetch_optional(&self.pool)
+        .await
+    }
+}
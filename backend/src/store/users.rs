@@ -0,0 +1,163 @@
+use sqlx::PgPool;
+
+use crate::users::{Role, User};
+
+/// Columns of the `users` table, in the order of [`User`]'s fields.
+const COLUMNS: &str = "id, display_name, email, phone, roles, created_at, deactivated_at, digest";
+
+/// Changes to make to a user with [`UserStore::update`].
+// leaving and removing contact details are different changes
+#[allow(clippy::option_option)]
+#[derive(Clone, Debug, Default)]
+pub struct UserUpdate {
+    /// New name to show for the user.
+    pub display_name: Option<String>,
+    /// New email address of the user, or `Some(None)` to remove it.
+    pub email: Option<Option<String>>,
+    /// New phone number of the user, or `Some(None)` to remove it.
+    pub phone: Option<Option<String>>,
+    /// Roles to grant the user, replacing their current roles.
+    pub roles: Option<Vec<Role>>,
+    /// Whether to reactivate (`true`) or deactivate (`false`) the user.
+    pub active: Option<bool>,
+}
+
+/// Storage of registered users, in the `users` table.
+#[derive(Clone, Debug)]
+pub struct UserStore {
+    pool: PgPool,
+}
+
+impl UserStore {
+    /// Create a store using the database behind `pool`.
+    #[must_use]
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Register a user, returning `None` if the ID is already taken.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn create(
+        &self,
+        id: &str,
+        display_name: &str,
+        email: Option<&str>,
+        phone: Option<&str>,
+        roles: &[Role],
+    ) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as(&format!(
+            "INSERT INTO users (id, display_name, email, phone, roles)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (id) DO NOTHING
+            RETURNING {COLUMNS}"
+        ))
+        .bind(id)
+        .bind(display_name)
+        .bind(email)
+        .bind(phone)
+        .bind(roles)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// List every user, ordered by ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn list(&self) -> Result<Vec<User>, sqlx::Error> {
+        sqlx::query_as(&format!("SELECT {COLUMNS} FROM users ORDER BY id"))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Get a user by ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn get(&self, id: &str) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as(&format!("SELECT {COLUMNS} FROM users WHERE id = $1"))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Change a user, returning the user as changed or `None` if they don't
+    /// exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn update(&self, id: &str, update: &UserUpdate) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as(&format!(
+            "UPDATE users
+            SET display_name = coalesce($2, display_name),
+                email = CASE WHEN $3 THEN $4 ELSE email END,
+                phone = CASE WHEN $5 THEN $6 ELSE phone END,
+                roles = coalesce($7, roles),
+                deactivated_at = CASE $8::boolean
+                    WHEN true THEN NULL
+                    WHEN false THEN coalesce(deactivated_at, now())
+                    ELSE deactivated_at
+                END
+            WHERE id = $1
+            RETURNING {COLUMNS}"
+        ))
+        .bind(id)
+        .bind(update.display_name.as_deref())
+        .bind(update.email.is_some())
+        .bind(update.email.as_ref().and_then(Option::as_deref))
+        .bind(update.phone.is_some())
+        .bind(update.phone.as_ref().and_then(Option::as_deref))
+        .bind(update.roles.as_deref())
+        .bind(update.active)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Opt a user in to (`true`) or out of (`false`) digest emails,
+    /// returning whether they exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn set_digest(&self, id: &str, enabled: bool) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE users SET digest = $2 WHERE id = $1")
+            .bind(id)
+            .bind(enabled)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Get the roles of a user, or `None` if they aren't registered or have
+    /// been deactivated.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn active_roles(&self, id: &str) -> Result<Option<Vec<Role>>, sqlx::Error> {
+        sqlx::query_scalar("SELECT roles FROM users WHERE id = $1 AND deactivated_at IS NULL")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Whether a user is registered but deactivated.
+    ///
+    /// # Errors
+    ///
+    /// Returns any database error encountered.
+    pub async fn is_deactivated(&self, id: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT EXISTS (SELECT FROM users WHERE id = $1 AND deactivated_at IS NOT NULL)",
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,277 @@
+//! One-way sync of tasks from other to-do applications' APIs.
+//!
+//! Each provider's responses are parsed into [`RemoteTask`]s, which are
+//! stored with [`TaskStore::upsert_external`](crate::store::TaskStore::upsert_external).
+//! That remembers which task each remote item became, and the version of the
+//! task as of the last sync, so that later syncs update the same task and can
+//! tell whether it has since been changed locally.
+//!
+//! Only the title, description, status and due date are synced; the other
+//! fields of a task are left as they are set locally.
+
+pub mod google;
+pub mod microsoft;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    DueWindow, FieldChange, TaskDiff, TodoStatus, TodoTask, TodoTaskUnchecked, import::NO_DUE_DATE,
+};
+
+/// Application which tasks are synced from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncProvider {
+    /// Google Tasks, with the Tasks API.
+    Google,
+    /// Microsoft To Do, with the Microsoft Graph API.
+    Microsoft,
+}
+
+impl SyncProvider {
+    /// Get the name recorded as the source of tasks synced from the provider.
+    #[must_use]
+    pub fn source(self) -> &'static str {
+        match self {
+            Self::Google => "google",
+            Self::Microsoft => "microsoft",
+        }
+    }
+
+    /// Get the path and query, relative to the API's base URL, of the first
+    /// page of tasks in `list`.
+    #[must_use]
+    pub fn tasks_path(self, list: &str) -> String {
+        match self {
+            Self::Google => google::tasks_path(list),
+            Self::Microsoft => microsoft::tasks_path(list),
+        }
+    }
+
+    /// Parse a page of tasks in `list` from the JSON `body` of a response.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `body` isn't a page of tasks from this provider.
+    pub fn parse_page(self, list: &str, body: &[u8]) -> Result<Page, serde_json::Error> {
+        match self {
+            Self::Google => google::parse_page(list, body),
+            Self::Microsoft => microsoft::parse_page(body),
+        }
+    }
+}
+
+/// Page of tasks from a provider's API.
+#[derive(Clone, Debug)]
+pub struct Page {
+    /// Tasks on the page, leaving out any which were deleted.
+    pub tasks: Vec<RemoteTask>,
+    /// Path and query, relative to the API's base URL, of the next page, if
+    /// there is one.
+    pub next: Option<String>,
+}
+
+/// Task as kept by the provider.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteTask {
+    /// Provider's ID for the task.
+    pub id: String,
+    /// Title of the task, which may be empty.
+    pub title: String,
+    /// Plain text notes on the task, if any.
+    pub notes: Option<String>,
+    /// Status of the task.
+    pub status: TodoStatus,
+    /// Date & time at which the task is due, if it has a due date.
+    pub due: Option<DateTime<Utc>>,
+}
+
+impl RemoteTask {
+    /// Convert the remote task into a task, due within `window` around
+    /// `now`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the remote task has no due date, or isn't a valid
+    /// task.
+    pub fn to_task(&self, window: DueWindow, now: DateTime<Utc>) -> Result<TodoTask, &'static str> {
+        TodoTaskUnchecked::new(
+            self.title.clone(),
+            self.notes.clone().filter(|notes| !notes.is_empty()),
+            self.status.clone(),
+            self.due.ok_or(NO_DUE_DATE)?,
+            Vec::new(),
+        )
+        .check(window, now)
+    }
+}
+
+/// Which version of a task is kept when it has been changed both locally and
+/// by the provider since it was last synced.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictRule {
+    /// Overwrite the local changes with the provider's.
+    RemoteWins,
+    /// Keep the local changes, skipping the task until the provider's version
+    /// matches them.
+    ///
+    /// This is the default value with [`Default::default`].
+    #[default]
+    LocalWins,
+}
+
+/// What syncing a remote task did to the local task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncOutcome {
+    /// A task was created for it.
+    Created,
+    /// The task was updated with the provider's changes.
+    Updated,
+    /// The task didn't need updating.
+    Unchanged,
+    /// The task was left as it is, as it conflicts with the provider's
+    /// version.
+    Skipped,
+}
+
+/// Get the changes to the fields which are synced needed to get from `old` to
+/// `new`.
+#[must_use]
+pub fn synced_changes(old: &TodoTask, new: &TodoTask) -> TaskDiff {
+    let mut diff = TaskDiff::between(old, new);
+    diff.changes.retain(|change| {
+        matches!(
+            change,
+            FieldChange::Title { .. }
+                | FieldChange::Description { .. }
+                | FieldChange::Status { .. }
+                | FieldChange::Due { .. }
+        )
+    });
+    diff
+}
+
+/// Work out how to bring a `local` task in line with its `remote` version,
+/// given its state as of the last sync, `base`.
+///
+/// Returns the outcome, and the changes to make to `local`, which are empty
+/// unless the outcome is [`SyncOutcome::Updated`].
+#[must_use]
+pub fn reconcile(
+    base: &TodoTask,
+    local: &TodoTask,
+    remote: &TodoTask,
+    rule: ConflictRule,
+) -> (SyncOutcome, TaskDiff) {
+    if synced_changes(base, remote).is_empty() {
+        return (SyncOutcome::Unchanged, TaskDiff::default());
+    }
+    if rule == ConflictRule::LocalWins && !synced_changes(base, local).is_empty() {
+        return (SyncOutcome::Skipped, TaskDiff::default());
+    }
+    let changes = synced_changes(local, remote);
+    let outcome = if changes.is_empty() {
+        SyncOutcome::Unchanged
+    } else {
+        SyncOutcome::Updated
+    };
+    (outcome, changes)
+}
+
+/// Percent-encode `value` for use as a segment of a URL's path, or a value in
+/// its query.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use rstest::*;
+
+    use super::*;
+
+    #[fixture]
+    fn base() -> TodoTask {
+        let now = Utc::now();
+        let due = now + TimeDelta::hours(12);
+        TodoTask::new(
+            "my title".to_string(),
+            None,
+            TodoStatus::InProgress,
+            &due,
+            now,
+        )
+    }
+
+    #[rstest]
+    #[case(ConflictRule::LocalWins, SyncOutcome::Skipped, "Local")]
+    #[case(ConflictRule::RemoteWins, SyncOutcome::Updated, "Remote")]
+    fn conflicting_changes(
+        base: TodoTask,
+        #[case] rule: ConflictRule,
+        #[case] outcome: SyncOutcome,
+        #[case] title: &str,
+    ) {
+        let mut local = base.clone();
+        local.set_title("Local".to_string());
+        local.set_tags(vec!["kept".to_string()]);
+        let mut remote = base.clone();
+        remote.set_title("Remote".to_string());
+
+        let (actual, changes) = reconcile(&base, &local, &remote, rule);
+        assert_eq!(actual, outcome);
+        changes.apply(&mut local, Utc::now());
+        assert_eq!(local.title(), title);
+        assert_eq!(local.tags(), ["kept"]);
+    }
+
+    #[rstest]
+    fn unsynced_changes(base: TodoTask) {
+        let mut local = base.clone();
+        local.set_title("Local".to_string());
+        let mut remote = base.clone();
+        remote.set_tags(vec!["ignored".to_string()]);
+
+        let (outcome, changes) = reconcile(&base, &local, &remote, ConflictRule::RemoteWins);
+        assert_eq!(outcome, SyncOutcome::Unchanged);
+        assert!(changes.is_empty());
+    }
+
+    #[rstest]
+    fn remote_changes(base: TodoTask) {
+        let mut remote = base.clone();
+        remote.status = TodoStatus::Complete;
+
+        let (outcome, changes) = reconcile(&base, &base, &remote, ConflictRule::LocalWins);
+        assert_eq!(outcome, SyncOutcome::Updated);
+        assert_eq!(changes.changes.len(), 1);
+    }
+
+    #[test]
+    fn remote_due_outside_window() {
+        let now = "2026-10-16T12:00:00Z".parse().unwrap();
+        let remote = RemoteTask {
+            id: "1".to_string(),
+            title: "Renew passport".to_string(),
+            notes: None,
+            status: TodoStatus::NotStarted,
+            due: Some(now + TimeDelta::days(366 * 60)),
+        };
+        let error = remote.to_task(DueWindow::default(), now).unwrap_err();
+        assert_eq!(TodoTask::invalid_field(error), Some("due"));
+    }
+
+    #[test]
+    fn encode() {
+        assert_eq!(percent_encode("AbC-1_~."), "AbC-1_~.");
+        assert_eq!(percent_encode("a/b=="), "a%2Fb%3D%3D");
+    }
+}
@@ -0,0 +1,126 @@
+//! Parsing of tasks from the Google Tasks API.
+//!
+//! Google Tasks only keeps the date a task is due, so tasks are due at the
+//! end of that day, in UTC. Deleted tasks are left out; hidden tasks, which
+//! were completed and then cleared from view, are still included.
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use super::{Page, RemoteTask, percent_encode};
+use crate::{TodoStatus, import::end_of_day};
+
+/// Page of the tasks in a task list.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Tasks {
+    #[serde(default)]
+    items: Vec<Task>,
+    next_page_token: Option<String>,
+}
+
+/// Task in a task list.
+#[derive(Deserialize)]
+struct Task {
+    id: String,
+    #[serde(default)]
+    title: String,
+    notes: Option<String>,
+    status: Status,
+    due: Option<DateTime<Utc>>,
+    #[serde(default)]
+    deleted: bool,
+}
+
+/// Status of a task.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Status {
+    NeedsAction,
+    Completed,
+}
+
+/// Get the path of the first page of tasks in `list`, see
+/// [`SyncProvider::tasks_path`](super::SyncProvider::tasks_path).
+#[must_use]
+pub fn tasks_path(list: &str) -> String {
+    format!(
+        "/tasks/v1/lists/{}/tasks?showCompleted=true&showHidden=true&maxResults=100",
+        percent_encode(list)
+    )
+}
+
+/// Parse a page of tasks in `list`, see
+/// [`SyncProvider::parse_page`](super::SyncProvider::parse_page).
+///
+/// # Errors
+///
+/// Returns an error if `body` isn't a page of tasks from the Tasks API.
+pub fn parse_page(list: &str, body: &[u8]) -> Result<Page, serde_json::Error> {
+    let page: Tasks = serde_json::from_slice(body)?;
+    let next = page
+        .next_page_token
+        .map(|token| format!("{}&pageToken={}", tasks_path(list), percent_encode(&token)));
+    let tasks = page
+        .items
+        .into_iter()
+        .filter(|task| !task.deleted)
+        .map(|task| RemoteTask {
+            id: task.id,
+            title: task.title,
+            notes: task.notes,
+            status: match task.status {
+                Status::NeedsAction => TodoStatus::NotStarted,
+                Status::Completed => TodoStatus::Complete,
+            },
+            due: task.due.map(|due| end_of_day(due.date_naive())),
+        })
+        .collect();
+    Ok(Page { tasks, next })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DueWindow;
+
+    #[test]
+    fn tasks_page() {
+        let body = br#"{
+            "kind": "tasks#tasks",
+            "nextPageToken": "abc/123",
+            "items": [
+                {"id": "t1", "title": "Renew passport", "notes": "Photos first",
+                    "status": "needsAction", "due": "2025-04-01T00:00:00.000Z"},
+                {"id": "t2", "title": "Old", "status": "completed", "hidden": true},
+                {"id": "t3", "title": "Gone", "status": "needsAction", "deleted": true}
+            ]
+        }"#;
+        let page = parse_page("list/1", body).unwrap();
+        assert_eq!(
+            page.next.as_deref(),
+            Some(
+                "/tasks/v1/lists/list%2F1/tasks?showCompleted=true&showHidden=true&maxResults=100\
+                &pageToken=abc%2F123"
+            )
+        );
+        assert_eq!(page.tasks.len(), 2);
+
+        let (window, now) = (DueWindow::default(), Utc::now());
+        let passport = page.tasks[0].to_task(window, now).unwrap();
+        assert_eq!(passport.title(), "Renew passport");
+        assert_eq!(passport.description(), Some("Photos first"));
+        assert_eq!(passport.status, TodoStatus::NotStarted);
+        assert_eq!(passport.due().to_rfc3339(), "2025-04-01T23:59:59+00:00");
+
+        assert_eq!(page.tasks[1].status, TodoStatus::Complete);
+        assert!(page.tasks[1].to_task(window, now).is_err());
+    }
+
+    #[test]
+    fn last_page() {
+        let page = parse_page("l", br#"{"kind": "tasks#tasks"}"#).unwrap();
+        assert!(page.tasks.is_empty());
+        assert!(page.next.is_none());
+    }
+}
@@ -0,0 +1,150 @@
+//! Parsing of tasks from Microsoft To Do, with the Microsoft Graph API.
+//!
+//! Microsoft To Do only keeps the date a task is due, so tasks are due at the
+//! end of that day, in UTC. Notes are only taken from task bodies in plain
+//! text, and tasks waiting on others are [`Blocked`](TodoStatus::Blocked).
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use super::{Page, RemoteTask, percent_encode};
+use crate::{TodoStatus, import::end_of_day};
+
+/// Page of the tasks in a task list.
+#[derive(Deserialize)]
+struct Tasks {
+    value: Vec<Task>,
+    /// Absolute URL of the next page.
+    #[serde(rename = "@odata.nextLink")]
+    next_link: Option<String>,
+}
+
+/// Task in a task list.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Task {
+    id: String,
+    #[serde(default)]
+    title: String,
+    body: Option<Body>,
+    status: Status,
+    due_date_time: Option<DateTimeTimeZone>,
+}
+
+/// Body of a task.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Body {
+    content: String,
+    content_type: String,
+}
+
+/// Status of a task.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Status {
+    NotStarted,
+    InProgress,
+    Completed,
+    WaitingOnOthers,
+    Deferred,
+}
+
+/// Floating date & time, with the name of the time zone it's in.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DateTimeTimeZone {
+    date_time: String,
+}
+
+/// Get the path of the first page of tasks in `list`, see
+/// [`SyncProvider::tasks_path`](super::SyncProvider::tasks_path).
+#[must_use]
+pub fn tasks_path(list: &str) -> String {
+    format!("/v1.0/me/todo/lists/{}/tasks", percent_encode(list))
+}
+
+/// Parse a page of tasks, see
+/// [`SyncProvider::parse_page`](super::SyncProvider::parse_page).
+///
+/// # Errors
+///
+/// Returns an error if `body` isn't a page of tasks from the Graph API.
+pub fn parse_page(body: &[u8]) -> Result<Page, serde_json::Error> {
+    let page: Tasks = serde_json::from_slice(body)?;
+    // the next page is fetched through the configured base URL instead
+    let next = page.next_link.map(|link| {
+        let authority = link.find("://").map_or(0, |scheme| scheme + 3);
+        link[authority..].find('/').map_or_else(
+            || "/".to_string(),
+            |path| link[authority + path..].to_string(),
+        )
+    });
+    let tasks = page
+        .value
+        .into_iter()
+        .map(|task| RemoteTask {
+            id: task.id,
+            title: task.title,
+            notes: task
+                .body
+                .filter(|body| body.content_type.eq_ignore_ascii_case("text"))
+                .map(|body| body.content),
+            status: match task.status {
+                Status::NotStarted | Status::Deferred => TodoStatus::NotStarted,
+                Status::InProgress => TodoStatus::InProgress,
+                Status::Completed => TodoStatus::Complete,
+                Status::WaitingOnOthers => TodoStatus::Blocked,
+            },
+            due: task.due_date_time.and_then(|due| {
+                let date = due.date_time.get(..10)?.parse::<NaiveDate>().ok()?;
+                Some(end_of_day(date))
+            }),
+        })
+        .collect();
+    Ok(Page { tasks, next })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::DueWindow;
+
+    #[test]
+    fn tasks_page() {
+        let body = br#"{
+            "@odata.context": "https://graph.microsoft.com/v1.0/$metadata#tasks",
+            "@odata.nextLink": "https://graph.microsoft.com/v1.0/me/todo/lists/AQ==/tasks?$skip=10",
+            "value": [
+                {"id": "t1", "title": "Book venue", "status": "waitingOnOthers",
+                    "body": {"content": "Two quotes", "contentType": "text"},
+                    "dueDateTime": {"dateTime": "2025-05-06T00:00:00.0000000", "timeZone": "UTC"}},
+                {"id": "t2", "title": "Styled", "status": "completed",
+                    "body": {"content": "<p>hi</p>", "contentType": "html"}}
+            ]
+        }"#;
+        let page = parse_page(body).unwrap();
+        assert_eq!(
+            page.next.as_deref(),
+            Some("/v1.0/me/todo/lists/AQ==/tasks?$skip=10")
+        );
+
+        let venue = page.tasks[0]
+            .to_task(DueWindow::default(), Utc::now())
+            .unwrap();
+        assert_eq!(venue.title(), "Book venue");
+        assert_eq!(venue.description(), Some("Two quotes"));
+        assert_eq!(venue.status, TodoStatus::Blocked);
+        assert_eq!(venue.due().to_rfc3339(), "2025-05-06T23:59:59+00:00");
+
+        assert_eq!(page.tasks[1].notes, None);
+        assert_eq!(page.tasks[1].status, TodoStatus::Complete);
+    }
+
+    #[test]
+    fn list_path() {
+        assert_eq!(tasks_path("AQ/x="), "/v1.0/me/todo/lists/AQ%2Fx%3D/tasks");
+    }
+}
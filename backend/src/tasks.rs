@@ -1,11 +1,25 @@
-use chrono::{DateTime, TimeZone, Utc};
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Months, TimeDelta, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, Row, postgres::PgRow, prelude::Type};
+use serde_json::{Map, Value};
+#[cfg(feature = "postgres")]
+use sqlx::{
+    Decode, Encode, FromRow, Postgres, Row,
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgRow, PgTypeInfo, PgValueRef, types::PgInterval},
+    prelude::Type,
+};
+use uuid::Uuid;
+
+use crate::{Colour, calendar::WorkCalendar, i18n::Locale};
 
 /// Status of a "to-do" item.
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
-#[sqlx(type_name = "todo_status")]
-#[sqlx(rename_all = "snake_case")]
+///
+/// Besides the built-in statuses, deployments may define statuses of their
+/// own in their [`Workflow`](crate::workflow::Workflow).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum TodoStatus {
     /// Not yet started.
     ///
@@ -20,6 +34,130 @@ pub enum TodoStatus {
     Cancelled,
     /// Cannot be started due to external circumstances.
     Blocked,
+    /// Defined by the deployment's workflow, with this name.
+    ///
+    /// Serialized as its name, like the built-in statuses, which it must not
+    /// share. Work on tasks with a custom status is taken to be ongoing.
+    #[serde(untagged)]
+    Custom(String),
+}
+
+impl TodoStatus {
+    /// Every status, in the order tasks usually move through them.
+    pub const ALL: [Self; 5] = [
+        Self::NotStarted,
+        Self::InProgress,
+        Self::Blocked,
+        Self::Complete,
+        Self::Cancelled,
+    ];
+
+    /// Whether work on tasks with this status has stopped short of
+    /// completion, so they may be given a reason for it.
+    #[must_use]
+    pub fn is_stopped(&self) -> bool {
+        matches!(self, Self::Cancelled | Self::Blocked)
+    }
+
+    /// Get the status named `name`, which is custom unless it is the name of
+    /// a built-in status.
+    #[must_use]
+    pub fn named(name: &str) -> Self {
+        name.parse()
+            .unwrap_or_else(|_| Self::Custom(name.to_owned()))
+    }
+
+    /// Get the name of the status if it is custom.
+    #[must_use]
+    pub fn custom_name(&self) -> Option<&str> {
+        match self {
+            Self::Custom(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Get the value of the status in the database's `task_status` type.
+    #[cfg(feature = "postgres")]
+    fn column(&self) -> StatusColumn {
+        match self {
+            Self::NotStarted => StatusColumn::NotStarted,
+            Self::InProgress => StatusColumn::InProgress,
+            Self::Complete => StatusColumn::Complete,
+            Self::Cancelled => StatusColumn::Cancelled,
+            Self::Blocked => StatusColumn::Blocked,
+            Self::Custom(_) => StatusColumn::Custom,
+        }
+    }
+}
+
+/// Value of the database's `task_status` type.
+///
+/// Custom statuses are all stored as `custom`, with their names in a
+/// `custom_status` column alongside.
+#[cfg(feature = "postgres")]
+#[derive(Clone, Copy, Type)]
+#[sqlx(type_name = "task_status")]
+#[sqlx(rename_all = "snake_case")]
+enum StatusColumn {
+    NotStarted,
+    InProgress,
+    Complete,
+    Cancelled,
+    Blocked,
+    Custom,
+}
+
+#[cfg(feature = "postgres")]
+impl Type<Postgres> for TodoStatus {
+    fn type_info() -> PgTypeInfo {
+        StatusColumn::type_info()
+    }
+
+    /// Statuses may also be decoded from their names as text, such as
+    /// `coalesce(custom_status, status::text)`.
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        StatusColumn::compatible(ty) || <&str as Type<Postgres>>::compatible(ty)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl Encode<'_, Postgres> for TodoStatus {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        self.column().encode_by_ref(buf)
+    }
+}
+
+/// Decodes custom statuses as their names, or as `custom` if decoded from a
+/// `task_status` alone.
+#[cfg(feature = "postgres")]
+impl Decode<'_, Postgres> for TodoStatus {
+    fn decode(value: PgValueRef<'_>) -> Result<Self, BoxDynError> {
+        Ok(Self::named(<&str as Decode<Postgres>>::decode(value)?))
+    }
+}
+
+impl FromStr for TodoStatus {
+    type Err = &'static str;
+
+    /// Parse a status from its name.
+    ///
+    /// Matching is case-insensitive and ignores underscores, so both the JSON
+    /// (`InProgress`) and database (`in_progress`) spellings are accepted.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalised: String = s
+            .chars()
+            .filter(|c| *c != '_')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        match normalised.as_str() {
+            "notstarted" => Ok(Self::NotStarted),
+            "inprogress" => Ok(Self::InProgress),
+            "complete" => Ok(Self::Complete),
+            "cancelled" => Ok(Self::Cancelled),
+            "blocked" => Ok(Self::Blocked),
+            _ => Err("unknown task status"),
+        }
+    }
 }
 
 /// "To-do" task.
@@ -37,9 +175,14 @@ pub enum TodoStatus {
 ///     Some("My description".to_string()),
 ///     TodoStatus::InProgress,
 ///     &due,
+///     Utc::now(),
 /// );
 /// ```
-#[derive(Clone, Debug, Serialize)]
+///
+/// Deserializing a task validates it the same way as
+/// [`TryFrom<TodoTaskUnchecked>`](TodoTaskUnchecked).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "TodoTaskUnchecked")]
 pub struct TodoTask {
     /// Title of the task.
     ///
@@ -50,11 +193,113 @@ pub struct TodoTask {
     /// If `Some`, it is illegal for this to be empty.
     description: Option<String>,
     /// Current status of the task.
+    ///
+    /// Change it with [`Self::transition`] to keep when the task was
+    /// completed, and why it was stopped, up to date.
     pub status: TodoStatus,
+    /// Why work on the task stopped, if it is cancelled or blocked and a
+    /// reason was given.
+    ///
+    /// If `Some`, it is illegal for this to be empty, or for the status not
+    /// to be [stopped](TodoStatus::is_stopped).
+    status_reason: Option<String>,
     /// Date & time at which the task is due, in UTC.
     ///
     /// UTC is the state that the time is stored in memory and the database.
+    /// New due dates must be within the [`DueWindow`], which stored ones may
+    /// since have left.
     due: DateTime<Utc>,
+    /// Free-form labels attached to the task.
+    ///
+    /// No tag may be empty or contain whitespace.
+    tags: Vec<String>,
+    /// Estimated effort needed to complete the task, if estimated.
+    ///
+    /// Serialized as a whole number of seconds. It is illegal for this to be
+    /// negative or longer than [`MAX_ESTIMATE_HOURS`].
+    #[serde(with = "optional_seconds")]
+    estimate: Option<TimeDelta>,
+    /// Percentage of the task which is done, if set manually.
+    ///
+    /// It is illegal for this to be more than 100. When unset, listings
+    /// compute it from the status of the task and of the tasks it depends on.
+    progress: Option<u8>,
+    /// Colour for clients to render the task in, if any.
+    colour: Option<Colour>,
+    /// Welsh translation of the title, if any.
+    ///
+    /// If `Some`, it is illegal for this to be empty.
+    title_cy: Option<String>,
+    /// Welsh translation of the description, if any.
+    ///
+    /// If `Some`, it is illegal for this to be empty, or for the description
+    /// to be `None`.
+    description_cy: Option<String>,
+    /// When the task was last marked complete, if it still is.
+    ///
+    /// Maintained by [`Self::transition`], and not given when creating or
+    /// replacing a task.
+    completed_at: Option<DateTime<Utc>>,
+}
+
+/// Largest allowed [`TodoTask`] estimate, in hours.
+pub const MAX_ESTIMATE_HOURS: i64 = 100_000;
+
+/// Default number of years before or after now that [`TodoTask`] due dates
+/// may be.
+pub const DEFAULT_DUE_WINDOW_YEARS: u32 = 50;
+
+/// Number of years before or after now that new due dates may be, to catch
+/// mistyped years such as 20025.
+///
+/// Only new due dates are checked, so tasks already stored with due dates
+/// outside the window are still read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DueWindow {
+    years: u32,
+}
+
+impl DueWindow {
+    /// Create a window of `years` years either side of now.
+    #[must_use]
+    pub const fn years(years: u32) -> Self {
+        Self { years }
+    }
+
+    /// Check whether `due` is within the window around `now`.
+    #[must_use]
+    pub fn contains(self, due: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        let window = Months::new(self.years.saturating_mul(12));
+        // a window reaching past the dates chrono can represent isn't a limit
+        now.checked_sub_months(window)
+            .is_none_or(|earliest| due >= earliest)
+            && now
+                .checked_add_months(window)
+                .is_none_or(|latest| due <= latest)
+    }
+
+    /// Check that the due date of `task` is within the window around `now`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error, which [`TodoTask::invalid_field`] attributes to the
+    /// due date, if it isn't.
+    pub fn check(self, task: &TodoTask, now: DateTime<Utc>) -> Result<(), &'static str> {
+        if self.contains(task.due, now) {
+            Ok(())
+        } else {
+            Err(DUE_OUT_OF_WINDOW)
+        }
+    }
+}
+
+/// Error for due dates outside the [`DueWindow`].
+const DUE_OUT_OF_WINDOW: &str = "due date is too far in the past or future";
+
+impl Default for DueWindow {
+    fn default() -> Self {
+        Self::years(DEFAULT_DUE_WINDOW_YEARS)
+    }
 }
 
 impl TodoTask {
@@ -64,6 +309,13 @@ impl TodoTask {
     /// - `title` may not be empty
     /// - `description` may not be `Some` *and* empty
     ///
+    /// The task is created with no tags, estimate, progress, colour or Welsh
+    /// translations, see [`Self::set_tags`], [`Self::set_estimate`],
+    /// [`Self::set_progress`], [`Self::set_colour`] and
+    /// [`Self::set_welsh`].
+    /// A task created [complete](TodoStatus::Complete) was completed at
+    /// `now`.
+    ///
     /// # Panics
     ///
     /// Panics if the above invariants are not upheld.
@@ -73,19 +325,29 @@ impl TodoTask {
         description: Option<String>,
         status: TodoStatus,
         due: &DateTime<TZ>,
+        now: DateTime<Utc>,
     ) -> Self {
         let mut to_return = Self {
             // we can set `title` to an invalid value here because it will
             // always be replaced by the .set_title call
             title: String::new(),
             description: None,
-            status,
-            due: Utc::now(),
+            status: TodoStatus::NotStarted,
+            status_reason: None,
+            due: now,
+            tags: Vec::new(),
+            estimate: None,
+            progress: None,
+            colour: None,
+            title_cy: None,
+            description_cy: None,
+            completed_at: None,
         };
 
         // use setters for DRY with upholding our invariants
         to_return.set_title(title);
         to_return.set_description(description);
+        to_return.transition(status, now);
         to_return.set_due(due);
 
         to_return
@@ -123,9 +385,14 @@ impl TodoTask {
     /// # Panics
     ///
     /// Panics if `new_description` is `Some("")`.
+    ///
+    /// Any Welsh translation of the description is removed along with it.
     pub fn set_description(&mut self, new_description: Option<String>) {
         debug_assert!(!matches!(new_description.as_deref(), Some("")));
 
+        if new_description.is_none() {
+            self.description_cy = None;
+        }
         self.description = new_description;
     }
 
@@ -139,24 +406,324 @@ impl TodoTask {
     ///
     /// This method is generic over timezones with `TZ`.
     /// Time zone conversion is performed automatically.
+    /// The date isn't checked against the [`DueWindow`], as for restoring
+    /// stored due dates; give new ones with [`Self::set_due_within`].
     pub fn set_due<TZ: TimeZone>(&mut self, new_due: &DateTime<TZ>) {
         self.due = new_due.with_timezone(&Utc);
     }
 
-    /// Check if this task is past due.
+    /// Set the due date of the task, if it is within `window` around `now`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error, which [`Self::invalid_field`] attributes to the due
+    /// date, and leaves the task as it was, if the date is outside the
+    /// window.
+    pub fn set_due_within<TZ: TimeZone>(
+        &mut self,
+        new_due: &DateTime<TZ>,
+        window: DueWindow,
+        now: DateTime<Utc>,
+    ) -> Result<(), &'static str> {
+        let new_due = new_due.with_timezone(&Utc);
+        if !window.contains(new_due, now) {
+            return Err(DUE_OUT_OF_WINDOW);
+        }
+        self.due = new_due;
+        Ok(())
+    }
+
+    /// Get the tags of the task.
+    #[must_use]
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Set the tags of the task.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any tag is empty or contains whitespace.
+    pub fn set_tags(&mut self, new_tags: Vec<String>) {
+        debug_assert!(new_tags.iter().all(|t| valid_tag(t)));
+
+        self.tags = new_tags;
+    }
+
+    /// Get the estimated effort needed to complete the task.
+    #[must_use]
+    pub fn estimate(&self) -> Option<TimeDelta> {
+        self.estimate
+    }
+
+    /// Set the estimated effort needed to complete the task.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_estimate` is negative or longer than
+    /// [`MAX_ESTIMATE_HOURS`].
+    pub fn set_estimate(&mut self, new_estimate: Option<TimeDelta>) {
+        debug_assert!(new_estimate.is_none_or(valid_estimate));
+
+        self.estimate = new_estimate;
+    }
+
+    /// Get the manually set percentage of the task which is done.
     #[must_use]
-    pub fn past_due(&self) -> bool {
-        self.due < Utc::now()
+    pub fn progress(&self) -> Option<u8> {
+        self.progress
+    }
+
+    /// Set the percentage of the task which is done, or `None` to compute it
+    /// automatically.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_progress` is more than 100.
+    pub fn set_progress(&mut self, new_progress: Option<u8>) {
+        debug_assert!(new_progress.is_none_or(valid_progress));
+
+        self.progress = new_progress;
+    }
+
+    /// Get the Welsh translation of the title.
+    #[must_use]
+    pub fn title_cy(&self) -> Option<&str> {
+        self.title_cy.as_deref()
+    }
+
+    /// Get the Welsh translation of the description.
+    #[must_use]
+    pub fn description_cy(&self) -> Option<&str> {
+        self.description_cy.as_deref()
+    }
+
+    /// Set the Welsh translations of the title and description.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either is `Some("")`, or if `description_cy` is `Some` but
+    /// the task has no description.
+    pub fn set_welsh(&mut self, title_cy: Option<String>, description_cy: Option<String>) {
+        debug_assert!(!matches!(title_cy.as_deref(), Some("")));
+        debug_assert!(!matches!(description_cy.as_deref(), Some("")));
+        debug_assert!(description_cy.is_none() || self.description.is_some());
+
+        self.title_cy = title_cy;
+        self.description_cy = description_cy;
+    }
+
+    /// Prefer the title and description in `locale`, where the task has
+    /// translations of them.
+    #[must_use]
+    pub fn localised(mut self, locale: Locale) -> Self {
+        if locale == Locale::Welsh {
+            if let Some(title) = &self.title_cy {
+                self.title.clone_from(title);
+            }
+            if let Some(description) = &self.description_cy {
+                self.description = Some(description.clone());
+            }
+        }
+        self
+    }
+
+    /// Get the colour to render the task in.
+    #[must_use]
+    pub fn colour(&self) -> Option<&Colour> {
+        self.colour.as_ref()
+    }
+
+    /// Set the colour to render the task in.
+    pub fn set_colour(&mut self, new_colour: Option<Colour>) {
+        self.colour = new_colour;
+    }
+
+    /// Get when the task was last marked complete, if it still is.
+    #[must_use]
+    pub fn completed_at(&self) -> Option<&DateTime<Utc>> {
+        self.completed_at.as_ref()
+    }
+
+    /// Change the status of the task at the instant `at`.
+    ///
+    /// Marking the task complete records `at` as when it was completed,
+    /// unless it already was; marking it anything else clears that. The
+    /// status reason is kept only if the new status is also
+    /// [stopped](TodoStatus::is_stopped).
+    pub fn transition(&mut self, status: TodoStatus, at: DateTime<Utc>) {
+        self.completed_at = match status {
+            TodoStatus::Complete => self.completed_at.or(Some(at)),
+            _ => None,
+        };
+        if !status.is_stopped() {
+            self.status_reason = None;
+        }
+        self.status = status;
+    }
+
+    /// Get why work on the task stopped, if given.
+    #[must_use]
+    pub fn status_reason(&self) -> Option<&str> {
+        self.status_reason.as_deref()
+    }
+
+    /// Set why work on the task stopped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_reason` is `Some("")`, or is `Some` while the task's
+    /// status isn't [stopped](TodoStatus::is_stopped).
+    pub fn set_status_reason(&mut self, new_reason: Option<String>) {
+        debug_assert!(!matches!(new_reason.as_deref(), Some("")));
+        debug_assert!(new_reason.is_none() || self.status.is_stopped());
+
+        self.status_reason = new_reason;
+    }
+
+    /// Check if this task is past due at `now`.
+    ///
+    /// If only `working_hours` count, the task isn't past due until some
+    /// working time has passed since it was due.
+    #[must_use]
+    pub fn past_due(&self, working_hours: Option<&WorkCalendar>, now: DateTime<Utc>) -> bool {
+        self.due < working_hours.map_or(now, |calendar| calendar.last_working_time(now))
     }
 }
 
+#[cfg(feature = "postgres")]
 impl FromRow<'_, PgRow> for TodoTask {
     fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
         Ok(Self {
             title: row.try_get("title")?,
             description: row.try_get("description")?,
-            status: row.try_get("status")?,
+            // custom statuses are stored as `custom`, with their names apart
+            status: match row.try_get("custom_status")? {
+                Some(name) => TodoStatus::Custom(name),
+                None => row.try_get("status")?,
+            },
+            status_reason: row.try_get("status_reason")?,
             due: row.try_get("due")?,
+            tags: row.try_get("tags")?,
+            estimate: row
+                .try_get::<Option<PgInterval>, _>("estimate")?
+                .map(|interval| {
+                    TimeDelta::days(i64::from(interval.months) * 30 + i64::from(interval.days))
+                        + TimeDelta::microseconds(interval.microseconds)
+                }),
+            progress: row
+                .try_get::<Option<i16>, _>("progress")?
+                .map(u8::try_from)
+                .transpose()
+                .map_err(|e| sqlx::Error::ColumnDecode {
+                    index: "progress".to_string(),
+                    source: Box::new(e),
+                })?,
+            colour: row.try_get("colour")?,
+            title_cy: row.try_get("title_cy")?,
+            description_cy: row.try_get("description_cy")?,
+            completed_at: row.try_get("completed_at")?,
+        })
+    }
+}
+
+/// Check whether `tag` is acceptable as a task tag.
+fn valid_tag(tag: &str) -> bool {
+    !tag.is_empty() && !tag.contains(char::is_whitespace)
+}
+
+/// Check whether `estimate` is acceptable as a task estimate.
+fn valid_estimate(estimate: TimeDelta) -> bool {
+    estimate >= TimeDelta::zero() && estimate <= TimeDelta::hours(MAX_ESTIMATE_HOURS)
+}
+
+/// Check whether `progress` is acceptable as a task's progress percentage.
+fn valid_progress(progress: u8) -> bool {
+    progress <= 100
+}
+
+/// Deserialization of tasks stored by this application, such as in events,
+/// keeping when they were completed.
+pub(crate) mod stored {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, de::Error};
+
+    use super::{TodoStatus, TodoTask, TodoTaskUnchecked};
+
+    /// Fields of a stored task, including those maintained by the task.
+    #[derive(Deserialize)]
+    struct Stored {
+        #[serde(flatten)]
+        task: TodoTaskUnchecked,
+        /// Missing from tasks stored before completion times were kept.
+        #[serde(default)]
+        completed_at: Option<DateTime<Utc>>,
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<TodoTask, D::Error> {
+        let Stored { task, completed_at } = Stored::deserialize(deserializer)?;
+        let mut task = TodoTask::try_from(task).map_err(D::Error::custom)?;
+        task.completed_at = completed_at.filter(|_| task.status == TodoStatus::Complete);
+        Ok(task)
+    }
+
+    pub(crate) fn deserialize_boxed<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Box<TodoTask>, D::Error> {
+        deserialize(deserializer).map(Box::new)
+    }
+}
+
+/// A [`TodoTask`] stored by this application, deserialized with [`stored`].
+#[cfg(feature = "postgres")]
+#[derive(Debug, Deserialize)]
+pub(crate) struct StoredTask(#[serde(deserialize_with = "stored::deserialize")] pub TodoTask);
+
+/// Serialization of optional durations as whole numbers of seconds.
+pub(crate) mod optional_seconds {
+    use chrono::TimeDelta;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+
+    // serde passes fields by reference
+    #[allow(clippy::ref_option)]
+    pub(crate) fn serialize<S: Serializer>(
+        value: &Option<TimeDelta>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.map(|d| d.num_seconds()).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<TimeDelta>, D::Error> {
+        Option::<i64>::deserialize(deserializer)?
+            .map(|seconds| {
+                TimeDelta::try_seconds(seconds).ok_or_else(|| D::Error::custom("duration too long"))
+            })
+            .transpose()
+    }
+}
+
+/// A [`TodoTask`] along with its unique identifier in the database.
+///
+/// Serializes as the task's fields with an additional `id` field.
+#[derive(Clone, Debug, Serialize)]
+pub struct TaskRecord {
+    /// Unique identifier of the task.
+    pub id: Uuid,
+    /// The task itself.
+    #[serde(flatten)]
+    pub task: TodoTask,
+}
+
+#[cfg(feature = "postgres")]
+impl FromRow<'_, PgRow> for TaskRecord {
+    fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            task: TodoTask::from_row(row)?,
         })
     }
 }
@@ -164,15 +731,155 @@ impl FromRow<'_, PgRow> for TodoTask {
 /// Unchecked version of [`TodoTask`].
 ///
 /// Intended for upholding invariants from deserialization.
-/// Use [`Self::try_from`] to validate and convert to a [`TodoTask`].
+/// Use [`Self::check`] to validate and convert a new task to a [`TodoTask`],
+/// or [`Self::try_from`] for one which was stored.
 #[derive(Deserialize, Clone, Debug)]
 pub struct TodoTaskUnchecked {
-    title: String,
-    description: Option<String>,
-    status: TodoStatus,
-    due: DateTime<Utc>,
+    pub(crate) title: String,
+    pub(crate) description: Option<String>,
+    pub(crate) status: TodoStatus,
+    #[serde(default)]
+    pub(crate) status_reason: Option<String>,
+    pub(crate) due: DateTime<Utc>,
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+    #[serde(default, with = "optional_seconds")]
+    pub(crate) estimate: Option<TimeDelta>,
+    #[serde(default)]
+    pub(crate) progress: Option<u8>,
+    #[serde(default)]
+    pub(crate) colour: Option<Colour>,
+    #[serde(default)]
+    pub(crate) title_cy: Option<String>,
+    #[serde(default)]
+    pub(crate) description_cy: Option<String>,
 }
 
+impl TodoTaskUnchecked {
+    /// Names of the fields of a task, as deserialized.
+    pub const FIELDS: &[&str] = &[
+        "title",
+        "description",
+        "status",
+        "status_reason",
+        "due",
+        "tags",
+        "estimate",
+        "progress",
+        "colour",
+        "title_cy",
+        "description_cy",
+    ];
+
+    /// Names of the members of `object`, a task as JSON, which aren't fields
+    /// of a task, and so would be ignored by deserializing it.
+    pub fn unknown_fields(object: &Map<String, Value>) -> impl Iterator<Item = &str> {
+        object
+            .keys()
+            .map(String::as_str)
+            .filter(|key| !Self::FIELDS.contains(key))
+    }
+
+    /// Collect the fields of a task to be validated, leaving its status
+    /// reason, estimate, progress, colour and Welsh translations unset.
+    #[must_use]
+    pub fn new(
+        title: String,
+        description: Option<String>,
+        status: TodoStatus,
+        due: DateTime<Utc>,
+        tags: Vec<String>,
+    ) -> Self {
+        Self {
+            title,
+            description,
+            status,
+            status_reason: None,
+            due,
+            tags,
+            estimate: None,
+            progress: None,
+            colour: None,
+            title_cy: None,
+            description_cy: None,
+        }
+    }
+
+    /// Validate the task as by [`TryFrom<TodoTaskUnchecked>`], and check
+    /// that its due date is within `window` around `now`, as new tasks must
+    /// be.
+    ///
+    /// # Errors
+    ///
+    /// Returns the problem with the task, which [`TodoTask::invalid_field`]
+    /// attributes to the field at fault.
+    pub fn check(self, window: DueWindow, now: DateTime<Utc>) -> Result<TodoTask, &'static str> {
+        let task = TodoTask::try_from(self)?;
+        window.check(&task, now)?;
+        Ok(task)
+    }
+}
+
+/// Problem with a task given as JSON, from [`TodoTask::from_json`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidTask {
+    /// Path to the field at fault, such as `title` or `tags[0]`, or `None`
+    /// if the problem is with the task as a whole.
+    pub field: Option<String>,
+    /// Human-readable description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for InvalidTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{field}: {}", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for InvalidTask {}
+
+impl From<&str> for InvalidTask {
+    /// Find the field which `error`, from validating a task with
+    /// [`TryFrom<TodoTaskUnchecked>`], is about.
+    fn from(error: &str) -> Self {
+        Self {
+            field: TodoTask::invalid_field(error).map(str::to_owned),
+            message: error.to_owned(),
+        }
+    }
+}
+
+/// Errors validating tasks, with the fields they are about.
+const INVALID_FIELDS: [(&str, &str); 12] = [
+    (DUE_OUT_OF_WINDOW, "due"),
+    (
+        "Welsh description requires a description in the primary language",
+        "description_cy",
+    ),
+    (
+        "custom statuses cannot be blank or named after built-in statuses",
+        "status",
+    ),
+    ("title cannot be empty", "title"),
+    ("description cannot be empty", "description"),
+    ("status reason cannot be empty", "status_reason"),
+    (
+        "only cancelled or blocked tasks can have a status reason",
+        "status_reason",
+    ),
+    ("tags cannot be empty or contain whitespace", "tags"),
+    (
+        "estimate cannot be negative or longer than 100000 hours",
+        "estimate",
+    ),
+    ("progress cannot be more than 100 percent", "progress"),
+    ("Welsh title cannot be empty", "title_cy"),
+    ("Welsh description cannot be empty", "description_cy"),
+];
+
 impl TryFrom<TodoTaskUnchecked> for TodoTask {
     type Error = &'static str;
 
@@ -181,8 +888,23 @@ impl TryFrom<TodoTaskUnchecked> for TodoTask {
             title,
             description,
             status,
+            status_reason,
             due,
+            tags,
+            estimate,
+            progress,
+            colour,
+            title_cy,
+            description_cy,
         } = value;
+        if description.is_none() && description_cy.is_some() {
+            return Err("Welsh description requires a description in the primary language");
+        }
+        if let TodoStatus::Custom(name) = &status {
+            if name.trim().is_empty() || name.parse::<TodoStatus>().is_ok() {
+                return Err("custom statuses cannot be blank or named after built-in statuses");
+            }
+        }
         Ok(Self {
             title: if title.is_empty() {
                 return Err("title cannot be empty");
@@ -194,13 +916,88 @@ impl TryFrom<TodoTaskUnchecked> for TodoTask {
             } else {
                 description
             },
+            status_reason: match status_reason.as_deref() {
+                Some("") => return Err("status reason cannot be empty"),
+                Some(_) if !status.is_stopped() => {
+                    return Err("only cancelled or blocked tasks can have a status reason");
+                }
+                _ => status_reason,
+            },
             status,
             due,
+            tags: if tags.iter().all(|t| valid_tag(t)) {
+                tags
+            } else {
+                return Err("tags cannot be empty or contain whitespace");
+            },
+            estimate: if estimate.is_none_or(valid_estimate) {
+                estimate
+            } else {
+                return Err("estimate cannot be negative or longer than 100000 hours");
+            },
+            progress: if progress.is_none_or(valid_progress) {
+                progress
+            } else {
+                return Err("progress cannot be more than 100 percent");
+            },
+            colour,
+            title_cy: if matches!(title_cy.as_deref(), Some("")) {
+                return Err("Welsh title cannot be empty");
+            } else {
+                title_cy
+            },
+            description_cy: if matches!(description_cy.as_deref(), Some("")) {
+                return Err("Welsh description cannot be empty");
+            } else {
+                description_cy
+            },
+            // a task given whole is completed when it's stored, by the
+            // store's clock
+            completed_at: None,
         })
     }
 }
 
+impl TodoTask {
+    /// Get the name of the field which `error`, from validating a task with
+    /// [`TryFrom<TodoTaskUnchecked>`], is about.
+    #[must_use]
+    pub fn invalid_field(error: &str) -> Option<&'static str> {
+        INVALID_FIELDS
+            .iter()
+            .find(|(message, _)| *message == error)
+            .map(|(_, field)| *field)
+    }
+
+    /// Deserialize a task from JSON and validate it, as the API does with
+    /// request bodies, with the due date within `window` around `now`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the problem with the task, and the field it is about, if the
+    /// JSON is malformed or the task is invalid.
+    pub fn from_json(
+        json: &str,
+        window: DueWindow,
+        now: DateTime<Utc>,
+    ) -> Result<Self, InvalidTask> {
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let task: TodoTaskUnchecked =
+            serde_path_to_error::deserialize(&mut deserializer).map_err(|e| InvalidTask {
+                field: Some(e.path().to_string()).filter(|path| path != "."),
+                message: e.into_inner().to_string(),
+            })?;
+        deserializer.end().map_err(|e| InvalidTask {
+            field: None,
+            message: e.to_string(),
+        })?;
+        task.check(window, now).map_err(InvalidTask::from)
+    }
+}
+
 #[cfg(test)]
+// the original setter tests predate the lint
+#[allow(clippy::should_panic_without_expect)]
 mod tests {
     use chrono::TimeDelta;
     use rstest::*;
@@ -209,8 +1006,15 @@ mod tests {
 
     #[fixture]
     pub fn sample_task() -> TodoTask {
-        let due = Utc::now() + TimeDelta::hours(12);
-        TodoTask::new("my title".to_string(), None, TodoStatus::InProgress, &due)
+        let now = Utc::now();
+        let due = now + TimeDelta::hours(12);
+        TodoTask::new(
+            "my title".to_string(),
+            None,
+            TodoStatus::InProgress,
+            &due,
+            now,
+        )
     }
 
     #[rstest]
@@ -246,12 +1050,259 @@ mod tests {
         assert_eq!(sample_task.due(), &new_due);
     }
 
+    #[rstest]
+    #[case("2125-01-01T00:00:00Z")]
+    #[case("1925-01-01T00:00:00Z")]
+    fn distant_due(#[case] due: &str) {
+        let json = format!(
+            r#"{{"title": "t", "description": null, "status": "NotStarted", "due": "{due}"}}"#
+        );
+        // tasks are read whatever their due date, and checked when given
+        let task: TodoTask = serde_json::from_str(&json).unwrap();
+        let now = "2026-10-16T12:00:00Z".parse().unwrap();
+        let error = DueWindow::default().check(&task, now).unwrap_err();
+        assert_eq!(TodoTask::invalid_field(error), Some("due"));
+    }
+
+    #[rstest]
+    fn due_window(mut sample_task: TodoTask) {
+        let now = "2026-10-16T12:00:00Z".parse().unwrap();
+        let window = DueWindow::years(1);
+        assert!(window.contains(now - TimeDelta::days(364), now));
+        assert!(!window.contains(now - TimeDelta::days(367), now));
+        assert!(!window.contains(now + TimeDelta::days(367), now));
+        let unlimited = DueWindow::years(u32::MAX);
+        assert!(unlimited.contains(now + TimeDelta::days(365 * 1000), now));
+
+        let due = *sample_task.due();
+        let error = sample_task
+            .set_due_within(&(now + TimeDelta::days(400)), window, now)
+            .unwrap_err();
+        assert_eq!(TodoTask::invalid_field(error), Some("due"));
+        assert_eq!(sample_task.due(), &due);
+        let due = now + TimeDelta::days(30);
+        sample_task.set_due_within(&due, window, now).unwrap();
+        assert_eq!(sample_task.due(), &due);
+
+        let task = TodoTaskUnchecked::new(
+            "t".into(),
+            None,
+            TodoStatus::NotStarted,
+            now + TimeDelta::days(400),
+            Vec::new(),
+        );
+        assert!(TodoTask::try_from(task.clone()).is_ok());
+        assert_eq!(task.check(window, now).unwrap_err(), DUE_OUT_OF_WINDOW);
+    }
+
+    #[rstest]
+    fn transition(mut sample_task: TodoTask) {
+        let completed_at = Utc::now() - TimeDelta::hours(1);
+        sample_task.transition(TodoStatus::Complete, completed_at);
+        assert_eq!(sample_task.completed_at(), Some(&completed_at));
+
+        // already complete
+        sample_task.transition(TodoStatus::Complete, Utc::now());
+        assert_eq!(sample_task.completed_at(), Some(&completed_at));
+
+        sample_task.transition(TodoStatus::InProgress, Utc::now());
+        assert_eq!(sample_task.status, TodoStatus::InProgress);
+        assert_eq!(sample_task.completed_at(), None);
+    }
+
+    #[rstest]
+    fn set_tags(mut sample_task: TodoTask) {
+        let new_tags = vec!["urgent".to_string(), "home".to_string()];
+        sample_task.set_tags(new_tags.clone());
+        assert_eq!(sample_task.tags(), new_tags);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("two words")]
+    #[should_panic(expected = "assertion failed")]
+    fn invalid_tag(mut sample_task: TodoTask, #[case] tag: &str) {
+        sample_task.set_tags(vec![tag.to_string()]);
+    }
+
+    #[rstest]
+    fn set_estimate(mut sample_task: TodoTask) {
+        sample_task.set_estimate(Some(TimeDelta::hours(3)));
+        assert_eq!(sample_task.estimate(), Some(TimeDelta::hours(3)));
+    }
+
+    #[rstest]
+    #[case(-1)]
+    #[case(MAX_ESTIMATE_HOURS * 3600 + 1)]
+    fn invalid_estimate(#[case] seconds: i64) {
+        let json = format!(
+            r#"{{"title": "t", "description": null, "status": "NotStarted",
+                "due": "2025-01-01T00:00:00Z", "estimate": {seconds}}}"#
+        );
+        assert!(serde_json::from_str::<TodoTask>(&json).is_err());
+    }
+
+    #[rstest]
+    #[case(None)]
+    #[case(Some(0))]
+    #[case(Some(100))]
+    fn set_progress(mut sample_task: TodoTask, #[case] progress: Option<u8>) {
+        sample_task.set_progress(progress);
+        assert_eq!(sample_task.progress(), progress);
+    }
+
+    #[rstest]
+    #[should_panic(expected = "assertion failed")]
+    fn invalid_progress(mut sample_task: TodoTask) {
+        sample_task.set_progress(Some(101));
+    }
+
+    #[rstest]
+    fn localised(mut sample_task: TodoTask) {
+        sample_task.set_description(Some("my description".to_string()));
+        sample_task.set_welsh(Some("fy nheitl".to_string()), None);
+
+        let welsh = sample_task.clone().localised(Locale::Welsh);
+        assert_eq!(welsh.title(), "fy nheitl");
+        assert_eq!(welsh.description(), Some("my description"));
+        let english = sample_task.localised(Locale::English);
+        assert_eq!(english.title(), "my title");
+    }
+
+    #[rstest]
+    #[case(r#""title_cy": """#)]
+    #[case(r#""description": "d", "description_cy": """#)]
+    #[case(r#""description": null, "description_cy": "d""#)]
+    fn invalid_welsh(#[case] fields: &str) {
+        let json = format!(
+            r#"{{"title": "t", "status": "NotStarted", "due": "2025-01-01T00:00:00Z", {fields}}}"#
+        );
+        assert!(serde_json::from_str::<TodoTask>(&json).is_err());
+    }
+
+    #[rstest]
+    #[case(r#""title": """#, "title")]
+    #[case(r#""title": "t", "tags": ["two words"]"#, "tags")]
+    #[case(r#""title": "t", "description_cy": "d""#, "description_cy")]
+    #[case(r#""title": "t", "progress": 101"#, "progress")]
+    fn invalid_field(#[case] fields: &str, #[case] field: &str) {
+        let json =
+            format!(r#"{{"status": "NotStarted", "due": "2025-01-01T00:00:00Z", {fields}}}"#);
+        let task: TodoTaskUnchecked = serde_json::from_str(&json).unwrap();
+        let error = TodoTask::try_from(task).unwrap_err();
+        assert_eq!(TodoTask::invalid_field(error), Some(field));
+    }
+
+    #[rstest]
+    #[case(r#""title": """#, Some("title"), "title cannot be empty")]
+    #[case(r#""title": "t", "tags": [7]"#, Some("tags[0]"), "invalid type")]
+    #[case(r#""title": "t"}} {{"#, None, "trailing characters")]
+    #[case(r#""title": "t", "progress": 101"#, Some("progress"), "more than 100")]
+    fn from_json(#[case] fields: &str, #[case] field: Option<&str>, #[case] message: &str) {
+        let json = format!(
+            r#"{{"status": "NotStarted", "due": "{}", {fields}}}"#,
+            Utc::now()
+        );
+        let (window, now) = (DueWindow::default(), Utc::now());
+        let error = TodoTask::from_json(&json, window, now).unwrap_err();
+        assert_eq!(error.field.as_deref(), field);
+        assert!(error.message.contains(message), "{error}");
+
+        let json = format!(
+            r#"{{"title": "t", "status": "Blocked", "due": "{}"}}"#,
+            Utc::now()
+        );
+        assert_eq!(
+            TodoTask::from_json(&json, window, now).unwrap().title(),
+            "t"
+        );
+        let json = r#"{"title": "t", "status": "Blocked", "due": "2125-06-30T12:00:00Z"}"#;
+        let error = TodoTask::from_json(json, window, now).unwrap_err();
+        assert_eq!(error.field.as_deref(), Some("due"));
+    }
+
+    #[rstest]
+    fn status_reason(mut sample_task: TodoTask) {
+        sample_task.transition(TodoStatus::Blocked, Utc::now());
+        sample_task.set_status_reason(Some("waiting on legal".to_string()));
+        assert_eq!(sample_task.status_reason(), Some("waiting on legal"));
+
+        sample_task.transition(TodoStatus::Cancelled, Utc::now());
+        assert_eq!(sample_task.status_reason(), Some("waiting on legal"));
+
+        sample_task.transition(TodoStatus::InProgress, Utc::now());
+        assert_eq!(sample_task.status_reason(), None);
+    }
+
+    #[rstest]
+    #[case("Blocked", r#""status_reason": """#)]
+    #[case("InProgress", r#""status_reason": "waiting""#)]
+    fn invalid_status_reason(#[case] status: &str, #[case] fields: &str) {
+        let json = format!(
+            r#"{{"title": "t", "status": "{status}", "due": "2025-01-01T00:00:00Z", {fields}}}"#
+        );
+        assert!(serde_json::from_str::<TodoTask>(&json).is_err());
+    }
+
+    #[rstest]
+    #[case("InProgress", TodoStatus::InProgress)]
+    #[case("in_progress", TodoStatus::InProgress)]
+    #[case("notstarted", TodoStatus::NotStarted)]
+    #[case("CANCELLED", TodoStatus::Cancelled)]
+    fn parse_status(#[case] input: &str, #[case] expected: TodoStatus) {
+        assert_eq!(input.parse::<TodoStatus>(), Ok(expected));
+    }
+
+    #[rstest]
+    fn unknown_fields() {
+        let json = r#"{"title": "t", "status": "NotStarted", "due": "2025-01-01T00:00:00Z",
+            "estimate": 60, "priority": "high", "progress": 5, "assignee": null}"#;
+        let object: Map<String, Value> = serde_json::from_str(json).unwrap();
+        let mut unknown: Vec<_> = TodoTaskUnchecked::unknown_fields(&object).collect();
+        unknown.sort_unstable();
+        assert_eq!(unknown, ["assignee", "priority"]);
+    }
+
+    #[rstest]
+    fn custom_status() {
+        let json = r#"{"title": "t", "status": "InReview", "due": "2025-01-01T00:00:00Z"}"#;
+        let task: TodoTask = serde_json::from_str(json).unwrap();
+        assert_eq!(task.status, TodoStatus::Custom("InReview".to_string()));
+        assert_eq!(task.status.custom_name(), Some("InReview"));
+        assert_eq!(
+            serde_json::to_value(&task.status).unwrap(),
+            Value::from("InReview")
+        );
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("in_progress")]
+    fn invalid_custom_status(#[case] status: &str) {
+        let json =
+            format!(r#"{{"title": "t", "status": "{status}", "due": "2025-01-01T00:00:00Z"}}"#);
+        assert!(serde_json::from_str::<TodoTask>(&json).is_err());
+    }
+
+    #[rstest]
+    fn parse_unknown_status() {
+        assert!("Finished".parse::<TodoStatus>().is_err());
+    }
+
     #[rstest]
     fn past_due(mut sample_task: TodoTask) {
-        sample_task.set_due(&(Utc::now() - TimeDelta::days(1)));
-        assert!(sample_task.past_due());
+        let now = Utc::now();
+        sample_task.set_due(&(now - TimeDelta::days(1)));
+        assert!(sample_task.past_due(None, now));
+        assert!(!sample_task.past_due(None, now - TimeDelta::days(2)));
+
+        sample_task.set_due(&(now + TimeDelta::days(1)));
+        assert!(!sample_task.past_due(None, now));
+        assert!(sample_task.past_due(None, now + TimeDelta::days(2)));
 
-        sample_task.set_due(&(Utc::now() + TimeDelta::days(1)));
-        assert!(!sample_task.past_due());
+        // no working time has passed since the task was due
+        let never: WorkCalendar = "Mon 00:00-00:01".parse().unwrap();
+        sample_task.set_due(&never.last_working_time(now));
+        assert!(!sample_task.past_due(Some(&never), now));
     }
 }
@@ -0,0 +1,89 @@
+//! Thumbnails of image [attachments](crate::attachments), so listings can
+//! show previews without downloading full-size files.
+//!
+//! Thumbnails are generated in the background after upload, as PNGs at most
+//! [`THUMBNAIL_SIZE`] pixels wide and high.
+
+use std::io::Cursor;
+
+use image::{ImageFormat, ImageReader, Limits};
+
+/// Largest width and height of thumbnails, in pixels.
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+/// Media type of thumbnails.
+pub const THUMBNAIL_CONTENT_TYPE: &str = "image/png";
+
+/// Media types of the attachments which thumbnails are generated for.
+pub const IMAGE_CONTENT_TYPES: [&str; 4] = ["image/gif", "image/jpeg", "image/png", "image/webp"];
+
+/// Largest width and height of images which thumbnails are generated for, in
+/// pixels, so small files can't expand to huge images when decoded.
+const MAX_IMAGE_SIZE: u32 = 12_000;
+
+/// Most memory decoding an image may take, in bytes.
+const MAX_DECODING_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Thumbnail of an attachment, as far as it has been generated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Thumbnail {
+    /// The thumbnail hasn't been generated yet.
+    Pending,
+    /// The attachment has no thumbnail, since it isn't an image, is
+    /// quarantined or couldn't be decoded.
+    Unavailable,
+    /// PNG of the thumbnail.
+    Ready(Vec<u8>),
+}
+
+/// Whether thumbnails are generated for attachments of `content_type`.
+#[must_use]
+pub fn is_image(content_type: &str) -> bool {
+    IMAGE_CONTENT_TYPES.contains(&content_type)
+}
+
+/// Generate the thumbnail of the image `content`, keeping its aspect ratio.
+///
+/// # Errors
+///
+/// Returns a description of the problem if the image couldn't be decoded,
+/// e.g. if it isn't a supported format or is too large.
+pub fn generate(content: &[u8]) -> Result<Vec<u8>, String> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_SIZE);
+    limits.max_image_height = Some(MAX_IMAGE_SIZE);
+    limits.max_alloc = Some(MAX_DECODING_BYTES);
+
+    let mut reader = ImageReader::new(Cursor::new(content))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?;
+    reader.limits(limits);
+    let image = reader.decode().map_err(|e| e.to_string())?;
+
+    let mut png = Cursor::new(Vec::new());
+    image
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .write_to(&mut png, ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(png.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, RgbImage};
+
+    #[test]
+    fn thumbnails() {
+        let mut jpeg = Cursor::new(Vec::new());
+        RgbImage::new(1024, 512)
+            .write_to(&mut jpeg, ImageFormat::Jpeg)
+            .unwrap();
+        let thumbnail = image::load_from_memory(&generate(jpeg.get_ref()).unwrap()).unwrap();
+        assert_eq!(thumbnail.dimensions(), (256, 128));
+
+        assert!(generate(b"not an image").is_err());
+        assert!(is_image("image/webp"));
+        assert!(!is_image("image/svg+xml"));
+    }
+}
@@ -0,0 +1,80 @@
+//! Personal access tokens, which authenticate requests as their owner.
+//!
+//! The secrets identifying browser sessions are generated and hashed in the
+//! same way.
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+#[cfg(feature = "postgres")]
+use sqlx::{FromRow, prelude::Type};
+use uuid::Uuid;
+
+/// Prefix of every token, so leaked tokens are easy to recognise.
+const PREFIX: &str = "dts_";
+
+/// Operation which a token permits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "postgres", derive(Type))]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "postgres", sqlx(type_name = "token_scope"))]
+#[cfg_attr(feature = "postgres", sqlx(rename_all = "snake_case"))]
+pub enum TokenScope {
+    /// Reading tasks and related data.
+    Read,
+    /// Creating, changing and erasing tasks and related data.
+    Write,
+}
+
+/// Details of a personal access token, without the token itself.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct AccessToken {
+    /// ID of the token, used to refer to it when revoking it.
+    pub id: Uuid,
+    /// Owner whom the token authenticates as.
+    pub owner: String,
+    /// Description of what the token is for.
+    pub name: String,
+    /// Operations which the token permits.
+    pub scopes: Vec<TokenScope>,
+    /// Date & time at which the token was created.
+    pub created_at: DateTime<Utc>,
+    /// Date & time after which the token is no longer valid, if any.
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Date & time at which the token was last used to authenticate.
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// Date & time at which the token was revoked, if it has been.
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Generate a new random token.
+#[must_use]
+pub fn generate() -> String {
+    let mut bytes = [0; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("{PREFIX}{}", URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// Hash a token for storage, so that tokens can't be recovered from the
+/// database.
+#[must_use]
+pub fn hash(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_tokens_differ() {
+        let (a, b) = (generate(), generate());
+        assert!(a.starts_with(PREFIX));
+        assert_eq!(a.len(), PREFIX.len() + 43);
+        assert_ne!(a, b);
+        assert_ne!(hash(&a), hash(&b));
+    }
+}
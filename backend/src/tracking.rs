@@ -0,0 +1,37 @@
+//! Tracking of the time spent working on [`TodoTask`](crate::TodoTask)s.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+#[cfg(feature = "postgres")]
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Period of time spent on a task, tracked with a timer.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct TimeEntry {
+    /// ID of the entry.
+    pub id: Uuid,
+    /// ID of the task worked on.
+    pub task_id: Uuid,
+    /// Owner who spent the time.
+    pub owner: String,
+    /// Date & time at which the timer was started.
+    pub started_at: DateTime<Utc>,
+    /// Date & time at which the timer was stopped, or `None` if it's running.
+    pub stopped_at: Option<DateTime<Utc>>,
+}
+
+/// Entry of an owner's timesheet.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct TimesheetEntry {
+    /// The tracked period of time.
+    #[serde(flatten)]
+    #[cfg_attr(feature = "postgres", sqlx(flatten))]
+    pub entry: TimeEntry,
+    /// Title of the task worked on.
+    pub title: String,
+    /// Number of seconds tracked, up to now if the timer is running.
+    pub seconds: i64,
+}
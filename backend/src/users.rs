@@ -0,0 +1,41 @@
+//! Registered users, the principals which own tasks.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "postgres")]
+use sqlx::{FromRow, prelude::Type};
+
+/// Role granting a user extra permissions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "postgres", derive(Type))]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "postgres", sqlx(type_name = "user_role"))]
+#[cfg_attr(feature = "postgres", sqlx(rename_all = "snake_case"))]
+pub enum Role {
+    /// Managing users and reading the security event log.
+    Admin,
+    /// Reading the security event log.
+    Auditor,
+}
+
+/// Registered user.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "postgres", derive(FromRow))]
+pub struct User {
+    /// ID of the user, which is also the owner of their tasks.
+    pub id: String,
+    /// Name to show for the user.
+    pub display_name: String,
+    /// Email address of the user, if known.
+    pub email: Option<String>,
+    /// Phone number of the user in E.164 format, if known.
+    pub phone: Option<String>,
+    /// Roles granted to the user.
+    pub roles: Vec<Role>,
+    /// Date & time at which the user was registered.
+    pub created_at: DateTime<Utc>,
+    /// Date & time at which the user was deactivated, if they have been.
+    pub deactivated_at: Option<DateTime<Utc>>,
+    /// Whether the user is emailed digests of their tasks.
+    pub digest: bool,
+}
@@ -0,0 +1,433 @@
+//! Notifications sent to browsers with the Web Push protocol.
+//!
+//! Browsers subscribe with the push service of their vendor, identifying
+//! this server by the public half of its VAPID key, and give the resulting
+//! [`PushSubscription`] to `POST /users/{user_id}/push-subscriptions`.
+//! Notifications are then encrypted for the browser as in RFC 8291 and posted
+//! to the subscription's endpoint, authenticated with a VAPID token as in
+//! RFC 8292.
+//!
+//! Push services are only reached over HTTPS, which is left to an HTTP proxy
+//! originating TLS, as for REST hooks.
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use base64::{
+    Engine,
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use http_body_util::Full;
+use hyper::{Request, StatusCode, Uri, body::Bytes, header, http::uri::Authority};
+use ring::{
+    aead, agreement, hkdf,
+    rand::SystemRandom,
+    signature::{ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, KeyPair},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::FromRow;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    clock::Clock,
+    egress::Egress,
+    notify::{Channel, Notification, Notifier},
+    store::PushSubscriptionStore,
+};
+
+/// How long delivering a notification to a push service may take.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Number of seconds push services keep notifications for browsers which
+/// are offline.
+const TTL_SECONDS: u32 = 24 * 60 * 60;
+
+/// Size of the single record notifications are encrypted in.
+const RECORD_SIZE: u32 = 4096;
+
+/// Subscription of a browser to notifications, as given by its
+/// `PushSubscription.toJSON()`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PushSubscription {
+    /// URL of the push service to post notifications to.
+    pub endpoint: String,
+    /// Keys to encrypt notifications for the browser with.
+    pub keys: SubscriptionKeys,
+}
+
+/// Browser subscribed by a user, as listed to them.
+#[derive(Clone, Debug, Serialize, FromRow)]
+pub struct Subscribed {
+    /// ID of the subscription.
+    pub id: Uuid,
+    /// URL of the push service notifications are posted to.
+    pub endpoint: String,
+    /// When the browser was subscribed.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Keys of a [`PushSubscription`], base64url-encoded.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriptionKeys {
+    /// Browser's P-256 public key, uncompressed.
+    pub p256dh: String,
+    /// Secret shared with the browser.
+    pub auth: String,
+}
+
+impl PushSubscription {
+    /// Check that the subscription's endpoint is an `https` URL at one of
+    /// `allowed_hosts`, and that its keys are valid.
+    ///
+    /// Endpoints are checked like REST hooks are, so subscriptions can't be
+    /// used to make requests to internal services.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of why the subscription is refused.
+    pub fn check(&self, allowed_hosts: &[String]) -> Result<(), &'static str> {
+        let uri: Uri = self
+            .endpoint
+            .parse()
+            .map_err(|_| "push endpoint is invalid")?;
+        if uri.scheme_str() != Some("https") {
+            return Err("push endpoint must use https");
+        }
+        let host = uri.host().unwrap_or_default();
+        if !allowed_hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+        {
+            return Err("push endpoint host is not allowed");
+        }
+        self.decoded_keys().map(|_| ())
+    }
+
+    /// Decode the browser's public key and the shared secret.
+    fn decoded_keys(&self) -> Result<(Vec<u8>, Vec<u8>), &'static str> {
+        let decode = |key: &str| URL_SAFE_NO_PAD.decode(key.trim_end_matches('='));
+        match (decode(&self.keys.p256dh), decode(&self.keys.auth)) {
+            (Ok(public), Ok(auth)) if public.len() == 65 && public[0] == 4 && auth.len() == 16 => {
+                Ok((public, auth))
+            }
+            _ => Err("push subscription keys are invalid"),
+        }
+    }
+}
+
+/// Key pair identifying this server to push services, as in RFC 8292.
+#[derive(Debug)]
+pub struct VapidKey {
+    key_pair: EcdsaKeyPair,
+    rng: SystemRandom,
+}
+
+impl VapidKey {
+    /// Read a P-256 private key in PKCS#8 PEM, as made by
+    /// `openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-256`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key can't be read.
+    pub fn from_pem(pem: &str) -> Result<Self, String> {
+        let base64: String = pem
+            .lines()
+            .filter(|line| !line.starts_with("-----"))
+            .map(str::trim)
+            .collect();
+        let der = STANDARD
+            .decode(base64)
+            .map_err(|e| format!("VAPID key is not PEM: {e}"))?;
+        let rng = SystemRandom::new();
+        let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &der, &rng)
+            .map_err(|e| format!("VAPID key is not a P-256 key in PKCS#8: {e}"))?;
+        Ok(Self { key_pair, rng })
+    }
+
+    /// Public key, base64url-encoded, which browsers subscribe with as the
+    /// `applicationServerKey`.
+    #[must_use]
+    pub fn public_key(&self) -> String {
+        URL_SAFE_NO_PAD.encode(self.key_pair.public_key())
+    }
+
+    /// Value of the `Authorization` header of requests to `endpoint`, made
+    /// at `now` by the server whose contact is `subject`.
+    fn authorization(
+        &self,
+        endpoint: &Uri,
+        subject: &str,
+        now: DateTime<Utc>,
+    ) -> Result<String, String> {
+        let audience = format!(
+            "{}://{}",
+            endpoint.scheme_str().unwrap_or("https"),
+            endpoint.authority().map_or("", Authority::as_str)
+        );
+        let encode = |value: serde_json::Value| URL_SAFE_NO_PAD.encode(value.to_string());
+        let token = format!(
+            "{}.{}",
+            encode(json!({ "typ": "JWT", "alg": "ES256" })),
+            encode(json!({
+                "aud": audience,
+                "exp": (now + TimeDelta::hours(12)).timestamp(),
+                "sub": subject,
+            }))
+        );
+        let signature = self
+            .key_pair
+            .sign(&self.rng, token.as_bytes())
+            .map_err(|_| "failed to sign VAPID token")?;
+        Ok(format!(
+            "vapid t={token}.{}, k={}",
+            URL_SAFE_NO_PAD.encode(signature),
+            self.public_key()
+        ))
+    }
+}
+
+/// Length of key material to derive with HKDF.
+struct Length(usize);
+
+impl hkdf::KeyType for Length {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+/// Derive `length` bytes from `ikm` with HKDF-SHA256.
+fn hkdf(salt: &[u8], ikm: &[u8], info: &[&[u8]], length: usize) -> Result<Vec<u8>, String> {
+    let mut output = vec![0; length];
+    hkdf::Salt::new(hkdf::HKDF_SHA256, salt)
+        .extract(ikm)
+        .expand(info, Length(length))
+        .and_then(|okm| okm.fill(&mut output))
+        .map_err(|_| "failed to derive keys")?;
+    Ok(output)
+}
+
+/// Encrypt `payload` for the browser with the public key `browser_key` and
+/// secret `auth`, as the body of an `aes128gcm` request in RFC 8291.
+fn encrypt(
+    payload: &[u8],
+    browser_key: &[u8],
+    auth: &[u8],
+    rng: &SystemRandom,
+) -> Result<Vec<u8>, String> {
+    let failed = |_| "failed to encrypt notification".to_owned();
+    let private =
+        agreement::EphemeralPrivateKey::generate(&agreement::ECDH_P256, rng).map_err(failed)?;
+    let public = private.compute_public_key().map_err(failed)?;
+    let shared = agreement::agree_ephemeral(
+        private,
+        &agreement::UnparsedPublicKey::new(&agreement::ECDH_P256, browser_key),
+        <[u8]>::to_vec,
+    )
+    .map_err(failed)?;
+
+    let ikm = hkdf(
+        auth,
+        &shared,
+        &[b"WebPush: info\0", browser_key, public.as_ref()],
+        32,
+    )?;
+    let salt: [u8; 16] = rand::random();
+    let key = hkdf(&salt, &ikm, &[b"Content-Encoding: aes128gcm\0"], 16)?;
+    let nonce = hkdf(&salt, &ikm, &[b"Content-Encoding: nonce\0"], 12)?;
+
+    // the only record is the last, so is padded with just its delimiter
+    let mut record = payload.to_vec();
+    record.push(2);
+    let key =
+        aead::LessSafeKey::new(aead::UnboundKey::new(&aead::AES_128_GCM, &key).map_err(failed)?);
+    key.seal_in_place_append_tag(
+        aead::Nonce::try_assume_unique_for_key(&nonce).map_err(failed)?,
+        aead::Aad::empty(),
+        &mut record,
+    )
+    .map_err(failed)?;
+
+    let mut body = Vec::with_capacity(86 + record.len());
+    body.extend_from_slice(&salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(65);
+    body.extend_from_slice(public.as_ref());
+    body.extend_from_slice(&record);
+    Ok(body)
+}
+
+/// Notifier sending notifications to the browsers users subscribed.
+#[derive(Debug)]
+pub struct WebPushNotifier {
+    /// Storage of the subscriptions.
+    pub subscriptions: PushSubscriptionStore,
+    /// Key identifying this server to push services.
+    pub key: VapidKey,
+    /// Contact for this server, a `mailto:` or `https:` URL, which push
+    /// services may use if there is a problem with its notifications.
+    pub subject: String,
+    /// Client reaching push services.
+    pub http: Egress,
+    /// Clock telling the time that pushes are authorised at.
+    pub clock: Arc<dyn Clock>,
+}
+
+impl WebPushNotifier {
+    /// Post `payload` to the browser subscribed with `subscription`,
+    /// returning the status of the response.
+    async fn push(
+        &self,
+        subscription: &PushSubscription,
+        payload: &[u8],
+    ) -> Result<StatusCode, String> {
+        let endpoint: Uri = subscription
+            .endpoint
+            .parse()
+            .map_err(|_| "push endpoint is invalid")?;
+        let (browser_key, auth) = subscription.decoded_keys()?;
+        let body = encrypt(payload, &browser_key, &auth, &self.key.rng)?;
+        let request = Request::post(endpoint.to_string())
+            .header(
+                header::HOST,
+                endpoint.authority().map_or("", Authority::as_str),
+            )
+            .header(
+                header::AUTHORIZATION,
+                self.key
+                    .authorization(&endpoint, &self.subject, self.clock.now())?,
+            )
+            .header(header::CONTENT_ENCODING, "aes128gcm")
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .header("TTL", TTL_SECONDS)
+            .body(Full::<Bytes>::from(body))
+            .map_err(|e| e.to_string())?;
+
+        match tokio::time::timeout(DELIVERY_TIMEOUT, self.http.request(request)).await {
+            Ok(result) => result
+                .map(|response| response.status())
+                .map_err(|e| e.to_string()),
+            Err(_) => Err("timed out".to_owned()),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebPushNotifier {
+    fn channel(&self) -> Channel {
+        Channel::WebPush
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<(), String> {
+        let subscriptions = self
+            .subscriptions
+            .for_user(&notification.user_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        if subscriptions.is_empty() {
+            debug!(user = notification.user_id, "no browsers to push to");
+            return Ok(());
+        }
+        let payload = json!({
+            "title": notification.summary(),
+            "event": notification.event,
+            "task_id": notification.task_id,
+        })
+        .to_string();
+
+        let mut failure = None;
+        for (id, subscription) in subscriptions {
+            match self.push(&subscription, payload.as_bytes()).await {
+                Ok(status) if status.is_success() => {}
+                // the browser unsubscribed, or the subscription expired
+                Ok(StatusCode::NOT_FOUND | StatusCode::GONE) => {
+                    info!(%id, "removing expired push subscription");
+                    if let Err(e) = self.subscriptions.remove(id).await {
+                        warn!(error = format!("{e}"), "failed to remove push subscription");
+                    }
+                }
+                Ok(status) => failure = Some(format!("push service responded {status}")),
+                Err(e) => failure = Some(e),
+            }
+        }
+        failure.map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn subscription(endpoint: &str, p256dh: &str) -> PushSubscription {
+        PushSubscription {
+            endpoint: endpoint.to_owned(),
+            keys: SubscriptionKeys {
+                p256dh: p256dh.to_owned(),
+                auth: URL_SAFE_NO_PAD.encode([7; 16]),
+            },
+        }
+    }
+
+    #[rstest]
+    #[case("https://fcm.googleapis.com/fcm/send/abc", Ok(()))]
+    #[case(
+        "http://fcm.googleapis.com/fcm/send/abc",
+        Err("push endpoint must use https")
+    )]
+    #[case("https://internal.example/", Err("push endpoint host is not allowed"))]
+    fn check(#[case] endpoint: &str, #[case] expected: Result<(), &str>) {
+        let mut key = vec![4];
+        key.extend([1; 64]);
+        let allowed = ["fcm.googleapis.com".to_owned()];
+        assert_eq!(
+            subscription(endpoint, &URL_SAFE_NO_PAD.encode(key)).check(&allowed),
+            expected
+        );
+        assert!(subscription(endpoint, "c2hvcnQ").check(&allowed).is_err());
+    }
+
+    #[test]
+    fn encryption_round_trip() {
+        // the browser's side of the exchange
+        let rng = SystemRandom::new();
+        let browser =
+            agreement::EphemeralPrivateKey::generate(&agreement::ECDH_P256, &rng).unwrap();
+        let browser_key = browser.compute_public_key().unwrap();
+        let auth = [9; 16];
+
+        let body = encrypt(b"hello", browser_key.as_ref(), &auth, &rng).unwrap();
+        let (salt, rest) = body.split_at(16);
+        assert_eq!(rest[..4], RECORD_SIZE.to_be_bytes());
+        assert_eq!(rest[4], 65);
+        let (server_key, ciphertext) = rest[5..].split_at(65);
+
+        let shared = agreement::agree_ephemeral(
+            browser,
+            &agreement::UnparsedPublicKey::new(&agreement::ECDH_P256, server_key),
+            <[u8]>::to_vec,
+        )
+        .unwrap();
+        let ikm = hkdf(
+            &auth,
+            &shared,
+            &[b"WebPush: info\0", browser_key.as_ref(), server_key],
+            32,
+        )
+        .unwrap();
+        let key = hkdf(salt, &ikm, &[b"Content-Encoding: aes128gcm\0"], 16).unwrap();
+        let nonce = hkdf(salt, &ikm, &[b"Content-Encoding: nonce\0"], 12).unwrap();
+        let key = aead::LessSafeKey::new(aead::UnboundKey::new(&aead::AES_128_GCM, &key).unwrap());
+        let mut record = ciphertext.to_vec();
+        let plaintext = key
+            .open_in_place(
+                aead::Nonce::try_assume_unique_for_key(&nonce).unwrap(),
+                aead::Aad::empty(),
+                &mut record,
+            )
+            .unwrap();
+        assert_eq!(plaintext, b"hello\x02");
+    }
+}
@@ -0,0 +1,247 @@
+//! Pool bounding how much background work runs at once, such as hook
+//! deliveries, imports and thumbnailing, so a flood of it can't starve
+//! requests of the database and CPU.
+//!
+//! Work is either spawned, to run when a worker is free, or run by the
+//! caller once a worker is free. Spawned work waits in a bounded queue, and
+//! is refused once the queue is full, which callers pass on as backpressure.
+
+use std::{
+    fmt::{self, Write},
+    future::Future,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+use serde::Serialize;
+use tokio::sync::Semaphore;
+
+/// Error spawning work on a [`WorkerPool`] whose queue is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolFull;
+
+impl fmt::Display for PoolFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the background worker queue is full")
+    }
+}
+
+impl std::error::Error for PoolFull {}
+
+/// State of a [`WorkerPool`] at some moment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct PoolStatus {
+    /// Number of workers.
+    pub workers: usize,
+    /// Number of units of work waiting for a worker.
+    pub queued: usize,
+    /// Most spawned work which may wait for a worker.
+    pub queue_capacity: usize,
+    /// Number of units of work running.
+    pub running: usize,
+    /// Number of units of work refused because the queue was full.
+    pub rejected: u64,
+}
+
+/// State shared between the handles of a pool.
+#[derive(Debug)]
+struct Shared {
+    /// Permits for each worker, taken by running work.
+    workers: Semaphore,
+    /// Number of workers.
+    size: usize,
+    /// Most spawned work which may wait for a worker.
+    queue_capacity: usize,
+    /// Work waiting for a worker.
+    queued: AtomicUsize,
+    /// Work spawned while waiting for a worker, counted in `queued` too.
+    spawned: AtomicUsize,
+    /// Work running.
+    running: AtomicUsize,
+    /// Work refused because the queue was full.
+    rejected: AtomicU64,
+}
+
+/// Decrements a counter when dropped, so it is kept right if work panics or
+/// is cancelled.
+struct Counted<'a>(&'a AtomicUsize);
+
+impl<'a> Counted<'a> {
+    fn new(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for Counted<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Pool of a fixed number of workers running background work.
+#[derive(Clone, Debug)]
+pub struct WorkerPool {
+    shared: Arc<Shared>,
+}
+
+impl WorkerPool {
+    /// Create a pool of `size` workers, with up to `queue_capacity` spawned
+    /// work waiting for them.
+    #[must_use]
+    pub fn new(size: usize, queue_capacity: usize) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                workers: Semaphore::new(size),
+                size,
+                queue_capacity,
+                queued: AtomicUsize::new(0),
+                spawned: AtomicUsize::new(0),
+                running: AtomicUsize::new(0),
+                rejected: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Run `work` once a worker is free, returning its output.
+    ///
+    /// The caller waits for as long as the queue is, so this suits work
+    /// which is already in the background, such as scheduled jobs.
+    pub async fn run<F: Future>(&self, work: F) -> F::Output {
+        let permit = {
+            let _queued = Counted::new(&self.shared.queued);
+            // the semaphore is never closed, so this always gets a permit
+            self.shared.workers.acquire().await.ok()
+        };
+        let _running = Counted::new(&self.shared.running);
+        let output = work.await;
+        drop(permit);
+        output
+    }
+
+    /// Spawn `work` to run once a worker is free.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PoolFull`] without spawning it if the queue is full.
+    pub fn spawn<F>(&self, work: F) -> Result<(), PoolFull>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let shared = &self.shared;
+        let spawned = shared.spawned.fetch_add(1, Ordering::Relaxed);
+        if spawned >= shared.queue_capacity {
+            shared.spawned.fetch_sub(1, Ordering::Relaxed);
+            shared.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(PoolFull);
+        }
+        let pool = self.clone();
+        tokio::spawn(async move {
+            let shared = Arc::clone(&pool.shared);
+            pool.run(async move {
+                shared.spawned.fetch_sub(1, Ordering::Relaxed);
+                work.await;
+            })
+            .await;
+        });
+        Ok(())
+    }
+
+    /// Number of units of work waiting for a worker.
+    #[must_use]
+    pub fn queued(&self) -> usize {
+        self.shared.queued.load(Ordering::Relaxed)
+    }
+
+    /// Number of units of work running.
+    #[must_use]
+    pub fn running(&self) -> usize {
+        self.shared.running.load(Ordering::Relaxed)
+    }
+
+    /// Current state of the pool.
+    #[must_use]
+    pub fn status(&self) -> PoolStatus {
+        PoolStatus {
+            workers: self.shared.size,
+            queued: self.queued(),
+            queue_capacity: self.shared.queue_capacity,
+            running: self.running(),
+            rejected: self.shared.rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Render the pool's metrics in the Prometheus text exposition format.
+    #[must_use]
+    pub fn render_metrics(&self) -> String {
+        let mut text = String::new();
+        let status = self.status();
+        let gauges = [
+            ("workers", "Number of background workers.", status.workers),
+            (
+                "queued",
+                "Background work waiting for a worker.",
+                status.queued,
+            ),
+            ("running", "Background work running.", status.running),
+        ];
+        for (name, help, value) in gauges {
+            let _ = writeln!(
+                text,
+                "# HELP dts_worker_pool_{name} {help}\n\
+                # TYPE dts_worker_pool_{name} gauge\n\
+                dts_worker_pool_{name} {value}"
+            );
+        }
+        let _ = writeln!(
+            text,
+            "# HELP dts_worker_pool_rejected_total Background work refused because the queue was full.\n\
+            # TYPE dts_worker_pool_rejected_total counter\n\
+            dts_worker_pool_rejected_total {}",
+            status.rejected
+        );
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::oneshot;
+
+    #[tokio::test]
+    async fn backpressure() {
+        let pool = WorkerPool::new(1, 2);
+        let (release, released) = oneshot::channel::<()>();
+        let (started, running) = oneshot::channel();
+        pool.spawn(async move {
+            let _ = started.send(());
+            let _ = released.await;
+        })
+        .unwrap();
+        running.await.unwrap();
+        assert_eq!(pool.running(), 1);
+
+        // the worker is busy, so these wait for it
+        let (done, finished) = oneshot::channel();
+        pool.spawn(async move {
+            let _ = done.send(());
+        })
+        .unwrap();
+        pool.spawn(async {}).unwrap();
+        assert_eq!(pool.spawn(async {}), Err(PoolFull));
+        let status = pool.status();
+        assert_eq!((status.running, status.rejected), (1, 1));
+        assert!(
+            pool.render_metrics()
+                .contains("dts_worker_pool_rejected_total 1\n")
+        );
+
+        release.send(()).unwrap();
+        finished.await.unwrap();
+        assert_eq!(pool.run(async { 42 }).await, 42);
+        pool.spawn(async {}).unwrap();
+    }
+}
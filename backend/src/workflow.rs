@@ -0,0 +1,186 @@
+//! Statuses which deployments define in addition to the built-in ones, and
+//! the changes of status they allow.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+#[cfg(feature = "postgres")]
+use sqlx::PgPool;
+
+use crate::{TodoStatus, i18n::Locale};
+
+/// Custom statuses and the allowed transitions between statuses, as
+/// configured by a deployment.
+///
+/// Read from JSON like:
+///
+/// ```
+/// use dts_developer_challenge::{TodoStatus, workflow::Workflow};
+///
+/// let workflow: Workflow = serde_json::from_str(
+///     r#"{
+///         "statuses": ["InReview"],
+///         "transitions": {
+///             "NotStarted": ["InProgress", "Cancelled"],
+///             "InProgress": ["InReview", "Blocked", "Cancelled"],
+///             "InReview": ["InProgress", "Complete"],
+///             "Blocked": ["InProgress", "Cancelled"]
+///         }
+///     }"#,
+/// )
+/// .unwrap();
+/// let in_review = TodoStatus::named("InReview");
+/// assert!(workflow.allows(&TodoStatus::InProgress, &in_review));
+/// assert!(!workflow.allows(&TodoStatus::NotStarted, &TodoStatus::Complete));
+/// ```
+///
+/// The default workflow has no custom statuses, and allows any transition.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Workflow {
+    /// Names of the custom statuses, in the order tasks usually move through
+    /// them.
+    #[serde(default)]
+    pub statuses: Vec<String>,
+    /// Statuses which each status may change to, or `None` to allow any
+    /// change.
+    ///
+    /// Statuses missing from it can't be changed from.
+    #[serde(default)]
+    pub transitions: Option<HashMap<TodoStatus, Vec<TodoStatus>>>,
+}
+
+impl Workflow {
+    /// Check that the custom statuses are valid and distinct, and that the
+    /// transitions are between statuses of the workflow.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the first problem found.
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, name) in self.statuses.iter().enumerate() {
+            if name.trim().is_empty() {
+                return Err("custom statuses cannot be blank".to_owned());
+            }
+            if TodoStatus::named(name).custom_name().is_none() {
+                return Err(format!("`{name}` is already a built-in status"));
+            }
+            if self.statuses[..i].contains(name) {
+                return Err(format!("custom status `{name}` is defined twice"));
+            }
+        }
+        for (from, targets) in self.transitions.iter().flatten() {
+            if let Some(status) = std::iter::once(from)
+                .chain(targets)
+                .find(|status| !self.knows(status))
+            {
+                return Err(format!(
+                    "transition from `{}` mentions unknown status `{}`",
+                    from.label(Locale::English),
+                    status.label(Locale::English)
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Get every status of the workflow, built-in statuses first.
+    pub fn all(&self) -> impl Iterator<Item = TodoStatus> {
+        TodoStatus::ALL
+            .into_iter()
+            .chain(self.statuses.iter().cloned().map(TodoStatus::Custom))
+    }
+
+    /// Check whether `status` is a status of the workflow.
+    #[must_use]
+    pub fn knows(&self, status: &TodoStatus) -> bool {
+        status
+            .custom_name()
+            .is_none_or(|name| self.statuses.iter().any(|custom| custom == name))
+    }
+
+    /// Check that `status` is a status of the workflow, as for task
+    /// validation.
+    ///
+    /// # Errors
+    ///
+    /// Returns a validation message if it isn't.
+    pub fn check(&self, status: &TodoStatus) -> Result<(), &'static str> {
+        if self.knows(status) {
+            Ok(())
+        } else {
+            Err("unknown task status")
+        }
+    }
+
+    /// Check whether a task may change from status `from` to `to`.
+    ///
+    /// Keeping the same status is always allowed.
+    #[must_use]
+    pub fn allows(&self, from: &TodoStatus, to: &TodoStatus) -> bool {
+        from == to
+            || self.transitions.as_ref().is_none_or(|transitions| {
+                transitions
+                    .get(from)
+                    .is_some_and(|targets| targets.contains(to))
+            })
+    }
+
+    /// Record the custom statuses in the `statuses` table, which tasks with
+    /// them reference.
+    ///
+    /// Statuses no longer in the workflow are kept, for any tasks which still
+    /// have them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database couldn't be updated.
+    #[cfg(feature = "postgres")]
+    pub async fn record(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let positions: Vec<i32> = (0..).take(self.statuses.len()).collect();
+        sqlx::query(
+            "INSERT INTO statuses (name, position)
+            SELECT * FROM unnest($1::text[], $2::integer[])
+            ON CONFLICT (name) DO UPDATE SET position = excluded.position",
+        )
+        .bind(&self.statuses)
+        .bind(positions)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(r#"{"statuses": [" "]}"#)]
+    #[case(r#"{"statuses": ["complete"]}"#)]
+    #[case(r#"{"statuses": ["InReview", "InReview"]}"#)]
+    #[case(r#"{"transitions": {"NotStarted": ["InReview"]}}"#)]
+    fn invalid(#[case] json: &str) {
+        let workflow: Workflow = serde_json::from_str(json).unwrap();
+        assert!(workflow.validate().is_err());
+    }
+
+    #[rstest]
+    fn restricted_transitions() {
+        let workflow: Workflow = serde_json::from_str(
+            r#"{"statuses": ["InReview"], "transitions": {"InReview": ["Complete"]}}"#,
+        )
+        .unwrap();
+        workflow.validate().unwrap();
+        let in_review = TodoStatus::named("InReview");
+
+        assert!(workflow.knows(&in_review));
+        assert!(!workflow.knows(&TodoStatus::named("Triage")));
+        assert!(workflow.allows(&in_review, &TodoStatus::Complete));
+        assert!(workflow.allows(&TodoStatus::Blocked, &TodoStatus::Blocked));
+        // statuses without transitions can't be changed from
+        assert!(!workflow.allows(&TodoStatus::Complete, &in_review));
+        assert!(Workflow::default().allows(&TodoStatus::Complete, &TodoStatus::NotStarted));
+    }
+}